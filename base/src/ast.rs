@@ -408,7 +408,7 @@ pub type SpannedAlias<'ast, Id> = Spanned<AliasData<Id, AstType<'ast, Id>>, Byte
 
 pub type SpannedAstType<'ast, Id> = Spanned<Type<Id, AstType<'ast, Id>>, BytePos>;
 
-#[derive(Eq, PartialEq, Debug, AstClone)]
+#[derive(Default, Eq, PartialEq, Debug, AstClone)]
 pub struct ExprField<'ast, Id, E> {
     pub metadata: BaseMetadata<'ast>,
     pub name: Spanned<Id, BytePos>,
@@ -611,6 +611,8 @@ pub struct TypeBinding<'ast, Id> {
     pub metadata: BaseMetadata<'ast>,
     pub name: Spanned<Id, BytePos>,
     pub alias: SpannedAlias<'ast, Id>,
+    /// Default values of the fields of a record type (`type Config = { port : Int = 8080 }`)
+    pub defaults: &'ast mut [ExprField<'ast, Id, SpannedExpr<'ast, Id>>],
     pub finalized_alias: Option<Alias<Id, ArcType<Id>>>,
 }
 
@@ -643,6 +645,32 @@ impl<Id> Argument<Id> {
     }
 }
 
+/// Prefix of the names given to arguments which are written as a pattern (`\{ x } -> x`). The
+/// parser binds the pattern with a `let` at the start of the body of the function.
+pub const PATTERN_ARGUMENT_PREFIX: &str = "pattern_arg?";
+
+/// If `arg` was written as a pattern, returns the pattern together with the remaining body of the
+/// function
+pub fn argument_pattern<'a, 'ast, Id>(
+    arg: &Argument<SpannedIdent<Id>>,
+    body: &'a SpannedExpr<'ast, Id>,
+) -> Option<(&'a SpannedPattern<'ast, Id>, &'a SpannedExpr<'ast, Id>)>
+where
+    Id: AsRef<str>,
+{
+    let name = arg.name.value.name.as_ref();
+    if !name.starts_with(PATTERN_ARGUMENT_PREFIX) {
+        return None;
+    }
+    match &body.value {
+        Expr::LetBindings(ValueBindings::Plain(bind), rest) => match &bind.expr.value {
+            Expr::Ident(id) if id.name.as_ref() == name => Some((&bind.name, &**rest)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 #[derive(Eq, PartialEq, Debug, AstClone)]
 pub enum ValueBindings<'ast, Id> {
     Plain(&'ast mut ValueBinding<'ast, Id>),
@@ -902,6 +930,11 @@ pub fn walk_expr<'a, 'ast, V>(v: &mut V, e: &'a $($mut)* SpannedExpr<'ast, V::Id
             for binding in &$($mut)* **bindings {
                 v.visit_spanned_ident(&$($mut)* binding.name);
                 v.visit_alias(&$($mut)* binding.alias);
+                for default in &$($mut)* *binding.defaults {
+                    if let Some(ref $($mut)* expr) = default.value {
+                        v.visit_expr(expr);
+                    }
+                }
             }
             v.visit_expr(expr)
         }
@@ -1035,6 +1068,85 @@ mod ref_ {
 }
 pub use self::ref_::{walk_alias, walk_ast_type, walk_expr, walk_pattern, Visitor};
 
+/// Visitor trait which can replace the expressions and patterns it visits. Returning `Some` from
+/// `replace_*` replaces the visited node with the returned value. By default the `replace_*`
+/// functions walk the children of the node and return `None`. If they are overridden the user
+/// will need to call `walk_replace_*` to continue traversing the tree (before or after
+/// constructing the replacement).
+pub trait ReplaceVisitor<'ast> {
+    type Ident: 'ast;
+
+    fn replace_expr(
+        &mut self,
+        e: &mut SpannedExpr<'ast, Self::Ident>,
+    ) -> Option<Expr<'ast, Self::Ident>> {
+        walk_replace_expr(self, e);
+        None
+    }
+
+    fn replace_pattern(
+        &mut self,
+        p: &mut SpannedPattern<'ast, Self::Ident>,
+    ) -> Option<Pattern<'ast, Self::Ident>> {
+        walk_replace_pattern(self, p);
+        None
+    }
+}
+
+struct ReplaceAdapter<'v, V: ?Sized>(&'v mut V);
+
+impl<'a, 'ast, V> MutVisitor<'a, 'ast> for ReplaceAdapter<'_, V>
+where
+    V: ?Sized + ReplaceVisitor<'ast>,
+    V::Ident: 'a,
+{
+    type Ident = V::Ident;
+
+    fn visit_expr(&mut self, e: &'a mut SpannedExpr<'ast, Self::Ident>) {
+        visit_replace_expr(self.0, e)
+    }
+
+    fn visit_pattern(&mut self, p: &'a mut SpannedPattern<'ast, Self::Ident>) {
+        visit_replace_pattern(self.0, p)
+    }
+}
+
+/// Calls `replace_expr` on `e` and replaces it if a replacement is returned
+pub fn visit_replace_expr<'ast, V>(v: &mut V, e: &mut SpannedExpr<'ast, V::Ident>)
+where
+    V: ?Sized + ReplaceVisitor<'ast>,
+{
+    if let Some(replacement) = v.replace_expr(e) {
+        e.value = replacement;
+    }
+}
+
+/// Calls `replace_pattern` on `p` and replaces it if a replacement is returned
+pub fn visit_replace_pattern<'ast, V>(v: &mut V, p: &mut SpannedPattern<'ast, V::Ident>)
+where
+    V: ?Sized + ReplaceVisitor<'ast>,
+{
+    if let Some(replacement) = v.replace_pattern(p) {
+        p.value = replacement;
+    }
+}
+
+/// Walks the children of `e`, calling `replace_*` on each encountered expression and pattern
+pub fn walk_replace_expr<'ast, V>(v: &mut V, e: &mut SpannedExpr<'ast, V::Ident>)
+where
+    V: ?Sized + ReplaceVisitor<'ast>,
+{
+    walk_mut_expr(&mut ReplaceAdapter(v), e)
+}
+
+/// Walks the children of `p`, calling `replace_pattern` on each encountered pattern
+pub fn walk_replace_pattern<'ast, V>(v: &mut V, p: &mut SpannedPattern<'ast, V::Ident>)
+where
+    V: ?Sized + ReplaceVisitor<'ast>,
+{
+    walk_mut_pattern(&mut ReplaceAdapter(v), &mut p.value)
+}
+
 /// Trait which abstracts over things that have a type.
 /// It is not guaranteed that the correct type is returned until after typechecking
 pub trait Typed {
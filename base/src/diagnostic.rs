@@ -0,0 +1,292 @@
+//! Structured, machine readable diagnostics.
+//!
+//! `Diagnostic` contains the same information as the diagnostics which are emitted as text but
+//! with every span resolved to a file, byte range and line and column so that tools (editors,
+//! build systems) do not need to parse the emitted text. `to_json` serializes diagnostics as JSON.
+use std::{fmt::Write, ops::Range};
+
+use codespan_reporting::{
+    diagnostic::{self as codespan, LabelStyle},
+    files::Files,
+};
+
+use crate::{
+    error::AsDiagnostic,
+    pos::{BytePos, Span},
+    source::{CodeMap, FileId},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Severity {
+    Bug,
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Bug => "bug",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        }
+    }
+}
+
+impl From<codespan::Severity> for Severity {
+    fn from(severity: codespan::Severity) -> Self {
+        match severity {
+            codespan::Severity::Bug => Severity::Bug,
+            codespan::Severity::Error => Severity::Error,
+            codespan::Severity::Warning => Severity::Warning,
+            codespan::Severity::Note => Severity::Note,
+            codespan::Severity::Help => Severity::Help,
+        }
+    }
+}
+
+/// A line and column, both starting at 1
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A range of bytes in a named file
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct FileRange {
+    pub file: String,
+    /// The byte offsets of the range from the start of the file
+    pub range: Range<usize>,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl FileRange {
+    fn new(map: &CodeMap, file_id: FileId, range: Range<usize>) -> Option<Self> {
+        let position = |byte_index| {
+            map.location(file_id, byte_index)
+                .ok()
+                .map(|location| Position {
+                    line: location.line_number,
+                    column: location.column_number,
+                })
+        };
+        Some(FileRange {
+            file: map.name(file_id).ok()?,
+            start: position(range.start)?,
+            end: position(range.end)?,
+            range,
+        })
+    }
+
+    fn from_span(map: &CodeMap, span: Span<BytePos>) -> Option<Self> {
+        Self::new(map, span.start(), span.to_range(map)?)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Label {
+    pub range: FileRange,
+    pub message: String,
+}
+
+/// An edit which would fix the diagnostic by replacing the source at `range` with `replacement`
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Fix {
+    pub message: String,
+    pub range: FileRange,
+    pub replacement: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    /// Where the diagnostic was reported. Only missing for errors without a location.
+    pub primary: Option<Label>,
+    /// Other locations which are related to the diagnostic
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub fixes: Vec<Fix>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic without any location
+    pub fn message(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Creates the diagnostic of `error`, resolving its spans using `map`
+    pub fn new<E>(map: &CodeMap, error: &E) -> Self
+    where
+        E: ?Sized + AsDiagnostic,
+    {
+        let diagnostic = error.as_diagnostic(map);
+        let mut result = Diagnostic {
+            code: diagnostic.code,
+            notes: diagnostic.notes,
+            ..Diagnostic::message(diagnostic.severity.into(), diagnostic.message)
+        };
+        for label in diagnostic.labels {
+            let style = label.style;
+            let range = match FileRange::new(map, label.file_id, label.range) {
+                Some(range) => range,
+                None => {
+                    // Keep the message of labels which do not point into a known file
+                    if !label.message.is_empty() {
+                        result.notes.push(label.message);
+                    }
+                    continue;
+                }
+            };
+            let label = Label {
+                range,
+                message: label.message,
+            };
+            match style {
+                LabelStyle::Primary if result.primary.is_none() => result.primary = Some(label),
+                _ => result.secondary.push(label),
+            }
+        }
+        result.fixes = error
+            .suggested_fixes()
+            .into_iter()
+            .filter_map(|fix| {
+                Some(Fix {
+                    range: FileRange::from_span(map, fix.span)?,
+                    message: fix.message,
+                    replacement: fix.replacement,
+                })
+            })
+            .collect();
+        result
+    }
+
+    /// Serializes the diagnostic as a JSON object
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        out.push_str("{\"severity\":");
+        write_json_str(out, self.severity.as_str());
+        out.push_str(",\"code\":");
+        match &self.code {
+            Some(code) => write_json_str(out, code),
+            None => out.push_str("null"),
+        }
+        out.push_str(",\"message\":");
+        write_json_str(out, &self.message);
+        out.push_str(",\"primary\":");
+        match &self.primary {
+            Some(label) => label.write_json(out),
+            None => out.push_str("null"),
+        }
+        out.push_str(",\"secondary\":");
+        write_json_array(out, &self.secondary, Label::write_json);
+        out.push_str(",\"notes\":");
+        write_json_array(out, &self.notes, |note, out| write_json_str(out, note));
+        out.push_str(",\"fixes\":");
+        write_json_array(out, &self.fixes, Fix::write_json);
+        out.push('}');
+    }
+}
+
+/// Serializes `diagnostics` as a JSON array
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let mut out = String::new();
+    write_json_array(&mut out, diagnostics, Diagnostic::write_json);
+    out
+}
+
+impl Position {
+    fn write_json(&self, out: &mut String) {
+        write!(out, "{{\"line\":{},\"column\":{}}}", self.line, self.column).unwrap();
+    }
+}
+
+impl FileRange {
+    /// Writes the fields of the range, without the enclosing braces
+    fn write_json_fields(&self, out: &mut String) {
+        out.push_str("\"file\":");
+        write_json_str(out, &self.file);
+        write!(
+            out,
+            ",\"range\":[{},{}],\"start\":",
+            self.range.start, self.range.end
+        )
+        .unwrap();
+        self.start.write_json(out);
+        out.push_str(",\"end\":");
+        self.end.write_json(out);
+    }
+}
+
+impl Label {
+    fn write_json(&self, out: &mut String) {
+        out.push('{');
+        self.range.write_json_fields(out);
+        out.push_str(",\"message\":");
+        write_json_str(out, &self.message);
+        out.push('}');
+    }
+}
+
+impl Fix {
+    fn write_json(&self, out: &mut String) {
+        out.push('{');
+        self.range.write_json_fields(out);
+        out.push_str(",\"message\":");
+        write_json_str(out, &self.message);
+        out.push_str(",\"replacement\":");
+        write_json_str(out, &self.replacement);
+        out.push('}');
+    }
+}
+
+fn write_json_array<T>(
+    out: &mut String,
+    values: &[T],
+    mut write_value: impl FnMut(&T, &mut String),
+) {
+    out.push('[');
+    for (i, value) in values.iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        write_value(value, out);
+    }
+    out.push(']');
+}
+
+fn write_json_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -13,7 +13,8 @@ use std::vec;
 use codespan_reporting::diagnostic::{Diagnostic, Label};
 
 use crate::{
-    pos::{BytePos, Spanned},
+    diagnostic,
+    pos::{BytePos, Span, Spanned},
     source::FileId,
 };
 
@@ -229,6 +230,17 @@ impl<E: fmt::Display> InFile<E> {
         self.error
     }
 
+    /// Returns a structured representation of each error, suitable for consumption by tools
+    pub fn diagnostics(&self) -> Vec<diagnostic::Diagnostic>
+    where
+        E: AsDiagnostic,
+    {
+        self.error
+            .iter()
+            .map(|error| diagnostic::Diagnostic::new(&self.source, error))
+            .collect()
+    }
+
     pub fn emit_string(&self) -> crate::source::Result<String>
     where
         E: AsDiagnostic,
@@ -313,8 +325,22 @@ impl<E, H> From<E> for Help<E, H> {
     }
 }
 
+/// An edit which would fix an error by replacing the source at `span` with `replacement`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SuggestedFix {
+    /// Describes the fix, for instance "Did you mean `map`?"
+    pub message: String,
+    pub span: Span<BytePos>,
+    pub replacement: String,
+}
+
 pub trait AsDiagnostic {
     fn as_diagnostic(&self, map: &crate::source::CodeMap) -> Diagnostic<FileId>;
+
+    /// Returns edits which would fix the error, if any are known
+    fn suggested_fixes(&self) -> Vec<SuggestedFix> {
+        Vec::new()
+    }
 }
 
 impl<E> AsDiagnostic for Spanned<E, BytePos>
@@ -340,8 +366,30 @@ where
                     .push(Label::primary(self.span.start(), range));
             }
         }
+        point_to_call_sites(map, &mut diagnostic);
         diagnostic
     }
+
+    fn suggested_fixes(&self) -> Vec<SuggestedFix> {
+        self.value.suggested_fixes()
+    }
+}
+
+/// Moves labels which point into generated code, such as the implicit prelude, to the place in
+/// the user's code that the generated code was inserted at
+fn point_to_call_sites(map: &crate::source::CodeMap, diagnostic: &mut Diagnostic<FileId>) {
+    for label in &mut diagnostic.labels {
+        let generated = match map.generated(label.file_id) {
+            Some(generated) => generated,
+            None => continue,
+        };
+        let note = format!("in code generated by {}", generated.generator);
+        if !diagnostic.notes.contains(&note) {
+            diagnostic.notes.push(note);
+        }
+        label.file_id = generated.call_site.start();
+        label.range = generated.call_site.to_range(map).unwrap_or(0..0);
+    }
 }
 
 impl<E, H> AsDiagnostic for Help<E, H>
@@ -366,6 +414,10 @@ where
         }
         diagnostic
     }
+
+    fn suggested_fixes(&self) -> Vec<SuggestedFix> {
+        self.error.suggested_fixes()
+    }
 }
 
 impl AsDiagnostic for Box<dyn ::std::error::Error + Send + Sync> {
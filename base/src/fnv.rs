@@ -1,20 +1,82 @@
 extern crate fnv;
 
 use std::collections::{HashMap, HashSet};
-use std::hash::BuildHasherDefault;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
 
 pub use self::fnv::FnvHasher;
 
+/// `BuildHasher` for `FnvHasher`
+pub type FnvBuildHasher = BuildHasherDefault<FnvHasher>;
+
 /// Non-crypto `HashMap` using Fnv Hasher
 ///
 /// The default hashing implementation in `std::collections` uses `SipHasher`
 /// since gluon doesn't need the cryptographic guarantee provided by `SipHasher`,
 /// we've opted for the faster fnv hash.
-pub type FnvMap<K, V> = HashMap<K, V, BuildHasherDefault<FnvHasher>>;
+pub type FnvMap<K, V> = HashMap<K, V, FnvBuildHasher>;
 
 /// Non-crypto `HashSet` using Fnv Hasher
 ///
 /// The default hashing implementation in `std::collections` uses `SipHasher`
 /// since gluon doesn't need the cryptographic guarantee provided by `SipHasher`,
 /// we've opted for the faster fnv hash.
-pub type FnvSet<K> = HashSet<K, BuildHasherDefault<FnvHasher>>;
+pub type FnvSet<K> = HashSet<K, FnvBuildHasher>;
+
+/// Non-crypto `HashMap` which exposes the raw entry API, making it possible to compute the hash
+/// of a key once and reuse it for multiple lookups. The hasher can be replaced through `S`.
+pub type RawFnvMap<K, V, S = FnvBuildHasher> = hashbrown::HashMap<K, V, S>;
+
+/// Hasher for keys which hash themselves as a single integer, such as interned symbols (which
+/// hash their address). Instead of feeding the integer through fnv byte by byte, the integer is
+/// mixed with a single multiplication.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        // Fallback for keys which are not a single integer
+        let mut hasher = FnvHasher::with_key(self.0);
+        hasher.write(bytes);
+        self.0 = hasher.finish();
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i.into());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// `BuildHasher` for `SymbolHasher`
+pub type SymbolBuildHasher = BuildHasherDefault<SymbolHasher>;
+
+/// `HashMap` optimized for interned keys such as `Symbol`
+pub type SymbolMap<K, V> = RawFnvMap<K, V, SymbolBuildHasher>;
+
+/// Computes the hash of `key` using the hasher produced by `build_hasher`. The hash can be passed
+/// to the `from_hash` methods of the raw entry API.
+#[inline]
+pub fn hash_key<S, Q>(build_hasher: &S, key: &Q) -> u64
+where
+    S: BuildHasher,
+    Q: ?Sized + Hash,
+{
+    let mut hasher = build_hasher.build_hasher();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
@@ -87,6 +87,7 @@ macro_rules! chain {
 #[macro_use]
 pub mod macros;
 pub mod ast;
+pub mod diagnostic;
 pub mod error;
 pub mod fixed;
 pub mod fnv;
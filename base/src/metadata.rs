@@ -155,6 +155,11 @@ impl Metadata {
     pub fn attributes(&self) -> impl Iterator<Item = &Attribute> {
         self.attributes.iter()
     }
+
+    /// Returns true if the value is marked with `#[private]`, which hides it from other modules
+    pub fn is_private(&self) -> bool {
+        self.get_attribute("private").is_some()
+    }
 }
 
 impl<'ast> BaseMetadata<'ast> {
@@ -49,7 +49,7 @@ pub struct Positioned<T, Pos> {
 }
 
 /// A region of code in a source file
-#[derive(Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd, Hash)]
 #[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
 #[cfg_attr(feature = "memory_usage", derive(HeapSizeOf))]
 pub struct Span<I> {
@@ -1,34 +1,39 @@
 //! A map data type which allows the same key to exist at multiple scope levels
 use std::borrow::Borrow;
-use std::collections::hash_map::{self, IterMut};
 use std::fmt;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash};
 
-use crate::fnv::FnvMap;
+use hashbrown::hash_map::{self, IterMut};
+use rpds::{HashTrieMap, HashTrieMapSync, ListSync};
+
+use crate::fnv::{FnvBuildHasher, RawFnvMap};
 
 /// A map struct which allows for the introduction of different scopes
 /// Introducing a new scope will make it possible to introduce additional
 /// variables with names already defined, shadowing the old name
 /// After exiting a scope the shadowed variable will again be re introduced
-pub struct ScopedMap<K: Eq + Hash, V> {
+///
+/// The hasher can be replaced through `S`, for instance with `SymbolBuildHasher` when the keys are
+/// interned symbols.
+pub struct ScopedMap<K: Eq + Hash, V, S = FnvBuildHasher> {
     /// A hashmap storing a key -> value mapping
     /// Stores a vector of values in which the value at the top is value returned from 'get'
-    map: FnvMap<K, Vec<V>>,
+    map: RawFnvMap<K, Vec<V>, S>,
     /// A vector of scopes, when entering a scope, None is added as a marker
     /// when later exiting a scope, values are removed from the map until the marker is found
     scopes: Vec<Option<K>>,
 }
 
-impl<K: Eq + Hash, V> Default for ScopedMap<K, V> {
+impl<K: Eq + Hash, V, S: Default> Default for ScopedMap<K, V, S> {
     fn default() -> Self {
         ScopedMap {
-            map: FnvMap::default(),
+            map: RawFnvMap::default(),
             scopes: Vec::default(),
         }
     }
 }
 
-impl<K, V> fmt::Debug for ScopedMap<K, V>
+impl<K, V, S> fmt::Debug for ScopedMap<K, V, S>
 where
     K: Eq + Hash + fmt::Debug + Clone,
     V: fmt::Debug,
@@ -38,11 +43,20 @@ where
     }
 }
 
-#[allow(dead_code)]
 impl<K: Eq + Hash + Clone, V> ScopedMap<K, V> {
     pub fn new() -> ScopedMap<K, V> {
         ScopedMap::default()
     }
+}
+
+#[allow(dead_code)]
+impl<K: Eq + Hash + Clone, V, S: BuildHasher> ScopedMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> ScopedMap<K, V, S> {
+        ScopedMap {
+            map: RawFnvMap::with_hasher(hasher),
+            scopes: Vec::default(),
+        }
+    }
 
     pub fn num_scopes(&self) -> usize {
         self.scopes.iter().filter(|s| s.is_none()).count() + 1
@@ -67,7 +81,7 @@ impl<K: Eq + Hash + Clone, V> ScopedMap<K, V> {
 
     /// Exits the current scope, returning an iterator over the (key, value) pairs that are removed
     /// When `ExitScopeIter` is dropped any remaining pairs of the scope is removed as well.
-    pub fn exit_scope(&mut self) -> ExitScopeIter<K, V> {
+    pub fn exit_scope(&mut self) -> ExitScopeIter<K, V, S> {
         ExitScopeIter {
             map: self,
             done: false,
@@ -133,7 +147,7 @@ impl<K: Eq + Hash + Clone, V> ScopedMap<K, V> {
         self.map.get(k).map(|x| &x[..])
     }
 
-    pub fn entry(&mut self, key: K) -> Entry<K, V> {
+    pub fn entry(&mut self, key: K) -> Entry<K, V, S> {
         match self.map.entry(key) {
             hash_map::Entry::Occupied(entry) => {
                 if entry.get().is_empty() {
@@ -231,7 +245,7 @@ impl<K: Eq + Hash + Clone, V> ScopedMap<K, V> {
     }
 }
 
-impl<K: Eq + Hash, V> ScopedMap<K, V> {
+impl<K: Eq + Hash, V, S> ScopedMap<K, V, S> {
     /// Returns an iterator of the (key, values) pairs inserted in the map
     pub fn iter_mut(&mut self) -> IterMut<K, Vec<V>> {
         self.map.iter_mut()
@@ -244,7 +258,7 @@ impl<K: Eq + Hash, V> ScopedMap<K, V> {
     }
 }
 
-impl<K: Eq + Hash + Clone, V> Extend<(K, V)> for ScopedMap<K, V> {
+impl<K: Eq + Hash + Clone, V, S: BuildHasher> Extend<(K, V)> for ScopedMap<K, V, S> {
     fn extend<T>(&mut self, iter: T)
     where
         T: IntoIterator<Item = (K, V)>,
@@ -255,12 +269,15 @@ impl<K: Eq + Hash + Clone, V> Extend<(K, V)> for ScopedMap<K, V> {
     }
 }
 
-pub enum Entry<'a, K, V> {
-    Vacant(VacantEntry<'a, K, V>),
-    Occupied(OccupiedEntry<'a, K, V>),
+pub enum Entry<'a, K, V, S = FnvBuildHasher> {
+    Vacant(VacantEntry<'a, K, V, S>),
+    Occupied(OccupiedEntry<'a, K, V, S>),
 }
 
-impl<'a, K, V> Entry<'a, K, V> {
+impl<'a, K, V, S: BuildHasher> Entry<'a, K, V, S>
+where
+    K: Hash,
+{
     pub fn or_insert(self, default: V) -> &'a mut V
     where
         K: Clone,
@@ -279,17 +296,23 @@ impl<'a, K, V> Entry<'a, K, V> {
     }
 }
 
-pub enum InnerVacantEntry<'a, K, V> {
-    Vacant(hash_map::VacantEntry<'a, K, Vec<V>>, &'a mut Vec<Option<K>>),
-    Occupied(OccupiedEntry<'a, K, V>),
+pub enum InnerVacantEntry<'a, K, V, S = FnvBuildHasher> {
+    Vacant(
+        hash_map::VacantEntry<'a, K, Vec<V>, S>,
+        &'a mut Vec<Option<K>>,
+    ),
+    Occupied(OccupiedEntry<'a, K, V, S>),
 }
-pub struct VacantEntry<'a, K, V>(InnerVacantEntry<'a, K, V>);
-pub struct OccupiedEntry<'a, K, V>(
-    hash_map::OccupiedEntry<'a, K, Vec<V>>,
+pub struct VacantEntry<'a, K, V, S = FnvBuildHasher>(InnerVacantEntry<'a, K, V, S>);
+pub struct OccupiedEntry<'a, K, V, S = FnvBuildHasher>(
+    hash_map::OccupiedEntry<'a, K, Vec<V>, S>,
     &'a mut Vec<Option<K>>,
 );
 
-impl<'a, K, V> VacantEntry<'a, K, V> {
+impl<'a, K, V, S: BuildHasher> VacantEntry<'a, K, V, S>
+where
+    K: Hash,
+{
     pub fn insert(self, value: V) -> &'a mut V
     where
         K: Clone,
@@ -307,7 +330,7 @@ impl<'a, K, V> VacantEntry<'a, K, V> {
     }
 }
 
-impl<'a, K, V> OccupiedEntry<'a, K, V> {
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S> {
     pub fn key(&self) -> &K {
         self.0.key()
     }
@@ -362,27 +385,30 @@ where
     }
 }
 
-pub struct ExitScopeIter<'a, K, V>
+pub struct ExitScopeIter<'a, K, V, S = FnvBuildHasher>
 where
     K: 'a + Eq + Hash + Clone,
     V: 'a,
+    S: BuildHasher,
 {
-    map: &'a mut ScopedMap<K, V>,
+    map: &'a mut ScopedMap<K, V, S>,
     done: bool,
 }
 
-impl<'a, K, V> Drop for ExitScopeIter<'a, K, V>
+impl<'a, K, V, S> Drop for ExitScopeIter<'a, K, V, S>
 where
     K: Eq + Hash + Clone,
+    S: BuildHasher,
 {
     fn drop(&mut self) {
         for _ in self {}
     }
 }
 
-impl<'a, K, V> Iterator for ExitScopeIter<'a, K, V>
+impl<'a, K, V, S> Iterator for ExitScopeIter<'a, K, V, S>
 where
     K: Eq + Hash + Clone,
+    S: BuildHasher,
 {
     type Item = (K, V);
 
@@ -405,6 +431,163 @@ where
     }
 }
 
+type PersistentMap<K, V> = HashTrieMapSync<K, ListSync<V>, FnvBuildHasher>;
+
+/// A persistent version of `ScopedMap`. Cloning the map is `O(1)` which makes it possible to
+/// cheaply snapshot the current scopes (see `snapshot` and `restore`) without needing to exactly
+/// pair each `enter_scope` with an `exit_scope`.
+pub struct PersistentScopedMap<K: Eq + Hash, V> {
+    /// Maps each key to a list of values, the first of which is the value returned from `get`
+    map: PersistentMap<K, V>,
+    /// The maps which were active when each of the currently open scopes were entered
+    scopes: ListSync<PersistentMap<K, V>>,
+}
+
+impl<K: Eq + Hash, V> Clone for PersistentScopedMap<K, V> {
+    fn clone(&self) -> Self {
+        PersistentScopedMap {
+            map: self.map.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> Default for PersistentScopedMap<K, V> {
+    fn default() -> Self {
+        PersistentScopedMap {
+            map: HashTrieMap::new_with_hasher_and_ptr_kind(Default::default()),
+            scopes: ListSync::new_sync(),
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for PersistentScopedMap<K, V>
+where
+    K: Eq + Hash + fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Eq + Hash, V> PersistentScopedMap<K, V> {
+    pub fn new() -> PersistentScopedMap<K, V> {
+        PersistentScopedMap::default()
+    }
+
+    pub fn num_scopes(&self) -> usize {
+        self.scopes.len() + 1
+    }
+
+    /// Introduces a new scope
+    pub fn enter_scope(&mut self) {
+        self.scopes.push_front_mut(self.map.clone());
+    }
+
+    /// Exits the current scope, removing all values inserted since the scope was entered.
+    /// Exiting the outermost scope removes every value in the map.
+    pub fn exit_scope(&mut self) {
+        match self.scopes.first() {
+            Some(map) => {
+                self.map = map.clone();
+                self.scopes.drop_first_mut();
+            }
+            None => self.map = HashTrieMap::new_with_hasher_and_ptr_kind(Default::default()),
+        }
+    }
+
+    /// Returns a snapshot of the current state of the map which can later be passed to `restore`
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    /// Restores the map (including its scopes) to the state it had when `snapshot` was taken
+    pub fn restore(&mut self, snapshot: Self) {
+        *self = snapshot;
+    }
+
+    /// Inserts a value, shadowing any previous value with the same key
+    pub fn insert(&mut self, k: K, v: V) {
+        let values = match self.map.get(&k) {
+            Some(values) => values.push_front(v),
+            None => ListSync::new_sync().push_front(v),
+        };
+        self.map.insert_mut(k, values);
+    }
+
+    /// Returns a reference to the last inserted value corresponding to the key
+    pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash,
+    {
+        self.map.get(k).and_then(|values| values.first())
+    }
+
+    /// Returns an iterator over all values inserted with the key, starting with the most recently
+    /// inserted value
+    pub fn get_all<'a, Q: ?Sized>(&'a self, k: &Q) -> impl Iterator<Item = &'a V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash,
+    {
+        self.map.get(k).into_iter().flat_map(|values| values.iter())
+    }
+
+    pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash,
+    {
+        self.map.contains_key(k)
+    }
+
+    /// Returns true if the key has a value declared in the current scope
+    pub fn in_current_scope<Q: ?Sized>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash,
+    {
+        match (self.map.get(k), self.scopes.first()) {
+            (Some(values), Some(outer)) => {
+                outer.get(k).map_or(0, |outer| outer.len()) < values.len()
+            }
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Returns the number of elements in the container.
+    /// Shadowed elements are counted
+    pub fn len(&self) -> usize {
+        self.map.values().map(|values| values.len()).sum()
+    }
+
+    /// Returns true if this map is empty
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator of the visible (key, value) pairs in the map
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map
+            .iter()
+            .filter_map(|(k, values)| Some((k, values.first()?)))
+    }
+}
+
+impl<K: Eq + Hash, V> Extend<(K, V)> for PersistentScopedMap<K, V> {
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = (K, V)>,
+    {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -442,4 +625,30 @@ mod tests {
         assert_eq!(map.get("b"), None);
         map.exit_scope();
     }
+
+    #[test]
+    fn persistent() {
+        let mut map = PersistentScopedMap::new();
+        map.insert("a", 0);
+        map.insert("b", 1);
+        map.enter_scope();
+        map.insert("a", 1);
+        map.insert("c", 2);
+        assert!(map.in_current_scope("a"));
+        assert!(!map.in_current_scope("b"));
+        assert_eq!(map.get_all("a").collect::<Vec<_>>(), [&1, &0]);
+
+        let snapshot = map.snapshot();
+        map.exit_scope();
+        assert_eq!(map.get("a"), Some(&0));
+        assert_eq!(map.get("c"), None);
+        assert_eq!(map.num_scopes(), 1);
+
+        map.restore(snapshot);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get("c"), Some(&2));
+        assert_eq!(map.len(), 4);
+        map.exit_scope();
+        assert_eq!(map.get("a"), Some(&0));
+    }
 }
@@ -15,6 +15,17 @@ pub type FileId = BytePos;
 pub struct FileMap {
     file: SimpleFile<String, String>,
     span_start: FileId,
+    generated: Option<GeneratedCode>,
+}
+
+/// Describes a file containing code which was generated for another file, such as the implicit
+/// prelude. Diagnostics pointing into generated code are shown at the call site instead.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeneratedCode {
+    /// What generated the code, shown as "in code generated by `generator`"
+    pub generator: String,
+    /// Where, in the code the user wrote, the generated code is inserted
+    pub call_site: Span<BytePos>,
 }
 
 impl fmt::Debug for FileMap {
@@ -31,6 +42,7 @@ impl FileMap {
         Self {
             file: SimpleFile::new(name, source),
             span_start: BytePos(1),
+            generated: None,
         }
     }
 
@@ -38,6 +50,7 @@ impl FileMap {
         FileMap {
             file: SimpleFile::new(name, source),
             span_start,
+            generated: None,
         }
     }
 
@@ -67,6 +80,11 @@ impl FileMap {
     pub fn name(&self) -> &str {
         self.file.name()
     }
+
+    /// Returns where the code in this file was generated from, if it was not written by the user
+    pub fn generated(&self) -> Option<&GeneratedCode> {
+        self.generated.as_ref()
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -80,17 +98,42 @@ impl CodeMap {
     }
 
     pub fn add_filemap(&mut self, filename: String, source: String) -> Arc<FileMap> {
-        let start_index = self
-            .files
+        self.push_filemap(FileMap::with_index(filename, source, self.next_start()))
+    }
+
+    /// Adds a file containing generated code. Diagnostics inside the file are reported at
+    /// `generated.call_site`.
+    pub fn add_generated_filemap(
+        &mut self,
+        filename: String,
+        source: String,
+        generated: GeneratedCode,
+    ) -> Arc<FileMap> {
+        self.push_filemap(FileMap {
+            generated: Some(generated),
+            ..FileMap::with_index(filename, source, self.next_start())
+        })
+    }
+
+    fn next_start(&self) -> FileId {
+        self.files
             .last()
             .map(|file| file.span().end())
             .unwrap_or_default()
-            + ByteOffset::from(1);
-        let file_map = Arc::new(FileMap::with_index(filename, source, start_index));
+            + ByteOffset::from(1)
+    }
+
+    fn push_filemap(&mut self, file_map: FileMap) -> Arc<FileMap> {
+        let file_map = Arc::new(file_map);
         self.files.push(file_map.clone());
         file_map
     }
 
+    /// Returns where the code at `pos` was generated from, if it is inside generated code
+    pub fn generated(&self, pos: BytePos) -> Option<&GeneratedCode> {
+        self.get(pos)?.generated()
+    }
+
     pub fn to_usize(&self, pos: BytePos) -> Option<usize> {
         self.get(pos)?.to_usize(pos)
     }
@@ -125,7 +168,10 @@ impl CodeMap {
             if src.len() <= (max - min).to_usize() {
                 let start_index = self.files[i].span().start();
                 let name = self.files[i].name().to_owned();
-                let new_file = Arc::new(FileMap::with_index(name, src, start_index));
+                let new_file = Arc::new(FileMap {
+                    generated: self.files[i].generated.clone(),
+                    ..FileMap::with_index(name, src, start_index)
+                });
                 self.files[i] = new_file.clone();
                 new_file
             } else {
@@ -149,15 +195,21 @@ impl CodeMap {
                         } else {
                             self.files[j - 1].span().end() + ByteOffset(1)
                         };
-                        let new_file = Arc::new(FileMap::with_index(
-                            file.name().to_owned(),
-                            src,
-                            start_index,
-                        ));
+                        let new_file = Arc::new(FileMap {
+                            generated: file.generated.clone(),
+                            ..FileMap::with_index(file.name().to_owned(), src, start_index)
+                        });
                         self.files.insert(j, new_file.clone());
                         new_file
                     }
-                    None => self.add_filemap(file.name().to_owned(), src),
+                    None => match &file.generated {
+                        Some(generated) => self.add_generated_filemap(
+                            file.name().to_owned(),
+                            src,
+                            generated.clone(),
+                        ),
+                        None => self.add_filemap(file.name().to_owned(), src),
+                    },
                 }
             }
         })
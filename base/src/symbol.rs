@@ -11,6 +11,7 @@ use std::{
 
 use crate::{
     ast::{DisplayEnv, IdentEnv},
+    fnv::SymbolMap,
     pos::{BytePos, Span},
 };
 
@@ -592,6 +593,29 @@ impl SymbolInner {
     }
 }
 
+/// A copyable handle to a `Symbol` which has been registered in a `Symbols` table.
+///
+/// Unlike `Symbol`, copying a `SymbolId` does not touch any reference count and comparing or
+/// hashing one is a single integer operation. The `Symbol` it refers to is retrieved with
+/// `Symbols::resolve`.
+///
+/// ```
+/// # use gluon_base::symbol::Symbols;
+/// let mut symbols = Symbols::new();
+/// let a = symbols.simple_symbol("a");
+/// let id = symbols.id(&a);
+/// assert_eq!(symbols.id(&a), id);
+/// assert_eq!(*symbols.resolve(id), a);
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct SymbolId(u32);
+
+impl SymbolId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
 /// `Symbols` is a bidirectional mapping between `Symbol`s and their name as represented in a
 /// source file.
 /// Used to make identifiers within a single module point to the same symbol
@@ -599,15 +623,53 @@ impl SymbolInner {
 pub struct Symbols {
     indexes:
         hashbrown::HashMap<SymbolData<&'static Name>, Symbol, BuildHasherDefault<fnv::FnvHasher>>,
+    /// The symbols which have been assigned a `SymbolId`, indexed by that id
+    symbol_ids: Vec<Symbol>,
+    ids: SymbolMap<Symbol, SymbolId>,
 }
 
 impl Symbols {
     pub fn new() -> Symbols {
         Symbols {
             indexes: Default::default(),
+            symbol_ids: Vec::new(),
+            ids: Default::default(),
         }
     }
 
+    /// Returns the `SymbolId` of `symbol`, assigning a new id if the symbol has not been seen
+    /// before
+    pub fn id(&mut self, symbol: &Symbol) -> SymbolId {
+        if let Some(id) = self.ids.get(symbol) {
+            return *id;
+        }
+        let id = SymbolId(u32::try_from(self.symbol_ids.len()).expect("Too many symbols"));
+        self.symbol_ids.push(symbol.clone());
+        self.ids.insert(symbol.clone(), id);
+        id
+    }
+
+    /// Returns the `SymbolId` of `symbol` if it has been assigned one
+    pub fn get_id(&self, symbol: &SymbolRef) -> Option<SymbolId> {
+        self.ids.get(symbol).cloned()
+    }
+
+    /// Looks up the symbol for `name` (creating it if it does not exist) and returns its id
+    pub fn symbol_id<N>(&mut self, name: SymbolData<N>) -> SymbolId
+    where
+        N: Into<NameBuf> + AsRef<Name>,
+    {
+        let symbol = self.symbol(name);
+        self.id(&symbol)
+    }
+
+    /// Returns the `Symbol` that `id` refers to
+    ///
+    /// Panics if `id` was not created by this `Symbols` table
+    pub fn resolve(&self, id: SymbolId) -> &Symbol {
+        &self.symbol_ids[id.index()]
+    }
+
     pub fn simple_symbol<N>(&mut self, name: N) -> Symbol
     where
         N: Into<NameBuf> + AsRef<Name>,
@@ -773,6 +835,16 @@ impl<'a> SymbolModule<'a> {
     pub fn symbols(&mut self) -> &mut Symbols {
         self.symbols
     }
+
+    /// Returns the `SymbolId` of `symbol`, same as `Symbols::id`
+    pub fn id(&mut self, symbol: &Symbol) -> SymbolId {
+        self.symbols.id(symbol)
+    }
+
+    /// Returns the `Symbol` that `id` refers to, same as `Symbols::resolve`
+    pub fn resolve(&self, id: SymbolId) -> &Symbol {
+        self.symbols.resolve(id)
+    }
 }
 
 impl DisplayEnv for Symbols {
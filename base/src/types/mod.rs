@@ -23,7 +23,7 @@ use gluon_codegen::AstClone;
 
 use crate::{
     ast::{AstClone, EmptyEnv, HasMetadata, IdentEnv},
-    fnv::FnvMap,
+    fnv::{FnvMap, RawFnvMap},
     kind::{ArcKind, Kind, KindCache, KindEnv},
     merge::{merge, merge_collect},
     metadata::Metadata,
@@ -2573,7 +2573,19 @@ where
     }
 }
 
-fn is_tuple<T>(typ: &T) -> bool
+/// Returns the position of the tuple element stored in the record field `name` (`_1` => `1`)
+pub fn tuple_index(name: &str) -> Option<usize> {
+    let index = name.strip_prefix('_')?;
+    if index.starts_with(|c: char| !c.is_ascii_digit())
+        || (index.len() > 1 && index.starts_with('0'))
+    {
+        return None;
+    }
+    index.parse().ok()
+}
+
+/// Returns true if `typ` is a record with the fields of a tuple (`_0`, `_1`, ...)
+pub fn is_tuple<T>(typ: &T) -> bool
 where
     T: TypePtr,
     T::SpannedId: AsRef<str>,
@@ -2581,10 +2593,9 @@ where
     match **typ {
         Type::Record(_) => {
             type_field_iter(typ).next().is_none()
-                && row_iter(typ).enumerate().all(|(i, field)| {
-                    let name = field.name.as_ref();
-                    name.starts_with('_') && name[1..].parse() == Ok(i)
-                })
+                && row_iter(typ)
+                    .enumerate()
+                    .all(|(i, field)| tuple_index(field.name.as_ref()) == Some(i))
         }
         _ => false,
     }
@@ -2657,7 +2668,7 @@ where
             Type::Function(..) => self.pretty_function(printer).nest(INDENT),
             Type::App(ref t, ref args) => match self.typ.as_function() {
                 Some(_) => self.pretty_function(printer).nest(INDENT),
-                None => {
+                None => Self::pretty_hooked(t, args, printer).unwrap_or_else(|| {
                     let doc = dt(Prec::Top, t).pretty_(printer);
                     let arg_doc = arena.concat(args.iter().map(|arg| {
                         printer
@@ -2666,7 +2677,7 @@ where
                     }));
                     let doc = doc.append(arg_doc.nest(INDENT));
                     p.enclose(Prec::Constructor, arena, doc).group()
-                }
+                }),
             },
             Type::Variant(ref row) => {
                 let mut first = true;
@@ -2780,17 +2791,49 @@ where
             // This should not be displayed normally as it should only exist in `ExtendRow`
             // which handles `EmptyRow` explicitly
             Type::EmptyRow => arena.text("EmptyRow"),
-            Type::Ident(ref id) => {
+            Type::Ident(ref id) => Self::pretty_hooked(typ, &[], printer).unwrap_or_else(|| {
                 printer.symbol_with(&id.name, Name::new(id.as_ref()).name().as_str())
-            }
+            }),
             Type::Projection(ref ids) => arena.concat(Itertools::intersperse(
                 ids.iter().map(|id| printer.symbol(id)),
                 arena.text("."),
             )),
-            Type::Alias(ref alias) => printer.symbol(&alias.name),
+            Type::Alias(ref alias) => Self::pretty_hooked(typ, &[], printer)
+                .unwrap_or_else(|| printer.symbol(&alias.name)),
         }
     }
 
+    /// Displays `typ` applied to `args` with the display hook registered for `typ`, if there is one
+    fn pretty_hooked<A>(
+        typ: &'a T,
+        args: &'a [T],
+        printer: &Printer<'a, I, A>,
+    ) -> Option<DocBuilder<'a, Arena<'a, A>, A>>
+    where
+        A: Clone,
+    {
+        let name = match **typ {
+            Type::Ident(ref id) => &id.name,
+            Type::Alias(ref alias) => &alias.name,
+            _ => return None,
+        };
+        let hook = pretty_print::display_hook(name.as_ref())?;
+        let args = args
+            .iter()
+            .map(|arg| {
+                let mut out = Vec::new();
+                top(arg)
+                    .pretty(printer)
+                    .group()
+                    .1
+                    .render(usize::MAX, &mut out)
+                    .expect("Vec<u8> is not a fallible writer");
+                String::from_utf8(out).expect("utf-8")
+            })
+            .collect::<Vec<_>>();
+        Some(printer.symbol_with(name, hook(&args)))
+    }
+
     fn pretty_record_like<A>(
         row: &'a T,
         printer: &Printer<'a, I, A>,
@@ -2928,6 +2971,14 @@ where
                 arena,
                 pretty_field(field),
                 rhs.group(),
+                match field
+                    .typ
+                    .metadata()
+                    .and_then(|metadata| metadata.get_attribute("default"))
+                {
+                    Some(default) => chain![arena, " = ", default],
+                    None => arena.nil(),
+                },
                 if i + 1 != fields.len() {
                     arena.text(",")
                 } else {
@@ -4070,7 +4121,7 @@ where
     T: TypeExt<Id = Id>,
     T::Types: Default + Extend<T> + FromIterator<T>,
 {
-    set: FnvMap<Interned<T>, ()>,
+    set: RawFnvMap<Interned<T>, ()>,
     scratch: Interned<T>,
     type_cache: TypeCache<Id, T>,
 }
@@ -4153,13 +4204,13 @@ where
     }
 
     fn intern_flags(&mut self, typ: Type<Id, T>, flags: Flags) -> T {
-        use std::collections::hash_map::Entry;
+        use hashbrown::hash_map::RawEntryMut;
 
         T::alloc(&mut self.scratch.0, typ, flags);
-        match self.set.entry(self.scratch.clone()) {
-            Entry::Occupied(entry) => return entry.key().0.clone(),
-            Entry::Vacant(entry) => {
-                entry.insert(());
+        match self.set.raw_entry_mut().from_key(&self.scratch) {
+            RawEntryMut::Occupied(entry) => entry.key().0.clone(),
+            RawEntryMut::Vacant(entry) => {
+                entry.insert(self.scratch.clone(), ());
                 self.scratch.0.clone()
             }
         }
@@ -1,4 +1,10 @@
-use std::{borrow::Cow, fmt, marker::PhantomData};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+};
 
 use pretty::{Arena, Doc, DocAllocator, DocBuilder};
 
@@ -56,6 +62,51 @@ pub fn doc_comment<'a, A>(
     }
 }
 
+/// Displays a type given the already displayed arguments that it is applied to
+pub type DisplayHook = Arc<dyn Fn(&[String]) -> String + Send + Sync>;
+
+static DISPLAY_HOOKS: RwLock<BTreeMap<String, DisplayHook>> = RwLock::new(BTreeMap::new());
+
+/// Registers `hook` to display the type named `name` (the name it was registered with in the vm)
+/// wherever types are printed, such as in type errors, the REPL and generated documentation. Any
+/// previous hook for `name` is replaced.
+///
+/// The returned text is treated as plain text and is escaped as necessary, so it is safe to
+/// include in the HTML of the generated documentation.
+///
+/// ```
+/// use gluon_base::types::{pretty_print, ArcType, KindedIdent, Type};
+///
+/// pretty_print::register_display_hook("Handle", |args| format!("Handle<{}>", args.join(", ")));
+///
+/// let handle = Type::ident(KindedIdent::new("Handle"));
+/// let typ: ArcType<&str> = Type::app(handle, Some(Type::int()).into_iter().collect());
+/// assert_eq!(typ.to_string(), "Handle<Int>");
+/// ```
+pub fn register_display_hook(
+    name: impl Into<String>,
+    hook: impl Fn(&[String]) -> String + Send + Sync + 'static,
+) {
+    DISPLAY_HOOKS
+        .write()
+        .unwrap()
+        .insert(name.into(), Arc::new(hook));
+}
+
+/// Removes the hook registered for `name`, returning `true` if there was one
+pub fn remove_display_hook(name: &str) -> bool {
+    DISPLAY_HOOKS.write().unwrap().remove(name).is_some()
+}
+
+pub(crate) fn display_hook(name: &str) -> Option<DisplayHook> {
+    let hooks = DISPLAY_HOOKS.read().unwrap();
+    if hooks.is_empty() {
+        None
+    } else {
+        hooks.get(name).cloned()
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Filter {
     Drop,
@@ -203,7 +254,11 @@ impl<'a, I, A> Printer<'a, I, A> {
         self.symbol_with(symbol, (self.symbol_text)(symbol))
     }
 
-    pub fn symbol_with(&self, symbol: &'a I, text: &'a str) -> DocBuilder<'a, Arena<'a, A>, A> {
+    pub fn symbol_with(
+        &self,
+        symbol: &'a I,
+        text: impl Into<Cow<'a, str>>,
+    ) -> DocBuilder<'a, Arena<'a, A>, A> {
         let doc = self.arena.text(text);
         match (self.annotate_symbol)(symbol) {
             Some(ann) => doc.annotate(ann),
@@ -480,3 +480,23 @@ fn forall_hides_generic_flag() {
     assert_eq!(gen.flags(), Flags::HAS_GENERICS);
     assert_eq!(Type::forall(vec![a], gen).flags(), Flags::HAS_FORALL);
 }
+
+#[test]
+fn display_hook() {
+    pretty_print::register_display_hook("EntityId", |_| "EntityId 42".to_string());
+    pretty_print::register_display_hook("Pair", |args| format!("<{}>", args.join(" & ")));
+
+    let entity: ArcType<&str> = Type::ident(KindedIdent::new("EntityId"));
+    let pair: ArcType<&str> = Type::app(
+        Type::ident(KindedIdent::new("Pair")),
+        collect![entity.clone(), Type::array(Type::int())],
+    );
+    assert_eq_display!(
+        Type::function(vec![pair], entity.clone()),
+        "<EntityId 42 & Array Int> -> EntityId 42"
+    );
+
+    assert!(pretty_print::remove_display_hook("EntityId"));
+    assert!(!pretty_print::remove_display_hook("EntityId"));
+    assert_eq_display!(entity, "EntityId");
+}
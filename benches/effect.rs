@@ -0,0 +1,105 @@
+use criterion::{black_box, criterion_group, criterion_main, Bencher, Criterion};
+
+use gluon::{new_vm, vm::api::FunctionRef, ThreadExt};
+
+// Compares the overhead of `std.effect` against the same program written with a direct monad
+
+fn count_down(b: &mut Bencher, name: &str, text: &str) {
+    let vm = new_vm();
+    vm.load_script(name, text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let mut count_down: FunctionRef<fn(i32) -> i32> = vm.get_global(name).unwrap();
+    b.iter(|| {
+        let result = count_down.call(1000).unwrap();
+        black_box(result)
+    })
+}
+
+fn direct_recursion(b: &mut Bencher) {
+    count_down(
+        b,
+        "direct_recursion",
+        r#"
+        let loop n : Int -> Int =
+            if n #Int== 0 then 0
+            else loop (n #Int- 1)
+        loop
+        "#,
+    )
+}
+
+fn state_monad(b: &mut Bencher) {
+    count_down(
+        b,
+        "state_monad",
+        r#"
+        let { wrap } = import! std.applicative
+        let state @ { State, get, put, ? } = import! std.state
+
+        let loop _ : () -> State Int Int =
+            do n = get
+            if n #Int== 0 then wrap n
+            else
+                seq put (n #Int- 1)
+                loop ()
+        \n -> state.evalState (loop ()) n
+        "#,
+    )
+}
+
+fn state_effect(b: &mut Bencher) {
+    count_down(
+        b,
+        "state_effect",
+        r#"
+        let { wrap } = import! std.applicative
+        let { Eff, run_pure, ? } = import! std.effect
+        let { State, get, put, eval_state } = import! std.effect.state
+
+        let loop _ : () -> Eff [| state : State Int | r |] Int =
+            do n = get
+            if n #Int== 0 then wrap n
+            else
+                seq put (n #Int- 1)
+                loop ()
+        \n -> run_pure (eval_state n (loop ()))
+        "#,
+    )
+}
+
+fn state_effect_nested(b: &mut Bencher) {
+    count_down(
+        b,
+        "state_effect_nested",
+        r#"
+        let { wrap } = import! std.applicative
+        let { Eff, run_pure, ? } = import! std.effect
+        let { State, get, put, eval_state } = import! std.effect.state
+        let { Reader, run_reader } = import! std.effect.reader
+        let { Error, run_error } = import! std.effect.error
+        let { Writer, run_writer } = import! std.effect.writer
+        let { Result } = import! std.result
+
+        let loop _ : () -> Eff [| state : State Int, reader : Reader Int, error : Error String, writer : Writer String | r |] Int =
+            do n = get
+            if n #Int== 0 then wrap n
+            else
+                seq put (n #Int- 1)
+                loop ()
+        \n ->
+            match (run_pure (eval_state n (run_writer (run_error (run_reader 0 (loop ())))))).value with
+            | Ok x -> x
+            | Err _ -> -1
+        "#,
+    )
+}
+
+fn effect_benchmark(c: &mut Criterion) {
+    c.bench_function("direct recursion", direct_recursion);
+    c.bench_function("state monad", state_monad);
+    c.bench_function("state effect", state_effect);
+    c.bench_function("state effect under nested handlers", state_effect_nested);
+}
+
+criterion_group!(effect, effect_benchmark);
+criterion_main!(effect);
@@ -0,0 +1,31 @@
+use criterion::{black_box, criterion_group, criterion_main, Bencher, Criterion};
+
+use gluon::new_vm;
+
+// Measures how registering types scales with the number of types which are already registered
+
+struct Marker;
+
+fn register_types(b: &mut Bencher, existing: usize) {
+    let vm = new_vm();
+    for i in 0..existing {
+        vm.register_type::<Marker>(&format!("bench.Existing{}", i), &[])
+            .unwrap();
+    }
+    let names: Vec<_> = (0..100).map(|i| format!("bench.Type{}", i)).collect();
+    b.iter(|| {
+        for name in &names {
+            black_box(vm.register_type::<Marker>(name, &["a"]).unwrap());
+        }
+    })
+}
+
+fn register_type_benchmark(c: &mut Criterion) {
+    c.bench_function("register 100 types", |b| register_types(b, 0));
+    c.bench_function("register 100 types with 1000 registered", |b| {
+        register_types(b, 1000)
+    });
+}
+
+criterion_group!(register_type, register_type_benchmark);
+criterion_main!(register_type);
@@ -0,0 +1,431 @@
+//! Checks that `match` expressions cover every value of the matched type and that each of their
+//! alternatives can be reached.
+//!
+//! Uses the usefulness algorithm from "Warnings for pattern matching" (Luc Maranget) where the
+//! alternatives of a `match` are rows of a pattern matrix. A row is useful if there is a value
+//! which it matches but no earlier row matches, and the values which no row matches are reported
+//! as witnesses of a non-exhaustive match.
+use std::iter::{once, repeat};
+
+use crate::base::{
+    ast::{self, Expr, Literal, Pattern, SpannedExpr, SpannedPattern, Typed, Visitor},
+    pos::{self, Span},
+    resolve,
+    symbol::Symbol,
+    types::{self, ArcType, NullInterner, Type, TypeEnv, TypeExt},
+};
+
+use crate::warning::{Warning, Warnings};
+
+/// The maximum number of uncovered patterns which are reported for a single `match`
+const MAX_WITNESSES: usize = 3;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Ctor {
+    Variant(String),
+    Record(Vec<String>),
+    Tuple(usize),
+    Literal(Literal),
+}
+
+#[derive(Clone, Debug)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+impl Pat {
+    fn is_wild(&self) -> bool {
+        match self {
+            Pat::Wild => true,
+            Pat::Ctor(..) => false,
+        }
+    }
+
+    fn fmt(&self, out: &mut String, parens: bool) {
+        match self {
+            Pat::Wild => out.push('_'),
+            Pat::Ctor(Ctor::Variant(name), args) => {
+                let parens = parens && !args.is_empty();
+                if parens {
+                    out.push('(');
+                }
+                out.push_str(name);
+                for arg in args {
+                    out.push(' ');
+                    arg.fmt(out, true);
+                }
+                if parens {
+                    out.push(')');
+                }
+            }
+            Pat::Ctor(Ctor::Tuple(_), args) => {
+                out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(", ");
+                    }
+                    arg.fmt(out, false);
+                }
+                out.push(')');
+            }
+            Pat::Ctor(Ctor::Record(names), args) => {
+                let fields: Vec<_> = names
+                    .iter()
+                    .zip(args)
+                    .filter(|(_, arg)| !arg.is_wild())
+                    .collect();
+                if fields.is_empty() {
+                    out.push('_');
+                    return;
+                }
+                out.push_str("{ ");
+                for (i, (name, arg)) in fields.into_iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(", ");
+                    }
+                    out.push_str(name);
+                    out.push_str(" = ");
+                    arg.fmt(out, false);
+                }
+                out.push_str(" }");
+            }
+            // Literals have no finite set of constructors so they are never part of a witness
+            Pat::Ctor(Ctor::Literal(_), _) => out.push('_'),
+        }
+    }
+}
+
+type Row = Vec<Pat>;
+
+/// Returns the rows which match `ctor`, with the arguments of `ctor` replacing the first column
+fn specialize(rows: &[Row], ctor: &Ctor, arity: usize) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| {
+            let args = match &row[0] {
+                Pat::Wild => repeat(Pat::Wild).take(arity).collect(),
+                Pat::Ctor(c, args) if c == ctor => args.clone(),
+                Pat::Ctor(..) => return None,
+            };
+            Some(args.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// Returns the rows which match any value in the first column, without the first column
+fn default_rows(rows: &[Row]) -> Vec<Row> {
+    rows.iter()
+        .filter(|row| row[0].is_wild())
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+fn head_ctors(rows: &[Row]) -> Vec<&Ctor> {
+    let mut ctors = Vec::new();
+    for row in rows {
+        if let Pat::Ctor(ctor, _) = &row[0] {
+            if !ctors.contains(&ctor) {
+                ctors.push(ctor);
+            }
+        }
+    }
+    ctors
+}
+
+struct Checker<'e> {
+    env: &'e dyn TypeEnv<Type = ArcType>,
+    warnings: Warnings,
+    max_witnesses: usize,
+}
+
+pub fn check_expr(env: &dyn TypeEnv<Type = ArcType>, expr: &SpannedExpr<Symbol>) -> Warnings {
+    let mut checker = Checker {
+        env,
+        warnings: Warnings::new(),
+        max_witnesses: MAX_WITNESSES,
+    };
+    checker.visit_expr(expr);
+    checker.warnings
+}
+
+/// Returns patterns, formatted as source code, which match the values that no alternative of the
+/// `match` on `scrutinee` matches. At most `max_witnesses` patterns are returned.
+pub fn missing_patterns(
+    env: &dyn TypeEnv<Type = ArcType>,
+    scrutinee: &SpannedExpr<Symbol>,
+    alts: &[ast::Alternative<Symbol>],
+    max_witnesses: usize,
+) -> Vec<String> {
+    let checker = Checker {
+        env,
+        warnings: Warnings::new(),
+        max_witnesses,
+    };
+    let rows = match checker.lower_alternatives(alts) {
+        Some(rows) => rows,
+        None => return Vec::new(),
+    };
+    checker
+        .missing(&rows, &[checker.scrutinee_type(scrutinee)])
+        .iter()
+        .map(|witness| {
+            let mut out = String::new();
+            witness[0].fmt(&mut out, false);
+            out
+        })
+        .collect()
+}
+
+impl Checker<'_> {
+    fn remove_aliases(&self, typ: &ArcType) -> ArcType {
+        resolve::remove_aliases(self.env, NullInterner::new(), typ.clone())
+    }
+
+    /// Returns every constructor of `typ` along with the types of their arguments, or `None` if
+    /// the values of `typ` can't be enumerated by constructors (such as `Int` or a function).
+    fn constructors(&self, typ: &ArcType, used: &[&Ctor]) -> Option<Vec<(Ctor, Vec<ArcType>)>> {
+        let typ = self.remove_aliases(typ);
+        let typ = typ.remove_forall();
+        match **typ {
+            Type::Variant(ref row) => Some(
+                row.row_iter()
+                    .map(|field| {
+                        let ctor = Ctor::Variant(field.name.declared_name().into());
+                        (
+                            ctor,
+                            field.typ.remove_forall().arg_iter().cloned().collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+            Type::Record(_) => {
+                let types = typ.row_iter().map(|field| field.typ.clone()).collect();
+                let ctor = if types::is_tuple(typ) {
+                    Ctor::Tuple(typ.row_iter().count())
+                } else {
+                    Ctor::Record(
+                        typ.row_iter()
+                            .map(|field| field.name.declared_name().into())
+                            .collect(),
+                    )
+                };
+                Some(vec![(ctor, types)])
+            }
+            // If the type is not known but the patterns destructure a record or tuple then that
+            // is the only constructor of the type
+            _ => used.iter().find_map(|&ctor| {
+                let arity = match ctor {
+                    Ctor::Record(names) => names.len(),
+                    Ctor::Tuple(arity) => *arity,
+                    Ctor::Variant(_) | Ctor::Literal(_) => return None,
+                };
+                Some(vec![(ctor.clone(), vec![Type::hole(); arity])])
+            }),
+        }
+    }
+
+    fn arg_types(&self, typ: &ArcType, ctor: &Ctor, arity: usize) -> Vec<ArcType> {
+        self.constructors(typ, &[ctor])
+            .and_then(|ctors| ctors.into_iter().find(|(c, _)| c == ctor))
+            .map(|(_, arg_types)| arg_types)
+            .filter(|arg_types| arg_types.len() == arity)
+            .unwrap_or_else(|| vec![Type::hole(); arity])
+    }
+
+    /// Converts `pattern` into a row entry. Returns `None` if the pattern contains errors.
+    fn lower(&self, pattern: &SpannedPattern<Symbol>) -> Option<Pat> {
+        Some(match &pattern.value {
+            Pattern::As(_, pattern) => return self.lower(pattern),
+            Pattern::Ident(_) => Pat::Wild,
+            Pattern::Literal(literal) => Pat::Ctor(Ctor::Literal(literal.clone()), Vec::new()),
+            Pattern::Constructor(id, args) => Pat::Ctor(
+                Ctor::Variant(id.name.declared_name().into()),
+                args.iter()
+                    .map(|arg| self.lower(arg))
+                    .collect::<Option<_>>()?,
+            ),
+            Pattern::Tuple { elems, .. } => {
+                let args = elems
+                    .iter()
+                    .map(|elem| self.lower(elem))
+                    .collect::<Option<Vec<_>>>()?;
+                if args.iter().all(Pat::is_wild) {
+                    Pat::Wild
+                } else {
+                    Pat::Ctor(Ctor::Tuple(args.len()), args)
+                }
+            }
+            Pattern::Record { typ, fields, .. } => {
+                let typ = self.remove_aliases(typ);
+                let mut names = Vec::new();
+                let mut args = Vec::new();
+                for field in typ.row_iter() {
+                    let value = ast::pattern_values(fields)
+                        .find(|(name, _)| name.value.name_eq(&field.name))
+                        .and_then(|(_, value)| value.as_ref());
+                    names.push(field.name.declared_name().to_string());
+                    args.push(match value {
+                        Some(value) => self.lower(value)?,
+                        None => Pat::Wild,
+                    });
+                }
+                if args.iter().all(Pat::is_wild) {
+                    Pat::Wild
+                } else {
+                    Pat::Ctor(Ctor::Record(names), args)
+                }
+            }
+            Pattern::Error => return None,
+        })
+    }
+
+    /// Converts the patterns of `alts` into the rows of a pattern matrix. Returns `None` if a
+    /// pattern contains errors.
+    fn lower_alternatives(&self, alts: &[ast::Alternative<Symbol>]) -> Option<Vec<Row>> {
+        alts.iter()
+            .map(|alt| self.lower(&alt.pattern).map(|pat| vec![pat]))
+            .collect()
+    }
+
+    fn scrutinee_type(&self, scrutinee: &SpannedExpr<Symbol>) -> ArcType {
+        scrutinee
+            .try_type_of(self.env)
+            .unwrap_or_else(|_| Type::hole())
+    }
+
+    /// Returns true if `row` matches a value which none of `rows` match
+    fn is_useful(&self, rows: &[Row], row: &[Pat], types: &[ArcType]) -> bool {
+        let (typ, rest_types) = match types.split_first() {
+            Some(x) => x,
+            None => return rows.is_empty(),
+        };
+        match &row[0] {
+            Pat::Ctor(ctor, args) => {
+                let types: Vec<_> = self
+                    .arg_types(typ, ctor, args.len())
+                    .into_iter()
+                    .chain(rest_types.iter().cloned())
+                    .collect();
+                let row: Row = args.iter().chain(&row[1..]).cloned().collect();
+                self.is_useful(&specialize(rows, ctor, args.len()), &row, &types)
+            }
+            Pat::Wild => {
+                let used = head_ctors(rows);
+                match self.constructors(typ, &used) {
+                    Some(ctors) if ctors.iter().all(|(ctor, _)| used.contains(&ctor)) => {
+                        ctors.iter().any(|(ctor, arg_types)| {
+                            let arity = arg_types.len();
+                            let row: Row = repeat(Pat::Wild)
+                                .take(arity)
+                                .chain(row[1..].iter().cloned())
+                                .collect();
+                            let types: Vec<_> =
+                                arg_types.iter().chain(rest_types).cloned().collect();
+                            self.is_useful(&specialize(rows, ctor, arity), &row, &types)
+                        })
+                    }
+                    _ => self.is_useful(&default_rows(rows), &row[1..], rest_types),
+                }
+            }
+        }
+    }
+
+    /// Returns rows of patterns which match values that none of `rows` match
+    fn missing(&self, rows: &[Row], types: &[ArcType]) -> Vec<Row> {
+        let (typ, rest_types) = match types.split_first() {
+            Some(x) => x,
+            None => {
+                return if rows.is_empty() {
+                    vec![Vec::new()]
+                } else {
+                    Vec::new()
+                };
+            }
+        };
+        let used = head_ctors(rows);
+        let ctors = self.constructors(typ, &used);
+        match ctors {
+            // Name every constructor which is missing if some of them are matched, otherwise `_`
+            // is a more useful witness than listing every constructor
+            Some(ctors)
+                if !used.is_empty() || ctors.iter().all(|(ctor, _)| used.contains(&ctor)) =>
+            {
+                let mut result: Vec<Row> = Vec::new();
+                for (ctor, arg_types) in ctors {
+                    let arity = arg_types.len();
+                    let types: Vec<_> = arg_types
+                        .into_iter()
+                        .chain(rest_types.iter().cloned())
+                        .collect();
+                    for mut witness in self.missing(&specialize(rows, &ctor, arity), &types) {
+                        let rest = witness.split_off(arity);
+                        result.push(once(Pat::Ctor(ctor.clone(), witness)).chain(rest).collect());
+                        if result.len() >= self.max_witnesses {
+                            return result;
+                        }
+                    }
+                }
+                result
+            }
+            _ => self
+                .missing(&default_rows(rows), rest_types)
+                .into_iter()
+                .map(|witness| once(Pat::Wild).chain(witness).collect())
+                .collect(),
+        }
+    }
+
+    fn check_match(
+        &mut self,
+        expr: &SpannedExpr<Symbol>,
+        scrutinee: &SpannedExpr<Symbol>,
+        alts: &[ast::Alternative<Symbol>],
+    ) {
+        // Code generated by macros such as `derive` reuses the span of the macro for every node
+        // and may contain catch all alternatives that are only sometimes needed
+        if alts.iter().any(|alt| alt.pattern.span == expr.span) {
+            return;
+        }
+        let rows = match self.lower_alternatives(alts) {
+            Some(rows) => rows,
+            None => return,
+        };
+        let types = [self.scrutinee_type(scrutinee)];
+
+        for (i, (alt, row)) in alts.iter().zip(&rows).enumerate() {
+            if !self.is_useful(&rows[..i], row, &types) {
+                self.warnings.push(pos::spanned(
+                    alt.pattern.span,
+                    Warning::UnreachableAlternative,
+                ));
+            }
+        }
+
+        let witnesses = self.missing(&rows, &types);
+        if !witnesses.is_empty() {
+            let witnesses = witnesses
+                .iter()
+                .map(|witness| {
+                    let mut out = String::new();
+                    witness[0].fmt(&mut out, false);
+                    out
+                })
+                .collect();
+            self.warnings.push(pos::spanned(
+                Span::new(expr.span.start(), scrutinee.span.end()),
+                Warning::NonExhaustiveMatch { witnesses },
+            ));
+        }
+    }
+}
+
+impl<'a> Visitor<'a, '_> for Checker<'_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &SpannedExpr<Symbol>) {
+        if let Expr::Match(scrutinee, alts) = &expr.value {
+            self.check_match(expr, scrutinee, alts);
+        }
+        ast::walk_expr(self, expr);
+    }
+}
@@ -17,6 +17,7 @@ extern crate gluon_base as base;
 #[macro_use]
 extern crate gluon_codegen;
 
+pub mod exhaustiveness;
 pub mod kindcheck;
 pub mod metadata;
 mod recursion_check;
@@ -26,6 +27,8 @@ mod typ;
 pub mod typecheck;
 pub mod unify;
 pub mod unify_type;
+pub mod unused;
+pub mod warning;
 
 mod implicits;
 
@@ -273,6 +273,11 @@ pub fn rename<'s, 'ast>(
                     }
                     for bind in &mut **bindings {
                         self.visit_alias(&mut bind.alias);
+                        for default in &mut *bind.defaults {
+                            if let Some(ref mut expr) = default.value {
+                                self.visit_expr(expr);
+                            }
+                        }
                     }
 
                     return TailCall::TailCall;
@@ -3,18 +3,21 @@
 //! checking of types are done in the `unify_type` and `kindcheck` modules.
 use std::{
     borrow::{BorrowMut, Cow},
+    convert::TryFrom,
     mem,
     sync::Arc,
 };
 
+use ordered_float::NotNan;
+
 use crate::base::{
     ast::{
-        self, Argument, AstType, DisplayEnv, Do, Expr, IdentEnv, KindedIdent, Literal, MutVisitor,
-        Pattern, PatternField, SpannedExpr, SpannedIdent, SpannedPattern, TypeBinding, Typed,
-        TypedIdent, ValueBinding, ValueBindings,
+        self, Argument, AstClone, AstType, DisplayEnv, Do, Expr, ExprField, HasMetadata, IdentEnv,
+        KindedIdent, Literal, MutVisitor, Pattern, PatternField, SpannedExpr, SpannedIdent,
+        SpannedPattern, TypeBinding, Typed, TypedIdent, ValueBinding, ValueBindings,
     },
     error::Errors,
-    fnv::{FnvMap, FnvSet},
+    fnv::{FnvMap, FnvSet, SymbolBuildHasher},
     kind::{ArcKind, Kind, KindCache, KindEnv},
     merge,
     metadata::{Metadata, MetadataEnv},
@@ -23,8 +26,8 @@ use crate::base::{
     scoped_map::{self, ScopedMap},
     symbol::{Symbol, SymbolModule, SymbolRef, Symbols},
     types::{
-        self, Alias, AliasRef, AppVec, ArcType, ArgType, Field, Flags, Generic, PrimitiveEnv, Type,
-        TypeCache, TypeContext, TypeEnv, TypeExt, TypePtr, Walker,
+        self, Alias, AliasRef, AppVec, ArcType, ArgType, BuiltinType, Field, Flags, Generic,
+        PrimitiveEnv, Type, TypeCache, TypeContext, TypeEnv, TypeExt, TypePtr, Walker,
     },
 };
 
@@ -41,7 +44,7 @@ use self::{
     mod_type::{ModType, ModTypeRef, TypeModifier},
 };
 
-pub use self::error::{Help, HelpError, SpannedTypeError, TypeError};
+pub use self::error::{Help, HelpError, SpannedTypeError, Suggestion, TypeError};
 
 mod error;
 mod generalize;
@@ -63,12 +66,12 @@ pub(crate) struct Environment<'a> {
     /// The global environment which the typechecker extracts types from
     environment: &'a (dyn TypecheckEnv<Type = RcType> + 'a),
     /// Stack allocated variables
-    stack: ScopedMap<Symbol, StackBinding>,
+    stack: ScopedMap<Symbol, StackBinding, SymbolBuildHasher>,
     /// Types which exist in some scope (`type Test = ... in ...`)
-    stack_types: ScopedMap<Symbol, (RcType, Alias<Symbol, RcType>)>,
+    stack_types: ScopedMap<Symbol, (RcType, Alias<Symbol, RcType>), SymbolBuildHasher>,
     kind_cache: KindCache,
-    type_variables: ScopedMap<Symbol, RcType>,
-    skolem_variables: ScopedMap<Symbol, RcType>,
+    type_variables: ScopedMap<Symbol, RcType, SymbolBuildHasher>,
+    skolem_variables: ScopedMap<Symbol, RcType, SymbolBuildHasher>,
 }
 
 impl<'a> KindEnv for Environment<'a> {
@@ -135,8 +138,20 @@ pub struct Typecheck<'a, 'ast> {
     kind_cache: KindCache,
 
     pub(crate) implicit_resolver: implicits::ImplicitResolver<'a>,
-    unbound_variables: ScopedMap<Symbol, ArcKind>,
+    unbound_variables: ScopedMap<Symbol, ArcKind, SymbolBuildHasher>,
     refined_variables: ScopedMap<u32, ()>,
+    /// The fields with default values of record types defined in the module, keyed by the name
+    /// of the type, along with the bindings which hold the values
+    record_defaults: FnvMap<Symbol, Vec<(Symbol, Symbol)>>,
+    /// Spans of the integer literals which the program evaluates to directly. These get the type
+    /// that the host expects of the program, which should not change the type of the literals.
+    host_typed_literals: FnvSet<Span<BytePos>>,
+    /// Local bindings of modules (`let m = import! m`) which are in scope, mapped to the module
+    /// they refer to
+    module_bindings: ScopedMap<Symbol, Symbol, SymbolBuildHasher>,
+    /// Fields of the record pattern of the let binding being checked which have already been
+    /// reported as private
+    private_pattern_fields: FnvSet<Symbol>,
     pub(crate) ast_arena: ast::ArenaRef<'a, 'ast, Symbol>,
 }
 
@@ -164,19 +179,23 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
         Typecheck {
             environment: Environment {
                 environment,
-                stack: ScopedMap::new(),
-                stack_types: ScopedMap::new(),
+                stack: ScopedMap::default(),
+                stack_types: ScopedMap::default(),
                 kind_cache: interner.kind_cache.clone(),
-                skolem_variables: ScopedMap::new(),
-                type_variables: ScopedMap::new(),
+                skolem_variables: ScopedMap::default(),
+                type_variables: ScopedMap::default(),
             },
             symbols: symbols,
             named_variables: FnvMap::default(),
             errors: Errors::new(),
             kind_cache: interner.kind_cache.clone(),
             implicit_resolver: crate::implicits::ImplicitResolver::new(environment, metadata),
-            unbound_variables: ScopedMap::new(),
+            unbound_variables: ScopedMap::default(),
             refined_variables: ScopedMap::new(),
+            record_defaults: FnvMap::default(),
+            host_typed_literals: FnvSet::default(),
+            module_bindings: ScopedMap::default(),
+            private_pattern_fields: FnvSet::default(),
             subs,
             ast_arena,
         }
@@ -200,10 +219,47 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
         self.translate_arc_type(&typ)
     }
 
+    /// Finds the type of `id`, reporting an error at `span` (which should be the span of `id`) if
+    /// it is not defined
     fn find_at(&mut self, span: Span<BytePos>, id: &Symbol) -> ModType {
         match self.find(id) {
             Ok(typ) => typ,
-            Err(err) => ModType::wobbly(self.error(span, err)),
+            Err(err) => {
+                let err = self.suggest_variables(span, err);
+                ModType::wobbly(self.error(span, err))
+            }
+        }
+    }
+
+    /// Adds the variables in scope with names similar to an undefined variable as suggestions for
+    /// replacing the variable at `span`
+    fn suggest_variables(
+        &self,
+        span: Span<BytePos>,
+        err: TypeError<Symbol, RcType>,
+    ) -> TypeError<Symbol, RcType> {
+        match err {
+            TypeError::UndefinedVariable(id, _) => {
+                let name = id.declared_name();
+                let is_constructor = name.starts_with(char::is_uppercase);
+                let candidates = self
+                    .environment
+                    .stack
+                    .iter()
+                    .map(|(symbol, _)| (symbol.declared_name(), symbol))
+                    .filter(|(candidate, _)| {
+                        candidate.starts_with(char::is_uppercase) == is_constructor
+                    });
+                let suggestions = error::similar_names(name, candidates)
+                    .into_iter()
+                    .map(|symbol| Suggestion {
+                        name: symbol.clone(),
+                        span,
+                    })
+                    .collect();
+                TypeError::UndefinedVariable(id, suggestions)
+            }
+            err => err,
         }
     }
 
@@ -221,7 +277,7 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                     Ok(ModType::wobbly(self.subs.new_var()))
                 } else {
                     info!("Undefined variable {}", id);
-                    Err(TypeError::UndefinedVariable(id.clone()))
+                    Err(TypeError::UndefinedVariable(id.clone(), Vec::new()))
                 }
             }
         }
@@ -356,12 +412,14 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
         self.environment.stack.enter_scope();
         self.environment.stack_types.enter_scope();
         self.implicit_resolver.enter_scope();
+        self.module_bindings.enter_scope();
     }
 
     fn exit_scope(&mut self) {
         self.environment.stack.exit_scope();
         self.environment.stack_types.exit_scope();
         self.implicit_resolver.exit_scope();
+        self.module_bindings.exit_scope();
     }
 
     fn generalize_binding(
@@ -391,7 +449,7 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
             use self::TypeError::*;
 
             match err.value.error {
-                UndefinedVariable(_)
+                UndefinedVariable(..)
                 | UndefinedType(_)
                 | DuplicateTypeDefinition(_)
                 | DuplicateField(_)
@@ -399,17 +457,23 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 | EmptyCase
                 | KindError(_)
                 | RecursionCheck(_)
+                | PrivateField { .. }
                 | Message(_) => (),
                 NotAFunction(ref mut typ)
-                | UndefinedField(ref mut typ, _)
+                | UndefinedField(ref mut typ, ..)
                 | PatternError {
                     constructor_type: ref mut typ,
                     ..
                 }
                 | InvalidProjection(ref mut typ)
+                | Hole(ref mut typ)
                 | TypeConstructorReturnsWrongType {
                     actual: ref mut typ,
                     ..
+                }
+                | NotALazyType {
+                    actual: ref mut typ,
+                    ..
                 } => self.generalize_type(0, typ, err.span),
                 UnableToResolveImplicit(ref mut inner_err) => {
                     use crate::implicits::ErrorKind::*;
@@ -511,6 +575,10 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
 
         let temp = expected_type.and_then(|expected| self.create_unifiable_signature(expected));
         let expected_type = temp.as_ref().or(expected_type);
+        self.host_typed_literals.clear();
+        if expected_type.is_some() {
+            tail_literal_spans(expr, &mut self.host_typed_literals);
+        }
 
         let mut typ = if let Some(expected_type) = expected_type {
             self.skolemize_in(
@@ -674,8 +742,22 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
             return Ok((result?, Vec::new()));
         }
         match expr.value {
+            Expr::Ident(ref mut id) if id.name.declared_name() == "_" => {
+                // A typed hole, report the type that is expected in its place so the user (or
+                // `completion::suggest_for_hole`) can figure out what to fill it with
+                let typ = match expected_type.take() {
+                    Some(expected) => expected.concrete.clone(),
+                    None => self.subs.new_var(),
+                };
+                self.error(expr.span, TypeError::Hole(typ.clone()));
+                id.typ = self.subs.bind_arc(&typ);
+                Ok((ModType::wobbly(typ), Vec::new()))
+            }
             Expr::Ident(ref mut id) => {
-                let typ = self.find(&id.name)?;
+                let span = expr.span;
+                let typ = self
+                    .find(&id.name)
+                    .map_err(|err| self.suggest_variables(span, err))?;
                 let modifier = typ.modifier;
                 let (args, typ) = self.instantiate_sigma(
                     expr.span,
@@ -685,16 +767,49 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 id.typ = self.subs.bind_arc(&typ);
                 Ok((ModType::new(modifier, typ), args))
             }
-            Expr::Literal(ref lit) => Ok((
-                ModType::rigid(match *lit {
-                    Literal::Int(_) => self.subs.int(),
-                    Literal::Byte(_) => self.subs.byte(),
-                    Literal::Float(_) => self.subs.float(),
-                    Literal::String(_) => self.subs.string(),
-                    Literal::Char(_) => self.subs.char(),
-                }),
-                Vec::new(),
-            )),
+            Expr::Literal(ref mut lit) => {
+                let mut from_num = false;
+                if let (Literal::Int(i), Some(expected)) = (&*lit, &*expected_type) {
+                    // Integer literals are `Int` by default but they are also allowed to be
+                    // `Float` or `Byte` if that is the type that is expected of them, as long as
+                    // the value can be represented exactly. Any other number type gets the value
+                    // through its `Num` implementation
+                    let from_host = self.host_typed_literals.contains(&expr.span);
+                    let expected = self.remove_aliases(self.subs.real(&expected.concrete).clone());
+                    match *expected {
+                        _ if from_host => (),
+                        Type::Builtin(BuiltinType::Float) if *i as f64 as i128 == *i as i128 => {
+                            *lit = Literal::Float(NotNan::new(*i as f64).unwrap());
+                        }
+                        Type::Builtin(BuiltinType::Byte) if u8::try_from(*i).is_ok() => {
+                            *lit = Literal::Byte(*i as u8);
+                        }
+                        Type::Builtin(_)
+                        | Type::Variable(_)
+                        | Type::Hole
+                        | Type::Error
+                        | Type::Function(..) => (),
+                        _ => from_num = true,
+                    }
+                }
+                if from_num && self.int_literal_from_num(expr) {
+                    return self.typecheck_(expr, expected_type);
+                }
+                let lit = match &expr.value {
+                    Expr::Literal(lit) => lit,
+                    _ => unreachable!(),
+                };
+                Ok((
+                    ModType::rigid(match *lit {
+                        Literal::Int(_) => self.subs.int(),
+                        Literal::Byte(_) => self.subs.byte(),
+                        Literal::Float(_) => self.subs.float(),
+                        Literal::String(_) => self.subs.string(),
+                        Literal::Char(_) => self.subs.char(),
+                    }),
+                    Vec::new(),
+                ))
+            }
             Expr::App {
                 ref mut func,
                 ref mut implicit_args,
@@ -712,7 +827,7 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                     *implicit_args = self.ast_arena.alloc_extend(implicit_vec);
                 }
 
-                typ
+                typ.map(|(typ, implicit_args)| (self.abstract_escaping_types(typ), implicit_args))
             }
             Expr::IfElse(ref mut pred, ref mut if_true, ref mut if_false) => {
                 let bool_type = self.bool();
@@ -748,7 +863,12 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                     let return_type = match &op_name[1 + op_type.len()..] {
                         "+" | "-" | "*" | "/" => prim_type.clone(),
                         "==" | "<" => self.bool(),
-                        _ => return Err(TypeError::UndefinedVariable(op.value.name.clone())),
+                        _ => {
+                            return Err(TypeError::UndefinedVariable(
+                                op.value.name.clone(),
+                                Vec::new(),
+                            ))
+                        }
                     };
                     ModType::rigid(self.subs.function(
                         vec![prim_type.clone(), prim_type.clone()],
@@ -926,6 +1046,14 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                                 typ
                             }
                             None => {
+                                // Private fields are removed from the type of the module so
+                                // report why the field is missing
+                                if let Some(module) = self.private_field_module(expr, field_id) {
+                                    return Err(TypeError::PrivateField {
+                                        module,
+                                        field: field_id.clone(),
+                                    });
+                                }
                                 // FIXME As the polymorphic `record_type` do not have the type
                                 // fields which `typ` this unification is only done after we
                                 // checked if the field exists which lets field accesses on
@@ -991,6 +1119,12 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
             } => {
                 let level = self.subs.var_id();
 
+                if base.is_none() {
+                    if let Some(expected_type) = expected_type {
+                        self.add_default_fields(&expected_type.concrete, expr.span, fields);
+                    }
+                }
+
                 let mut modifier = expected_type
                     .as_ref()
                     .map(|t| t.modifier)
@@ -1337,22 +1471,20 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
             );
 
             let arg_typ = self.typecheck(arg, ModType::wobbly(&arg_typ));
-
-            if arg_typ.modifier == TypeModifier::Rigid {
-                types::walk_type(&self.subs.zonk(&arg_typ), &mut |typ: &RcType| {
-                    if let Type::Variable(var) = &**typ {
-                        return_variables.insert(var.id);
-                    }
-                });
-            }
+            self.add_return_variables(&arg_typ, &mut return_variables);
 
             func_type = ret_typ;
         }
 
         let mut not_a_function_index = None;
 
+        // Lambdas whose parameter types are not yet known are checked after the other arguments
+        // so that the parameter types inferred from those arguments are pushed into the lambda
+        // body (`map (\x -> x.field) list` reports errors in `x.field` instead of at `list`)
+        let mut deferred_lambdas = Vec::new();
+
         let mut prev_arg_end = implicit_args.last().map_or(span, |arg| arg.span).end();
-        for arg in args.map(|arg| arg.borrow_mut()) {
+        for (i, arg) in args.map(|arg| arg.borrow_mut()).enumerate() {
             let errors_before = self.errors.len();
             let (arg_ty, ret_ty) = self.subsume_function(
                 prev_arg_end,
@@ -1368,19 +1500,22 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 break;
             }
 
-            let arg_ty = self.typecheck(arg, ModType::wobbly(&arg_ty));
+            prev_arg_end = arg.span.end();
 
-            if arg_ty.modifier == TypeModifier::Rigid {
-                types::walk_type(&self.subs.zonk(&arg_ty), &mut |typ: &RcType| {
-                    if let Type::Variable(var) = &**typ {
-                        return_variables.insert(var.id);
-                    }
-                });
+            let is_last = i + 1 == args_len as usize;
+            if !is_last && self.has_unknown_lambda_parameters(arg, &arg_ty) {
+                deferred_lambdas.push((arg, arg_ty));
+            } else {
+                let arg_ty = self.typecheck(arg, ModType::wobbly(&arg_ty));
+                self.add_return_variables(&arg_ty, &mut return_variables);
             }
 
             func_type = ret_ty;
+        }
 
-            prev_arg_end = arg.span.end();
+        for (arg, arg_ty) in deferred_lambdas {
+            let arg_ty = self.typecheck(arg, ModType::wobbly(&arg_ty));
+            self.add_return_variables(&arg_ty, &mut return_variables);
         }
 
         if let Some(arg) = not_a_function_index {
@@ -1425,6 +1560,148 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
         Ok(ModType::new(modifier, func_type))
     }
 
+    /// Makes the types of a record returned from a function abstract if they refer to the type
+    /// variables of that function, as `Set` does in
+    ///
+    /// ```f#
+    /// let make_set ord : forall a . Ord a -> _ =
+    ///     type Set = Array a
+    ///     ...
+    /// ```
+    ///
+    /// As those variables are not in scope outside of the function each application returns new,
+    /// opaque types in their place, which keeps the sets of different applications apart.
+    fn abstract_escaping_types(&mut self, typ: ModType) -> ModType {
+        let record = self.subs.zonk(&typ.concrete);
+        if !matches!(*record, Type::Record(_)) {
+            return typ;
+        }
+
+        // Types which refer to an abstracted type must be abstracted as well
+        let mut abstracted: Vec<(Symbol, Alias<Symbol, RcType>)> = Vec::new();
+        loop {
+            let escaping: Vec<_> = record
+                .type_field_iter()
+                .filter(|field| {
+                    !abstracted.iter().any(|(name, _)| *name == field.typ.name)
+                        && self.refers_to_escaping_type(field.typ.unresolved_type(), &abstracted)
+                })
+                .map(|field| (field.typ.name.clone(), field.typ.params().to_owned()))
+                .collect();
+            if escaping.is_empty() {
+                break;
+            }
+            for (name, params) in escaping {
+                let opaque = self.opaque();
+                let alias = self.new_alias(Symbol::from(name.as_str()), params, opaque);
+                abstracted.push((name, alias));
+            }
+        }
+        if abstracted.is_empty() {
+            return typ;
+        }
+
+        let replace = |typ: &RcType| {
+            types::walk_move_type(typ.clone(), &mut |typ: &RcType| {
+                let name = match &**typ {
+                    Type::Alias(alias) => &alias.name,
+                    Type::Ident(id) => &id.name,
+                    _ => return None,
+                };
+                abstracted
+                    .iter()
+                    .find(|(abstracted, _)| abstracted == name)
+                    .map(|(_, alias)| alias.as_type().clone())
+            })
+        };
+        let types = record
+            .type_field_iter()
+            .map(|field| {
+                let alias = abstracted
+                    .iter()
+                    .find(|(name, _)| *name == field.typ.name)
+                    .map_or_else(|| field.typ.clone(), |(_, alias)| alias.clone());
+                Field::new(field.name.clone(), alias)
+            })
+            .collect();
+        let mut row_iter = record.row_iter();
+        let fields = row_iter
+            .by_ref()
+            .map(|field| Field::new(field.name.clone(), replace(&field.typ)))
+            .collect();
+        let rest = row_iter.current_type().clone();
+        ModType::new(typ.modifier, self.poly_record(types, fields, rest))
+    }
+
+    /// Returns true if `typ` refers to a skolem which is not in scope or to one of the types in
+    /// `abstracted`
+    fn refers_to_escaping_type(
+        &self,
+        typ: &RcType,
+        abstracted: &[(Symbol, Alias<Symbol, RcType>)],
+    ) -> bool {
+        let is_abstracted = |name: &Symbol| abstracted.iter().any(|(n, _)| n == name);
+        let mut escaping = false;
+        types::walk_type(typ, &mut |typ: &RcType| match &**typ {
+            Type::Skolem(skolem) => {
+                let in_scope = self
+                    .environment
+                    .skolem_variables
+                    .iter()
+                    .chain(self.environment.type_variables.iter())
+                    .any(|(_, typ)| matches!(&**typ, Type::Skolem(s) if s.id == skolem.id));
+                escaping |= !in_scope;
+            }
+            Type::Alias(alias) => escaping |= is_abstracted(&alias.name),
+            Type::Ident(id) => escaping |= is_abstracted(&id.name),
+            _ => (),
+        });
+        escaping
+    }
+
+    /// Records the variables of a rigid argument type, which are then known to be determined by
+    /// the arguments of the application
+    fn add_return_variables(&self, arg_ty: &ModType, return_variables: &mut FnvSet<u32>) {
+        if arg_ty.modifier == TypeModifier::Rigid {
+            types::walk_type(&self.subs.zonk(arg_ty), &mut |typ: &RcType| {
+                if let Type::Variable(var) = &**typ {
+                    return_variables.insert(var.id);
+                }
+            });
+        }
+    }
+
+    /// Returns true if `arg` is a lambda and the types of its parameters, as given by the
+    /// expected type `arg_ty`, still contain unresolved type variables
+    fn has_unknown_lambda_parameters(
+        &self,
+        arg: &SpannedExpr<'ast, Symbol>,
+        arg_ty: &RcType,
+    ) -> bool {
+        let mut arg = arg;
+        let lambda = loop {
+            match &arg.value {
+                Expr::Lambda(lambda) => break lambda,
+                // `(\x -> ...)` is parsed as a tuple of one element
+                Expr::Tuple { elems, .. } if elems.len() == 1 => arg = &elems[0],
+                _ => return false,
+            }
+        };
+        let mut typ = self.subs.zonk(arg_ty);
+        for _ in lambda.args.iter() {
+            typ = match typ.as_explicit_function() {
+                Some((param, ret)) => {
+                    if param.flags().contains(Flags::HAS_VARIABLES) {
+                        return true;
+                    }
+                    ret.clone()
+                }
+                None => return false,
+            };
+        }
+        false
+    }
+
     fn typecheck_lambda(
         &mut self,
         function_type: ModType,
@@ -1575,8 +1852,12 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 match_type.concrete = self.subs.real(&match_type).clone();
                 match_type.concrete = self.instantiate_generics(&match_type);
                 match_type.concrete = self.subs.zonk(&match_type);
-                // Find the enum constructor and return the types for its arguments
-                let ctor_type = self.find_at(span, &id.name);
+                // Find the enum constructor and return the types for its arguments. `span` may
+                // include parentheses around the pattern so no replacements are suggested.
+                let ctor_type = match self.find(&id.name) {
+                    Ok(typ) => typ,
+                    Err(err) => ModType::wobbly(self.error(span, err)),
+                };
 
                 id.typ = self.subs.bind_arc(&ctor_type);
 
@@ -1645,6 +1926,9 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                         .find(|f| f.name.name_eq(name))
                         .map(|f| f.typ.clone())
                         .unwrap_or_else(|| {
+                            if self.private_pattern_fields.remove(name) {
+                                return self.subs.error();
+                            }
                             let typ = self.subs.new_var();
                             missing_fields_from_match_type.push(Field {
                                 name: name.clone(),
@@ -1687,11 +1971,24 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                             &field_type.typ
                         }
                         None => {
+                            let suggestions = error::similar_names(
+                                name.declared_name(),
+                                record_match_type
+                                    .type_field_iter()
+                                    .map(|field| (field.name.declared_name(), &field.name)),
+                            )
+                            .into_iter()
+                            .map(|field| Suggestion {
+                                name: field.clone(),
+                                span,
+                            })
+                            .collect();
                             self.error(
                                 span,
                                 TypeError::UndefinedField(
                                     match_type.concrete.clone(),
                                     name.clone(),
+                                    suggestions,
                                 ),
                             );
                             // We still define the type so that any uses later on in the program
@@ -1873,6 +2170,7 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 }
                 Expr::TypeBindings(ref mut bindings, ref mut body) => {
                     self.typecheck_type_bindings(bindings, body);
+                    self.bind_record_defaults(bindings, body);
 
                     scope_count += 1;
 
@@ -1993,6 +2291,32 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
             debug!("let {:?} : {}", bind.name, typ);
 
             if !is_recursive {
+                if let Pattern::Record { ref fields, .. } = bind.name.value {
+                    for field in &**fields {
+                        if let PatternField::Value { name, .. } = field {
+                            if let Some(module) = self.private_field_module(&bind.expr, &name.value)
+                            {
+                                self.error(
+                                    name.span,
+                                    TypeError::PrivateField {
+                                        module,
+                                        field: name.value.clone(),
+                                    },
+                                );
+                                self.private_pattern_fields.insert(name.value.clone());
+                            }
+                        }
+                    }
+                }
+
+                if let Pattern::Ident(ref id) = bind.name.value {
+                    if bind.args.is_empty() {
+                        if let Some(module) = self.module_of(&bind.expr) {
+                            self.module_bindings.insert(id.name.clone(), module);
+                        }
+                    }
+                }
+
                 let resolved_type = &mut resolved_types[i].concrete;
                 bind.resolved_type = self.subs.bind_arc(&resolved_type);
                 // Merge the type declaration and the actual type
@@ -2000,6 +2324,7 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 self.generalize_binding(level, resolved_type, bind);
                 debug!("Generalized mid {}", resolved_type);
                 self.typecheck_let_pattern(&mut bind.name, resolved_type.clone());
+                self.private_pattern_fields.clear();
                 debug!("Generalized to {}", bind.resolved_type);
                 self.finish_pattern(level, &mut bind.name, &resolved_type);
             } else {
@@ -2042,9 +2367,51 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
         self.environment.skolem_variables.exit_scope();
     }
 
+    /// Returns the module that `expr` refers to, either directly or through a local binding
+    fn module_of(&self, expr: &SpannedExpr<'ast, Symbol>) -> Option<Symbol> {
+        match &expr.value {
+            Expr::MacroExpansion { replacement, .. } => self.module_of(replacement),
+            Expr::Tuple { elems, .. } if elems.len() == 1 => self.module_of(&elems[0]),
+            Expr::Ident(id) if id.name.is_global() => Some(id.name.clone()),
+            Expr::Ident(id) => self.module_bindings.get(&id.name).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Returns the module of `module_expr` if it marks `field` as `#[private]`
+    fn private_field_module(
+        &self,
+        module_expr: &SpannedExpr<'ast, Symbol>,
+        field: &Symbol,
+    ) -> Option<Symbol> {
+        let module = self.module_of(module_expr)?;
+        let is_private = self
+            .environment
+            .environment
+            .get_metadata(&module)
+            .and_then(|metadata| metadata.module.get(field.declared_name()).cloned())
+            .map_or(false, |metadata| metadata.is_private());
+        if is_private {
+            Some(module)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if `typ` is `std.lazy.Lazy`, either directly or through aliases
+    fn is_lazy_type(&self, typ: &RcType) -> bool {
+        let is_lazy =
+            |alias: &AliasRef<Symbol, RcType>| alias.name.definition_name() == "std.lazy.Lazy";
+        let typ = resolve::canonical_alias(&self.environment, &mut &self.subs, typ, is_lazy);
+        resolve::peek_alias(&self.environment, &typ)
+            .ok()
+            .and_then(|alias| alias)
+            .map_or(false, |alias| is_lazy(&alias))
+    }
+
     fn typecheck_type_bindings(
         &mut self,
-        bindings: &mut [TypeBinding<Symbol>],
+        bindings: &mut [TypeBinding<'ast, Symbol>],
         expr: &SpannedExpr<'ast, Symbol>,
     ) {
         self.enter_scope();
@@ -2189,6 +2556,204 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
         }
     }
 
+    /// Binds the default values of the fields of record types right after the type definitions
+    /// so that each default is evaluated once, in source order. Record expressions which omit a
+    /// field refer to the binding of its default.
+    fn bind_record_defaults(
+        &mut self,
+        bindings: &mut [TypeBinding<'ast, Symbol>],
+        body: &mut SpannedExpr<'ast, Symbol>,
+    ) {
+        let mut default_bindings = Vec::new();
+        for bind in bindings {
+            let mut defaults = Vec::with_capacity(bind.defaults.len());
+            for ExprField { name, value, .. } in &mut *bind.defaults {
+                let expr = match value.take() {
+                    Some(expr) => expr,
+                    None => continue,
+                };
+                let field_type = types::row_iter(
+                    bind.alias
+                        .value
+                        .unresolved_type_mut()
+                        .remove_single_forall(),
+                )
+                .find(|field| field.name.value.name_eq(&name.value))
+                .map(|field| field.typ.ast_clone(self.ast_arena));
+
+                let id = Symbol::from(default_binding_name(
+                    bind.name.value.declared_name(),
+                    name.value.declared_name(),
+                ));
+                defaults.push((name.value.clone(), id.clone()));
+                default_bindings.push(ValueBinding {
+                    name: pos::spanned(name.span, Pattern::Ident(TypedIdent::new(id))),
+                    typ: field_type,
+                    expr,
+                    ..ValueBinding::default()
+                });
+            }
+            if !defaults.is_empty() {
+                self.record_defaults
+                    .insert(bind.alias.value.name.clone(), defaults);
+            }
+        }
+
+        for bind in default_bindings.into_iter().rev() {
+            let rest = mem::take(body);
+            *body = pos::spanned(rest.span, Expr::let_binding(self.ast_arena, bind, rest));
+        }
+    }
+
+    /// Returns expressions for the default values of the fields of `record_type`. Defaults of
+    /// types defined in this module refer to their bindings directly. The defaults of imported
+    /// types are read from the `default` attributes in the metadata of the type, which only
+    /// describe the value when the default is a literal.
+    fn record_field_defaults(
+        &mut self,
+        record_type: &RcType,
+        span: Span<BytePos>,
+    ) -> Vec<(Symbol, SpannedExpr<'ast, Symbol>)> {
+        let alias_name = match record_type.alias_ident() {
+            Some(name) => name.clone(),
+            None => return Vec::new(),
+        };
+        if let Some(defaults) = self.record_defaults.get(&alias_name) {
+            return defaults
+                .iter()
+                .map(|(name, id)| {
+                    (
+                        name.clone(),
+                        pos::spanned(span, Expr::Ident(TypedIdent::new(id.clone()))),
+                    )
+                })
+                .collect();
+        }
+
+        let name = alias_name.name();
+        let module = name.module();
+        if module.as_str().is_empty() || module == self.symbols.module() {
+            return Vec::new();
+        }
+        let module_id = Symbol::from(format!("@{}", module));
+        let type_metadata = match self
+            .environment
+            .get_metadata(&module_id)
+            .and_then(|metadata| metadata.module.get(name.name().as_str()).cloned())
+        {
+            Some(metadata) => metadata,
+            None => return Vec::new(),
+        };
+
+        let record_type =
+            resolve::remove_aliases_cow(&self.environment, &mut &self.subs, record_type);
+        record_type
+            .row_iter()
+            .filter_map(|field| {
+                let source = type_metadata
+                    .module
+                    .get(field.name.declared_name())?
+                    .get_attribute("default")?;
+                let literal = parse_literal(source)?;
+                Some((
+                    field.name.clone(),
+                    pos::spanned(span, Expr::Literal(literal)),
+                ))
+            })
+            .collect()
+    }
+
+    /// Replaces the integer literal `expr` with `std.num.from_int expr` so that it gets the type of
+    /// the `Num` implementation which is resolved for it. Returns `false` if `std.num` has not been
+    /// loaded, in which case the literal is left as an `Int`.
+    fn int_literal_from_num(&mut self, expr: &mut SpannedExpr<'ast, Symbol>) -> bool {
+        let module_id = Symbol::from("@std.num");
+        let from_int = match self
+            .environment
+            .find_type(&module_id)
+            .and_then(|module_type| {
+                module_type
+                    .row_iter()
+                    .find(|field| field.name.declared_name() == "from_int")
+                    .map(|field| field.name.clone())
+            }) {
+            Some(from_int) => from_int,
+            None => return false,
+        };
+        let span = expr.span;
+        let literal = pos::spanned(span, mem::take(&mut expr.value));
+        let module_expr = pos::spanned(span, Expr::Ident(TypedIdent::new(module_id)));
+        let func = pos::spanned(
+            span,
+            Expr::Projection(
+                self.ast_arena.alloc(module_expr),
+                from_int,
+                Default::default(),
+            ),
+        );
+        expr.value = Expr::App {
+            func: self.ast_arena.alloc(func),
+            implicit_args: &mut [],
+            args: self.ast_arena.alloc_extend(Some(literal)),
+        };
+        true
+    }
+
+    /// Adds the default values of the fields that are missing from a record expression whose
+    /// expected type is a record type with defaults. The fields are put in the same order as in
+    /// the record type.
+    fn add_default_fields(
+        &mut self,
+        expected_type: &RcType,
+        span: Span<BytePos>,
+        fields: &mut &'ast mut [ExprField<'ast, Symbol, SpannedExpr<'ast, Symbol>>],
+    ) {
+        let expected_type = self.subs.real(expected_type).clone();
+        let mut defaults = self.record_field_defaults(&expected_type, span);
+        defaults.retain(|(name, _)| !fields.iter().any(|field| field.name.value.name_eq(name)));
+        if defaults.is_empty() {
+            return;
+        }
+
+        let record_type =
+            resolve::remove_aliases_cow(&self.environment, &mut &self.subs, &expected_type);
+
+        let mut explicit_fields: Vec<_> = fields
+            .iter_mut()
+            .map(|field| Some(mem::take(field)))
+            .collect();
+        let mut defaults: Vec<_> = defaults.into_iter().map(Some).collect();
+        let mut new_fields = Vec::with_capacity(explicit_fields.len() + defaults.len());
+        for field in record_type.row_iter() {
+            let explicit_field = explicit_fields.iter_mut().find(|explicit_field| {
+                explicit_field.as_ref().map_or(false, |explicit_field| {
+                    explicit_field.name.value.name_eq(&field.name)
+                })
+            });
+            if let Some(explicit_field) = explicit_field {
+                new_fields.extend(explicit_field.take());
+            } else if let Some((name, expr)) = defaults
+                .iter_mut()
+                .find(|default| {
+                    default
+                        .as_ref()
+                        .map_or(false, |(name, _)| name.name_eq(&field.name))
+                })
+                .and_then(Option::take)
+            {
+                new_fields.push(ExprField {
+                    metadata: Default::default(),
+                    name: pos::spanned(expr.span, name),
+                    value: Some(expr),
+                });
+            }
+        }
+        // Fields which do not exist in the type are kept so that they get reported as errors
+        new_fields.extend(explicit_fields.into_iter().flatten());
+
+        *fields = self.ast_arena.alloc_extend(new_fields);
+    }
+
     fn kindcheck(&mut self, typ: &mut AstType<Symbol>) {
         let result = {
             let mut check = KindCheck::new(
@@ -2213,7 +2778,10 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
             Type::Generic(id) => {
                 if !self.environment.type_variables.contains_key(&id.id) {
                     info!("Undefined type variable {}", id.id);
-                    self.error(typ.span(), TypeError::UndefinedVariable(id.id.clone()));
+                    self.error(
+                        typ.span(),
+                        TypeError::UndefinedVariable(id.id.clone(), Vec::new()),
+                    );
                 }
             }
 
@@ -2238,9 +2806,28 @@ impl<'a, 'ast> Typecheck<'a, 'ast> {
                 }
             }
 
-            Type::Record(_) => {
+            Type::Record(row) => {
                 // Inside records variables are bound implicitly to the closest field
                 // so variables are allowed to be undefined/implicit
+                for field in types::row_iter(row) {
+                    let is_lazy = field
+                        .typ
+                        .metadata()
+                        .map_or(false, |metadata| metadata.get_attribute("lazy").is_some());
+                    if !is_lazy {
+                        continue;
+                    }
+                    let actual = self.translate_ast_type(&field.typ);
+                    if !self.is_lazy_type(&actual) {
+                        self.error(
+                            field.typ.span(),
+                            TypeError::NotALazyType {
+                                field: field.name.value.clone(),
+                                actual,
+                            },
+                        );
+                    }
+                }
             }
 
             _ => {
@@ -3177,7 +3764,9 @@ pub fn translate_projected_type(
                             }
                         }))
                         .next()
-                        .ok_or_else(|| TypeError::UndefinedField(typ, symbol.clone()))?,
+                        .ok_or_else(|| {
+                            TypeError::UndefinedField(typ, symbol.clone(), Vec::new())
+                        })?,
                 )
             }
             None => Some(
@@ -3186,7 +3775,7 @@ pub fn translate_projected_type(
                         env.find_type_info(&symbol)
                             .map(|alias| alias.typ(interner).into_owned())
                     })
-                    .ok_or_else(|| TypeError::UndefinedVariable(symbol.clone()))?,
+                    .ok_or_else(|| TypeError::UndefinedVariable(symbol.clone(), Vec::new()))?,
             ),
         };
     }
@@ -3196,7 +3785,7 @@ pub fn translate_projected_type(
         .type_field_iter()
         .find(|field| field.name.name_eq(&type_symbol))
         .map(|field| field.typ.clone().into_type())
-        .ok_or_else(|| TypeError::UndefinedField(typ, type_symbol))
+        .ok_or_else(|| TypeError::UndefinedField(typ, type_symbol, Vec::new()))
 }
 
 fn with_pattern_types<'a: 'b, 'b, 'ast>(
@@ -3297,6 +3886,77 @@ fn function_arg_iter<'a, 'b, 'ast>(
     FunctionArgIter { tc, typ }
 }
 
+/// Collects the spans of the integer literals which `expr` may evaluate to directly
+fn tail_literal_spans(expr: &SpannedExpr<Symbol>, spans: &mut FnvSet<Span<BytePos>>) {
+    match &expr.value {
+        Expr::Literal(Literal::Int(_)) => {
+            spans.insert(expr.span);
+        }
+        Expr::LetBindings(_, body) | Expr::TypeBindings(_, body) => tail_literal_spans(body, spans),
+        Expr::IfElse(_, if_true, if_false) => {
+            tail_literal_spans(if_true, spans);
+            tail_literal_spans(if_false, spans);
+        }
+        Expr::Match(_, alts) => {
+            for alt in &**alts {
+                tail_literal_spans(&alt.expr, spans);
+            }
+        }
+        Expr::Block(exprs) => {
+            if let Some(last) = exprs.last() {
+                tail_literal_spans(last, spans);
+            }
+        }
+        Expr::Tuple { elems, .. } if elems.len() == 1 => tail_literal_spans(&elems[0], spans),
+        Expr::MacroExpansion { replacement, .. } => tail_literal_spans(replacement, spans),
+        _ => (),
+    }
+}
+
+/// The name of the binding which holds the default value of `field` in the record type `typ`.
+/// The leading `_` keeps the binding from being reported as unused.
+fn default_binding_name(typ: &str, field: &str) -> String {
+    format!("__default_{}_{}", typ, field)
+}
+
+/// Parses the source of a literal, as it is kept in the `default` attribute of a record field.
+/// Returns `None` for anything else, including literals with escapes or a non-decimal base.
+fn parse_literal(source: &str) -> Option<Literal> {
+    let source = source.trim();
+    if let Some(string) = source
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .filter(|s| !s.contains(&['\\', '"'][..]))
+    {
+        return Some(Literal::String(string.into()));
+    }
+    if let Some(c) = source.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        let mut chars = c.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(Literal::Char(c)),
+            _ => None,
+        };
+    }
+    let number = source.replace('_', "");
+    if !number
+        .trim_start_matches('-')
+        .starts_with(|c: char| c.is_ascii_digit())
+    {
+        return None;
+    }
+    if let Some(byte) = number.strip_suffix('b') {
+        return byte.parse().ok().map(Literal::Byte);
+    }
+    if let Ok(int) = number.parse() {
+        return Some(Literal::Int(int));
+    }
+    number
+        .parse()
+        .ok()
+        .and_then(|float| NotNan::new(float).ok())
+        .map(Literal::Float)
+}
+
 /// Returns a span of the innermost expression of a group of nested `let` and `type` bindings.
 /// This span is useful for more precisely marking the span of a type error.
 ///
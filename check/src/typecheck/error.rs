@@ -6,8 +6,8 @@ use pretty::Arena;
 
 use base::{
     ast,
-    error::AsDiagnostic,
-    pos::{self, BytePos, Spanned},
+    error::{AsDiagnostic, SuggestedFix},
+    pos::{self, BytePos, Span, Spanned},
     source::FileId,
     types::{ArcType, AsId, Filter, ToDoc, TypeExt, TypeFormatter},
 };
@@ -19,17 +19,44 @@ use crate::{
     unify_type::{self, Error as UnifyTypeError},
 };
 
+/// A name which is similar to a name which could not be found
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct Suggestion<I> {
+    pub name: I,
+    /// The location of the misspelled name, which `name` should replace
+    pub span: Span<BytePos>,
+}
+
+/// Returns up to three of the `candidates` which are the most similar to `name`, most similar
+/// first
+pub(crate) fn similar_names<'a, I>(
+    name: &str,
+    candidates: impl IntoIterator<Item = (&'a str, I)>,
+) -> Vec<I> {
+    let mut similar = candidates
+        .into_iter()
+        .filter(|&(candidate, _)| candidate != name)
+        .map(|(candidate, id)| (strsim::jaro_winkler(name, candidate), candidate, id))
+        .filter(|&(similarity, ..)| similarity >= 0.8)
+        .collect::<Vec<_>>();
+    similar.sort_by(|l, r| r.0.partial_cmp(&l.0).unwrap().then_with(|| l.1.cmp(r.1)));
+    similar.dedup_by(|l, r| l.1 == r.1);
+    similar.into_iter().take(3).map(|(.., id)| id).collect()
+}
+
 /// Type representing a single error when checking a type
 #[derive(Debug, Eq, PartialEq, Clone, Hash, Functor)]
 pub enum TypeError<I, T> {
-    /// Variable has not been defined before it was used
-    UndefinedVariable(I),
+    /// Variable has not been defined before it was used. Carries the variables in scope whose
+    /// names are similar to it.
+    UndefinedVariable(I, Vec<Suggestion<I>>),
     /// Attempt to call a type which is not a function
     NotAFunction(T),
     /// Type has not been defined before it was used
     UndefinedType(I),
-    /// Type were expected to have a certain field
-    UndefinedField(T, I),
+    /// Type were expected to have a certain field. Carries the fields of the type whose names are
+    /// similar to it.
+    UndefinedField(T, I, Vec<Suggestion<I>>),
     /// Constructor type was found in a pattern but did not have the expected number of arguments
     PatternError {
         constructor_type: T,
@@ -59,6 +86,18 @@ pub enum TypeError<I, T> {
         expected: I,
         actual: T,
     },
+    /// A field marked as `#[private]` were accessed from outside of its module
+    PrivateField {
+        module: I,
+        field: I,
+    },
+    /// A field marked as lazy (`~field : Type`) did not have a `Lazy` type
+    NotALazyType {
+        field: I,
+        actual: T,
+    },
+    /// A `_` was used as an expression, carries the type which is expected in its place
+    Hole(T),
 }
 
 impl<I, T> From<KindCheckError<I, T>> for TypeError<I, T> {
@@ -66,7 +105,7 @@ impl<I, T> From<KindCheckError<I, T>> for TypeError<I, T> {
         match e {
             UnifyError::Other(KindError::UndefinedType(name)) => TypeError::UndefinedType(name),
             UnifyError::Other(KindError::UndefinedField(typ, name)) => {
-                TypeError::UndefinedField(typ, name)
+                TypeError::UndefinedField(typ, name, Vec::new())
             }
             e => TypeError::KindError(e),
         }
@@ -99,10 +138,10 @@ where
         use self::TypeError::*;
         use pretty::DocAllocator;
         match &*self {
-            UndefinedVariable(name) => write!(f, "Undefined variable `{}`", name),
+            UndefinedVariable(name, _) => write!(f, "Undefined variable `{}`", name),
             NotAFunction(typ) => write!(f, "`{}` is not a function", typ),
             UndefinedType(name) => write!(f, "Type `{}` is not defined", name),
-            UndefinedField(typ, field) => {
+            UndefinedField(typ, field, _) => {
                 let fields = [field.clone()];
                 let filter = unify_type::similarity_filter(typ, &fields);
                 let arena = Arena::<()>::new();
@@ -219,6 +258,17 @@ where
                 "The constructor returns the type `{}` instead of the expected type `{}`",
                 actual, expected
             ),
+            PrivateField { module, field } => write!(
+                f,
+                "The field `{}` is private to the module `{}`",
+                field, module
+            ),
+            NotALazyType { field, actual } => write!(
+                f,
+                "The field `{}` is marked as lazy but its type `{}` is not `Lazy`",
+                field, actual
+            ),
+            Hole(typ) => write!(f, "Found hole `_` of type `{}`", typ),
         }
     }
 }
@@ -237,9 +287,43 @@ where
         use self::TypeError::*;
         match *self {
             UnableToResolveImplicit(ref err) => err.as_diagnostic(map),
+            UndefinedVariable(_, ref suggestions) | UndefinedField(_, _, ref suggestions)
+                if !suggestions.is_empty() =>
+            {
+                let mut note = String::from("help: did you mean ");
+                for (i, suggestion) in suggestions.iter().enumerate() {
+                    if i != 0 {
+                        note.push_str(if i + 1 == suggestions.len() {
+                            " or "
+                        } else {
+                            ", "
+                        });
+                    }
+                    note.push_str(&format!("`{}`", suggestion.name));
+                }
+                note.push('?');
+                Diagnostic::error()
+                    .with_message(self.to_string())
+                    .with_notes(vec![note])
+            }
             _ => Diagnostic::error().with_message(self.to_string()),
         }
     }
+
+    fn suggested_fixes(&self) -> Vec<SuggestedFix> {
+        use self::TypeError::*;
+        match self {
+            UndefinedVariable(_, suggestions) | UndefinedField(_, _, suggestions) => suggestions
+                .iter()
+                .map(|suggestion| SuggestedFix {
+                    message: format!("Did you mean `{}`?", suggestion.name),
+                    span: suggestion.span,
+                    replacement: suggestion.name.as_ref().to_string(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
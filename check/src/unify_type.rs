@@ -229,7 +229,10 @@ where
                         i if i < fields.len() - 1 => ", ",
                         _ => " and ",
                     };
-                    write!(f, "{}{}", sep, field)?;
+                    match types::tuple_index(field.as_ref()) {
+                        Some(index) if types::is_tuple(typ) => write!(f, "{}{}", sep, index)?,
+                        _ => write!(f, "{}{}", sep, field)?,
+                    }
                 }
                 Ok(())
             }
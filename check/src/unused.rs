@@ -0,0 +1,249 @@
+//! Warns about let bindings, function arguments and imports which are never referenced.
+//!
+//! Bindings whose name starts with `_` are never reported and `#[allow(unused)]` disables the
+//! warnings for the names and arguments of a let binding.
+use crate::base::{
+    ast::{
+        self, Argument, Expr, Pattern, SpannedExpr, SpannedIdent, SpannedPattern, ValueBinding,
+        Visitor,
+    },
+    fnv::FnvSet,
+    pos::{self, BytePos, Span},
+    symbol::{Symbol, SymbolId, Symbols},
+    types::ArgType,
+};
+
+use crate::warning::{Warning, Warnings};
+
+/// A group of names which are bound together by a single let binding or argument
+struct Binder {
+    span: Span<BytePos>,
+    /// The module which is bound, if this binds the result of `import!`
+    import: Option<String>,
+    names: Vec<(SymbolId, Span<BytePos>)>,
+}
+
+struct Checker {
+    /// Assigns ids to the symbols so that tracking a use does not need to clone the symbol
+    symbols: Symbols,
+    used: FnvSet<SymbolId>,
+    /// Constructors are not renamed so they are only tracked by name
+    used_constructors: FnvSet<String>,
+    binders: Vec<Binder>,
+    /// The names of type bindings. Code generated by `#[derive]` is spanned by the name of the
+    /// type it is derived for.
+    type_names: Vec<Span<BytePos>>,
+}
+
+pub fn check_expr(expr: &SpannedExpr<Symbol>) -> Warnings {
+    let mut checker = Checker {
+        symbols: Symbols::new(),
+        used: FnvSet::default(),
+        used_constructors: FnvSet::default(),
+        binders: Vec::new(),
+        type_names: Vec::new(),
+    };
+    checker.visit_expr(expr);
+    checker.warnings()
+}
+
+fn is_constructor(name: &Symbol) -> bool {
+    name.declared_name().starts_with(char::is_uppercase)
+}
+
+fn allows_unused(bind: &ValueBinding<Symbol>) -> bool {
+    bind.metadata.get_attribute("allow").map_or(false, |lints| {
+        lints.split(',').any(|lint| lint.trim() == "unused")
+    })
+}
+
+/// Returns the module that `expr` imports if it is an expanded `import!`
+fn imported_module(expr: &SpannedExpr<Symbol>) -> Option<String> {
+    match &expr.value {
+        Expr::MacroExpansion { replacement, .. } => match &replacement.value {
+            Expr::Ident(id) if id.name.is_global() => Some(id.name.definition_name().into()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn pattern_names(
+    symbols: &mut Symbols,
+    pattern: &SpannedPattern<Symbol>,
+    names: &mut Vec<(SymbolId, Span<BytePos>)>,
+) {
+    match &pattern.value {
+        Pattern::Ident(id) => names.push((symbols.id(&id.name), pattern.span)),
+        Pattern::As(id, pattern) => {
+            names.push((symbols.id(&id.value), id.span));
+            pattern_names(symbols, pattern, names);
+        }
+        Pattern::Record { fields, .. } => {
+            for (name, value) in ast::pattern_values(fields) {
+                match value {
+                    Some(pattern) => pattern_names(symbols, pattern, names),
+                    None => names.push((symbols.id(&name.value), name.span)),
+                }
+            }
+        }
+        Pattern::Tuple { elems: args, .. } | Pattern::Constructor(_, args) => {
+            for arg in &**args {
+                pattern_names(symbols, arg, names);
+            }
+        }
+        Pattern::Literal(_) | Pattern::Error => (),
+    }
+}
+
+impl Checker {
+    fn let_binding(&mut self, bind: &ValueBinding<Symbol>) {
+        if allows_unused(bind) {
+            return;
+        }
+        let mut names = Vec::new();
+        pattern_names(&mut self.symbols, &bind.name, &mut names);
+        // Values brought into scope by `?` are only referred to implicitly so the import is
+        // always considered used
+        let implicit_import = match &bind.name.value {
+            Pattern::Record {
+                implicit_import, ..
+            } => implicit_import.is_some(),
+            _ => false,
+        };
+        self.binders.push(Binder {
+            span: bind.name.span,
+            import: if implicit_import {
+                None
+            } else {
+                imported_module(&bind.expr)
+            },
+            names,
+        });
+        self.arguments(&bind.args);
+    }
+
+    fn arguments(&mut self, args: &[Argument<SpannedIdent<Symbol>>]) {
+        for arg in args {
+            // Implicit arguments are used by implicit resolution, which may not need them
+            if arg.arg_type == ArgType::Implicit {
+                continue;
+            }
+            self.binders.push(Binder {
+                span: arg.name.span,
+                import: None,
+                names: vec![(self.symbols.id(&arg.name.value.name), arg.name.span)],
+            });
+        }
+    }
+
+    fn is_generated(&self, span: Span<BytePos>) -> bool {
+        self.type_names
+            .iter()
+            .any(|type_name| type_name.contains(span))
+    }
+
+    fn is_used(&self, id: SymbolId) -> bool {
+        let name = self.symbols.resolve(id);
+        self.used.contains(&id)
+            || (is_constructor(name) && self.used_constructors.contains(name.declared_name()))
+    }
+
+    fn use_symbol(&mut self, name: &Symbol) {
+        let id = self.symbols.id(name);
+        self.used.insert(id);
+    }
+
+    fn warnings(self) -> Warnings {
+        let mut warnings = Warnings::new();
+        for binder in &self.binders {
+            if self.is_generated(binder.span) {
+                continue;
+            }
+            let names = binder
+                .names
+                .iter()
+                .filter(|&&(id, _)| !self.symbols.resolve(id).declared_name().starts_with('_'))
+                .collect::<Vec<_>>();
+            if names.is_empty() {
+                continue;
+            }
+            if names.iter().any(|&&(id, _)| self.is_used(id)) {
+                for &(id, span) in names {
+                    if !self.is_used(id) {
+                        warnings.push(pos::spanned(
+                            span,
+                            Warning::UnusedBinding {
+                                name: self.symbols.resolve(id).declared_name().into(),
+                            },
+                        ));
+                    }
+                }
+            } else if let Some(module) = &binder.import {
+                warnings.push(pos::spanned(
+                    binder.span,
+                    Warning::UnusedImport {
+                        module: module.clone(),
+                    },
+                ));
+            } else {
+                warnings.extend(names.into_iter().map(|&(id, span)| {
+                    pos::spanned(
+                        span,
+                        Warning::UnusedBinding {
+                            name: self.symbols.resolve(id).declared_name().into(),
+                        },
+                    )
+                }));
+            }
+        }
+        warnings
+    }
+}
+
+impl<'a> Visitor<'a, '_> for Checker {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &SpannedExpr<Symbol>) {
+        match &expr.value {
+            Expr::Ident(id) => {
+                if is_constructor(&id.name) {
+                    self.used_constructors
+                        .insert(id.name.declared_name().into());
+                }
+                self.use_symbol(&id.name);
+            }
+            Expr::Infix { op, .. } => {
+                self.use_symbol(&op.value.name);
+            }
+            Expr::Record { exprs, .. } => {
+                // `{ x }` refers to the binding `x`
+                for field in &**exprs {
+                    if field.value.is_none() {
+                        self.use_symbol(&field.name.value);
+                    }
+                }
+            }
+            Expr::LetBindings(binds, _) => {
+                for bind in binds {
+                    self.let_binding(bind);
+                }
+            }
+            Expr::Lambda(lambda) => self.arguments(&lambda.args),
+            Expr::TypeBindings(binds, _) => {
+                self.type_names
+                    .extend(binds.iter().map(|bind| bind.name.span));
+            }
+            _ => (),
+        }
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &SpannedPattern<Symbol>) {
+        if let Pattern::Constructor(id, _) = &pattern.value {
+            self.used_constructors
+                .insert(id.name.declared_name().into());
+        }
+        ast::walk_pattern(self, &pattern.value);
+    }
+}
@@ -0,0 +1,57 @@
+//! Warnings about programs which compile but most likely do not do what was intended.
+use std::fmt;
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use crate::base::{
+    error::{AsDiagnostic, Errors},
+    pos::{BytePos, Spanned},
+    source::{CodeMap, FileId},
+};
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum Warning {
+    /// The alternatives of a `match` expression do not cover every value of the matched type.
+    /// Contains example patterns of values which are not matched by any alternative.
+    NonExhaustiveMatch { witnesses: Vec<String> },
+    /// An alternative of a `match` expression can never be reached as every value it matches is
+    /// matched by an earlier alternative
+    UnreachableAlternative,
+    /// A let binding or function argument which is never referenced
+    UnusedBinding { name: String },
+    /// The result of an `import!` which is never referenced
+    UnusedImport { module: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::NonExhaustiveMatch { witnesses } => {
+                write!(f, "Non-exhaustive match, the following patterns are not covered: ")?;
+                for (i, witness) in witnesses.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "`{}`", witness)?;
+                }
+                Ok(())
+            }
+            Warning::UnreachableAlternative => write!(
+                f,
+                "Unreachable alternative, every value it matches is matched by an earlier alternative"
+            ),
+            Warning::UnusedBinding { name } => write!(f, "Unused binding `{}`", name),
+            Warning::UnusedImport { module } => write!(f, "Unused import of `{}`", module),
+        }
+    }
+}
+
+impl AsDiagnostic for Warning {
+    fn as_diagnostic(&self, _map: &CodeMap) -> Diagnostic<FileId> {
+        Diagnostic::warning().with_message(self.to_string())
+    }
+}
+
+pub type SpannedWarning = Spanned<Warning, BytePos>;
+
+pub type Warnings = Errors<SpannedWarning>;
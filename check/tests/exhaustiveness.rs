@@ -0,0 +1,115 @@
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+#[macro_use]
+mod support;
+
+use crate::check::{exhaustiveness, warning::Warning};
+
+fn warnings(text: &str) -> Vec<Warning> {
+    let _ = env_logger::try_init();
+
+    let (expr, result) = support::typecheck_expr(text);
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
+    let env = support::MockEnv::new();
+    exhaustiveness::check_expr(&env, expr.expr())
+        .into_iter()
+        .map(|warning| warning.value)
+        .collect()
+}
+
+fn non_exhaustive(witnesses: &[&str]) -> Vec<Warning> {
+    vec![Warning::NonExhaustiveMatch {
+        witnesses: witnesses.iter().map(|s| s.to_string()).collect(),
+    }]
+}
+
+#[test]
+fn exhaustive_match() {
+    let text = r"
+type Option a = | None | Some a
+match Some 1 with
+| Some x -> x
+| None -> 0
+";
+    assert_eq!(warnings(text), vec![]);
+}
+
+#[test]
+fn missing_constructor() {
+    let text = r"
+type Option a = | None | Some a
+match Some 1 with
+| Some x -> x
+";
+    assert_eq!(warnings(text), non_exhaustive(&["None"]));
+}
+
+#[test]
+fn missing_nested_constructor() {
+    let text = r"
+type Option a = | None | Some a
+match Some (Some 1) with
+| Some (Some x) -> x
+| None -> 0
+";
+    assert_eq!(warnings(text), non_exhaustive(&["Some None"]));
+}
+
+#[test]
+fn missing_constructor_and_partially_matched_constructor() {
+    let text = r"
+type Shape = | Circle Float | Rect Float Float | Empty
+match Empty with
+| Circle r -> r
+| Rect w 1.0 -> w
+";
+    assert_eq!(warnings(text), non_exhaustive(&["Rect _ _", "Empty"]));
+}
+
+#[test]
+fn missing_tuple_element() {
+    let text = r"
+type AB = | A | B
+match (A, B) with
+| (A, _) -> 1
+| (B, A) -> 2
+";
+    assert_eq!(warnings(text), non_exhaustive(&["(B, B)"]));
+}
+
+#[test]
+fn missing_literal() {
+    let text = r#"
+match 1 with
+| 1 -> ""
+| 2 -> ""
+"#;
+    assert_eq!(warnings(text), non_exhaustive(&["_"]));
+}
+
+#[test]
+fn unreachable_alternative() {
+    let text = r"
+type Option a = | None | Some a
+match Some 1 with
+| _ -> 0
+| Some x -> x
+";
+    assert_eq!(warnings(text), vec![Warning::UnreachableAlternative]);
+}
+
+#[test]
+fn unreachable_after_covering_every_constructor() {
+    let text = r"
+type AB = | A | B
+match A with
+| A -> 1
+| B -> 2
+| x -> 3
+";
+    assert_eq!(warnings(text), vec![Warning::UnreachableAlternative]);
+}
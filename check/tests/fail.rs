@@ -7,6 +7,7 @@ extern crate gluon_parser as parser;
 
 use crate::base::{
     ast::KindedIdent,
+    error::AsDiagnostic,
     symbol::Symbol,
     types::{ArcType, Type},
 };
@@ -86,6 +87,67 @@ Test "" 2
     assert_err!(result, UndefinedVariable(..));
 }
 
+#[test]
+fn undefined_variable_suggests_similar_names() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let length = 1
+let width = 2
+lenght
+"#;
+    let result = support::typecheck(text);
+
+    let errors = match result {
+        Ok(typ) => panic!("Expected an error, got {}", typ),
+        Err(support::Error::Parser(err)) => panic!("{}", err),
+        Err(support::Error::Check(err)) => err.into_errors(),
+    };
+    let error = (&errors).into_iter().next().unwrap();
+    match &error.value.error {
+        TypeError::UndefinedVariable(_, suggestions) => {
+            let names = suggestions
+                .iter()
+                .map(|suggestion| suggestion.name.declared_name())
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["length"]);
+        }
+        err => panic!("Unexpected error {}", err),
+    }
+
+    let fixes = error.suggested_fixes();
+    assert_eq!(fixes.len(), 1);
+    assert_eq!(fixes[0].replacement, "length");
+    assert_eq!(fixes[0].span, error.span);
+}
+
+#[test]
+fn undefined_type_field_suggests_similar_names() {
+    let _ = env_logger::try_init();
+    let text = r#"
+type Test = Int
+let { Tset } = { Test }
+()
+"#;
+    let result = support::typecheck(text);
+
+    let errors = match result {
+        Ok(typ) => panic!("Expected an error, got {}", typ),
+        Err(support::Error::Parser(err)) => panic!("{}", err),
+        Err(support::Error::Check(err)) => err.into_errors(),
+    };
+    let error = (&errors).into_iter().next().unwrap();
+    match &error.value.error {
+        TypeError::UndefinedField(_, _, suggestions) => {
+            let names = suggestions
+                .iter()
+                .map(|suggestion| suggestion.name.declared_name())
+                .collect::<Vec<_>>();
+            assert_eq!(names, ["Test"]);
+        }
+        err => panic!("Unexpected error {}", err),
+    }
+}
+
 #[test]
 fn undefined_type_in_pattern_match_triggers_only_one_error() {
     let _ = env_logger::try_init();
@@ -368,6 +430,22 @@ y
     assert_eq!(errors[0].span, Span::new(14.into(), 15.into()));
 }
 
+#[test]
+fn lambda_argument_error_is_reported_in_lambda_body() {
+    use crate::base::pos::Span;
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let apply f x = f x
+apply (\r -> r.y) { x = 1 }
+"#;
+    let result = support::typecheck(text);
+    let errors: Vec<_> = result.unwrap_err().unwrap_check().into_errors().into();
+    assert_eq!(errors.len(), 1);
+    // The type of `r` is inferred from `{ x = 1 }` so the error is reported at `r.y`
+    assert_eq!(errors[0].span, Span::new(35.into(), 38.into()));
+}
+
 #[test]
 fn issue_286() {
     let _ = ::env_logger::try_init();
@@ -459,6 +537,18 @@ Found: Int
     );
 }
 
+#[test]
+fn undefined_tuple_field() {
+    let _ = env_logger::try_init();
+    let text = r#"
+(1, "").2
+"#;
+    let result = support::typecheck(text);
+
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("lacks the following fields: 2"), "{}", err);
+}
+
 test_check_err! {
     undefined_field_after_overload,
     r#"
@@ -782,3 +872,106 @@ Cons "" Nil
     "#,
 Unification { .. }
 }
+
+test_check_err! {
+    lazy_record_field_without_lazy_type,
+    r#"
+type Stream a = { value : a, ~tail : Stream a }
+()
+    "#,
+NotALazyType { .. }
+}
+
+test_check_err! {
+    lazy_record_field_with_type_named_lazy,
+    r#"
+type Lazy a = | Lazy (() -> a)
+type Stream a = { value : a, ~tail : Lazy (Stream a) }
+()
+    "#,
+NotALazyType { .. }
+}
+
+test_check_err! {
+    int_literal_out_of_range_for_byte,
+    r#"
+let x : Byte = 256
+x
+    "#,
+Unification { .. }
+}
+
+test_check_err! {
+    int_literal_not_exactly_representable_as_float,
+    r#"
+let x : Float = 9007199254740993
+x
+    "#,
+Unification { .. }
+}
+
+test_check_err! {
+    // `std.num` is not loaded so there is no `Num` implementation to convert the literal with
+    int_literal_is_int_without_std_num,
+    r#"
+type Num = | Num Int
+let x : Num = 1
+x
+    "#,
+Unification { .. }
+}
+
+test_check_err! {
+    record_field_default_wrong_type,
+    r#"
+type Config = { port : Int = "8080" }
+()
+    "#,
+Unification { .. }
+}
+
+test_check_err! {
+    record_field_without_default_is_required,
+    r#"
+type Config = { port : Int = 8080, name : String }
+let config : Config = { port = 80 }
+config
+    "#,
+Unification { .. }
+}
+
+test_check_err! {
+    named_argument_with_unknown_name,
+    r#"
+type DrawArgs = { width : Int = 1, color : String = "black" }
+let draw args : DrawArgs -> Int = args.width
+draw ~colour="red"
+    "#,
+Unification { .. }
+}
+
+#[test]
+fn typed_hole_reports_expected_type() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let f x : Int -> String = ""
+f _
+"#;
+    let result = support::typecheck(text);
+
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("Found hole `_` of type `Int`"), "{}", err);
+}
+
+#[test]
+fn typed_hole_without_expected_type() {
+    let _ = env_logger::try_init();
+    let text = r#"
+let x = _
+x
+"#;
+    let result = support::typecheck(text);
+
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("Found hole `_` of type"), "{}", err);
+}
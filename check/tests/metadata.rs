@@ -397,3 +397,28 @@ let x ?test : [Test a] -> a = test.x
         })
     );
 }
+
+#[test]
+fn propagate_private_attribute_to_module_field() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+#[private]
+let helper x = x
+let id x = helper x
+{ helper, id }
+"#;
+    let (mut expr, result) = support::typecheck_expr(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let metadata = metadata(&MockEnv, &mut expr);
+    assert_eq!(
+        metadata.module.get("helper").map(|m| m.is_private()),
+        Some(true)
+    );
+    assert_eq!(
+        metadata.module.get("id").map(|m| m.is_private()),
+        Some(false)
+    );
+}
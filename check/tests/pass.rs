@@ -1185,3 +1185,70 @@ Cons 0 Nil
     "#,
     "test.List Int"
 }
+
+test_check! {
+    int_literal_as_float,
+    r#"
+let x : Float = 1
+x #Float+ 2
+"#,
+    "Float"
+}
+
+test_check! {
+    int_literal_as_float_in_branches,
+    r#"
+let x : Float = if True then 1 else -2
+x
+"#,
+    "Float"
+}
+
+test_check! {
+    int_literal_as_byte,
+    r#"
+let x : Byte = 255
+x
+"#,
+    "Byte"
+}
+
+test_check! {
+    record_field_defaults,
+    r#"
+type Config = { port : Int = 8080, host : String = "0.0.0.0" }
+let config : Config = { host = "localhost" }
+config.port
+"#,
+    "Int"
+}
+
+test_check! {
+    record_field_defaults_all_omitted,
+    r#"
+type Config = { port : Int = 8080, name : String }
+let config : Config = { name = "test" }
+config
+"#,
+    "test.Config"
+}
+
+test_check! {
+    named_arguments,
+    r#"
+type DrawArgs = { width : Int, color : String }
+let draw args : DrawArgs -> Int = args.width
+draw ~width=3 ~color="red"
+"#,
+    "Int"
+}
+
+test_check! {
+    optional_named_arguments,
+    r#"
+type DrawArgs = { width : Int = 1, color : String = "black" }
+let draw args : DrawArgs -> Int = args.width
+draw ~color="red"
+"#,
+    "Int"
+}
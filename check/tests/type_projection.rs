@@ -135,3 +135,119 @@ t
 
     assert_req!(result.map(|t| t.to_string()), Ok("Params Int String"));
 }
+
+test_check! {
+    type_capturing_function_type_variable,
+    r#"
+    let make x : forall a . a -> _ =
+        type Box = | Box a
+        let boxed : Box = Box x
+        { Box, boxed }
+    let module = make 1
+    let x : module.Box = module.boxed
+    x
+    "#,
+    "test.Box"
+}
+
+test_check! {
+    recursive_type_capturing_function_type_variable,
+    r#"
+    let make x : forall a . a -> _ =
+        type List = | Nil | Cons a List
+        let single : List = Cons x Nil
+        { List, single }
+    let module = make ""
+    module.single
+    "#,
+    "test.List"
+}
+
+#[test]
+fn types_capturing_different_type_variables_are_distinct() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let make x : forall a . a -> _ =
+    type Box = | Box a
+    let boxed : Box = Box x
+    { Box, boxed }
+let int_module = make 1
+let string_module = make ""
+let x : string_module.Box = int_module.boxed
+x
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn types_of_different_applications_are_distinct() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let make x : forall a . a -> _ =
+    type Box = | Box a
+    let boxed : Box = Box x
+    { Box, boxed }
+let module1 = make 1
+let module2 = make 2
+let x : module2.Box = module1.boxed
+x
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn type_capturing_function_type_variable_is_abstract() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let make x : forall a . a -> _ =
+    type Set = Array a
+    let empty : Set = []
+    { Set, empty }
+let int_set = make 1
+let x : Array Int = int_set.empty
+x
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn type_capturing_function_type_variable_does_not_take_the_variable_as_a_parameter() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let make x : forall a . a -> _ =
+    type Set = Array a
+    let empty : Set = []
+    { Set, empty }
+let int_set = make 1
+let f x : int_set.Set String -> () = ()
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, KindError(..));
+}
+
+test_check! {
+    functions_of_a_module_accept_its_abstract_type,
+    r#"
+    let make x : forall a . a -> _ =
+        type Set = Array a
+        let empty : Set = []
+        let insert y s : a -> Set -> Set = s
+        { Set, empty, insert }
+    let int_set = make 1
+    let x : int_set.Set = int_set.insert 2 int_set.empty
+    x
+    "#,
+    "test.Set"
+}
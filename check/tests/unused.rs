@@ -0,0 +1,87 @@
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+#[macro_use]
+mod support;
+
+use crate::check::{unused, warning::Warning};
+
+fn warnings(text: &str) -> Vec<Warning> {
+    let _ = env_logger::try_init();
+
+    let (expr, result) = support::typecheck_expr(text);
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
+    unused::check_expr(expr.expr())
+        .into_iter()
+        .map(|warning| warning.value)
+        .collect()
+}
+
+fn unused_bindings(names: &[&str]) -> Vec<Warning> {
+    names
+        .iter()
+        .map(|name| Warning::UnusedBinding {
+            name: name.to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn unused_let_binding() {
+    let text = r"
+let x = 1
+let y = 2
+y
+";
+    assert_eq!(warnings(text), unused_bindings(&["x"]));
+}
+
+#[test]
+fn unused_arguments() {
+    let text = r"
+let f x y = x
+let g = \a b -> b
+f (g 1 2) 3
+";
+    assert_eq!(warnings(text), unused_bindings(&["y", "a"]));
+}
+
+#[test]
+fn unused_record_field_binding() {
+    let text = r"
+let { x, y } = { x = 1, y = 2 }
+x
+";
+    assert_eq!(warnings(text), unused_bindings(&["y"]));
+}
+
+#[test]
+fn record_field_pun_uses_binding() {
+    let text = r"
+let x = 1
+{ x }
+";
+    assert_eq!(warnings(text), vec![]);
+}
+
+#[test]
+fn underscore_prefix_suppresses_warning() {
+    let text = r"
+let _x = 1
+\_y -> 2
+";
+    assert_eq!(warnings(text), vec![]);
+}
+
+#[test]
+fn allow_unused_attribute_suppresses_warning() {
+    let text = r"
+#[allow(unused)]
+let f x = 1
+2
+";
+    assert_eq!(warnings(text), vec![]);
+}
@@ -160,6 +160,7 @@ mod attr;
 mod functor;
 mod getable;
 mod pushable;
+mod quote_expr;
 mod shared;
 mod trace;
 mod userdata;
@@ -201,6 +202,39 @@ pub fn functor(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     functor::derive(input.into()).into()
 }
 
+/// Builds a gluon expression from a template for a macro implemented in Rust, see
+/// `gluon::quote`.
+///
+/// The first argument is a `gluon::quote::Quoter` and the rest is the template, either as gluon
+/// tokens or as a string literal. Each `$name` in the template is replaced by the local variable
+/// `name`, which must be an expression (`SpannedExpr`) or an identifier (`Symbol`).
+///
+/// Tokens are limited to what Rust can tokenize and lose their layout, so use a string for
+/// templates containing lambdas (`\x -> ...`) or indentation sensitive blocks.
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate gluon_codegen;
+/// extern crate gluon;
+///
+/// use gluon::{
+///     base::{ast::SpannedExpr, symbol::Symbol},
+///     quote::{QuoteError, Quoter},
+/// };
+///
+/// fn square<'ast>(
+///     quoter: &mut Quoter<'_, 'ast>,
+///     e: SpannedExpr<'ast, Symbol>,
+/// ) -> Result<SpannedExpr<'ast, Symbol>, QuoteError> {
+///     quote_expr!(quoter, let x = $e in x * x)
+/// }
+/// # fn main() {}
+/// ```
+#[proc_macro]
+pub fn quote_expr(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    quote_expr::expand(input.into()).into()
+}
+
 #[doc(hidden)]
 #[proc_macro_derive(AstClone, attributes(gluon))]
 pub fn ast_clone(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
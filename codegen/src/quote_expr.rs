@@ -0,0 +1,108 @@
+use proc_macro2::{Delimiter, Ident, Spacing, Span, TokenStream, TokenTree};
+use syn::{parse::ParseStream, Expr, LitStr, Token};
+
+pub fn expand(input: TokenStream) -> TokenStream {
+    syn::parse::Parser::parse2(parse, input).unwrap_or_else(|err| err.to_compile_error())
+}
+
+fn parse(input: ParseStream) -> syn::Result<TokenStream> {
+    let quoter: Expr = input.parse()?;
+    input.parse::<Token![,]>()?;
+    let tokens: TokenStream = input.parse()?;
+
+    let mut template = String::new();
+    let mut splices = Vec::new();
+    match string_template(&tokens) {
+        Some(lit) => {
+            template = lit.value();
+            splices_in_string(&template, lit.span(), &mut splices);
+        }
+        None => write_tokens(tokens, &mut template, &mut splices),
+    }
+
+    let template = template.trim_end();
+    let splices = splices.iter().map(|ident| {
+        let name = ident.to_string();
+        quote! { (#name, ::gluon::quote::Splice::from(#ident)) }
+    });
+    Ok(quote! {
+        (#quoter).expr(#template, ::std::vec![#(#splices),*])
+    })
+}
+
+/// Returns the template if it was given as a single string literal
+fn string_template(tokens: &TokenStream) -> Option<LitStr> {
+    let mut iter = tokens.clone().into_iter();
+    match (iter.next(), iter.next()) {
+        (Some(lit @ TokenTree::Literal(_)), None) => syn::parse2(lit.into()).ok(),
+        _ => None,
+    }
+}
+
+fn push_splice(splices: &mut Vec<Ident>, ident: Ident) {
+    if !splices.contains(&ident) {
+        splices.push(ident);
+    }
+}
+
+/// Finds the `$name` splices in a template given as a string, using the same rules as
+/// `gluon::quote::Quoter::expr`
+fn splices_in_string(template: &str, span: Span, splices: &mut Vec<Ident>) {
+    let mut rest = template;
+    while let Some(i) = rest.find('$') {
+        rest = &rest[i + 1..];
+        if rest.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            push_splice(splices, Ident::new(&rest[..end], span));
+            rest = &rest[end..];
+        }
+    }
+}
+
+/// Writes `tokens` as gluon source into `template`, recording each `$name` in `splices`
+fn write_tokens(tokens: TokenStream, template: &mut String, splices: &mut Vec<Ident>) {
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '$' => {
+                if let Some(TokenTree::Ident(ident)) = iter.peek() {
+                    template.push('$');
+                    template.push_str(&ident.to_string());
+                    template.push(' ');
+                    push_splice(splices, ident.clone());
+                    iter.next();
+                } else {
+                    // Part of an operator such as `<$>`
+                    template.push('$');
+                    if punct.spacing() == Spacing::Alone {
+                        template.push(' ');
+                    }
+                }
+            }
+            TokenTree::Punct(punct) => {
+                template.push(punct.as_char());
+                if punct.spacing() == Spacing::Alone {
+                    template.push(' ');
+                }
+            }
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                template.push_str(open);
+                write_tokens(group.stream(), template, splices);
+                template.push_str(close);
+                template.push(' ');
+            }
+            TokenTree::Ident(_) | TokenTree::Literal(_) => {
+                template.push_str(&token.to_string());
+                template.push(' ');
+            }
+        }
+    }
+}
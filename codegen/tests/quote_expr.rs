@@ -0,0 +1,111 @@
+#[macro_use]
+extern crate gluon_codegen;
+extern crate gluon;
+
+mod init;
+
+use std::mem;
+
+use gluon::{
+    base::{
+        ast::{self, SpannedExpr},
+        mk_ast_arena,
+        symbol::{Symbol, Symbols},
+    },
+    quote::{QuoteError, Quoter},
+    vm::macros::{self, Macro, MacroExpander, MacroFuture},
+    ThreadExt,
+};
+
+use init::new_vm;
+
+/// `square! e` expands to `let x = e in x * x`, with a fresh `x`
+#[derive(Trace)]
+#[gluon(crate_name = "gluon::vm")]
+struct Square;
+
+impl Macro for Square {
+    fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+        &self,
+        env: &'b mut MacroExpander<'a>,
+        symbols: &'c mut Symbols,
+        arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+        args: &'b mut [SpannedExpr<'ast, Symbol>],
+    ) -> MacroFuture<'r, 'ast> {
+        Box::pin(async move {
+            let e = match args {
+                [arg] => mem::take(arg),
+                _ => return Err(macros::Error::message("`square!` expects 1 argument")),
+            };
+            let x = Symbol::from("x");
+            let mut quoter = Quoter::new(env.vm, arena.borrow(), symbols, e.span);
+            Ok(quote_expr!(quoter, let $x = $e in $x * $x)?.into())
+        })
+    }
+}
+
+/// `twice! f` expands to `\x -> f (f x)`
+#[derive(Trace)]
+#[gluon(crate_name = "gluon::vm")]
+struct Twice;
+
+impl Macro for Twice {
+    fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+        &self,
+        env: &'b mut MacroExpander<'a>,
+        symbols: &'c mut Symbols,
+        arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+        args: &'b mut [SpannedExpr<'ast, Symbol>],
+    ) -> MacroFuture<'r, 'ast> {
+        Box::pin(async move {
+            let f = match args {
+                [arg] => mem::take(arg),
+                _ => return Err(macros::Error::message("`twice!` expects 1 argument")),
+            };
+            let mut quoter = Quoter::new(env.vm, arena.borrow(), symbols, f.span);
+            Ok(quote_expr!(quoter, "let f = $f\n\\x -> f (f x)")?.into())
+        })
+    }
+}
+
+#[test]
+fn quote_tokens() {
+    let vm = new_vm();
+    vm.get_macros().insert("square".into(), Square);
+
+    let (result, _) = vm
+        .run_expr::<i32>("test", "let x = 2 in square! (x + 1)")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 9);
+}
+
+#[test]
+fn quote_string() {
+    let vm = new_vm();
+    vm.get_macros().insert("twice".into(), Twice);
+
+    let (result, _) = vm
+        .run_expr::<i32>("test", "(twice! (\\x -> x * 3)) 2")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 18);
+}
+
+#[test]
+fn quote_errors() {
+    let vm = new_vm();
+    let mut symbols = Symbols::new();
+    mk_ast_arena!(arena);
+    let arena = arena.borrow();
+    let mut quoter = Quoter::new(&vm, arena, &mut symbols, Default::default());
+
+    assert_eq!(
+        quoter.expr("$missing + 1", vec![]).map(|_| ()),
+        Err(QuoteError::UnknownSplice("missing".into()))
+    );
+
+    let e = quoter.expr("1", vec![]).unwrap();
+    assert_eq!(
+        quote_expr!(quoter, let $e = 1 in 2).map(|_| ()),
+        Err(QuoteError::ExpectedIdent("e".into()))
+    );
+}
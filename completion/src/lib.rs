@@ -2,8 +2,9 @@
 #![doc(html_root_url = "https://docs.rs/gluon_completion/0.18.0")] // # GLUON
 
 extern crate gluon_base as base;
+extern crate gluon_check as check;
 
-use std::{borrow::Cow, cmp::Ordering, iter::once, path::PathBuf, sync::Arc};
+use std::{borrow::Cow, cmp::Ordering, fmt, iter::once, path::PathBuf, sync::Arc};
 
 use codespan::ByteOffset;
 
@@ -17,17 +18,19 @@ use crate::base::{
     filename_to_module,
     fnv::{FnvMap, FnvSet},
     kind::ArcKind,
-    metadata::Metadata,
+    metadata::{Metadata, MetadataEnv},
     pos::{self, BytePos, HasSpan, Span, Spanned},
     resolve,
     scoped_map::ScopedMap,
-    symbol::{Name, Symbol, SymbolRef},
+    symbol::{Name, Symbol, SymbolId, SymbolRef, Symbols},
     types::{
-        walk_type_, AliasData, ArcType, ControlVisitation, Generic, NullInterner, Type, TypeEnv,
-        TypeExt,
+        walk_move_type, walk_type_, AliasData, ArcType, ControlVisitation, Generic, NullInterner,
+        Type, TypeEnv, TypeExt,
     },
 };
 
+use crate::check::{exhaustiveness, TypecheckEnv};
+
 #[derive(Clone, Debug)]
 pub struct Found<'a, 'ast> {
     pub match_: Option<Match<'a, 'ast>>,
@@ -104,6 +107,12 @@ where
 pub struct Suggestion {
     pub name: String,
     pub typ: Either<ArcKind, ArcType>,
+    /// The first paragraph of the documentation of the suggested item, if it is known
+    pub doc: Option<String>,
+    /// The metadata of the suggested item, if it is known
+    pub metadata: Option<Arc<Metadata>>,
+    /// How well `name` matches the text that has been typed, higher scores are better matches
+    pub score: u32,
 }
 
 struct Suggest<E> {
@@ -226,6 +235,17 @@ struct FindVisitor<'a, 'ast, F> {
     /// The span of the currently inspected expression, used to determine if a position is in that
     /// expression or outside it (macro expanded)
     source_span: Span<BytePos>,
+
+    /// The expressions containing `pos` which were visited, used by `batch` to resume the search
+    /// for the next position from the innermost expression containing it
+    checkpoints: Option<Vec<Checkpoint<'a, 'ast>>>,
+}
+
+/// The state of a `FindVisitor` when entering `expr`
+struct Checkpoint<'a, 'ast> {
+    expr: &'a SpannedExpr<'ast, Symbol>,
+    enclosing_matches: usize,
+    near_matches: usize,
 }
 
 impl<'a, 'ast, F> FindVisitor<'a, 'ast, F> {
@@ -495,11 +515,21 @@ where
         // When inside a macro expanded expression we do a exhaustive search for an unexpanded
         // expression
         if self.is_macro_expanded(current.span) {
+            // Which expressions are visited in the expansion depends on the expressions before
+            // them so the search can't be resumed from inside it
+            self.checkpoints = None;
             VisitUnExpanded(self).visit_expr(current);
             return;
         }
 
         if current.span.containment(self.pos) == Ordering::Equal {
+            if let Some(checkpoints) = &mut self.checkpoints {
+                checkpoints.push(Checkpoint {
+                    expr: current,
+                    enclosing_matches: self.enclosing_matches.len(),
+                    near_matches: self.near_matches.len(),
+                });
+            }
             self.enclosing_matches.push(Match::Expr(current));
         } else {
             self.near_matches.push(Match::Expr(current));
@@ -701,8 +731,15 @@ where
                 }
             }
             Expr::MacroExpansion {
-                ref replacement, ..
-            } => self.visit_expr(replacement),
+                ref original,
+                ref replacement,
+            } => match import_path_literal(original) {
+                // Search the path as it was written so that it can be completed
+                Some(path) if path.span.containment(self.pos) == Ordering::Equal => {
+                    self.visit_expr(original)
+                }
+                _ => self.visit_expr(replacement),
+            },
             Expr::Annotated(..) => unimplemented!(), // FIXME
             Expr::Error(..) => (),
         }
@@ -756,6 +793,65 @@ where
     }
 }
 
+/// Returns the string literal argument of `expr` if it is an import of a file path
+/// (`import! "std/map.glu"`)
+fn import_path_literal<'a, 'ast>(
+    expr: &'a SpannedExpr<'ast, Symbol>,
+) -> Option<&'a SpannedExpr<'ast, Symbol>> {
+    match &expr.value {
+        Expr::App { func, args, .. } => match (&func.value, &**args) {
+            (Expr::Ident(id), [arg]) if id.name.declared_name() == "import!" => match arg.value {
+                Expr::Literal(ast::Literal::String(_)) => Some(arg),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the record argument of `expr` if it formats the string literal `fmt` with
+/// `std.fmt.format` (`format "{name}" { name = "world" }`)
+fn format_args<'a, 'ast>(
+    expr: &'a SpannedExpr<'ast, Symbol>,
+    fmt: &SpannedExpr<'ast, Symbol>,
+) -> Option<&'a SpannedExpr<'ast, Symbol>> {
+    match &expr.value {
+        Expr::App { func, args, .. } => {
+            let name = match &func.value {
+                Expr::Ident(id) => id.name.declared_name(),
+                Expr::Projection(_, field, _) => field.declared_name(),
+                _ => return None,
+            };
+            match &**args {
+                [first, args, ..] if name == "format" && first.span == fmt.span => Some(args),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the field path (`a.b`) of the replacement field of the format string `fmt` which is
+/// open at the end of `fmt`, if it has not reached its spec yet
+fn open_replacement_field(fmt: &str) -> Option<&str> {
+    let mut start = None;
+    let mut chars = fmt.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' if start.is_none() && chars.peek().map(|&(_, c)| c) == Some('{') => {
+                chars.next();
+            }
+            '{' => start = Some(i + 1),
+            '}' => start = None,
+            _ => (),
+        }
+    }
+    start
+        .map(|start| &fmt[start..])
+        .filter(|field| !field.contains(':'))
+}
+
 fn complete_at<'a, 'ast, F>(
     on_found: F,
     source_span: Span<BytePos>,
@@ -772,22 +868,29 @@ where
         enclosing_matches: vec![Match::Expr(expr)],
         near_matches: vec![],
         source_span,
+        checkpoints: None,
     };
     visitor.visit_expr(expr);
-    let enclosing_matches = visitor.enclosing_matches;
-    let near_matches = visitor.near_matches;
-    match visitor.found {
-        MatchState::Found(match_) => Ok(Found {
-            match_: Some(match_),
-            enclosing_matches,
-            near_matches,
-        }),
-        MatchState::Empty => Ok(Found {
-            match_: None,
-            enclosing_matches,
-            near_matches,
-        }),
-        MatchState::NotFound => Err(()),
+    visitor.into_found()
+}
+
+impl<'a, 'ast, F> FindVisitor<'a, 'ast, F> {
+    fn into_found(self) -> Result<Found<'a, 'ast>, ()> {
+        let enclosing_matches = self.enclosing_matches;
+        let near_matches = self.near_matches;
+        match self.found {
+            MatchState::Found(match_) => Ok(Found {
+                match_: Some(match_),
+                enclosing_matches,
+                near_matches,
+            }),
+            MatchState::Empty => Ok(Found {
+                match_: None,
+                enclosing_matches,
+                near_matches,
+            }),
+            MatchState::NotFound => Err(()),
+        }
     }
 }
 
@@ -915,6 +1018,76 @@ where
     extract.extract(&found)
 }
 
+/// Runs `extract` at each of `positions`, returning the results in the same order.
+///
+/// The positions are visited in order and the search for each position resumes from the innermost
+/// expression which also contained the previous position, so the parts of the AST enclosing
+/// several positions are only walked once.
+pub fn batch<'a, 'ast, T>(
+    extract: T,
+    source_span: Span<BytePos>,
+    expr: &'a SpannedExpr<'ast, Symbol>,
+    positions: &[BytePos],
+) -> Vec<Result<T::Output, ()>>
+where
+    T: Extract<'a> + Clone,
+{
+    let mut order: Vec<usize> = (0..positions.len()).collect();
+    order.sort_by_key(|&i| positions[i]);
+
+    let mut results: Vec<_> = positions.iter().map(|_| Err(())).collect();
+    let mut checkpoints: Vec<Checkpoint> = Vec::new();
+    let mut enclosing_matches = vec![Match::Expr(expr)];
+    let mut near_matches = Vec::new();
+    for i in order {
+        let pos = positions[i];
+        while let Some(checkpoint) = checkpoints.last() {
+            if checkpoint.expr.span.containment(pos) == Ordering::Equal {
+                break;
+            }
+            checkpoints.pop();
+        }
+        let start = match checkpoints.pop() {
+            Some(checkpoint) => {
+                enclosing_matches.truncate(checkpoint.enclosing_matches);
+                near_matches.truncate(checkpoint.near_matches);
+                checkpoint.expr
+            }
+            None => {
+                enclosing_matches.truncate(1);
+                near_matches.clear();
+                expr
+            }
+        };
+
+        let mut visitor = FindVisitor {
+            pos,
+            on_found: (),
+            found: MatchState::NotFound,
+            enclosing_matches,
+            near_matches,
+            source_span,
+            checkpoints: Some(checkpoints),
+        };
+        visitor.visit_expr(start);
+
+        checkpoints = visitor.checkpoints.take().unwrap_or_default();
+        let (enclosing, near) = match visitor.into_found() {
+            Ok(found) => {
+                results[i] = extract.clone().extract(&found);
+                (found.enclosing_matches, found.near_matches)
+            }
+            Err(()) => {
+                checkpoints.clear();
+                (vec![Match::Expr(expr)], Vec::new())
+            }
+        };
+        enclosing_matches = enclosing;
+        near_matches = near;
+    }
+    results
+}
+
 pub fn find<'ast, T>(
     env: &T,
     source_span: Span<BytePos>,
@@ -928,6 +1101,50 @@ where
     completion(extract, source_span, expr, pos)
 }
 
+/// Information about the value or type at a position, as shown when hovering over it
+#[derive(Debug, PartialEq)]
+pub struct TypeInfo {
+    /// The kind of a type or the type of a value
+    pub typ: Either<ArcKind, ArcType>,
+    /// The definition of the type alias at the position, if it refers to one
+    pub alias: Option<AliasData<Symbol, ArcType>>,
+}
+
+/// Collects the type aliases which are in scope at the position
+#[derive(Default)]
+struct LocalAliases(FnvMap<Symbol, AliasData<Symbol, ArcType>>);
+
+impl OnFound for LocalAliases {
+    fn on_alias(&mut self, alias: &AliasData<Symbol, ArcType>) {
+        self.0.insert(alias.name.clone(), alias.clone());
+    }
+}
+
+/// Like `find` but also returns the definition of the type alias at `pos`, looking first at the
+/// aliases defined in `expr` and then in `env`
+pub fn find_type_info<'ast, T>(
+    env: &T,
+    source_span: Span<BytePos>,
+    expr: &SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+) -> Result<TypeInfo, ()>
+where
+    T: TypeEnv<Type = ArcType>,
+{
+    let mut aliases = LocalAliases::default();
+    let found = complete_at(&mut aliases, source_span, expr, pos)?;
+    let typ = TypeAt { env }.extract(&found)?;
+    let alias = match found.match_ {
+        Some(Match::Type(_, id, _)) => aliases
+            .0
+            .get(id)
+            .cloned()
+            .or_else(|| env.find_type_info(id).map(|alias| (**alias).clone())),
+        _ => None,
+    };
+    Ok(TypeInfo { typ, alias })
+}
+
 pub fn find_all_symbols<'ast>(
     source_span: Span<BytePos>,
     expr: &SpannedExpr<'ast, Symbol>,
@@ -935,166 +1152,1092 @@ pub fn find_all_symbols<'ast>(
 ) -> Result<(String, Vec<Span<BytePos>>), ()> {
     let extract = IdentAt;
     completion(extract, source_span, expr, pos).map(|symbol| {
-        struct ExtractIdents<'b> {
-            result: Vec<Span<BytePos>>,
-            symbol: &'b SymbolRef,
+        let mut visitor = ExtractIdents::new(symbol);
+        visitor.visit_expr(expr);
+        (visitor.symbol.declared_name().to_string(), visitor.result)
+    })
+}
+
+struct ExtractIdents<'b> {
+    result: Vec<Span<BytePos>>,
+    /// The spans in `result` which are record fields without a value or pattern (`{ x }`)
+    shorthands: Vec<Span<BytePos>>,
+    symbol: &'b SymbolRef,
+}
+
+impl<'b> ExtractIdents<'b> {
+    fn new(symbol: &'b SymbolRef) -> Self {
+        ExtractIdents {
+            result: Vec::new(),
+            shorthands: Vec::new(),
+            symbol,
         }
-        impl<'a, 'b> Visitor<'a, '_> for ExtractIdents<'b> {
-            type Ident = Symbol;
+    }
+}
 
-            fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
-                match e.value {
-                    Expr::Ident(ref id) if id.name == *self.symbol => {
-                        self.result.push(e.span);
-                    }
-                    _ => walk_expr(self, e),
-                }
+impl<'a, 'b> Visitor<'a, '_> for ExtractIdents<'b> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+        match e.value {
+            Expr::Ident(ref id) if id.name == *self.symbol => {
+                self.result.push(e.span);
+            }
+            Expr::Record { ref exprs, .. } => {
+                // Fields without a value (`{ x }`) refer to the variable of the same name
+                let symbol = self.symbol;
+                let start = self.shorthands.len();
+                self.shorthands.extend(
+                    exprs
+                        .iter()
+                        .filter(|field| field.value.is_none() && field.name.value == *symbol)
+                        .map(|field| field.name.span),
+                );
+                self.result.extend_from_slice(&self.shorthands[start..]);
+                walk_expr(self, e)
             }
+            _ => walk_expr(self, e),
+        }
+    }
 
-            fn visit_pattern(&mut self, p: &'a SpannedPattern<Self::Ident>) {
-                match p.value {
-                    Pattern::As(ref id, ref pat) if id.value == *self.symbol => {
-                        self.result.push(p.span);
-                        walk_pattern(self, &pat.value);
-                    }
-                    Pattern::Ident(ref id) if id.name == *self.symbol => {
-                        self.result.push(p.span);
-                    }
-                    _ => walk_pattern(self, &p.value),
-                }
+    fn visit_pattern(&mut self, p: &'a SpannedPattern<Self::Ident>) {
+        match p.value {
+            Pattern::As(ref id, ref pat) if id.value == *self.symbol => {
+                self.result.push(id.span);
+                self.visit_pattern(pat);
+            }
+            Pattern::Ident(ref id) if id.name == *self.symbol => {
+                self.result.push(p.span);
+            }
+            Pattern::Record { ref fields, .. } => {
+                // Fields without a pattern (`{ x }`) bind a variable of the same name
+                let symbol = self.symbol;
+                let start = self.shorthands.len();
+                self.shorthands
+                    .extend(fields.iter().filter_map(|field| match field {
+                        PatternField::Value { name, value: None } if name.value == *symbol => {
+                            Some(name.span)
+                        }
+                        _ => None,
+                    }));
+                self.result.extend_from_slice(&self.shorthands[start..]);
+                walk_pattern(self, &p.value)
             }
+            _ => walk_pattern(self, &p.value),
         }
-        let mut visitor = ExtractIdents {
-            result: Vec::new(),
-            symbol,
-        };
-        visitor.visit_expr(expr);
-        (visitor.symbol.declared_name().to_string(), visitor.result)
-    })
+    }
+
+    fn visit_spanned_typed_ident(&mut self, id: &'a SpannedIdent<Self::Ident>) {
+        // Function arguments and infix operators
+        if id.value.name == *self.symbol {
+            self.result.push(id.span);
+        }
+    }
 }
 
-pub fn symbol<'a, 'ast>(
-    source_span: Span<BytePos>,
-    expr: &'a SpannedExpr<'ast, Symbol>,
-    pos: BytePos,
-) -> Result<&'a SymbolRef, ()> {
-    let extract = IdentAt;
-    completion(extract, source_span, expr, pos)
+/// A replacement of the text at `span` with `new_text`
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TextEdit {
+    pub span: Span<BytePos>,
+    pub new_text: String,
 }
 
-pub type SpCompletionSymbol<'a, 'ast> = Spanned<CompletionSymbol<'a, 'ast>, BytePos>;
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenameError {
+    /// There is no variable defined in the expression at the position
+    NoSymbol,
+    /// The new name is not a valid name for the variable
+    InvalidName(String),
+    /// After renaming, the variable reference at the span would refer to a different variable
+    Conflict(Span<BytePos>),
+}
 
-#[derive(Debug, PartialEq)]
-pub struct CompletionSymbol<'a, 'ast> {
-    pub name: &'a Symbol,
-    pub content: CompletionSymbolContent<'a, 'ast>,
-    pub children: Vec<SpCompletionSymbol<'a, 'ast>>,
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenameError::NoSymbol => write!(f, "No variable which can be renamed was found"),
+            RenameError::InvalidName(name) => write!(f, "`{}` is not a valid name", name),
+            RenameError::Conflict(span) => write!(
+                f,
+                "Renaming would change which variable the reference at {} refers to",
+                span
+            ),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum CompletionValueKind {
-    Parameter,
-    Binding,
+impl std::error::Error for RenameError {}
+
+/// A module which can be imported to bring an undefined name into scope
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportSuggestion {
+    /// The module which exports the name
+    pub module: String,
+    /// Inserts `let { name } = import! module` at the start of the source
+    pub edit: TextEdit,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum CompletionSymbolContent<'a, 'ast> {
-    Value {
-        typ: &'a ArcType,
-        kind: CompletionValueKind,
-        expr: Option<&'a SpannedExpr<'ast, Symbol>>,
-    },
-    Type {
-        typ: &'a AstType<'ast, Symbol>,
-    },
+const KEYWORDS: &[&str] = &[
+    "rec", "else", "forall", "if", "in", "let", "do", "seq", "match", "then", "type", "with",
+];
+
+/// Checks that `new_name` is an identifier (or operator) of the same kind as `old_name`
+fn is_valid_rename(old_name: &str, new_name: &str) -> bool {
+    let mut chars = new_name.chars();
+    match chars.next() {
+        Some(c) if ast::is_operator_char(c) => {
+            old_name.starts_with(ast::is_operator_char) && chars.all(ast::is_operator_char)
+        }
+        Some(c) if c.is_lowercase() || c == '_' => {
+            !old_name.starts_with(ast::is_operator_char)
+                && chars.all(|c| c.is_alphanumeric() || c == '_')
+                && !KEYWORDS.contains(&new_name)
+        }
+        _ => false,
+    }
 }
 
-pub fn all_symbols<'a, 'ast>(
+/// Finds the variable at `pos` and returns the edits which renames its definition and all its
+/// uses to `new_name`.
+///
+/// Fails if `new_name` is not a valid name or if the renamed variable would shadow, or be shadowed
+/// by, another variable at any of the places where either of them is referred to.
+pub fn rename<'ast>(
     source_span: Span<BytePos>,
-    expr: &'a SpannedExpr<'ast, Symbol>,
-) -> Vec<SpCompletionSymbol<'a, 'ast>> {
-    struct AllIdents<'r, 'a, 'ast> {
-        source_span: Span<BytePos>,
-        result: &'r mut Vec<Spanned<CompletionSymbol<'a, 'ast>, BytePos>>,
+    expr: &SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+    new_name: &str,
+) -> Result<Vec<TextEdit>, RenameError> {
+    let symbol = completion(IdentAt, source_span, expr, pos).map_err(|_| RenameError::NoSymbol)?;
+    let old_name = symbol.declared_name();
+    // Constructors and types are defined in type bindings which we do not rename
+    if symbol.is_global() || old_name.starts_with(char::is_uppercase) {
+        return Err(RenameError::NoSymbol);
+    }
+    if !is_valid_rename(old_name, new_name) {
+        return Err(RenameError::InvalidName(new_name.to_string()));
     }
 
-    impl<'a, 'ast> Visitor<'a, 'ast> for AllIdents<'_, 'a, 'ast> {
-        type Ident = Symbol;
+    let is_macro_expanded =
+        |span: Span<BytePos>| span.start().0 == 0 || !source_span.contains(span);
 
-        fn visit_ast_type(&mut self, typ: &'a ast::AstType<'ast, Self::Ident>) {
-            match &**typ {
-                Type::Record(_) | Type::Variant(_) | Type::Effect(_) => {
-                    for field in base::types::row_iter(typ) {
-                        let mut children = Vec::new();
-                        idents_of(self.source_span, &field.typ, &mut children);
-                        self.result.push(pos::spanned(
-                            field.name.span,
-                            CompletionSymbol {
-                                name: &field.name.value,
-                                content: CompletionSymbolContent::Type { typ: &field.typ },
-                                children,
-                            },
-                        ));
-                    }
-                }
-                _ => ast::walk_ast_type(self, typ),
-            }
+    let mut visitor = ExtractIdents::new(symbol);
+    visitor.visit_expr(expr);
+    if visitor.result.is_empty() || visitor.result.iter().any(|&span| is_macro_expanded(span)) {
+        return Err(RenameError::NoSymbol);
+    }
+
+    let mut references = References {
+        names: [old_name, new_name],
+        result: Vec::new(),
+    };
+    references.visit_expr(expr);
+    // The scopes of the references mostly contain the same symbols so they are shared through ids
+    let mut symbols = Symbols::new();
+    for (reference, span) in references.result {
+        if is_macro_expanded(span) {
+            continue;
+        }
+        let mut scope = ScopeNames {
+            symbols: &mut symbols,
+            names: Vec::new(),
+        };
+        if complete_at(&mut scope, source_span, expr, span.start()).is_err() {
+            continue;
+        }
+        let innermost = |name: &SymbolRef| {
+            let id = scope.symbols.get_id(name)?;
+            scope.names.iter().rposition(|&s| s == id)
+        };
+        let target = match innermost(symbol) {
+            Some(target) => target,
+            None if reference == symbol => return Err(RenameError::NoSymbol),
+            None => continue,
+        };
+        let conflict = if reference == symbol {
+            // A variable with the new name would shadow the renamed variable
+            scope.names[target + 1..]
+                .iter()
+                .any(|&id| scope.symbols.resolve(id).declared_name() == new_name)
+        } else {
+            // The renamed variable would shadow the variable referred to
+            innermost(reference).map_or(true, |index| index < target)
+        };
+        if conflict {
+            return Err(RenameError::Conflict(span));
         }
+    }
 
-        fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
-            if self.source_span.contains(e.span) {
-                let source_span = self.source_span;
-                match &e.value {
-                    Expr::TypeBindings(binds, expr) => {
-                        self.result.extend(binds.iter().map(|bind| {
-                            let mut children = Vec::new();
-                            idents_of(source_span, &bind.alias, &mut children);
+    let mut edits: Vec<_> = visitor
+        .result
+        .iter()
+        .map(|&span| TextEdit {
+            span,
+            new_text: if visitor.shorthands.contains(&span) {
+                // The field name must be kept, so `{ x }` becomes `{ x = new_name }`
+                format!("{} = {}", old_name, new_name)
+            } else {
+                new_name.to_string()
+            },
+        })
+        .collect();
+    edits.sort();
+    edits.dedup();
+    Ok(edits)
+}
 
-                            pos::spanned(
-                                bind.name.span,
-                                CompletionSymbol {
-                                    name: bind
-                                        .finalized_alias
-                                        .as_ref()
-                                        .map(|alias| &alias.name)
-                                        .unwrap_or(&bind.name.value),
-                                    content: CompletionSymbolContent::Type {
-                                        typ: &bind.alias.unresolved_type(),
-                                    },
-                                    children,
-                                },
-                            )
-                        }));
+/// Finds the variable references whose name is one of `names`
+struct References<'a, 'b> {
+    names: [&'b str; 2],
+    result: Vec<(&'a SymbolRef, Span<BytePos>)>,
+}
 
-                        self.visit_expr(expr);
-                    }
-                    Expr::LetBindings(binds, expr) => {
-                        for bind in binds {
-                            let mut children = Vec::new();
+impl<'a> Visitor<'a, '_> for References<'a, '_> {
+    type Ident = Symbol;
 
-                            children.extend(bind.args.iter().map(|arg| {
-                                pos::spanned(
-                                    arg.name.span,
-                                    CompletionSymbol {
-                                        name: &arg.name.value.name,
-                                        content: CompletionSymbolContent::Value {
-                                            typ: &arg.name.value.typ,
-                                            kind: CompletionValueKind::Parameter,
-                                            expr: None,
-                                        },
-                                        children: Vec::new(),
-                                    },
-                                )
-                            }));
+    fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+        let names = self.names;
+        match e.value {
+            Expr::Ident(ref id) if names.contains(&id.name.declared_name()) => {
+                self.result.push((&id.name, e.span));
+            }
+            Expr::Infix { ref op, .. } if names.contains(&op.value.name.declared_name()) => {
+                self.result.push((&op.value.name, op.span));
+            }
+            Expr::Record { ref exprs, .. } => {
+                self.result.extend(
+                    exprs
+                        .iter()
+                        .filter(|field| {
+                            field.value.is_none()
+                                && names.contains(&field.name.value.declared_name())
+                        })
+                        .map(|field| (&*field.name.value, field.name.span)),
+                );
+            }
+            _ => (),
+        }
+        walk_expr(self, e)
+    }
+}
 
-                            idents_of(source_span, &bind.expr, &mut children);
+/// Collects the variables which are in scope, ordered from the outermost to the innermost binding
+struct ScopeNames<'s> {
+    symbols: &'s mut Symbols,
+    names: Vec<SymbolId>,
+}
 
-                            match &bind.name.value {
-                                Pattern::Ident(id) => {
-                                    self.result.push(pos::spanned(
+impl ScopeNames<'_> {
+    fn push(&mut self, name: &Symbol) {
+        let id = self.symbols.id(name);
+        self.names.push(id);
+    }
+}
+
+impl OnFound for ScopeNames<'_> {
+    fn on_ident(&mut self, ident: &TypedIdent) {
+        self.push(&ident.name);
+    }
+
+    fn on_pattern(&mut self, pattern: &SpannedPattern<Symbol>) {
+        match &pattern.value {
+            Pattern::As(id, pat) => {
+                self.push(&id.value);
+                self.on_pattern(pat);
+            }
+            Pattern::Ident(id) => self.push(&id.name),
+            Pattern::Record { fields, .. } => {
+                for field in &**fields {
+                    match field {
+                        PatternField::Value {
+                            value: Some(value), ..
+                        } => self.on_pattern(value),
+                        PatternField::Value { name, value: None } => self.push(&name.value),
+                        PatternField::Type { .. } => (),
+                    }
+                }
+            }
+            Pattern::Tuple { elems: args, .. } | Pattern::Constructor(_, args) => {
+                for arg in &**args {
+                    self.on_pattern(arg);
+                }
+            }
+            Pattern::Literal(_) | Pattern::Error => (),
+        }
+    }
+
+    fn on_alias(&mut self, alias: &AliasData<Symbol, ArcType>) {
+        let aliased_type = alias.unresolved_type().remove_forall();
+        if let Type::Variant(ref row) = **aliased_type {
+            for field in row.row_iter() {
+                self.push(&field.name);
+            }
+        }
+    }
+}
+
+/// The maximum number of arms that `missing_match_arms` adds
+const MAX_MISSING_ARMS: usize = 32;
+
+/// Finds the `match` expression at `pos` and returns the edit which adds a `| pattern -> ` arm at
+/// the end of it for each pattern that the existing arms do not cover, as reported by the
+/// exhaustiveness checker.
+///
+/// Returns `None` if there is no `match` at `pos` or if the existing arms are exhaustive.
+pub fn missing_match_arms<'ast>(
+    env: &dyn TypeEnv<Type = ArcType>,
+    source_span: Span<BytePos>,
+    expr: &SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+) -> Option<TextEdit> {
+    let found = complete_at((), source_span, expr, pos).ok()?;
+    let innermost_match = found
+        .enclosing_matches
+        .iter()
+        .chain(&found.match_)
+        .rev()
+        .find_map(|enclosing_match| match *enclosing_match {
+            Match::Expr(match_expr) => match match_expr.value {
+                Expr::Match(ref scrutinee, ref alts) => Some((match_expr, scrutinee, alts)),
+                _ => None,
+            },
+            _ => None,
+        });
+    let (match_expr, scrutinee, alts) = innermost_match?;
+
+    let missing = exhaustiveness::missing_patterns(env, scrutinee, alts, MAX_MISSING_ARMS);
+    if missing.is_empty() {
+        return None;
+    }
+
+    let mut new_text = String::new();
+    for pattern in missing {
+        new_text.push_str("\n| ");
+        new_text.push_str(&pattern);
+        new_text.push_str(" -> ");
+    }
+
+    let end = match_expr.span.end();
+    Some(TextEdit {
+        span: Span::new(end, end),
+        new_text,
+    })
+}
+
+/// Where the identifier found by `definition_at` is defined
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Definition {
+    /// The span of the binding which defines the identifier
+    Span(Span<BytePos>),
+    /// The identifier refers to the module with this name, such as `std.map`
+    Module(String),
+}
+
+/// Resolves the identifier at `pos` to the binding which defines it, which may be a let binding, a
+/// function argument, a pattern, a type binding, a variant constructor or an imported module.
+pub fn definition_at<'ast>(
+    source_span: Span<BytePos>,
+    expr: &SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+) -> Result<Definition, ()> {
+    let symbol = completion(IdentAt, source_span, expr, pos)?;
+    if symbol.is_global() {
+        return Ok(Definition::Module(symbol.definition_name().to_string()));
+    }
+
+    let mut visitor = FindDefinition {
+        symbol,
+        result: None,
+    };
+    visitor.visit_expr(expr);
+    match visitor.result {
+        // Bindings which were introduced by macros (such as the implicit prelude) do not exist in
+        // the source
+        Some(span) if span.start().0 != 0 && source_span.contains(span) => {
+            Ok(Definition::Span(span))
+        }
+        _ => Err(()),
+    }
+}
+
+/// Finds the binding which defines `symbol`. Bindings are always visited before the expressions
+/// they are in scope for so the search stops at the first binding found.
+struct FindDefinition<'b> {
+    symbol: &'b SymbolRef,
+    result: Option<Span<BytePos>>,
+}
+
+impl<'a> Visitor<'a, '_> for FindDefinition<'_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+        if self.result.is_some() {
+            return;
+        }
+        match e.value {
+            Expr::Infix {
+                ref lhs, ref rhs, ..
+            } => {
+                // The operator refers to a binding instead of defining one
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            Expr::TypeBindings(ref binds, _) => {
+                // Types are referred to by the name of their alias which includes the module
+                let symbol = self.symbol;
+                match binds
+                    .iter()
+                    .find(|bind| bind.name.value == *symbol || bind.alias.value.name == *symbol)
+                {
+                    Some(bind) => self.result = Some(bind.name.span),
+                    None => walk_expr(self, e),
+                }
+            }
+            _ => walk_expr(self, e),
+        }
+    }
+
+    fn visit_pattern(&mut self, p: &'a SpannedPattern<Self::Ident>) {
+        if self.result.is_some() {
+            return;
+        }
+        match p.value {
+            Pattern::As(ref id, _) if id.value == *self.symbol => self.result = Some(id.span),
+            Pattern::Ident(ref id) if id.name == *self.symbol => self.result = Some(p.span),
+            Pattern::Record { ref fields, .. } => {
+                // Fields without a pattern (`{ x }`, `{ Type }`) bind a name of their own
+                let symbol = self.symbol;
+                self.result = fields.iter().find_map(|field| match field {
+                    PatternField::Value { name, value: None } | PatternField::Type { name }
+                        if name.value == *symbol =>
+                    {
+                        Some(name.span)
+                    }
+                    _ => None,
+                });
+                if self.result.is_none() {
+                    walk_pattern(self, &p.value)
+                }
+            }
+            _ => walk_pattern(self, &p.value),
+        }
+    }
+
+    fn visit_spanned_typed_ident(&mut self, id: &'a SpannedIdent<Self::Ident>) {
+        // Function arguments
+        if self.result.is_none() && id.value.name == *self.symbol {
+            self.result = Some(id.span);
+        }
+    }
+
+    fn visit_ast_type(&mut self, typ: &'a AstType<'_, Self::Ident>) {
+        if self.result.is_some() {
+            return;
+        }
+        match **typ {
+            // Variant constructors
+            Type::ExtendRow { ref fields, .. } => {
+                match fields.iter().find(|field| field.name.value == *self.symbol) {
+                    Some(field) => self.result = Some(field.name.span),
+                    None => ast::walk_ast_type(self, typ),
+                }
+            }
+            _ => ast::walk_ast_type(self, typ),
+        }
+    }
+}
+
+/// Where the binding that a symbol refers to comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OriginKind {
+    /// A binding defined in the expression itself, such as a let binding or a function argument
+    Local,
+    /// A name brought into scope by the implicit prelude
+    Prelude,
+    /// A module, or a field of a module, brought into scope with `import!`
+    Import,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolOrigin {
+    pub kind: OriginKind,
+    /// The module which defines the symbol, `None` for local bindings
+    pub module: Option<String>,
+    /// The name that `module` exports the symbol as, `None` if the symbol refers to the module
+    /// itself or to a local binding
+    pub exported_name: Option<String>,
+    /// The span of the binding which brings the symbol into scope. Missing for the implicit
+    /// prelude and for modules which are used directly, as in `(import! std.list).map`.
+    pub definition_span: Option<Span<BytePos>>,
+}
+
+impl SymbolOrigin {
+    /// The fully qualified name of an imported symbol, such as `std.list.map`
+    pub fn qualified_name(&self) -> Option<String> {
+        let module = self.module.as_ref()?;
+        Some(match &self.exported_name {
+            Some(name) => format!("{}.{}", module, name),
+            None => module.clone(),
+        })
+    }
+}
+
+/// Resolves the identifier at `pos` to the module it was imported from, or to the binding which
+/// defines it if it is a local binding.
+///
+/// Fields of imported modules are followed through let bindings and record patterns
+/// (`let { map } = import! std.list`, `let list = import! std.list in list.map`). If `env`
+/// contains the metadata of the imported module, fields which it re-exports are resolved to the
+/// module which defines them.
+pub fn resolve<'ast, T>(
+    env: &T,
+    source_span: Span<BytePos>,
+    expr: &SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+) -> Result<SymbolOrigin, ()>
+where
+    T: ?Sized + MetadataEnv,
+{
+    let found = complete_at((), source_span, expr, pos)?;
+
+    let mut bindings = ImportBindings::default();
+    bindings.visit_expr(expr);
+
+    // `module.field` refers to the field of the module that `module` is bound to
+    let (symbol, field) = match (&found.match_, found.enclosing_match()) {
+        (
+            Some(Match::Ident(_, field, _)),
+            Match::Expr(Spanned {
+                value: Expr::Projection(record, _, _),
+                ..
+            }),
+        ) => match &record.value {
+            Expr::Ident(id) => (&*id.name, Some(field.declared_name().to_string())),
+            _ => match imported_module(record) {
+                Some(module) => {
+                    let field = field.declared_name().to_string();
+                    return Ok(import_origin(env, module, Some(field), None));
+                }
+                None => return Err(()),
+            },
+        },
+        _ => (IdentAt.extract(&found)?, None),
+    };
+
+    if symbol.is_global() {
+        let module = symbol.definition_name().to_string();
+        return Ok(import_origin(env, module, field, None));
+    }
+
+    let binding = bindings.0.get(symbol);
+    if let Some((module, exported_name)) = bindings.imported_from(symbol) {
+        let exported_name = match (exported_name, field) {
+            (Some(_), Some(_)) => return Err(()),
+            (exported_name, field) => exported_name.or(field),
+        };
+        let span = binding.map(|binding| binding.span);
+        let mut origin = import_origin(env, module, exported_name, span);
+        // Names bound by the implicit prelude are bound in a generated source
+        if span.map_or(false, |span| !source_span.contains(span)) {
+            origin.kind = OriginKind::Prelude;
+            origin.definition_span = None;
+        }
+        return Ok(origin);
+    }
+
+    if field.is_some() {
+        return Err(());
+    }
+    let mut visitor = FindDefinition {
+        symbol,
+        result: None,
+    };
+    visitor.visit_expr(expr);
+    Ok(SymbolOrigin {
+        kind: OriginKind::Local,
+        module: None,
+        exported_name: None,
+        definition_span: visitor
+            .result
+            .filter(|span| span.start().0 != 0 && source_span.contains(*span)),
+    })
+}
+
+fn import_origin<T>(
+    env: &T,
+    module: String,
+    exported_name: Option<String>,
+    definition_span: Option<Span<BytePos>>,
+) -> SymbolOrigin
+where
+    T: ?Sized + MetadataEnv,
+{
+    // The metadata of a field records the symbol it was originally defined as
+    let definition = exported_name.as_ref().and_then(|name| {
+        let metadata = env.get_metadata(&Symbol::from(format!("@{}", module)))?;
+        let definition = metadata.module.get(name)?.definition.clone()?;
+        let definition = Name::new(definition.definition_name());
+        if definition.module().as_str().is_empty() {
+            None
+        } else {
+            Some((
+                definition.module().to_string(),
+                definition.declared_name().to_string(),
+            ))
+        }
+    });
+    let (module, exported_name) = match definition {
+        Some((module, name)) => (module, Some(name)),
+        None => (module, exported_name),
+    };
+    SymbolOrigin {
+        kind: OriginKind::Import,
+        module: Some(module),
+        exported_name,
+        definition_span,
+    }
+}
+
+/// Returns the module that `expr` refers to if it is an `import!`
+fn imported_module(expr: &SpannedExpr<Symbol>) -> Option<String> {
+    match &expr.value {
+        Expr::Ident(id) if id.name.is_global() => Some(id.name.definition_name().to_string()),
+        Expr::MacroExpansion { replacement, .. } => imported_module(replacement),
+        _ => None,
+    }
+}
+
+enum BindingSource {
+    /// The binding is bound to a module
+    Module(String),
+    /// The binding is bound to another binding
+    Alias(Symbol),
+}
+
+struct ImportBinding {
+    span: Span<BytePos>,
+    source: BindingSource,
+    /// The field which is bound, if the binding is part of a record pattern
+    field: Option<String>,
+}
+
+/// Collects the let bindings which are bound to modules or to other bindings
+#[derive(Default)]
+struct ImportBindings(FnvMap<Symbol, ImportBinding>);
+
+impl ImportBindings {
+    /// Returns the module that `symbol` is imported from and the field of the module it is bound
+    /// to
+    fn imported_from(&self, symbol: &SymbolRef) -> Option<(String, Option<String>)> {
+        let binding = self.0.get(symbol)?;
+        match &binding.source {
+            BindingSource::Module(module) => Some((module.clone(), binding.field.clone())),
+            BindingSource::Alias(alias) => match self.imported_from(alias)? {
+                (module, None) => Some((module, binding.field.clone())),
+                (module, Some(field)) if binding.field.is_none() => Some((module, Some(field))),
+                // Fields of nested records are not followed
+                _ => None,
+            },
+        }
+    }
+
+    fn add_pattern(&mut self, pattern: &SpannedPattern<Symbol>, source: &BindingSource) {
+        let mut add = |symbol: &Symbol, span, field: Option<&Symbol>| {
+            let source = match source {
+                BindingSource::Module(module) => BindingSource::Module(module.clone()),
+                BindingSource::Alias(alias) => BindingSource::Alias(alias.clone()),
+            };
+            self.0.insert(
+                symbol.clone(),
+                ImportBinding {
+                    span,
+                    source,
+                    field: field.map(|field| field.declared_name().to_string()),
+                },
+            );
+        };
+        match &pattern.value {
+            Pattern::Ident(id) => add(&id.name, pattern.span, None),
+            Pattern::As(id, _) => add(&id.value, id.span, None),
+            Pattern::Record { fields, .. } => {
+                for field in &**fields {
+                    match field {
+                        PatternField::Type { name } | PatternField::Value { name, value: None } => {
+                            add(&name.value, name.span, Some(&name.value))
+                        }
+                        PatternField::Value {
+                            name,
+                            value:
+                                Some(Spanned {
+                                    value: Pattern::Ident(id),
+                                    span,
+                                }),
+                        } => add(&id.name, *span, Some(&name.value)),
+                        PatternField::Value { .. } => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl<'a> Visitor<'a, '_> for ImportBindings {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+        if let Expr::LetBindings(binds, _) = &e.value {
+            for bind in binds.iter().filter(|bind| bind.args.is_empty()) {
+                let source = match imported_module(&bind.expr) {
+                    Some(module) => BindingSource::Module(module),
+                    None => match &bind.expr.value {
+                        Expr::Ident(id) => BindingSource::Alias(id.name.clone()),
+                        _ => continue,
+                    },
+                };
+                self.add_pattern(&bind.name, &source);
+            }
+        }
+        walk_expr(self, e)
+    }
+}
+
+/// A typechecked module which `find_all_references` searches
+#[derive(Clone, Copy, Debug)]
+pub struct ModuleExpr<'a, 'ast> {
+    /// The name that the module is imported with, such as `std.map`
+    pub name: &'a str,
+    pub expr: &'a SpannedExpr<'ast, Symbol>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Reference {
+    /// The name of the module containing the reference
+    pub module: String,
+    pub span: Span<BytePos>,
+}
+
+/// Finds all references to `symbol`, which is defined in the module named `module`, in `modules`.
+///
+/// Besides the references inside `module` itself this finds the uses of the fields that `module`
+/// exports `symbol` as, whether they are accessed by projecting out of `import!`ed module
+/// (`(import! module).symbol`) or by binding them with a record pattern (`let { symbol } = import!
+/// module`). Fields which are re-exported are followed into the modules importing them.
+pub fn find_all_references<'a>(
+    modules: &[ModuleExpr<'a, '_>],
+    module: &'a str,
+    symbol: &'a SymbolRef,
+) -> Vec<Reference> {
+    let mut result = Vec::new();
+
+    let mut visited = FnvSet::default();
+    let mut work = vec![(module, symbol)];
+    while let Some((module_name, symbol)) = work.pop() {
+        if !visited.insert((module_name, symbol)) {
+            continue;
+        }
+        let module = match modules.iter().find(|module| module.name == module_name) {
+            Some(module) => module,
+            None => continue,
+        };
+
+        let mut visitor = ExtractIdents::new(symbol);
+        visitor.visit_expr(module.expr);
+        result.extend(visitor.result.into_iter().map(|span| Reference {
+            module: module_name.to_string(),
+            span,
+        }));
+
+        let exported_as = module_exports(module.expr, symbol);
+        if exported_as.is_empty() {
+            continue;
+        }
+        for importer in modules {
+            let mut visitor = ImportedFieldReferences {
+                module: module_name,
+                fields: &exported_as,
+                module_aliases: FnvSet::default(),
+                result: Vec::new(),
+                bound: Vec::new(),
+            };
+            visitor.visit_expr(importer.expr);
+            result.extend(visitor.result.into_iter().map(|span| Reference {
+                module: importer.name.to_string(),
+                span,
+            }));
+            work.extend(
+                visitor
+                    .bound
+                    .into_iter()
+                    .map(|symbol| (importer.name, symbol)),
+            );
+        }
+    }
+
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// Returns the names of the fields that `symbol` is exported as from the module `expr`
+fn module_exports<'a>(mut expr: &'a SpannedExpr<'_, Symbol>, symbol: &SymbolRef) -> Vec<&'a str> {
+    loop {
+        expr = match expr.value {
+            Expr::LetBindings(_, ref body) | Expr::TypeBindings(_, ref body) => body,
+            Expr::Annotated(ref expr, _) => expr,
+            Expr::MacroExpansion {
+                ref replacement, ..
+            } => replacement,
+            Expr::Record { ref exprs, .. } => {
+                return exprs
+                    .iter()
+                    .filter(|field| match field.value {
+                        Some(ref value) => match value.value {
+                            Expr::Ident(ref id) => id.name == *symbol,
+                            _ => false,
+                        },
+                        None => field.name.value == *symbol,
+                    })
+                    .map(|field| field.name.value.declared_name())
+                    .collect();
+            }
+            _ => return Vec::new(),
+        };
+    }
+}
+
+/// Finds the uses of `fields` of the module named `module`
+struct ImportedFieldReferences<'a, 'b> {
+    module: &'b str,
+    fields: &'b [&'b str],
+    /// Local variables which the module has been bound to
+    module_aliases: FnvSet<Symbol>,
+    result: Vec<Span<BytePos>>,
+    /// Local variables which the fields have been bound to with the same name
+    bound: Vec<&'a SymbolRef>,
+}
+
+impl ImportedFieldReferences<'_, '_> {
+    fn is_module(&self, expr: &SpannedExpr<'_, Symbol>) -> bool {
+        match expr.value {
+            Expr::Ident(ref id) => {
+                (id.name.is_global() && id.name.as_pretty_str() == self.module)
+                    || self.module_aliases.contains(&id.name)
+            }
+            Expr::Annotated(ref expr, _) => self.is_module(expr),
+            Expr::MacroExpansion {
+                ref replacement, ..
+            } => self.is_module(replacement),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Visitor<'a, '_> for ImportedFieldReferences<'a, '_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+        match e.value {
+            Expr::Projection(ref expr, ref field, _)
+                if self.fields.contains(&field.declared_name()) && self.is_module(expr) =>
+            {
+                let len = field.declared_name().len();
+                self.result.push(Span::new(
+                    e.span.end() - ByteOffset::from(len as i64),
+                    e.span.end(),
+                ));
+            }
+            Expr::LetBindings(ref binds, _) => {
+                for bind in binds.iter() {
+                    if !self.is_module(&bind.expr) {
+                        continue;
+                    }
+                    match bind.name.value {
+                        Pattern::Ident(ref id) => {
+                            self.module_aliases.insert(id.name.clone());
+                        }
+                        Pattern::Record { ref fields, .. } => {
+                            for field in fields.iter() {
+                                if let PatternField::Value { name, value } = field {
+                                    if self.fields.contains(&name.value.declared_name()) {
+                                        self.result.push(name.span);
+                                        if value.is_none() {
+                                            self.bound.push(&name.value);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                walk_expr(self, e)
+            }
+            _ => walk_expr(self, e),
+        }
+    }
+}
+
+pub fn symbol<'a, 'ast>(
+    source_span: Span<BytePos>,
+    expr: &'a SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+) -> Result<&'a SymbolRef, ()> {
+    let extract = IdentAt;
+    completion(extract, source_span, expr, pos)
+}
+
+pub type SpCompletionSymbol<'a, 'ast> = Spanned<CompletionSymbol<'a, 'ast>, BytePos>;
+
+/// A symbol defined in a module. The span of the `Spanned` wrapping the symbol is the span of its
+/// name.
+#[derive(Debug, PartialEq)]
+pub struct CompletionSymbol<'a, 'ast> {
+    pub name: &'a Symbol,
+    /// The span of the whole definition, including the body of a binding or the type of a field
+    pub range: Span<BytePos>,
+    pub content: CompletionSymbolContent<'a, 'ast>,
+    /// The symbols which are defined inside this symbol's definition
+    pub children: Vec<SpCompletionSymbol<'a, 'ast>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompletionValueKind {
+    Parameter,
+    Binding,
+    /// A field of a record expression (`{ field = ... }`)
+    Field,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompletionTypeKind {
+    /// A type binding (`type Test = ...`)
+    Alias,
+    /// A field of a record type
+    Field,
+    /// A constructor of a variant type
+    Constructor,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompletionSymbolContent<'a, 'ast> {
+    Value {
+        typ: &'a ArcType,
+        kind: CompletionValueKind,
+        expr: Option<&'a SpannedExpr<'ast, Symbol>>,
+    },
+    Type {
+        typ: &'a AstType<'ast, Symbol>,
+        kind: CompletionTypeKind,
+    },
+}
+
+pub fn all_symbols<'a, 'ast>(
+    source_span: Span<BytePos>,
+    expr: &'a SpannedExpr<'ast, Symbol>,
+) -> Vec<SpCompletionSymbol<'a, 'ast>> {
+    struct AllIdents<'r, 'a, 'ast> {
+        source_span: Span<BytePos>,
+        result: &'r mut Vec<Spanned<CompletionSymbol<'a, 'ast>, BytePos>>,
+    }
+
+    impl<'a, 'ast> Visitor<'a, 'ast> for AllIdents<'_, 'a, 'ast> {
+        type Ident = Symbol;
+
+        fn visit_ast_type(&mut self, typ: &'a ast::AstType<'ast, Self::Ident>) {
+            match &**typ {
+                Type::Record(_) | Type::Variant(_) | Type::Effect(_) => {
+                    for field in base::types::row_iter(typ) {
+                        let mut children = Vec::new();
+                        idents_of(self.source_span, &field.typ, &mut children);
+
+                        let range = Span::new(
+                            field.name.span.start(),
+                            written_end(field.name.span, &field.typ),
+                        );
+                        let kind = match **typ {
+                            Type::Variant(_) => CompletionTypeKind::Constructor,
+                            _ => CompletionTypeKind::Field,
+                        };
+                        self.result.push(pos::spanned(
+                            field.name.span,
+                            CompletionSymbol {
+                                name: &field.name.value,
+                                range,
+                                content: CompletionSymbolContent::Type {
+                                    typ: &field.typ,
+                                    kind,
+                                },
+                                children,
+                            },
+                        ));
+                    }
+                }
+                _ => ast::walk_ast_type(self, typ),
+            }
+        }
+
+        fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
+            if self.source_span.contains(e.span) {
+                let source_span = self.source_span;
+                match &e.value {
+                    Expr::TypeBindings(binds, expr) => {
+                        self.result.extend(binds.iter().map(|bind| {
+                            let mut children = Vec::new();
+                            idents_of(source_span, &bind.alias, &mut children);
+
+                            pos::spanned(
+                                bind.name.span,
+                                CompletionSymbol {
+                                    name: bind
+                                        .finalized_alias
+                                        .as_ref()
+                                        .map(|alias| &alias.name)
+                                        .unwrap_or(&bind.name.value),
+                                    range: bind.span(),
+                                    content: CompletionSymbolContent::Type {
+                                        typ: &bind.alias.unresolved_type(),
+                                        kind: CompletionTypeKind::Alias,
+                                    },
+                                    children,
+                                },
+                            )
+                        }));
+
+                        self.visit_expr(expr);
+                    }
+                    Expr::LetBindings(binds, expr) => {
+                        for bind in binds {
+                            let mut children = Vec::new();
+
+                            children.extend(bind.args.iter().map(|arg| {
+                                pos::spanned(
+                                    arg.name.span,
+                                    CompletionSymbol {
+                                        name: &arg.name.value.name,
+                                        range: arg.name.span,
+                                        content: CompletionSymbolContent::Value {
+                                            typ: &arg.name.value.typ,
+                                            kind: CompletionValueKind::Parameter,
+                                            expr: None,
+                                        },
+                                        children: Vec::new(),
+                                    },
+                                )
+                            }));
+
+                            idents_of(source_span, &bind.expr, &mut children);
+
+                            match &bind.name.value {
+                                Pattern::Ident(id) => {
+                                    self.result.push(pos::spanned(
                                         bind.name.span,
                                         CompletionSymbol {
                                             name: &id.name,
+                                            range: Span::new(
+                                                bind.name.span.start(),
+                                                bind.expr.span.end(),
+                                            ),
                                             content: CompletionSymbolContent::Value {
                                                 typ: &id.typ,
                                                 kind: CompletionValueKind::Binding,
@@ -1104,12 +2247,64 @@ pub fn all_symbols<'a, 'ast>(
                                         },
                                     ));
                                 }
-                                _ => self.result.extend(children),
+                                // The fields of a record which is unpacked are skipped along
+                                // with the values they are unpacked into
+                                _ => {
+                                    for child in children {
+                                        match child.value.content {
+                                            CompletionSymbolContent::Value {
+                                                kind: CompletionValueKind::Field,
+                                                ..
+                                            } => self.result.extend(child.value.children),
+                                            _ => self.result.push(child),
+                                        }
+                                    }
+                                }
                             }
                         }
 
                         self.visit_expr(expr);
                     }
+                    Expr::Record {
+                        typ, exprs, base, ..
+                    } => {
+                        for field in &**exprs {
+                            let expr = match &field.value {
+                                // Punned fields and fields added by the typechecker refer to
+                                // symbols defined elsewhere
+                                Some(expr) if expr.span.start() >= field.name.span.end() => expr,
+                                _ => continue,
+                            };
+                            let field_type = typ
+                                .row_iter()
+                                .find(|f| f.name.name_eq(&field.name.value))
+                                .map(|f| &f.typ);
+
+                            let mut children = Vec::new();
+                            idents_of(source_span, expr, &mut children);
+
+                            match field_type {
+                                Some(field_type) => self.result.push(pos::spanned(
+                                    field.name.span,
+                                    CompletionSymbol {
+                                        name: &field.name.value,
+                                        range: Span::new(field.name.span.start(), expr.span.end()),
+                                        content: CompletionSymbolContent::Value {
+                                            typ: field_type,
+                                            kind: CompletionValueKind::Field,
+                                            expr: Some(expr),
+                                        },
+                                        children,
+                                    },
+                                )),
+                                None => self.result.extend(children),
+                            }
+                        }
+
+                        if let Some(base) = base {
+                            self.visit_expr(base);
+                        }
+                    }
                     _ => walk_expr(self, e),
                 }
             } else {
@@ -1118,6 +2313,27 @@ pub fn all_symbols<'a, 'ast>(
         }
     }
 
+    /// Returns the end of the parts of `typ` which are written after `name`. The types of variant
+    /// constructors are created by the compiler, only their arguments appear in the source.
+    fn written_end(name: Span<BytePos>, mut typ: &AstType<'_, Symbol>) -> BytePos {
+        let is_written = |typ: &AstType<'_, Symbol>| typ.span().start() > name.start();
+        let mut end = name.end();
+        loop {
+            if is_written(typ) {
+                return end.max(typ.span().end());
+            }
+            match **typ {
+                Type::Function(_, ref arg, ref ret) => {
+                    if is_written(arg) {
+                        end = end.max(arg.span().end());
+                    }
+                    typ = ret;
+                }
+                _ => return end,
+            }
+        }
+    }
+
     trait Visit<'a, 'ast> {
         type Ident: 'a + 'ast;
         fn visit(&'a self, visitor: &mut impl Visitor<'a, 'ast, Ident = Self::Ident>);
@@ -1192,11 +2408,132 @@ where
     SuggestionQuery::default().suggest(env, source_span, expr, pos)
 }
 
+/// Finds the typed hole (`_` used as an expression) at `pos` and suggests the bindings in scope
+/// whose types can be used in its place.
+///
+/// `expr` must have been typechecked so that the hole has been given the type that is expected
+/// of it. The suggestions are ordered from the closest match to the loosest: bindings whose type
+/// is exactly the type of the hole come first, then bindings whose type unifies with it and
+/// last the polymorphic bindings which only match after their type has been instantiated.
+pub fn suggest_for_hole<'ast, T>(
+    env: &T,
+    source_span: Span<BytePos>,
+    expr: &SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+) -> Vec<Suggestion>
+where
+    T: TypecheckEnv<Type = ArcType>,
+{
+    let mut suggest = Suggest::new(env);
+    let found = match complete_at(&mut suggest, source_span, expr, pos) {
+        Ok(found) => found,
+        Err(()) => return vec![],
+    };
+    let hole_type = match found.match_ {
+        Some(Match::Expr(Spanned {
+            value: Expr::Ident(id),
+            ..
+        })) if id.name.declared_name() == "_" => &id.typ,
+        _ => return vec![],
+    };
+    // Any part of the type that the typechecker could not infer may be filled by anything
+    let hole_type = walk_move_type(hole_type.clone(), &mut |typ: &ArcType| match **typ {
+        Type::Variable(_) => Some(Type::hole()),
+        _ => None,
+    });
+
+    let mut result: Vec<_> = suggest
+        .stack
+        .iter()
+        .filter(|&(name, _)| name.declared_name() != "_")
+        .filter_map(|(name, typ)| {
+            let score = if *typ == hole_type {
+                3
+            } else if check::check_signature(env, &hole_type, typ) {
+                match **typ {
+                    Type::Forall(..) => 1,
+                    _ => 2,
+                }
+            } else {
+                return None;
+            };
+            Some(Suggestion {
+                name: name.declared_name().into(),
+                typ: Either::Right(typ.clone()),
+                doc: None,
+                metadata: None,
+                score,
+            })
+        })
+        .collect();
+    result.sort_by(|l, r| r.score.cmp(&l.score).then_with(|| l.name.cmp(&r.name)));
+    result
+}
+
+/// How the text that has been typed is matched against the names which may be suggested
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Suggests the names which start with the typed text
+    Prefix,
+    /// Suggests the names which contain the characters of the typed text in order, ignoring case
+    /// (`fmp` matches `filter_map`). The suggestions are sorted by their score.
+    Fuzzy,
+}
+
+/// Scores how well `name` matches `query`, returning `None` if it does not match at all.
+///
+/// Each matched character is worth a point, with bonuses for characters which directly follow the
+/// previous match or which start a word (so `fm` is a better match for `filter_map` than `fi`).
+fn match_score(name: &str, query: &str, mode: MatchMode) -> Option<u32> {
+    if mode == MatchMode::Prefix && !name.starts_with(query) {
+        return None;
+    }
+
+    let mut score = 0;
+    let mut query_chars = query.chars().peekable();
+    let mut previous: Option<char> = None;
+    let mut previous_matched = false;
+    for c in name.chars() {
+        let q = match query_chars.peek() {
+            Some(&q) => q,
+            None => break,
+        };
+        let matched = c == q || (mode == MatchMode::Fuzzy && c.to_lowercase().eq(q.to_lowercase()));
+        if matched {
+            query_chars.next();
+            score += 1;
+            if previous_matched {
+                score += 2;
+            }
+            let word_start = match previous {
+                None => true,
+                Some(p) => p == '_' || p == '.' || (p.is_lowercase() && c.is_uppercase()),
+            };
+            if word_start {
+                score += 3;
+            }
+        }
+        previous_matched = matched;
+        previous = Some(c);
+    }
+
+    if query_chars.peek().is_some() {
+        None
+    } else {
+        Some(score)
+    }
+}
+
 pub struct SuggestionQuery {
     pub paths: Vec<PathBuf>,
     pub modules: Vec<Cow<'static, str>>,
     pub prefix_filter: bool,
+    /// How names are matched against the text that has been typed
+    pub match_mode: MatchMode,
     pub span: Option<Span<BytePos>>,
+    /// Reads the `//!` comment at the start of the files of suggested `import!` modules to
+    /// document modules which have not been loaded
+    pub module_headers: bool,
 }
 
 impl Default for SuggestionQuery {
@@ -1205,7 +2542,9 @@ impl Default for SuggestionQuery {
             paths: Vec::new(),
             modules: Vec::new(),
             prefix_filter: true,
+            match_mode: MatchMode::Prefix,
             span: None,
+            module_headers: false,
         }
     }
 }
@@ -1215,8 +2554,17 @@ impl SuggestionQuery {
         Self::default()
     }
 
+    fn score(&self, name: &str, prefix: &str) -> Option<u32> {
+        let score = match_score(name, prefix, self.match_mode);
+        if self.prefix_filter {
+            score
+        } else {
+            Some(score.unwrap_or(0))
+        }
+    }
+
     fn filter(&self, name: &str, prefix: &str) -> bool {
-        !self.prefix_filter || name.starts_with(prefix)
+        self.score(name, prefix).is_some()
     }
 
     fn suggest_fields_of_type(
@@ -1230,28 +2578,35 @@ impl SuggestionQuery {
             .map(|name| name.value.as_ref())
             .collect();
 
-        let should_suggest = |name: &str| {
+        let score = |name: &str| {
             // Filter out fields that has already been defined in the pattern
-            (!existing_fields.contains(name) && self.filter(name, prefix))
-                // But keep exact matches to keep that suggestion when the user has typed a whole
-                // field
-                || name == prefix
+            // But keep exact matches to keep that suggestion when the user has typed a whole
+            // field
+            if existing_fields.contains(name) && name != prefix {
+                None
+            } else {
+                self.score(name, prefix)
+            }
         };
 
-        let fields = typ
-            .row_iter()
-            .filter(|field| should_suggest(field.name.declared_name()))
-            .map(|field| Suggestion {
+        let fields = typ.row_iter().filter_map(|field| {
+            Some(Suggestion {
                 name: field.name.declared_name().into(),
                 typ: Either::Right(field.typ.clone()),
-            });
-        let types = typ
-            .type_field_iter()
-            .filter(|field| should_suggest(field.name.declared_name()))
-            .map(|field| Suggestion {
+                doc: None,
+                metadata: None,
+                score: score(field.name.declared_name())?,
+            })
+        });
+        let types = typ.type_field_iter().filter_map(|field| {
+            Some(Suggestion {
                 name: field.name.declared_name().into(),
                 typ: Either::Right(field.typ.clone().into_type()),
-            });
+                doc: None,
+                metadata: None,
+                score: score(field.name.declared_name())?,
+            })
+        });
         result.extend(fields.chain(types));
     }
 
@@ -1278,6 +2633,120 @@ impl SuggestionQuery {
         expr: &SpannedExpr<'ast, Symbol>,
         pos: BytePos,
     ) -> Vec<Suggestion>
+    where
+        T: TypeEnv<Type = ArcType>,
+    {
+        self.suggest_(env, None, None, source_span, expr, pos)
+    }
+
+    /// Same as `suggest` but uses `metadata` to hide the fields of imported modules that are
+    /// marked as `#[private]`
+    pub fn suggest_with_metadata<'ast, T>(
+        &self,
+        env: &T,
+        metadata: &dyn MetadataEnv,
+        source_span: Span<BytePos>,
+        expr: &SpannedExpr<'ast, Symbol>,
+        pos: BytePos,
+    ) -> Vec<Suggestion>
+    where
+        T: TypeEnv<Type = ArcType>,
+    {
+        self.suggest_(env, Some(metadata), None, source_span, expr, pos)
+    }
+
+    /// Same as `suggest_with_metadata` but also attaches the metadata and documentation of each
+    /// suggested item. `local_metadata` is the metadata of the bindings in `expr`, as returned by
+    /// `check::metadata::metadata`.
+    pub fn suggest_with_documentation<'ast, T>(
+        &self,
+        env: &T,
+        metadata: &dyn MetadataEnv,
+        local_metadata: &FnvMap<Symbol, Arc<Metadata>>,
+        source_span: Span<BytePos>,
+        expr: &SpannedExpr<'ast, Symbol>,
+        pos: BytePos,
+    ) -> Vec<Suggestion>
+    where
+        T: TypeEnv<Type = ArcType>,
+    {
+        self.suggest_(
+            env,
+            Some(metadata),
+            Some(local_metadata),
+            source_span,
+            expr,
+            pos,
+        )
+    }
+
+    /// Finds the modules which export the variable or type referred to at `pos` (usually the
+    /// position of an undefined variable or type error) and returns, for each of them, the edit
+    /// which imports the name at the start of `source_span`.
+    ///
+    /// The candidates are the modules in `paths` and `modules`, but only the modules whose types
+    /// can be found in `env` (which usually means that they have been loaded) can be checked for
+    /// the name.
+    pub fn suggest_imports<'ast, T>(
+        &self,
+        env: &T,
+        source_span: Span<BytePos>,
+        expr: &SpannedExpr<'ast, Symbol>,
+        pos: BytePos,
+    ) -> Vec<ImportSuggestion>
+    where
+        T: TypeEnv<Type = ArcType>,
+    {
+        let name = match completion(IdentAt, source_span, expr, pos) {
+            Ok(symbol) => symbol.declared_name(),
+            Err(()) => return vec![],
+        };
+
+        let mut modules = self
+            .module_files("")
+            .into_iter()
+            .map(|(module, _)| module)
+            .chain(self.modules.iter().map(|module| module.to_string()))
+            .collect::<Vec<_>>();
+        modules.sort();
+        modules.dedup();
+
+        let binding = if name.starts_with(ast::is_operator_char) {
+            format!("({})", name)
+        } else {
+            name.to_string()
+        };
+        let start = source_span.start();
+        modules
+            .into_iter()
+            .filter(|module| {
+                env.find_type(SymbolRef::new(module)).map_or(false, |typ| {
+                    typ.row_iter()
+                        .any(|field| field.name.declared_name() == name)
+                        || typ
+                            .type_field_iter()
+                            .any(|field| field.name.declared_name() == name)
+                })
+            })
+            .map(|module| ImportSuggestion {
+                edit: TextEdit {
+                    span: Span::new(start, start),
+                    new_text: format!("let {{ {} }} = import! {}\n", binding, module),
+                },
+                module,
+            })
+            .collect()
+    }
+
+    fn suggest_<'ast, T>(
+        &self,
+        env: &T,
+        metadata: Option<&dyn MetadataEnv>,
+        local_metadata: Option<&FnvMap<Symbol, Arc<Metadata>>>,
+        source_span: Span<BytePos>,
+        expr: &SpannedExpr<'ast, Symbol>,
+        pos: BytePos,
+    ) -> Vec<Suggestion>
     where
         T: TypeEnv<Type = ArcType>,
     {
@@ -1288,6 +2757,7 @@ impl SuggestionQuery {
             Err(()) => return vec![],
         };
         let mut result = vec![];
+        let local_metadata = |name: &Symbol| local_metadata.and_then(|env| env.get(name)).cloned();
 
         let enclosing_match = found.enclosing_matches.last().unwrap();
         match found.match_ {
@@ -1295,17 +2765,71 @@ impl SuggestionQuery {
                 Match::Expr(expr) => match expr.value {
                     Expr::Ident(ref id) if id.name.is_global() => {
                         let name = id.name.definition_name();
-                        self.suggest_module_import(env, name, &mut result);
+                        self.suggest_module_import(env, metadata, name, &mut result);
                     }
                     Expr::Ident(ref id) => {
                         self.suggest_local(
                             &mut result,
                             &suggest,
+                            &local_metadata,
                             &enclosing_match,
                             id.name.declared_name(),
                         );
                     }
-                    _ => self.suggest_local(&mut result, &suggest, &enclosing_match, ""),
+                    Expr::Literal(ast::Literal::String(ref path))
+                        if found.enclosing_matches.iter().rev().nth(1).map_or(
+                            false,
+                            |parent| match parent {
+                                Match::Expr(parent) => import_path_literal(parent).is_some(),
+                                _ => false,
+                            },
+                        ) =>
+                    {
+                        // Only the part of the path before the cursor is used as the prefix
+                        let after_cursor = (expr.span.end() - pos).to_usize().saturating_sub(1);
+                        let path = path
+                            .get(..path.len().saturating_sub(after_cursor))
+                            .unwrap_or(path);
+                        let mut module = filename_to_module(path);
+                        if path.ends_with('/') && !module.is_empty() {
+                            module.push('.');
+                        }
+                        self.suggest_module_import(env, metadata, &module, &mut result);
+                    }
+                    Expr::Literal(ast::Literal::String(ref fmt)) => {
+                        let args = found
+                            .enclosing_matches
+                            .iter()
+                            .rev()
+                            .nth(1)
+                            .and_then(|parent| match parent {
+                                Match::Expr(parent) => format_args(parent, expr),
+                                _ => None,
+                            });
+                        let after_cursor = (expr.span.end() - pos).to_usize().saturating_sub(1);
+                        let field = fmt
+                            .get(..fmt.len().saturating_sub(after_cursor))
+                            .and_then(open_replacement_field);
+                        match (args, field) {
+                            (Some(args), Some(field)) => {
+                                self.suggest_format_fields(env, &mut result, args, field)
+                            }
+                            _ => self.suggest_local(
+                                &mut result,
+                                &suggest,
+                                &local_metadata,
+                                &enclosing_match,
+                                "",
+                            ),
+                        }
+                    }
+                    _ => self.suggest_local(
+                        &mut result,
+                        &suggest,
+                        &local_metadata,
+                        &enclosing_match,
+                        "",
+                    ),
                 },
 
                 Match::Pattern(pattern) => {
@@ -1320,16 +2844,17 @@ impl SuggestionQuery {
                         }
                         _ => "",
                     };
-                    result.extend(
-                        suggest
-                            .patterns
-                            .iter()
-                            .filter(|&(name, _)| self.filter(name.declared_name(), prefix))
-                            .map(|(name, typ)| Suggestion {
-                                name: name.declared_name().into(),
-                                typ: Either::Right(typ.clone()),
-                            }),
-                    );
+                    result.extend(suggest.patterns.iter().filter_map(|(name, typ)| {
+                        let score = self.score(name.declared_name(), prefix)?;
+                        let metadata = local_metadata(name);
+                        Some(Suggestion {
+                            name: name.declared_name().into(),
+                            typ: Either::Right(typ.clone()),
+                            doc: metadata_doc(metadata.as_deref()),
+                            metadata,
+                            score,
+                        })
+                    }));
                 }
                 Match::Ident(_, ident, _) => match *enclosing_match {
                     Match::Expr(context) => match context.value {
@@ -1338,23 +2863,61 @@ impl SuggestionQuery {
                                 let typ = resolve::remove_aliases(&env, NullInterner::new(), typ);
                                 let id = ident.as_ref();
 
+                                let module_metadata = match (&expr.value, metadata) {
+                                    (Expr::Ident(module), Some(metadata))
+                                        if module.name.is_global() =>
+                                    {
+                                        metadata.get_metadata(&module.name)
+                                    }
+                                    _ => None,
+                                };
+                                let is_private = |name: &str| {
+                                    module_metadata
+                                        .as_ref()
+                                        .and_then(|metadata| metadata.module.get(name))
+                                        .map_or(false, |metadata| metadata.is_private())
+                                };
+                                // Fields of local records are never hidden so their metadata is
+                                // only used for documentation
+                                let record_metadata = match &expr.value {
+                                    Expr::Ident(record) if !record.name.is_global() => {
+                                        local_metadata(&record.name)
+                                    }
+                                    _ => module_metadata.clone(),
+                                };
+
                                 let iter = typ
                                     .row_iter()
-                                    .filter(move |field| self.filter(field.name.as_ref(), id))
-                                    .map(|field| (field.name.clone(), field.typ.clone()));
-                                result.extend(iter.map(|(name, typ)| Suggestion {
-                                    name: name.declared_name().into(),
-                                    typ: Either::Right(typ),
-                                }));
+                                    .filter(|field| !is_private(field.name.declared_name()))
+                                    .filter_map(|field| {
+                                        let metadata =
+                                            record_metadata.as_ref().and_then(|metadata| {
+                                                metadata.module.get(field.name.declared_name())
+                                            });
+                                        Some(Suggestion {
+                                            name: field.name.declared_name().into(),
+                                            typ: Either::Right(field.typ.clone()),
+                                            doc: metadata_doc(metadata.map(|m| &**m)),
+                                            metadata: metadata.cloned(),
+                                            score: self.score(field.name.as_ref(), id)?,
+                                        })
+                                    });
+                                result.extend(iter);
                             }
                         }
                         Expr::Ident(ref id) if id.name.is_global() => {
-                            self.suggest_module_import(env, id.name.as_pretty_str(), &mut result);
+                            self.suggest_module_import(
+                                env,
+                                metadata,
+                                id.name.as_pretty_str(),
+                                &mut result,
+                            );
                         }
                         _ => {
                             self.suggest_local(
                                 &mut result,
                                 &suggest,
+                                &local_metadata,
                                 enclosing_match,
                                 ident.declared_name(),
                             );
@@ -1363,6 +2926,7 @@ impl SuggestionQuery {
                                 self.suggest_local_type(
                                     &mut result,
                                     &suggest,
+                                    &local_metadata,
                                     enclosing_match,
                                     ident.declared_name(),
                                 );
@@ -1392,6 +2956,7 @@ impl SuggestionQuery {
                 Match::Type(_, ident, _) => self.suggest_local_type(
                     &mut result,
                     &suggest,
+                    &local_metadata,
                     enclosing_match,
                     ident.declared_name(),
                 ),
@@ -1399,19 +2964,32 @@ impl SuggestionQuery {
 
             None => match *enclosing_match {
                 Match::Expr(..) | Match::Ident(..) => {
-                    self.suggest_local(&mut result, &suggest, &enclosing_match, "");
+                    self.suggest_local(
+                        &mut result,
+                        &suggest,
+                        &local_metadata,
+                        &enclosing_match,
+                        "",
+                    );
                     if let Match::Expr(Spanned {
                         value: Expr::Record { .. },
                         ..
                     }) = *enclosing_match
                     {
-                        self.suggest_local_type(&mut result, &suggest, enclosing_match, "");
+                        self.suggest_local_type(
+                            &mut result,
+                            &suggest,
+                            &local_metadata,
+                            enclosing_match,
+                            "",
+                        );
                     }
                 }
 
                 Match::Type(_, ident, _) => self.suggest_local_type(
                     &mut result,
                     &suggest,
+                    &local_metadata,
                     enclosing_match,
                     ident.declared_name(),
                 ),
@@ -1423,20 +3001,70 @@ impl SuggestionQuery {
                             self.suggest_fields_of_type(&mut result, fields, "", &typ);
                         }
                     }
-                    _ => result.extend(suggest.patterns.iter().map(|(name, typ)| Suggestion {
-                        name: name.declared_name().into(),
-                        typ: Either::Right(typ.clone()),
+                    _ => result.extend(suggest.patterns.iter().map(|(name, typ)| {
+                        let metadata = local_metadata(name);
+                        Suggestion {
+                            name: name.declared_name().into(),
+                            typ: Either::Right(typ.clone()),
+                            doc: metadata_doc(metadata.as_deref()),
+                            metadata,
+                            score: 0,
+                        }
                     })),
                 },
             },
         }
+
+        if self.match_mode == MatchMode::Fuzzy {
+            // Shorter names are closer matches when the scores are equal
+            result.sort_by(|l, r| r.score.cmp(&l.score).then(l.name.len().cmp(&r.name.len())));
+        }
         result
     }
 
+    /// Suggests the fields of the record `args` for the replacement field `field` (`a.b.prefix`)
+    /// of a format string
+    fn suggest_format_fields<T>(
+        &self,
+        env: &T,
+        result: &mut Vec<Suggestion>,
+        args: &SpannedExpr<'_, Symbol>,
+        field: &str,
+    ) where
+        T: TypeEnv<Type = ArcType>,
+    {
+        let mut typ = match args.try_type_of(&env) {
+            Ok(typ) => resolve::remove_aliases(env, NullInterner::new(), typ),
+            Err(_) => return,
+        };
+        let mut path = field.split('.');
+        let prefix = path.next_back().unwrap_or("");
+        for name in path {
+            typ = match typ
+                .row_iter()
+                .find(|field| field.name.declared_name() == name)
+            {
+                Some(field) => resolve::remove_aliases(env, NullInterner::new(), field.typ.clone()),
+                None => return,
+            };
+        }
+
+        result.extend(typ.row_iter().filter_map(|field| {
+            Some(Suggestion {
+                name: field.name.declared_name().into(),
+                typ: Either::Right(field.typ.clone()),
+                doc: None,
+                metadata: None,
+                score: self.score(field.name.declared_name(), prefix)?,
+            })
+        }));
+    }
+
     fn suggest_local<T>(
         &self,
         result: &mut Vec<Suggestion>,
         suggest: &Suggest<T>,
+        local_metadata: &dyn Fn(&Symbol) -> Option<Arc<Metadata>>,
         context: &Match,
         ident: &str,
     ) where
@@ -1446,7 +3074,6 @@ impl SuggestionQuery {
             suggest
                 .stack
                 .iter()
-                .filter(move |&(k, _)| self.filter(k.declared_name(), ident))
                 .filter(|&(k, _)| match context {
                     // If inside a record expression, remove any fields that have already been used
                     Match::Expr(&Spanned {
@@ -1464,9 +3091,16 @@ impl SuggestionQuery {
                         .all(|already_used_field| already_used_field.value != *k),
                     _ => true,
                 })
-                .map(|(k, typ)| Suggestion {
-                    name: k.declared_name().into(),
-                    typ: Either::Right(typ.clone()),
+                .filter_map(move |(k, typ)| {
+                    let score = self.score(k.declared_name(), ident)?;
+                    let metadata = local_metadata(k);
+                    Some(Suggestion {
+                        name: k.declared_name().into(),
+                        typ: Either::Right(typ.clone()),
+                        doc: metadata_doc(metadata.as_deref()),
+                        metadata,
+                        score,
+                    })
                 }),
         )
     }
@@ -1475,6 +3109,7 @@ impl SuggestionQuery {
         &self,
         result: &mut Vec<Suggestion>,
         suggest: &Suggest<T>,
+        local_metadata: &dyn Fn(&Symbol) -> Option<Arc<Metadata>>,
         context: &Match,
         ident: &str,
     ) where
@@ -1484,7 +3119,6 @@ impl SuggestionQuery {
             suggest
                 .type_stack
                 .iter()
-                .filter(|&(k, _)| self.filter(k.declared_name(), ident))
                 .filter(|&(k, _)| match context {
                     // If inside a record expression, remove any fields that have already been used
                     Match::Expr(&Spanned {
@@ -1502,24 +3136,27 @@ impl SuggestionQuery {
                         .all(|already_used_field| already_used_field.value != *k),
                     _ => true,
                 })
-                .map(|(name, kind)| Suggestion {
-                    name: name.declared_name().into(),
-                    typ: Either::Left(kind.clone()),
+                .filter_map(|(name, kind)| {
+                    let score = self.score(name.declared_name(), ident)?;
+                    let metadata = local_metadata(name);
+                    Some(Suggestion {
+                        name: name.declared_name().into(),
+                        typ: Either::Left(kind.clone()),
+                        doc: metadata_doc(metadata.as_deref()),
+                        metadata,
+                        score,
+                    })
                 }),
         );
     }
 
-    fn suggest_module_import<T>(&self, env: &T, path: &str, suggestions: &mut Vec<Suggestion>)
-    where
-        T: TypeEnv<Type = ArcType>,
-    {
+    /// Returns the name and file of each module under `module_prefix` in the search paths
+    fn module_files(&self, module_prefix: &str) -> Vec<(String, PathBuf)> {
         use std::ffi::OsStr;
-        let path = Name::new(path);
 
-        let base = PathBuf::from(path.module().as_str().replace(".", "/"));
+        let base = PathBuf::from(module_prefix.replace(".", "/"));
 
-        let modules = self
-            .paths
+        self.paths
             .iter()
             .flat_map(|root| {
                 let walk_root = root.join(&*base);
@@ -1534,38 +3171,70 @@ impl SuggestionQuery {
                                 .path()
                                 .strip_prefix(&*root)
                                 .expect("Root is not a prefix of path from walk_dir");
-                            unprefixed_file.to_str().map(filename_to_module)
+                            let module = unprefixed_file.to_str().map(filename_to_module)?;
+                            Some((module, entry.into_path()))
                         } else {
                             None
                         }
                     })
             })
-            .collect::<Vec<String>>();
+            .collect()
+    }
+
+    fn suggest_module_import<T>(
+        &self,
+        env: &T,
+        metadata: Option<&dyn MetadataEnv>,
+        path: &str,
+        suggestions: &mut Vec<Suggestion>,
+    ) where
+        T: TypeEnv<Type = ArcType>,
+    {
+        let path = Name::new(path);
+
+        let modules = self.module_files(path.module().as_str());
+
+        let module_metadata = |module: &str| {
+            metadata.and_then(|metadata| metadata.get_metadata(SymbolRef::new(module)))
+        };
+        let module_doc = |module_metadata: Option<&Metadata>, file: Option<&PathBuf>| {
+            metadata_doc(module_metadata).or_else(|| {
+                file.filter(|_| self.module_headers)
+                    .and_then(|file| std::fs::read_to_string(file).ok())
+                    .and_then(|contents| doc_summary(&module_header(&contents)))
+            })
+        };
 
         suggestions.extend(
             modules
                 .iter()
-                .map(|s| &s[..])
-                .chain(self.modules.iter().map(|s| &s[..]))
-                .filter(|module| self.filter(module, path.as_str()))
-                .map(|module| {
-                    let name = module[path.module().as_str().len()..]
-                        .trim_start_matches('.')
-                        .split('.')
-                        .next()
-                        .unwrap()
-                        .to_string();
-                    Suggestion {
-                        name,
+                .map(|(module, file)| (&module[..], Some(file)))
+                .chain(self.modules.iter().map(|s| (&s[..], None)))
+                .filter_map(|(module, file)| {
+                    let score = self.score(module, path.as_str())?;
+                    let rest = module[path.module().as_str().len()..].trim_start_matches('.');
+                    let name = rest.split('.').next().unwrap();
+                    // Suggest the module containing `module` when `module` is nested deeper
+                    let suggested_module = &module[..module.len() - rest.len() + name.len()];
+                    let metadata = module_metadata(suggested_module);
+                    Some(Suggestion {
+                        name: name.to_string(),
                         typ: Either::Right(
-                            env.find_type(SymbolRef::new(module))
+                            env.find_type(SymbolRef::new(suggested_module))
                                 .unwrap_or_else(Type::hole),
                         ),
-                    }
+                        doc: module_doc(metadata.as_deref(), file.filter(|_| rest == name)),
+                        metadata,
+                        score,
+                    })
                 }),
         );
 
-        suggestions.sort_by(|l, r| l.name.cmp(&r.name));
+        suggestions.sort_by(|l, r| {
+            l.name
+                .cmp(&r.name)
+                .then(r.doc.is_some().cmp(&l.doc.is_some()))
+        });
         suggestions.dedup_by(|l, r| l.name == r.name);
     }
 
@@ -1631,6 +3300,45 @@ impl SuggestionQuery {
     }
 }
 
+/// Returns the `//!` comment at the start of a module's source, without the comment markers
+fn module_header(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| line.trim())
+        // Skip the shebang line and directives such as `//@NO-IMPLICIT-PRELUDE`
+        .skip_while(|line| line.is_empty() || line.starts_with("#!") || line.starts_with("//@"))
+        .take_while(|line| line.starts_with("//!"))
+        .map(|line| {
+            let line = &line["//!".len()..];
+            line.strip_prefix(' ').unwrap_or(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the first paragraph of the doc comment in `metadata`
+fn metadata_doc(metadata: Option<&Metadata>) -> Option<String> {
+    metadata
+        .and_then(|metadata| metadata.comment.as_ref())
+        .and_then(|comment| doc_summary(&comment.content))
+}
+
+/// Returns the first paragraph of a doc comment
+fn doc_summary(comment: &str) -> Option<String> {
+    let summary = comment
+        .lines()
+        .map(|line| line.trim())
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if summary.is_empty() {
+        None
+    } else {
+        Some(summary)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct SignatureHelp {
     pub name: String,
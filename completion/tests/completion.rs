@@ -10,7 +10,7 @@ extern crate gluon_completion as completion;
 extern crate gluon_parser as parser;
 
 use crate::base::{
-    ast::Sp,
+    ast::{MutVisitor, Sp},
     kind::{ArcKind, Kind},
     pos::{BytePos, Span},
     types::{ArcType, Field, Type},
@@ -51,6 +51,43 @@ fn find_all_symbols(s: &str, pos: BytePos) -> Result<(String, Vec<Span<BytePos>>
     completion::find_all_symbols(expr.span, &expr, pos)
 }
 
+fn definition_at(s: &str, pos: BytePos) -> Result<completion::Definition, ()> {
+    let (expr, result) = support::typecheck_expr(s);
+    let expr = expr.expr();
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    completion::definition_at(expr.span, &expr, pos)
+}
+
+fn rename(
+    s: &str,
+    pos: BytePos,
+    new_name: &str,
+) -> Result<Vec<completion::TextEdit>, completion::RenameError> {
+    let (expr, result) = support::typecheck_expr(s);
+    let expr = expr.expr();
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    completion::rename(expr.span, &expr, pos, new_name)
+}
+
+fn missing_match_arms(s: &str, pos: BytePos) -> Option<completion::TextEdit> {
+    let env = MockEnv::new();
+
+    let (expr, result) = support::typecheck_expr(s);
+    let expr = expr.expr();
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    completion::missing_match_arms(&env, expr.span, &expr, pos)
+}
+
+fn edit(span: Span<BytePos>, new_text: &str) -> completion::TextEdit {
+    completion::TextEdit {
+        span,
+        new_text: new_text.to_string(),
+    }
+}
+
 fn find_kind2(s: &str) -> Result<ArcKind, ()> {
     find_span_type2(s).map(|t| {
         t.1.as_ref()
@@ -392,6 +429,335 @@ test #Int+ test #Int+ dummy
     );
 }
 
+#[test]
+fn definition_of_variables() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let test = 1
+let f x = x #Int+ test
+let g = \y -> f y
+let { test } = { test = 2 }
+g test
+"#;
+    let definition = |line, column| definition_at(text, loc(text, line, column));
+    let span = |line, start, end| {
+        Ok(completion::Definition::Span(Span::new(
+            loc(text, line, start),
+            loc(text, line, end),
+        )))
+    };
+
+    // Let binding
+    assert_eq!(definition(2, 19), span(1, 4, 8));
+    // Function argument
+    assert_eq!(definition(2, 11), span(2, 6, 7));
+    // Lambda argument
+    assert_eq!(definition(3, 16), span(3, 9, 10));
+    // The shadowing binding of a record pattern
+    assert_eq!(definition(5, 3), span(4, 6, 10));
+    assert_eq!(definition(5, 0), span(3, 4, 5));
+}
+
+#[test]
+fn definition_of_types_and_constructors() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Opt = | Some Int | None
+let x : Opt = Some 1
+match x with
+| Some y -> y
+| None -> 0
+"#;
+    let definition = |line, column| definition_at(text, loc(text, line, column));
+    let span = |line, start, end| {
+        Ok(completion::Definition::Span(Span::new(
+            loc(text, line, start),
+            loc(text, line, end),
+        )))
+    };
+
+    // Type binding
+    assert_eq!(definition(2, 9), span(1, 5, 8));
+    // Variant constructor
+    assert_eq!(definition(2, 15), span(1, 13, 17));
+    // Pattern
+    assert_eq!(definition(4, 12), span(4, 7, 8));
+}
+
+#[test]
+fn definition_of_imported_module() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let m = import! m
+m
+"#;
+    let (mut expr, _) = support::typecheck_partial_expr(text);
+    support::ReplaceImport.visit_expr(expr.expr_mut());
+    let expr = expr.expr();
+
+    assert_eq!(
+        completion::definition_at(expr.span, expr, loc(text, 1, 17)),
+        Ok(completion::Definition::Module("m".to_string()))
+    );
+    assert_eq!(
+        completion::definition_at(expr.span, expr, loc(text, 2, 0)),
+        Ok(completion::Definition::Span(Span::new(
+            loc(text, 1, 4),
+            loc(text, 1, 5)
+        )))
+    );
+}
+
+#[test]
+fn resolve_symbol_origin() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let __prelude = import! std.prelude
+let { show } = __prelude
+let list = import! std.list
+let { map } = list
+let x = 1
+show (map x (list.filter x))
+"#;
+    let (mut expr, _) = support::typecheck_partial_expr(text);
+    support::ReplaceImport.visit_expr(expr.expr_mut());
+    let expr = expr.expr();
+
+    // Treat the first two lines as generated by the implicit prelude
+    let source_span = Span::new(loc(text, 3, 0), expr.span.end());
+    let resolve = |line, column| {
+        completion::resolve(&MockEnv::new(), source_span, expr, loc(text, line, column))
+    };
+    let import = |module: &str, name: Option<&str>, span| {
+        Ok(completion::SymbolOrigin {
+            kind: completion::OriginKind::Import,
+            module: Some(module.to_string()),
+            exported_name: name.map(|name| name.to_string()),
+            definition_span: span,
+        })
+    };
+
+    assert_eq!(
+        resolve(6, 0),
+        Ok(completion::SymbolOrigin {
+            kind: completion::OriginKind::Prelude,
+            module: Some("std.prelude".to_string()),
+            exported_name: Some("show".to_string()),
+            definition_span: None,
+        })
+    );
+    assert_eq!(
+        resolve(6, 6),
+        import(
+            "std.list",
+            Some("map"),
+            Some(Span::new(loc(text, 4, 6), loc(text, 4, 9)))
+        )
+    );
+    assert_eq!(
+        resolve(6, 19),
+        import(
+            "std.list",
+            Some("filter"),
+            Some(Span::new(loc(text, 3, 4), loc(text, 3, 8)))
+        )
+    );
+    assert_eq!(
+        resolve(6, 10),
+        Ok(completion::SymbolOrigin {
+            kind: completion::OriginKind::Local,
+            module: None,
+            exported_name: None,
+            definition_span: Some(Span::new(loc(text, 5, 4), loc(text, 5, 5))),
+        })
+    );
+    assert_eq!(
+        resolve(6, 19).unwrap().qualified_name(),
+        Some("std.list.filter".to_string())
+    );
+}
+
+#[test]
+fn rename_variable() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let test = 1
+let dummy =
+    let test = 3
+    test
+let f x = x #Int+ test
+{ test, f }
+"#;
+    let result = rename(text, loc(text, 1, 5), "renamed");
+
+    assert_eq!(
+        result,
+        Ok(vec![
+            edit(Span::new(loc(text, 1, 4), loc(text, 1, 8)), "renamed"),
+            edit(Span::new(loc(text, 5, 18), loc(text, 5, 22)), "renamed"),
+            edit(
+                Span::new(loc(text, 6, 2), loc(text, 6, 6)),
+                "test = renamed"
+            ),
+        ])
+    );
+}
+
+#[test]
+fn rename_function_argument() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let f x = x #Int+ 1
+f
+"#;
+    let result = rename(text, loc(text, 1, 10), "y");
+
+    assert_eq!(
+        result,
+        Ok(vec![
+            edit(Span::new(loc(text, 1, 6), loc(text, 1, 7)), "y"),
+            edit(Span::new(loc(text, 1, 10), loc(text, 1, 11)), "y"),
+        ])
+    );
+}
+
+#[test]
+fn rename_invalid_name() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let test = 1
+test
+"#;
+    for name in &["", "1abc", "Test", "let", "+", "a-b"] {
+        assert_eq!(
+            rename(text, loc(text, 1, 5), name),
+            Err(completion::RenameError::InvalidName(name.to_string()))
+        );
+    }
+}
+
+#[test]
+fn rename_conflict_with_shadowing() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let x = 1
+let y = 2
+x #Int+ y
+"#;
+    // `x` would be shadowed by `y` at its use
+    assert_eq!(
+        rename(text, loc(text, 1, 4), "y"),
+        Err(completion::RenameError::Conflict(Span::new(
+            loc(text, 3, 0),
+            loc(text, 3, 1)
+        )))
+    );
+    // The use of `x` would refer to the renamed `y`
+    assert_eq!(
+        rename(text, loc(text, 2, 4), "x"),
+        Err(completion::RenameError::Conflict(Span::new(
+            loc(text, 3, 0),
+            loc(text, 3, 1)
+        )))
+    );
+    // `z` is not used anywhere else
+    assert!(rename(text, loc(text, 2, 4), "z").is_ok());
+}
+
+#[test]
+fn missing_match_arms_adds_uncovered_constructors() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Shape = | Circle Float | Rect Float Float | Empty
+match Empty with
+| Circle r -> r
+| Rect w 1.0 -> w
+"#;
+    let end = loc(text, 4, 17);
+    assert_eq!(
+        missing_match_arms(text, loc(text, 2, 2)),
+        Some(edit(Span::new(end, end), "\n| Rect _ _ -> \n| Empty -> "))
+    );
+}
+
+#[test]
+fn missing_match_arms_on_exhaustive_match() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type AB = | A | B
+let f x =
+    match x with
+    | A -> 1
+    | B -> 2
+let g x =
+    match x with
+    | A -> 1
+    | y -> 2
+{ f, g }
+"#;
+    assert_eq!(missing_match_arms(text, loc(text, 4, 7)), None);
+    assert_eq!(missing_match_arms(text, loc(text, 8, 7)), None);
+}
+
+#[test]
+fn find_all_references_across_modules() {
+    let _ = env_logger::try_init();
+
+    let module_text = r#"
+let x = 1
+let y = x
+{ x, y }
+"#;
+    let importer_text = r#"
+let m = import! m
+let { x } = import! m
+m.x #Int+ x
+"#;
+    let (module, _) = support::typecheck_partial_expr(module_text);
+    let (mut importer, _) = support::typecheck_partial_expr(importer_text);
+    support::ReplaceImport.visit_expr(importer.expr_mut());
+
+    let modules = [
+        completion::ModuleExpr {
+            name: "m",
+            expr: module.expr(),
+        },
+        completion::ModuleExpr {
+            name: "n",
+            expr: importer.expr(),
+        },
+    ];
+    let symbol =
+        completion::symbol(module.expr().span, module.expr(), loc(module_text, 1, 4)).unwrap();
+    let result = completion::find_all_references(&modules, "m", symbol);
+
+    let reference = |module: &str, text: &str, row, start, end| completion::Reference {
+        module: module.to_string(),
+        span: Span::new(loc(text, row, start), loc(text, row, end)),
+    };
+    assert_eq!(
+        result,
+        vec![
+            reference("m", module_text, 1, 4, 5),
+            reference("m", module_text, 2, 8, 9),
+            reference("m", module_text, 3, 2, 3),
+            reference("n", importer_text, 2, 6, 7),
+            reference("n", importer_text, 3, 2, 3),
+            reference("n", importer_text, 3, 10, 11),
+        ]
+    );
+}
+
 #[derive(PartialEq, Debug)]
 struct Symbols {
     name: String,
@@ -469,6 +835,115 @@ let { x, y } = { x = 1, y = 2 }
     );
 }
 
+#[test]
+fn all_symbols_ranges() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let dummy a =
+    let test = a
+    test
+type Enum =
+    | A Int
+    | B
+{ dummy }
+"#;
+
+    let (expr, result) = support::typecheck_expr(text);
+    let expr = expr.expr();
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let symbols = completion::all_symbols(expr.span, &expr);
+
+    let dummy = &symbols[0];
+    assert_eq!(dummy.span, Span::new(loc(text, 1, 4), loc(text, 1, 9)));
+    assert_eq!(
+        dummy.value.range,
+        Span::new(loc(text, 1, 4), loc(text, 3, 8))
+    );
+    let test = &dummy.value.children[1];
+    assert_eq!(
+        test.value.range,
+        Span::new(loc(text, 2, 8), loc(text, 2, 16))
+    );
+
+    let enum_ = &symbols[1];
+    assert_eq!(
+        enum_.value.range,
+        Span::new(loc(text, 4, 5), loc(text, 6, 7))
+    );
+    match enum_.value.content {
+        completion::CompletionSymbolContent::Type { ref kind, .. } => {
+            assert_eq!(*kind, completion::CompletionTypeKind::Alias)
+        }
+        _ => panic!(),
+    }
+    let a = &enum_.value.children[0];
+    assert_eq!(a.value.range, Span::new(loc(text, 5, 6), loc(text, 5, 11)));
+    match a.value.content {
+        completion::CompletionSymbolContent::Type { ref kind, .. } => {
+            assert_eq!(*kind, completion::CompletionTypeKind::Constructor)
+        }
+        _ => panic!(),
+    }
+    let b = &enum_.value.children[1];
+    assert_eq!(b.value.range, Span::new(loc(text, 6, 6), loc(text, 6, 7)));
+}
+
+#[test]
+fn all_symbols_nested_in_modules() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let module =
+    let helper x = x
+    type Inner = { value : Int }
+    {
+        Inner,
+        helper,
+        nested = { deep = 1 },
+        run = \y -> (let z = y in z),
+    }
+module
+"#;
+
+    let (expr, result) = support::typecheck_expr(text);
+    let expr = expr.expr();
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let symbols = completion::all_symbols(expr.span, &expr);
+
+    let nested = &symbols[0].value.children[2];
+    assert_eq!(
+        nested.value.range,
+        Span::new(loc(text, 7, 8), loc(text, 7, 29))
+    );
+    assert_eq!(
+        simple_symbols(symbols),
+        vec![Symbols {
+            name: "module".into(),
+            children: vec![
+                Symbols {
+                    name: "helper".into(),
+                    children: vec!["x".into()]
+                },
+                Symbols {
+                    name: "test.Inner".into(),
+                    children: vec!["value".into()]
+                },
+                Symbols {
+                    name: "nested".into(),
+                    children: vec!["deep".into()]
+                },
+                Symbols {
+                    name: "run".into(),
+                    children: vec!["z".into()]
+                },
+            ]
+        }]
+    );
+}
+
 #[test]
 fn completion_on_type() {
     let _ = env_logger::try_init();
@@ -514,6 +989,91 @@ let x : Test Int = Test 1
     assert_eq!(result, expected);
 }
 
+fn find_type_info(s: &str, pos: BytePos) -> Result<completion::TypeInfo, ()> {
+    let env = MockEnv::new();
+
+    let (expr, result) = support::typecheck_expr(s);
+    let expr = expr.expr();
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    completion::find_type_info(&env, expr.span, &expr, pos)
+}
+
+#[test]
+fn type_info_on_type() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Test a = | Test a
+let x : Test Int = Test 1
+1.0
+"#;
+    let result = find_type_info(text, loc(text, 2, 11)).unwrap();
+    assert_eq!(
+        result.typ,
+        Either::Left(Kind::function(Kind::typ(), Kind::typ()))
+    );
+    let alias = result.alias.expect("Alias");
+    assert_eq!(alias.name.declared_name(), "Test");
+    assert_eq!(alias.params().len(), 1);
+
+    let result = find_type_info(text, loc(text, 1, 7)).unwrap();
+    assert_eq!(
+        result
+            .alias
+            .map(|alias| alias.name.declared_name().to_string()),
+        Some("Test".into())
+    );
+}
+
+#[test]
+fn type_info_on_value() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+type Test a = | Test a
+let x : Test Int = Test 1
+x
+"#;
+    let result = find_type_info(text, loc(text, 3, 0));
+    assert_eq!(result.map(|info| info.alias), Ok(None),);
+}
+
+#[test]
+fn batch_matches_single_queries() {
+    let _ = env_logger::try_init();
+
+    let env = MockEnv::new();
+    let text = r#"
+type Test a = | Test a
+let id x = x
+let record = { a = 1, b = "abc" }
+let f x y : Int -> Int -> Int =
+    let z = id x
+    match Test z with
+    | Test w -> w #Int+ y
+let x : Test Int = Test (f record.a 2)
+x
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    let expr = expr.expr();
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let extract = (completion::SpanAt, completion::TypeAt { env: &env });
+    let mut positions: Vec<_> = (0..text.len() as u32 + 2).map(BytePos::from).collect();
+    positions.reverse();
+    positions.push(BytePos::from(20));
+
+    let expected: Vec<_> = positions
+        .iter()
+        .map(|&pos| completion::completion(extract, expr.span, &expr, pos))
+        .collect();
+    assert_eq!(
+        completion::batch(extract, expr.span, &expr, &positions),
+        expected
+    );
+}
+
 #[test]
 #[ignore]
 fn completion_on_function_type() {
@@ -56,6 +56,34 @@ fn suggest_metadata(s: &str, pos: BytePos, name: &str) -> Option<Metadata> {
     })
 }
 
+fn suggest_documentation(s: &str, pos: BytePos) -> Vec<(String, Option<String>, bool)> {
+    let env = MockEnv::new();
+
+    let (expr, _result) = support::typecheck_expr(s);
+    let expr = expr.expr();
+
+    let (_, metadata_map) = check::metadata::metadata(&env, &expr);
+    let mut suggestions = completion::SuggestionQuery::new().suggest_with_documentation(
+        &env,
+        &env,
+        &metadata_map,
+        expr.span,
+        &expr,
+        pos,
+    );
+    suggestions.sort_by(|l, r| l.name.cmp(&r.name));
+    suggestions
+        .into_iter()
+        .map(|suggestion| {
+            (
+                suggestion.name,
+                suggestion.doc,
+                suggestion.metadata.is_some(),
+            )
+        })
+        .collect()
+}
+
 #[test]
 fn metadata_at_variable() {
     let _ = env_logger::try_init();
@@ -190,3 +218,45 @@ module.ab
     });
     assert_eq!(result, expected);
 }
+
+#[test]
+fn suggest_documentation_of_variables() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+/// The first
+///
+/// Details
+let abc = 1
+let abb = 2
+ab
+"#;
+    let result = suggest_documentation(text, loc(text, 6, 2));
+
+    let expected = vec![
+        ("abb".to_string(), None, true),
+        ("abc".to_string(), Some("The first".to_string()), true),
+    ];
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn suggest_documentation_of_fields() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let module = {
+        /// test
+        abc = 1,
+        abb = 2
+    }
+module.ab
+"#;
+    let result = suggest_documentation(text, BytePos::from(81));
+
+    let expected = vec![
+        ("abb".to_string(), None, false),
+        ("abc".to_string(), Some("test".to_string()), true),
+    ];
+    assert_eq!(result, expected);
+}
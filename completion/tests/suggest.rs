@@ -14,11 +14,10 @@ use std::{
 
 use either::Either;
 
-use crate::base::ast::{expr_to_path, walk_mut_expr, Expr, MutVisitor, SpannedExpr, TypedIdent};
+use crate::base::ast::MutVisitor;
 use crate::base::pos::{BytePos, Span};
-use crate::base::symbol::Symbol;
 use crate::base::types::Type;
-use crate::completion::{Suggestion, SuggestionQuery};
+use crate::completion::{MatchMode, Suggestion, SuggestionQuery};
 
 #[allow(unused)]
 mod support;
@@ -31,42 +30,16 @@ fn suggest_types(s: &str, pos: BytePos) -> Result<Vec<Suggestion>, ()> {
 fn suggest_query(query: &SuggestionQuery, s: &str, pos: BytePos) -> Result<Vec<Suggestion>, ()> {
     let env = MockEnv::new();
 
-    struct ReplaceImport;
-
-    impl<'a> MutVisitor<'a, '_> for ReplaceImport {
-        type Ident = Symbol;
-
-        fn visit_expr(&mut self, expr: &mut SpannedExpr<'_, Symbol>) {
-            let replacement = match expr.value {
-                Expr::App {
-                    ref func, ref args, ..
-                } => match func.value {
-                    Expr::Ident(ref id) if id.name.declared_name() == "import!" => {
-                        let mut path = "@".to_string();
-                        expr_to_path(&args[0], &mut path).unwrap();
-                        Some(Expr::Ident(TypedIdent {
-                            name: Symbol::from(path),
-                            typ: Type::hole(),
-                        }))
-                    }
-                    _ => None,
-                },
-                _ => None,
-            };
-            match replacement {
-                Some(replacement) => expr.value = replacement,
-                None => walk_mut_expr(self, expr),
-            }
-        }
-    }
-
     let (mut expr, _result) = support::typecheck_partial_expr(s);
     let expr = expr.expr_mut();
 
-    ReplaceImport.visit_expr(expr);
+    support::ReplaceImport.visit_expr(expr);
 
     let mut vec = query.suggest(&env, expr.span, &expr, pos);
-    vec.sort_by(|l, r| l.name.cmp(&r.name));
+    // Fuzzy suggestions are already sorted by their score
+    if query.match_mode == MatchMode::Prefix {
+        vec.sort_by(|l, r| l.name.cmp(&r.name));
+    }
     Ok(vec)
 }
 
@@ -213,6 +186,9 @@ a
     let expected = Ok(vec![Suggestion {
         name: "aa".into(),
         typ: Either::Right(Type::int()),
+        doc: None,
+        metadata: None,
+        score: 4,
     }]);
 
     assert_eq!(result, expected);
@@ -478,6 +454,92 @@ import! std.
     );
 }
 
+#[test]
+fn suggest_module_import_path() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+import! "std/prelu"
+"#;
+    let query = SuggestionQuery {
+        paths: vec![find_gluon_root()],
+        ..SuggestionQuery::default()
+    };
+    let result = suggest_query_loc(&query, text, 1, 18);
+    assert_eq!(result, Ok(vec!["prelude".into()]));
+}
+
+#[test]
+fn suggest_module_import_path_on_slash() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+import! "std/"
+"#;
+    let query = SuggestionQuery {
+        paths: vec![find_gluon_root()],
+        ..SuggestionQuery::default()
+    };
+    let suggestions = suggest_query_loc(&query, text, 1, 13).unwrap();
+    assert!(
+        suggestions.iter().any(|s| s == "prelude"),
+        "{:?}",
+        suggestions
+    );
+}
+
+#[test]
+fn suggest_format_string_field() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let format fmt args : String -> a -> String = fmt
+format "hello {na}" { name = "world", nested = { count = 1 }, other = 2 }
+"#;
+    let result = suggest_loc(text, 2, 17);
+    assert_eq!(result, Ok(vec!["name".into()]));
+}
+
+#[test]
+fn suggest_format_string_nested_field() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let format fmt args : String -> a -> String = fmt
+format "{{}} {name} {nested.}" { name = "world", nested = { count = 1 }, other = 2 }
+"#;
+    let result = suggest_loc(text, 2, 28);
+    assert_eq!(result, Ok(vec!["count".into()]));
+}
+
+#[test]
+fn suggest_fuzzy() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let filter_map = 1
+let fmap = 2
+let format = ""
+let map = 3
+fmp
+"#;
+    let query = SuggestionQuery {
+        match_mode: MatchMode::Fuzzy,
+        ..SuggestionQuery::default()
+    };
+    let result = suggest_query(&query, text, loc(text, 5, 3)).map(|vec| {
+        vec.into_iter()
+            .map(|suggestion| (suggestion.name, suggestion.score))
+            .collect::<Vec<_>>()
+    });
+    // `filter_map` is the better match as `m` starts a word
+    let expected = Ok(vec![("filter_map".to_string(), 9), ("fmap".to_string(), 8)]);
+    assert_eq!(result, expected);
+
+    let result = suggest_query_loc(&SuggestionQuery::default(), text, 5, 3);
+    assert_eq!(result, Ok(vec![]));
+}
+
 #[test]
 fn suggest_module_import_typed() {
     let _ = env_logger::try_init();
@@ -495,6 +557,34 @@ import! std.prelud
     let expected = Ok(vec![Suggestion {
         name: "prelude".into(),
         typ: Either::Right(Type::int()),
+        doc: None,
+        metadata: None,
+        score: 34,
+    }]);
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn suggest_module_import_documented() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+import! std.prelud
+"#;
+    let query = SuggestionQuery {
+        paths: vec![find_gluon_root()],
+        module_headers: true,
+        ..SuggestionQuery::default()
+    };
+    let result = suggest_query(&query, text, loc(text, 1, 12));
+
+    let expected = Ok(vec![Suggestion {
+        name: "prelude".into(),
+        typ: Either::Right(Type::int()),
+        doc: Some("Definitions which gets implicit re-export in every file.".into()),
+        metadata: None,
+        score: 34,
     }]);
 
     assert_eq!(result, expected);
@@ -834,3 +924,169 @@ let abc = 1
 
     assert_eq!(result, expected);
 }
+
+/// Knows the types of the `std.list`, `std.option` and `std.int` modules
+struct ModuleEnv;
+
+impl base::kind::KindEnv for ModuleEnv {
+    fn find_kind(&self, _id: &base::symbol::SymbolRef) -> Option<base::kind::ArcKind> {
+        None
+    }
+}
+
+impl base::types::TypeEnv for ModuleEnv {
+    type Type = base::types::ArcType;
+
+    fn find_type(&self, id: &base::symbol::SymbolRef) -> Option<base::types::ArcType> {
+        use crate::base::types::{Alias, Field};
+
+        let value = |name: &str| Field::new(support::intern(name), Type::int());
+        let type_ = |name: &str| {
+            Field::new(
+                support::intern(name),
+                Alias::new(support::intern(name), Vec::new(), Type::int()),
+            )
+        };
+        match id.definition_name() {
+            "std.list" => Some(Type::record(
+                vec![type_("List")],
+                vec![value("map"), value("of")],
+            )),
+            "std.option" => Some(Type::record(
+                vec![type_("Option")],
+                vec![value("map"), value("unwrap")],
+            )),
+            "std.int" => Some(Type::record(vec![], vec![value("+")])),
+            _ => None,
+        }
+    }
+
+    fn find_type_info(
+        &self,
+        _id: &base::symbol::SymbolRef,
+    ) -> Option<base::types::Alias<base::symbol::Symbol, base::types::ArcType>> {
+        None
+    }
+}
+
+fn suggest_imports(s: &str, pos: BytePos) -> Vec<completion::ImportSuggestion> {
+    let query = SuggestionQuery {
+        modules: vec![
+            "std.list".into(),
+            "std.option".into(),
+            "std.int".into(),
+            "std.not_loaded".into(),
+        ],
+        ..SuggestionQuery::default()
+    };
+
+    let (expr, _result) = support::typecheck_partial_expr(s);
+    let expr = expr.expr();
+
+    query.suggest_imports(&ModuleEnv, expr.span, &expr, pos)
+}
+
+fn import_edit(at: BytePos, module: &str, new_text: &str) -> completion::ImportSuggestion {
+    completion::ImportSuggestion {
+        module: module.into(),
+        edit: completion::TextEdit {
+            span: Span::new(at, at),
+            new_text: new_text.into(),
+        },
+    }
+}
+
+#[test]
+fn suggest_imports_for_undefined_variable() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let x = 1
+map x
+"#;
+    let start = loc(text, 1, 0);
+    let result = suggest_imports(text, loc(text, 2, 1));
+    let expected = vec![
+        import_edit(start, "std.list", "let { map } = import! std.list\n"),
+        import_edit(start, "std.option", "let { map } = import! std.option\n"),
+    ];
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn suggest_imports_for_undefined_type() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let x : Option Int = 1
+x
+"#;
+    let start = loc(text, 1, 0);
+    let result = suggest_imports(text, loc(text, 1, 9));
+    let expected = vec![import_edit(
+        start,
+        "std.option",
+        "let { Option } = import! std.option\n",
+    )];
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn suggest_imports_for_undefined_operator() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+1 + 2
+"#;
+    let start = loc(text, 1, 0);
+    let result = suggest_imports(text, loc(text, 1, 2));
+    let expected = vec![import_edit(
+        start,
+        "std.int",
+        "let { (+) } = import! std.int\n",
+    )];
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn suggest_imports_for_unknown_name() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let x = 1
+does_not_exist x
+"#;
+    assert_eq!(suggest_imports(text, loc(text, 2, 1)), vec![]);
+}
+
+fn suggest_for_hole(s: &str, pos: BytePos) -> Vec<(String, u32)> {
+    let env = MockEnv::new();
+
+    let (expr, _result) = support::typecheck_partial_expr(s);
+    let expr = expr.expr();
+
+    completion::suggest_for_hole(&env, expr.span, &expr, pos)
+        .into_iter()
+        .map(|suggestion| (suggestion.name, suggestion.score))
+        .collect()
+}
+
+#[test]
+fn suggest_bindings_matching_hole_type() {
+    let _ = env_logger::try_init();
+
+    let text = r#"
+let id x = x
+let count = 1
+let f x : Int -> Int = x
+let apply h : (Int -> Int) -> Int = h count
+apply _
+"#;
+    let result = suggest_for_hole(text, loc(text, 5, 6));
+    let expected = vec![("f".to_string(), 3), ("id".to_string(), 1)];
+
+    assert_eq!(result, expected);
+}
@@ -3,7 +3,7 @@
 use std::{fmt, iter::FromIterator};
 
 use crate::base::{
-    ast::RootExpr,
+    ast::{expr_to_path, walk_mut_expr, Expr, MutVisitor, RootExpr, SpannedExpr, TypedIdent},
     error::{Errors, InFile},
     kind::{ArcKind, Kind, KindEnv},
     metadata::{Metadata, MetadataEnv},
@@ -52,6 +52,40 @@ pub fn parse_new(s: &str) -> Result<RootExpr<Symbol>, (Option<RootExpr<Symbol>>,
     parse_partial_root_expr(&mut module, &TypeCache::new(), s)
 }
 
+/// Replaces `import! path` with a global identifier for `path`
+pub struct ReplaceImport;
+
+impl<'a> MutVisitor<'a, '_> for ReplaceImport {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &mut SpannedExpr<'_, Symbol>) {
+        let replacement = match expr.value {
+            Expr::App {
+                ref func, ref args, ..
+            } => match func.value {
+                // File paths are left unexpanded as their modules can't be found
+                Expr::Ident(ref id)
+                    if id.name.declared_name() == "import!"
+                        && !matches!(args[0].value, Expr::Literal(_)) =>
+                {
+                    let mut path = "@".to_string();
+                    expr_to_path(&args[0], &mut path).unwrap();
+                    Some(Expr::Ident(TypedIdent {
+                        name: Symbol::from(path),
+                        typ: Type::hole(),
+                    }))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        match replacement {
+            Some(replacement) => expr.value = replacement,
+            None => walk_mut_expr(self, expr),
+        }
+    }
+}
+
 pub struct MockEnv {
     bool: Alias<Symbol, ArcType>,
     int: ArcType,
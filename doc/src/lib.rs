@@ -39,6 +39,8 @@ use gluon::{
     Thread, ThreadExt,
 };
 
+pub mod schema;
+
 pub type Error = anyhow::Error;
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -69,10 +71,26 @@ pub struct Field {
     #[serde(rename = "type")]
     pub typ: String,
     pub attributes: String,
+    /// Default values of the fields of a record type
+    pub defaults: Vec<FieldDefault>,
+    /// Documentation of the fields of a record type
+    pub fields: Vec<FieldComment>,
     pub comment: String,
     pub definition_line: Option<u32>,
 }
 
+#[derive(Serialize, PartialEq, Debug)]
+pub struct FieldDefault {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct FieldComment {
+    pub name: String,
+    pub comment: String,
+}
+
 struct SymbolLinkRenderer {
     escaped: String,
     un_escaped: String,
@@ -142,9 +160,10 @@ fn print_type(current_module: &str, typ: &ArcType) -> String {
 
 fn hidden(meta: &Metadata, field: &str) -> bool {
     meta.module.get(field).map_or(false, |meta| {
-        meta.attributes().any(|attr| {
-            attr.name == "doc" && attr.arguments.as_ref().map_or(false, |arg| arg == "hidden")
-        })
+        meta.is_private()
+            || meta.attributes().any(|attr| {
+                attr.name == "doc" && attr.arguments.as_ref().map_or(false, |arg| arg == "hidden")
+            })
     })
 }
 
@@ -171,6 +190,8 @@ pub fn record(
             .filter(|field| !hidden(meta, field.name.as_ref()))
             .map(|field| {
                 let attributes;
+                let defaults;
+                let fields;
                 let comment;
                 let definition_line;
 
@@ -180,6 +201,38 @@ pub fn record(
                             .attributes()
                             .format_with("", |x, f| f(&format_args!("{}\n", x)))
                             .to_string();
+                        defaults = field
+                            .typ
+                            .unresolved_type()
+                            .remove_forall()
+                            .row_iter()
+                            .filter_map(|record_field| {
+                                let name = record_field.name.definition_name();
+                                meta.module
+                                    .get(name)
+                                    .and_then(|meta| meta.get_attribute("default"))
+                                    .map(|value| FieldDefault {
+                                        name: name.to_string(),
+                                        value: value.to_string(),
+                                    })
+                            })
+                            .collect();
+                        fields = field
+                            .typ
+                            .unresolved_type()
+                            .remove_forall()
+                            .row_iter()
+                            .filter_map(|record_field| {
+                                let name = record_field.name.definition_name();
+                                meta.module
+                                    .get(name)
+                                    .and_then(|meta| meta.comment.as_ref())
+                                    .map(|comment| FieldComment {
+                                        name: name.to_string(),
+                                        comment: comment.content.clone(),
+                                    })
+                            })
+                            .collect();
                         comment = meta
                             .comment
                             .as_ref()
@@ -190,6 +243,8 @@ pub fn record(
                     }
                     None => {
                         attributes = "".to_string();
+                        defaults = Vec::new();
+                        fields = Vec::new();
                         comment = "".to_string();
                         definition_line = None;
                     }
@@ -208,6 +263,8 @@ pub fn record(
                         .collect(),
                     typ: print_type(current_module, &field.typ.unresolved_type().remove_forall()),
                     attributes,
+                    defaults,
+                    fields,
                     comment,
                     definition_line,
                 }
@@ -258,6 +315,8 @@ pub fn record(
                     args,
                     typ: print_type(current_module, &field.typ),
                     attributes,
+                    defaults: Vec::new(),
+                    fields: Vec::new(),
                     comment,
                     definition_line,
                 }
@@ -0,0 +1,335 @@
+//! Descriptions of the JSON values which gluon types are serialized to.
+//!
+//! A `Schema` follows the encoding used by the derived `Serialize` and `Deserialize` instances
+//! (and `std.json.ser`/`std.json.de`). Records are objects, `Option a` is `a` or `null`,
+//! `Map String a` is an object with arbitrary keys and the constructors of a variant are encoded
+//! as their argument (or `null` if they have none). This lets hosts which are configured through
+//! gluon (or JSON deserialized into gluon types) document their configuration format from the
+//! type itself.
+//!
+//! ```
+//! use gluon::{ThreadExt, VmBuilder};
+//! use gluon_doc::schema::Schema;
+//!
+//! let vm = VmBuilder::new().import_paths(Some(vec!["..".into()])).build();
+//! let (_, typ) = vm
+//!     .typecheck_str("config", "{ port = 80, host = \"localhost\" }", None)
+//!     .unwrap();
+//! let schema = Schema::new(&vm.get_env(), &typ);
+//! assert_eq!(
+//!     schema.to_typescript("Config"),
+//!     "type Config = {\n    port: number;\n    host: string;\n};\n"
+//! );
+//! ```
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use gluon::base::{
+    resolve::remove_aliases_cow,
+    symbol::Symbol,
+    types::{arg_iter, ArcType, BuiltinType, NullInterner, Type, TypeEnv, TypeExt},
+};
+
+/// The shape of a serialized value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// Any JSON value (used for `std.json.Value` and types which can't be described)
+    Any,
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array(Box<Shape>),
+    /// An object with arbitrary keys whose values have the given shape
+    Map(Box<Shape>),
+    /// An object with the given fields, in the order they are declared in the type
+    Object(Vec<(String, Shape)>),
+    /// One of several shapes
+    Union(Vec<Shape>),
+    /// The shape or `null`
+    Nullable(Box<Shape>),
+    /// A reference to one of the named `definitions` of the schema
+    Ref(String),
+}
+
+impl Shape {
+    fn refers_to(&self, name: &str) -> bool {
+        match self {
+            Shape::Array(shape) | Shape::Map(shape) | Shape::Nullable(shape) => {
+                shape.refers_to(name)
+            }
+            Shape::Object(fields) => fields.iter().any(|(_, shape)| shape.refers_to(name)),
+            Shape::Union(shapes) => shapes.iter().any(|shape| shape.refers_to(name)),
+            Shape::Ref(other) => other == name,
+            _ => false,
+        }
+    }
+}
+
+/// The description of the values of a type along with the named types it refers to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schema {
+    pub root: Shape,
+    /// Shapes of the non-generic type aliases used by `root`, keyed by their name
+    pub definitions: BTreeMap<String, Shape>,
+}
+
+impl Schema {
+    /// Creates the schema of the values of `typ`, resolving aliases through `env`
+    pub fn new(env: &dyn TypeEnv<Type = ArcType>, typ: &ArcType) -> Schema {
+        let mut builder = Builder {
+            env,
+            definitions: BTreeMap::new(),
+            names: BTreeMap::new(),
+            expanding: Vec::new(),
+        };
+        let root = builder.shape(typ);
+        Schema {
+            root,
+            definitions: builder
+                .definitions
+                .into_iter()
+                .map(|(name, shape)| (name, shape.unwrap_or(Shape::Any)))
+                .collect(),
+        }
+    }
+
+    /// Returns the schema as a (draft 7) JSON Schema document
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = json!({ "$schema": "http://json-schema.org/draft-07/schema#" });
+        if let (Value::Object(schema), Value::Object(root)) = (&mut schema, json_schema(&self.root))
+        {
+            schema.extend(root);
+            if !self.definitions.is_empty() {
+                schema.insert(
+                    "definitions".into(),
+                    self.definitions
+                        .iter()
+                        .map(|(name, shape)| (name.clone(), json_schema(shape)))
+                        .collect::<serde_json::Map<_, _>>()
+                        .into(),
+                );
+            }
+        }
+        schema
+    }
+
+    /// Returns the schema as TypeScript type declarations, with the root declared as `name`
+    pub fn to_typescript(&self, name: &str) -> String {
+        let mut out = String::new();
+        for (definition, shape) in &self.definitions {
+            out.push_str(&format!(
+                "type {} = {};\n\n",
+                definition,
+                typescript(shape, 0)
+            ));
+        }
+        match &self.root {
+            Shape::Ref(root) if root == name => {
+                // The root is already declared under the requested name
+                out.pop();
+            }
+            root => out.push_str(&format!("type {} = {};\n", name, typescript(root, 0))),
+        }
+        out
+    }
+}
+
+struct Builder<'a> {
+    env: &'a dyn TypeEnv<Type = ArcType>,
+    /// `None` while the definition is being built
+    definitions: BTreeMap<String, Option<Shape>>,
+    /// The definition name assigned to each alias
+    names: BTreeMap<Symbol, String>,
+    /// Generic aliases which are currently being inlined
+    expanding: Vec<Symbol>,
+}
+
+impl Builder<'_> {
+    fn shape(&mut self, typ: &ArcType) -> Shape {
+        if let Some(alias) = typ.alias_ident() {
+            let args = typ.unapplied_args();
+            match (alias.definition_name(), &args[..]) {
+                ("std.types.Bool", []) => return Shape::Boolean,
+                ("std.types.Option", [arg]) => {
+                    return match self.shape(arg) {
+                        shape @ Shape::Nullable(_) | shape @ Shape::Any => shape,
+                        shape => Shape::Nullable(Box::new(shape)),
+                    }
+                }
+                ("std.map.Map", [_, value]) => return Shape::Map(Box::new(self.shape(value))),
+                ("std.json.Value", []) => return Shape::Any,
+                _ => (),
+            }
+
+            let alias = alias.clone();
+            if args.is_empty() {
+                return self.definition(&alias, typ);
+            }
+            if self.expanding.contains(&alias) {
+                // Recursive generic types can't be inlined
+                return Shape::Any;
+            }
+            self.expanding.push(alias);
+            let shape = self.resolved_shape(typ);
+            self.expanding.pop();
+            return shape;
+        }
+        self.resolved_shape(typ)
+    }
+
+    fn definition(&mut self, alias: &Symbol, typ: &ArcType) -> Shape {
+        if let Some(name) = self.names.get(alias) {
+            return Shape::Ref(name.clone());
+        }
+
+        // Register the name before building the shape so recursive types can refer to it
+        let name = self.definition_name(alias);
+        self.names.insert(alias.clone(), name.clone());
+        self.definitions.insert(name.clone(), None);
+
+        let shape = self.resolved_shape(typ);
+        match shape {
+            Shape::Object(_) | Shape::Union(_) => (),
+            // Only records and variants are worth naming
+            _ if !shape.refers_to(&name) => {
+                self.names.remove(alias);
+                self.definitions.remove(&name);
+                return shape;
+            }
+            _ => (),
+        }
+        self.definitions.insert(name.clone(), Some(shape));
+        Shape::Ref(name)
+    }
+
+    fn definition_name(&self, alias: &Symbol) -> String {
+        let declared = alias.declared_name();
+        let mut name = declared.to_string();
+        let mut i = 1;
+        while self.definitions.contains_key(&name) {
+            name = format!("{}{}", declared, i);
+            i += 1;
+        }
+        name
+    }
+
+    fn resolved_shape(&mut self, typ: &ArcType) -> Shape {
+        let typ = remove_aliases_cow(self.env, &mut NullInterner, typ);
+        match &**typ {
+            Type::Builtin(BuiltinType::Int) | Type::Builtin(BuiltinType::Byte) => Shape::Integer,
+            Type::Builtin(BuiltinType::Float) => Shape::Number,
+            Type::Builtin(BuiltinType::String) | Type::Builtin(BuiltinType::Char) => Shape::String,
+            Type::App(f, args) => match (&**f, &args[..]) {
+                (Type::Builtin(BuiltinType::Array), [elem]) => {
+                    Shape::Array(Box::new(self.shape(elem)))
+                }
+                _ => Shape::Any,
+            },
+            Type::Record(row) => {
+                let fields: Vec<_> = row
+                    .row_iter()
+                    .map(|field| {
+                        (
+                            field.name.declared_name().to_string(),
+                            self.shape(&field.typ),
+                        )
+                    })
+                    .collect();
+                if fields.is_empty() {
+                    Shape::Null
+                } else {
+                    Shape::Object(fields)
+                }
+            }
+            Type::Variant(row) => {
+                let mut shapes = Vec::new();
+                for variant in row.row_iter() {
+                    let args: Vec<_> = arg_iter(&variant.typ).collect();
+                    let shape = match &args[..] {
+                        [] => Shape::Null,
+                        [arg] => self.shape(arg),
+                        // Can't be serialized by the derived instances
+                        _ => Shape::Any,
+                    };
+                    if !shapes.contains(&shape) {
+                        shapes.push(shape);
+                    }
+                }
+                if shapes.len() == 1 {
+                    shapes.pop().unwrap()
+                } else {
+                    Shape::Union(shapes)
+                }
+            }
+            Type::Forall(_, typ) => self.shape(typ),
+            _ => Shape::Any,
+        }
+    }
+}
+
+fn json_schema(shape: &Shape) -> Value {
+    match shape {
+        Shape::Any => json!({}),
+        Shape::Null => json!({ "type": "null" }),
+        Shape::Boolean => json!({ "type": "boolean" }),
+        Shape::Integer => json!({ "type": "integer" }),
+        Shape::Number => json!({ "type": "number" }),
+        Shape::String => json!({ "type": "string" }),
+        Shape::Array(elem) => json!({ "type": "array", "items": json_schema(elem) }),
+        Shape::Map(value) => {
+            json!({ "type": "object", "additionalProperties": json_schema(value) })
+        }
+        Shape::Object(fields) => json!({
+            "type": "object",
+            "properties": fields
+                .iter()
+                .map(|(name, shape)| (name.clone(), json_schema(shape)))
+                .collect::<serde_json::Map<_, _>>(),
+            "required": fields.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        }),
+        Shape::Union(shapes) => {
+            json!({ "anyOf": shapes.iter().map(json_schema).collect::<Vec<_>>() })
+        }
+        Shape::Nullable(shape) => json!({ "anyOf": [json_schema(shape), { "type": "null" }] }),
+        Shape::Ref(name) => json!({ "$ref": format!("#/definitions/{}", name) }),
+    }
+}
+
+fn typescript(shape: &Shape, indent: usize) -> String {
+    match shape {
+        Shape::Any => "any".into(),
+        Shape::Null => "null".into(),
+        Shape::Boolean => "boolean".into(),
+        Shape::Integer | Shape::Number => "number".into(),
+        Shape::String => "string".into(),
+        Shape::Array(elem) => match **elem {
+            Shape::Union(_) | Shape::Nullable(_) => format!("({})[]", typescript(elem, indent)),
+            _ => format!("{}[]", typescript(elem, indent)),
+        },
+        Shape::Map(value) => format!("{{ [key: string]: {} }}", typescript(value, indent)),
+        Shape::Object(fields) => {
+            let mut out = String::from("{\n");
+            for (name, shape) in fields {
+                out.push_str(&format!(
+                    "{:indent$}{}: {};\n",
+                    "",
+                    name,
+                    typescript(shape, indent + 4),
+                    indent = indent + 4
+                ));
+            }
+            out.push_str(&format!("{:indent$}}}", "", indent = indent));
+            out
+        }
+        Shape::Union(shapes) => shapes
+            .iter()
+            .map(|shape| typescript(shape, indent))
+            .collect::<Vec<_>>()
+            .join(" | "),
+        Shape::Nullable(shape) => format!("{} | null", typescript(shape, indent)),
+        Shape::Ref(name) => name.clone(),
+    }
+}
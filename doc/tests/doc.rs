@@ -46,6 +46,8 @@ let test x = x
                 }],
                 typ: handlebars::html_escape("forall a . a -> a"),
                 attributes: "".to_string(),
+                defaults: Vec::new(),
+                fields: Vec::new(),
                 comment: "This is the test function".to_string(),
                 definition_line: None,
             }],
@@ -71,6 +73,35 @@ let test x = x
     );
 }
 
+#[test]
+fn private() {
+    let module = r#"
+#[private]
+let helper x = x
+let test x = helper x
+{ helper, test }
+"#;
+    doc_check(
+        module,
+        doc::Record {
+            types: Vec::new(),
+            values: vec![doc::Field {
+                name: "test".to_string(),
+                args: vec![doc::Argument {
+                    implicit: false,
+                    name: "x".to_string(),
+                }],
+                typ: handlebars::html_escape("forall a . a -> a"),
+                attributes: "".to_string(),
+                defaults: Vec::new(),
+                fields: Vec::new(),
+                comment: "".to_string(),
+                definition_line: None,
+            }],
+        },
+    );
+}
+
 #[test]
 fn check_links() {
     let _ = env_logger::try_init();
@@ -93,3 +124,59 @@ fn check_links() {
 
     assert!(errors.is_empty(), "{}", errors.iter().format("\n"));
 }
+
+#[test]
+fn record_field_defaults() {
+    let module = r#"
+type Config = { port : Int = 8080, name : String }
+{ Config }
+"#;
+    let vm = new_vm();
+    let (expr, typ) = vm.typecheck_str("basic", module, None).unwrap();
+    let (meta, _) = metadata(&vm.get_env(), &expr.expr());
+
+    let out = doc::record(
+        "basic",
+        &typ,
+        &Default::default(),
+        &<() as gluon::base::source::Source>::new(""),
+        &meta,
+    );
+    assert_eq!(
+        out.types[0].defaults,
+        vec![doc::FieldDefault {
+            name: "port".to_string(),
+            value: "8080".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn record_field_comments() {
+    let module = r#"
+type Config = {
+    /// The port to listen on
+    port : Int,
+    name : String,
+}
+{ Config }
+"#;
+    let vm = new_vm();
+    let (expr, typ) = vm.typecheck_str("basic", module, None).unwrap();
+    let (meta, _) = metadata(&vm.get_env(), &expr.expr());
+
+    let out = doc::record(
+        "basic",
+        &typ,
+        &Default::default(),
+        &<() as gluon::base::source::Source>::new(""),
+        &meta,
+    );
+    assert_eq!(
+        out.types[0].fields,
+        vec![doc::FieldComment {
+            name: "port".to_string(),
+            comment: "The port to listen on".to_string(),
+        }]
+    );
+}
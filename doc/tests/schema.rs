@@ -0,0 +1,101 @@
+use serde_json::json;
+
+use gluon_doc::schema::Schema;
+
+use gluon::{RootedThread, ThreadExt};
+
+fn new_vm() -> RootedThread {
+    ::gluon::VmBuilder::new()
+        .import_paths(Some(vec!["..".into()]))
+        .build()
+}
+
+fn schema(expr: &str) -> Schema {
+    let vm = new_vm();
+    let (_, typ) = vm
+        .typecheck_str("schema", expr, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    Schema::new(&vm.get_env(), &typ)
+}
+
+#[test]
+fn config_typescript() {
+    let schema = schema(
+        r#"
+let map @ { Map } = import! std.map
+type Level = | Low | High Int
+type Server = { host : String, port : Int, tls : Bool, level : Level }
+type Config = {
+    servers : Array Server,
+    timeout : Option Float,
+    labels : Map String String,
+}
+let config : Config = { servers = [], timeout = None, labels = map.empty }
+config
+"#,
+    );
+    assert_eq!(
+        schema.to_typescript("Config"),
+        r#"type Config = {
+    servers: Server[];
+    timeout: number | null;
+    labels: { [key: string]: string };
+};
+
+type Level = null | number;
+
+type Server = {
+    host: string;
+    port: number;
+    tls: boolean;
+    level: Level;
+};
+"#
+    );
+}
+
+#[test]
+fn record_json_schema() {
+    let schema = schema(r#"{ name = "", tags = [""], retries = Some 1 }"#);
+    assert_eq!(
+        schema.to_json_schema(),
+        json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "retries": { "anyOf": [{ "type": "integer" }, { "type": "null" }] },
+            },
+            "required": ["name", "tags", "retries"],
+        })
+    );
+}
+
+#[test]
+fn recursive_type_json_schema() {
+    let schema = schema(
+        r#"
+type Node = { name : String, children : Array Node }
+let node : Node = { name = "", children = [] }
+node
+"#,
+    );
+    assert_eq!(
+        schema.to_json_schema(),
+        json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "$ref": "#/definitions/Node",
+            "definitions": {
+                "Node": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "children": { "type": "array", "items": { "$ref": "#/definitions/Node" } },
+                    },
+                    "required": ["name", "children"],
+                },
+            },
+        })
+    );
+}
@@ -0,0 +1,368 @@
+//! Semantic differences between two versions of a module.
+//!
+//! Rather than comparing text, `diff` compares the bindings at the top level of two expressions,
+//! so changes in formatting, comments or the order of bindings are ignored.
+use std::{collections::HashMap, fmt};
+
+use base::{
+    ast::{
+        walk_expr, walk_pattern, AstType, Expr, Pattern, PatternField, SpannedExpr, SpannedPattern,
+        TypeBinding, TypedIdent, ValueBinding, Visitor,
+    },
+    pos::{BytePos, Spanned},
+    symbol::Symbol,
+    types::{ArcType, Type, TypeExt},
+};
+
+/// Whether a binding is a value (`let`) or a type (`type`)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum BindingKind {
+    Value,
+    Type,
+}
+
+/// A binding at the top level of a module
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Binding {
+    pub name: String,
+    pub kind: BindingKind,
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            BindingKind::Value => write!(f, "let {}", self.name),
+            BindingKind::Type => write!(f, "type {}", self.name),
+        }
+    }
+}
+
+/// A difference between the bindings of two modules
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Change {
+    Added(Binding),
+    Removed(Binding),
+    /// A binding which was given a new name but otherwise left unchanged
+    Renamed {
+        old: Binding,
+        new: Binding,
+    },
+    /// The type of a value binding or the definition of a type binding changed. For value bindings
+    /// the type is the inferred type if `expr` has been typechecked, otherwise the annotated type
+    /// (if any).
+    SignatureChanged {
+        binding: Binding,
+        old: String,
+        new: String,
+    },
+    /// The signature of the binding is unchanged but its definition is not
+    BodyChanged(Binding),
+}
+
+impl Change {
+    /// The binding, in the new module if it still exists there, that the change applies to
+    pub fn binding(&self) -> &Binding {
+        match self {
+            Change::Added(binding)
+            | Change::Removed(binding)
+            | Change::SignatureChanged { binding, .. }
+            | Change::BodyChanged(binding) => binding,
+            Change::Renamed { new, .. } => new,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(binding) => write!(f, "added `{}`", binding),
+            Change::Removed(binding) => write!(f, "removed `{}`", binding),
+            Change::Renamed { old, new } => write!(f, "renamed `{}` to `{}`", old, new.name),
+            Change::SignatureChanged { binding, old, new } => write!(
+                f,
+                "changed the signature of `{}` from `{}` to `{}`",
+                binding, old, new
+            ),
+            Change::BodyChanged(binding) => write!(f, "changed the definition of `{}`", binding),
+        }
+    }
+}
+
+/// Returns the bindings which were added, removed, renamed or changed at the top level of `new`
+/// compared to `old`.
+///
+/// Bindings are matched by name, so a binding which is both renamed and changed is reported as
+/// removed and added. Changes are ordered by the position of the binding in `new`, followed by
+/// the bindings which were removed.
+pub fn diff(old: &SpannedExpr<Symbol>, new: &SpannedExpr<Symbol>) -> Vec<Change> {
+    let old = collect_items(old);
+    let new = collect_items(new);
+
+    let mut old_by_name = HashMap::<_, Vec<usize>>::new();
+    for (i, item) in old.iter().enumerate().rev() {
+        old_by_name.entry(&item.binding).or_default().push(i);
+    }
+
+    let mut matched_old = vec![false; old.len()];
+    // (index in `changes`, index in `new`) of the bindings which only exist in `new`
+    let mut added = Vec::new();
+    let mut changes = Vec::new();
+    for (new_index, item) in new.iter().enumerate() {
+        // Bindings which shadow an earlier binding of the same name are matched in order
+        let old_index = old_by_name
+            .get_mut(&item.binding)
+            .and_then(|indices| indices.pop());
+        match old_index {
+            Some(old_index) => {
+                matched_old[old_index] = true;
+                let old_item = &old[old_index];
+                if old_item.signature != item.signature {
+                    changes.push(Change::SignatureChanged {
+                        binding: item.binding.clone(),
+                        old: old_item.signature.clone().unwrap_or_default(),
+                        new: item.signature.clone().unwrap_or_default(),
+                    });
+                } else if old_item.body != item.body {
+                    changes.push(Change::BodyChanged(item.binding.clone()));
+                }
+            }
+            None => {
+                added.push((changes.len(), new_index));
+                changes.push(Change::Added(item.binding.clone()));
+            }
+        }
+    }
+
+    let mut removed = Vec::new();
+    for (old_item, _) in old
+        .iter()
+        .zip(&matched_old)
+        .filter(|(_, matched)| !**matched)
+    {
+        let renamed = added.iter().position(|&(_, new_index)| {
+            let new_item = &new[new_index];
+            new_item.binding.kind == old_item.binding.kind
+                && new_item.signature == old_item.signature
+                && new_item.body == old_item.body
+        });
+        match renamed {
+            Some(position) => {
+                let (change_index, new_index) = added.remove(position);
+                changes[change_index] = Change::Renamed {
+                    old: old_item.binding.clone(),
+                    new: new[new_index].binding.clone(),
+                };
+            }
+            None => removed.push(Change::Removed(old_item.binding.clone())),
+        }
+    }
+    changes.extend(removed);
+    changes
+}
+
+struct Item {
+    binding: Binding,
+    signature: Option<String>,
+    /// Tokens describing the definition of the binding, excluding its name and any spans
+    body: Vec<String>,
+}
+
+fn collect_items(mut expr: &SpannedExpr<Symbol>) -> Vec<Item> {
+    let mut items = Vec::new();
+    loop {
+        expr = match &expr.value {
+            Expr::LetBindings(binds, body) => {
+                for bind in &**binds {
+                    collect_value_items(bind, &mut items);
+                }
+                body
+            }
+            Expr::TypeBindings(binds, body) => {
+                items.extend(binds.iter().map(type_item));
+                body
+            }
+            Expr::MacroExpansion { original, .. } => original,
+            _ => break,
+        };
+    }
+    items
+}
+
+fn collect_value_items(bind: &ValueBinding<Symbol>, items: &mut Vec<Item>) {
+    let mut fingerprint = Fingerprint::default();
+    fingerprint.tokens.push(format!("args {}", bind.args.len()));
+    for arg in &*bind.args {
+        fingerprint.visit_spanned_typed_ident(&arg.name);
+    }
+    if let Some(typ) = &bind.typ {
+        fingerprint.visit_ast_type(typ);
+    }
+    fingerprint.visit_expr(&bind.expr);
+    let body = fingerprint.tokens;
+
+    match &bind.name.value {
+        Pattern::Ident(id) => items.push(Item {
+            binding: value_binding(&id.name),
+            signature: signature(&bind.resolved_type)
+                .or_else(|| bind.typ.as_ref().map(|typ| typ.to_string())),
+            body,
+        }),
+        _ => {
+            let mut idents = Vec::new();
+            pattern_idents(&bind.name, &mut idents);
+            // Each binding introduced by the pattern depends on the whole definition
+            items.extend(idents.into_iter().map(|(name, typ)| Item {
+                binding: value_binding(name),
+                signature: typ.and_then(signature),
+                body: body.clone(),
+            }));
+        }
+    }
+}
+
+fn type_item(bind: &TypeBinding<Symbol>) -> Item {
+    let alias = &bind.alias.value;
+    let mut signature = String::new();
+    for param in alias.params() {
+        signature.push_str(param.id.declared_name());
+        signature.push(' ');
+    }
+    signature.push_str("= ");
+    signature.push_str(&alias.unresolved_type().to_string());
+
+    Item {
+        binding: Binding {
+            name: bind.name.value.declared_name().to_string(),
+            kind: BindingKind::Type,
+        },
+        // The default values of fields are included in the signature
+        signature: Some(signature),
+        body: Vec::new(),
+    }
+}
+
+fn value_binding(name: &Symbol) -> Binding {
+    Binding {
+        name: name.declared_name().to_string(),
+        kind: BindingKind::Value,
+    }
+}
+
+fn signature(typ: &ArcType<Symbol>) -> Option<String> {
+    match **typ {
+        Type::Hole => None,
+        _ => Some(typ.to_string()),
+    }
+}
+
+/// Collects the names bound by `pattern` along with their types, if they are known
+fn pattern_idents<'a>(
+    pattern: &'a SpannedPattern<Symbol>,
+    idents: &mut Vec<(&'a Symbol, Option<&'a ArcType<Symbol>>)>,
+) {
+    match &pattern.value {
+        Pattern::Ident(id) => idents.push((&id.name, Some(&id.typ))),
+        Pattern::As(name, pattern) => {
+            idents.push((&name.value, None));
+            pattern_idents(pattern, idents);
+        }
+        Pattern::Record { typ, fields, .. } => {
+            for field in &**fields {
+                match field {
+                    PatternField::Value {
+                        value: Some(pattern),
+                        ..
+                    } => pattern_idents(pattern, idents),
+                    PatternField::Value { name, value: None } => {
+                        let typ = typ
+                            .row_iter()
+                            .find(|field| field.name.declared_name() == name.value.declared_name())
+                            .map(|field| &field.typ);
+                        idents.push((&name.value, typ));
+                    }
+                    PatternField::Type { .. } => (),
+                }
+            }
+        }
+        Pattern::Tuple { elems, .. } => {
+            for elem in &**elems {
+                pattern_idents(elem, idents);
+            }
+        }
+        Pattern::Constructor(_, args) => {
+            for arg in &**args {
+                pattern_idents(arg, idents);
+            }
+        }
+        Pattern::Literal(_) | Pattern::Error => (),
+    }
+}
+
+/// Records the parts of an expression which affect its meaning. Spans and inferred types are
+/// ignored so that moving or reformatting a binding does not change its fingerprint.
+#[derive(Default)]
+struct Fingerprint {
+    tokens: Vec<String>,
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for Fingerprint {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        let token = match &expr.value {
+            Expr::Literal(literal) => format!("{:?}", literal),
+            Expr::App {
+                implicit_args,
+                args,
+                ..
+            } => format!("App {} {}", implicit_args.len(), args.len()),
+            Expr::Lambda(lambda) => format!("Lambda {}", lambda.args.len()),
+            Expr::Match(_, alts) => format!("Match {}", alts.len()),
+            Expr::Projection(_, field, _) => format!("Projection {}", field.declared_name()),
+            Expr::Array(array) => format!("Array {}", array.exprs.len()),
+            Expr::Record {
+                types, exprs, base, ..
+            } => format!("Record {} {} {}", types.len(), exprs.len(), base.is_some()),
+            Expr::Tuple { elems, .. } => format!("Tuple {}", elems.len()),
+            Expr::LetBindings(binds, _) => format!(
+                "LetBindings {:?}",
+                binds.iter().map(|bind| bind.args.len()).collect::<Vec<_>>()
+            ),
+            Expr::TypeBindings(binds, _) => format!("TypeBindings {}", binds.len()),
+            Expr::Block(exprs) => format!("Block {}", exprs.len()),
+            Expr::Do(do_expr) => format!("Do {}", do_expr.id.is_some()),
+            Expr::Annotated(_, typ) => format!("Annotated {}", typ),
+            // Only the code as it was written is compared
+            Expr::MacroExpansion { original, .. } => return self.visit_expr(original),
+            _ => expr.value.kind().to_string(),
+        };
+        self.tokens.push(token);
+        walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'a SpannedPattern<'ast, Symbol>) {
+        let token = match &pattern.value {
+            Pattern::Literal(literal) => format!("{:?}", literal),
+            Pattern::Constructor(_, args) => format!("Constructor {}", args.len()),
+            Pattern::Record { fields, .. } => format!("Record {}", fields.len()),
+            Pattern::Tuple { elems, .. } => format!("Tuple {}", elems.len()),
+            Pattern::As(..) => "As".to_string(),
+            Pattern::Ident(_) => "Ident".to_string(),
+            Pattern::Error => "Error".to_string(),
+        };
+        self.tokens.push(token);
+        walk_pattern(self, &pattern.value);
+    }
+
+    fn visit_ident(&mut self, id: &'a TypedIdent<Symbol>) {
+        self.tokens.push(id.name.declared_name().to_string());
+    }
+
+    fn visit_spanned_ident(&mut self, id: &'a Spanned<Symbol, BytePos>) {
+        self.tokens.push(id.value.declared_name().to_string());
+    }
+
+    fn visit_ast_type(&mut self, typ: &'a AstType<'ast, Symbol>) {
+        self.tokens.push(typ.to_string());
+    }
+}
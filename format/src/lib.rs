@@ -7,10 +7,19 @@ extern crate gluon_base as base;
 extern crate itertools;
 extern crate pretty;
 
-use base::{ast::SpannedExpr, source::Source, symbol::Symbol};
+use base::{
+    ast::{SpannedExpr, ValueBinding},
+    source::Source,
+    symbol::Symbol,
+};
 
+mod diff;
 mod pretty_print;
 
+pub use crate::diff::{diff, Binding, BindingKind, Change};
+
+const WIDTH: usize = 100;
+
 pub fn pretty_expr(input: &dyn Source, expr: &SpannedExpr<Symbol>) -> String {
     Formatter::default().pretty_expr(input, expr)
 }
@@ -25,22 +34,126 @@ pub struct Formatter {
 
 impl Formatter {
     pub fn pretty_expr(&self, source: &dyn Source, expr: &SpannedExpr<Symbol>) -> String {
-        let input = source.src();
-        let newline = match input.find(|c: char| c == '\n' || c == '\r') {
-            Some(i) => {
-                if input[i..].starts_with("\r\n") {
-                    "\r\n"
-                } else if input[i..].starts_with("\r") {
-                    "\r"
-                } else {
-                    "\n"
-                }
-            }
-            None => "\n",
-        };
+        let arena = pretty::Arena::<()>::new();
+        let printer = pretty_print::Printer::new(&arena, source, self.clone());
+        printer.format(WIDTH, newline(source.src()), &expr)
+    }
+
+    /// Formats `expr` so that it can be inserted into existing code at `point`, for instance as the
+    /// text of a code action. `source` is the source `expr` was parsed from.
+    ///
+    /// Lines after the first are indented to match the line at `point` and the result is not
+    /// terminated by a newline.
+    pub fn pretty_inserted_expr(
+        &self,
+        source: &dyn Source,
+        expr: &SpannedExpr<Symbol>,
+        point: &InsertionPoint,
+    ) -> String {
+        let arena = pretty::Arena::<()>::new();
+        let printer = pretty_print::Printer::new(&arena, source, self.clone());
+        let text = printer.format_node(point.width(), point.newline, expr);
+        point.indent_lines(&text)
+    }
 
+    /// Formats `bind` as a `let` binding which can be inserted into existing code at `point`.
+    /// `source` is the source `bind` was parsed from.
+    ///
+    /// The binding is indented to match the line at `point` and is followed by a newline, after
+    /// which the code that was at `point` continues with its original indentation.
+    pub fn pretty_inserted_binding(
+        &self,
+        source: &dyn Source,
+        bind: &ValueBinding<Symbol>,
+        point: &InsertionPoint,
+    ) -> String {
         let arena = pretty::Arena::<()>::new();
         let printer = pretty_print::Printer::new(&arena, source, self.clone());
-        printer.format(100, newline, &expr)
+        let text = printer.format_binding(point.width(), point.newline, bind);
+        let mut text = point.indent_lines(&text);
+        text.push_str(point.newline);
+        text.push_str(if point.line_start {
+            point.before
+        } else {
+            point.indent
+        });
+        text
+    }
+}
+
+/// A position in existing code where formatted code is inserted
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InsertionPoint<'a> {
+    /// The whitespace between the start of the line and the insertion, if only whitespace precedes
+    /// the insertion on its line
+    before: &'a str,
+    /// The indentation of the line containing the insertion
+    indent: &'a str,
+    line_start: bool,
+    newline: &'static str,
+}
+
+impl<'a> InsertionPoint<'a> {
+    /// Creates an insertion point at the byte offset `position` in `text`
+    pub fn new(text: &'a str, position: usize) -> Self {
+        let line_begin = text[..position]
+            .rfind(|c: char| c == '\n' || c == '\r')
+            .map_or(0, |i| i + 1);
+        let line = &text[line_begin..];
+        let indent = &line[..line.len() - line.trim_start_matches(is_indent).len()];
+        let before = &text[line_begin..position];
+        let line_start = before.chars().all(is_indent);
+        InsertionPoint {
+            before: if line_start { before } else { "" },
+            indent,
+            line_start,
+            newline: newline(text),
+        }
+    }
+
+    /// The indentation of the line containing the insertion
+    pub fn indent(&self) -> &'a str {
+        self.indent
+    }
+
+    fn width(&self) -> usize {
+        WIDTH.saturating_sub(self.indent.len()).max(1)
+    }
+
+    fn indent_lines(&self, text: &str) -> String {
+        let mut result = String::new();
+        if self.line_start && self.indent.starts_with(self.before) {
+            result.push_str(&self.indent[self.before.len()..]);
+        }
+        for (i, line) in text.lines().enumerate() {
+            if i != 0 {
+                result.push_str(self.newline);
+                if !line.is_empty() {
+                    result.push_str(self.indent);
+                }
+            }
+            result.push_str(line);
+        }
+        result
+    }
+}
+
+fn is_indent(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// Returns the line ending used by `input`
+fn newline(input: &str) -> &'static str {
+    match input.find(|c: char| c == '\n' || c == '\r') {
+        Some(i) => {
+            if input[i..].starts_with("\r\n") {
+                "\r\n"
+            } else if input[i..].starts_with("\r") {
+                "\r"
+            } else {
+                "\n"
+            }
+        }
+        None => "\n",
     }
 }
@@ -9,8 +9,8 @@ use {
 use self::types::pretty_print as pretty_types;
 use base::{
     ast::{
-        Do, Expr, Literal, Pattern, PatternField, SpannedExpr, SpannedPattern, ValueBinding,
-        ValueBindings,
+        self, Argument, Do, Expr, Literal, Pattern, PatternField, SpannedExpr, SpannedIdent,
+        SpannedPattern, ValueBinding, ValueBindings,
     },
     kind::Kind,
     metadata::Attribute,
@@ -77,8 +77,45 @@ where
     where
         A: Clone,
     {
-        let doc = self.pretty_expr(expr).1;
-        doc.pretty(width)
+        self.render(width, hardline, self.pretty_expr(expr))
+    }
+
+    /// Formats `expr` without the comments surrounding it
+    pub(super) fn format_node(
+        &self,
+        width: usize,
+        hardline: &'a str,
+        expr: &'a SpannedExpr<I>,
+    ) -> String
+    where
+        A: Clone,
+    {
+        self.render(width, hardline, self.pretty_expr_(expr.span.start(), expr))
+    }
+
+    pub(super) fn format_binding(
+        &self,
+        width: usize,
+        hardline: &'a str,
+        bind: &'a ValueBinding<I>,
+    ) -> String
+    where
+        A: Clone,
+    {
+        self.render(width, hardline, self.pretty_binding(bind))
+    }
+
+    fn render(
+        &self,
+        width: usize,
+        hardline: &'a str,
+        doc: DocBuilder<'a, Arena<'a, A>, A>,
+    ) -> String
+    where
+        A: Clone,
+    {
+        doc.1
+            .pretty(width)
             .to_string()
             .lines()
             .map(|s| format!("{}{}", s.trim_end(), hardline))
@@ -146,6 +183,16 @@ where
         let span = Span::new(previous_end, expr.span.start());
         let comments = self.comments(span);
         let doc = match expr.value {
+            // The prefix form `!r`
+            Expr::App {
+                ref func, ref args, ..
+            } if args.len() == 1
+                && func.span.end() == args[0].span.start()
+                && self.source.src_slice(func.span) == "!" =>
+            {
+                chain![arena, "!", pretty(&args[0])]
+            }
+
             Expr::App {
                 ref implicit_args,
                 ref func,
@@ -162,7 +209,29 @@ where
                             } else {
                                 arena.nil()
                             })
-                            .append(pretty(arg))
+                            .append(match arg.value {
+                                // `f ~name=value` is parsed as `f { name = value }`
+                                Expr::Record { ref exprs, .. }
+                                    if self.source.src_slice(arg.span).starts_with('~') =>
+                                {
+                                    arena.concat(Itertools::intersperse(
+                                        exprs.iter().map(|field| {
+                                            chain![
+                                                arena,
+                                                "~",
+                                                pretty_types::ident(
+                                                    arena,
+                                                    field.name.value.as_ref() as &str
+                                                ),
+                                                "=",
+                                                field.value.as_ref().map_or(arena.nil(), pretty)
+                                            ]
+                                        }),
+                                        arena.line(),
+                                    ))
+                                }
+                                _ => pretty(arg),
+                            })
                     });
                 pretty(func)
                     .append(arena.concat(arg_iter).nest(INDENT))
@@ -218,57 +287,6 @@ where
             .group(),
 
             Expr::LetBindings(ref binds, ref body) => {
-                let binding = |bind: &'a ValueBinding<I>| {
-                    let decl = chain![
-                        arena,
-                        "let ",
-                        chain![
-                            arena,
-                            self.pretty_pattern(&bind.name),
-                            " ",
-                            arena.concat(bind.args.iter().map(|arg| {
-                                chain![
-                                    arena,
-                                    if arg.arg_type == ArgType::Implicit {
-                                        arena.text("?")
-                                    } else {
-                                        arena.nil()
-                                    },
-                                    arena.text(arg.name.value.name.as_ref() as &str).append(" ")
-                                ]
-                            }))
-                        ]
-                        .group(),
-                        match bind.typ {
-                            None => arena.nil(),
-                            Some(ref typ) => {
-                                arena
-                                    .text(": ")
-                                    .append(types::pretty_print(self, typ))
-                                    .append(self.space_after(typ.span().end()))
-                                    .nest(INDENT)
-                            }
-                        },
-                        "="
-                    ]
-                    .group();
-                    chain![
-                        arena,
-                        pretty_types::doc_comment(arena, bind.metadata.comment()),
-                        self.pretty_attributes(bind.metadata.attributes()),
-                        self.hang(
-                            decl,
-                            (self.space_before(bind.expr.span.start()), true),
-                            &bind.expr
-                        )
-                        .group(),
-                        if self.formatter.expanded {
-                            arena.hardline()
-                        } else {
-                            arena.nil()
-                        }
-                    ]
-                };
                 let is_recursive = match binds {
                     ValueBindings::Recursive(_) => true,
                     ValueBindings::Plain(_) => false,
@@ -287,7 +305,7 @@ where
                     arena.concat(
                         binds
                             .iter()
-                            .map(|bind| binding(bind))
+                            .map(|bind| self.pretty_binding(bind))
                             .interleave(newlines_iter!(self, binds.iter().map(|bind| bind.span())))
                     ),
                     if is_recursive {
@@ -351,7 +369,11 @@ where
                 arena,
                 pretty(expr),
                 ".",
-                pretty_types::ident(arena, field.as_ref() as &str)
+                match types::tuple_index(field.as_ref()) {
+                    // Tuple elements are written as `t.0`
+                    Some(index) => arena.text(index.to_string()),
+                    None => pretty_types::ident(arena, field.as_ref() as &str),
+                }
             ],
 
             Expr::Record { .. } | Expr::Tuple { .. } | Expr::Lambda(_) => {
@@ -543,6 +565,78 @@ where
         }
     }
 
+    fn pretty_binding(&self, bind: &'a ValueBinding<I>) -> DocBuilder<'a, Arena<'a, A>, A>
+    where
+        A: Clone,
+    {
+        let arena = self.arena;
+        let (args, body) = self.pretty_arguments(&bind.args, &bind.expr);
+        let decl = chain![
+            arena,
+            "let ",
+            chain![arena, self.pretty_pattern(&bind.name), " ", args].group(),
+            match bind.typ {
+                None => arena.nil(),
+                Some(ref typ) => {
+                    arena
+                        .text(": ")
+                        .append(types::pretty_print(self, typ))
+                        .append(self.space_after(typ.span().end()))
+                        .nest(INDENT)
+                }
+            },
+            "="
+        ]
+        .group();
+        chain![
+            arena,
+            pretty_types::doc_comment(arena, bind.metadata.comment()),
+            self.pretty_attributes(bind.metadata.attributes()),
+            self.hang(decl, (self.space_before(body.span.start()), true), body)
+                .group(),
+            if self.formatter.expanded {
+                arena.hardline()
+            } else {
+                arena.nil()
+            }
+        ]
+    }
+
+    /// Prints the arguments of a function, arguments which were written as patterns are printed as
+    /// patterns and the `let` binding them is skipped in the returned body
+    fn pretty_arguments<'ast>(
+        &self,
+        args: &'a [Argument<SpannedIdent<I>>],
+        mut body: &'a SpannedExpr<'ast, I>,
+    ) -> (DocBuilder<'a, Arena<'a, A>, A>, &'a SpannedExpr<'ast, I>)
+    where
+        A: Clone,
+    {
+        let arena = self.arena;
+        let mut doc = arena.nil();
+        for arg in args {
+            let arg_doc = match ast::argument_pattern(arg, body) {
+                Some((pattern, rest)) => {
+                    body = rest;
+                    self.pretty_pattern(pattern)
+                }
+                None => arena.text(arg.name.value.name.as_ref() as &str),
+            };
+            doc = chain![
+                arena,
+                doc,
+                if arg.arg_type == ArgType::Implicit {
+                    arena.text("?")
+                } else {
+                    arena.nil()
+                },
+                arg_doc,
+                " "
+            ];
+        }
+        (doc, body)
+    }
+
     fn pretty_if_expr(&self, mut expr: &'a SpannedExpr<I>) -> DocBuilder<'a, Arena<'a, A>, A>
     where
         A: Clone,
@@ -600,20 +694,11 @@ where
         let arena = self.arena;
         match expr.value {
             Expr::Lambda(ref lambda) => {
-                let from = chain![
-                    arena,
-                    "\\",
-                    arena.concat(lambda.args.iter().map(|arg| {
-                        arena.text(arg.name.value.name.as_ref() as &str).append(" ")
-                    })),
-                    "->"
-                ];
+                let (args, body) = self.pretty_arguments(&lambda.args, &lambda.body);
+                let from = chain![arena, "\\", args, "->"];
                 decls.push((body_spacing, from));
-                let (body, trailer) = self.pretty_lambda(
-                    decls,
-                    (self.space_before(lambda.body.span.start()), true),
-                    &lambda.body,
-                );
+                let (body, trailer) =
+                    self.pretty_lambda(decls, (self.space_before(body.span.start()), true), body);
                 (body, trailer)
             }
             Expr::Record {
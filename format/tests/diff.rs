@@ -0,0 +1,210 @@
+extern crate gluon_format as format;
+
+use gluon::{base::ast::OwnedExpr, base::symbol::Symbol, RootedThread, ThreadExt, VmBuilder};
+
+use format::{diff, Binding, BindingKind, Change};
+
+fn new_vm() -> RootedThread {
+    let thread = VmBuilder::new()
+        .import_paths(Some(vec![".".into(), "..".into()]))
+        .build();
+    thread.get_database_mut().set_implicit_prelude(false);
+    thread
+}
+
+fn parse(src: &str) -> OwnedExpr<Symbol> {
+    let thread = new_vm();
+    let type_cache = thread.global_env().type_cache();
+    thread.parse_expr(type_cache, "test", src).unwrap()
+}
+
+fn parsed_diff(old: &str, new: &str) -> Vec<Change> {
+    diff(parse(old).expr(), parse(new).expr())
+}
+
+fn typed_diff(old: &str, new: &str) -> Vec<Change> {
+    let (old, _) = new_vm().typecheck_str("test", old, None).unwrap();
+    let (new, _) = new_vm().typecheck_str("test", new, None).unwrap();
+    diff(old.expr(), new.expr())
+}
+
+fn value(name: &str) -> Binding {
+    Binding {
+        name: name.into(),
+        kind: BindingKind::Value,
+    }
+}
+
+fn type_(name: &str) -> Binding {
+    Binding {
+        name: name.into(),
+        kind: BindingKind::Type,
+    }
+}
+
+#[test]
+fn formatting_and_order_are_ignored() {
+    let old = r#"
+let x = 1
+let y = x + 2
+{ x, y }
+"#;
+    let new = r#"
+// A comment
+let y =
+    x + 2
+let x = 1
+{ x, y }
+"#;
+    assert_eq!(parsed_diff(old, new), vec![]);
+}
+
+#[test]
+fn added_removed_and_changed_bindings() {
+    let old = r#"
+let x = 1
+let y = x + 2
+let z = "abc"
+{ x, y, z }
+"#;
+    let new = r#"
+let x = 1
+let y = x + 3
+let w = 1.0
+{ x, y, w }
+"#;
+    assert_eq!(
+        parsed_diff(old, new),
+        vec![
+            Change::BodyChanged(value("y")),
+            Change::Added(value("w")),
+            Change::Removed(value("z")),
+        ]
+    );
+}
+
+#[test]
+fn renamed_binding() {
+    let old = r#"
+let add_one x = x + 1
+let y = add_one 2
+{ add_one, y }
+"#;
+    let new = r#"
+let increment x = x + 1
+let y = increment 2
+{ increment, y }
+"#;
+    assert_eq!(
+        parsed_diff(old, new),
+        vec![
+            Change::Renamed {
+                old: value("add_one"),
+                new: value("increment"),
+            },
+            Change::BodyChanged(value("y")),
+        ]
+    );
+}
+
+#[test]
+fn renamed_arguments_change_the_body() {
+    let old = r#"
+let f x = x
+f
+"#;
+    let new = r#"
+let f y = y
+f
+"#;
+    assert_eq!(parsed_diff(old, new), vec![Change::BodyChanged(value("f"))]);
+}
+
+#[test]
+fn annotated_signature_changed() {
+    let old = r#"
+let f x : Int -> Int = x
+f
+"#;
+    let new = r#"
+let f x : Float -> Float = x
+f
+"#;
+    assert_eq!(
+        parsed_diff(old, new),
+        vec![Change::SignatureChanged {
+            binding: value("f"),
+            old: "Int -> Int".into(),
+            new: "Float -> Float".into(),
+        }]
+    );
+}
+
+#[test]
+fn inferred_signature_changed() {
+    let old = r#"
+let f x = 1
+let g x = x
+{ f, g }
+"#;
+    let new = r#"
+let f x = "abc"
+let g y = y
+{ f, g }
+"#;
+    assert_eq!(
+        typed_diff(old, new),
+        vec![
+            Change::SignatureChanged {
+                binding: value("f"),
+                old: "a -> Int".into(),
+                new: "a -> String".into(),
+            },
+            Change::BodyChanged(value("g")),
+        ]
+    );
+}
+
+#[test]
+fn type_bindings() {
+    let old = r#"
+type Point = { x : Int, y : Int }
+type Name = String
+type Config = { port : Int = 8080 }
+()
+"#;
+    let new = r#"
+type Point = { x : Float, y : Float }
+type Identifier = String
+type Config = { port : Int = 80 }
+()
+"#;
+    assert_eq!(
+        parsed_diff(old, new),
+        vec![
+            Change::SignatureChanged {
+                binding: type_("Point"),
+                old: "= { x : Int, y : Int }".into(),
+                new: "= { x : Float, y : Float }".into(),
+            },
+            Change::Renamed {
+                old: type_("Name"),
+                new: type_("Identifier"),
+            },
+            Change::SignatureChanged {
+                binding: type_("Config"),
+                old: "= { port : Int = 8080 }".into(),
+                new: "= { port : Int = 80 }".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn display_change() {
+    let change = Change::Renamed {
+        old: type_("Name"),
+        new: type_("Identifier"),
+    };
+    assert_eq!(change.to_string(), "renamed `type Name` to `Identifier`");
+}
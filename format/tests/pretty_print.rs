@@ -6,7 +6,17 @@ extern crate gluon_format as format;
 
 use {difference::assert_diff, expect_test::expect};
 
-use gluon::{RootedThread, ThreadExt, VmBuilder};
+use gluon::{
+    base::{
+        ast::{Expr, RootExpr, ValueBinding},
+        source::FileMap,
+        symbol::{Symbol, Symbols},
+        types::TypeCache,
+    },
+    RootedThread, ThreadExt, VmBuilder,
+};
+
+use format::InsertionPoint;
 
 macro_rules! test_format {
     ($name: ident, $initial: expr) => {
@@ -66,6 +76,14 @@ fn dont_lose_information_in_literals() {
     assert_eq!(&format_expr(expr).unwrap(), expr);
 }
 
+#[test]
+fn preserve_radix_of_numeric_literals() {
+    let expr = r#"
+(0b1010, -0xFF, 0o17b, 1_000_000, 1.5e-1_0)
+"#;
+    assert_eq!(&format_expr(expr).unwrap(), expr);
+}
+
 #[test]
 fn raw_string_literal() {
     let expr = r####"
@@ -237,6 +255,23 @@ x
     assert_diff!(&format_expr(expr).unwrap(), expr, "\n", 0);
 }
 
+#[test]
+fn preserve_named_arguments() {
+    let expr = r#"
+draw 1 ~width=3 ~color="red"
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, "\n", 0);
+}
+
+#[test]
+fn preserve_record_field_defaults() {
+    let expr = r#"
+type Config = { port : Int = 8080, host : String = "0.0.0.0" }
+x
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, "\n", 0);
+}
+
 #[test]
 fn doc_comment_in_record_expr() {
     let expr = r#"
@@ -783,6 +818,18 @@ type OpenVariant r a = .. r
     assert_diff!(&format_expr(expr).unwrap(), expr, "\n", 0);
 }
 
+test_format! {
+    tuple_field_access,
+    r#"
+let t = ((1, 2), 3)
+t.0.1 + t._1
+"#,
+    r#"
+let t = ((1, 2), 3)
+t.0.1 + t.1
+"#
+}
+
 test_format! {
     issue_793_1,
 r#"
@@ -837,3 +884,122 @@ io.println "World"
     "#]]
     .assert_eq(&format_expr(expr).unwrap());
 }
+
+fn parse_snippet(source: &FileMap) -> RootExpr<Symbol> {
+    gluon::parser::parse_partial_root_expr(&mut Symbols::new(), &TypeCache::default(), source)
+        .unwrap_or_else(|(_, err)| panic!("{}", err))
+}
+
+fn first_binding<'a>(expr: &'a RootExpr<Symbol>) -> &'a ValueBinding<'a, Symbol> {
+    match &expr.expr().value {
+        Expr::LetBindings(binds, _) => &binds[0],
+        expr => panic!("Expected a binding, found {:?}", expr),
+    }
+}
+
+#[test]
+fn inserted_binding_matches_indentation() {
+    let source = FileMap::new(
+        "snippet".into(),
+        "let   { map }   =  import! std.functor\n()".into(),
+    );
+    let expr = parse_snippet(&source);
+    let target = "let f x =\n    let y = x\n    y\n";
+    let position = target.find("    let y").unwrap();
+
+    let text = format::Formatter::default().pretty_inserted_binding(
+        &source,
+        first_binding(&expr),
+        &InsertionPoint::new(target, position),
+    );
+    assert_eq!(text, "    let { map } = import! std.functor\n");
+
+    let text = format::Formatter::default().pretty_inserted_binding(
+        &source,
+        first_binding(&expr),
+        &InsertionPoint::new(target, position + 4),
+    );
+    assert_eq!(text, "let { map } = import! std.functor\n    ");
+}
+
+#[test]
+fn inserted_binding_indents_continuation_lines() {
+    let source = FileMap::new(
+        "snippet".into(),
+        r#"
+let { Functor, Applicative, Monad, map, wrap, flat_map, join, apply, ap, map2, map3, (<*>), (>>=) } = import! std.prelude
+()
+"#
+        .into(),
+    );
+    let expr = parse_snippet(&source);
+    let target = "let f x =\r\n    x\r\n";
+    let position = target.find("    x").unwrap();
+
+    let text = format::Formatter::default().pretty_inserted_binding(
+        &source,
+        first_binding(&expr),
+        &InsertionPoint::new(target, position),
+    );
+    assert_eq!(text.matches("\r\n").count(), text.matches('\n').count());
+    assert_eq!(
+        text.replace("\r\n", "\n"),
+        r#"    let {
+        Functor,
+        Applicative,
+        Monad,
+        map,
+        wrap,
+        flat_map,
+        join,
+        apply,
+        ap,
+        map2,
+        map3,
+        (<*>),
+        (>>=)
+    } =
+        import! std.prelude
+"#
+    );
+}
+
+#[test]
+fn inserted_expr_in_line() {
+    let source = FileMap::new("snippet".into(), "f   1 (  g 2 )".into());
+    let expr = parse_snippet(&source);
+    let target = "let x =\n    io.println \n    ()";
+    let position = target.find("\n    ()").unwrap();
+
+    let text = format::Formatter::default().pretty_inserted_expr(
+        &source,
+        expr.expr(),
+        &InsertionPoint::new(target, position),
+    );
+    assert_eq!(text, "f 1 (g 2)");
+}
+
+#[test]
+fn record_pattern_arguments() {
+    let expr = r#"
+let add { x, y } = x + y
+let f = \{ x } y -> x + y
+rec let g n { step } = g (n + step) { step }
+1
+"#;
+    assert_eq!(&format_expr(expr).unwrap(), expr);
+}
+
+#[test]
+fn desugared_expressions_are_formatted_as_written() {
+    let expr = r#"
+import! std.list as list
+import! { map } from std.functor
+let r = ref 1
+r := !r + 2
+do x = r
+do y = r
+wrap (x, y)
+"#;
+    assert_eq!(&format_expr(expr).unwrap(), expr);
+}
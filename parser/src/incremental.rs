@@ -0,0 +1,290 @@
+//! Incremental reparsing of the top level `let` and `type` bindings of a module.
+//!
+//! A module is parsed into a chain of binding groups (`let a = .. let b = .. type C = .. body`).
+//! When an edit falls inside a single group only the source of that group is reparsed, the
+//! groups before it are left untouched and the groups after it are reused with their spans
+//! shifted by the size of the edit.
+
+use std::marker::PhantomData;
+
+use crate::base::{
+    ast::{
+        self, walk_mut_alias, walk_mut_ast_type, walk_mut_expr, walk_mut_pattern, AstType, Expr,
+        IdentEnv, MutVisitor, SpannedAlias, SpannedExpr, SpannedIdent, SpannedPattern,
+    },
+    pos::{self, ByteOffset, BytePos, Span, Spanned},
+    types::{ArcType, TypeCache},
+};
+
+use crate::{parse_partial_expr, ParseErrors, ParserSource};
+
+/// A replacement of `span` in the old source with `new_text`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TextEdit {
+    pub span: Span<BytePos>,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    pub fn new(span: Span<BytePos>, new_text: impl Into<String>) -> Self {
+        TextEdit {
+            span,
+            new_text: new_text.into(),
+        }
+    }
+
+    /// Returns the source which results from applying this edit to `source`
+    pub fn apply<S>(&self, source: &S) -> String
+    where
+        S: ?Sized + ParserSource,
+    {
+        let src = source.src();
+        let (start, end) = self.source_range(source);
+        let mut new_src = String::with_capacity(src.len() - (end - start) + self.new_text.len());
+        new_src.push_str(&src[..start]);
+        new_src.push_str(&self.new_text);
+        new_src.push_str(&src[end..]);
+        new_src
+    }
+
+    fn source_range<S>(&self, source: &S) -> (usize, usize)
+    where
+        S: ?Sized + ParserSource,
+    {
+        let offset = source.start_index().to_usize();
+        (
+            self.span.start().to_usize() - offset,
+            self.span.end().to_usize() - offset,
+        )
+    }
+
+    /// How far the text after the edit moves
+    fn delta(&self) -> ByteOffset {
+        let old_len = self.span.end().to_usize() - self.span.start().to_usize();
+        ByteOffset::from(self.new_text.len() as i64 - old_len as i64)
+    }
+}
+
+/// Source text starting at an arbitrary position
+struct Fragment {
+    src: String,
+    start: BytePos,
+}
+
+impl ParserSource for Fragment {
+    fn src(&self) -> &str {
+        &self.src
+    }
+    fn start_index(&self) -> BytePos {
+        self.start
+    }
+}
+
+/// Text appended after a binding group so that it can be parsed on its own
+const PLACEHOLDER_BODY: &str = "()";
+
+/// Updates `expr`, which must have been parsed from `old_source`, so that it matches the source
+/// which results from applying `edit` to `old_source`. The new source is assumed to start at the
+/// same index as `old_source`.
+///
+/// If the edit is contained within a single top level binding group, only that group is
+/// reparsed. Otherwise, or if the group no longer parses cleanly on its own, the whole new source
+/// is parsed instead.
+pub fn reparse_incremental<'ast, Id, S>(
+    arena: ast::ArenaRef<'_, 'ast, Id>,
+    symbols: &mut dyn IdentEnv<Ident = Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    expr: &mut SpannedExpr<'ast, Id>,
+    old_source: &S,
+    edit: &TextEdit,
+) -> Result<(), ParseErrors>
+where
+    Id: Clone + AsRef<str> + std::fmt::Debug,
+    S: ?Sized + ParserSource,
+{
+    let new_source = Fragment {
+        src: edit.apply(old_source),
+        start: old_source.start_index(),
+    };
+
+    if let Some(depth) = find_edited_group(expr, old_source, edit) {
+        if reparse_group(arena, symbols, type_cache, expr, depth, &new_source, edit) {
+            return Ok(());
+        }
+    }
+
+    debug!("Falling back to a full reparse");
+    match parse_partial_expr(arena, symbols, type_cache, &new_source) {
+        Ok(new_expr) => {
+            *expr = new_expr;
+            Ok(())
+        }
+        Err((new_expr, errors)) => {
+            *expr = new_expr.unwrap_or_else(|| pos::spanned(new_source.span(), Expr::Error(None)));
+            Err(errors)
+        }
+    }
+}
+
+fn group_body<'a, 'ast, Id>(expr: &'a SpannedExpr<'ast, Id>) -> Option<&'a SpannedExpr<'ast, Id>> {
+    match &expr.value {
+        Expr::LetBindings(_, body) | Expr::TypeBindings(_, body) => Some(&**body),
+        _ => None,
+    }
+}
+
+fn group_body_mut<'a, 'ast, Id>(
+    expr: &'a mut SpannedExpr<'ast, Id>,
+) -> Option<&'a mut SpannedExpr<'ast, Id>> {
+    match &mut expr.value {
+        Expr::LetBindings(_, body) | Expr::TypeBindings(_, body) => Some(&mut **body),
+        _ => None,
+    }
+}
+
+/// Returns how many binding groups must be skipped to reach the group containing `edit`
+fn find_edited_group<Id, S>(expr: &SpannedExpr<Id>, source: &S, edit: &TextEdit) -> Option<usize>
+where
+    S: ?Sized + ParserSource,
+{
+    let src = source.src();
+    let starts_line = |pos: BytePos| {
+        let index = pos.to_usize() - source.start_index().to_usize();
+        index == 0 || src[..index].ends_with('\n')
+    };
+
+    let mut group = expr;
+    let mut depth = 0;
+    while let Some(body) = group_body(group) {
+        if edit.span.start() < group.span.start() {
+            return None;
+        }
+        if edit.span.end() <= body.span.start() {
+            // The group is parsed on its own so it must be laid out at the start of a line, as
+            // must the body which follows it
+            return if starts_line(group.span.start()) && starts_line(body.span.start()) {
+                Some(depth)
+            } else {
+                None
+            };
+        }
+        group = body;
+        depth += 1;
+    }
+    None
+}
+
+fn reparse_group<'ast, Id>(
+    arena: ast::ArenaRef<'_, 'ast, Id>,
+    symbols: &mut dyn IdentEnv<Ident = Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    expr: &mut SpannedExpr<'ast, Id>,
+    depth: usize,
+    new_source: &Fragment,
+    edit: &TextEdit,
+) -> bool
+where
+    Id: Clone + AsRef<str> + std::fmt::Debug,
+{
+    let delta = edit.delta();
+
+    let mut group = &*expr;
+    for _ in 0..depth {
+        group = group_body(group).expect("group");
+    }
+    let group_start = group.span.start();
+    let body_start = group_body(group).expect("group").span.start() + delta;
+
+    let offset = new_source.start.to_usize();
+    let mut fragment = Fragment {
+        src: new_source.src[group_start.to_usize() - offset..body_start.to_usize() - offset]
+            .to_string(),
+        start: group_start,
+    };
+    fragment.src.push_str(PLACEHOLDER_BODY);
+
+    let mut new_group = match parse_partial_expr(arena, symbols, type_cache, &fragment) {
+        Ok(new_group) => new_group,
+        Err(_) => return false,
+    };
+    match group_body(&new_group) {
+        // The edit may have merged or split groups in which case the placeholder ends up
+        // somewhere else
+        Some(body) if body.span.start() == body_start => (),
+        _ => return false,
+    }
+
+    // Only commit to modifying `expr` once the group is known to have been reparsed
+    let mut group = expr;
+    for _ in 0..depth {
+        group.span = Span::new(group.span.start(), group.span.end() + delta);
+        group = group_body_mut(group).expect("group");
+    }
+
+    let mut body = std::mem::take(group_body_mut(&mut *group).expect("group"));
+    ShiftSpans(delta, PhantomData).visit_expr(&mut body);
+
+    let end = body.span.end();
+    *group_body_mut(&mut new_group).expect("group") = body;
+    new_group.span = Span::new(new_group.span.start(), end);
+    *group = new_group;
+
+    true
+}
+
+/// Moves every span in the visited tree by a fixed offset
+struct ShiftSpans<Id>(ByteOffset, PhantomData<Id>);
+
+impl<Id> ShiftSpans<Id> {
+    fn shift(&self, span: &mut Span<BytePos>) {
+        // Nodes created by the parser without a location in the source (such as the empty row of
+        // a record type) keep their default span
+        if *span == Span::default() {
+            return;
+        }
+        *span = Span::new(span.start() + self.0, span.end() + self.0);
+    }
+}
+
+impl<'a, 'ast, Id> MutVisitor<'a, 'ast> for ShiftSpans<Id>
+where
+    Id: 'a + 'ast,
+{
+    type Ident = Id;
+
+    fn visit_expr(&mut self, e: &'a mut SpannedExpr<'ast, Id>) {
+        self.shift(&mut e.span);
+        if let Expr::TypeBindings(bindings, _) = &mut e.value {
+            for bind in bindings.iter_mut() {
+                for default in bind.defaults.iter_mut() {
+                    self.shift(&mut default.name.span);
+                }
+            }
+        }
+        walk_mut_expr(self, e);
+    }
+
+    fn visit_pattern(&mut self, p: &'a mut SpannedPattern<'ast, Id>) {
+        self.shift(&mut p.span);
+        walk_mut_pattern(self, &mut p.value);
+    }
+
+    fn visit_spanned_typed_ident(&mut self, id: &'a mut SpannedIdent<Id>) {
+        self.shift(&mut id.span);
+        self.visit_ident(&mut id.value)
+    }
+
+    fn visit_alias(&mut self, alias: &'a mut SpannedAlias<'ast, Id>) {
+        self.shift(&mut alias.span);
+        walk_mut_alias(self, alias);
+    }
+
+    fn visit_spanned_ident(&mut self, id: &'a mut Spanned<Id, BytePos>) {
+        self.shift(&mut id.span);
+    }
+
+    fn visit_ast_type(&mut self, s: &'a mut AstType<'ast, Id>) {
+        self.shift(s.span_mut());
+        walk_mut_ast_type(self, s);
+    }
+}
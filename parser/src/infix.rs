@@ -14,6 +14,7 @@ use std::fmt;
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::mem;
+use std::str::FromStr;
 
 /// The fixity (associativity) of an infix operator
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -55,7 +56,7 @@ pub struct OpMeta {
 }
 
 impl OpMeta {
-    pub fn new(precedence: i32, fixity: Fixity) -> OpMeta {
+    pub const fn new(precedence: i32, fixity: Fixity) -> OpMeta {
         OpMeta {
             precedence: precedence,
             fixity: fixity,
@@ -69,11 +70,72 @@ impl fmt::Display for OpMeta {
     }
 }
 
+impl FromStr for OpMeta {
+    type Err = Error;
+
+    /// Parses the contents of an `#[infix(..)]` attribute such as `left, 6`
+    fn from_str(s: &str) -> Result<OpMeta, Error> {
+        let mut iter = s.splitn(2, ",");
+        let fixity = match iter.next().ok_or(Error::InvalidFixity)?.trim() {
+            "left" => Fixity::Left,
+            "right" => Fixity::Right,
+            _ => {
+                return Err(Error::InvalidFixity);
+            }
+        };
+        let precedence = iter
+            .next()
+            .and_then(|s| s.trim().parse().ok())
+            .and_then(|precedence| {
+                if precedence >= 0 {
+                    Some(precedence)
+                } else {
+                    None
+                }
+            })
+            .ok_or(Error::InvalidPrecedence)?;
+        Ok(OpMeta { fixity, precedence })
+    }
+}
+
+/// The operators of the primitive operations (`#Int+`, `#Float==` etc) and of `&&` and `||`, which
+/// have a fixed precedence instead of being declared with `#[infix(..)]`
+pub const BUILTIN_OPERATORS: &[(&str, OpMeta)] = &[
+    ("*", OpMeta::new(7, Fixity::Left)),
+    ("/", OpMeta::new(7, Fixity::Left)),
+    ("+", OpMeta::new(6, Fixity::Left)),
+    ("-", OpMeta::new(6, Fixity::Left)),
+    ("==", OpMeta::new(4, Fixity::Left)),
+    ("/=", OpMeta::new(4, Fixity::Left)),
+    ("<", OpMeta::new(4, Fixity::Left)),
+    (">", OpMeta::new(4, Fixity::Left)),
+    ("<=", OpMeta::new(4, Fixity::Left)),
+    (">=", OpMeta::new(4, Fixity::Left)),
+    ("&&", OpMeta::new(3, Fixity::Right)),
+    ("||", OpMeta::new(2, Fixity::Right)),
+];
+
+/// Which side of an infix operator an operand is on
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Operand {
+    Lhs,
+    Rhs,
+}
+
 /// A table of operator metadata
+#[derive(Clone, Debug)]
 pub struct OpTable<Id> {
     pub operators: FnvMap<Id, OpMeta>,
 }
 
+impl<Id> Default for OpTable<Id> {
+    fn default() -> Self {
+        OpTable {
+            operators: FnvMap::default(),
+        }
+    }
+}
+
 impl<Id> OpTable<Id> {
     pub fn new<I>(ops: I) -> OpTable<Id>
     where
@@ -84,6 +146,14 @@ impl<Id> OpTable<Id> {
             operators: ops.into_iter().collect(),
         }
     }
+
+    /// Declares the fixity and precedence of `name`, returning the previous declaration
+    pub fn insert(&mut self, name: Id, meta: OpMeta) -> Option<OpMeta>
+    where
+        Id: Eq + Hash,
+    {
+        self.operators.insert(name, meta)
+    }
 }
 
 impl<Id> OpTable<Id>
@@ -99,107 +169,40 @@ where
         })
     }
 
-    fn get(&self, name: &Id) -> Option<&OpMeta> {
+    /// Returns the fixity and precedence of `name`, which is either declared in the table or is
+    /// one of the `BUILTIN_OPERATORS`
+    pub fn get(&self, name: &Id) -> Option<&OpMeta> {
         self.operators.get(name).or_else(|| {
             let name = name.as_ref();
             if name.starts_with('#') || name == "&&" || name == "||" {
-                const OPS: &[(&str, OpMeta)] = &[
-                    (
-                        "*",
-                        OpMeta {
-                            precedence: 7,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "/",
-                        OpMeta {
-                            precedence: 7,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "+",
-                        OpMeta {
-                            precedence: 6,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "-",
-                        OpMeta {
-                            precedence: 6,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "==",
-                        OpMeta {
-                            precedence: 4,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "/=",
-                        OpMeta {
-                            precedence: 4,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "<",
-                        OpMeta {
-                            precedence: 4,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        ">",
-                        OpMeta {
-                            precedence: 4,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "<=",
-                        OpMeta {
-                            precedence: 4,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        ">=",
-                        OpMeta {
-                            precedence: 4,
-                            fixity: Fixity::Left,
-                        },
-                    ),
-                    (
-                        "&&",
-                        OpMeta {
-                            precedence: 3,
-                            fixity: Fixity::Right,
-                        },
-                    ),
-                    (
-                        "||",
-                        OpMeta {
-                            precedence: 2,
-                            fixity: Fixity::Right,
-                        },
-                    ),
-                ];
-
                 let op = name
                     .trim_start_matches('#')
                     .trim_start_matches(char::is_alphanumeric);
 
-                OPS.iter().find(|t| t.0 == op).map(|t| &t.1)
+                BUILTIN_OPERATORS.iter().find(|t| t.0 == op).map(|t| &t.1)
             } else {
                 None
             }
         })
     }
+
+    /// Returns true if an infix expression using `operand_op` must be wrapped in parentheses when
+    /// it is the `side` operand of `op`. Operators without a known fixity always need parentheses.
+    pub fn needs_parens(&self, op: &Id, operand_op: &Id, side: Operand) -> bool {
+        let (op, operand_op) = match (self.get(op), self.get(operand_op)) {
+            (Some(op), Some(operand_op)) => (op, operand_op),
+            _ => return true,
+        };
+        match operand_op.precedence.cmp(&op.precedence) {
+            Ordering::Greater => false,
+            Ordering::Less => true,
+            Ordering::Equal => match (op.fixity, operand_op.fixity, side) {
+                (Fixity::Left, Fixity::Left, Operand::Lhs)
+                | (Fixity::Right, Fixity::Right, Operand::Rhs) => false,
+                _ => true,
+            },
+        }
+    }
 }
 
 pub struct Reparser<'s, 'ast, Id: 's> {
@@ -867,4 +870,41 @@ mod tests {
 
         assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
+
+    #[test]
+    fn parse_op_meta() {
+        assert_eq!("left, 6".parse(), Ok(OpMeta::new(6, Fixity::Left)));
+        assert_eq!(" right,0 ".parse(), Ok(OpMeta::new(0, Fixity::Right)));
+        assert_eq!("up, 6".parse::<OpMeta>(), Err(InvalidFixity));
+        assert_eq!("left, -1".parse::<OpMeta>(), Err(InvalidPrecedence));
+        assert_eq!("left".parse::<OpMeta>(), Err(InvalidPrecedence));
+    }
+
+    #[test]
+    fn needs_parens() {
+        let ops = OpTable::new(vec![
+            ("+".to_string(), OpMeta::new(6, Fixity::Left)),
+            ("*".to_string(), OpMeta::new(7, Fixity::Left)),
+            ("++".to_string(), OpMeta::new(5, Fixity::Right)),
+        ]);
+        let needs_parens = |op: &str, operand_op: &str, side| {
+            ops.needs_parens(&op.into(), &operand_op.into(), side)
+        };
+
+        // (1 * 2) + 3
+        assert!(!needs_parens("+", "*", Operand::Lhs));
+        // (1 + 2) * 3
+        assert!(needs_parens("*", "+", Operand::Lhs));
+        // (1 + 2) + 3 and 1 + (2 + 3)
+        assert!(!needs_parens("+", "+", Operand::Lhs));
+        assert!(needs_parens("+", "+", Operand::Rhs));
+        // (a ++ b) ++ c and a ++ (b ++ c)
+        assert!(needs_parens("++", "++", Operand::Lhs));
+        assert!(!needs_parens("++", "++", Operand::Rhs));
+        // Builtin operators have a fixity without being declared
+        assert!(!needs_parens("#Int+", "#Int*", Operand::Rhs));
+        assert!(needs_parens("&&", "||", Operand::Rhs));
+        // Undeclared operators
+        assert!(needs_parens("+", "<>", Operand::Lhs));
+    }
 }
@@ -21,8 +21,8 @@ use itertools::Either;
 
 use crate::base::{
     ast::{
-        self, AstType, Do, Expr, IdentEnv, PatternField, RootExpr, Sp, SpannedExpr, SpannedPattern,
-        TypedIdent, ValueBinding,
+        self, AstType, Do, Expr, IdentEnv, PatternField, RootExpr, Sp, SpannedExpr, SpannedIdent,
+        SpannedPattern, TypedIdent, ValueBinding, ValueBindings,
     },
     error::{AsDiagnostic, Errors},
     fnv::FnvMap,
@@ -35,14 +35,17 @@ use crate::base::{
 };
 
 use crate::{
-    infix::{Fixity, OpMeta, OpTable, Reparser},
+    infix::{OpMeta, OpTable, Reparser},
     layout::Layout,
-    token::{BorrowedToken, Tokenizer},
+    token::BorrowedToken,
 };
 
 pub use crate::{
-    infix::Error as InfixError, layout::Error as LayoutError, token::Error as TokenizeError,
-    token::Token,
+    incremental::{reparse_incremental, TextEdit},
+    infix::Error as InfixError,
+    layout::Error as LayoutError,
+    token::Error as TokenizeError,
+    token::{nearest_checkpoint, Checkpoint as TokenizerCheckpoint, Token, Tokenizer},
 };
 
 lalrpop_mod!(
@@ -51,6 +54,7 @@ lalrpop_mod!(
     grammar
 );
 
+mod incremental;
 pub mod infix;
 mod layout;
 mod str_suffix;
@@ -63,6 +67,50 @@ fn new_ident<Id>(type_cache: &TypeCache<Id, ArcType<Id>>, name: Id) -> TypedIden
     }
 }
 
+type PatternArgument<'ast, Id> = (
+    ast::Argument<SpannedIdent<Id>>,
+    Option<SpannedPattern<'ast, Id>>,
+);
+
+/// Binds the arguments which were written as patterns with a `let` at the start of `body`, in
+/// the same order as the arguments
+fn bind_pattern_arguments<'ast, Id>(
+    arena: ast::ArenaRef<'_, 'ast, Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    args: Vec<PatternArgument<'ast, Id>>,
+    mut body: SpannedExpr<'ast, Id>,
+) -> (
+    &'ast mut [ast::Argument<SpannedIdent<Id>>],
+    SpannedExpr<'ast, Id>,
+)
+where
+    Id: Clone,
+{
+    let mut patterns = Vec::new();
+    let args = arena.alloc_extend(args.into_iter().map(|(arg, pattern)| {
+        if let Some(pattern) = pattern {
+            patterns.push((arg.name.clone(), pattern));
+        }
+        arg
+    }));
+    for (name, pattern) in patterns.into_iter().rev() {
+        let span = Span::new(pattern.span.start(), body.span.end());
+        let bind = arena.alloc(ValueBinding {
+            metadata: Default::default(),
+            name: pattern,
+            typ: None,
+            resolved_type: type_cache.hole(),
+            args: &mut [],
+            expr: pos::spanned(name.span, Expr::Ident(name.value)),
+        });
+        body = pos::spanned(
+            span,
+            Expr::LetBindings(ValueBindings::Plain(bind), arena.alloc(body)),
+        );
+    }
+    (args, body)
+}
+
 type LalrpopError<'input> =
     lalrpop_util::ParseError<BytePos, BorrowedToken<'input>, Spanned<Error, BytePos>>;
 
@@ -330,10 +378,12 @@ impl_temp_vec! {
     ast::ExprField<'ast, Id, ArcType<Id>> => expr_field_types,
     ast::ExprField<'ast, Id, SpannedExpr<'ast, Id>> => expr_field_exprs,
     ast::TypeBinding<'ast, Id> => type_bindings,
+    (Spanned<Id, BytePos>, SpannedExpr<'ast, Id>) => field_defaults,
     ValueBinding<'ast, Id> => value_bindings,
     ast::Do<'ast, Id> => do_exprs,
     ast::Alternative<'ast, Id> => alts,
     ast::Argument<ast::SpannedIdent<Id>> => args,
+    PatternArgument<'ast, Id> => pattern_args,
     FieldExpr<'ast, Id> => field_expr,
     ast::InnerAstType<'ast, Id> => types,
     AstType<'ast, Id> => type_ptrs,
@@ -438,6 +488,30 @@ pub fn parse_expr<'ast>(
     parse_partial_expr(arena, symbols, type_cache, input).map_err(|t| t.1)
 }
 
+/// Parses a standalone type expression such as `Option Int`
+pub fn parse_partial_type<'ast, Id, S>(
+    arena: ast::ArenaRef<'_, 'ast, Id>,
+    symbols: &mut dyn IdentEnv<Ident = Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    input: &S,
+) -> Result<AstType<'ast, Id>, (Option<AstType<'ast, Id>>, ParseErrors)>
+where
+    Id: Clone + AsRef<str> + std::fmt::Debug,
+    S: ?Sized + ParserSource,
+{
+    parse_with(input, &mut |parse_errors, layout| {
+        grammar::TopTypeParser::new().parse(
+            &input,
+            type_cache,
+            arena,
+            symbols,
+            parse_errors,
+            &mut TempVecs::new(),
+            layout,
+        )
+    })
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ReplLine<'ast, Id> {
     Expr(SpannedExpr<'ast, Id>),
@@ -548,39 +622,14 @@ where
                 .get(id)
                 .and_then(|meta| meta.get_attribute("infix"))
             {
-                Some(infix_attribute) => {
-                    fn parse_infix(s: &str) -> Result<OpMeta, InfixError> {
-                        let mut iter = s.splitn(2, ",");
-                        let fixity = match iter.next().ok_or(InfixError::InvalidFixity)?.trim() {
-                            "left" => Fixity::Left,
-                            "right" => Fixity::Right,
-                            _ => {
-                                return Err(InfixError::InvalidFixity);
-                            }
-                        };
-                        let precedence = iter
-                            .next()
-                            .and_then(|s| s.trim().parse().ok())
-                            .and_then(|precedence| {
-                                if precedence >= 0 {
-                                    Some(precedence)
-                                } else {
-                                    None
-                                }
-                            })
-                            .ok_or(InfixError::InvalidPrecedence)?;
-                        Ok(OpMeta { fixity, precedence })
+                Some(infix_attribute) => match infix_attribute.parse::<OpMeta>() {
+                    Ok(op_meta) => {
+                        self.op_table.insert(id.clone(), op_meta);
                     }
-
-                    match parse_infix(infix_attribute) {
-                        Ok(op_meta) => {
-                            self.op_table.operators.insert(id.clone(), op_meta);
-                        }
-                        Err(err) => {
-                            self.errors.push(pos::spanned(span, err.into()));
-                        }
+                    Err(err) => {
+                        self.errors.push(pos::spanned(span, err.into()));
                     }
-                }
+                },
 
                 None => {
                     if id.as_ref().starts_with(is_operator_char) {
@@ -624,7 +673,7 @@ where
         }
     }
 
-    let mut op_table = OpTable::new(None);
+    let mut op_table = OpTable::default();
     CheckInfix {
         metadata,
         errors: &mut errors,
@@ -1,4 +1,4 @@
-use std::{fmt, str};
+use std::{borrow::Cow, convert::TryFrom, fmt, str};
 
 use codespan::ByteOffset;
 
@@ -52,6 +52,7 @@ pub enum Token<S> {
     Pipe,
     RArrow,
     Question,
+    Tilde,
 
     LBrace,
     LBracket,
@@ -118,6 +119,7 @@ where
             Pipe => "Pipe",
             RArrow => "RArrow",
             Question => "Question",
+            Tilde => "Tilde",
 
             OpenBlock => "OpenBlock",
             CloseBlock => "CloseBlock",
@@ -182,6 +184,7 @@ impl<S> Token<S> {
             Pipe => Pipe,
             RArrow => RArrow,
             Question => Question,
+            Tilde => Tilde,
 
             OpenBlock => OpenBlock,
             CloseBlock => CloseBlock,
@@ -258,6 +261,9 @@ quick_error! {
         UnterminatedStringLiteral {
             display("unterminated string literal")
         }
+        UnterminatedBlockComment {
+            display("unterminated block comment")
+        }
         InvalidRawStringDelimiter {
             display("raw strings can only use `#` as a delimter")
         }
@@ -276,6 +282,9 @@ quick_error! {
         HexLiteralIncomplete {
             display("cannot parse hex literal, incomplete")
         }
+        NumericLiteralIncomplete {
+            display("cannot parse numeric literal, incomplete")
+        }
     }
 }
 
@@ -307,6 +316,10 @@ fn is_hex(ch: u8) -> bool {
     (ch as char).is_digit(16)
 }
 
+fn is_digit_or_underscore(ch: u8) -> bool {
+    is_digit(ch) || ch == b'_'
+}
+
 struct CharLocations<'input> {
     location: Location,
     chars: str_suffix::Iter<'input>,
@@ -345,11 +358,33 @@ impl<'input> Iterator for CharLocations<'input> {
     }
 }
 
+/// A position between two tokens which a `Tokenizer` can be rolled back to or resumed from
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Checkpoint {
+    location: Location,
+    errors: usize,
+}
+
+impl Checkpoint {
+    /// The location of the first character lexed after the checkpoint
+    pub fn location(&self) -> Location {
+        self.location
+    }
+}
+
+/// Returns the last checkpoint in `checkpoints` which is at or before `pos`. `checkpoints` must
+/// be sorted by their location, as returned by `Tokenizer::line_checkpoints`.
+pub fn nearest_checkpoint(checkpoints: &[Checkpoint], pos: BytePos) -> Option<Checkpoint> {
+    let end = checkpoints.partition_point(|checkpoint| checkpoint.location.absolute <= pos);
+    end.checked_sub(1).map(|i| checkpoints[i])
+}
+
 pub struct Tokenizer<'input> {
     input: &'input str,
     chars: CharLocations<'input>,
     start_index: BytePos,
     pub errors: Errors<SpError>,
+    line_checkpoints: Option<Vec<Checkpoint>>,
 }
 
 impl<'input> Tokenizer<'input> {
@@ -364,6 +399,72 @@ impl<'input> Tokenizer<'input> {
             chars,
             start_index: input.start_index(),
             errors: Errors::new(),
+            line_checkpoints: None,
+        }
+    }
+
+    /// Creates a tokenizer which starts lexing `input` at `checkpoint`.
+    ///
+    /// `input` only needs to be the same as the source the checkpoint was taken from up to the
+    /// checkpoint, so an edited file can be lexed again from the last checkpoint before the edit.
+    pub fn resume<S>(input: &'input S, checkpoint: Checkpoint) -> Tokenizer<'input>
+    where
+        S: ?Sized + crate::ParserSource,
+    {
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer.chars = tokenizer.chars_at(checkpoint.location);
+        tokenizer
+    }
+
+    /// Returns a checkpoint at the current position, which is always between two tokens
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            location: self.chars.location,
+            errors: self.errors.len(),
+        }
+    }
+
+    /// Continues lexing from `checkpoint`, discarding any errors found after it
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.chars = self.chars_at(checkpoint.location);
+        while self.errors.len() > checkpoint.errors {
+            self.errors.pop();
+        }
+    }
+
+    /// Records a checkpoint at the start of each line which is not inside a token (such as a
+    /// multiline string or comment)
+    pub fn record_line_checkpoints(&mut self) {
+        self.line_checkpoints.get_or_insert_with(Vec::new);
+    }
+
+    /// The checkpoints recorded since `record_line_checkpoints` was called, in the order they
+    /// appear in the input
+    pub fn line_checkpoints(&self) -> &[Checkpoint] {
+        self.line_checkpoints.as_ref().map_or(&[], |c| c)
+    }
+
+    fn record_line_checkpoint(&mut self) {
+        let checkpoint = Checkpoint {
+            location: self.chars.location,
+            errors: self.errors.len(),
+        };
+        if let Some(checkpoints) = &mut self.line_checkpoints {
+            if checkpoint.location.column == Column::from(1)
+                && checkpoints.last().map_or(true, |last| {
+                    last.location.absolute < checkpoint.location.absolute
+                })
+            {
+                checkpoints.push(checkpoint);
+            }
+        }
+    }
+
+    fn chars_at(&self, location: Location) -> CharLocations<'input> {
+        let offset = (location.absolute - self.start_index).to_usize();
+        CharLocations {
+            location,
+            chars: StrSuffix::new(&self.input[offset..]).iter(),
         }
     }
 
@@ -468,6 +569,7 @@ impl<'input> Tokenizer<'input> {
 
     fn block_comment(&mut self, start: Location) -> Result<Option<SpannedToken<'input>>, SpError> {
         self.bump(); // Skip first b'*'
+        let opening_end = self.next_loc();
 
         loop {
             let (_, comment) = self.take_until(start, |ch| ch == b'*');
@@ -488,7 +590,12 @@ impl<'input> Tokenizer<'input> {
                     }
                 }
                 Some((_, _)) => continue,
-                None => return self.eof_error(),
+                None => {
+                    // Point at the `/*` which opened the comment instead of the end of the file
+                    self.errors
+                        .push(pos::spanned2(start, opening_end, UnterminatedBlockComment));
+                    return Ok(None);
+                }
             }
         }
     }
@@ -504,6 +611,9 @@ impl<'input> Tokenizer<'input> {
             "=" => Token::Equals,
             "|" => Token::Pipe,
             "->" => Token::RArrow,
+            // `~` only marks a named argument or a lazy field when it is directly followed by
+            // the name, otherwise it is left to be used as an operator
+            "~" if self.test_lookahead(is_ident_start) => Token::Tilde,
             "#" => {
                 // Is this too permissive?
                 self.take_while(start, is_ident_start);
@@ -536,13 +646,51 @@ impl<'input> Tokenizer<'input> {
     }
 
     fn string_literal(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
+        let checkpoint = self.checkpoint();
+        if let Ok(token) = self.string_literal_content(start, false)? {
+            return Ok(token);
+        }
+
+        // Strings may span several lines but an unterminated string most likely ends at the end
+        // of the line it starts on, so the rest of the input is lexed again from there instead of
+        // being swallowed by the string
+        self.rollback(checkpoint);
+        let content_end = match self.string_literal_content(start, true)? {
+            Ok(token) => return Ok(token),
+            Err(content_end) => content_end,
+        };
+
+        let end = self.next_loc();
+        let token = Token::StringLiteral(StringLiteral::Escaped(
+            self.slice(checkpoint.location, content_end),
+        ));
+        self.recover(start, end, UnterminatedStringLiteral, token)
+    }
+
+    /// Lexes a string literal up to and including its closing `"`. If the string is not closed
+    /// before the end of the input (or the end of the line if `stop_at_newline` is set) the
+    /// location where its content ends is returned instead.
+    fn string_literal_content(
+        &mut self,
+        start: Location,
+        stop_at_newline: bool,
+    ) -> Result<Result<SpannedToken<'input>, Location>> {
         let content_start = self.next_loc();
         loop {
             let scan_start = self.next_loc();
-            self.take_until(scan_start, |b| b == b'"' || b == b'\\');
+            self.take_until(scan_start, |b| {
+                b == b'"' || b == b'\\' || (stop_at_newline && b == b'\n')
+            });
+            if stop_at_newline && self.test_lookahead(|b| b == b'\n') {
+                break;
+            }
             match self.bump() {
-                Some((start, b'\\')) => {
-                    self.escape_code(start)?;
+                Some((escape_start, b'\\')) => {
+                    if stop_at_newline && self.lookahead().map_or(true, |(_, b)| b == b'\n') {
+                        // A backslash without an escape code is left out of the string
+                        return Ok(Err(escape_start));
+                    }
+                    self.escape_code(escape_start)?;
                 }
                 Some((_, b'"')) => {
                     let end = self.next_loc();
@@ -553,16 +701,12 @@ impl<'input> Tokenizer<'input> {
                     let token = Token::StringLiteral(StringLiteral::Escaped(
                         self.slice(content_start, content_end),
                     ));
-                    return Ok(pos::spanned2(start, end, token));
+                    return Ok(Ok(pos::spanned2(start, end, token)));
                 }
                 _ => break,
             }
         }
-
-        let end = self.chars.location;
-
-        let token = Token::StringLiteral(StringLiteral::Escaped(self.slice(content_start, end)));
-        self.recover(start, end, UnterminatedStringLiteral, token)
+        Ok(Err(self.next_loc()))
     }
 
     fn raw_string_literal(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
@@ -652,64 +796,64 @@ impl<'input> Tokenizer<'input> {
         }
     }
 
+    /// Returns true if the token starting at `start` directly follows a `.` token, as in the
+    /// tuple projection `t.0`
+    fn follows_dot(&self, start: Location) -> bool {
+        let before = &self.input[..(start.absolute - self.start_index).to_usize()];
+        let operator_start = before
+            .bytes()
+            .rposition(|ch| !is_operator_byte(ch))
+            .map_or(0, |i| i + 1);
+        &before[operator_start..] == "."
+    }
+
+    /// Returns the input which has not been consumed yet
+    fn remaining(&self) -> &'input [u8] {
+        let offset = (self.next_loc().absolute - self.start_index).to_usize();
+        self.input[offset..].as_bytes()
+    }
+
+    /// Returns true if the input continues with the exponent of a float literal (`e10`, `E-3`)
+    fn at_exponent(&self) -> bool {
+        match self.remaining() {
+            [b'e', rest @ ..] | [b'E', rest @ ..] => match rest {
+                [b'-', ch, ..] | [b'+', ch, ..] => is_digit(*ch),
+                [ch, ..] => is_digit(*ch),
+                [] => false,
+            },
+            _ => false,
+        }
+    }
+
     fn numeric_literal(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
-        let (end, int) = self.take_while(start, is_digit);
+        let (end, int) = self.take_while(start, is_digit_or_underscore);
 
         Ok(match self.lookahead() {
+            // `t.0.1` projects out of a tuple twice
+            Some((_, b'.')) if self.follows_dot(start) => self.int_literal(start, end, int)?,
             Some((_, b'.')) => {
                 self.bump(); // Skip b'.'
-                let (end, float) = self.take_while(start, is_digit);
-                match self.lookahead() {
-                    Some((next, ch)) if is_ident_start(ch) => {
-                        let ch = self.chars.chars.as_str_suffix().restore_char(&[ch]);
-                        self.recover(end, next, UnexpectedChar(ch), ())?;
-                    }
-                    _ => (),
-                }
-                pos::spanned2(
-                    start,
-                    end,
-                    Token::FloatLiteral(NotNan::new(float.parse().unwrap()).unwrap()),
-                )
+                self.take_while(start, is_digit_or_underscore);
+                self.float_literal(start)?
             }
-            Some((_, b'x')) => {
-                self.bump(); // Skip b'x'
-                let int_start = self.next_loc();
-                let end1 = end;
-                let (end, hex) = self.take_while(int_start, is_hex);
-                match int {
-                    "0" | "-0" => {
-                        match self.lookahead() {
-                            Some((lookahead_end, ch)) if is_ident_start(ch) => {
-                                let ch = self.chars.chars.as_str_suffix().restore_char(&[ch]);
-
-                                self.recover(end, lookahead_end, UnexpectedChar(ch), ())?;
-                            }
-                            _ => (),
-                        }
-                        if hex.is_empty() {
-                            return self.recover(
-                                start,
-                                end,
-                                HexLiteralIncomplete,
-                                Token::IntLiteral(0),
-                            );
-                        }
-                        let is_positive = int == "0";
-                        match i64_from_hex(hex, is_positive) {
-                            Ok(val) => pos::spanned2(start, end, Token::IntLiteral(val)),
-                            Err(err) => return self.recover(start, end, err, Token::IntLiteral(0)),
-                        }
-                    }
-                    _ => {
-                        return self.recover(
-                            start,
-                            end1,
-                            HexLiteralWrongPrefix,
-                            Token::IntLiteral(0),
-                        )
-                    }
+            Some((_, b'e')) | Some((_, b'E')) if self.at_exponent() => self.float_literal(start)?,
+            Some((_, b'x')) => match int {
+                "0" | "-0" => self.radix_literal(start, int, 16)?,
+                _ => {
+                    self.bump(); // Skip b'x'
+                    self.take_while(start, is_hex);
+                    return self.recover(start, end, HexLiteralWrongPrefix, Token::IntLiteral(0));
                 }
+            },
+            Some((_, b'o')) if int == "0" || int == "-0" => self.radix_literal(start, int, 8)?,
+            Some((_, b'b'))
+                if (int == "0" || int == "-0")
+                    && self
+                        .remaining()
+                        .get(1)
+                        .map_or(false, |&ch| ch == b'0' || ch == b'1') =>
+            {
+                self.radix_literal(start, int, 2)?
             }
             Some((_, b'b')) => {
                 self.bump(); // Skip b'b'
@@ -721,7 +865,7 @@ impl<'input> Tokenizer<'input> {
                     }
                     _ => (),
                 }
-                if let Ok(val) = int.parse() {
+                if let Ok(val) = without_underscores(int).parse() {
                     pos::spanned2(start, end, Token::ByteLiteral(val))
                 } else {
                     self.recover(start, end, NonParseableInt, Token::ByteLiteral(0))?
@@ -731,22 +875,104 @@ impl<'input> Tokenizer<'input> {
                 let ch = self.chars.chars.as_str_suffix().restore_char(&[ch]);
                 self.recover(start, start, UnexpectedChar(ch), ())?;
 
-                if let Ok(val) = int.parse() {
-                    pos::spanned2(start, end, Token::IntLiteral(val))
-                } else {
-                    self.recover(start, end, NonParseableInt, Token::IntLiteral(0))?
-                }
-            }
-            None | Some(_) => {
-                if let Ok(val) = int.parse() {
-                    pos::spanned2(start, end, Token::IntLiteral(val))
-                } else {
-                    self.recover(start, end, NonParseableInt, Token::IntLiteral(0))?
-                }
+                self.int_literal(start, end, int)?
             }
+            None | Some(_) => self.int_literal(start, end, int)?,
         })
     }
 
+    fn int_literal(
+        &mut self,
+        start: Location,
+        end: Location,
+        int: &str,
+    ) -> Result<SpannedToken<'input>, SpError> {
+        if let Ok(val) = without_underscores(int).parse() {
+            Ok(pos::spanned2(start, end, Token::IntLiteral(val)))
+        } else {
+            self.recover(start, end, NonParseableInt, Token::IntLiteral(0))
+        }
+    }
+
+    /// Lexes the rest of a float literal, starting at its (optional) exponent
+    fn float_literal(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
+        if self.at_exponent() {
+            self.bump(); // Skip b'e'
+            if self.test_lookahead(|ch| ch == b'-' || ch == b'+') {
+                self.bump();
+            }
+            self.take_while(start, is_digit_or_underscore);
+        }
+        let end = self.next_loc();
+        match self.lookahead() {
+            Some((next, ch)) if is_ident_start(ch) => {
+                let ch = self.chars.chars.as_str_suffix().restore_char(&[ch]);
+                self.recover(end, next, UnexpectedChar(ch), ())?;
+            }
+            _ => (),
+        }
+        let float = without_underscores(self.slice(start, end));
+        Ok(pos::spanned2(
+            start,
+            end,
+            Token::FloatLiteral(NotNan::new(float.parse().unwrap()).unwrap()),
+        ))
+    }
+
+    /// Lexes a `0x`, `0o` or `0b` literal, `int` is the `0` or `-0` before the radix prefix.
+    /// Binary and octal literals may be suffixed with `b` to make them byte literals.
+    fn radix_literal(
+        &mut self,
+        start: Location,
+        int: &str,
+        radix: u32,
+    ) -> Result<SpannedToken<'input>, SpError> {
+        self.bump(); // Skip the radix prefix
+        let digits_start = self.next_loc();
+        let (mut end, digits) = self.take_while(digits_start, |ch| {
+            ch == b'_' || (ch as char).is_digit(radix)
+        });
+
+        let is_byte = radix != 16 && self.test_lookahead(|ch| ch == b'b');
+        if is_byte {
+            self.bump(); // Skip b'b'
+            end = self.next_loc();
+        }
+
+        match self.lookahead() {
+            Some((lookahead_end, ch)) if is_ident_start(ch) || is_digit(ch) => {
+                let ch = self.chars.chars.as_str_suffix().restore_char(&[ch]);
+                self.recover(end, lookahead_end, UnexpectedChar(ch), ())?;
+            }
+            _ => (),
+        }
+
+        let default = if is_byte {
+            Token::ByteLiteral(0)
+        } else {
+            Token::IntLiteral(0)
+        };
+        if without_underscores(digits).is_empty() {
+            let err = if radix == 16 {
+                HexLiteralIncomplete
+            } else {
+                NumericLiteralIncomplete
+            };
+            return self.recover(start, end, err, default);
+        }
+
+        let is_positive = int == "0";
+        let token = match i64_from_radix(digits, radix, is_positive) {
+            Ok(val) if is_byte => match u8::try_from(val) {
+                Ok(val) => Token::ByteLiteral(val),
+                Err(_) => return self.recover(start, end, NonParseableInt, default),
+            },
+            Ok(val) => Token::IntLiteral(val),
+            Err(err) => return self.recover(start, end, err, default),
+        };
+        Ok(pos::spanned2(start, end, token))
+    }
+
     fn identifier(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
         let (mut end, mut ident) = self.take_while(start, is_ident_continue);
         match self.lookahead() {
@@ -783,7 +1009,12 @@ impl<'input> Iterator for Tokenizer<'input> {
     type Item = Result<SpannedToken<'input>, SpError>;
 
     fn next(&mut self) -> Option<Result<SpannedToken<'input>, SpError>> {
-        while let Some((start, ch)) = self.bump() {
+        loop {
+            self.record_line_checkpoint();
+            let (start, ch) = match self.bump() {
+                Some(next) => next,
+                None => break,
+            };
             return match ch {
                 b',' => Some(Ok(pos::spanned2(start, self.next_loc(), Token::Comma))),
                 b'\\' => Some(Ok(pos::spanned2(start, self.next_loc(), Token::Lambda))),
@@ -853,25 +1084,31 @@ impl<'input> Iterator for Tokenizer<'input> {
     }
 }
 
-/// Converts partial hex literal (i.e. part after `0x` or `-0x`) to 64 bit signed integer.
+/// Removes the `_` separators from a numeric literal
+fn without_underscores(literal: &str) -> Cow<str> {
+    if literal.contains('_') {
+        Cow::Owned(literal.replace('_', ""))
+    } else {
+        Cow::Borrowed(literal)
+    }
+}
+
+/// Converts the digits of a radix literal (i.e. the part after `0x` or `-0x`) to a 64 bit signed
+/// integer, skipping any `_` separators.
 ///
 /// This is basically a copy and adaptation of `std::num::from_str_radix`.
-fn i64_from_hex(hex: &str, is_positive: bool) -> Result<i64, Error> {
-    const RADIX: u32 = 16;
-    let digits = hex.as_bytes();
+fn i64_from_radix(digits: &str, radix: u32, is_positive: bool) -> Result<i64, Error> {
     let sign: i64 = if is_positive { 1 } else { -1 };
     let mut result = 0i64;
-    for &c in digits {
-        let x = (c as char).to_digit(RADIX).expect("valid hex literal");
+    for c in digits.bytes().filter(|&c| c != b'_') {
+        let x = (c as char).to_digit(radix).expect("valid radix literal");
         result = result
-            .checked_mul(RADIX as i64)
+            .checked_mul(radix as i64)
             .and_then(|result| result.checked_add((x as i64) * sign))
-            .ok_or_else(|| {
-                if is_positive {
-                    HexLiteralOverflow
-                } else {
-                    HexLiteralUnderflow
-                }
+            .ok_or_else(|| match (radix, is_positive) {
+                (16, true) => HexLiteralOverflow,
+                (16, false) => HexLiteralUnderflow,
+                _ => NonParseableInt,
             })?;
     }
     Ok(result)
@@ -1097,6 +1334,29 @@ mod test {
         );
     }
 
+    #[test]
+    fn string_literal_unterminated_at_end_of_line() {
+        assert_eq!(
+            tokenizer("\"bar\nbaz").next(),
+            Some(error2(0, 4, UnterminatedStringLiteral))
+        );
+        let tokens: Vec<_> = tokenizer("\"bar\\\nbaz")
+            .map(|result| result.map(|token| token.value).map_err(|err| err.value))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![Err(UnterminatedStringLiteral), Ok(Identifier("baz"))]
+        );
+    }
+
+    #[test]
+    fn block_comment_unterminated() {
+        assert_eq!(
+            tokenizer("foo /* bar").last(),
+            Some(error2(4, 6, UnterminatedBlockComment))
+        );
+    }
+
     #[test]
     fn char_literals() {
         test(
@@ -1227,8 +1487,8 @@ mod test {
         );
 
         assert_eq!(
-            tokenizer(r#"0xff_"#).next(),
-            Some(error2(4, 4, UnexpectedChar('_')))
+            tokenizer(r#"0xffg"#).next(),
+            Some(error2(4, 4, UnexpectedChar('g')))
         );
 
         assert_eq!(
@@ -1303,6 +1563,113 @@ mod test {
         );
     }
 
+    #[test]
+    fn tuple_projections() {
+        test(
+            r#"t.0.1 1.5"#,
+            vec![
+                (r#"~        "#, Identifier("t")),
+                (r#" ~       "#, Dot),
+                (r#"  ~      "#, IntLiteral(0)),
+                (r#"   ~     "#, Dot),
+                (r#"    ~    "#, IntLiteral(1)),
+                (r#"      ~~~"#, FloatLiteral(NotNan::new(1.5).unwrap())),
+            ],
+        );
+    }
+
+    #[test]
+    fn binary_and_octal_literals() {
+        test(
+            r#"0b1010 -0b11 0o777 0b101b 0o17b"#,
+            vec![
+                (r#"~~~~~~                         "#, IntLiteral(10)),
+                (r#"       ~~~~~                   "#, IntLiteral(-3)),
+                (r#"             ~~~~~             "#, IntLiteral(511)),
+                (r#"                   ~~~~~~      "#, ByteLiteral(5)),
+                (r#"                          ~~~~~"#, ByteLiteral(15)),
+            ],
+        );
+    }
+
+    #[test]
+    fn byte_literal_zero_is_not_binary() {
+        test(
+            r#"0b 0b2"#,
+            vec![
+                (r#"~~    "#, ByteLiteral(0)),
+                (r#"   ~~ "#, ByteLiteral(0)),
+                (r#"     ~"#, IntLiteral(2)),
+            ],
+        );
+    }
+
+    #[test]
+    fn binary_literals_unexpected_digit() {
+        assert_eq!(
+            tokenizer(r#"0b102"#).next(),
+            Some(error2(4, 4, UnexpectedChar('2')))
+        );
+    }
+
+    #[test]
+    fn octal_literals_incomplete() {
+        assert_eq!(
+            tokenizer(r#"0o"#).last(),
+            Some(error2(0, 2, NumericLiteralIncomplete))
+        );
+    }
+
+    #[test]
+    fn underscore_separated_literals() {
+        test(
+            r#"1_000_000 0xFF_FF 0b1111_0000 1_0b 1_000.5"#,
+            vec![
+                (
+                    r#"~~~~~~~~~                                 "#,
+                    IntLiteral(1_000_000),
+                ),
+                (
+                    r#"          ~~~~~~~                         "#,
+                    IntLiteral(0xFFFF),
+                ),
+                (
+                    r#"                  ~~~~~~~~~~~             "#,
+                    IntLiteral(0b1111_0000),
+                ),
+                (
+                    r#"                              ~~~~        "#,
+                    ByteLiteral(10),
+                ),
+                (
+                    r#"                                   ~~~~~~~"#,
+                    FloatLiteral(NotNan::new(1000.5).unwrap()),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn float_literals_with_exponent() {
+        test(
+            r#"1e3 2.5E-1_0 -1_0.0e+2"#,
+            vec![
+                (
+                    r#"~~~                   "#,
+                    FloatLiteral(NotNan::new(1e3).unwrap()),
+                ),
+                (
+                    r#"    ~~~~~~~~          "#,
+                    FloatLiteral(NotNan::new(2.5e-10).unwrap()),
+                ),
+                (
+                    r#"             ~~~~~~~~~"#,
+                    FloatLiteral(NotNan::new(-10.0e2).unwrap()),
+                ),
+            ],
+        );
+    }
+
     #[test]
     fn float_literals_unexpected_char() {
         assert_eq!(
@@ -1373,4 +1740,82 @@ mod test {
             ],
         );
     }
+
+    fn tokens<'input>(
+        tokenizer: &mut Tokenizer<'input>,
+    ) -> Vec<Result<SpannedToken<'input>, SpError>> {
+        std::iter::from_fn(|| match tokenizer.next()? {
+            Ok(Spanned {
+                value: Token::EOF, ..
+            }) => None,
+            result => Some(result),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn rollback_yields_the_same_tokens() {
+        let input = "let x = 1\nin \"abc\" + x";
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer.next();
+        tokenizer.next();
+        let checkpoint = tokenizer.checkpoint();
+        let rest = tokens(&mut tokenizer);
+
+        tokenizer.rollback(checkpoint);
+        assert_eq!(tokens(&mut tokenizer), rest);
+    }
+
+    #[test]
+    fn rollback_discards_errors() {
+        let input = "x 'ab' y";
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer.next();
+        let checkpoint = tokenizer.checkpoint();
+        tokens(&mut tokenizer);
+        assert!(tokenizer.errors.len() > 0);
+
+        tokenizer.rollback(checkpoint);
+        assert_eq!(tokenizer.errors.len(), 0);
+    }
+
+    #[test]
+    fn line_checkpoints_skip_multiline_tokens() {
+        let input = "a\n/* b\nc */\nd\n";
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer.record_line_checkpoints();
+        tokens(&mut tokenizer);
+
+        let lines: Vec<_> = tokenizer
+            .line_checkpoints()
+            .iter()
+            .map(|checkpoint| checkpoint.location().line)
+            .collect();
+        assert_eq!(
+            lines,
+            [Line::from(0), Line::from(1), Line::from(3), Line::from(4)]
+        );
+    }
+
+    #[test]
+    fn resume_after_edit() {
+        let input = "let x = 1\nlet y = 2\nx + y";
+        let edited = "let x = 1\nlet y = 2\nx * yz";
+        let edit = BytePos::from(input.find('+').unwrap() as u32 + 1);
+
+        let mut tokenizer = Tokenizer::new(input);
+        tokenizer.record_line_checkpoints();
+        tokens(&mut tokenizer);
+        let checkpoint = nearest_checkpoint(tokenizer.line_checkpoints(), edit).unwrap();
+        assert_eq!(checkpoint.location().line, Line::from(2));
+
+        let expected: Vec<_> = tokens(&mut Tokenizer::new(edited))
+            .into_iter()
+            .skip_while(|token| {
+                token.as_ref().unwrap().span.start().absolute < checkpoint.location().absolute
+            })
+            .collect();
+        assert_eq!(tokens(&mut Tokenizer::resume(edited, checkpoint)), expected);
+        assert_eq!(expected.len(), 3);
+    }
 }
@@ -36,3 +36,126 @@ type Test = Int
 "#;
     parse_clear_span!(text);
 }
+
+#[test]
+fn lazy_record_field() {
+    use crate::base::{
+        ast::{Expr, HasMetadata},
+        types::{row_iter, Type},
+    };
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Stream a = { value : a, ~tail : Lazy (Stream a) }
+()
+"#;
+    let expr = parse_clear_span!(text);
+    match &expr.expr().value {
+        Expr::TypeBindings(binds, _) => match &**binds[0].alias.value.unresolved_type() {
+            Type::Record(row) => {
+                let lazy_fields: Vec<_> = row_iter(row)
+                    .filter(|field| {
+                        field
+                            .typ
+                            .metadata()
+                            .map_or(false, |m| m.get_attribute("lazy").is_some())
+                    })
+                    .map(|field| field.name.value.clone())
+                    .collect();
+                assert_eq!(lazy_fields, ["tail"]);
+            }
+            typ => panic!("Expected record, found {:?}", typ),
+        },
+        expr => panic!("Expected type bindings, found {:?}", expr),
+    }
+}
+
+#[test]
+fn invalid_record_field_operator() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Stream a = { value : a, !tail : Lazy (Stream a) }
+()
+"#;
+    assert!(parse(text).is_err());
+}
+
+#[test]
+fn record_field_default() {
+    use crate::base::{
+        ast::{Expr, HasMetadata},
+        types::row_iter,
+    };
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Config = { port : Int = 8080, host : String = "0.0.0.0", debug : Bool }
+()
+"#;
+    let expr = parse_clear_span!(text);
+    match &expr.expr().value {
+        Expr::TypeBindings(binds, _) => {
+            let defaults: Vec<_> = binds[0]
+                .defaults
+                .iter()
+                .map(|field| field.name.value.clone())
+                .collect();
+            assert_eq!(defaults, ["port", "host"]);
+
+            let attributes: Vec<_> = row_iter(binds[0].alias.value.unresolved_type())
+                .filter_map(|field| {
+                    field
+                        .typ
+                        .metadata()
+                        .and_then(|m| m.get_attribute("default"))
+                        .map(|s| s.to_string())
+                })
+                .collect();
+            assert_eq!(attributes, ["8080", r#""0.0.0.0""#]);
+        }
+        expr => panic!("Expected type bindings, found {:?}", expr),
+    }
+}
+
+#[test]
+fn record_field_doc_comments() {
+    use crate::base::{
+        ast::{Expr, HasMetadata},
+        types::row_iter,
+    };
+
+    let _ = ::env_logger::try_init();
+    let text = r#"
+type Config = {
+    /// The port to listen on
+    port : Int = 8080,
+    host : String,
+    /// Evaluated on first use
+    ~fallback : Lazy String,
+}
+()
+"#;
+    let expr = parse_clear_span!(text);
+    match &expr.expr().value {
+        Expr::TypeBindings(binds, _) => {
+            let comments: Vec<_> = row_iter(binds[0].alias.value.unresolved_type())
+                .map(|field| {
+                    field
+                        .typ
+                        .metadata()
+                        .and_then(|m| m.comment.as_ref())
+                        .map(|comment| comment.content.clone())
+                })
+                .collect();
+            assert_eq!(
+                comments,
+                [
+                    Some("The port to listen on".to_string()),
+                    None,
+                    Some("Evaluated on first use".to_string()),
+                ]
+            );
+        }
+        expr => panic!("Expected type bindings, found {:?}", expr),
+    }
+}
@@ -142,6 +142,7 @@ test_parse! {
                 metadata: BaseMetadata { metadata: Some(arena.alloc(line_comment("Test"))) },
                 name: no_loc(intern("Test")),
                 alias: alias(arena, intern("Test"), Vec::new(), test),
+                defaults: arena.alloc_extend(vec![]),
                 finalized_alias: None,
             },
             TypeBinding {
@@ -155,6 +156,7 @@ test_parse! {
                 }) ) },
                 name: no_loc(intern("Test2")),
                 alias: alias(arena, intern("Test2"), Vec::new(), test2),
+                defaults: arena.alloc_extend(vec![]),
                 finalized_alias: None,
             },
         ];
@@ -187,12 +189,49 @@ test_parse! {
     |arena| field_access(arena, record(arena, vec![(intern("x"), Some(int(1)))]), "x")
 }
 
+test_parse! {
+    tuple_field_access,
+    "t.0.1",
+    |arena| field_access(arena, field_access(arena, id("t"), "_0"), "_1")
+}
+
 test_parse! {
     builtin_op,
     "x #Int+ 1",
     |arena| binop(arena, id("x"), "#Int+", int(1))
 }
 
+test_parse! {
+    reference_sugar,
+    "r := !r",
+    |arena| binop(arena, id("r"), ":=", app(arena, id("!"), vec![id("r")]))
+}
+
+test_parse! {
+    named_arguments,
+    "draw 1 ~width=3 ~color=red",
+    |arena| app(
+        arena,
+        id("draw"),
+        vec![
+            int(1),
+            record(
+                arena,
+                vec![
+                    ("width".into(), Some(int(3))),
+                    ("color".into(), Some(id("red"))),
+                ],
+            ),
+        ],
+    )
+}
+
+test_parse! {
+    tilde_operator,
+    "(~) 1 2 ~ 3",
+    |arena| binop(arena, app(arena, id("~"), vec![int(1), int(2)]), "~", int(3))
+}
+
 test_parse! {
     op_identifier,
     "let (==) = \\x y -> x #Int== y in (==) 1 2",
@@ -575,6 +614,7 @@ id
                 },
                 name: no_loc(intern("Test")),
                 alias: alias(arena, intern("Test"), Vec::new(), typ(arena, "Int")),
+                defaults: arena.alloc_extend(vec![]),
                 finalized_alias: None,
             }],
             id("id"),
@@ -616,6 +656,7 @@ id
                     },
                     name: no_loc(intern("Test")),
                     alias: alias(arena, intern("Test"), Vec::new(), typ(arena, "Int")),
+                    defaults: arena.alloc_extend(vec![]),
                     finalized_alias: None,
                 }],
                 id("id"),
@@ -653,6 +694,7 @@ id
                 },
                 name: no_loc(intern("Test")),
                 alias: alias(arena, intern("Test"), Vec::new(), typ(arena, "Int")),
+                defaults: arena.alloc_extend(vec![]),
                 finalized_alias: None,
             }],
             id("id"),
@@ -916,6 +958,7 @@ fn alias_in_record_type() {
                         Default::default(),
                     ),
                 ),
+                defaults: arena.alloc_extend(vec![]),
                 finalized_alias: None,
             }]),
             arena.alloc(int(1))
@@ -1016,3 +1059,68 @@ f 1 // abc
         pos::Span::new(2.into(), 5.into())
     );
 }
+
+#[test]
+fn replace_visitor_replaces_nested_expressions() {
+    let _ = ::env_logger::try_init();
+
+    struct ReplaceIdent;
+
+    impl<'ast> ReplaceVisitor<'ast> for ReplaceIdent {
+        type Ident = String;
+
+        fn replace_expr(
+            &mut self,
+            e: &mut SpannedExpr<'ast, String>,
+        ) -> Option<Expr<'ast, String>> {
+            match &e.value {
+                Expr::Ident(id) if id.name == "x" => Some(Expr::Literal(Literal::Int(1))),
+                _ => {
+                    walk_replace_expr(self, e);
+                    None
+                }
+            }
+        }
+    }
+
+    let mut expr = parse_clear_span!("let y = x in x * y");
+    visit_replace_expr(&mut ReplaceIdent, expr.expr_mut());
+
+    mk_ast_arena!(arena);
+    let arena = arena.borrow();
+    assert_eq!(
+        *expr.expr(),
+        let_(arena, "y", int(1), binop(arena, int(1), "*", id("y")))
+    );
+}
+
+#[test]
+fn record_pattern_arguments() {
+    let _ = ::env_logger::try_init();
+
+    let e = parse_new!(r#"\{ x, y } z -> x"#);
+    match &e.expr().value {
+        Expr::Lambda(lambda) => {
+            assert_eq!(lambda.args.len(), 2);
+            let (pattern, body) =
+                argument_pattern(&lambda.args[0], &lambda.body).expect("pattern argument");
+            match &pattern.value {
+                Pattern::Record { fields, .. } => assert_eq!(fields.len(), 2),
+                _ => panic!("Expected record pattern, got {:?}", pattern),
+            }
+            assert!(argument_pattern(&lambda.args[1], body).is_none());
+            assert_eq!(body.value, id("x").value);
+        }
+        _ => panic!("Expected lambda, got {:?}", e.expr()),
+    }
+
+    let e = parse_new!(r#"let f { x } = x in f"#);
+    match &e.expr().value {
+        Expr::LetBindings(binds, _) => {
+            let (_, body) =
+                argument_pattern(&binds[0].args[0], &binds[0].expr).expect("pattern argument");
+            assert_eq!(body.value, id("x").value);
+        }
+        _ => panic!("Expected let, got {:?}", e.expr()),
+    }
+}
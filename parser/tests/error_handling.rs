@@ -8,7 +8,7 @@ mod support;
 
 use {
     base::{
-        ast::{Expr, Pattern, PatternField, TypedIdent},
+        ast::{AstType, Expr, Pattern, PatternField, TypedIdent, ValueBinding},
         mk_ast_arena,
         pos::{self, BytePos},
         types::Type,
@@ -72,9 +72,10 @@ y
 test_parse_error! {
     unclosed_string,
     r#"
-"abc
-123"#,
-    |_: base::ast::ArenaRef<'_, '_, String>| string("abc\n123"),
+let x = "abc
+let y = 123
+y"#,
+    |arena| let_(arena, "x", string("abc"), let_(arena, "y", int(123), id("y"))),
     {
         let error = Error::Token(parser::TokenizeError::UnterminatedStringLiteral);
         let span = pos::span(BytePos::from(0), BytePos::from(0));
@@ -82,6 +83,20 @@ test_parse_error! {
     }
 }
 
+test_parse_error! {
+    unclosed_block_comment,
+    r#"
+let x = 1
+x /* abc
+123"#,
+    |arena| let_(arena, "x", int(1), id("x")),
+    {
+        let error = Error::Token(parser::TokenizeError::UnterminatedBlockComment);
+        let span = pos::span(BytePos::from(0), BytePos::from(0));
+        ParseErrors::from(vec![pos::spanned(span, error)])
+    }
+}
+
 #[test]
 fn tokenizer_error_is_returned() {
     let _ = ::env_logger::try_init();
@@ -329,3 +344,111 @@ do
         )
     );
 }
+
+#[test]
+fn invalid_prefix_operator() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+let x = +1
+x
+"#;
+    assert!(parse(expr).is_err());
+}
+
+#[test]
+fn record_field_default_outside_type_definition() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+let x : { port : Int = 8080 } = { port = 80 }
+x
+"#;
+    assert!(parse(expr).is_err());
+}
+
+#[test]
+fn record_field_default_in_nested_record_type() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+type Config = { server : { port : Int = 8080 } }
+()
+"#;
+    assert!(parse(expr).is_err());
+}
+
+test_parse_error! {
+    error_in_let_binding_type_resumes_at_next_binding,
+    r#"
+let x : -> = 1
+let y = 2
+y
+"#,
+    |arena| no_loc(Expr::let_binding(
+        arena,
+        ValueBinding {
+            name: no_loc(Pattern::Error),
+            expr: int(1),
+            ..ValueBinding::default()
+        },
+        let_(arena, "y", int(2), id("y")),
+    )),
+    vec![no_loc(Error::UnexpectedToken(Token::RArrow, vec![]))],
+}
+
+test_parse_error! {
+    error_in_type_binding_resumes_at_next_binding,
+    r#"
+type Test = Int ->
+let x = 1
+x
+"#,
+    |arena| type_decl(
+        arena,
+        intern("Test"),
+        vec![],
+        AstType::new(arena, no_loc(Type::Error)),
+        let_(arena, "x", int(1), id("x")),
+    ),
+    vec![no_loc(Error::UnexpectedToken(Token::In, vec![]))],
+}
+
+#[test]
+fn errors_in_multiple_bindings_are_all_reported() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+type Test = Int ->
+let x : -> = 1
+let y = 2
+y
+"#;
+    let (expr, errors) = parse(expr).unwrap_err();
+    assert_eq!(
+        remove_expected(errors),
+        ParseErrors::from(vec![
+            no_loc(Error::UnexpectedToken(Token::In, vec![])),
+            no_loc(Error::UnexpectedToken(Token::RArrow, vec![])),
+        ])
+    );
+
+    // The binding after the errors is still parsed
+    let expr = expr.expect("Recovered expression");
+    let body = match &expr.expr().value {
+        Expr::TypeBindings(_, body) => match &body.value {
+            Expr::LetBindings(_, body) => body,
+            _ => panic!("Expected a let binding: {:?}", body),
+        },
+        _ => panic!("Expected a type binding: {:?}", expr.expr()),
+    };
+    match &body.value {
+        Expr::LetBindings(bindings, _) => {
+            assert_eq!(
+                bindings[0].name.value,
+                Pattern::Ident(TypedIdent::new(intern("y")))
+            )
+        }
+        _ => panic!("Expected a let binding: {:?}", body),
+    }
+}
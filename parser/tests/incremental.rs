@@ -0,0 +1,111 @@
+#[macro_use]
+extern crate pretty_assertions;
+
+extern crate env_logger;
+extern crate gluon_base as base;
+extern crate gluon_parser as parser;
+
+#[macro_use]
+mod support;
+
+use crate::{
+    base::{
+        mk_ast_arena,
+        pos::{self, BytePos},
+        types::TypeCache,
+    },
+    parser::{parse_partial_expr, reparse_incremental, TextEdit},
+};
+
+use crate::support::*;
+
+/// Replaces the first occurence of `old_text` in `source`
+fn edit(source: &str, old_text: &str, new_text: &str) -> TextEdit {
+    let start = source.find(old_text).unwrap() + 1;
+    let span = pos::span(
+        BytePos::from(start as u32),
+        BytePos::from((start + old_text.len()) as u32),
+    );
+    TextEdit::new(span, new_text)
+}
+
+/// Checks that reparsing `source` incrementally after `edit` gives the same result as parsing the
+/// edited source from scratch
+fn check_reparse(source: &str, edit: TextEdit) {
+    let _ = ::env_logger::try_init();
+
+    let mut symbols = MockEnv::<String>::new();
+    let type_cache = TypeCache::default();
+
+    mk_ast_arena!(arena);
+    let mut expr = parse_partial_expr(arena.borrow(), &mut symbols, &type_cache, source).unwrap();
+    reparse_incremental(
+        arena.borrow(),
+        &mut symbols,
+        &type_cache,
+        &mut expr,
+        source,
+        &edit,
+    )
+    .unwrap();
+
+    let new_source = edit.apply(source);
+    let expected =
+        parse_partial_expr(arena.borrow(), &mut symbols, &type_cache, &new_source[..]).unwrap();
+    assert_eq!(expr, expected);
+}
+
+static SOURCE: &str = r#"
+let x = 1
+/// A type
+type Test = { a : Int, b : String }
+let f y : Int -> Int =
+    let z = y
+    z
+{ x, f, Test }
+"#;
+
+#[test]
+fn reparse_first_binding() {
+    check_reparse(SOURCE, edit(SOURCE, "x = 1", "x = 123"));
+}
+
+#[test]
+fn reparse_type_binding() {
+    check_reparse(SOURCE, edit(SOURCE, "b : String", "b : String, c : Float"));
+}
+
+#[test]
+fn reparse_binding_shrinking_source() {
+    check_reparse(SOURCE, edit(SOURCE, "let z = y\n    z", "y"));
+}
+
+#[test]
+fn reparse_split_binding() {
+    check_reparse(SOURCE, edit(SOURCE, "x = 1\n", "x = 1\nlet w = 2\n"));
+}
+
+#[test]
+fn reparse_body() {
+    check_reparse(SOURCE, edit(SOURCE, "{ x, f, Test }", "{ x, f }"));
+}
+
+#[test]
+fn reparse_with_syntax_error() {
+    let _ = ::env_logger::try_init();
+
+    let mut symbols = MockEnv::<String>::new();
+    let type_cache = TypeCache::default();
+
+    mk_ast_arena!(arena);
+    let mut expr = parse_partial_expr(arena.borrow(), &mut symbols, &type_cache, SOURCE).unwrap();
+    let result = reparse_incremental(
+        arena.borrow(),
+        &mut symbols,
+        &type_cache,
+        &mut expr,
+        SOURCE,
+        &edit(SOURCE, "x = 1", "x = "),
+    );
+    assert!(result.is_err());
+}
@@ -17,7 +17,7 @@ use crate::base::{
     types::{Alias, AliasData, ArcType, Field, Generic, KindedIdent, Type, TypeCache, TypeContext},
 };
 use crate::parser::{
-    infix::{Fixity, OpMeta, OpTable, Reparser},
+    infix::{Fixity, OpMeta, OpTable, Reparser, BUILTIN_OPERATORS},
     parse_partial_expr, Error, ParseErrors,
 };
 
@@ -110,31 +110,21 @@ pub fn parse(input: &str) -> Result<RootExpr<String>, (Option<RootExpr<String>>,
 
     let mut expr = parse_string(&mut symbols, input)?;
     let op_table = OpTable::new(
-        vec![
-            ("*", OpMeta::new(7, Fixity::Left)),
-            ("/", OpMeta::new(7, Fixity::Left)),
-            ("%", OpMeta::new(7, Fixity::Left)),
-            ("+", OpMeta::new(6, Fixity::Left)),
-            ("-", OpMeta::new(6, Fixity::Left)),
-            (":", OpMeta::new(5, Fixity::Right)),
-            ("++", OpMeta::new(5, Fixity::Right)),
-            ("&&", OpMeta::new(3, Fixity::Right)),
-            ("||", OpMeta::new(2, Fixity::Right)),
-            ("$", OpMeta::new(0, Fixity::Right)),
-            ("==", OpMeta::new(4, Fixity::Left)),
-            ("/=", OpMeta::new(4, Fixity::Left)),
-            ("<", OpMeta::new(4, Fixity::Left)),
-            (">", OpMeta::new(4, Fixity::Left)),
-            ("<=", OpMeta::new(4, Fixity::Left)),
-            (">=", OpMeta::new(4, Fixity::Left)),
-            // Hack for some library operators
-            ("<<", OpMeta::new(9, Fixity::Right)),
-            (">>", OpMeta::new(9, Fixity::Left)),
-            ("<|", OpMeta::new(0, Fixity::Right)),
-            ("|>", OpMeta::new(0, Fixity::Left)),
-        ]
-        .into_iter()
-        .map(|(s, op)| (s.to_string(), op)),
+        BUILTIN_OPERATORS
+            .iter()
+            .cloned()
+            .chain(vec![
+                ("%", OpMeta::new(7, Fixity::Left)),
+                (":", OpMeta::new(5, Fixity::Right)),
+                ("++", OpMeta::new(5, Fixity::Right)),
+                ("$", OpMeta::new(0, Fixity::Right)),
+                // Hack for some library operators
+                ("<<", OpMeta::new(9, Fixity::Right)),
+                (">>", OpMeta::new(9, Fixity::Left)),
+                ("<|", OpMeta::new(0, Fixity::Right)),
+                ("|>", OpMeta::new(0, Fixity::Left)),
+            ])
+            .map(|(s, op)| (s.to_string(), op)),
     );
 
     expr.with_arena(|arena, expr| {
@@ -380,6 +370,7 @@ pub fn type_decl<'ast>(
             metadata: BaseMetadata::default(),
             name: no_loc(name.clone()),
             alias: no_loc(AliasData::new(name, arena.alloc_extend(args), typ)),
+            defaults: arena.alloc_extend(vec![]),
             finalized_alias: None,
         }],
         body,
@@ -0,0 +1,107 @@
+//! Structured output for `--json`, where everything the REPL would print is instead written to
+//! stdout as JSON events, one per line.
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+use gluon::{
+    base::filename_to_module,
+    compiler_pipeline::{Executable, ExecuteValue},
+    Result, Thread, ThreadExt,
+};
+
+use crate::repl;
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// The value of an evaluated expression or file
+    Result {
+        value: String,
+        #[serde(rename = "type")]
+        typ: String,
+    },
+    /// Text which the program printed to stdout
+    Print { text: &'a str },
+    /// An error which stopped the evaluation
+    Error { message: String },
+}
+
+pub fn emit(event: &Event) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let result = serde_json::to_writer(&mut stdout, event)
+        .map_err(io::Error::from)
+        .and_then(|()| writeln!(stdout))
+        .and_then(|()| stdout.flush());
+    if let Err(err) = result {
+        warn!("Unable to write event: {}", err);
+    }
+}
+
+/// Emits everything which `vm` prints as `Print` events
+pub fn capture_prints(vm: &Thread) {
+    vm.global_env()
+        .set_stdout_handler(Some(Box::new(|text| emit(&Event::Print { text }))));
+}
+
+fn emit_result(value: String, typ: &gluon::base::types::ArcType) {
+    emit(&Event::Result {
+        value,
+        typ: typ.to_string(),
+    });
+}
+
+/// Runs each file, emitting the value it evaluates to
+pub async fn run_files(vm: &Thread, files: &[String]) -> Result<()> {
+    for file in files {
+        let source = fs::read_to_string(file)?;
+        let module_name = filename_to_module(file);
+        let mut db = vm.get_database();
+        let mut module_compiler = vm.module_compiler(&mut db);
+        let ExecuteValue { value, typ, .. } = (&*source)
+            .run_expr(
+                &mut module_compiler,
+                vm.root_thread(),
+                &module_name,
+                &source,
+                None,
+            )
+            .await?;
+        emit_result(repl::format_value(vm, &typ, value.get_variant()), &typ);
+    }
+    Ok(())
+}
+
+/// Evaluates the inputs read from stdin. Each line of stdin must be a JSON string containing the
+/// source to evaluate, which lets a single input span multiple lines.
+pub async fn run(vm: &Thread) -> Result<()> {
+    let stdin = io::stdin();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stdin.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let input: String = match serde_json::from_str(&line) {
+            Ok(input) => input,
+            Err(err) => {
+                emit(&Event::Error {
+                    message: format!("Expected the input to be a JSON string: {}", err),
+                });
+                continue;
+            }
+        };
+        match repl::eval_line_(vm.root_thread(), &input).await {
+            Ok(Some((value, typ))) => emit_result(value, &typ),
+            Ok(None) => (),
+            Err(err) => emit(&Event::Error {
+                message: err.to_string(),
+            }),
+        }
+    }
+}
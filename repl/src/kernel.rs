@@ -0,0 +1,542 @@
+//! A Jupyter kernel, making gluon usable from notebooks.
+//!
+//! Jupyter starts the kernel as `gluon kernel <connection file>` and then talks to it through the
+//! sockets described in the connection file, using the Jupyter messaging protocol.
+use std::{
+    convert::TryFrom,
+    fmt::Write,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use structopt::StructOpt;
+use zeromq::{
+    PubSocket, RepSocket, RouterSocket, Socket, SocketRecv, SocketSend, ZmqMessage, ZmqResult,
+};
+
+use gluon::{
+    base::{
+        resolve,
+        types::{ArcType, NullInterner, Type, TypeExt},
+    },
+    vm::{api::ValueRef, Variants},
+    RootedThread, Thread,
+};
+
+use crate::repl;
+
+/// Separates the routing identities from the rest of a message
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+const PROTOCOL_VERSION: &str = "5.3";
+
+#[derive(StructOpt)]
+pub struct Opt {
+    #[structopt(
+        name = "CONNECTION_FILE",
+        parse(from_os_str),
+        help = "The connection file written by Jupyter"
+    )]
+    connection_file: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct ConnectionInfo {
+    transport: String,
+    ip: String,
+    shell_port: u16,
+    iopub_port: u16,
+    stdin_port: u16,
+    control_port: u16,
+    hb_port: u16,
+    key: String,
+    signature_scheme: String,
+}
+
+struct Message {
+    identities: Vec<Bytes>,
+    header: Value,
+    content: Value,
+}
+
+impl Message {
+    fn msg_type(&self) -> &str {
+        self.header["msg_type"].as_str().unwrap_or("")
+    }
+}
+
+struct Kernel {
+    vm: RootedThread,
+    key: Option<Hmac<Sha256>>,
+    session: String,
+    message_count: u64,
+    execution_count: u64,
+    iopub: PubSocket,
+    /// Everything printed by the cell being executed
+    output: Arc<Mutex<String>>,
+    /// The thread executing the current cell, so that it can be interrupted
+    running: Arc<Mutex<Option<RootedThread>>>,
+}
+
+/// Returns the current time in the ISO 8601 format used by message headers
+fn now() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let secs_of_day = secs % 86400;
+
+    // Converts days since the epoch to a date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = secs / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders records as a table of their fields and arrays of records as a table with a row for each
+/// record. Returns `None` for all other values.
+fn html_table(vm: &Thread, typ: &ArcType, value: Variants) -> Option<String> {
+    let env = vm.get_env();
+    let typ = resolve::remove_aliases_cow(&env, &mut NullInterner, typ);
+    let mut html = String::from("<table>\n");
+    match &**typ {
+        Type::Record(_) => {
+            let data = match value.as_ref() {
+                ValueRef::Data(data) => data,
+                _ => return None,
+            };
+            if typ.row_iter().next().is_none() {
+                return None;
+            }
+            html.push_str("<tr><th>Field</th><th>Value</th></tr>\n");
+            for (i, field) in typ.row_iter().enumerate() {
+                let value = repl::format_value(vm, &field.typ, data.get_variant(i)?);
+                writeln!(
+                    html,
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    escape_html(field.name.declared_name()),
+                    escape_html(&value)
+                )
+                .unwrap();
+            }
+        }
+        Type::App(_, args) if typ.is_array() => {
+            let element_type = resolve::remove_aliases_cow(&env, &mut NullInterner, &args[0]);
+            let fields = match &**element_type {
+                Type::Record(_) => element_type.row_iter().collect::<Vec<_>>(),
+                _ => return None,
+            };
+            if fields.is_empty() {
+                return None;
+            }
+            let array = match value.as_ref() {
+                ValueRef::Array(array) => array,
+                _ => return None,
+            };
+
+            html.push_str("<tr>");
+            for field in &fields {
+                write!(html, "<th>{}</th>", escape_html(field.name.declared_name())).unwrap();
+            }
+            html.push_str("</tr>\n");
+            for element in array.iter() {
+                let data = match element.as_ref() {
+                    ValueRef::Data(data) => data,
+                    _ => return None,
+                };
+                html.push_str("<tr>");
+                for (i, field) in fields.iter().enumerate() {
+                    let value = repl::format_value(vm, &field.typ, data.get_variant(i)?);
+                    write!(html, "<td>{}</td>", escape_html(&value)).unwrap();
+                }
+                html.push_str("</tr>\n");
+            }
+        }
+        _ => return None,
+    }
+    html.push_str("</table>");
+    Some(html)
+}
+
+fn mac(key: &Hmac<Sha256>, parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = key.clone();
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+impl Kernel {
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        match &self.key {
+            Some(key) => hex::encode(mac(key, parts).finalize().into_bytes()),
+            None => String::new(),
+        }
+    }
+
+    /// Checks `signature` against the signature of `parts` in constant time
+    fn verify(&self, parts: &[&[u8]], signature: &[u8]) -> bool {
+        match &self.key {
+            Some(key) => hex::decode(signature).map_or(false, |signature| {
+                mac(key, parts).verify_slice(&signature).is_ok()
+            }),
+            None => signature.is_empty(),
+        }
+    }
+
+    fn decode(&self, message: ZmqMessage) -> Option<Message> {
+        let frames = message.into_vec();
+        let delimiter = frames.iter().position(|frame| &frame[..] == DELIMITER)?;
+        let parts = frames.get(delimiter + 1..delimiter + 6)?;
+        let signature = &parts[0];
+        let signed = parts[1..].iter().map(|part| &part[..]).collect::<Vec<_>>();
+        if !self.verify(&signed, signature) {
+            warn!("Ignoring message with an invalid signature");
+            return None;
+        }
+        let parse = |part: &Bytes| serde_json::from_slice(part).ok();
+        Some(Message {
+            identities: frames[..delimiter].to_vec(),
+            header: parse(&parts[1])?,
+            content: parse(&parts[4])?,
+        })
+    }
+
+    fn encode(
+        &mut self,
+        identities: Vec<Bytes>,
+        parent: &Message,
+        msg_type: &str,
+        content: Value,
+    ) -> ZmqMessage {
+        self.message_count += 1;
+        let header = json!({
+            "msg_id": format!("{}-{}", self.session, self.message_count),
+            "session": self.session,
+            "username": "kernel",
+            "date": now(),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        });
+        let parts = [
+            header.to_string(),
+            parent.header.to_string(),
+            "{}".to_string(),
+            content.to_string(),
+        ];
+        let signature = self.sign(&[
+            parts[0].as_bytes(),
+            parts[1].as_bytes(),
+            parts[2].as_bytes(),
+            parts[3].as_bytes(),
+        ]);
+
+        let mut frames = identities;
+        frames.push(Bytes::from_static(DELIMITER));
+        frames.push(signature.into());
+        frames.extend(parts.iter().map(|part| Bytes::from(part.clone())));
+        ZmqMessage::try_from(frames).expect("Messages always contain frames")
+    }
+
+    async fn reply(
+        &mut self,
+        socket: &mut RouterSocket,
+        request: &Message,
+        msg_type: &str,
+        content: Value,
+    ) -> ZmqResult<()> {
+        let message = self.encode(request.identities.clone(), request, msg_type, content);
+        socket.send(message).await
+    }
+
+    async fn publish(&mut self, parent: &Message, msg_type: &str, content: Value) -> ZmqResult<()> {
+        let topic = Bytes::from(msg_type.to_string());
+        let message = self.encode(vec![topic], parent, msg_type, content);
+        self.iopub.send(message).await
+    }
+
+    async fn publish_output(&mut self, parent: &Message) -> ZmqResult<()> {
+        let text = std::mem::take(&mut *self.output.lock().unwrap());
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.publish(parent, "stream", json!({ "name": "stdout", "text": text }))
+            .await
+    }
+
+    /// Handles a request on the shell or control socket. Returns `true` if the kernel should shut
+    /// down.
+    async fn handle(&mut self, socket: &mut RouterSocket, message: ZmqMessage) -> ZmqResult<bool> {
+        let request = match self.decode(message) {
+            Some(request) => request,
+            None => return Ok(false),
+        };
+        debug!("Kernel request: {}", request.msg_type());
+
+        self.publish(&request, "status", json!({ "execution_state": "busy" }))
+            .await?;
+        let mut shutdown = false;
+        match request.msg_type() {
+            "kernel_info_request" => {
+                let content = json!({
+                    "status": "ok",
+                    "protocol_version": PROTOCOL_VERSION,
+                    "implementation": "gluon",
+                    "implementation_version": env!("CARGO_PKG_VERSION"),
+                    "language_info": {
+                        "name": "gluon",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "mimetype": "text/x-gluon",
+                        "file_extension": ".glu",
+                    },
+                    "banner": "gluon",
+                    "help_links": [],
+                });
+                self.reply(socket, &request, "kernel_info_reply", content)
+                    .await?;
+            }
+            "execute_request" => {
+                let content = self.execute(&request).await?;
+                self.reply(socket, &request, "execute_reply", content)
+                    .await?;
+            }
+            "complete_request" => {
+                let content = self.complete(&request).await;
+                self.reply(socket, &request, "complete_reply", content)
+                    .await?;
+            }
+            "is_complete_request" => {
+                let code = request.content["code"].as_str().unwrap_or("");
+                let status = if repl::is_incomplete(&self.vm, code) {
+                    "incomplete"
+                } else {
+                    "complete"
+                };
+                let content = json!({ "status": status, "indent": "" });
+                self.reply(socket, &request, "is_complete_reply", content)
+                    .await?;
+            }
+            "shutdown_request" => {
+                let content = json!({
+                    "status": "ok",
+                    "restart": request.content["restart"].as_bool().unwrap_or(false),
+                });
+                self.reply(socket, &request, "shutdown_reply", content)
+                    .await?;
+                shutdown = true;
+            }
+            msg_type => debug!("Ignoring unsupported request `{}`", msg_type),
+        }
+        self.publish(&request, "status", json!({ "execution_state": "idle" }))
+            .await?;
+        Ok(shutdown)
+    }
+
+    /// Executes a cell, publishing its output and result. Returns the content of the reply.
+    async fn execute(&mut self, request: &Message) -> ZmqResult<Value> {
+        let code = request.content["code"].as_str().unwrap_or("");
+        let silent = request.content["silent"].as_bool().unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+            let content = json!({ "code": code, "execution_count": self.execution_count });
+            self.publish(request, "execute_input", content).await?;
+        }
+
+        let result = match self.vm.new_thread() {
+            Ok(thread) => {
+                *self.running.lock().unwrap() = Some(thread.clone());
+                let result = repl::eval_line_value(thread, code).await.map(|value| {
+                    value.map(|(value, typ)| {
+                        let mut data = json!({
+                            "text/plain": repl::format_value(value.vm(), &typ, value.get_variant()),
+                        });
+                        if let Some(html) = html_table(value.vm(), &typ, value.get_variant()) {
+                            data["text/html"] = html.into();
+                        }
+                        data
+                    })
+                });
+                *self.running.lock().unwrap() = None;
+                result.map_err(|err| err.to_string())
+            }
+            Err(err) => Err(err.to_string()),
+        };
+        self.publish_output(request).await?;
+
+        match result {
+            Ok(data) => {
+                if let (Some(data), false) = (data, silent) {
+                    let content = json!({
+                        "execution_count": self.execution_count,
+                        "data": data,
+                        "metadata": {},
+                    });
+                    self.publish(request, "execute_result", content).await?;
+                }
+                Ok(json!({
+                    "status": "ok",
+                    "execution_count": self.execution_count,
+                    "payload": [],
+                    "user_expressions": {},
+                }))
+            }
+            Err(message) => {
+                let error = json!({
+                    "ename": "Error",
+                    "evalue": message,
+                    "traceback": message.lines().collect::<Vec<_>>(),
+                });
+                self.publish(request, "error", error.clone()).await?;
+                let mut content = error;
+                content["status"] = "error".into();
+                content["execution_count"] = self.execution_count.into();
+                Ok(content)
+            }
+        }
+    }
+
+    async fn complete(&self, request: &Message) -> Value {
+        let code = request.content["code"].as_str().unwrap_or("");
+        // Jupyter counts the cursor position in unicode code points
+        let cursor_pos = request.content["cursor_pos"].as_u64().unwrap_or(0) as usize;
+        let pos = code
+            .char_indices()
+            .nth(cursor_pos)
+            .map_or(code.len(), |(i, _)| i);
+
+        let matches = repl::complete(&self.vm, "<kernel>", code, pos)
+            .await
+            .unwrap_or_default();
+
+        // Get the start of the completed identifier
+        let ident_start = code[..pos]
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| c.is_whitespace() || c == '.')
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        json!({
+            "status": "ok",
+            "matches": matches,
+            "cursor_start": code[..ident_start].chars().count(),
+            "cursor_end": cursor_pos,
+            "metadata": {},
+        })
+    }
+}
+
+pub async fn run(opt: &Opt, vm: &Thread) -> anyhow::Result<()> {
+    let info: ConnectionInfo = serde_json::from_str(&fs::read_to_string(&opt.connection_file)?)?;
+    let key = if info.key.is_empty() {
+        None
+    } else if info.signature_scheme == "hmac-sha256" {
+        Some(Hmac::<Sha256>::new_from_slice(info.key.as_bytes())?)
+    } else {
+        anyhow::bail!("Unsupported signature scheme `{}`", info.signature_scheme);
+    };
+
+    let endpoint = |port| format!("{}://{}:{}", info.transport, info.ip, port);
+    let mut shell = RouterSocket::new();
+    shell.bind(&endpoint(info.shell_port)).await?;
+    let mut control = RouterSocket::new();
+    control.bind(&endpoint(info.control_port)).await?;
+    // Reading input from the user is not supported but the socket is still expected to exist
+    let mut stdin = RouterSocket::new();
+    stdin.bind(&endpoint(info.stdin_port)).await?;
+    let mut iopub = PubSocket::new();
+    iopub.bind(&endpoint(info.iopub_port)).await?;
+    let mut heartbeat = RepSocket::new();
+    heartbeat.bind(&endpoint(info.hb_port)).await?;
+
+    tokio::spawn(async move {
+        while let Ok(message) = heartbeat.recv().await {
+            if heartbeat.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let output = Arc::new(Mutex::new(String::new()));
+    vm.global_env().set_stdout_handler(Some(Box::new({
+        let output = output.clone();
+        move |text| output.lock().unwrap().push_str(text)
+    })));
+
+    // Jupyter interrupts the kernel by sending SIGINT
+    let running = Arc::new(Mutex::new(None::<RootedThread>));
+    tokio::spawn({
+        let running = running.clone();
+        async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if let Some(thread) = &*running.lock().unwrap() {
+                    thread.interrupt();
+                }
+            }
+        }
+    });
+
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut kernel = Kernel {
+        vm: vm.root_thread(),
+        key,
+        session: format!("{:x}-{:x}", std::process::id(), since_epoch.as_nanos()),
+        message_count: 0,
+        execution_count: 0,
+        iopub,
+        output,
+        running,
+    };
+
+    loop {
+        let shutdown = tokio::select! {
+            message = shell.recv() => kernel.handle(&mut shell, message?).await?,
+            message = control.recv() => kernel.handle(&mut control, message?).await?,
+        };
+        if shutdown {
+            return Ok(());
+        }
+    }
+}
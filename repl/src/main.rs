@@ -32,9 +32,13 @@ use crate::base::{
 };
 
 use gluon::{
-    new_vm_async, vm::thread::ThreadInternal, vm::Error as VMError, Result, Thread, ThreadExt,
+    bundle::Bundler, new_vm_async, vm::coverage::Coverage, vm::thread::ThreadInternal,
+    vm::Error as VMError, Result, Thread, ThreadExt,
 };
 
+mod json;
+#[cfg(feature = "kernel")]
+mod kernel;
 mod repl;
 
 quick_error! {
@@ -105,12 +109,45 @@ pub struct FmtOpt {
     input: Vec<PathBuf>,
 }
 
+#[derive(StructOpt)]
+#[structopt(about = "Bundles a gluon program and the modules it imports into a single file")]
+pub struct BundleOpt {
+    #[structopt(name = "FILE", help = "The main file of the program")]
+    input: String,
+    #[structopt(
+        short = "o",
+        long = "output",
+        parse(from_os_str),
+        help = "File to write the bundle to"
+    )]
+    output: PathBuf,
+    #[structopt(
+        long = "source-map",
+        parse(from_os_str),
+        help = "Writes a JSON source map of the bundle to this file"
+    )]
+    source_map: Option<PathBuf>,
+    #[structopt(
+        long = "include-std",
+        help = "Bundles the modules of the standard library as well"
+    )]
+    include_std: bool,
+}
+
 #[derive(StructOpt)]
 pub enum SubOpt {
     #[structopt(name = "fmt", about = "Formats gluon source code")]
     Fmt(FmtOpt),
+    #[structopt(
+        name = "bundle",
+        about = "Bundles a gluon program and the modules it imports into a single file"
+    )]
+    Bundle(BundleOpt),
     #[structopt(name = "doc", about = "Documents gluon source code")]
     Doc(::gluon_doc::Opt),
+    #[cfg(feature = "kernel")]
+    #[structopt(name = "kernel", about = "Runs a Jupyter kernel")]
+    Kernel(kernel::Opt),
 }
 
 const LONG_VERSION: &str = concat!(clap::crate_version!(), "\n", "commit: ", env!("GIT_HASH"));
@@ -143,12 +180,33 @@ pub struct Opt {
     )]
     debug_level: base::DebugLevel,
 
+    #[structopt(
+        long = "json",
+        help = "Writes results, errors and printed text to stdout as JSON events, one per line. \
+                With --interactive each line of stdin must be a JSON string to evaluate"
+    )]
+    json: bool,
+
     #[structopt(
         long = "no-std",
         help = "Skip searching the internal standard library for requested modules."
     )]
     no_std: bool,
 
+    #[structopt(
+        long = "std-root",
+        parse(from_os_str),
+        help = "Loads the standard library from this directory instead of the internal one"
+    )]
+    std_root: Option<PathBuf>,
+
+    #[structopt(
+        long = "coverage",
+        parse(from_os_str),
+        help = "Writes the line coverage of the executed files in the lcov format to this file"
+    )]
+    coverage: Option<PathBuf>,
+
     #[structopt(name = "FILE", help = "Executes each file as a gluon program")]
     input: Vec<String>,
 
@@ -175,6 +233,13 @@ where
     Ok(())
 }
 
+fn write_coverage(path: &Path, coverage: &Coverage) -> Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    coverage.write_lcov(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
 #[cfg(feature = "env_logger")]
 fn init_env_logger() {
     let _ = ::env_logger::try_init();
@@ -236,6 +301,37 @@ async fn fmt_stdio(thread: &Thread) -> Result<()> {
     Ok(())
 }
 
+fn bundle(thread: &Thread, bundle_opt: &BundleOpt) -> std::result::Result<(), Error> {
+    let module = filename_to_module(&bundle_opt.input);
+    let bundle = Bundler::new()
+        .include_std(bundle_opt.include_std)
+        .bundle(thread, &module)?;
+    fs::write(&bundle_opt.output, &bundle.source).map_err(gluon::Error::from)?;
+
+    if let Some(path) = &bundle_opt.source_map {
+        let source_map = &bundle.source_map;
+        let mappings = source_map
+            .mappings()
+            .iter()
+            .map(|mapping| {
+                serde_json::json!({
+                    "bundle_offset": mapping.bundle_offset,
+                    "bundle_len": mapping.bundle_len,
+                    "module": mapping.module,
+                    "original_offset": mapping.original_offset,
+                    "original_len": mapping.original_len,
+                })
+            })
+            .collect::<Vec<_>>();
+        let json = serde_json::json!({
+            "modules": source_map.modules(),
+            "mappings": mappings,
+        });
+        fs::write(path, json.to_string()).map_err(gluon::Error::from)?;
+    }
+    Ok(())
+}
+
 async fn run(opt: &Opt, color: Color, vm: &Thread) -> std::result::Result<(), Error> {
     vm.global_env().set_debug_level(opt.debug_level.clone());
     match opt.subcommand_opt {
@@ -270,20 +366,45 @@ async fn run(opt: &Opt, color: Color, vm: &Thread) -> std::result::Result<(), Er
                 fmt_stdio(&thread).await?;
             }
         }
+        Some(SubOpt::Bundle(ref bundle_opt)) => {
+            let thread = new_vm_async().await;
+            thread.get_database_mut().use_standard_lib(!opt.no_std);
+            bundle(&thread, bundle_opt)?;
+        }
         Some(SubOpt::Doc(ref doc_opt)) => {
             let input = &doc_opt.input;
             let output = &doc_opt.output;
             let thread = new_vm_async().await;
             gluon_doc::generate_for_path(&thread, input, output)?;
         }
+        #[cfg(feature = "kernel")]
+        Some(SubOpt::Kernel(ref kernel_opt)) => {
+            kernel::run(kernel_opt, vm).await?;
+        }
         None => {
-            if opt.interactive {
+            if opt.json && opt.interactive {
+                json::run(vm).await?;
+            } else if opt.interactive {
                 let prompt = opt.prompt.clone();
                 let debug_level = opt.debug_level.clone();
                 let use_std_lib = !opt.no_std;
                 repl::run(color, &prompt, debug_level, use_std_lib).await?;
             } else if !opt.input.is_empty() {
-                run_files(&vm, &opt.input).await?;
+                let coverage = opt.coverage.as_ref().map(|path| {
+                    let coverage = Arc::new(Coverage::new());
+                    vm.context().set_coverage(Some(coverage.clone()));
+                    (path, coverage)
+                });
+                let result = if opt.json {
+                    json::run_files(&vm, &opt.input).await
+                } else {
+                    run_files(&vm, &opt.input).await
+                };
+                if let Some((path, coverage)) = coverage {
+                    vm.context().set_coverage(None);
+                    write_coverage(path, &coverage)?;
+                }
+                result?;
             } else {
                 writeln!(io::stderr(), "{}", Opt::clap().get_matches().usage())
                     .expect("Error writing help to stderr");
@@ -302,12 +423,20 @@ async fn main() {
     let vm = new_vm_async().await;
     vm.get_database_mut()
         .use_standard_lib(!opt.no_std)
+        .std_root(opt.std_root.clone())
         .run_io(true);
 
+    if opt.json {
+        json::capture_prints(&vm);
+    }
+
     let color = opt.color;
     let result = run(&opt, opt.color, &vm).await;
     if let Err(err) = result {
         match err {
+            err if opt.json => json::emit(&json::Event::Error {
+                message: err.to_string(),
+            }),
             Error::Gluon(gluon::Error::VM(VMError::Message(_))) => {
                 eprintln!("{}\n{}", err, vm.context().stacktrace(0))
             }
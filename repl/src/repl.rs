@@ -1,13 +1,24 @@
 extern crate gluon_completion as completion;
 
-use std::{borrow::Cow, error::Error as StdError, path::PathBuf, str::FromStr, sync::Mutex};
+use std::{
+    borrow::Cow,
+    error::Error as StdError,
+    fmt, mem,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::Poll,
+};
 
-use futures::{channel::oneshot, future, prelude::*};
+use futures::{
+    channel::oneshot,
+    future::{self, BoxFuture},
+    prelude::*,
+};
 
 use crate::base::{
     ast::{self, AstClone, Expr, Pattern, RootExpr, SpannedPattern, Typed, TypedIdent},
     error::InFile,
-    kind::Kind,
     mk_ast_arena, pos, resolve,
     symbol::{Symbol, SymbolModule},
     types::{ArcType, TypeExt},
@@ -20,14 +31,15 @@ use crate::vm::{
         IO,
     },
     internal::ValuePrinter,
-    thread::{ActiveThread, RootedValue, Thread},
+    thread::{ActiveThread, HookFlags, RootedValue, Thread, ThreadInternal},
     {self, Error as VMError, Result as VMResult},
 };
 
 use gluon::{
     compiler_pipeline::{Executable, ExecuteValue},
+    debugger::FrameScope,
     import::add_extern_module_with_deps,
-    query::CompilerDatabase,
+    query::{CompilationBase, CompilerDatabase},
     Error as GluonError, Result as GluonResult, RootedThread, ThreadExt,
 };
 
@@ -54,13 +66,8 @@ fn type_of_expr(args: WithVM<&str>) -> impl Future<Output = IO<Result<String, St
 fn find_kind(args: WithVM<&str>) -> IO<Result<String, String>> {
     let vm = args.vm;
     let args = args.value.trim();
-    IO::Value(match vm.find_type_info(args) {
-        Ok(ref alias) => {
-            let kind = alias.params().iter().rev().fold(Kind::typ(), |acc, arg| {
-                Kind::function(arg.kind.clone(), acc)
-            });
-            Ok(format!("{}", kind))
-        }
+    IO::Value(match vm.kindcheck_type("<repl>", args) {
+        Ok(kind) => Ok(format!("{}", kind)),
         Err(err) => Err(format!("{}", err)),
     })
 }
@@ -118,7 +125,12 @@ fn switch_debug_level(args: WithVM<&str>) -> IO<Result<String, String>> {
     IO::Value(Ok(vm.global_env().get_debug_level().to_string()))
 }
 
-fn complete(thread: &Thread, name: &str, fileinput: &str, pos: usize) -> GluonResult<Vec<String>> {
+pub(crate) async fn complete(
+    thread: &Thread,
+    name: &str,
+    fileinput: &str,
+    pos: usize,
+) -> GluonResult<Vec<String>> {
     use gluon::compiler_pipeline::*;
 
     let mut db = thread.get_database();
@@ -137,12 +149,16 @@ fn complete(thread: &Thread, name: &str, fileinput: &str, pos: usize) -> GluonRe
     };
 
     // Only need the typechecker to fill infer the types as best it can regardless of errors
-    let _ = (&mut expr).typecheck(&mut module_compiler, thread, &name, fileinput);
+    let _ = (&mut expr)
+        .typecheck(&mut module_compiler, thread, &name, fileinput)
+        .await;
     let file_map = module_compiler
         .get_filemap(&name)
         .ok_or_else(|| VMError::from("FileMap is missing for completion".to_string()))?;
-    let suggestions = completion::suggest(
-        &thread.get_env(),
+    let env = thread.get_env();
+    let suggestions = completion::SuggestionQuery::default().suggest_with_metadata(
+        &env,
+        &env,
         file_map.span(),
         &expr.expr(),
         file_map.span().start() + pos::ByteOffset::from(pos as i64),
@@ -164,32 +180,37 @@ struct Completer {
 
 impl rustyline::Helper for Completer {}
 
+/// Returns true if `line` is the start of an expression which continues on the following lines
+pub(crate) fn is_incomplete(thread: &Thread, line: &str) -> bool {
+    let is_incomplete = |err: &gluon::parser::ParseErrors| {
+        use gluon::parser::Token;
+
+        err.iter().any(|err| match &err.value {
+            gluon::parser::Error::UnexpectedToken(Token::CloseBlock, _) => true,
+            _ => false,
+        })
+    };
+
+    let mut db = thread.get_database();
+    let mut module_compiler = thread.module_compiler(&mut db);
+    mk_ast_arena!(arena);
+    let filemap = thread.get_database().add_filemap("line", line);
+    let mut module = SymbolModule::new("line".into(), module_compiler.mut_symbols());
+    match parse_partial_repl_line((*arena).borrow(), &mut module, &*filemap) {
+        Err((_, err)) => is_incomplete(&err),
+        Ok(_) => false,
+    }
+}
+
 impl rustyline::validate::Validator for Completer {
     fn validate(
         &self,
         ctx: &mut rustyline::validate::ValidationContext,
     ) -> rustyline::Result<rustyline::validate::ValidationResult> {
-        let line = ctx.input();
-
-        let is_incomplete = |err: &gluon::parser::ParseErrors| {
-            use gluon::parser::Token;
-
-            err.iter().any(|err| match &err.value {
-                gluon::parser::Error::UnexpectedToken(Token::CloseBlock, _) => true,
-                _ => false,
-            })
-        };
-
-        let mut db = self.thread.get_database();
-        let mut module_compiler = self.thread.module_compiler(&mut db);
-        mk_ast_arena!(arena);
-        let filemap = self.thread.get_database().add_filemap("line", line);
-        let mut module = SymbolModule::new("line".into(), module_compiler.mut_symbols());
-        match parse_partial_repl_line((*arena).borrow(), &mut module, &*filemap) {
-            Err((_, err)) if is_incomplete(&err) => {
-                Ok(rustyline::validate::ValidationResult::Incomplete)
-            }
-            Ok(_) | Err(_) => Ok(rustyline::validate::ValidationResult::Valid(None)),
+        if is_incomplete(&self.thread, ctx.input()) {
+            Ok(rustyline::validate::ValidationResult::Incomplete)
+        } else {
+            Ok(rustyline::validate::ValidationResult::Valid(None))
         }
     }
 }
@@ -248,7 +269,7 @@ impl rustyline::completion::Completer for Completer {
         pos: usize,
         _: &rustyline::Context,
     ) -> rustyline::Result<(usize, Vec<String>)> {
-        let result = complete(&self.thread, "<repl>", line, pos);
+        let result = futures::executor::block_on(complete(&self.thread, "<repl>", line, pos));
 
         // Get the start of the completed identifier
         let ident_start = line[..pos]
@@ -360,29 +381,291 @@ fn readline(editor: &Editor, prompt: &str) -> IO<Result<String, ReadlineError>>
     IO::Value(Ok(input))
 }
 
+#[derive(Clone, Debug, PartialEq)]
+struct Breakpoint {
+    module: String,
+    /// One-based line number
+    line: usize,
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.module, self.line)
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Lines entered directly into the repl are compiled as the module `line`
+        let (module, line) = match s.rfind(':') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => ("line", s),
+        };
+        match line.parse() {
+            Ok(line) if line > 0 => Ok(Breakpoint {
+                module: module.to_string(),
+                line,
+            }),
+            _ => Err(format!(
+                "Expected a breakpoint as `[MODULE:]LINE`, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Default)]
+struct DebuggerState {
+    breakpoints: Vec<Breakpoint>,
+    /// The variables of the frame where a breakpoint was last hit
+    frame: Option<(Breakpoint, FrameScope)>,
+    /// Set by the hook when a breakpoint is hit, until the evaluation has been suspended
+    paused: bool,
+    /// The rest of the evaluation which is suspended at the last breakpoint
+    suspended: Option<(RootedThread, BoxFuture<'static, ()>)>,
+}
+
+#[derive(Userdata, Trace, VmType)]
+#[gluon(vm_type = "Debugger")]
+#[gluon_trace(skip)]
+struct Debugger {
+    state: Arc<Mutex<DebuggerState>>,
+}
+
+impl_userdata! { Debugger }
+
+impl Debugger {
+    /// Installs a hook on `thread` which captures the frame of each breakpoint that is hit and
+    /// suspends the execution until it is continued with `continue_execution`.
+    fn install_hook(&self, thread: &Thread) {
+        if self.state.lock().unwrap().breakpoints.is_empty() {
+            return;
+        }
+        let state = self.state.clone();
+        let mut context = thread.context();
+        context.set_hook(Some(Box::new(move |thread, debug_info| {
+            if let Some(frame) = debug_info.stack_info(0) {
+                let mut state = state.lock().unwrap();
+                let hit = frame.line().and_then(|line| {
+                    state
+                        .breakpoints
+                        .iter()
+                        .find(|breakpoint| {
+                            breakpoint.module == frame.source_name()
+                                && breakpoint.line == line.number().to_usize()
+                        })
+                        .cloned()
+                });
+                if let Some(breakpoint) = hit {
+                    state.frame = Some((breakpoint, FrameScope::capture(thread, &frame)));
+                    state.paused = true;
+                    // The hook is not run again for this line when the thread is resumed
+                    return Poll::Pending;
+                }
+            }
+            Poll::Ready(Ok(()))
+        })));
+        context.set_hook_mask(HookFlags::LINE_FLAG);
+    }
+}
+
+/// Drives `eval` until it finishes or is suspended at a breakpoint, in which case it is stored in
+/// the debugger so that it can be continued later
+fn run_until_breakpoint(
+    vm: RootedThread,
+    state: Arc<Mutex<DebuggerState>>,
+    eval: BoxFuture<'static, ()>,
+) -> impl Future<Output = ()> {
+    let mut eval = Some(eval);
+    future::poll_fn(move |cx| {
+        let eval_ref = eval.as_mut().expect("Polled after completion");
+        if eval_ref.as_mut().poll(cx).is_ready() {
+            eval = None;
+            return Poll::Ready(());
+        }
+        let mut state = state.lock().unwrap();
+        if !mem::take(&mut state.paused) {
+            return Poll::Pending;
+        }
+        if let Some((breakpoint, _)) = &state.frame {
+            print_line(&vm, &format!("Breakpoint hit at {}", breakpoint));
+        }
+        state.suspended = Some((vm.clone(), eval.take().unwrap()));
+        Poll::Ready(())
+    })
+}
+
+fn continue_execution(debugger: &Debugger) -> impl Future<Output = IO<Result<(), String>>> {
+    let state = debugger.state.clone();
+    let suspended = state.lock().unwrap().suspended.take();
+    async move {
+        match suspended {
+            Some((vm, eval)) => {
+                run_until_breakpoint(vm, state, eval).await;
+                IO::Value(Ok(()))
+            }
+            None => IO::Value(Err("Execution is not suspended at a breakpoint".to_string())),
+        }
+    }
+}
+
+/// Prints `text` as a line of output of the repl
+fn print_line(vm: &Thread, text: &str) {
+    if let Err(err) = vm.global_env().write_stdout(&format!("{}\n", text)) {
+        eprintln!("{}", err);
+    }
+}
+
+fn new_debugger(_: ()) -> IO<Debugger> {
+    IO::Value(Debugger {
+        state: Default::default(),
+    })
+}
+
+fn set_breakpoint(
+    debugger: &Debugger,
+    WithVM { vm, value: arg }: WithVM<&str>,
+) -> IO<Result<String, String>> {
+    let mut state = debugger.state.lock().unwrap();
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return IO::Value(Ok(if state.breakpoints.is_empty() {
+            "No breakpoints are set".to_string()
+        } else {
+            state
+                .breakpoints
+                .iter()
+                .map(|breakpoint| breakpoint.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }));
+    }
+    let mut breakpoint = match arg.parse::<Breakpoint>() {
+        Ok(breakpoint) => breakpoint,
+        Err(err) => return IO::Value(Err(err)),
+    };
+    let mut msg = String::new();
+    // Lines entered into the repl are all compiled as `line` so the lines of the previous input
+    // say nothing about the next
+    let executable_lines = if breakpoint.module == "line" {
+        None
+    } else {
+        vm.get_database().peek_executable_lines(&breakpoint.module)
+    };
+    if let Some(lines) = executable_lines {
+        let line = pos::Line(breakpoint.line as u32 - 1);
+        if !lines.contains(line) {
+            match lines.next_executable(line) {
+                Some(next) => {
+                    msg = format!("Line {} has no code, ", breakpoint.line);
+                    breakpoint.line = next.to_usize() + 1;
+                }
+                None => {
+                    return IO::Value(Err(format!(
+                        "`{}` has no code at or after line {}",
+                        breakpoint.module, breakpoint.line
+                    )))
+                }
+            }
+        }
+    }
+    msg.push_str(&format!("Breakpoint set at {}", breakpoint));
+    if !state.breakpoints.contains(&breakpoint) {
+        state.breakpoints.push(breakpoint);
+    }
+    IO::Value(Ok(msg))
+}
+
+fn eval_in_frame(
+    debugger: &Debugger,
+    WithVM { vm, value: expr }: WithVM<&str>,
+) -> impl Future<Output = IO<Result<String, String>>> {
+    let frame = debugger.state.lock().unwrap().frame.clone();
+    let vm = vm.new_thread().unwrap();
+    let expr = expr.to_string();
+    async move {
+        let (_, scope) = match frame {
+            Some(frame) => frame,
+            None => return IO::Value(Err("No breakpoint has been hit".to_string())),
+        };
+        IO::Value(
+            match vm.eval_in_frame_async(&scope, "<frame>", &expr).await {
+                Ok((value, typ)) => Ok(format_value(value.vm(), &typ, value.get_variant())),
+                Err(err) => Err(err.to_string()),
+            },
+        )
+    }
+}
+
 fn eval_line(
     De(color): De<crate::Color>,
+    debugger: &Debugger,
     WithVM { vm, value: line }: WithVM<&str>,
 ) -> impl Future<Output = IO<()>> {
     let vm = vm.new_thread().unwrap(); // TODO Reuse the current thread
+    let state = debugger.state.clone();
+    let is_suspended = state.lock().unwrap().suspended.is_some();
+    debugger.install_hook(&vm);
     let line = line.to_string();
     async move {
-        eval_line_(vm.root_thread(), &line)
-            .map(move |result| match result {
-                Ok(x) => IO::Value(x),
-                Err(err) => {
-                    let mut stderr = termcolor::StandardStream::stderr(color.into());
-                    if let Err(err) = err.emit(&mut stderr) {
-                        eprintln!("{}", err);
+        if is_suspended {
+            print_line(
+                &vm,
+                "Execution is suspended at a breakpoint, use `:continue` to resume it",
+            );
+            return IO::Value(());
+        }
+        let eval = {
+            let vm = vm.clone();
+            async move {
+                match eval_line_(vm.root_thread(), &line).await {
+                    Ok(evaluated) => {
+                        if let Some((value, _)) = evaluated {
+                            print_line(&vm, &value);
+                        }
+                    }
+                    Err(err) => {
+                        let mut stderr = termcolor::StandardStream::stderr(color.into());
+                        if let Err(err) = err.emit(&mut stderr) {
+                            eprintln!("{}", err);
+                        }
                     }
-                    IO::Value(())
                 }
-            })
-            .await
+            }
+        };
+        run_until_breakpoint(vm, state, eval.boxed()).await;
+        IO::Value(())
     }
 }
 
-async fn eval_line_(vm: RootedThread, line: &str) -> gluon::Result<()> {
+/// Formats `value` the way the repl prints values
+pub(crate) fn format_value(vm: &Thread, typ: &ArcType, value: vm::Variants) -> String {
+    let env = vm.get_env();
+    let debug_level = vm.global_env().get_debug_level();
+    ValuePrinter::new(&env, typ, value, &debug_level)
+        .width(80)
+        .max_level(5)
+        .to_string()
+}
+
+/// Evaluates a line of input, returning the formatted value and type of the result. Returns
+/// `None` if the line was empty.
+pub(crate) async fn eval_line_(
+    vm: RootedThread,
+    line: &str,
+) -> gluon::Result<Option<(String, ArcType)>> {
+    Ok(eval_line_value(vm, line)
+        .await?
+        .map(|(value, typ)| (format_value(value.vm(), &typ, value.get_variant()), typ)))
+}
+
+/// Evaluates a line of input like `eval_line_` but returns the value itself
+pub(crate) async fn eval_line_value(
+    vm: RootedThread,
+    line: &str,
+) -> gluon::Result<Option<(RootedValue<RootedThread>, ArcType)>> {
     let mut is_let_binding = false;
     let mut eval_expr;
     let value = {
@@ -407,7 +690,7 @@ async fn eval_line_(vm: RootedThread, line: &str) -> gluon::Result<()> {
                     }
                 };
                 match repl_line {
-                    None => return Ok(()),
+                    None => return Ok(None),
                     Some(ReplLine::Expr(expr)) => RootExpr::new(arena.clone(), arena.alloc(expr)),
                     Some(ReplLine::Let(let_binding)) => {
                         is_let_binding = true;
@@ -473,16 +756,7 @@ async fn eval_line_(vm: RootedThread, line: &str) -> gluon::Result<()> {
             &value.as_ref(),
         )?;
     }
-    let vm = value.vm();
-    let env = vm.get_env();
-    let debug_level = vm.global_env().get_debug_level();
-    println!(
-        "{}",
-        ValuePrinter::new(&env, &typ, value.get_variant(), &debug_level)
-            .width(80)
-            .max_level(5)
-    );
-    Ok(())
+    Ok(Some((value, typ)))
 }
 
 fn set_globals(
@@ -643,17 +917,24 @@ struct Settings<'a> {
 }
 
 fn load_repl(vm: &Thread) -> vm::Result<vm::ExternModule> {
+    vm.register_type::<Debugger>("Debugger", &[])?;
+
     vm::ExternModule::new(
         vm,
         record!(
             type Color => Color,
             type Settings => Settings<'static>,
+            type Debugger => Debugger,
             type_of_expr => primitive!(1, async fn type_of_expr),
             find_info => primitive!(1, find_info),
             find_kind => primitive!(1, find_kind),
             parse_color => primitive!(1, "parse_color", |s: &str| s.parse::<Color>()),
             switch_debug_level => primitive!(1, switch_debug_level),
-            eval_line => primitive!(2, async fn eval_line),
+            new_debugger => primitive!(1, new_debugger),
+            set_breakpoint => primitive!(2, set_breakpoint),
+            eval_in_frame => primitive!(2, async fn eval_in_frame),
+            continue_execution => primitive!(1, async fn continue_execution),
+            eval_line => primitive!(3, async fn eval_line),
             finish_or_interrupt => primitive!(2, async fn finish_or_interrupt),
         ),
     )
@@ -787,6 +1068,10 @@ mod tests {
             find_kind.call_async("std.prelude.Semigroup").await,
             Ok(IO::Value(Ok("Type -> Type".into())))
         );
+        assert_eq!(
+            find_kind.call_async("std.prelude.Semigroup Int").await,
+            Ok(IO::Value(Ok("Type".into())))
+        );
     }
 
     #[tokio::test]
@@ -811,6 +1096,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_breakpoint() {
+        assert_eq!(
+            "3".parse(),
+            Ok(Breakpoint {
+                module: "line".into(),
+                line: 3
+            })
+        );
+        assert_eq!(
+            "std.list:12".parse(),
+            Ok(Breakpoint {
+                module: "std.list".into(),
+                line: 12
+            })
+        );
+        assert!("test:0".parse::<Breakpoint>().is_err());
+        assert!("test:".parse::<Breakpoint>().is_err());
+    }
+
+    #[tokio::test]
+    async fn eval_in_frame_at_breakpoint() {
+        let _ = env_logger::try_init();
+        let vm = new_vm().await;
+        vm.get_database_mut().set_optimize(false);
+
+        let debugger = Debugger {
+            state: Default::default(),
+        };
+        let _ = set_breakpoint(
+            &debugger,
+            WithVM {
+                vm: &vm,
+                value: "3",
+            },
+        );
+
+        let thread = vm.new_thread().unwrap();
+        debugger.install_hook(&thread);
+        let value = Arc::new(Mutex::new(None));
+        let eval = {
+            let thread = thread.clone();
+            let value = value.clone();
+            async move {
+                let evaluated = eval_line_(thread, "let f x =\n    let y = x #Int+ 1\n    y\nf 2")
+                    .await
+                    .unwrap_or_else(|err| panic!("{}", err));
+                *value.lock().unwrap() = evaluated.map(|(value, _)| value);
+            }
+        };
+        run_until_breakpoint(thread, debugger.state.clone(), eval.boxed()).await;
+        assert!(debugger.state.lock().unwrap().suspended.is_some());
+        assert_eq!(*value.lock().unwrap(), None);
+
+        let result = eval_in_frame(
+            &debugger,
+            WithVM {
+                vm: &vm,
+                value: "x #Int* y",
+            },
+        )
+        .await;
+        assert_eq!(result, IO::Value(Ok("6".into())));
+
+        assert_eq!(continue_execution(&debugger).await, IO::Value(Ok(())));
+        assert_eq!(*value.lock().unwrap(), Some("3".to_string()));
+        assert!(debugger.state.lock().unwrap().suspended.is_none());
+    }
+
     #[tokio::test]
     async fn complete_repl_empty() {
         let _ = env_logger::try_init();
@@ -818,6 +1172,19 @@ mod tests {
         compile_repl(&vm)
             .await
             .unwrap_or_else(|err| panic!("{}", err));
-        complete(&vm, "<repl>", "", 0).unwrap_or_else(|err| panic!("{}", err));
+        complete(&vm, "<repl>", "", 0)
+            .await
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    #[tokio::test]
+    async fn complete_repl_fields() {
+        let _ = env_logger::try_init();
+        let vm = new_vm().await;
+        let line = "let io = import! std.io\nio.printl";
+        let suggestions = complete(&vm, "<repl>", line, line.len())
+            .await
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(suggestions, ["println"]);
     }
 }
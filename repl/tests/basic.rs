@@ -7,6 +7,16 @@ use std::process::{Command, Stdio};
 
 #[test]
 fn issue_365_run_io_from_command_line() {
+    let output = gluon_command().arg("tests/print.glu").output().unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr != "" {
+        panic!("{}", stderr);
+    }
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "123\n");
+}
+
+fn gluon_command() -> Command {
     if ::std::env::var("GLUON_PATH").is_err() {
         ::std::env::set_var("GLUON_PATH", "..");
     }
@@ -17,17 +27,73 @@ fn issue_365_run_io_from_command_line() {
         .and_then(|p| p.parent())
         .expect("folder")
         .join("gluon");
-    let output = Command::new(&*gluon_path)
+    let mut command = Command::new(&*gluon_path);
+    command
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
+        .stderr(Stdio::piped());
+    command
+}
+
+#[test]
+fn json_output_from_file() {
+    let output = gluon_command()
+        .arg("--json")
         .arg("tests/print.glu")
         .output()
-        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+        .unwrap();
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    if stderr != "" {
-        panic!("{}", stderr);
-    }
-    assert_eq!(String::from_utf8_lossy(&output.stdout), "123\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let events: Vec<_> = stdout.lines().collect();
+    assert_eq!(events[0], r#"{"event":"print","text":"123\n"}"#);
+    assert!(
+        events[1].starts_with(r#"{"event":"result","#),
+        "{}",
+        events[1]
+    );
+}
+
+#[test]
+fn json_interactive() {
+    use std::io::Write;
+
+    let mut child = gluon_command()
+        .arg("--json")
+        .arg("--interactive")
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            br#""1 + 2"
+"let x = \"abc\""
+"x ++ 1"
+1
+"#,
+        )
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let events: Vec<_> = stdout.lines().collect();
+    assert_eq!(events.len(), 4, "{}", stdout);
+    assert_eq!(events[0], r#"{"event":"result","value":"3","type":"Int"}"#);
+    assert_eq!(
+        events[1],
+        r#"{"event":"result","value":"\"abc\"","type":"String"}"#
+    );
+    assert!(
+        events[2].starts_with(r#"{"event":"error","#),
+        "{}",
+        events[2]
+    );
+    assert!(
+        events[3].starts_with(r#"{"event":"error","#),
+        "{}",
+        events[3]
+    );
 }
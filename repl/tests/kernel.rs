@@ -0,0 +1,276 @@
+#![cfg(feature = "kernel")]
+
+use std::{
+    convert::TryFrom,
+    env,
+    net::TcpListener,
+    path::Path,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use zeromq::{DealerSocket, Socket, SocketRecv, SocketSend, SubSocket, ZmqMessage};
+
+const KEY: &str = "a0436f6c-1916-498b-8eb9-e81ab9368e84";
+
+struct Client {
+    shell: DealerSocket,
+    iopub: SubSocket,
+    kernel: Child,
+    message_count: u64,
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn sign(parts: &[Bytes]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(KEY.as_bytes()).unwrap();
+    for part in parts {
+        mac.update(part);
+    }
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Returns the header, parent header and content of a message, checking its signature
+fn decode(message: ZmqMessage) -> (Value, Value, Value) {
+    let frames = message.into_vec();
+    let delimiter = frames
+        .iter()
+        .position(|frame| &frame[..] == b"<IDS|MSG>")
+        .expect("delimiter");
+    let parts = &frames[delimiter + 1..];
+    assert_eq!(sign(&parts[1..5]).as_bytes(), &parts[0][..]);
+    let parse = |part: &Bytes| serde_json::from_slice(part).unwrap();
+    (parse(&parts[1]), parse(&parts[2]), parse(&parts[4]))
+}
+
+impl Client {
+    async fn start() -> Client {
+        if env::var("GLUON_PATH").is_err() {
+            env::set_var("GLUON_PATH", "..");
+        }
+
+        let (shell_port, iopub_port) = (free_port(), free_port());
+        let connection_file = env::temp_dir().join(format!("gluon-kernel-{}.json", shell_port));
+        let connection = json!({
+            "transport": "tcp",
+            "ip": "127.0.0.1",
+            "shell_port": shell_port,
+            "iopub_port": iopub_port,
+            "stdin_port": free_port(),
+            "control_port": free_port(),
+            "hb_port": free_port(),
+            "key": KEY,
+            "signature_scheme": "hmac-sha256",
+        });
+        std::fs::write(&connection_file, connection.to_string()).unwrap();
+
+        let path = env::args().next().unwrap();
+        let gluon_path = Path::new(&path[..])
+            .parent()
+            .and_then(|p| p.parent())
+            .expect("folder")
+            .join("gluon");
+        let kernel = Command::new(&*gluon_path)
+            .arg("kernel")
+            .arg(&connection_file)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut shell = DealerSocket::new();
+        shell
+            .connect(&format!("tcp://127.0.0.1:{}", shell_port))
+            .await
+            .unwrap();
+        let mut iopub = SubSocket::new();
+        iopub
+            .connect(&format!("tcp://127.0.0.1:{}", iopub_port))
+            .await
+            .unwrap();
+        iopub.subscribe("").await.unwrap();
+
+        Client {
+            shell,
+            iopub,
+            kernel,
+            message_count: 0,
+        }
+    }
+
+    async fn send(&mut self, msg_type: &str, content: Value) -> String {
+        self.message_count += 1;
+        let msg_id = format!("test-{}", self.message_count);
+        let header = json!({
+            "msg_id": msg_id,
+            "session": "test",
+            "username": "test",
+            "date": "2021-01-01T00:00:00.000000Z",
+            "msg_type": msg_type,
+            "version": "5.3",
+        });
+        let parts = [
+            Bytes::from(header.to_string()),
+            Bytes::from("{}"),
+            Bytes::from("{}"),
+            Bytes::from(content.to_string()),
+        ];
+        let mut frames = vec![Bytes::from("<IDS|MSG>"), Bytes::from(sign(&parts))];
+        frames.extend(parts.iter().cloned());
+        self.shell
+            .send(ZmqMessage::try_from(frames).unwrap())
+            .await
+            .unwrap();
+        msg_id
+    }
+
+    /// Sends `kernel_info_request` until messages published by the kernel are received, as
+    /// messages published before the subscription reaches the kernel are dropped
+    async fn wait_for_iopub(&mut self) {
+        loop {
+            self.send("kernel_info_request", json!({})).await;
+            let (_, _, reply) = decode(self.shell.recv().await.unwrap());
+            assert_eq!(reply["implementation"], "gluon");
+            if let Ok(message) =
+                tokio::time::timeout(Duration::from_millis(200), self.iopub.recv()).await
+            {
+                message.unwrap();
+                return;
+            }
+        }
+    }
+
+    /// Sends a request and returns its reply along with the messages published while handling it
+    async fn request(&mut self, msg_type: &str, content: Value) -> (Value, Vec<(String, Value)>) {
+        let msg_id = self.send(msg_type, content).await;
+
+        let (header, _, reply) = decode(self.shell.recv().await.unwrap());
+        assert_eq!(header["msg_type"], msg_type.replace("request", "reply"));
+
+        let mut published = Vec::new();
+        loop {
+            let (header, parent, content) = decode(self.iopub.recv().await.unwrap());
+            if parent["msg_id"] != msg_id {
+                continue;
+            }
+            if header["msg_type"] == "status" && content["execution_state"] == "idle" {
+                break;
+            }
+            published.push((header["msg_type"].as_str().unwrap().to_string(), content));
+        }
+        (reply, published)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Avoid leaving the kernel running if the test fails
+        let _ = self.kernel.kill();
+    }
+}
+
+async fn run_kernel_test() {
+    let mut client = Client::start().await;
+
+    client.wait_for_iopub().await;
+
+    let (reply, published) = client
+        .request(
+            "execute_request",
+            json!({ "code": "let io = import! std.io\nio.println \"hello\"", "silent": false }),
+        )
+        .await;
+    assert_eq!(reply["status"], "ok");
+    let stream = published
+        .iter()
+        .find(|(msg_type, _)| msg_type == "stream")
+        .map(|(_, content)| content.clone());
+    assert_eq!(stream, Some(json!({ "name": "stdout", "text": "hello\n" })));
+
+    let (reply, published) = client
+        .request(
+            "execute_request",
+            json!({ "code": "{ x = 1, y = \"abc\" }", "silent": false }),
+        )
+        .await;
+    assert_eq!(reply["status"], "ok");
+    let result = published
+        .iter()
+        .find(|(msg_type, _)| msg_type == "execute_result")
+        .map(|(_, content)| content.clone())
+        .expect("execute_result");
+    assert_eq!(result["data"]["text/plain"], "{ x: 1, y: \"abc\", }");
+    assert_eq!(
+        result["data"]["text/html"],
+        "<table>\n<tr><th>Field</th><th>Value</th></tr>\n\
+         <tr><th>x</th><td>1</td></tr>\n\
+         <tr><th>y</th><td>&quot;abc&quot;</td></tr>\n</table>"
+    );
+
+    let (_, published) = client
+        .request(
+            "execute_request",
+            json!({ "code": "[{ a = 1, b = 2.0 }, { a = 3, b = 4.0 }]", "silent": false }),
+        )
+        .await;
+    let result = published
+        .iter()
+        .find(|(msg_type, _)| msg_type == "execute_result")
+        .map(|(_, content)| content.clone())
+        .expect("execute_result");
+    assert_eq!(
+        result["data"]["text/html"],
+        "<table>\n<tr><th>a</th><th>b</th></tr>\n\
+         <tr><td>1</td><td>2</td></tr>\n\
+         <tr><td>3</td><td>4</td></tr>\n</table>"
+    );
+
+    let (reply, published) = client
+        .request(
+            "execute_request",
+            json!({ "code": "1 + \"a\"", "silent": false }),
+        )
+        .await;
+    assert_eq!(reply["status"], "error");
+    assert!(published.iter().any(|(msg_type, _)| msg_type == "error"));
+
+    let (reply, _) = client
+        .request(
+            "complete_request",
+            json!({ "code": "let io = import! std.io\nio.printl", "cursor_pos": 33 }),
+        )
+        .await;
+    assert_eq!(reply["cursor_start"], 27);
+    assert_eq!(reply["cursor_end"], 33);
+    assert!(
+        reply["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|m| m == "println"),
+        "{}",
+        reply
+    );
+
+    let (reply, _) = client
+        .request("shutdown_request", json!({ "restart": false }))
+        .await;
+    assert_eq!(reply["status"], "ok");
+    assert!(client.kernel.wait().unwrap().success());
+}
+
+#[tokio::test]
+async fn kernel() {
+    tokio::time::timeout(Duration::from_secs(60), run_kernel_test())
+        .await
+        .expect("Kernel test timed out");
+}
@@ -171,3 +171,16 @@ fn assert() {
     repl.test("let { assert } = import! std.test", None);
     repl.test("assert False", None);
 }
+
+#[test]
+fn suspend_at_breakpoint() {
+    let mut repl = REPL::new();
+
+    repl.test(":b 1", None);
+    repl.test("let x = 1 #Int+ 2", Some("Breakpoint hit at line:1"));
+    repl.test("x", Some("Execution is suspended at a breakpoint"));
+    repl.test(":cont", Some("3"));
+    repl.test("x #Int* 2", Some("Breakpoint hit at line:1"));
+    repl.test(":ef x", Some("3"));
+    repl.test(":cont", Some("6"));
+}
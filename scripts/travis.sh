@@ -25,6 +25,7 @@ if [ -z $NO_NORMAL_TEST ]; then
     cargo test --features "test" --all --examples "$@"
     cargo test --features "test" --all --benches "$@"
     cargo test --features "test" -p gluon_parser --benches "$@"
+    cargo test -p gluon_repl --features "kernel" --test kernel "$@"
     echo "" | cargo run --features "test" --example 24
     cargo run --features "test" --example marshalling
 
@@ -32,6 +32,8 @@ perl -p -i -e 's/version *= *"[0-9.]+"([^#]+)# GLUON/version = "'$1'"$1# GLUON/'
 perl -p -i -e 's/^gluon *= *"[0-9.]+"/gluon = "'$1'"/' \
      README.md
 
+echo "$1" > std/VERSION
+
 perl -p -i -e 's/[0-9][0-9.]+([^#]+)# GLUON/'$1'$1# GLUON/' \
      $(ls **/src/lib.rs src/lib.rs)
 
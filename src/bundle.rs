@@ -0,0 +1,454 @@
+//! Bundling of a module and the modules it imports into a single gluon source file.
+//!
+//! Every module which the bundled module (transitively) imports is bound to a generated name at
+//! the top of the bundle, in dependency order, and each `import!` of it is replaced by that name.
+//! Since the bindings of a module stay local to its generated binding only the modules
+//! themselves need to be renamed.
+//!
+//! Modules implemented in Rust (see `add_extern_module`) can't be bundled so their imports are
+//! left as is, the same goes for the standard library unless `Bundler::include_std` is set.
+//!
+//! The `SourceMap` of a bundle translates positions in the bundle back to the modules they came
+//! from so that errors in the bundle can still be reported against the original files.
+
+use std::{borrow::Cow, sync::Arc};
+
+use crate::base::{
+    ast::{self, expr_to_path, Expr, Literal, Pattern, SpannedExpr, SpannedPattern, Visitor},
+    error::InFile,
+    filename_to_module,
+    fnv::FnvMap,
+    pos::{BytePos, Span},
+    symbol::{Symbol, SymbolModule},
+};
+
+use crate::{
+    import,
+    query::{self, ExternLoaderQuery},
+    vm::{macros, thread::Thread},
+    ModuleCompiler, Result, ThreadExt,
+};
+
+/// Indentation of the modules bound at the top of the bundle
+const INDENT: &str = "    ";
+
+/// A module and the modules it imports, combined into a single gluon source file
+#[derive(Clone, Debug)]
+pub struct Bundle {
+    pub source: String,
+    pub source_map: SourceMap,
+}
+
+/// Maps byte offsets in the source of a `Bundle` back to byte offsets in the modules it was
+/// created from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    modules: Vec<String>,
+    mappings: Vec<Mapping>,
+}
+
+/// A range of text in a bundle which originates from `module`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mapping {
+    pub bundle_offset: usize,
+    pub bundle_len: usize,
+    /// Index of the module in `SourceMap::modules`
+    pub module: usize,
+    pub original_offset: usize,
+    /// Differs from `bundle_len` where an `import!` was replaced
+    pub original_len: usize,
+}
+
+impl SourceMap {
+    /// The names of the modules which the bundle was created from
+    pub fn modules(&self) -> &[String] {
+        &self.modules
+    }
+
+    /// The mappings of the bundle, sorted by `bundle_offset`
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// Returns the module and the byte offset in its source which the byte `offset` of the bundle
+    /// originates from. Returns `None` for text which was generated during bundling.
+    pub fn original_position(&self, offset: usize) -> Option<(&str, usize)> {
+        let index = match self
+            .mappings
+            .binary_search_by_key(&offset, |mapping| mapping.bundle_offset)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let mapping = &self.mappings[index];
+        if offset >= mapping.bundle_offset + mapping.bundle_len {
+            return None;
+        }
+        let delta = (offset - mapping.bundle_offset).min(mapping.original_len);
+        Some((
+            &self.modules[mapping.module],
+            mapping.original_offset + delta,
+        ))
+    }
+
+    fn push(&mut self, mapping: Mapping) {
+        if mapping.bundle_len == 0 {
+            return;
+        }
+        if let Some(last) = self.mappings.last_mut() {
+            // Merge text which was copied without changes from the same place
+            if last.module == mapping.module
+                && last.bundle_len == last.original_len
+                && mapping.bundle_len == mapping.original_len
+                && last.bundle_offset + last.bundle_len == mapping.bundle_offset
+                && last.original_offset + last.original_len == mapping.original_offset
+            {
+                last.bundle_len += mapping.bundle_len;
+                last.original_len += mapping.original_len;
+                return;
+            }
+        }
+        self.mappings.push(mapping);
+    }
+}
+
+/// Creates `Bundle`s
+///
+/// ```rust
+/// # use gluon::{bundle::Bundler, new_vm, ThreadExt, query::CompilationBase};
+/// let vm = new_vm();
+/// vm.get_database_mut()
+///     .add_module("app.greeting".into(), r#"let greeting = "Hello" in { greeting }"#);
+/// let bundle = Bundler::new()
+///     .bundle(&vm, "app.greeting")
+///     .unwrap();
+/// assert_eq!(bundle.source, r#"let greeting = "Hello" in { greeting }"#);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Bundler {
+    include_std: bool,
+}
+
+impl Bundler {
+    pub fn new() -> Self {
+        Bundler::default()
+    }
+
+    /// Whether the modules of the standard library are included in the bundle (default: false)
+    pub fn include_std(mut self, include_std: bool) -> Self {
+        self.include_std = include_std;
+        self
+    }
+
+    /// Bundles `module` together with all modules that it imports
+    pub fn bundle(&self, thread: &Thread, module: &str) -> Result<Bundle> {
+        let mut db = thread.get_database();
+        let mut collector = ModuleCollector {
+            compiler: ModuleCompiler::new(&mut db),
+            thread,
+            include_std: self.include_std,
+            modules: Vec::new(),
+            indexes: FnvMap::default(),
+            stack: Vec::new(),
+        };
+        collector.collect(module)?;
+
+        let modules = collector.modules;
+        let names: FnvMap<_, _> = modules
+            .iter()
+            .enumerate()
+            .map(|(i, module)| (&module.name[..], format!("__bundle_{}", i)))
+            .collect();
+
+        let mut output = Output {
+            source: String::new(),
+            source_map: SourceMap {
+                modules: modules.iter().map(|module| module.name.clone()).collect(),
+                mappings: Vec::new(),
+            },
+        };
+        let (root, dependencies) = modules.split_last().expect("root module");
+        for (index, module) in dependencies.iter().enumerate() {
+            output.source.push_str("let ");
+            output.source.push_str(&names[&module.name[..]]);
+            output.source.push_str(" =\n");
+            output.push_module(index, module, &names, INDENT);
+            if !output.source.ends_with('\n') {
+                output.source.push('\n');
+            }
+        }
+        output.push_module(dependencies.len(), root, &names, "");
+
+        Ok(Bundle {
+            source: output.source,
+            source_map: output.source_map,
+        })
+    }
+}
+
+struct BundledModule {
+    name: String,
+    source: Arc<Cow<'static, str>>,
+    imports: Vec<FoundImport>,
+    /// String literals which span several lines and therefore must not be reindented
+    multiline_strings: Vec<(usize, usize)>,
+}
+
+struct FoundImport {
+    start: usize,
+    end: usize,
+    module: String,
+    /// The `{ .. }` or `name` of `import! { .. } from path` or `import! path as name`
+    binding: Option<(usize, usize)>,
+}
+
+struct ModuleCollector<'a, 'b> {
+    compiler: ModuleCompiler<'a, 'b>,
+    thread: &'a Thread,
+    include_std: bool,
+    modules: Vec<BundledModule>,
+    indexes: FnvMap<String, usize>,
+    stack: Vec<String>,
+}
+
+impl ModuleCollector<'_, '_> {
+    fn is_bundled(&self, module: &str) -> bool {
+        (self.include_std || !(module == "std" || module.starts_with("std.")))
+            && ExternLoaderQuery
+                .in_db(self.compiler.compiler())
+                .peek(&module.to_string())
+                .is_none()
+    }
+
+    /// Adds `module` and its imports to `self.modules`, each after the modules it imports
+    fn collect(&mut self, module: &str) -> Result<()> {
+        if self.indexes.contains_key(module) {
+            return Ok(());
+        }
+        if self.stack.iter().any(|m| m == module) {
+            return Err(macros::Error::new(import::Error::CyclicDependency(
+                module.to_string(),
+                self.stack.clone(),
+            ))
+            .into());
+        }
+
+        let source = query::module_source(&**self.compiler, module)?;
+        let (imports, multiline_strings) = self.scan(module, &source)?;
+
+        self.stack.push(module.to_string());
+        for import in &imports {
+            if self.is_bundled(&import.module) {
+                self.collect(&import.module)?;
+            }
+        }
+        self.stack.pop();
+
+        self.indexes.insert(module.to_string(), self.modules.len());
+        self.modules.push(BundledModule {
+            name: module.to_string(),
+            source,
+            imports,
+            multiline_strings,
+        });
+        Ok(())
+    }
+
+    fn scan(
+        &mut self,
+        module: &str,
+        source: &str,
+    ) -> Result<(Vec<FoundImport>, Vec<(usize, usize)>)> {
+        let map = self.compiler.add_filemap(module, source);
+        let type_cache = self.thread.global_env().type_cache();
+
+        mk_ast_arena!(arena);
+        let expr = parser::parse_partial_expr(
+            (*arena).borrow(),
+            &mut SymbolModule::new(module.into(), &mut self.compiler.symbols),
+            type_cache,
+            &*map,
+        )
+        .map_err(|(_, error)| InFile::new(self.compiler.code_map(), error))?;
+
+        let mut scanner = ImportScanner {
+            start: map.span().start(),
+            imports: Vec::new(),
+            multiline_strings: Vec::new(),
+            source,
+        };
+        scanner.visit_expr(&expr);
+
+        let mut imports = scanner.imports;
+        imports.retain(|import| self.is_bundled(&import.module));
+        imports.sort_by_key(|import| import.start);
+        Ok((imports, scanner.multiline_strings))
+    }
+}
+
+struct ImportScanner<'s> {
+    start: BytePos,
+    imports: Vec<FoundImport>,
+    multiline_strings: Vec<(usize, usize)>,
+    source: &'s str,
+}
+
+impl ImportScanner<'_> {
+    fn range(&self, span: Span<BytePos>) -> (usize, usize) {
+        (
+            span.start().to_usize() - self.start.to_usize(),
+            span.end().to_usize() - self.start.to_usize(),
+        )
+    }
+
+    fn add_string(&mut self, span: Span<BytePos>) {
+        let (start, end) = self.range(span);
+        if self.source[start..end].contains('\n') {
+            self.multiline_strings.push((start, end));
+        }
+    }
+}
+
+fn is_ident(expr: &SpannedExpr<Symbol>, name: &str) -> bool {
+    match &expr.value {
+        Expr::Ident(id) => id.name.declared_name() == name,
+        _ => false,
+    }
+}
+
+fn module_name(path: &SpannedExpr<Symbol>) -> Option<String> {
+    match &path.value {
+        Expr::Literal(Literal::String(filename)) => Some(filename_to_module(filename)),
+        _ => {
+            let mut module = String::new();
+            expr_to_path(path, &mut module).ok()?;
+            Some(module)
+        }
+    }
+}
+
+/// If `expr` is an unexpanded `import!`, returns the imported module and the span of the `{ .. }`
+/// or `name` of `import! { .. } from path` or `import! path as name`
+pub(crate) fn find_import(expr: &SpannedExpr<Symbol>) -> Option<(String, Option<Span<BytePos>>)> {
+    match &expr.value {
+        Expr::App { func, args, .. } if is_ident(func, "import!") => match &args[..] {
+            [path] => module_name(path).map(|module| (module, None)),
+            [path, keyword, name] if is_ident(keyword, "as") => {
+                module_name(path).map(|module| (module, Some(name.span)))
+            }
+            [fields, keyword, path] if is_ident(keyword, "from") => {
+                module_name(path).map(|module| (module, Some(fields.span)))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for ImportScanner<'_> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        // Malformed imports are left for the compiler to report
+        if let Some((module, binding)) = find_import(expr) {
+            let (start, end) = self.range(expr.span);
+            let binding = binding.map(|span| self.range(span));
+            self.imports.push(FoundImport {
+                start,
+                end,
+                module,
+                binding,
+            });
+            return;
+        }
+        if let Expr::Literal(Literal::String(_)) = &expr.value {
+            self.add_string(expr.span);
+        }
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'a SpannedPattern<'ast, Symbol>) {
+        if let Pattern::Literal(Literal::String(_)) = &pattern.value {
+            self.add_string(pattern.span);
+        }
+        ast::walk_pattern(self, &pattern.value);
+    }
+}
+
+struct Output {
+    source: String,
+    source_map: SourceMap,
+}
+
+impl Output {
+    fn push_original(&mut self, module_index: usize, source: &str, start: usize, end: usize) {
+        self.source_map.push(Mapping {
+            bundle_offset: self.source.len(),
+            bundle_len: end - start,
+            module: module_index,
+            original_offset: start,
+            original_len: end - start,
+        });
+        self.source.push_str(&source[start..end]);
+    }
+
+    fn push_replacement(&mut self, module_index: usize, start: usize, end: usize, text: &str) {
+        self.source_map.push(Mapping {
+            bundle_offset: self.source.len(),
+            bundle_len: text.len(),
+            module: module_index,
+            original_offset: start,
+            original_len: end - start,
+        });
+        self.source.push_str(text);
+    }
+
+    /// Writes the source of `module`, with its imports replaced, prefixing each line with
+    /// `indent`
+    fn push_module(
+        &mut self,
+        module_index: usize,
+        module: &BundledModule,
+        names: &FnvMap<&str, String>,
+        indent: &str,
+    ) {
+        let source = &module.source[..];
+        let line_end = |pos: usize| {
+            source[pos..]
+                .find('\n')
+                .map_or(source.len(), |i| pos + i + 1)
+        };
+        let in_string = |pos: usize| {
+            module
+                .multiline_strings
+                .iter()
+                .any(|&(start, end)| start < pos && pos < end)
+        };
+
+        let mut imports = module.imports.iter().peekable();
+        let mut pos = 0;
+        while pos < source.len() {
+            if !in_string(pos) {
+                self.source.push_str(indent);
+            }
+            let mut end = line_end(pos);
+            while let Some(import) = imports.peek().filter(|import| import.start < end) {
+                self.push_original(module_index, source, pos, import.start);
+
+                let name = &names[&import.module[..]];
+                let text = match import.binding {
+                    Some((start, end)) => format!("let {} = {}", &source[start..end], name),
+                    None => name.clone(),
+                };
+                self.push_replacement(module_index, import.start, import.end, &text);
+
+                pos = import.end;
+                end = line_end(pos);
+                imports.next();
+            }
+            self.push_original(module_index, source, pos, end);
+            pos = end;
+        }
+    }
+}
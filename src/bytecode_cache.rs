@@ -0,0 +1,208 @@
+//! Compiled modules cached on disk, stored as `.glubc` files.
+//!
+//! `ThreadExt::load_file_cached` writes the bytecode, type and metadata of the file it loads to a
+//! cache file, either next to the source (`src/main.glubc` for `src/main.glu`) or in a cache
+//! directory at the same relative path as the module (`cache/src/main.glubc`). The cache file
+//! records a hash of the source it was compiled from and is only used while the hash matches,
+//! otherwise the file is compiled again and the cache file replaced.
+//!
+//! Only the loaded file itself is cached, the modules it imports are loaded as usual. A cache
+//! file is not invalidated when the modules it imports change so the cache directory should be
+//! cleared when they do.
+use std::{
+    fs,
+    hash::Hasher,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    base::{filename_to_module, fnv::FnvHasher},
+    compiler_pipeline::{run_io, CompileValue, Compileable, ExecuteValue, Module},
+    query::{AsyncCompilation, Compilation},
+    serde::ser::SerializeState,
+    vm::{
+        serialization::{DeSeed, SeSeed},
+        thread::{Thread, ThreadInternal},
+    },
+    ModuleCompiler, Result, ThreadExt,
+};
+
+/// The file extension of bytecode cache files
+pub const EXTENSION: &str = "glubc";
+
+const MAGIC: &[u8; 4] = b"GLBC";
+
+/// Incremented whenever the encoding of cache files changes
+const VERSION: u32 = 1;
+
+/// Returns the path of the cache file of `filename`. Without a `cache_dir` the cache file is
+/// placed next to `filename`.
+pub fn cache_path(filename: &str, cache_dir: Option<&Path>) -> PathBuf {
+    match cache_dir {
+        Some(dir) => {
+            let mut path = dir.join(filename_to_module(filename).replace(".", "/"));
+            path.set_extension(EXTENSION);
+            path
+        }
+        None => Path::new(filename).with_extension(EXTENSION),
+    }
+}
+
+fn source_hash(source: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    // Bytecode is not compatible between different versions of gluon
+    hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.write(source.as_bytes());
+    hasher.finish()
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn write(module: &Module, hash: u64, mut writer: impl Write) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&hash.to_le_bytes())?;
+    let mut serializer = bincode::Serializer::new(writer, bincode::DefaultOptions::new());
+    module
+        .serialize_state(&mut serializer, &SeSeed::new())
+        .map_err(invalid_data)
+}
+
+fn write_file(module: &Module, hash: u64, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = io::BufWriter::new(fs::File::create(path)?);
+    write(module, hash, &mut writer)?;
+    writer.flush()
+}
+
+/// Reads the module cached at `path`, returning `None` if it was compiled from a different source
+fn read_file(thread: &Thread, hash: u64, path: &Path) -> io::Result<Option<Module>> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    let mut header = [0; 16];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid_data("Not a gluon bytecode cache file"));
+    }
+    let mut version = [0; 4];
+    version.copy_from_slice(&header[4..8]);
+    let mut source_hash = [0; 8];
+    source_hash.copy_from_slice(&header[8..]);
+    if u32::from_le_bytes(version) != VERSION || u64::from_le_bytes(source_hash) != hash {
+        return Ok(None);
+    }
+    let mut deserializer =
+        bincode::Deserializer::with_reader(reader, bincode::DefaultOptions::new());
+    DeSeed::new(thread, &mut thread.current_context())
+        .deserialize(&mut deserializer)
+        .map(Some)
+        .map_err(invalid_data)
+}
+
+/// Runs `module` and stores its value as the global `name`, the same way as `load_bytecode`
+async fn run_module(thread: &Thread, name: &str, module: Module) -> Result<()> {
+    let Module {
+        typ,
+        metadata,
+        module,
+    } = module;
+
+    // The globals of the module are looked up when it is instantiated so any modules it imports
+    // must be loaded first
+    {
+        let mut db = thread.get_database();
+        for global in &module.module_globals {
+            let global = global.definition_name().trim_start_matches('@');
+            if db.get_extern_global(global).is_none() {
+                db.global(global.into()).await?;
+            }
+        }
+    }
+
+    let id = module.function.id.clone();
+    let closure = thread.global_env().new_global_thunk(thread, module)?;
+    let value = thread.call_thunk_top(&closure).await?;
+    let mut value = ExecuteValue {
+        id,
+        expr: (),
+        typ,
+        metadata,
+        value,
+    };
+    if thread.get_database().compiler_settings().run_io {
+        value = run_io(thread.root_thread(), value).await?;
+    }
+
+    thread
+        .get_database_mut()
+        .set_global(name, value.typ, value.metadata, &value.value);
+    info!("Loaded module `{}`", name);
+    Ok(())
+}
+
+pub(crate) async fn load_file_cached(
+    thread: &Thread,
+    filename: &str,
+    cache_dir: Option<&Path>,
+) -> Result<()> {
+    let name = filename_to_module(filename);
+    let source = fs::read_to_string(filename)?;
+    let hash = source_hash(&source);
+    let path = cache_path(filename, cache_dir);
+
+    let cached = read_file(thread, hash, &path).unwrap_or_else(|err| {
+        if err.kind() != io::ErrorKind::NotFound {
+            warn!(
+                "Unable to read the bytecode cache `{}`: {}",
+                path.display(),
+                err
+            );
+        }
+        None
+    });
+    let module = match cached {
+        Some(module) => {
+            debug!("Using the bytecode cache `{}`", path.display());
+            module
+        }
+        None => {
+            let CompileValue {
+                typ,
+                metadata,
+                module,
+                ..
+            } = {
+                let mut db = thread.get_database();
+                (&source[..])
+                    .compile(
+                        &mut ModuleCompiler::new(&mut db),
+                        thread,
+                        &name,
+                        &source,
+                        None,
+                    )
+                    .await?
+            };
+            let module = Module {
+                typ,
+                metadata,
+                module,
+            };
+            // The cache only speeds up later loads, failing to write it is not an error
+            if let Err(err) = write_file(&module, hash, &path) {
+                warn!(
+                    "Unable to write the bytecode cache `{}`: {}",
+                    path.display(),
+                    err
+                );
+            }
+            module
+        }
+    };
+
+    run_module(thread, &name, module).await
+}
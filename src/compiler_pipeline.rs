@@ -21,21 +21,30 @@ use crate::ThreadExt;
 
 use crate::{
     base::{
-        ast::{self, OwnedExpr, RootExpr, SpannedExpr, Typed},
+        ast::{self, AstType, KindedIdent, MutVisitor, OwnedExpr, RootExpr, SpannedExpr, Typed},
         error::{Errors, InFile},
         fnv::FnvMap,
+        kind::{ArcKind, Kind, KindCache, KindEnv},
         metadata::Metadata,
-        resolve,
-        symbol::{Name, NameBuf, Symbol, SymbolModule},
-        types::{ArcType, NullInterner, Type, TypeCache},
+        pos, resolve,
+        symbol::{Name, NameBuf, Symbol, SymbolModule, SymbolRef},
+        types::{Alias, ArcType, NullInterner, Type, TypeCache, TypeEnv},
+    },
+    check::{
+        exhaustiveness,
+        kindcheck::KindCheck,
+        metadata, rename,
+        typecheck::{HelpError, TypeError},
+        unused,
+        warning::Warnings,
     },
-    check::{metadata, rename},
     query::{env, AsyncCompilation, Compilation},
     vm::{
         compiler::CompiledModule,
         core::{self, interpreter, CoreExpr},
         macros::MacroExpander,
         thread::{RootedThread, RootedValue, Thread, ThreadInternal, VmRoot},
+        vm::VmEnvInstance,
     },
     Error, ModuleCompiler, Result,
 };
@@ -133,6 +142,96 @@ pub fn parse_expr(
         .map_err(|err| err.map(|expr| expr.try_into_send().unwrap()))
 }
 
+/// Environment for kindchecking a standalone type expression. Types are looked up by their fully
+/// qualified name (`std.option.Option`), falling back to the types exported from the prelude.
+struct TypeExprEnv<'a> {
+    env: VmEnvInstance<'a>,
+    kind_cache: &'a KindCache,
+}
+
+impl KindEnv for TypeExprEnv<'_> {
+    fn find_kind(&self, id: &SymbolRef) -> Option<ArcKind> {
+        self.find_type_info(id)
+            .map(|alias| alias.kind(self.kind_cache).into_owned())
+    }
+}
+
+impl TypeEnv for TypeExprEnv<'_> {
+    type Type = ArcType;
+
+    fn find_type(&self, _id: &SymbolRef) -> Option<ArcType> {
+        None
+    }
+
+    fn find_type_info(&self, id: &SymbolRef) -> Option<Alias<Symbol, ArcType>> {
+        let name = id.definition_name();
+        self.env
+            .find_type_info(name)
+            .or_else(|_| self.env.find_type_info(&format!("std.prelude.{}", name)))
+            .ok()
+    }
+}
+
+/// Replaces `std.option.Option` projections with a single identifier so that the whole path is
+/// looked up in `TypeExprEnv`
+struct JoinProjections<'s, 'm> {
+    symbols: &'s mut SymbolModule<'m>,
+}
+
+impl<'a, 'ast> MutVisitor<'a, 'ast> for JoinProjections<'_, '_> {
+    type Ident = Symbol;
+
+    fn visit_ast_type(&mut self, typ: &'a mut AstType<'ast, Symbol>) {
+        if let Type::Projection(ids) = &**typ {
+            let name = ids
+                .iter()
+                .map(|id| id.definition_name())
+                .collect::<Vec<_>>()
+                .join(".");
+            **typ = Type::Ident(KindedIdent {
+                name: self.symbols.simple_symbol(name),
+                typ: Kind::hole(),
+            });
+        }
+        ast::walk_mut_ast_type(self, typ);
+    }
+}
+
+/// Parses and kindchecks the type expression `type_str`, returning its kind
+pub fn kindcheck_type(
+    compiler: &mut ModuleCompiler<'_, '_>,
+    thread: &Thread,
+    file: &str,
+    type_str: &str,
+) -> Result<ArcKind> {
+    let map = compiler.add_filemap(file, type_str);
+    let code_map = compiler.code_map();
+    let type_cache = thread.global_env().type_cache();
+
+    mk_ast_arena!(arena);
+    let mut symbols = SymbolModule::new(file.into(), &mut compiler.symbols);
+    let mut typ = parser::parse_partial_type((*arena).borrow(), &mut symbols, type_cache, &*map)
+        .map_err(|(_, error)| InFile::new(code_map.clone(), error))?;
+
+    JoinProjections {
+        symbols: &mut symbols,
+    }
+    .visit_ast_type(&mut typ);
+
+    let env = TypeExprEnv {
+        env: thread.get_env(),
+        kind_cache: &type_cache.kind_cache,
+    };
+    let mut kindcheck = KindCheck::new(&env, &mut symbols, type_cache.kind_cache.clone());
+    kindcheck.kindcheck_type(&mut typ).map_err(|errors| {
+        let errors = errors
+            .into_iter()
+            .map(|err| pos::spanned(err.span, HelpError::from(TypeError::from(err.value))))
+            .collect::<Errors<_>>();
+        Error::from(InFile::new(code_map, errors))
+    })
+}
+
 /// Result type of successful macro expansion
 #[derive(Debug)]
 pub struct MacroValue<E> {
@@ -473,6 +572,9 @@ pub struct TypecheckValue<E> {
     pub typ: ArcType,
     pub metadata_map: FnvMap<Symbol, Arc<Metadata>>,
     pub metadata: Arc<Metadata>,
+    /// Warnings about the expression, such as `match` expressions which do not cover every value
+    /// or bindings which are never used
+    pub warnings: Warnings,
 }
 
 impl<E> TypecheckValue<E> {
@@ -482,12 +584,14 @@ impl<E> TypecheckValue<E> {
             typ,
             metadata_map,
             metadata,
+            warnings,
         } = self;
         TypecheckValue {
             expr: f(expr),
             typ,
             metadata_map,
             metadata,
+            warnings,
         }
     }
 }
@@ -609,12 +713,25 @@ where
                         expr,
                         metadata_map,
                         metadata,
+                        warnings: Warnings::new(),
                     }),
                     error,
                 });
             }
         };
 
+        let mut warnings =
+            exhaustiveness::check_expr(&env(&*compiler.database), expr.borrow_mut().expr());
+        {
+            // The implicit prelude binds many names that a module does not use
+            let state = compiler.database.state();
+            warnings.extend(
+                unused::check_expr(expr.borrow_mut().expr())
+                    .into_iter()
+                    .filter(|warning| state.code_map.generated(warning.span.start()).is_none()),
+            );
+        }
+
         // Some metadata requires typechecking so recompute it if full metadata is required
         let (metadata, metadata_map) = if compiler.compiler_settings().full_metadata {
             let env = env(&*compiler.database);
@@ -628,6 +745,7 @@ where
             typ,
             metadata_map,
             metadata,
+            warnings,
         })
     }
 }
@@ -777,7 +895,12 @@ where
                     debug!("Translation returned: {}", expr);
 
                     if settings.optimize {
-                        core::optimize::optimize(&translator.allocator, &env, expr)
+                        core::optimize::optimize(
+                            &translator.allocator,
+                            &env,
+                            settings.optimization_level,
+                            expr,
+                        )
                     } else {
                         interpreter::Global {
                             value: core::freeze_expr(&translator.allocator, expr),
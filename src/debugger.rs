@@ -0,0 +1,175 @@
+//! Evaluation of expressions in the scope of a paused stack frame.
+//!
+//! A hook which pauses execution (by returning `Poll::Pending`) can capture the variables of a
+//! frame with `FrameScope::capture`. Expressions can then be evaluated against the captured
+//! variables with `ThreadExt::eval_in_frame`, where each local and upvariable is visible by name
+//! and with the type it was given by the typechecker.
+
+use crate::base::{
+    ast::{Argument, Expr, Lambda, RootExpr, TypedIdent},
+    pos,
+    types::{ArcType, Type, TypeExt},
+};
+
+use crate::vm::{
+    api::{Getable, Hole, OpaqueValue, OwnedFunction},
+    thread::{RootedThread, RootedValue, StackInfo, Thread, ThreadInternal},
+    Variants,
+};
+
+use crate::{compiler_pipeline::*, Error, ModuleCompiler, Result};
+
+/// A variable which is visible in a stack frame
+#[derive(Clone, Debug)]
+pub struct FrameVariable {
+    pub name: String,
+    pub typ: ArcType,
+    pub value: RootedValue<RootedThread>,
+}
+
+/// The variables which are visible at the current instruction of a stack frame. The values are
+/// rooted so the scope can be used after the hook which captured it has returned.
+#[derive(Clone, Debug, Default)]
+pub struct FrameScope {
+    variables: Vec<FrameVariable>,
+}
+
+impl FrameScope {
+    /// Captures the upvariables and locals which are visible in `frame`. `thread` must be the
+    /// thread that `frame` was taken from.
+    pub fn capture(thread: &Thread, frame: &StackInfo) -> Self {
+        let mut scope = FrameScope::default();
+        for (index, upvar) in frame.upvars().iter().enumerate() {
+            if let Some(value) = frame.upvar_value(index) {
+                scope.insert(thread, &upvar.name, &upvar.typ, value);
+            }
+        }
+        for local in frame.locals() {
+            if let Some(value) = frame.local_value(local) {
+                scope.insert(thread, local.name.declared_name(), &local.typ, value);
+            }
+        }
+        scope
+    }
+
+    fn insert(&mut self, thread: &Thread, name: &str, typ: &ArcType, value: Variants) {
+        if !is_bindable(name) {
+            return;
+        }
+        // Locals are visited from the outermost scope so later variables shadow earlier ones
+        self.variables.retain(|variable| variable.name != name);
+        self.variables.push(FrameVariable {
+            name: name.to_string(),
+            typ: typ.clone(),
+            value: thread.root_value(value),
+        });
+    }
+
+    /// Returns the variables in scope
+    pub fn variables(&self) -> &[FrameVariable] {
+        &self.variables
+    }
+
+    /// Returns the variable named `name` if it is in scope
+    pub fn get(&self, name: &str) -> Option<&FrameVariable> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+}
+
+/// Returns true if `name` can be referred to from an expression. Bindings generated by the
+/// compiler (such as the implicit prelude) and operators are left out.
+fn is_bindable(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_lowercase() || c == '_' => (),
+        _ => return false,
+    }
+    name != "_" && !name.starts_with("__") && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Evaluates `expr_str` with the variables of `scope` in scope, returning its value and type.
+///
+/// The expression is compiled as `\<variables> -> <expr>` where the arguments are given the
+/// types of the variables and is then applied to their values on a new thread so that any hook
+/// set on the paused thread is not triggered.
+pub async fn eval_in_frame(
+    compiler: &mut ModuleCompiler<'_, '_>,
+    thread: &Thread,
+    scope: &FrameScope,
+    name: &str,
+    expr_str: &str,
+) -> Result<(RootedValue<RootedThread>, ArcType)> {
+    let expr = {
+        mk_ast_arena!(arena);
+        let type_cache = thread.global_env().type_cache();
+        let body = parse_expr_inner((*arena).borrow(), compiler, type_cache, name, expr_str)
+            .map_err(|err| Error::from(err.error))?;
+        let expr = if scope.variables.is_empty() {
+            body
+        } else {
+            let span = body.span;
+            let args = scope
+                .variables
+                .iter()
+                .map(|variable| {
+                    let id = compiler.symbols.simple_symbol(&variable.name[..]);
+                    Argument::explicit(pos::spanned(span, TypedIdent::new(id)))
+                })
+                .collect::<Vec<_>>();
+            pos::spanned(
+                span,
+                Expr::Lambda(Lambda {
+                    id: TypedIdent::new(compiler.symbols.simple_symbol("eval_in_frame")),
+                    args: (*arena).borrow().alloc_extend(args),
+                    body: (*arena).borrow().alloc(body),
+                }),
+            )
+        };
+        RootExpr::new(arena.clone(), arena.alloc(expr))
+    };
+    let expr = expr.try_into_send().unwrap();
+
+    let expected_type = if scope.variables.is_empty() {
+        None
+    } else {
+        Some(Type::function(
+            scope.variables.iter().map(|variable| variable.typ.clone()),
+            Type::hole(),
+        ))
+    };
+
+    let eval_thread = thread.new_thread()?;
+    let ExecuteValue { value, typ, .. } = expr
+        .run_expr(
+            compiler,
+            eval_thread.clone(),
+            name,
+            expr_str,
+            expected_type.as_ref(),
+        )
+        .await?;
+
+    if scope.variables.is_empty() {
+        return Ok((value, typ));
+    }
+
+    let function: OwnedFunction<fn(Hole) -> Hole> =
+        Getable::from_value(&eval_thread, value.get_variant());
+    let result: OpaqueValue<RootedThread, Hole> = function
+        .call_any_async(
+            scope
+                .variables
+                .iter()
+                .map(|variable| variable.value.clone()),
+        )
+        .await?;
+
+    let mut result_type = &typ;
+    for _ in &scope.variables {
+        result_type = match result_type.remove_forall().as_function() {
+            Some((_, ret)) => ret,
+            None => break,
+        };
+    }
+    Ok((result.into_inner(), result_type.clone()))
+}
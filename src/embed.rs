@@ -0,0 +1,59 @@
+//! Implementation of the `embed!` macro.
+//!
+//! `embed! "assets/shader.vert"` expands to a string literal with the contents of the file, which
+//! is searched for in the same paths as the modules loaded by `import!`.
+use std::sync::Arc;
+
+use gluon_codegen::Trace;
+
+use {
+    base::{
+        ast::{self, Expr, Literal, SpannedExpr},
+        pos,
+        symbol::{Symbol, Symbols},
+    },
+    vm::macros::{self, Macro, MacroExpander, MacroFuture},
+};
+
+use crate::import::{Error, ImportApi};
+
+#[derive(Trace)]
+#[gluon(crate_name = "vm")]
+pub(crate) struct Embed;
+
+impl Macro for Embed {
+    fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+        &self,
+        env: &'b mut MacroExpander<'a>,
+        _symbols: &'c mut Symbols,
+        _arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+        args: &'b mut [SpannedExpr<'ast, Symbol>],
+    ) -> MacroFuture<'r, 'ast> {
+        Box::pin(async move {
+            let (span, filename) = match args {
+                [SpannedExpr {
+                    span,
+                    value: Expr::Literal(Literal::String(filename)),
+                }] => (*span, filename.clone()),
+                _ => {
+                    return Err(macros::Error::new(Error::String(
+                        "`embed!` expects a string literal with the path of the file".into(),
+                    )))
+                }
+            };
+
+            let import = env
+                .vm
+                .get_macros()
+                .get_capability::<Arc<dyn ImportApi>>(env.vm)
+                .ok_or_else(|| {
+                    macros::Error::new(Error::String(
+                        "`embed!` requires the `import!` macro to find files".into(),
+                    ))
+                })?;
+            let contents = import.read_file(&filename).map_err(macros::Error::new)?;
+
+            Ok(pos::spanned(span, Expr::Literal(Literal::String(contents))).into())
+        })
+    }
+}
@@ -0,0 +1,204 @@
+//! Entry points for fuzzing the parser and typechecker with harnesses such as `cargo fuzz`.
+//!
+//! The functions in this module never panic for any input. Inputs are rejected with an error if
+//! they are not valid UTF-8 or exceed `FuzzLimits::max_input_len`, which bounds the memory used
+//! and how deeply nested the parsed expression can be. The remaining work is run on a separate
+//! thread with a stack of `FuzzLimits::stack_size` bytes so that the recursion over deeply nested
+//! input can not overflow the caller's stack. The thread is reused for every input from the same
+//! calling thread.
+//!
+//! Any panic in the compiler is caught and returned as `FuzzError::Panic`, which a harness should
+//! treat as a crash.
+//!
+//! ```no_run
+//! // fuzz/fuzz_targets/parse.rs
+//! // #![no_main]
+//! // libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//! let data = b"let x = 1 in x";
+//! if let Err(err) = gluon::fuzz::parse_arbitrary(data) {
+//!     assert!(!err.is_bug(), "{}", err);
+//! }
+//! // });
+//! ```
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    str,
+    sync::mpsc,
+    thread,
+};
+
+use crate::{
+    base::{symbol::Symbols, types::TypeCache},
+    parser, Thread, ThreadExt,
+};
+
+/// The name of the module which is typechecked by `typecheck_arbitrary`
+const MODULE_NAME: &str = "fuzz";
+
+/// Limits which keep the compiler from running out of stack or memory on arbitrary input
+#[derive(Clone, Debug)]
+pub struct FuzzLimits {
+    /// Inputs larger than this (in bytes) are rejected without being parsed
+    pub max_input_len: usize,
+    /// The size of the stack the compiler runs on
+    pub stack_size: usize,
+}
+
+impl Default for FuzzLimits {
+    fn default() -> Self {
+        FuzzLimits {
+            max_input_len: 4096,
+            stack_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+quick_error! {
+/// Error returned when an input could not be parsed or typechecked
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum FuzzError {
+    /// The input is not valid UTF-8
+    InvalidUtf8 {
+        display("Input is not valid UTF-8")
+    }
+    /// The input is larger than `FuzzLimits::max_input_len`
+    TooLarge(len: usize, limit: usize) {
+        display("Input of {} bytes exceeds the limit of {} bytes", len, limit)
+    }
+    /// The compiler rejected the input with an error
+    Rejected(err: String) {
+        display("{}", err)
+    }
+    /// The compiler panicked while processing the input
+    Panic(message: String) {
+        display("Panicked: {}", message)
+    }
+}
+}
+
+impl FuzzError {
+    /// Returns true if the error indicates a bug in gluon instead of invalid input
+    pub fn is_bug(&self) -> bool {
+        match self {
+            FuzzError::Panic(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Parses `data` as a gluon expression using the default `FuzzLimits`
+pub fn parse_arbitrary(data: &[u8]) -> Result<(), FuzzError> {
+    parse_arbitrary_with(&FuzzLimits::default(), data)
+}
+
+/// Parses `data` as a gluon expression
+pub fn parse_arbitrary_with(limits: &FuzzLimits, data: &[u8]) -> Result<(), FuzzError> {
+    let source = check_input(limits, data)?.to_owned();
+    run_limited(limits, move || {
+        let mut symbols = Symbols::new();
+        parser::parse_partial_root_expr(&mut symbols, &TypeCache::default(), &*source)
+            .map(|_| ())
+            .map_err(|(_, err)| FuzzError::Rejected(err.to_string()))
+    })
+}
+
+/// Parses and typechecks `data` as a module using the default `FuzzLimits`
+pub fn typecheck_arbitrary(thread: &Thread, data: &[u8]) -> Result<(), FuzzError> {
+    typecheck_arbitrary_with(thread, &FuzzLimits::default(), data)
+}
+
+/// Parses and typechecks `data` as a module.
+///
+/// `thread` can be reused between calls to avoid the cost of creating a new virtual machine for
+/// each input.
+pub fn typecheck_arbitrary_with(
+    thread: &Thread,
+    limits: &FuzzLimits,
+    data: &[u8],
+) -> Result<(), FuzzError> {
+    let source = check_input(limits, data)?.to_owned();
+    let thread = thread.root_thread();
+    run_limited(limits, move || {
+        thread
+            .typecheck_str(MODULE_NAME, &source, None)
+            .map(|_| ())
+            .map_err(|err| FuzzError::Rejected(err.to_string()))
+    })
+}
+
+fn check_input<'a>(limits: &FuzzLimits, data: &'a [u8]) -> Result<&'a str, FuzzError> {
+    if data.len() > limits.max_input_len {
+        return Err(FuzzError::TooLarge(data.len(), limits.max_input_len));
+    }
+    str::from_utf8(data).map_err(|_| FuzzError::InvalidUtf8)
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A thread with a large stack which runs the inputs of one calling thread
+struct Worker {
+    stack_size: usize,
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Worker {
+    fn spawn(stack_size: usize) -> Result<Worker, FuzzError> {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name("gluon-fuzz".into())
+            .stack_size(stack_size)
+            .spawn(move || {
+                for job in receiver {
+                    job();
+                }
+            })
+            .map_err(|err| FuzzError::Rejected(format!("Unable to spawn thread: {}", err)))?;
+        Ok(Worker { stack_size, jobs })
+    }
+}
+
+thread_local! {
+    static WORKER: RefCell<Option<Worker>> = RefCell::new(None);
+}
+
+fn run_limited<F>(limits: &FuzzLimits, f: F) -> Result<(), FuzzError>
+where
+    F: FnOnce() -> Result<(), FuzzError> + Send + 'static,
+{
+    WORKER.with(|worker| {
+        let mut worker = worker.borrow_mut();
+        let jobs = match &*worker {
+            Some(worker) if worker.stack_size == limits.stack_size => worker.jobs.clone(),
+            _ => worker
+                .insert(Worker::spawn(limits.stack_size)?)
+                .jobs
+                .clone(),
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f))
+                .unwrap_or_else(|payload| Err(FuzzError::Panic(panic_message(payload))));
+            let _ = sender.send(result);
+        });
+        let result = jobs.send(job).ok().and_then(|()| receiver.recv().ok());
+        result.unwrap_or_else(|| {
+            // The worker is gone so the next input spawns a new one
+            *worker = None;
+            Err(FuzzError::Rejected("The fuzzing thread stopped".into()))
+        })
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast::<&str>()
+            .map(|message| String::from(*message))
+            .unwrap_or_else(|_| "Unknown panic".to_string()),
+    }
+}
@@ -3,11 +3,12 @@
 use std::{
     any::{Any, TypeId},
     borrow::Cow,
-    fs::File,
+    fmt,
+    fs::{self, File},
     io::Read,
     mem,
     ops::{Deref, DerefMut},
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
     sync::{Arc, Mutex, MutexGuard, RwLock},
 };
 
@@ -22,7 +23,7 @@ use {
 };
 
 use crate::base::{
-    ast::{self, expr_to_path, Expr, Literal, SpannedExpr},
+    ast::{self, expr_to_path, Expr, Literal, SpannedExpr, TypedIdent},
     filename_to_module, pos,
     source::FileId,
     symbol::{Symbol, Symbols},
@@ -38,6 +39,8 @@ use crate::vm::{
     ExternLoader, ExternModule,
 };
 
+#[cfg(feature = "serialization")]
+use crate::interface::ModuleInterface;
 use crate::{
     compiler_pipeline::{Salvage, SalvageResult},
     query::{AsyncCompilation, Compilation, CompilerDatabase},
@@ -56,6 +59,20 @@ quick_error! {
                 cycle.iter().chain(Some(module)).format(" -> ")
             )
         }
+        /// The standard library found at `path` is not compatible with this version of gluon
+        StdVersionMismatch(path: PathBuf, found: String, expected: String) {
+            display(
+                "The standard library at `{}` has version {} but version {} is required",
+                path.display(),
+                found,
+                expected
+            )
+        }
+        /// The importer could not find a module or file in any of its import paths
+        NotFound(err: NotFound) {
+            display("{}", err)
+            from()
+        }
         /// Generic message error
         String(message: String) {
             display("{}", message)
@@ -73,10 +90,103 @@ impl base::error::AsDiagnostic for Error {
         &self,
         _map: &base::source::CodeMap,
     ) -> codespan_reporting::diagnostic::Diagnostic<FileId> {
-        codespan_reporting::diagnostic::Diagnostic::error().with_message(self.to_string())
+        let diagnostic =
+            codespan_reporting::diagnostic::Diagnostic::error().with_message(self.to_string());
+        match self {
+            Error::NotFound(err) => diagnostic.with_notes(err.notes()),
+            _ => diagnostic,
+        }
+    }
+}
+
+/// Describes where the importer looked for a module or file which it could not find
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct NotFound {
+    /// What was searched for, `module` or `file`
+    pub kind: &'static str,
+    pub name: String,
+    /// The import paths which were searched
+    pub search_roots: Vec<PathBuf>,
+    /// The paths which were tried, in the order they were tried
+    pub candidates: Vec<PathBuf>,
+    /// Existing paths which differ from one of the candidates only in case or file extension
+    pub near_misses: Vec<PathBuf>,
+}
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Could not find {} '{}'. Searched {}.",
+            self.kind,
+            self.name,
+            self.search_roots
+                .iter()
+                .map(|p| format!("`{}`", p.display()))
+                .format(", ")
+        )
+    }
+}
+
+impl NotFound {
+    fn notes(&self) -> Vec<String> {
+        let list = |header: &str, paths: &[PathBuf]| {
+            let mut note = header.to_string();
+            for path in paths {
+                note.push_str(&format!("\n    `{}`", path.display()));
+            }
+            note
+        };
+        let mut notes = Vec::new();
+        if !self.candidates.is_empty() {
+            notes.push(list("Tried the paths:", &self.candidates));
+        }
+        if !self.near_misses.is_empty() {
+            notes.push(list(
+                "Found paths which differ only in case or file extension:",
+                &self.near_misses,
+            ));
+        }
+        notes
     }
 }
 
+/// Returns the existing paths under `root` which match `filename` if case and the file extension
+/// are ignored, excluding `root.join(filename)` itself
+fn near_misses(root: &Path, filename: &str) -> Vec<PathBuf> {
+    let stem = |name: &str| -> String {
+        Path::new(name)
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+    };
+    let mut components = Path::new(filename).components().peekable();
+    let mut paths = vec![root.to_owned()];
+    while let Some(component) = components.next() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy().to_lowercase(),
+            _ => {
+                paths = paths.iter().map(|path| path.join(component)).collect();
+                continue;
+            }
+        };
+        let is_last = components.peek().is_none();
+        paths = paths
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+            .filter(|entry| {
+                let entry_name = entry.file_name().to_string_lossy().to_lowercase();
+                entry_name == name || (is_last && stem(&entry_name) == stem(&name))
+            })
+            .map(|entry| entry.path())
+            .collect();
+    }
+    let path = root.join(filename);
+    paths.retain(|near_miss| *near_miss != path);
+    paths.sort();
+    paths
+}
+
 include!(concat!(env!("OUT_DIR"), "/std_modules.rs"));
 
 #[async_trait]
@@ -99,6 +209,17 @@ impl Importer for DefaultImporter {
         _vm: &Thread,
         modulename: &str,
     ) -> SalvageResult<ArcType> {
+        // Modules which only have an interface can be typechecked against but not loaded
+        #[cfg(feature = "serialization")]
+        {
+            if crate::query::interface_without_source(&*compiler.database, modulename)?.is_some() {
+                return compiler
+                    .database
+                    .module_type(modulename.to_string(), None)
+                    .await;
+            }
+        }
+
         let result = compiler.database.global(modulename.to_string()).await;
         // Forcibly load module_type so we can salvage a type for the error if necessary
         let _ = compiler
@@ -148,9 +269,13 @@ pub(crate) trait ImportApi: Send + Sync {
     fn get_module_source(
         &self,
         use_standard_lib: bool,
+        std_root: Option<&Path>,
         module: &str,
         filename: &str,
     ) -> Result<Cow<'static, str>, Error>;
+    fn read_file(&self, filename: &str) -> Result<String, Error>;
+    #[cfg(feature = "serialization")]
+    fn read_interface(&self, module: &str) -> Option<Result<ModuleInterface, Error>>;
     async fn load_module(
         &self,
         compiler: &mut ModuleCompiler<'_, '_>,
@@ -173,10 +298,18 @@ where
     fn get_module_source(
         &self,
         use_standard_lib: bool,
+        std_root: Option<&Path>,
         module: &str,
         filename: &str,
     ) -> Result<Cow<'static, str>, Error> {
-        Self::get_module_source(self, use_standard_lib, module, filename)
+        Self::get_module_source(self, use_standard_lib, std_root, module, filename)
+    }
+    fn read_file(&self, filename: &str) -> Result<String, Error> {
+        Self::read_file(self, filename)
+    }
+    #[cfg(feature = "serialization")]
+    fn read_interface(&self, module: &str) -> Option<Result<ModuleInterface, Error>> {
+        Self::read_interface(self, module)
     }
     async fn load_module(
         &self,
@@ -308,10 +441,15 @@ impl<I> Import<I> {
     pub(crate) fn get_module_source(
         &self,
         use_standard_lib: bool,
+        std_root: Option<&Path>,
         module: &str,
         filename: &str,
     ) -> Result<Cow<'static, str>, Error> {
-        let mut buffer = String::new();
+        if let Some(std_root) = std_root {
+            if module == "std" || module.starts_with("std.") {
+                return read_from_std_root(std_root, module, filename).map(Cow::Owned);
+            }
+        }
 
         // Retrieve the source, first looking in the standard library included in the
         // binary
@@ -323,34 +461,139 @@ impl<I> Import<I> {
         };
         Ok(match std_file {
             Some(tup) => Cow::Borrowed(tup.1),
-            None => {
-                let paths = self.paths.read().unwrap();
-                let file = paths
-                    .iter()
-                    .filter_map(|p| {
-                        let base = p.join(filename);
-                        match File::open(&base) {
-                            Ok(file) => Some(file),
-                            Err(_) => None,
-                        }
-                    })
-                    .next();
-                let mut file = file.ok_or_else(|| {
-                    Error::String(format!(
-                        "Could not find module '{}'. Searched {}.",
-                        module,
-                        paths
-                            .iter()
-                            .map(|p| format!("`{}`", p.display()))
-                            .format(", ")
-                    ))
-                })?;
-                file.read_to_string(&mut buffer)
-                    .map_err(|err| Error::IO(err.into()))?;
-                Cow::Owned(buffer)
-            }
+            None => Cow::Owned(self.read_from_paths("module", module, filename)?),
         })
     }
+
+    /// Reads `filename` from the first of the import paths which contains it, without loading it
+    /// as a module
+    pub(crate) fn read_file(&self, filename: &str) -> Result<String, Error> {
+        self.read_from_paths("file", filename, filename)
+    }
+
+    /// Reads the interface of `module` from the first of the import paths which contains it.
+    /// Returns `None` if no interface exists.
+    #[cfg(feature = "serialization")]
+    pub(crate) fn read_interface(&self, module: &str) -> Option<Result<ModuleInterface, Error>> {
+        let filename = crate::interface::interface_path(module);
+        let paths = self.paths.read().unwrap();
+        let (path, file) = paths.iter().find_map(|p| {
+            let path = p.join(&filename);
+            File::open(&path).ok().map(|file| (path, file))
+        })?;
+        Some(
+            ModuleInterface::read(std::io::BufReader::new(file)).map_err(|err| {
+                Error::String(format!(
+                    "Unable to read interface `{}`: {}",
+                    path.display(),
+                    err
+                ))
+            }),
+        )
+    }
+
+    fn read_from_paths(
+        &self,
+        kind: &'static str,
+        name: &str,
+        filename: &str,
+    ) -> Result<String, Error> {
+        let paths = self.paths.read().unwrap();
+        let file = paths
+            .iter()
+            .filter_map(|p| {
+                let base = p.join(filename);
+                match File::open(&base) {
+                    Ok(file) => Some(file),
+                    Err(_) => None,
+                }
+            })
+            .next();
+        let mut file = file.ok_or_else(|| NotFound {
+            kind,
+            name: name.to_string(),
+            search_roots: paths.clone(),
+            candidates: paths.iter().map(|p| p.join(filename)).collect(),
+            near_misses: paths
+                .iter()
+                .flat_map(|p| near_misses(p, filename))
+                .collect(),
+        })?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer)
+            .map_err(|err| Error::IO(err.into()))?;
+        Ok(buffer)
+    }
+}
+
+/// Converts an error from loading a module into a macro error. Errors from the importer itself
+/// (such as `NotFound`) are kept as is so that their notes are not lost.
+fn into_macro_error(err: crate::Error) -> MacroError {
+    match err {
+        crate::Error::Other(err) => err,
+        err => MacroError::message(err.to_string()),
+    }
+}
+
+/// The version of the standard library which this version of gluon requires. A standard library
+/// loaded from `std_root` must have a compatible version written in its `VERSION` file.
+pub const STD_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Versions are compatible if they only differ in the patch version (or, before 1.0, if the minor
+/// versions are equal)
+fn is_compatible_std_version(found: &str, expected: &str) -> bool {
+    let significant = |version: &str| -> Vec<String> {
+        let mut parts = version.trim().split('.').map(String::from);
+        let major = parts.next().unwrap_or_default();
+        if major == "0" {
+            vec![major, parts.next().unwrap_or_default()]
+        } else {
+            vec![major]
+        }
+    };
+    significant(found) == significant(expected)
+}
+
+fn check_std_version(std_root: &Path) -> Result<(), Error> {
+    let version_path = std_root.join("VERSION");
+    let mut found = String::new();
+    File::open(&version_path)
+        .and_then(|mut file| file.read_to_string(&mut found))
+        .map_err(|err| {
+            Error::String(format!(
+                "Unable to read the version of the standard library at `{}`: {}",
+                version_path.display(),
+                err
+            ))
+        })?;
+    if is_compatible_std_version(&found, STD_VERSION) {
+        Ok(())
+    } else {
+        Err(Error::StdVersionMismatch(
+            std_root.to_owned(),
+            found.trim().to_string(),
+            STD_VERSION.to_string(),
+        ))
+    }
+}
+
+/// Reads the `std` module `module` from the standard library directory `std_root`
+fn read_from_std_root(std_root: &Path, module: &str, filename: &str) -> Result<String, Error> {
+    check_std_version(std_root)?;
+
+    let path = std_root.join(filename.strip_prefix("std/").unwrap_or(filename));
+    let mut buffer = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut buffer))
+        .map_err(|err| {
+            Error::String(format!(
+                "Could not find module '{}' in the standard library at `{}`: {}",
+                module,
+                std_root.display(),
+                err
+            ))
+        })?;
+    Ok(buffer)
 }
 
 /// Adds an extern module to `thread`, letting it be loaded with `import! name` from gluon code.
@@ -496,7 +739,18 @@ where
     ) -> MacroFuture<'r, 'ast> {
         fn get_module_name(args: &[SpannedExpr<Symbol>]) -> Result<String, Error> {
             if args.len() != 1 {
-                return Err(Error::String("Expected import to get 1 argument".into()).into());
+                let binds_module = args.len() == 3
+                    && match &args[1].value {
+                        Expr::Ident(id) => ["as", "from"].contains(&id.name.as_str()),
+                        _ => false,
+                    };
+                let msg = if binds_module {
+                    "`import! path as name` and `import! { .. } from path` must be followed by \
+                     the expression that they are in scope for"
+                } else {
+                    "Expected import to get 1 argument"
+                };
+                return Err(Error::String(msg.into()).into());
             }
 
             let modulename = match args[0].value {
@@ -524,7 +778,7 @@ where
 
         info!("import! {}", modulename);
 
-        let mut db = try_future!(macros
+        let db = try_future!(macros
             .userdata
             .fork(macros.vm.root_thread())
             .downcast::<salsa::Snapshot<CompilerDatabase>>()
@@ -534,67 +788,16 @@ where
 
         let span = args[0].span;
 
-        #[cfg(feature = "tokio")]
-        if let Some(spawn) = macros.spawn {
-            use futures::task::SpawnExt;
-
-            let (tx, rx) = tokio::sync::oneshot::channel();
-            spawn
-                .spawn(Box::pin(async move {
-                    let result = std::panic::AssertUnwindSafe(db.import(modulename))
-                        .catch_unwind()
-                        .await
-                        .map(|r| {
-                            r.map_err(|salvage| {
-                                salvage.map_err(|err| MacroError::message(err.to_string()))
-                            })
-                        })
-                        .unwrap_or_else(|err| {
-                            Err(Salvage::from(MacroError::message(
-                                err.downcast::<String>()
-                                    .map(|s| *s)
-                                    .or_else(|e| e.downcast::<&str>().map(|s| String::from(&s[..])))
-                                    .unwrap_or_else(|_| "Unknown panic".to_string()),
-                            )))
-                        });
-                    // Drop the database before sending the result, otherwise the forker may drop before the forked database
-                    drop(db);
-                    let _ = tx.send(result);
-                }))
-                .unwrap();
-            return Box::pin(async move {
-                Ok(LazyMacroResult::from(move || {
-                    async move {
-                        rx.await
-                            .unwrap_or_else(|err| {
-                                Err(Salvage::from(MacroError::new(Error::String(
-                                    err.to_string(),
-                                ))))
-                            })
-                            .map(|id| pos::spanned(span, Expr::Ident(id)))
-                            .map_err(|salvage| {
-                                salvage.map(|id| pos::spanned(span, Expr::Ident(id)))
-                            })
-                    }
-                    .boxed()
-                }))
-            });
-        }
-
+        // Spawning the import lets sibling imports (and their own imports) be compiled in
+        // parallel, the result is joined once the expansion is forced
+        let import = spawn_import(macros.spawn, *db, modulename);
         Box::pin(async move {
             Ok(LazyMacroResult::from(move || {
                 async move {
-                    let result = db
-                        .import(modulename)
+                    import
                         .await
-                        .map_err(|salvage| {
-                            salvage
-                                .map(|id| pos::spanned(span, Expr::Ident(id)))
-                                .map_err(|err| MacroError::message(err.to_string()))
-                        })
-                        .map(move |id| pos::spanned(span, Expr::Ident(id)));
-                    drop(db);
-                    result
+                        .map(|id| pos::spanned(span, Expr::Ident(id)))
+                        .map_err(|salvage| salvage.map(|id| pos::spanned(span, Expr::Ident(id))))
                 }
                 .boxed()
             }))
@@ -602,6 +805,58 @@ where
     }
 }
 
+/// Imports `module`, spawning the import on `spawn` (the pool of `VmBuilder::compile_threads`)
+/// if there is one. Without a spawner the import only runs once the returned future is polled.
+pub(crate) fn spawn_import(
+    spawn: Option<&(dyn futures::task::Spawn + Send + Sync + '_)>,
+    mut db: salsa::Snapshot<CompilerDatabase>,
+    module: String,
+) -> future::BoxFuture<'static, SalvageResult<TypedIdent<Symbol>, MacroError>> {
+    use futures::{channel::oneshot, task::SpawnExt};
+
+    let import = async move {
+        let result = db
+            .import(module)
+            .await
+            .map_err(|salvage| salvage.map_err(into_macro_error));
+        // Drop the database before returning the result, otherwise the forker may drop before
+        // the forked database
+        drop(db);
+        result
+    };
+
+    let spawn = match spawn {
+        Some(spawn) => spawn,
+        None => return import.boxed(),
+    };
+
+    let (tx, rx) = oneshot::channel();
+    spawn
+        .spawn(async move {
+            let result = std::panic::AssertUnwindSafe(import)
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|err| {
+                    Err(Salvage::from(MacroError::message(
+                        err.downcast::<String>()
+                            .map(|s| *s)
+                            .or_else(|e| e.downcast::<&str>().map(|s| String::from(&s[..])))
+                            .unwrap_or_else(|_| "Unknown panic".to_string()),
+                    )))
+                });
+            let _ = tx.send(result);
+        })
+        .unwrap();
+    rx.map(|result| {
+        result.unwrap_or_else(|err| {
+            Err(Salvage::from(MacroError::new(Error::String(
+                err.to_string(),
+            ))))
+        })
+    })
+    .boxed()
+}
+
 unsafe impl<I> Trace for Import<I> {
     impl_trace! { self, _gc, () }
 }
@@ -0,0 +1,148 @@
+//! Resolution of the graph of modules that a module imports. The graph is only used to inspect
+//! the dependencies of a module, `import!` already compiles imports which do not depend on each
+//! other in parallel (see `VmBuilder::compile_threads`).
+//!
+//! The imports of each module are found by parsing it and looking for `import!`, without
+//! expanding any macros, so imports which are generated by other macros are only discovered when
+//! the module is compiled.
+use crate::base::{
+    ast::{self, SpannedExpr, Visitor},
+    fnv::{FnvMap, FnvSet},
+    symbol::{Symbol, SymbolModule},
+};
+
+use crate::{
+    bundle,
+    query::{self, ExternLoaderQuery},
+    vm::thread::Thread,
+    ModuleCompiler, Result, ThreadExt,
+};
+
+/// The modules that a module (transitively) imports
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportGraph {
+    /// Every module in the graph, each after the modules it imports
+    modules: Vec<String>,
+    imports: FnvMap<String, Vec<String>>,
+}
+
+impl ImportGraph {
+    /// Resolves the graph of modules imported by `module`
+    pub fn resolve(thread: &Thread, module: &str) -> Result<ImportGraph> {
+        let mut db = thread.get_database();
+        let mut resolver = Resolver {
+            compiler: ModuleCompiler::new(&mut db),
+            thread,
+            graph: ImportGraph::default(),
+            visiting: FnvSet::default(),
+        };
+        resolver.visit(module)?;
+        Ok(resolver.graph)
+    }
+
+    /// Every module in the graph, each after the modules it imports
+    pub fn modules(&self) -> &[String] {
+        &self.modules
+    }
+
+    /// The modules which `module` imports directly
+    pub fn imports(&self, module: &str) -> &[String] {
+        self.imports.get(module).map_or(&[], |imports| &imports[..])
+    }
+
+    /// Groups the modules so that every module only imports modules of earlier groups. The
+    /// modules of a group do not depend on each other and can be compiled in parallel.
+    pub fn levels(&self) -> Vec<Vec<String>> {
+        let mut depths: FnvMap<&str, usize> = FnvMap::default();
+        let mut levels: Vec<Vec<String>> = Vec::new();
+        for module in &self.modules {
+            // The modules are ordered after their imports so the depth of each import is known,
+            // except for imports that form a cycle
+            let depth = self
+                .imports(module)
+                .iter()
+                .filter_map(|import| depths.get(&import[..]))
+                .map(|depth| depth + 1)
+                .max()
+                .unwrap_or(0);
+            depths.insert(&module[..], depth);
+            if levels.len() <= depth {
+                levels.resize_with(depth + 1, Vec::new);
+            }
+            levels[depth].push(module.clone());
+        }
+        levels
+    }
+}
+
+struct Resolver<'a, 'b> {
+    compiler: ModuleCompiler<'a, 'b>,
+    thread: &'a Thread,
+    graph: ImportGraph,
+    visiting: FnvSet<String>,
+}
+
+impl Resolver<'_, '_> {
+    fn visit(&mut self, module: &str) -> Result<()> {
+        if self.graph.imports.contains_key(module) || !self.visiting.insert(module.to_string()) {
+            return Ok(());
+        }
+
+        let imports = self.scan(module)?;
+        for import in &imports {
+            self.visit(import)?;
+        }
+
+        self.graph.modules.push(module.to_string());
+        self.graph.imports.insert(module.to_string(), imports);
+        Ok(())
+    }
+
+    fn scan(&mut self, module: &str) -> Result<Vec<String>> {
+        // Modules implemented in Rust do not have a source to scan
+        if ExternLoaderQuery
+            .in_db(self.compiler.compiler())
+            .peek(&module.to_string())
+            .is_some()
+        {
+            return Ok(Vec::new());
+        }
+
+        let source = query::module_source(&**self.compiler, module)?;
+        let map = self.compiler.get_or_insert_filemap(module, &source);
+        let type_cache = self.thread.global_env().type_cache();
+
+        mk_ast_arena!(arena);
+        // Syntax errors are left for the compiler to report
+        let expr = match parser::parse_partial_expr(
+            (*arena).borrow(),
+            &mut SymbolModule::new(module.into(), &mut self.compiler.symbols),
+            type_cache,
+            &*map,
+        ) {
+            Ok(expr) | Err((Some(expr), _)) => expr,
+            Err((None, _)) => return Ok(Vec::new()),
+        };
+
+        let mut scanner = ImportScanner(Vec::new());
+        scanner.visit_expr(&expr);
+        Ok(scanner.0)
+    }
+}
+
+struct ImportScanner(Vec<String>);
+
+impl<'a, 'ast> Visitor<'a, 'ast> for ImportScanner {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        match bundle::find_import(expr) {
+            Some((module, _)) => {
+                if !self.0.contains(&module) {
+                    self.0.push(module);
+                }
+            }
+            None => ast::walk_expr(self, expr),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Typechecked module interfaces, stored as `.gluti` files.
+//!
+//! An interface contains what is needed to typecheck the modules which import a module, its type
+//! and its metadata, but none of its code. When `Settings::emit_interfaces` is set, the interface
+//! of each module which is typechecked is written to that directory, at the same relative path as
+//! the module's source (`std/map.gluti` for `std.map`).
+//!
+//! If `import!` can't find the source of a module it looks for its interface in the import paths
+//! instead, letting modules which depend on it be typechecked without access to its source.
+//! Running them still requires the module itself to be loaded, for instance as an extern module.
+//!
+//! Types which the interface refers to are recreated from the interface, so types from other
+//! modules are only equal to the types they were created from if they can be unified by their
+//! structure.
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    base::{
+        metadata::Metadata,
+        serialization::{SeSeed, Seed},
+        symbol::Symbol,
+        types::ArcType,
+    },
+    serde::{de::DeserializeState, ser::SerializeState},
+};
+
+/// The file extension of interface files
+pub const EXTENSION: &str = "gluti";
+
+const MAGIC: &[u8; 4] = b"GLTI";
+
+/// Incremented whenever the encoding of interfaces changes
+const VERSION: u32 = 1;
+
+/// The parts of a typechecked module which are visible to the modules importing it
+#[derive(Debug, SerializeState, DeserializeState)]
+#[serde(serialize_state = "SeSeed")]
+#[serde(deserialize_state = "Seed<Symbol, ArcType>")]
+pub struct ModuleInterface {
+    /// The name of the module, such as `std.map`
+    pub name: String,
+    #[serde(state)]
+    pub typ: ArcType,
+    pub metadata: Arc<Metadata>,
+}
+
+/// Returns the path, relative to an import path, of the interface of `module`
+pub fn interface_path(module: &str) -> PathBuf {
+    let mut path = PathBuf::from(module.replace(".", "/"));
+    path.set_extension(EXTENSION);
+    path
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl ModuleInterface {
+    /// Writes the interface to `writer`
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        let mut serializer = bincode::Serializer::new(writer, bincode::DefaultOptions::new());
+        self.serialize_state(&mut serializer, &SeSeed::default())
+            .map_err(invalid_data)
+    }
+
+    /// Reads an interface written by `write`
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("Not a gluon interface file"));
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&header[4..]);
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "Expected interface version {} but found {}",
+                VERSION, version
+            )));
+        }
+        let mut deserializer =
+            bincode::Deserializer::with_reader(reader, bincode::DefaultOptions::new());
+        Self::deserialize_state(&mut Seed::default(), &mut deserializer).map_err(invalid_data)
+    }
+
+    /// Writes the interface to its `interface_path` under `dir`, creating any missing directories
+    pub fn write_to_dir(&self, dir: &Path) -> io::Result<()> {
+        let path = dir.join(interface_path(&self.name));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+}
@@ -0,0 +1,102 @@
+//! Syntax sugar for `std.lazy`.
+//!
+//! `lazy! expr` expands to `(import! std.lazy).lazy (\_ -> expr)` and `force! expr` expands to
+//! `(import! std.lazy).force expr`.
+use std::mem;
+
+use gluon_codegen::Trace;
+
+use {
+    base::{
+        ast::{self, Argument, Expr, Lambda, SpannedExpr, TypedIdent},
+        pos::{self, BytePos, Span},
+        symbol::{Symbol, Symbols},
+    },
+    vm::macros::{self, Macro, MacroExpander, MacroFuture},
+};
+
+#[derive(Trace)]
+#[gluon(crate_name = "vm")]
+pub(crate) struct Lazy;
+
+impl Macro for Lazy {
+    fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+        &self,
+        env: &'b mut MacroExpander<'a>,
+        symbols: &'c mut Symbols,
+        arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+        args: &'b mut [SpannedExpr<'ast, Symbol>],
+    ) -> MacroFuture<'r, 'ast> {
+        Box::pin(async move {
+            let expr = single_argument("lazy", args)?;
+            let span = expr.span;
+
+            let thunk = {
+                let arena = arena.borrow();
+                pos::spanned(
+                    span,
+                    Expr::Lambda(Lambda {
+                        id: TypedIdent::new(symbols.simple_symbol("lazy_thunk")),
+                        args: arena.alloc_extend(Some(Argument::explicit(pos::spanned(
+                            span,
+                            TypedIdent::new(symbols.simple_symbol("_")),
+                        )))),
+                        body: arena.alloc(expr),
+                    }),
+                )
+            };
+
+            let mut out = std_lazy_call(arena.borrow(), symbols, span, "lazy", thunk);
+            env.run_once(symbols, arena, &mut out).await;
+            Ok(out.into())
+        })
+    }
+}
+
+#[derive(Trace)]
+#[gluon(crate_name = "vm")]
+pub(crate) struct Force;
+
+impl Macro for Force {
+    fn expand<'r, 'a: 'r, 'b: 'r, 'c: 'r, 'ast: 'r>(
+        &self,
+        env: &'b mut MacroExpander<'a>,
+        symbols: &'c mut Symbols,
+        arena: &'b mut ast::OwnedArena<'ast, Symbol>,
+        args: &'b mut [SpannedExpr<'ast, Symbol>],
+    ) -> MacroFuture<'r, 'ast> {
+        Box::pin(async move {
+            let expr = single_argument("force", args)?;
+            let span = expr.span;
+
+            let mut out = std_lazy_call(arena.borrow(), symbols, span, "force", expr);
+            env.run_once(symbols, arena, &mut out).await;
+            Ok(out.into())
+        })
+    }
+}
+
+fn single_argument<'ast>(
+    name: &str,
+    args: &mut [SpannedExpr<'ast, Symbol>],
+) -> Result<SpannedExpr<'ast, Symbol>, macros::Error> {
+    match args {
+        [arg] => Ok(mem::take(arg)),
+        _ => Err(macros::Error::message(format!(
+            "`{}!` expects 1 argument",
+            name
+        ))),
+    }
+}
+
+/// Creates `(import! std.lazy).<function> arg`
+fn std_lazy_call<'ast>(
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
+    symbols: &mut Symbols,
+    span: Span<BytePos>,
+    function: &str,
+    arg: SpannedExpr<'ast, Symbol>,
+) -> SpannedExpr<'ast, Symbol> {
+    let func = macros::import_field(arena, symbols, span, "std.lazy", function);
+    pos::spanned(span, Expr::app(arena, func, Some(arg)))
+}
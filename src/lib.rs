@@ -45,15 +45,34 @@ macro_rules! try_future {
     };
 }
 
+pub mod bundle;
+#[cfg(feature = "serialization")]
+pub mod bytecode_cache;
 pub mod compiler_pipeline;
+pub mod debugger;
+mod embed;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
 #[macro_use]
 pub mod import;
+pub mod import_graph;
+#[cfg(feature = "serialization")]
+pub mod interface;
+mod lazy;
 pub mod lift_io;
+pub mod listing;
+pub mod plugin;
+pub mod progress;
 #[doc(hidden)]
 pub mod query;
+pub mod quote;
 pub mod std_lib;
+pub mod test_report;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use crate::vm::{
+    core::optimize::OptimizationLevel,
     field_decl, primitive, record, record_p, record_type,
     thread::{RootedThread, Thread},
 };
@@ -67,13 +86,15 @@ use std::{
 
 use crate::base::{
     ast::{self, OwnedExpr, SpannedExpr},
+    diagnostic::{Diagnostic, Severity},
     error::{Errors, InFile},
     filename_to_module,
+    kind::ArcKind,
     metadata::Metadata,
     pos::{BytePos, Span, Spanned},
-    source::FileId,
-    symbol::{Symbol, Symbols},
-    types::{ArcType, TypeCache},
+    source::{FileId, GeneratedCode},
+    symbol::{Symbol, SymbolModule, Symbols},
+    types::{ArcType, TypeCache, TypeExt},
 };
 
 use crate::format::Formatter;
@@ -82,6 +103,7 @@ use crate::vm::{
     api::{Getable, Hole, OpaqueValue, VmType},
     compiler::CompiledModule,
     macros,
+    thread::RootedValue,
 };
 
 use crate::{
@@ -250,6 +272,20 @@ impl From<Errors<Error>> for Error {
 }
 
 impl Error {
+    /// Returns a structured representation of the error which tools can consume without parsing
+    /// the emitted text. `base::diagnostic::to_json` serializes the diagnostics as JSON.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Error::Parse(err) => err.diagnostics(),
+            Error::Typecheck(err) => err.diagnostics(),
+            Error::Macro(err) => err.diagnostics(),
+            Error::IO(_) | Error::VM(_) | Error::Other(_) => {
+                vec![Diagnostic::message(Severity::Error, self.to_string())]
+            }
+            Error::Multiple(errors) => errors.iter().flat_map(|err| err.diagnostics()).collect(),
+        }
+    }
+
     pub fn emit_string(&self) -> base::source::Result<String> {
         let mut output = Vec::new();
         self.emit(&mut codespan_reporting::term::termcolor::NoColor::new(
@@ -288,8 +324,11 @@ pub struct Settings {
     pub emit_debug_info: bool,
     pub full_metadata: bool,
     pub use_standard_lib: bool,
+    pub std_root: Option<PathBuf>,
     pub optimize: bool,
+    pub optimization_level: OptimizationLevel,
     pub run_io: bool,
+    pub emit_interfaces: Option<PathBuf>,
 }
 
 impl Default for Settings {
@@ -299,8 +338,11 @@ impl Default for Settings {
             emit_debug_info: true,
             full_metadata: false,
             use_standard_lib: true,
+            std_root: None,
             optimize: true,
+            optimization_level: OptimizationLevel::default(),
             run_io: false,
+            emit_interfaces: None,
         }
     }
 }
@@ -411,17 +453,54 @@ impl import::DatabaseMut {
         use_standard_lib set_use_standard_lib: bool
     }
 
+    runtime_option! {
+        /// Sets a directory which the modules of the standard library (`std.*`) are read from
+        /// instead of the standard library embedded in the binary or the import paths. The
+        /// directory must contain a `VERSION` file with a version compatible with
+        /// `import::STD_VERSION`.
+        /// (default: None)
+        std_root set_std_root: Option<PathBuf>
+    }
+
     runtime_option! {
         /// Whether the bytecode should be optimized
         /// (default: true)
         optimize set_optimize: bool
     }
 
+    runtime_option! {
+        /// Which optimizations are performed when `optimize` is set. `OptimizationLevel::Inline`
+        /// inlines small, pure functions from imported modules at their call sites
+        /// (default: OptimizationLevel::Inline)
+        optimization_level set_optimization_level: OptimizationLevel
+    }
+
     runtime_option! {
         /// Sets whether `IO` expressions are evaluated.
         /// (default: false)
         run_io set_run_io: bool
     }
+
+    runtime_option! {
+        /// Sets a directory which the interfaces (`.gluti` files) of the typechecked modules are
+        /// written to. Requires the `serialization` feature.
+        /// (default: None)
+        emit_interfaces set_emit_interfaces: Option<PathBuf>
+    }
+
+    /// Sets a callback which is called as modules are imported and compiled so that long
+    /// compilations can report their progress
+    pub fn progress_callback(
+        mut self,
+        callback: impl Fn(&progress::Progress) + Send + Sync + 'static,
+    ) -> Self {
+        self.set_progress_callback(Some(Arc::new(callback)));
+        self
+    }
+
+    pub fn set_progress_callback(&mut self, callback: Option<progress::ProgressCallback>) {
+        self.state().progress.set_callback(callback);
+    }
 }
 
 /// Extension trait which provides methods to load and execute gluon code
@@ -535,6 +614,7 @@ pub trait ThreadExt: Send + Sync {
             typ: vm.global_env().type_cache().hole(),
             metadata: Default::default(),
             metadata_map: Default::default(),
+            warnings: Default::default(),
         }
         .compile(
             &mut ModuleCompiler::new(&mut vm.get_database()),
@@ -547,6 +627,65 @@ pub trait ThreadExt: Send + Sync {
         .map(|result| result.module)
     }
 
+    /// Compiles `expr_str` and returns a listing of the core expression it was translated (and
+    /// optimized) into followed by the bytecode of each function, annotated with the source line
+    /// that emitted each instruction.
+    ///
+    /// The expression is compiled with the same settings as `run_expr` but is not run. The format
+    /// of the listing is intended for humans and may change, use `compile_listing` to inspect the
+    /// output programmatically.
+    fn emit_core(&self, name: &str, expr_str: &str) -> Result<String> {
+        futures::executor::block_on(self.emit_core_async(name, expr_str))
+    }
+
+    async fn emit_core_async(&self, name: &str, expr_str: &str) -> Result<String> {
+        Ok(self
+            .compile_listing_async(name, expr_str)
+            .await?
+            .to_string())
+    }
+
+    /// Compiles `expr_str` and returns the core expression and bytecode it compiled to
+    fn compile_listing(&self, name: &str, expr_str: &str) -> Result<listing::Listing> {
+        futures::executor::block_on(self.compile_listing_async(name, expr_str))
+    }
+
+    async fn compile_listing_async(&self, name: &str, expr_str: &str) -> Result<listing::Listing> {
+        let vm = self.thread();
+        let CompileValue {
+            core_expr,
+            typ,
+            module,
+            ..
+        } = expr_str
+            .compile(
+                &mut ModuleCompiler::new(&mut vm.get_database()),
+                vm,
+                name,
+                expr_str,
+                None,
+            )
+            .await?;
+        Ok(listing::Listing::new(
+            typ,
+            core_expr.to_string(),
+            &module.function,
+            expr_str,
+        ))
+    }
+
+    /// Evaluates `expr_str`, which must be a `std.test.TestCase`, and runs every test in it.
+    ///
+    /// Unlike `std.test.run_io` a failing test does not stop the run, the outcome of every test is
+    /// returned in the report.
+    fn run_tests(&self, name: &str, expr_str: &str) -> Result<test_report::TestReport> {
+        futures::executor::block_on(self.run_tests_async(name, expr_str))
+    }
+
+    async fn run_tests_async(&self, name: &str, expr_str: &str) -> Result<test_report::TestReport> {
+        test_report::run_tests(self.thread(), name, expr_str).await
+    }
+
     /// Compiles the source code `expr_str` into bytecode serialized using `serializer`
     #[cfg(feature = "serialization")]
     async fn compile_to_bytecode<S>(
@@ -632,6 +771,51 @@ pub trait ThreadExt: Send + Sync {
         }
     }
 
+    /// Returns the metadata of the module `module` (such as `std.map`), loading and typechecking
+    /// the module first if it is not already loaded.
+    ///
+    /// The metadata of each exported field, with its documentation and attributes, is found in
+    /// `Metadata::module` which lets hosts discover what scripts export without running them.
+    fn module_metadata(&self, module: &str) -> Result<Arc<Metadata>> {
+        futures::executor::block_on(self.module_metadata_async(module))
+    }
+
+    async fn module_metadata_async(&self, module: &str) -> Result<Arc<Metadata>> {
+        use crate::check::metadata;
+
+        let vm = self.thread();
+        let mut db = vm.get_database();
+
+        let typ = db.module_type(module.into(), None).await?;
+        let mut module_metadata = db.module_metadata(module.into(), None).await?;
+        let is_extern = query::ExternLoaderQuery
+            .in_db(&*db)
+            .peek(&module.to_string())
+            .is_some();
+        if !is_extern && !db.compiler_settings().full_metadata {
+            // Some metadata is only available after typechecking, same as in `extract_metadata`
+            let TypecheckValue { expr, .. } =
+                db.typechecked_source_module(module.into(), None).await?;
+            module_metadata = metadata::metadata(&vm.get_env(), &expr.expr()).0;
+        }
+
+        // Fields without documentation or attributes have no metadata of their own, add them so
+        // that every exported name is listed
+        let typ = typ.remove_forall();
+        let exports = Arc::make_mut(&mut module_metadata);
+        let names = typ
+            .row_iter()
+            .map(|field| &field.name)
+            .chain(typ.type_field_iter().map(|field| &field.name));
+        for name in names {
+            exports
+                .module
+                .entry(name.declared_name().to_string())
+                .or_default();
+        }
+        Ok(module_metadata)
+    }
+
     /// Compiles `input` and if it is successful runs the resulting code and stores the resulting
     /// value in the vm.
     ///
@@ -678,6 +862,29 @@ pub trait ThreadExt: Send + Sync {
         Ok(())
     }
 
+    /// Like `load_file` but caches the compiled bytecode, type and metadata of `filename` on
+    /// disk, next to `filename` or in `cache_dir` if it is given. Later calls load the cached
+    /// module instead of compiling `filename` again, as long as its source is unchanged.
+    ///
+    /// The module is stored the same way as by `load_bytecode`. See `bytecode_cache` for details.
+    #[cfg(feature = "serialization")]
+    fn load_file_cached<'vm>(
+        &'vm self,
+        filename: &str,
+        cache_dir: Option<&std::path::Path>,
+    ) -> Result<()> {
+        futures::executor::block_on(self.load_file_cached_async(filename, cache_dir))
+    }
+
+    #[cfg(feature = "serialization")]
+    async fn load_file_cached_async<'vm>(
+        &self,
+        filename: &str,
+        cache_dir: Option<&std::path::Path>,
+    ) -> Result<()> {
+        bytecode_cache::load_file_cached(self.thread(), filename, cache_dir).await
+    }
+
     /// Compiles and runs the expression in `expr_str`. If successful the value from running the
     /// expression is returned
     ///
@@ -798,6 +1005,57 @@ pub trait ThreadExt: Send + Sync {
         let expr = skip_implicit_prelude(file_map.span(), &expr.expr());
         Ok(formatter.pretty_expr(&*file_map, expr))
     }
+
+    /// Parses and kindchecks the type expression `type_str`, returning its kind. Types are
+    /// referred to by their fully qualified name (`std.option.Option`) or, for the types exported
+    /// by the prelude, by their plain name.
+    ///
+    /// ```rust
+    /// # use gluon::{new_vm, ThreadExt};
+    /// let vm = new_vm();
+    /// vm.run_expr::<()>("load", "let _ = import! std.option in ()").unwrap();
+    /// let kind = vm.kindcheck_type("example", "std.option.Option").unwrap();
+    /// assert_eq!(kind.to_string(), "Type -> Type");
+    /// ```
+    fn kindcheck_type(&self, file: &str, type_str: &str) -> Result<ArcKind> {
+        let vm = self.thread();
+        kindcheck_type(
+            &mut ModuleCompiler::new(&mut vm.get_database()),
+            vm,
+            file,
+            type_str,
+        )
+    }
+
+    /// Evaluates `expr_str` in the scope of a paused stack frame. The locals and upvariables
+    /// captured in `scope` can be referred to by name and keep the types they had in the frame.
+    ///
+    /// See the `debugger` module for how a `FrameScope` is captured.
+    fn eval_in_frame(
+        &self,
+        scope: &debugger::FrameScope,
+        name: &str,
+        expr_str: &str,
+    ) -> Result<(RootedValue<RootedThread>, ArcType)> {
+        futures::executor::block_on(self.eval_in_frame_async(scope, name, expr_str))
+    }
+
+    async fn eval_in_frame_async(
+        &self,
+        scope: &debugger::FrameScope,
+        name: &str,
+        expr_str: &str,
+    ) -> Result<(RootedValue<RootedThread>, ArcType)> {
+        let vm = self.thread();
+        debugger::eval_in_frame(
+            &mut ModuleCompiler::new(&mut vm.get_database()),
+            vm,
+            scope,
+            name,
+            expr_str,
+        )
+        .await
+    }
 }
 
 fn skip_implicit_prelude<'a, 'ast>(
@@ -851,7 +1109,24 @@ impl ModuleCompiler<'_, '_> {
             return;
         }
 
-        let prelude_expr = parse_expr_inner(arena, self, type_cache, "", PRELUDE).unwrap();
+        // Each module gets its own copy of the prelude so that errors inside it can be reported
+        // at the start of the module instead
+        let generated = GeneratedCode {
+            generator: "the implicit prelude".into(),
+            call_site: Span::new(expr.span.start(), expr.span.start()),
+        };
+        let prelude_map = self.state().add_generated_filemap(
+            &format!("{} (implicit prelude)", name),
+            PRELUDE,
+            generated,
+        );
+        let prelude_expr = parser::parse_partial_expr(
+            arena,
+            &mut SymbolModule::new(String::new(), &mut self.symbols),
+            type_cache,
+            &*prelude_map,
+        )
+        .unwrap();
         let original_expr = mem::replace(expr, prelude_expr);
 
         // Replace the 0 in the prelude with the actual expression
@@ -886,7 +1161,7 @@ let { ? } = import! std.string
 
 let { ? } = import! std.array
 
-let { error } = import! std.prim
+let { error, error_with } = import! std.prim
 
 let __error = error
 let __string_eq: String -> String -> Bool = (==)
@@ -897,6 +1172,7 @@ in ()
 #[derive(Default)]
 pub struct VmBuilder {
     import_paths: Option<Vec<PathBuf>>,
+    compile_threads: Option<usize>,
 }
 
 impl VmBuilder {
@@ -909,16 +1185,25 @@ impl VmBuilder {
         import_paths set_import_paths: Option<Vec<PathBuf>>
     }
 
+    option! {
+        /// Compiles modules imported with `import!` on a pool with this many threads, letting
+        /// independent modules be parsed and typechecked in parallel. Takes precedence over the
+        /// tokio spawner used by `build_async`.
+        /// (default: None)
+        compile_threads set_compile_threads: Option<usize>
+    }
+
     pub fn build(self) -> RootedThread {
-        futures::executor::block_on(self.build_inner(None))
+        let spawner = self.compile_thread_pool();
+        futures::executor::block_on(self.build_inner(spawner))
     }
 
     pub async fn build_async(self) -> RootedThread {
         #[allow(unused_mut, unused_assignments)]
-        let mut spawner = None;
+        let mut spawner = self.compile_thread_pool();
 
         #[cfg(feature = "tokio")]
-        {
+        if spawner.is_none() {
             struct TokioSpawn;
             impl futures::task::Spawn for TokioSpawn {
                 fn spawn_obj(
@@ -935,6 +1220,16 @@ impl VmBuilder {
         self.build_inner(spawner).await
     }
 
+    fn compile_thread_pool(&self) -> Option<Box<dyn futures::task::Spawn + Send + Sync>> {
+        let threads = self.compile_threads?;
+        let pool = futures::executor::ThreadPool::builder()
+            .pool_size(threads.max(1))
+            .name_prefix("gluon-compile-")
+            .create()
+            .expect("Unable to create the compilation thread pool");
+        Some(Box::new(pool))
+    }
+
     async fn build_inner(
         self,
         spawner: Option<Box<dyn futures::task::Spawn + Send + Sync>>,
@@ -961,6 +1256,9 @@ impl VmBuilder {
             }
 
             macros.insert(String::from("lift_io"), lift_io::LiftIo);
+            macros.insert(String::from("lazy"), lazy::Lazy);
+            macros.insert(String::from("force"), lazy::Force);
+            macros.insert(String::from("embed"), embed::Embed);
         }
 
         add_extern_module_with_deps(
@@ -975,6 +1273,7 @@ impl VmBuilder {
             r#"//@NO-IMPLICIT-PRELUDE
                     let _ = import! std.types
                     let _ = import! std.prim
+                    let _ = import! std.time.types
                     ()
                 "#,
         )
@@ -1003,6 +1302,20 @@ impl VmBuilder {
             vec!["std.path.types".into()],
         );
 
+        add_extern_module_with_deps(
+            &vm,
+            "std.time.prim",
+            crate::std_lib::time::load,
+            vec!["std.time.types".into()],
+        );
+
+        add_extern_module_with_deps(
+            &vm,
+            "std.log.prim",
+            crate::std_lib::log::load,
+            vec!["std.log.types".into()],
+        );
+
         add_extern_module_with_deps(
             &vm,
             "std.st.reference.prim",
@@ -1013,11 +1326,16 @@ impl VmBuilder {
         let deps: &[(_, fn(&Thread) -> _)] = &[
             ("std.array.prim", crate::vm::primitives::load_array),
             ("std.lazy.prim", crate::vm::lazy::load),
+            ("std.effect.prim", crate::vm::effect::load),
             ("std.reference.prim", crate::vm::reference::load),
+            ("std.thread.local.prim", crate::vm::thread_local::load),
             ("std.channel.prim", crate::vm::channel::load_channel),
             ("std.debug.prim", crate::vm::debug::load),
+            ("std.fmt.prim", crate::vm::format::load),
+            ("std.test.prim", crate::vm::property::load),
             ("std.process.prim", crate::std_lib::process::load),
             ("std.env.prim", crate::std_lib::env::load),
+            ("std.env.config.prim", crate::std_lib::env::load_config),
         ];
         for (name, load_fn) in deps {
             add_extern_module(&vm, name, load_fn);
@@ -1036,6 +1354,20 @@ impl VmBuilder {
             args(&vm, "std.json.prim", crate::vm::api::json::load)
         );
 
+        add_extern_module_if!(
+            #[cfg(feature = "cbor")],
+            available_if = "gluon is compiled with the 'cbor' feature",
+            dependencies = ["std.json"],
+            args(&vm, "std.cbor.prim", crate::vm::api::cbor::load)
+        );
+
+        add_extern_module_if!(
+            #[cfg(feature = "msgpack")],
+            available_if = "gluon is compiled with the 'msgpack' feature",
+            dependencies = ["std.json"],
+            args(&vm, "std.msgpack.prim", crate::vm::api::msgpack::load)
+        );
+
         add_extern_module_if!(
             #[cfg(feature = "regex")],
             available_if = "gluon is compiled with the 'regex' feature",
@@ -1043,6 +1375,13 @@ impl VmBuilder {
             args(&vm, "std.regex.prim", crate::std_lib::regex::load)
         );
 
+        add_extern_module_if!(
+            #[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))],
+            available_if = "gluon is compiled with the 'sqlite' feature and is not targeting WASM",
+            dependencies = ["std.db.sqlite.types", "std.json"],
+            args(&vm, "std.db.sqlite.prim", crate::std_lib::sqlite::load)
+        );
+
         add_extern_module_if!(
             #[cfg(feature = "web")],
             available_if = "gluon is compiled with the 'web' feature",
@@ -0,0 +1,130 @@
+//! Listings of what an expression compiles to.
+//!
+//! A `Listing` contains the (optimized) core expression which the typechecked expression was
+//! translated to as well as the bytecode of every function which was generated from it. Each
+//! instruction is annotated with the source line which caused it to be emitted (if debug
+//! information is enabled), which makes it possible to trace optimizer and code generation issues
+//! back to the source.
+use std::fmt;
+
+use crate::{
+    base::{pos::Line, symbol::Symbol, types::ArcType},
+    vm::{compiler::CompiledFunction, types::Instruction},
+};
+
+/// The compiled form of an expression
+#[derive(Clone, Debug)]
+pub struct Listing {
+    /// The type of the expression
+    pub typ: ArcType,
+    /// The core expression after optimization
+    pub core: String,
+    /// The compiled functions, starting with the function for the expression itself followed by
+    /// the functions nested in it
+    pub functions: Vec<FunctionListing>,
+}
+
+/// The bytecode of a single compiled function
+#[derive(Clone, Debug)]
+pub struct FunctionListing {
+    pub id: Symbol,
+    pub args: u32,
+    pub max_stack_size: u32,
+    pub instructions: Vec<InstructionListing>,
+}
+
+#[derive(Clone, Debug)]
+pub struct InstructionListing {
+    pub instruction: Instruction,
+    /// The line which emitted this instruction
+    pub line: Option<Line>,
+    /// The source text of `line`
+    pub source: Option<String>,
+}
+
+impl Listing {
+    pub(crate) fn new(typ: ArcType, core: String, function: &CompiledFunction, src: &str) -> Self {
+        let lines: Vec<&str> = src.lines().collect();
+        let mut functions = Vec::new();
+        add_functions(&mut functions, function, &lines);
+        Listing {
+            typ,
+            core,
+            functions,
+        }
+    }
+}
+
+fn add_functions(
+    functions: &mut Vec<FunctionListing>,
+    function: &CompiledFunction,
+    lines: &[&str],
+) {
+    let source_map = &function.debug_info.source_map;
+    functions.push(FunctionListing {
+        id: function.id.clone(),
+        args: function.args,
+        max_stack_size: function.max_stack_size,
+        instructions: function
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(i, &instruction)| {
+                let line = source_map.line(i);
+                InstructionListing {
+                    instruction,
+                    line,
+                    source: line
+                        .and_then(|line| lines.get(line.0 as usize))
+                        .map(|source| source.trim())
+                        .filter(|source| !source.is_empty())
+                        .map(|source| source.to_string()),
+                }
+            })
+            .collect(),
+    });
+    for inner in &function.inner_functions {
+        add_functions(functions, inner, lines);
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "// type: {}", self.typ)?;
+        writeln!(f, "// core")?;
+        writeln!(f, "{}", self.core.trim_end())?;
+        for function in &self.functions {
+            writeln!(f)?;
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FunctionListing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "// function {} (args: {}, max stack size: {})",
+            self.id, self.args, self.max_stack_size
+        )?;
+        let mut previous_line = None;
+        for (i, listing) in self.instructions.iter().enumerate() {
+            let instruction = format!("{:?}", listing.instruction);
+            match (listing.line, &listing.source) {
+                // Only annotate the first instruction of each line to keep the listing readable
+                (Some(line), Some(source)) if previous_line != Some(line) => writeln!(
+                    f,
+                    "{:>4}: {:<30} // {}: {}",
+                    i,
+                    instruction,
+                    line.0 + 1,
+                    source
+                )?,
+                _ => writeln!(f, "{:>4}: {}", i, instruction)?,
+            }
+            previous_line = listing.line;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! Discovery of plugins written in gluon.
+//!
+//! A plugin is a `.glu` file in a plugin directory which exports an entry point of a type
+//! specified by the host. `load_plugins` loads every such file as a module named after the file
+//! (`greet.glu` is loaded as `greet`) and extracts the entry point as a typed Rust value.
+//!
+//! ```no_run
+//! use gluon::{plugin::load_plugins, vm::api::FunctionRef, ThreadExt};
+//!
+//! let thread = gluon::new_vm();
+//! for plugin in load_plugins::<FunctionRef<fn(String) -> String>>(&thread, "plugins", "handler")
+//!     .unwrap()
+//! {
+//!     match plugin {
+//!         Ok(mut plugin) => println!("{}", plugin.entry_point.call("request".into()).unwrap()),
+//!         Err(err) => eprintln!("{}", err),
+//!     }
+//! }
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    result::Result as StdResult,
+};
+
+use crate::{
+    vm::api::{Getable, VmType},
+    Error, Result, Thread, ThreadExt,
+};
+
+quick_error! {
+/// Error returned when a file in the plugin directory could not be loaded as a plugin
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum PluginError {
+    /// The file could not be compiled or run
+    Load(path: PathBuf, err: Error) {
+        display("{}: {}", path.display(), err)
+    }
+    /// The module does not export the entry point or it has the wrong type
+    EntryPoint(path: PathBuf, err: Error) {
+        display("{}: Invalid entry point: {}", path.display(), err)
+    }
+}
+}
+
+/// A loaded plugin
+#[derive(Debug)]
+pub struct Plugin<T> {
+    /// The file the plugin was loaded from
+    pub path: PathBuf,
+    /// The name of the module the plugin was loaded as
+    pub module: String,
+    /// The value of the entry point exported by the plugin
+    pub entry_point: T,
+}
+
+/// Loads each `.glu` file in `dir` as a plugin which exports `entry_point` with the type `T`.
+///
+/// The plugins are returned in the order of their paths. A file which can not be loaded or
+/// exports an entry point of a different type results in a `PluginError` without stopping the
+/// other plugins from loading, so an application can report a broken plugin and continue. Only
+/// failing to read `dir` itself is returned as an error directly.
+pub fn load_plugins<'vm, T>(
+    thread: &'vm Thread,
+    dir: impl AsRef<Path>,
+    entry_point: &str,
+) -> Result<Vec<StdResult<Plugin<T>, PluginError>>>
+where
+    T: for<'value> Getable<'vm, 'value> + VmType,
+{
+    futures::executor::block_on(load_plugins_async(thread, dir, entry_point))
+}
+
+/// Async version of `load_plugins`
+pub async fn load_plugins_async<'vm, T>(
+    thread: &'vm Thread,
+    dir: impl AsRef<Path>,
+    entry_point: &str,
+) -> Result<Vec<StdResult<Plugin<T>, PluginError>>>
+where
+    T: for<'value> Getable<'vm, 'value> + VmType,
+{
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().map_or(false, |ext| ext == "glu") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut plugins = Vec::with_capacity(paths.len());
+    for path in paths {
+        plugins.push(load_plugin(thread, path, entry_point).await);
+    }
+    Ok(plugins)
+}
+
+async fn load_plugin<'vm, T>(
+    thread: &'vm Thread,
+    path: PathBuf,
+    entry_point: &str,
+) -> StdResult<Plugin<T>, PluginError>
+where
+    T: for<'value> Getable<'vm, 'value> + VmType,
+{
+    // `read_dir` only returns paths with a file name
+    let filename = path.file_name().unwrap().to_string_lossy().into_owned();
+    let module = crate::base::filename_to_module(&filename);
+
+    let result = match fs::read_to_string(&path) {
+        Ok(source) => thread.load_script_async(&filename, &source).await,
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = result {
+        return Err(PluginError::Load(path, err));
+    }
+
+    // `get_global` checks the type of the entry point against `T` with `check_signature`
+    match thread.get_global(&format!("{}.{}", module, entry_point)) {
+        Ok(entry_point) => Ok(Plugin {
+            path,
+            module,
+            entry_point,
+        }),
+        Err(err) => Err(PluginError::EntryPoint(path, err.into())),
+    }
+}
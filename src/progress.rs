@@ -0,0 +1,106 @@
+//! Progress reporting for compilations which load many modules.
+//!
+//! ```rust
+//! # use gluon::{new_vm, ThreadExt};
+//! let vm = new_vm();
+//! vm.get_database_mut().progress_callback(|progress| {
+//!     println!("{:.0}% {} {:?}", progress.percentage(), progress.module, progress.event)
+//! });
+//! vm.load_script("example", "let _ = import! std.list in ()").unwrap();
+//! ```
+
+use std::sync::Arc;
+
+use crate::{base::fnv::FnvSet, query::CompilerDatabase};
+
+/// Callback which receives the progress of the compiler
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// The phases that a module goes through when it is loaded
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Phase {
+    /// Parsing, macro expansion and typechecking
+    Typecheck,
+    /// Translation to the core language and optimization
+    Translate,
+    /// Compilation to bytecode
+    Compile,
+    /// Running the module to produce its value
+    Execute,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ProgressEvent {
+    /// The module was imported and will be loaded
+    ModuleStarted,
+    /// The module entered a new phase
+    Phase(Phase),
+    /// The module has been loaded (or failed to load)
+    ModuleFinished,
+}
+
+#[derive(Clone, Debug)]
+pub struct Progress<'a> {
+    pub module: &'a str,
+    pub event: ProgressEvent,
+    /// The number of modules which have finished loading
+    pub finished: usize,
+    /// The number of modules found so far. Increases as the imports of each module are found.
+    pub total: usize,
+}
+
+impl Progress<'_> {
+    /// The percentage of the modules found so far which have finished loading. As imports are
+    /// discovered during compilation this may decrease as well as increase.
+    pub fn percentage(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.finished as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ProgressState {
+    callback: Option<ProgressCallback>,
+    started: FnvSet<String>,
+    finished: FnvSet<String>,
+}
+
+impl ProgressState {
+    pub(crate) fn set_callback(&mut self, callback: Option<ProgressCallback>) {
+        *self = ProgressState {
+            callback,
+            ..ProgressState::default()
+        };
+    }
+}
+
+/// Reports `event` for `module` to the callback set on `db`, if there is one
+pub(crate) fn report(db: &CompilerDatabase, module: &str, event: ProgressEvent) {
+    let (callback, finished, total) = {
+        let mut state = db.state();
+        let progress = &mut state.progress;
+        let callback = match &progress.callback {
+            Some(callback) => callback.clone(),
+            None => return,
+        };
+        let is_new = match event {
+            ProgressEvent::ModuleStarted => progress.started.insert(module.to_string()),
+            ProgressEvent::ModuleFinished => progress.finished.insert(module.to_string()),
+            ProgressEvent::Phase(_) => true,
+        };
+        if !is_new {
+            return;
+        }
+        (callback, progress.finished.len(), progress.started.len())
+    };
+    // The state must not be locked while calling the callback as it may use the compiler
+    callback(&Progress {
+        module,
+        event,
+        finished,
+        total,
+    });
+}
@@ -1,23 +1,29 @@
 use std::{
     borrow::Cow,
     collections::hash_map,
+    mem,
     ops::Deref,
     result::Result as StdResult,
     sync::{Arc, Mutex, MutexGuard},
 };
 
-use salsa::{Database, OwnedDb};
+use salsa::{debug::DebugQueryTable, OwnedDb};
 
 use {
     base::{
-        ast::{self, OwnedExpr, TypedIdent},
+        ast::{self, OwnedExpr, SpannedExpr, TypedIdent, Visitor},
+        error::{Errors, InFile, Salvage},
         fnv::{FnvMap, FnvSet},
         kind::{ArcKind, KindEnv},
         metadata::{Metadata, MetadataEnv},
-        pos::BytePos,
-        source::{CodeMap, FileMap, Source},
+        pos::{self, BytePos, Line},
+        source::{CodeMap, FileMap, GeneratedCode, Source},
         symbol::{Name, Symbol, SymbolModule, SymbolRef},
-        types::{Alias, ArcType, NullInterner, PrimitiveEnv, TypeEnv, TypeExt},
+        types::{Alias, ArcType, NullInterner, PrimitiveEnv, Type, TypeEnv, TypeExt},
+    },
+    check::{
+        typecheck::{HelpError, TypeError},
+        warning::Warnings,
     },
     vm::{
         self,
@@ -28,13 +34,21 @@ use {
         internal::ClosureData,
         internal::Value,
         macros,
+        source_map::ExecutableLines,
         thread::{RootedThread, RootedValue, Thread, ThreadInternal},
         vm::VmEnv,
         ExternLoader,
     },
 };
 
-use crate::{compiler_pipeline::*, import::PtrEq, Error, ModuleCompiler, Result, Settings};
+#[cfg(feature = "serialization")]
+use crate::interface::ModuleInterface;
+use crate::{
+    compiler_pipeline::*,
+    import::PtrEq,
+    progress::{self, Phase, ProgressEvent, ProgressState},
+    Error, ModuleCompiler, Result, Settings,
+};
 
 pub use salsa;
 
@@ -80,6 +94,9 @@ pub struct State {
     pub(crate) code_map: CodeMap,
     pub(crate) inline_modules: FnvMap<String, Arc<Cow<'static, str>>>,
     pub(crate) index_map: FnvMap<String, BytePos>,
+    pub(crate) progress: ProgressState,
+    executable_lines: FnvMap<String, ExecutableLines>,
+    warnings: FnvMap<String, Warnings>,
     extern_globals: FnvSet<String>,
 }
 
@@ -114,6 +131,30 @@ impl State {
         file_map
     }
 
+    /// Adds a file containing generated code, such as the implicit prelude, which diagnostics
+    /// report at `generated.call_site`
+    #[doc(hidden)]
+    pub fn add_generated_filemap(
+        &mut self,
+        file: &str,
+        source: &str,
+        generated: GeneratedCode,
+    ) -> Arc<FileMap> {
+        match self.get_filemap(file) {
+            Some(ref file_map)
+                if file_map.src() == source && file_map.generated() == Some(&generated) =>
+            {
+                return file_map.clone()
+            }
+            _ => (),
+        }
+        let file_map =
+            self.code_map
+                .add_generated_filemap(file.to_string(), source.into(), generated);
+        self.index_map.insert(file.into(), file_map.span().start());
+        file_map
+    }
+
     pub(crate) fn get_or_insert_filemap<S>(&mut self, file: &str, source: S) -> Arc<FileMap>
     where
         S: AsRef<str> + Into<String>,
@@ -255,6 +296,14 @@ impl crate::query::CompilationBase for CompilerDatabase {
             .and_then(|r| r.ok())
     }
 
+    fn peek_executable_lines(&self, key: &str) -> Option<ExecutableLines> {
+        self.state().executable_lines.get(key).cloned()
+    }
+
+    fn peek_warnings(&self, key: &str) -> Option<Warnings> {
+        self.state().warnings.get(key).cloned()
+    }
+
     fn peek_global(&self, key: &str) -> Option<DatabaseGlobal> {
         GlobalInnerQuery
             .in_db(self)
@@ -347,7 +396,8 @@ impl CompilerDatabase {
             .discard_values()
             .sweep_all_revisions();
 
-        ModuleTextQuery.in_db(self).sweep(strategy);
+        // The text of the modules is kept so that a module whose text is unchanged is not compiled
+        // again
         TypecheckedSourceModuleQuery.in_db(self).sweep(strategy);
         CoreExprQuery.in_db(self).sweep(strategy);
         CompiledModuleQuery.in_db(self).sweep(strategy);
@@ -371,6 +421,10 @@ pub trait CompilationBase: Send {
     fn peek_module_type(&self, key: &str) -> Option<ArcType>;
     fn peek_module_metadata(&self, key: &str) -> Option<Arc<Metadata>>;
     fn peek_core_expr(&self, key: &str) -> Option<interpreter::Global<CoreExpr>>;
+    /// Returns the lines of `key` which have instructions, if it has been compiled
+    fn peek_executable_lines(&self, key: &str) -> Option<ExecutableLines>;
+    /// Returns the warnings found when typechecking `key`, if it has been typechecked
+    fn peek_warnings(&self, key: &str) -> Option<Warnings>;
     fn peek_global(&self, key: &str) -> Option<DatabaseGlobal>;
 }
 
@@ -393,8 +447,12 @@ pub trait Compilation: CompilationBase {
     #[salsa::transparent]
     fn get_extern_global(&self, name: &str) -> Option<DatabaseGlobal>;
 
+    #[doc(hidden)]
     #[salsa::dependencies]
-    fn module_text(&self, module: String) -> StdResult<Arc<Cow<'static, str>>, Error>;
+    async fn read_module_text(&self, module: String) -> StdResult<Arc<Cow<'static, str>>, Error>;
+
+    // Async since salsa can only validate dependencies on the async queries from async queries
+    async fn module_text(&self, module: String) -> StdResult<Arc<Cow<'static, str>>, Error>;
 
     #[salsa::cycle(recover_cycle_typecheck)]
     async fn typechecked_source_module(
@@ -521,21 +579,46 @@ fn get_extern_global(db: &dyn Compilation, name: &str) -> Option<DatabaseGlobal>
     }
 }
 
-fn module_text(db: &dyn Compilation, module: String) -> StdResult<Arc<Cow<'static, str>>, Error> {
-    db.salsa_runtime()
-        .report_synthetic_read(salsa::Durability::LOW);
+async fn read_module_text(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> StdResult<Arc<Cow<'static, str>>, Error> {
+    db.salsa_runtime().report_untracked_read();
 
-    let opt = { db.compiler().state().inline_modules.get(&module).cloned() };
+    module_source(&**db, &module)
+}
+
+async fn module_text(
+    db: &mut OwnedDb<'_, dyn Compilation + '_>,
+    module: String,
+) -> StdResult<Arc<Cow<'static, str>>, Error> {
+    // The source is read again in every revision but as long as it is unchanged the memoized text
+    // keeps its revision, so only the modules which (transitively) import a changed module are
+    // compiled again
+    db.read_module_text(module).await
+}
+
+/// Reads the source of `module` directly, without memoizing it in the `module_text` query
+pub(crate) fn module_source(
+    db: &dyn Compilation,
+    module: &str,
+) -> StdResult<Arc<Cow<'static, str>>, Error> {
+    let opt = { db.compiler().state().inline_modules.get(module).cloned() };
     let contents = if let Some(contents) = opt {
         contents
     } else {
         let mut filename = module.replace(".", "/");
         filename.push_str(".glu");
 
-        let use_standard_lib = db.compiler_settings().use_standard_lib;
+        let settings = db.compiler_settings();
         Arc::new(
             crate::get_import(db.thread())
-                .get_module_source(use_standard_lib, &module, &filename)
+                .get_module_source(
+                    settings.use_standard_lib,
+                    settings.std_root.as_deref(),
+                    module,
+                    &filename,
+                )
                 .map_err(macros::Error::new)?,
         )
     };
@@ -548,13 +631,19 @@ async fn typechecked_source_module(
     module: String,
     expected_type: Option<ArcType>,
 ) -> SalvageResult<TypecheckValue<Arc<OwnedExpr<Symbol>>>, Error> {
-    db.salsa_runtime().report_untracked_read();
+    progress::report(
+        db.compiler(),
+        &module,
+        ProgressEvent::Phase(Phase::Typecheck),
+    );
 
-    let text = db.module_text(module.clone())?;
+    let text = db.module_text(module.clone()).await?;
 
     let thread = db.thread().root_thread();
+    #[cfg(feature = "serialization")]
+    let emit_interfaces = db.compiler_settings().emit_interfaces;
     let mut compiler = ModuleCompiler::new(db);
-    let value = text
+    let mut value = text
         .typecheck_expected(
             &mut compiler,
             &thread,
@@ -565,9 +654,176 @@ async fn typechecked_source_module(
         .await
         .map_err(|err| err.map(|value| value.map(Arc::new)))?;
 
+    if let Err(err) = hide_private_fields(&mut value) {
+        let error = InFile::new(compiler.database.state().code_map.clone(), err).into();
+        return Err(Salvage {
+            value: Some(value.map(Arc::new)),
+            error,
+        });
+    }
+
+    // The typechecked module is discarded once it has been compiled so the warnings are recorded
+    // here
+    compiler
+        .database
+        .state()
+        .warnings
+        .insert(module.clone(), value.warnings.clone());
+
+    #[cfg(feature = "serialization")]
+    {
+        if let Some(dir) = emit_interfaces {
+            let interface = ModuleInterface {
+                name: module.clone(),
+                typ: value.typ.clone(),
+                metadata: value.metadata.clone(),
+            };
+            interface.write_to_dir(&dir).map_err(Error::from)?;
+        }
+    }
+
     Ok(value.map(Arc::new))
 }
 
+/// Removes the fields marked with `#[private]` from the record exported by a module, both from
+/// its type and from the record expression itself so that the fields are only visible inside the
+/// module. The fields are kept in the metadata so that other modules can be told why they are
+/// missing.
+///
+/// Fields can only be removed from a module which evaluates to a record expression without a base
+/// record, for any other module an error is returned instead.
+fn hide_private_fields(
+    value: &mut TypecheckValue<OwnedExpr<Symbol>>,
+) -> StdResult<(), check::typecheck::Error> {
+    fn tail_expr<'a, 'ast>(
+        expr: &'a mut SpannedExpr<'ast, Symbol>,
+    ) -> &'a mut SpannedExpr<'ast, Symbol> {
+        match expr.value {
+            ast::Expr::LetBindings(_, ref mut body) | ast::Expr::TypeBindings(_, ref mut body) => {
+                tail_expr(body)
+            }
+            _ => expr,
+        }
+    }
+
+    fn remove_fields(typ: &ArcType, is_private: &dyn Fn(&Symbol) -> bool) -> Option<ArcType> {
+        match &**typ {
+            Type::Forall(params, typ) => Some(Type::forall(
+                params.clone(),
+                remove_fields(typ, is_private)?,
+            )),
+            Type::Record(_) => {
+                let mut iter = typ.row_iter();
+                let fields = iter
+                    .by_ref()
+                    .filter(|field| !is_private(&field.name))
+                    .cloned()
+                    .collect();
+                let rest = iter.current_type().clone();
+                Some(Type::poly_record(
+                    typ.type_field_iter().cloned().collect(),
+                    fields,
+                    rest,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    let metadata = value.metadata.clone();
+    let is_private = |name: &Symbol| {
+        metadata
+            .module
+            .get(name.declared_name())
+            .map_or(false, |metadata| metadata.is_private())
+    };
+    if !metadata
+        .module
+        .values()
+        .any(|metadata| metadata.is_private())
+    {
+        return Ok(());
+    }
+
+    let tail = tail_expr(value.expr.expr_mut());
+    let span = tail.span;
+    let unsupported = || {
+        let error = TypeError::Message(
+            "Fields marked as `#[private]` can only be hidden in a module which ends with a \
+             record expression without a base record"
+                .to_string(),
+        );
+        Errors::from(vec![pos::spanned(span, HelpError::from(error))])
+    };
+    if let ast::Expr::Record {
+        typ,
+        exprs,
+        base: None,
+        ..
+    } = &mut tail.value
+    {
+        let (record_type, module_type) = match (
+            remove_fields(typ, &is_private),
+            remove_fields(&value.typ, &is_private),
+        ) {
+            (Some(record_type), Some(module_type)) => (record_type, module_type),
+            _ => return Err(unsupported()),
+        };
+
+        // Move the public fields to the front, keeping their order, and drop the rest
+        let fields = mem::take(exprs);
+        let mut public_fields = 0;
+        for i in 0..fields.len() {
+            if !is_private(&fields[i].name.value) {
+                fields.swap(public_fields, i);
+                public_fields += 1;
+            }
+        }
+        *exprs = &mut fields[..public_fields];
+
+        *typ = record_type;
+        value.typ = module_type;
+        Ok(())
+    } else {
+        Err(unsupported())
+    }
+}
+
+/// Returns the interface of `module` if its source can't be found but an interface for it exists
+#[cfg(feature = "serialization")]
+pub(crate) fn interface_without_source(
+    db: &dyn Compilation,
+    module: &str,
+) -> StdResult<Option<ModuleInterface>, Error> {
+    db.salsa_runtime()
+        .report_synthetic_read(salsa::Durability::LOW);
+
+    if db.compiler().state().inline_modules.contains_key(module) {
+        return Ok(None);
+    }
+    let import = crate::get_import(db.thread());
+    let interface = match import.read_interface(module) {
+        Some(interface) => interface.map_err(macros::Error::new)?,
+        None => return Ok(None),
+    };
+
+    let mut filename = module.replace(".", "/");
+    filename.push_str(".glu");
+    let settings = db.compiler_settings();
+    if import
+        .get_module_source(
+            settings.use_standard_lib,
+            settings.std_root.as_deref(),
+            module,
+            &filename,
+        )
+        .is_ok()
+    {
+        return Ok(None);
+    }
+    Ok(Some(interface))
+}
+
 async fn module_type(
     db: &mut OwnedDb<'_, dyn Compilation + '_>,
     name: String,
@@ -577,6 +833,12 @@ async fn module_type(
         let global = db.extern_module(name).await?;
         return Ok(global.typ.clone());
     }
+    #[cfg(feature = "serialization")]
+    {
+        if let Some(interface) = interface_without_source(&**db, &name)? {
+            return Ok(interface.typ);
+        }
+    }
     db.typechecked_source_module(name, expected_type)
         .await
         .map(|module| module.typ)
@@ -592,6 +854,12 @@ async fn module_metadata(
         let global = db.extern_module(name).await?;
         return Ok(global.metadata.clone());
     }
+    #[cfg(feature = "serialization")]
+    {
+        if let Some(interface) = interface_without_source(&**db, &name)? {
+            return Ok(interface.metadata);
+        }
+    }
     db.typechecked_source_module(name, expected_type)
         .await
         .map(|module| module.metadata)
@@ -603,17 +871,30 @@ async fn core_expr(
     module: String,
     expected_type: Option<ArcType>,
 ) -> StdResult<interpreter::Global<CoreExpr>, Error> {
-    db.salsa_runtime().report_untracked_read();
-
     let value = db
         .typechecked_source_module(module.clone(), expected_type.clone())
         .await?;
 
+    // The optimizer reads the core expressions of imported modules without tracking them so
+    // depend on the imported modules directly, otherwise this module would not be optimized
+    // again when one of its imports changes
+    for import in imported_modules(value.expr.expr()) {
+        if db.peek_global(&import).is_some() {
+            db.global(import).await?;
+        }
+    }
+
     // Ensure the type is stored in the database so we can collect typechecked_source_module later
     db.module_type(module.clone(), expected_type.clone())
         .await?;
     db.module_metadata(module.clone(), expected_type).await?;
 
+    progress::report(
+        db.compiler(),
+        &module,
+        ProgressEvent::Phase(Phase::Translate),
+    );
+
     let settings = db.compiler_settings();
 
     let env = env(db.compiler());
@@ -623,7 +904,12 @@ async fn core_expr(
         debug!("Translation returned: {}", expr);
 
         let core_expr = if settings.optimize {
-            core::optimize::optimize(&translator.allocator, &env, expr)
+            core::optimize::optimize(
+                &translator.allocator,
+                &env,
+                settings.optimization_level,
+                expr,
+            )
         } else {
             interpreter::Global {
                 value: core::freeze_expr(&translator.allocator, expr),
@@ -642,41 +928,80 @@ async fn compiled_module(
     expected_type: Option<ArcType>,
 ) -> StdResult<OpaqueValue<RootedThread, GcPtr<ClosureData>>, Error> {
     let core_expr = db.core_expr(module.clone(), expected_type).await?;
-    let settings = db.compiler_settings();
 
-    let mut compiler = ModuleCompiler::new(&mut *db);
+    progress::report(db.compiler(), &module, ProgressEvent::Phase(Phase::Compile));
 
-    let source = compiler
-        .get_filemap(&module)
-        .expect("Filemap does not exist");
+    let settings = db.compiler_settings();
 
     let name = Name::new(&module);
-    let symbols = SymbolModule::new(
-        String::from(AsRef::<str>::as_ref(name.module())),
-        &mut compiler.symbols,
-    );
-
     let thread = db.thread().root_thread();
-    let env = env(db.compiler());
-    let mut compiler = vm::compiler::Compiler::new(
-        &env,
-        thread.global_env(),
-        symbols,
-        &source,
-        module.clone(),
-        settings.emit_debug_info,
-    );
+    let mut compiled_module = {
+        let mut compiler = ModuleCompiler::new(&mut *db);
+
+        let source = compiler
+            .get_filemap(&module)
+            .expect("Filemap does not exist");
+
+        let symbols = SymbolModule::new(
+            String::from(AsRef::<str>::as_ref(name.module())),
+            &mut compiler.symbols,
+        );
+
+        let env = env(db.compiler());
+        let mut compiler = vm::compiler::Compiler::new(
+            &env,
+            thread.global_env(),
+            symbols,
+            &source,
+            module.clone(),
+            settings.emit_debug_info,
+        );
+
+        compiler.compile_expr(core_expr.value.expr())?
+    };
+
+    // The values of the globals are looked up when the module is instantiated, so the module
+    // must be compiled again if any of them change
+    for global in &compiled_module.module_globals {
+        let global = global.definition_name().trim_start_matches('@');
+        if db.get_extern_global(global).is_none() {
+            db.global(global.into()).await?;
+        }
+    }
 
-    let mut compiled_module = compiler.compile_expr(core_expr.value.expr())?;
     let module_id = Symbol::from(format!("@{}", name));
     compiled_module.function.id = module_id.clone();
     let closure = thread
         .global_env()
         .new_global_thunk(&thread, compiled_module)?;
 
+    // The compiled module is discarded once it has been loaded so the lines are recorded here
+    db.state()
+        .executable_lines
+        .insert(module, ExecutableLines::from_function(&closure.function));
+
     Ok(closure)
 }
 
+/// Returns the names of the modules that `expr` refers to
+fn imported_modules(expr: &SpannedExpr<Symbol>) -> Vec<String> {
+    struct Imports(FnvSet<String>);
+
+    impl<'a, 'ast> Visitor<'a, 'ast> for Imports {
+        type Ident = Symbol;
+
+        fn visit_ident(&mut self, id: &'a TypedIdent<Symbol>) {
+            if id.name.is_global() {
+                self.0.insert(id.name.definition_name().into());
+            }
+        }
+    }
+
+    let mut imports = Imports(FnvSet::default());
+    imports.visit_expr(expr);
+    imports.0.into_iter().collect()
+}
+
 async fn import(
     db: &mut OwnedDb<'_, dyn Compilation + '_>,
     modulename: String,
@@ -684,6 +1009,8 @@ async fn import(
     assert!(!modulename.starts_with('@'));
     let thread = db.thread().root_thread();
 
+    progress::report(db.compiler(), &modulename, ProgressEvent::ModuleStarted);
+
     let name = Symbol::from(format!("@{}", modulename));
     let result = crate::get_import(&thread)
         .load_module(&mut ModuleCompiler::new(&mut *db), &thread, &name)
@@ -692,6 +1019,8 @@ async fn import(
     let compiler = db.compiler();
     compiler.collect_garbage();
 
+    progress::report(compiler, &modulename, ProgressEvent::ModuleFinished);
+
     let typ = result.map_err(|salvage| {
         salvage.map(|typ| TypedIdent {
             name: name.clone(),
@@ -727,6 +1056,8 @@ async fn global_inner(
 
     let module_id = closure.function.name.clone();
 
+    progress::report(db.compiler(), &name, ProgressEvent::Phase(Phase::Execute));
+
     let vm = db.thread();
     let v = vm
         .call_thunk_top(&closure)
@@ -805,7 +1136,7 @@ async fn global(
 use std::cell::RefCell;
 pub struct Env<T>(RefCell<T>);
 
-pub(crate) fn env(env: &(dyn Compilation + '_)) -> Env<&'_ CompilerDatabase> {
+pub(crate) fn env<'a>(env: &'a (dyn Compilation + 'a)) -> Env<&'a CompilerDatabase> {
     Env(RefCell::new(env.compiler()))
 }
 pub(crate) fn snapshot_env<T>(env: T) -> Env<T>
@@ -957,6 +1288,28 @@ where
         env.get_extern_global(name)
             .or_else(|| env.peek_global(module.as_str().into()))
     }
+
+    fn source_line(&self, source_name: &str, line: Line) -> Option<String> {
+        let file_map = self.0.borrow().get_filemap(source_name)?;
+        file_map
+            .source()
+            .lines()
+            .nth(line.to_usize())
+            .map(|line| line.to_string())
+    }
+
+    fn global_names(&self) -> Vec<String> {
+        let env = self.0.borrow();
+        let mut names: Vec<_> = GlobalInnerQuery
+            .in_db(&**env)
+            .entries::<Vec<_>>()
+            .into_iter()
+            .filter(|entry| matches!(entry.value, Some(Ok(_))))
+            .map(|entry| entry.key)
+            .collect();
+        names.extend(env.state().extern_globals.iter().cloned());
+        names
+    }
 }
 
 fn get_scoped_global<'n, T>(
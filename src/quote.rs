@@ -0,0 +1,280 @@
+//! Quasi-quotation for macros implemented in Rust.
+//!
+//! Instead of building the expression a macro expands to node by node, a `Quoter` parses a
+//! template written in gluon syntax where `$name` marks the places to splice in expressions or
+//! identifiers created by the macro.
+//!
+//! The `quote_expr!` macro from `gluon_codegen` creates the template from its arguments and
+//! splices in the local variables of the same names.
+//!
+//! ```no_run
+//! # use gluon::{base::{ast::SpannedExpr, symbol::Symbol}, quote::{Quoter, QuoteError}};
+//! fn let_in<'ast>(
+//!     quoter: &mut Quoter<'_, 'ast>,
+//!     e: SpannedExpr<'ast, Symbol>,
+//!     body: SpannedExpr<'ast, Symbol>,
+//! ) -> Result<SpannedExpr<'ast, Symbol>, QuoteError> {
+//!     // Equivalent to `quote_expr!(quoter, let x = $e in $body)`
+//!     quoter.expr(
+//!         "let x = $e in $body",
+//!         vec![("e", e.into()), ("body", body.into())],
+//!     )
+//! }
+//! ```
+//!
+//! Nodes created from the template get the span of the macro invocation while spliced
+//! expressions keep their own spans. Identifiers in the template are created in the same scope as
+//! the code surrounding the expansion (see `Macro::expand`), so splice in a new `Symbol` to bind
+//! a name which can not be captured by other spliced expressions. Macros in the template, such
+//! as `import!`, are not expanded until the result is expanded with the `MacroExpander`.
+
+use crate::{
+    base::{
+        ast::{self, Expr, IdentEnv, MutVisitor, SpannedExpr, SpannedIdent, SpannedPattern},
+        error::Errors,
+        fnv::FnvMap,
+        pos::{BytePos, Span, Spanned},
+        symbol::{Symbol, Symbols},
+        types::{ArcType, TypeCache},
+    },
+    parser,
+    vm::macros,
+    Thread,
+};
+
+quick_error! {
+/// Error returned when a template could not be quoted
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub enum QuoteError {
+    /// The template refers to a splice which was not passed to `Quoter::expr`
+    UnknownSplice(name: String) {
+        display("No splice named `${}`", name)
+    }
+    /// An expression was spliced in more than once
+    DuplicateSplice(name: String) {
+        display("The expression `${}` is spliced more than once", name)
+    }
+    /// An expression was spliced into a position which requires an identifier
+    ExpectedIdent(name: String) {
+        display("`${}` must be an identifier", name)
+    }
+    /// The template is not a valid expression
+    Parse(template: String, message: String) {
+        display("Unable to parse the quoted expression `{}`: {}", template, message)
+    }
+}
+}
+
+impl From<QuoteError> for macros::Error {
+    fn from(err: QuoteError) -> Self {
+        macros::Error::message(err.to_string())
+    }
+}
+
+/// A value which can be spliced into a template
+pub enum Splice<'ast> {
+    /// Replaces an expression
+    Expr(SpannedExpr<'ast, Symbol>),
+    /// Replaces an identifier, wherever it is used
+    Ident(Symbol),
+}
+
+impl<'ast> From<SpannedExpr<'ast, Symbol>> for Splice<'ast> {
+    fn from(expr: SpannedExpr<'ast, Symbol>) -> Self {
+        Splice::Expr(expr)
+    }
+}
+
+impl From<Symbol> for Splice<'_> {
+    fn from(symbol: Symbol) -> Self {
+        Splice::Ident(symbol)
+    }
+}
+
+/// Builds expressions from templates, see the module documentation
+pub struct Quoter<'a, 'ast> {
+    arena: ast::ArenaRef<'a, 'ast, Symbol>,
+    symbols: &'a mut Symbols,
+    type_cache: &'a TypeCache<Symbol, ArcType>,
+    span: Span<BytePos>,
+}
+
+impl<'a, 'ast> Quoter<'a, 'ast> {
+    /// Creates a `Quoter` which allocates in `arena` and gives the nodes it creates `span`
+    pub fn new(
+        thread: &'a Thread,
+        arena: ast::ArenaRef<'a, 'ast, Symbol>,
+        symbols: &'a mut Symbols,
+        span: Span<BytePos>,
+    ) -> Self {
+        Quoter {
+            arena,
+            symbols,
+            type_cache: thread.global_env().type_cache(),
+            span,
+        }
+    }
+
+    /// Parses `template` as an expression, replacing each `$name` with the splice of that name
+    pub fn expr(
+        &mut self,
+        template: &str,
+        splices: Vec<(&str, Splice<'ast>)>,
+    ) -> Result<SpannedExpr<'ast, Symbol>, QuoteError> {
+        let mut splices: FnvMap<String, Option<Splice<'ast>>> = splices
+            .into_iter()
+            .map(|(name, splice)| (name.to_string(), Some(splice)))
+            .collect();
+        let source = replace_placeholders(template, &splices)?;
+
+        let mut env = SpliceEnv {
+            symbols: &mut *self.symbols,
+            splices: &splices,
+            placeholders: FnvMap::default(),
+        };
+        let mut expr = parser::parse_expr(self.arena, &mut env, self.type_cache, &source)
+            .map_err(|err| QuoteError::Parse(template.to_string(), err.to_string()))?;
+        let placeholders = env.placeholders;
+
+        let mut splicer = Splicer {
+            span: self.span,
+            placeholders: &placeholders,
+            splices: &mut splices,
+            errors: Errors::new(),
+        };
+        splicer.visit_expr(&mut expr);
+        match splicer.errors.pop() {
+            Some(err) => Err(err),
+            None => Ok(expr),
+        }
+    }
+}
+
+/// Prefix of the identifiers which `$name` is replaced by before parsing the template
+const PLACEHOLDER_PREFIX: &str = "quote_splice_";
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Replaces each `$name` in `template` with a placeholder identifier
+fn replace_placeholders<T>(
+    template: &str,
+    splices: &FnvMap<String, T>,
+) -> Result<String, QuoteError> {
+    let mut source = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find('$') {
+        source.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        if !after.starts_with(is_ident_start) {
+            // An operator such as `<$>`
+            source.push('$');
+            rest = after;
+            continue;
+        }
+        let end = after.find(|c| !is_ident_char(c)).unwrap_or(after.len());
+        let name = &after[..end];
+        if !splices.contains_key(name) {
+            return Err(QuoteError::UnknownSplice(name.to_string()));
+        }
+        source.push_str(PLACEHOLDER_PREFIX);
+        source.push_str(name);
+        rest = &after[end..];
+    }
+    source.push_str(rest);
+    Ok(source)
+}
+
+/// Creates symbols while parsing, resolving the placeholders of spliced identifiers to the
+/// spliced symbols
+struct SpliceEnv<'s, 'ast> {
+    symbols: &'s mut Symbols,
+    splices: &'s FnvMap<String, Option<Splice<'ast>>>,
+    /// The names of the spliced expressions, keyed by the symbols of their placeholders
+    placeholders: FnvMap<Symbol, String>,
+}
+
+impl ast::DisplayEnv for SpliceEnv<'_, '_> {
+    type Ident = Symbol;
+
+    fn string<'a>(&'a self, ident: &'a Symbol) -> &'a str {
+        self.symbols.string(ident)
+    }
+}
+
+impl IdentEnv for SpliceEnv<'_, '_> {
+    fn from_str(&mut self, s: &str) -> Symbol {
+        let name = s.strip_prefix(PLACEHOLDER_PREFIX);
+        match name.and_then(|name| Some((name, self.splices.get(name)?))) {
+            Some((_, Some(Splice::Ident(symbol)))) => symbol.clone(),
+            Some((name, _)) => {
+                let symbol = self.symbols.from_str(s);
+                self.placeholders.insert(symbol.clone(), name.to_string());
+                symbol
+            }
+            None => self.symbols.from_str(s),
+        }
+    }
+}
+
+/// Replaces the placeholders of spliced expressions and gives every other node `span`
+struct Splicer<'s, 'ast> {
+    span: Span<BytePos>,
+    placeholders: &'s FnvMap<Symbol, String>,
+    splices: &'s mut FnvMap<String, Option<Splice<'ast>>>,
+    errors: Errors<QuoteError>,
+}
+
+impl Splicer<'_, '_> {
+    fn check_ident(&mut self, symbol: &Symbol) {
+        if let Some(name) = self.placeholders.get(symbol) {
+            self.errors.push(QuoteError::ExpectedIdent(name.clone()));
+        }
+    }
+}
+
+impl<'a, 'ast> MutVisitor<'a, 'ast> for Splicer<'_, 'ast> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a mut SpannedExpr<'ast, Symbol>) {
+        if let Expr::Ident(id) = &expr.value {
+            if let Some(name) = self.placeholders.get(&id.name) {
+                match self.splices.get_mut(name).and_then(Option::take) {
+                    Some(Splice::Expr(splice)) => {
+                        *expr = splice;
+                    }
+                    _ => self.errors.push(QuoteError::DuplicateSplice(name.clone())),
+                }
+                return;
+            }
+        }
+        expr.span = self.span;
+        ast::walk_mut_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'a mut SpannedPattern<'ast, Symbol>) {
+        pattern.span = self.span;
+        ast::walk_mut_pattern(self, &mut pattern.value);
+    }
+
+    fn visit_spanned_typed_ident(&mut self, id: &'a mut SpannedIdent<Symbol>) {
+        id.span = self.span;
+        self.check_ident(&id.value.name);
+        self.visit_ident(&mut id.value);
+    }
+
+    fn visit_ident(&mut self, id: &'a mut ast::TypedIdent<Symbol>) {
+        self.check_ident(&id.name);
+        self.visit_typ(&mut id.typ);
+    }
+
+    fn visit_spanned_ident(&mut self, id: &'a mut Spanned<Symbol, BytePos>) {
+        id.span = self.span;
+        self.check_ident(&id.value);
+    }
+}
@@ -2,8 +2,12 @@ pub mod env;
 #[cfg(feature = "http")]
 pub mod http;
 pub mod io;
+pub mod log;
 pub mod process;
 #[cfg(all(feature = "random", not(target_arch = "wasm32")))]
 pub mod random;
 #[cfg(feature = "regex")]
 pub mod regex;
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+pub mod sqlite;
+pub mod time;
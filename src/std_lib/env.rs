@@ -46,7 +46,7 @@ fn var(key: &str) -> IO<String> {
     env::var(key).into()
 }
 
-field_decl! { key, value }
+field_decl! { key, value, path }
 
 type Entry = record_type! {
     key => String,
@@ -61,9 +61,49 @@ fn vars() -> IO<Vec<Entry>> {
     )
 }
 
+type Override = record_type! {
+    path => Vec<String>,
+    value => String
+};
+
+/// Returns the environment variables whose names start with `prefix` as configuration overrides.
+/// The rest of the name is lowercased and split at each `__` into the path of the value, so
+/// `APP_DATABASE__URL` sets `database.url` if the prefix is `APP_`.
+fn config_vars(prefix: &str) -> IO<Vec<Override>> {
+    IO::Value(
+        env::vars()
+            .filter_map(|(key, value)| {
+                let name = key.strip_prefix(prefix).filter(|name| !name.is_empty())?;
+                Some(record_no_decl! {
+                    path => name.to_lowercase().split("__").map(String::from).collect(),
+                    value => value
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Returns the command line arguments of the form `--a.b=value` as configuration overrides,
+/// ignoring all other arguments
+fn config_args(args: Vec<String>) -> Vec<Override> {
+    args.iter()
+        .filter_map(|arg| {
+            let (key, value) = arg.strip_prefix("--")?.split_once('=')?;
+            Some(record_no_decl! {
+                path => key.split('.').map(String::from).collect(),
+                value => value.to_string()
+            })
+        })
+        .collect()
+}
+
 mod std {
     pub mod env {
         pub use crate::std_lib::env as prim;
+
+        pub mod config {
+            pub use crate::std_lib::env as prim;
+        }
     }
 }
 
@@ -94,3 +134,13 @@ pub fn load(vm: &Thread) -> vm::Result<ExternModule> {
         },
     )
 }
+
+pub fn load_config(vm: &Thread) -> vm::Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            config_vars => primitive!(1, std::env::config::prim::config_vars),
+            config_args => primitive!(1, std::env::config::prim::config_args),
+        },
+    )
+}
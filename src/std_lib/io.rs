@@ -24,14 +24,18 @@ use crate::vm::{
 
 use crate::{compiler_pipeline::*, Error, ModuleCompiler, ThreadExt};
 
-fn print(s: &str) -> IO<()> {
-    print!("{}", s);
-    IO::Value(())
+fn print(WithVM { vm, value: s }: WithVM<&str>) -> IO<()> {
+    match vm.global_env().write_stdout(s) {
+        Ok(()) => IO::Value(()),
+        Err(err) => IO::Exception(err.to_string()),
+    }
 }
 
-fn println(s: &str) -> IO<()> {
-    println!("{}", s);
-    IO::Value(())
+fn println(WithVM { vm, value: s }: WithVM<&str>) -> IO<()> {
+    match vm.global_env().write_stdout(&format!("{}\n", s)) {
+        Ok(()) => IO::Value(()),
+        Err(err) => IO::Exception(err.to_string()),
+    }
 }
 
 fn flush_stdout() -> IO<()> {
@@ -230,6 +234,21 @@ fn read_line() -> IO<String> {
     }
 }
 
+/// Resets the stack to `frame_level` after `action` failed, returning the stacktrace of the frames
+/// that were removed
+fn unwind_to(vm: &Thread, frame_level: usize) -> vm::Result<vm::stack::Stacktrace> {
+    let mut context = vm.context();
+    let trace = {
+        let stack = context.stack_frame::<stack::State>();
+        crate::vm::thread::reset_stack(stack, frame_level)?
+    };
+
+    let mut stack = context.stack_frame::<stack::State>();
+    let len = stack.len();
+    stack.pop_many(len - 3);
+    Ok(trace)
+}
+
 /// IO a -> (String -> IO a) -> IO a
 fn catch<'vm>(
     action: OpaqueValue<&'vm Thread, IO<A>>,
@@ -244,19 +263,8 @@ fn catch<'vm>(
         match action.call_async(()).await {
             Ok(value) => IO::Value(value),
             Err(err) => {
-                {
-                    let mut context = vm.context();
-                    {
-                        let stack = context.stack_frame::<stack::State>();
-
-                        if let Err(err) = crate::vm::thread::reset_stack(stack, frame_level) {
-                            return IO::Exception(err.to_string().into());
-                        }
-                    }
-
-                    let mut stack = context.stack_frame::<stack::State>();
-                    let len = stack.len();
-                    stack.pop_many(len - 3);
+                if let Err(err) = unwind_to(&vm, frame_level) {
+                    return IO::Exception(err.to_string());
                 }
 
                 let err = format!("{}", err);
@@ -269,6 +277,69 @@ fn catch<'vm>(
     }
 }
 
+type TraceFrame = record_type! {
+    name => String,
+    line => Option<i64>
+};
+
+type Exception = record_type! {
+    message => String,
+    stacktrace => Vec<TraceFrame>
+};
+
+fn exception(err: vm::Error, trace: vm::stack::Stacktrace) -> Exception {
+    let message = match err {
+        vm::Error::Panic(message, _, _) => message,
+        err => err.to_string(),
+    };
+    let stacktrace = trace
+        .frames
+        .into_iter()
+        .map(|frame| match frame {
+            Some(frame) => record_no_decl! {
+                name => frame.name.declared_name().to_string(),
+                line => frame.line.map(|line| line.number().to_usize() as i64)
+            },
+            None => record_no_decl! {
+                name => "<unknown>".to_string(),
+                line => None
+            },
+        })
+        .collect();
+    record_no_decl! {
+        message => message,
+        stacktrace => stacktrace
+    }
+}
+
+/// IO a -> (Exception -> IO a) -> IO a
+fn recover<'vm>(
+    action: OpaqueValue<&'vm Thread, IO<A>>,
+    mut recover: OwnedFunction<fn(Exception) -> IO<OpaqueValue<RootedThread, A>>>,
+) -> impl Future<Output = IO<OpaqueValue<RootedThread, A>>> + Send {
+    let vm = action.vm().root_thread();
+    let frame_level = vm.context().frame_level();
+    let mut action: OwnedFunction<fn(()) -> OpaqueValue<RootedThread, A>> =
+        Getable::from_value(&vm, action.get_variant());
+
+    async move {
+        match action.call_async(()).await {
+            Ok(value) => IO::Value(value),
+            Err(err) => {
+                let trace = match unwind_to(&vm, frame_level) {
+                    Ok(trace) => trace,
+                    Err(err) => return IO::Exception(err.to_string()),
+                };
+
+                match recover.call_async(exception(err, trace)).await {
+                    Ok(value) => value,
+                    Err(err) => IO::Exception(format!("{}", err)),
+                }
+            }
+        }
+    }
+}
+
 fn throw(msg: String) -> IO<OpaqueValue<RootedThread, A>> {
     IO::Exception(msg)
 }
@@ -280,13 +351,13 @@ fn clear_frames<T>(mut err: Error, stack: StackFrame) -> IO<T> {
     };
     match err {
         // Ignore the stacktrace as we take a more specific range of the stack here
-        Error::VM(vm::Error::Panic(_, ref mut trace)) => *trace = Some(new_trace),
+        Error::VM(vm::Error::Panic(_, ref mut trace, _)) => *trace = Some(new_trace),
         _ => (),
     }
     IO::Exception(err.to_string())
 }
 
-field_decl! { value, typ }
+field_decl! { value, typ, message, stacktrace, name, line }
 
 // Can't create a minimal reproduction for why this reports as being unused...
 #[allow(dead_code)]
@@ -393,6 +464,7 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
             eprint => primitive!(1, std::io::prim::eprint),
             eprintln => primitive!(1, std::io::prim::eprintln),
             catch => primitive!(2, async fn std::io::prim::catch),
+            recover => primitive!(2, async fn std::io::prim::recover),
             throw => primitive!(1, std::io::prim::throw),
             run_expr => primitive!(1, async fn std::io::prim::run_expr),
             load_script => primitive!(2, async fn std::io::prim::load_script),
@@ -0,0 +1,112 @@
+//! Module containing bindings to the `log` library.
+//!
+//! Records are sent to the logger installed by the host application with the name of the gluon
+//! module which logged them as the target.
+
+use crate::vm::{
+    self,
+    api::{WithVM, IO},
+    thread::{Thread, ThreadInternal},
+    ExternModule,
+};
+
+/// The name of the module defining the wrappers around these primitives, which is skipped when
+/// looking for the module that logged a record
+const LOG_MODULE: &str = "std.log";
+
+#[derive(Clone, Copy, Getable, VmType)]
+#[gluon(vm_type = "std.log.types.Level")]
+#[gluon(crate_name = "::vm")]
+enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<Level> for log::Level {
+    fn from(level: Level) -> log::Level {
+        match level {
+            Level::Error => log::Level::Error,
+            Level::Warn => log::Level::Warn,
+            Level::Info => log::Level::Info,
+            Level::Debug => log::Level::Debug,
+            Level::Trace => log::Level::Trace,
+        }
+    }
+}
+
+#[derive(Getable, VmType)]
+#[gluon(vm_type = "std.log.types.Field")]
+#[gluon(crate_name = "::vm")]
+struct Field {
+    key: String,
+    value: String,
+}
+
+/// Returns the name of the innermost gluon module on the stack of `thread`, not counting `std.log`.
+///
+/// This must be called when the logging function is applied, as the frames of the caller are gone
+/// by the time the returned `IO` action runs. A caller which was replaced by a tail call can not
+/// be found either, in which case `gluon` is used as the target.
+fn caller_module(thread: &Thread) -> String {
+    let context = thread.context();
+    let debug_info = context.debug_info();
+    (0..debug_info.stack_info_len())
+        .filter_map(|level| {
+            let info = debug_info.stack_info(level)?;
+            let name = info.source_name();
+            if !name.is_empty() && name != "<unknown>" && name != LOG_MODULE {
+                Some(name.to_string())
+            } else {
+                None
+            }
+        })
+        .next()
+        .unwrap_or_else(|| "gluon".to_string())
+}
+
+fn target(unit: WithVM<()>) -> String {
+    caller_module(unit.vm)
+}
+
+fn enabled(target: &str, level: Level) -> IO<bool> {
+    IO::Value(log::log_enabled!(target: target, level.into()))
+}
+
+fn log(target: &str, level: Level, message: &str, fields: Vec<Field>) -> IO<()> {
+    let level = log::Level::from(level);
+    if level <= log::max_level() {
+        let key_values: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (&field.key[..], &field.value[..]))
+            .collect();
+        log::logger().log(
+            &log::Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", message))
+                .key_values(&key_values)
+                .build(),
+        );
+    }
+    IO::Value(())
+}
+
+mod std {
+    pub mod log {
+        pub use crate::std_lib::log as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> vm::Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            target => primitive!(1, std::log::prim::target),
+            enabled => primitive!(2, std::log::prim::enabled),
+            log => primitive!(4, std::log::prim::log),
+        },
+    )
+}
@@ -0,0 +1,213 @@
+//! Module containing bindings to SQLite through the `rusqlite` library.
+
+extern crate rusqlite;
+extern crate serde_json;
+
+use crate::real_std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use self::rusqlite::types::{Value as SqlValue, ValueRef};
+
+use crate::vm::{self, api::IO, thread::Thread, types::VmInt, ExternModule};
+
+type SharedConnection = Arc<Mutex<Option<rusqlite::Connection>>>;
+
+#[derive(Userdata, Trace, VmType)]
+#[gluon(vm_type = "std.db.sqlite.Connection")]
+#[gluon(crate_name = "::vm")]
+#[gluon_trace(skip)]
+struct Connection(SharedConnection);
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Connection")
+    }
+}
+
+/// A prepared statement. The compiled statement is kept in the statement cache of the connection
+/// and is looked up again each time the statement is run.
+#[derive(Userdata, Trace, VmType)]
+#[gluon(vm_type = "std.db.sqlite.Statement")]
+#[gluon(crate_name = "::vm")]
+#[gluon_trace(skip)]
+struct Statement {
+    connection: SharedConnection,
+    sql: String,
+}
+
+impl fmt::Debug for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Statement({:?})", self.sql)
+    }
+}
+
+#[derive(Getable, VmType)]
+#[gluon(vm_type = "std.db.sqlite.types.Value")]
+#[gluon(crate_name = "::vm")]
+enum Value {
+    Null,
+    Integer(VmInt),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl From<Value> for SqlValue {
+    fn from(value: Value) -> SqlValue {
+        match value {
+            Value::Null => SqlValue::Null,
+            Value::Integer(i) => SqlValue::Integer(i),
+            Value::Real(f) => SqlValue::Real(f),
+            Value::Text(s) => SqlValue::Text(s),
+            Value::Blob(b) => SqlValue::Blob(b),
+        }
+    }
+}
+
+fn to_json(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(s) => String::from_utf8_lossy(s).into(),
+        ValueRef::Blob(b) => b.iter().map(|&b| VmInt::from(b)).collect(),
+    }
+}
+
+fn params(params: Vec<Value>) -> impl rusqlite::Params {
+    rusqlite::params_from_iter(params.into_iter().map(SqlValue::from))
+}
+
+fn with_connection<T>(
+    connection: &SharedConnection,
+    f: impl FnOnce(&rusqlite::Connection) -> rusqlite::Result<T>,
+) -> IO<T> {
+    match *connection.lock().unwrap() {
+        Some(ref connection) => f(connection).into(),
+        None => IO::Exception("the connection has been closed".to_owned()),
+    }
+}
+
+/// Runs `statement` and returns each row as an object mapping the column names to their values
+fn query_rows(
+    statement: &mut rusqlite::Statement,
+    values: Vec<Value>,
+) -> rusqlite::Result<Vec<serde_json::Value>> {
+    let names: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let mut rows = statement.query(params(values))?;
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut object = serde_json::Map::new();
+        for (i, name) in names.iter().enumerate() {
+            object.insert(name.clone(), to_json(row.get_ref(i)?));
+        }
+        result.push(serde_json::Value::Object(object));
+    }
+    Ok(result)
+}
+
+fn open(path: &str) -> IO<Connection> {
+    rusqlite::Connection::open(path)
+        .map(|connection| Connection(Arc::new(Mutex::new(Some(connection)))))
+        .into()
+}
+
+fn close(connection: &Connection) -> IO<()> {
+    match connection.0.lock().unwrap().take() {
+        Some(connection) => connection.close().map_err(|(_, err)| err).into(),
+        None => IO::Value(()),
+    }
+}
+
+fn is_closed(connection: &Connection) -> bool {
+    connection.0.lock().unwrap().is_none()
+}
+
+fn execute(connection: &Connection, sql: &str, values: Vec<Value>) -> IO<VmInt> {
+    with_connection(&connection.0, |connection| {
+        connection
+            .execute(sql, params(values))
+            .map(|changed| changed as VmInt)
+    })
+}
+
+fn execute_batch(connection: &Connection, sql: &str) -> IO<()> {
+    with_connection(&connection.0, |connection| connection.execute_batch(sql))
+}
+
+fn query(connection: &Connection, sql: &str, values: Vec<Value>) -> IO<Vec<serde_json::Value>> {
+    with_connection(&connection.0, |connection| {
+        query_rows(&mut *connection.prepare_cached(sql)?, values)
+    })
+}
+
+fn last_insert_rowid(connection: &Connection) -> IO<VmInt> {
+    with_connection(&connection.0, |connection| {
+        Ok(connection.last_insert_rowid())
+    })
+}
+
+fn prepare(connection: &Connection, sql: String) -> IO<Statement> {
+    with_connection(&connection.0, |connection| {
+        connection.prepare_cached(&sql).map(|_| ())
+    })
+    .into_result()
+    .map(|()| Statement {
+        connection: connection.0.clone(),
+        sql,
+    })
+    .into()
+}
+
+fn execute_prepared(statement: &Statement, values: Vec<Value>) -> IO<VmInt> {
+    with_connection(&statement.connection, |connection| {
+        connection
+            .prepare_cached(&statement.sql)?
+            .execute(params(values))
+            .map(|changed| changed as VmInt)
+    })
+}
+
+fn query_prepared(statement: &Statement, values: Vec<Value>) -> IO<Vec<serde_json::Value>> {
+    with_connection(&statement.connection, |connection| {
+        query_rows(&mut *connection.prepare_cached(&statement.sql)?, values)
+    })
+}
+
+mod std {
+    pub mod db {
+        pub mod sqlite {
+            pub use crate::std_lib::sqlite as prim;
+        }
+    }
+}
+
+pub fn load(vm: &Thread) -> vm::Result<ExternModule> {
+    vm.register_type::<Connection>("std.db.sqlite.Connection", &[])?;
+    vm.register_type::<Statement>("std.db.sqlite.Statement", &[])?;
+
+    ExternModule::new(
+        vm,
+        record! {
+            type Connection => Connection,
+            type Statement => Statement,
+
+            open => primitive!(1, std::db::sqlite::prim::open),
+            close => primitive!(1, std::db::sqlite::prim::close),
+            is_closed => primitive!(1, std::db::sqlite::prim::is_closed),
+            execute => primitive!(3, std::db::sqlite::prim::execute),
+            execute_batch => primitive!(2, std::db::sqlite::prim::execute_batch),
+            query => primitive!(3, std::db::sqlite::prim::query),
+            last_insert_rowid => primitive!(1, std::db::sqlite::prim::last_insert_rowid),
+            prepare => primitive!(2, std::db::sqlite::prim::prepare),
+            execute_prepared => primitive!(2, std::db::sqlite::prim::execute_prepared),
+            query_prepared => primitive!(2, std::db::sqlite::prim::query_prepared)
+        },
+    )
+}
@@ -0,0 +1,29 @@
+//! Module containing bindings to `std::time`.
+
+use crate::real_std::time::{Duration, SystemTime};
+use crate::vm::{self, api::IO, thread::Thread, ExternModule};
+
+fn now(_: ()) -> IO<SystemTime> {
+    IO::Value(SystemTime::now())
+}
+
+fn sleep(duration: Duration) -> IO<()> {
+    crate::real_std::thread::sleep(duration);
+    IO::Value(())
+}
+
+mod std {
+    pub mod time {
+        pub use crate::std_lib::time as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> vm::Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            now => primitive!(1, std::time::prim::now),
+            sleep => primitive!(1, std::time::prim::sleep),
+        },
+    )
+}
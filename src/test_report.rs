@@ -0,0 +1,291 @@
+//! Structured results of running `std.test` tests.
+//!
+//! `ThreadExt::run_tests` evaluates an expression of type `std.test.TestCase`, runs every test in
+//! it and returns a `TestReport` describing the outcome of each test, instead of only the first
+//! failure. The report can be inspected directly or rendered as JUnit XML with
+//! `TestReport::to_junit`.
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::{
+    base::{
+        pos::Line,
+        types::{ArcType, Type},
+    },
+    vm::{
+        api::{generic::A, Getable, Hole, OpaqueValue, OwnedFunction, ValueRef, VmType, IO},
+        thread::{RootedThread, Thread},
+        Variants,
+    },
+    Result, ThreadExt,
+};
+
+/// The outcome of a single test
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestResult {
+    /// The names of the groups containing the test, outermost first
+    pub groups: Vec<String>,
+    pub name: String,
+    pub duration: Duration,
+    pub status: TestStatus,
+}
+
+impl TestResult {
+    /// The name of the test prefixed by the groups containing it, separated by `/`
+    pub fn full_name(&self) -> String {
+        let mut name = String::new();
+        for group in &self.groups {
+            name.push_str(group);
+            name.push('/');
+        }
+        name.push_str(&self.name);
+        name
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TestStatus {
+    Passed,
+    Failed(TestFailure),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestFailure {
+    /// The failed assertions, separated by newlines, or the error which aborted the test
+    pub message: String,
+    /// Where the failing test is defined, if debug information is available
+    pub location: Option<TestLocation>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TestLocation {
+    /// The module the test is defined in
+    pub source_name: String,
+    pub line: Line,
+}
+
+/// The results of every test that was run, in the order they were defined
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TestReport {
+    pub name: String,
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.status == TestStatus::Passed)
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.results.iter().map(|result| result.duration).sum()
+    }
+
+    /// Renders the report as a JUnit XML `testsuite`
+    pub fn to_junit(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        write!(
+            out,
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&self.name),
+            self.results.len(),
+            self.failed(),
+            self.duration().as_secs_f64()
+        )
+        .unwrap();
+        for result in &self.results {
+            let classname = if result.groups.is_empty() {
+                self.name.clone()
+            } else {
+                format!("{}.{}", self.name, result.groups.join("."))
+            };
+            write!(
+                out,
+                "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape_xml(&result.name),
+                escape_xml(&classname),
+                result.duration.as_secs_f64()
+            )
+            .unwrap();
+            match &result.status {
+                TestStatus::Passed => out.push_str("/>\n"),
+                TestStatus::Failed(failure) => {
+                    let first_line = failure.message.lines().next().unwrap_or("");
+                    write!(
+                        out,
+                        ">\n    <failure message=\"{}\">",
+                        escape_xml(first_line)
+                    )
+                    .unwrap();
+                    if let Some(location) = &failure.location {
+                        write!(
+                            out,
+                            "{}:{}\n",
+                            escape_xml(&location.source_name),
+                            location.line.0 + 1
+                        )
+                        .unwrap();
+                    }
+                    out.push_str(&escape_xml(&failure.message));
+                    out.push_str("</failure>\n  </testcase>\n");
+                }
+            }
+        }
+        out.push_str("</testsuite>\n");
+        out
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn test_type(vm: &Thread, name: &str, arg: ArcType) -> ArcType {
+    Type::app(
+        vm.find_type_info(name).unwrap().into_type(),
+        [arg, Type::unit()].iter().cloned().collect(),
+    )
+}
+
+struct TestEffIO;
+
+impl VmType for TestEffIO {
+    type Type = TestEffIO;
+    fn make_type(vm: &Thread) -> ArcType {
+        test_type(vm, "std.test.TestEffIO", A::make_type(vm))
+    }
+}
+
+type TestEff = OpaqueValue<RootedThread, TestEffIO>;
+type TestFn = OwnedFunction<fn(()) -> TestEff>;
+
+enum TestCase {
+    Test(String, TestFn),
+    Group(String, Vec<TestCase>),
+}
+
+impl VmType for TestCase {
+    type Type = TestCase;
+    fn make_type(vm: &Thread) -> ArcType {
+        test_type(vm, "std.test.TestCase", Hole::make_type(vm))
+    }
+}
+
+impl<'vm, 'value> Getable<'vm, 'value> for TestCase {
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        match value.as_ref() {
+            ValueRef::Data(data) => {
+                let name = String::from_value(vm, data.get_variant(0).unwrap());
+                let value = data.get_variant(1).unwrap();
+                match data.tag() {
+                    0 => TestCase::Test(name, TestFn::from_value(vm, value)),
+                    1 => TestCase::Group(name, Vec::from_value(vm, value)),
+                    _ => ice!("ValueRef has a wrong tag: {}", data.tag()),
+                }
+            }
+            _ => ice!("ValueRef is not a TestCase"),
+        }
+    }
+}
+
+fn test_location(test: &TestFn) -> Option<TestLocation> {
+    match test.get_variant().as_ref() {
+        ValueRef::Closure(closure) => {
+            let debug_info = closure.debug_info();
+            Some(TestLocation {
+                source_name: debug_info.source_name.clone(),
+                line: debug_info.source_map.line(0)?,
+            })
+        }
+        _ => None,
+    }
+}
+
+async fn run_test(test: TestFn) -> Result<std::result::Result<(), String>> {
+    // Run each test on its own thread so that a test which fails does not affect the others
+    let child_thread = test.vm().new_thread()?;
+    let mut test = TestFn::from_value(&child_thread, test.get_variant());
+    let test = test.call_async(()).await?;
+    let mut action: OwnedFunction<fn(TestEff) -> IO<std::result::Result<(), String>>> =
+        test.vm().get_global("std.test.run_io_result")?;
+    match action.call_async(test).await? {
+        IO::Value(result) => Ok(result),
+        IO::Exception(err) => Ok(Err(err)),
+    }
+}
+
+fn run_test_case<'a>(
+    test_case: TestCase,
+    groups: &'a mut Vec<String>,
+    results: &'a mut Vec<TestResult>,
+) -> BoxFuture<'a, ()> {
+    async move {
+        match test_case {
+            TestCase::Test(name, test) => {
+                let location = test_location(&test);
+                let start = Instant::now();
+                let result = run_test(test).await;
+                let duration = start.elapsed();
+                let status = match result {
+                    Ok(Ok(())) => TestStatus::Passed,
+                    Ok(Err(message)) => TestStatus::Failed(TestFailure { message, location }),
+                    Err(err) => TestStatus::Failed(TestFailure {
+                        message: err.to_string(),
+                        location,
+                    }),
+                };
+                results.push(TestResult {
+                    groups: groups.clone(),
+                    name,
+                    duration,
+                    status,
+                });
+            }
+            TestCase::Group(name, tests) => {
+                groups.push(name);
+                for test in tests {
+                    run_test_case(test, groups, results).await;
+                }
+                groups.pop();
+            }
+        }
+    }
+    .boxed()
+}
+
+pub(crate) async fn run_tests(vm: &Thread, name: &str, expr_str: &str) -> Result<TestReport> {
+    // `TestCase` can only be referred to once `std.test` is loaded
+    vm.load_file_async("std/test.glu").await?;
+    let (test_case, _) = vm.run_expr_async::<TestCase>(name, expr_str).await?;
+
+    let mut report = TestReport {
+        name: name.to_string(),
+        results: Vec::new(),
+    };
+    run_test_case(test_case, &mut Vec::new(), &mut report.results).await;
+    Ok(report)
+}
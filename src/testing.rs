@@ -0,0 +1,208 @@
+//! Helpers for writing golden tests of the compiler's output.
+//!
+//! Each helper renders some output of the compiler, the inferred types of a module, the
+//! diagnostics reported for it or its formatted source, as a string which can be compared
+//! against a file checked in next to the tests with `assert_snapshot`.
+//!
+//! Snapshots which do not exist yet are written instead of compared. Setting the
+//! `GLUON_UPDATE_SNAPSHOTS` environment variable overwrites every snapshot with the actual
+//! output.
+//!
+//! ```no_run
+//! use gluon::{new_vm, testing};
+//!
+//! # fn main() {
+//! let thread = new_vm();
+//! let source = "let x = 1 in { x }";
+//! let types = testing::module_types(&thread, "example", source).unwrap();
+//! testing::assert_snapshot("tests/golden/example.types", &types);
+//! # }
+//! ```
+
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    base::types::{ArcType, TypeExt},
+    format::Formatter,
+    Result, Thread, ThreadExt,
+};
+
+/// Environment variable which causes `assert_snapshot` to overwrite snapshots instead of comparing
+/// them
+pub const UPDATE_SNAPSHOTS: &str = "GLUON_UPDATE_SNAPSHOTS";
+
+/// Typechecks `source` as the module `name` and renders the types and values it exports, one per
+/// line
+pub fn module_types(thread: &Thread, name: &str, source: &str) -> Result<String> {
+    let (_, typ) = thread.typecheck_str(name, source, None)?;
+    Ok(render_types(&typ))
+}
+
+fn render_types(typ: &ArcType) -> String {
+    let mut output = String::new();
+    for field in typ.type_field_iter() {
+        let alias = &field.typ;
+        output.push_str("type ");
+        output.push_str(field.name.declared_name());
+        for param in alias.params() {
+            output.push(' ');
+            output.push_str(param.id.declared_name());
+        }
+        let body = alias.unresolved_type().to_string();
+        if body.contains('\n') {
+            output.push_str(" =\n");
+            for line in body.lines() {
+                output.push_str("    ");
+                output.push_str(line);
+                output.push('\n');
+            }
+        } else {
+            output.push_str(&format!(" = {}\n", body));
+        }
+    }
+    for field in typ.row_iter() {
+        output.push_str(&format!("{} : {}\n", field.name.declared_name(), field.typ));
+    }
+    if output.is_empty() {
+        output = format!("{}\n", typ);
+    }
+    output
+}
+
+/// Typechecks `source` as the module `name` and renders the diagnostics reported for it, or an
+/// empty string if it typechecks without errors
+pub fn diagnostics(thread: &Thread, name: &str, source: &str) -> String {
+    match thread.typecheck_str(name, source, None) {
+        Ok(_) => String::new(),
+        Err(err) => err
+            .emit_string()
+            .unwrap_or_else(|err| format!("Unable to emit diagnostics: {}\n", err)),
+    }
+}
+
+/// Formats `source` with the default `Formatter`
+pub fn formatted(thread: &Thread, name: &str, source: &str) -> Result<String> {
+    thread.format_expr(&mut Formatter::default(), name, source)
+}
+
+/// Compares `actual` against the snapshot stored at `path`, panicking with a line diff if they
+/// differ.
+pub fn assert_snapshot(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+    if let Err(mismatch) = check_snapshot(path, actual, env::var_os(UPDATE_SNAPSHOTS).is_some()) {
+        panic!("{}", mismatch);
+    }
+}
+
+/// A snapshot which does not match the actual output
+#[derive(Debug)]
+pub struct SnapshotMismatch {
+    path: PathBuf,
+    expected: String,
+    actual: String,
+}
+
+impl fmt::Display for SnapshotMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Snapshot `{}` does not match (set {} to update it)",
+            self.path.display(),
+            UPDATE_SNAPSHOTS
+        )?;
+        for line in diff_lines(&self.expected, &self.actual) {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SnapshotMismatch {}
+
+/// Compares `actual` against the snapshot stored at `path`. The snapshot is written instead if
+/// it does not exist or if `update` is true.
+pub fn check_snapshot(
+    path: &Path,
+    actual: &str,
+    update: bool,
+) -> std::result::Result<(), SnapshotMismatch> {
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) if !update => expected,
+        _ => {
+            write_snapshot(path, actual);
+            return Ok(());
+        }
+    };
+    // Checking out the snapshots on windows may have converted the line endings
+    if expected.replace("\r\n", "\n") == actual {
+        Ok(())
+    } else {
+        Err(SnapshotMismatch {
+            path: path.to_owned(),
+            expected,
+            actual: actual.to_owned(),
+        })
+    }
+}
+
+fn write_snapshot(path: &Path, actual: &str) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .unwrap_or_else(|err| panic!("Unable to create `{}`: {}", parent.display(), err));
+    }
+    fs::write(path, actual)
+        .unwrap_or_else(|err| panic!("Unable to write `{}`: {}", path.display(), err));
+}
+
+/// Returns the lines of `expected` and `actual` prefixed by `-` if they are only in `expected`,
+/// `+` if they are only in `actual` and ` ` if they are in both
+fn diff_lines(expected: &str, actual: &str) -> Vec<String> {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+
+    // Length of the longest common subsequence of `expected[i..]` and `actual[j..]`
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!(" {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(format!("-{}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_changed_line() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nd\nc\n"),
+            vec![" a", "-b", "+d", " c"]
+        );
+    }
+}
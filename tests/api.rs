@@ -6,19 +6,27 @@ extern crate gluon_vm;
 #[macro_use]
 extern crate gluon_codegen;
 
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use futures::prelude::*;
 
 use gluon::{
     base::types::{Alias, ArcType, Type},
-    import::{add_extern_module, add_extern_module_with_deps, Import},
-    query::Compilation,
+    import::{self, add_extern_module, add_extern_module_with_deps, Import},
+    progress::{Phase, ProgressEvent},
+    query::{Compilation, CompilationBase},
+    test_report::TestStatus,
     vm::{
         api::{
             de::De,
             scoped::{Ref, RefMut},
-            FunctionRef, FutureResult, Hole, OpaqueValue, OwnedFunction, RuntimeResult, VmType, IO,
+            FunctionRef, FutureResult, Getable, Hole, OpaqueValue, OwnedFunction, RuntimeResult,
+            ValueRef, VmType, WithVM, IO,
         },
         gc,
         thread::{RootedThread, Thread},
@@ -403,6 +411,138 @@ fn return_btreemap() {
     );
 }
 
+#[test]
+fn hash_map_round_trip() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+
+    add_extern_module_with_deps(
+        &vm,
+        "test",
+        |vm| {
+            ExternModule::new(
+                vm,
+                primitive!(1, "test", |()| {
+                    vec![("a".to_string(), 1), ("b".to_string(), 2)]
+                        .into_iter()
+                        .collect::<HashMap<_, VmInt>>()
+                }),
+            )
+        },
+        vec!["std.map".into()],
+    );
+
+    vm.run_expr::<()>("", "let _ = import! test in ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (result, _) = vm
+        .run_expr::<HashMap<String, VmInt>>(
+            "",
+            r#"
+let map @ { ? } = import! std.map
+let { (<>) } = import! std.semigroup
+(import! test) () <> map.singleton "c" 3
+"#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        result,
+        vec![
+            ("a".to_string(), 1),
+            ("b".to_string(), 2),
+            ("c".to_string(), 3)
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>()
+    );
+}
+
+#[test]
+fn borrowed_strings_in_containers() {
+    let _ = ::env_logger::try_init();
+
+    fn describe(name: Option<&str>) -> String {
+        format!("name: {}", name.unwrap_or("<none>"))
+    }
+    fn shout(s: Cow<str>) -> Cow<'static, str> {
+        Cow::Owned(s.to_uppercase())
+    }
+    fn either(result: Result<&str, &str>) -> Cow<'static, str> {
+        match result {
+            Ok(s) => Cow::Owned(s.to_string()),
+            Err(_) => Cow::Borrowed("error"),
+        }
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "test", |vm| {
+        ExternModule::new(
+            vm,
+            record! {
+                describe => primitive!(1, describe),
+                shout => primitive!(1, shout),
+                either => primitive!(1, either),
+            },
+        )
+    });
+
+    let (result, _) = vm
+        .run_expr::<(String, String, String, String, String)>(
+            "",
+            r#"
+let { Result } = import! std.result
+let { describe, shout, either } = import! test
+(describe (Some "a"), describe None, shout "abc", either (Ok "ok"), either (Err "err"))
+"#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        result,
+        (
+            "name: a".to_string(),
+            "name: <none>".to_string(),
+            "ABC".to_string(),
+            "ok".to_string(),
+            "error".to_string()
+        )
+    );
+}
+
+#[test]
+fn large_tuples() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (result, _) = vm
+        .run_expr::<(
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+            VmInt,
+        )>(
+            "",
+            "(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16)",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p) = result;
+    assert_eq!(
+        [a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p],
+        [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]
+    );
+}
+
 #[test]
 fn get_value_boxed_or_unboxed() {
     let _ = ::env_logger::try_init();
@@ -522,7 +662,11 @@ fn scoped_reference_out_of_scope() {
 
     let result = vm.run_expr::<VmInt>("<top>", expr);
     match result {
-        Err(gluon::Error::VM(Error::Panic(ref m, _))) if m == "Scoped pointer is invalidated" => (),
+        Err(gluon::Error::VM(Error::Panic(ref m, _, _)))
+            if m == "Scoped pointer is invalidated" =>
+        {
+            ()
+        }
         Err(err) => panic!("Wrong error: {:#?}", err),
         Ok(_) => panic!("Unexpected success"),
     }
@@ -777,3 +921,532 @@ fn clone_userdata() {
 
     assert_eq!(*result, Test(123));
 }
+
+#[test]
+fn clone_value_to_parent_thread() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let worker = vm.new_thread().unwrap();
+    let (value, _) = worker
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(
+            "worker",
+            r#"
+let y = 10
+{ names = ["a", "b"], add = \x -> x #Int+ y }
+"#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let value = worker
+        .clone_value_to(&vm, &value.into_inner())
+        .unwrap_or_else(|err| panic!("{}", err));
+    drop(worker);
+
+    let record = match value.get_variant().as_ref() {
+        ValueRef::Data(data) => data,
+        _ => panic!("Expected a record"),
+    };
+    let names = Vec::<String>::from_value(&vm, record.lookup_field(&vm, "names").unwrap());
+    assert_eq!(names, ["a", "b"]);
+    let mut add = FunctionRef::<fn(VmInt) -> VmInt>::from_value(
+        &vm,
+        record.lookup_field(&vm, "add").unwrap(),
+    );
+    assert_eq!(add.call(1), Ok(11));
+}
+
+#[test]
+fn record_field_names_are_in_declaration_order() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (value, _) = vm
+        .run_expr::<OpaqueValue<RootedThread, Hole>>(
+            "record",
+            "{ zeta = 1, alpha = 2, mu = 3, beta = 4, omega = 5 }",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let record = match value.get_variant().as_ref() {
+        ValueRef::Data(data) => data,
+        _ => panic!("Expected a record"),
+    };
+    let names: Vec<_> = record.field_names().map(|name| name.to_string()).collect();
+    assert_eq!(names, ["zeta", "alpha", "mu", "beta", "omega"]);
+}
+
+#[test]
+fn duration_and_system_time() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let (mut add, _) = vm
+        .run_expr::<FunctionRef<fn(Duration) -> Duration>>(
+            "duration",
+            "let time = import! std.time\n\\d -> time.add d (time.from_millis 1500)",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        add.call(Duration::from_millis(700)),
+        Ok(Duration::from_millis(2200))
+    );
+
+    let (mut id, _) = vm
+        .run_expr::<FunctionRef<fn(SystemTime) -> SystemTime>>(
+            "system_time",
+            "let time = import! std.time\nlet id t : time.SystemTime -> time.SystemTime = t\nid",
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    let times = [
+        UNIX_EPOCH + Duration::new(1_600_000_000, 123),
+        UNIX_EPOCH - Duration::new(10, 250_000_000),
+        UNIX_EPOCH - Duration::from_secs(10),
+    ];
+    for &time in &times {
+        assert_eq!(id.call(time), Ok(time));
+    }
+}
+
+#[test]
+fn clone_value_to_other_vm() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let other = make_vm();
+    let (value, _) = vm
+        .run_expr::<OpaqueValue<RootedThread, Hole>>("value", "1")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let value = value.into_inner();
+
+    assert!(vm.clone_value_to(&other, &value).is_err());
+    assert!(other.clone_value_to(&vm, &value).is_err());
+}
+
+#[test]
+fn reentrant_call_from_primitive() {
+    let _ = ::env_logger::try_init();
+
+    fn call_or(mut f: FunctionRef<fn(VmInt) -> VmInt>, x: VmInt, default: VmInt) -> VmInt {
+        let vm = f.vm().root_thread();
+        vm.with_reentrant_call(|_| f.call(x)).unwrap_or(default)
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "call_or", |thread| {
+        ExternModule::new(thread, primitive!(3, call_or))
+    });
+
+    let expr = r#"
+        let call_or = import! call_or
+        let f x = if x > 2 then error "too large" else x * 10
+        let nested x = call_or f x 3
+        call_or f 1 0 + call_or f 5 7 + call_or nested 9 4 + call_or nested 2 0
+    "#;
+    let (result, _) = vm
+        .run_expr::<VmInt>("<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 10 + 7 + 3 + 20);
+}
+
+#[test]
+fn reentrant_call_which_panics_resets_the_stack() {
+    let _ = ::env_logger::try_init();
+
+    fn call_catch(mut f: FunctionRef<fn(VmInt) -> VmInt>, x: VmInt) -> VmInt {
+        let vm = f.vm().root_thread();
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            vm.with_reentrant_call(|_| -> Result<VmInt, _> {
+                let _ = f.call(x);
+                panic!("Panic inside the callback")
+            })
+        }))
+        .map(|result| result.unwrap())
+        .unwrap_or(-1)
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "call_catch", |thread| {
+        ExternModule::new(thread, primitive!(2, call_catch))
+    });
+
+    let expr = r#"
+        let call_catch = import! call_catch
+        let f x = x * 10
+        let a = 1
+        call_catch f 1 + a + call_catch f 2
+    "#;
+    let (result, _) = vm
+        .run_expr::<VmInt>("<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, -1);
+}
+
+#[test]
+fn reentrant_call_while_stack_is_borrowed() {
+    let _ = ::env_logger::try_init();
+
+    fn call_locked(f: WithVM<FunctionRef<fn(VmInt) -> VmInt>>) -> bool {
+        let WithVM { vm, value: mut f } = f;
+        let _context = vm.current_context();
+        vm.with_reentrant_call(|_| f.call(1)).is_err()
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "call_locked", |thread| {
+        ExternModule::new(thread, primitive!(1, call_locked))
+    });
+
+    let (result, _) = vm
+        .run_expr::<bool>("<top>", "(import! call_locked) (\\x -> x)")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(result);
+}
+
+#[test]
+fn emit_core_listing() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let text = r#"
+let add_one x = x + 1
+add_one 2
+"#;
+    let listing = vm
+        .compile_listing("test", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(listing.typ, Type::int());
+    assert!(
+        listing.functions.len() >= 2,
+        "Expected add_one to be compiled into its own function: {}",
+        listing
+    );
+    assert!(
+        listing.functions.iter().any(|function| function
+            .instructions
+            .iter()
+            .any(|instruction| instruction.source.as_deref() == Some("let add_one x = x + 1"))),
+        "Expected instructions annotated with their source: {}",
+        listing
+    );
+
+    let text = vm.emit_core("test2", text).unwrap();
+    assert!(text.contains("// core"), "{}", text);
+    assert!(text.contains("add_one"), "{}", text);
+}
+
+#[test]
+fn swap_global_function() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    load_script(
+        &vm,
+        "counter",
+        r#"
+        let array = import! std.array
+        let offset = array.len [1, 2, 3]
+        let step x : Int -> Int = x #Int+ offset
+        { offset, step }
+        "#,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    load_script(
+        &vm,
+        "twice",
+        r#"
+        let { step } = import! counter
+        \x -> step (step x)
+        "#,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut step: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("counter.step").unwrap();
+    let mut twice: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("twice").unwrap();
+    assert_eq!(step.call(1), Ok(4));
+    assert_eq!(twice.call(1), Ok(7));
+
+    // `offset` is captured under the same name so the replacement keeps the old value
+    let (new_step, _) = vm
+        .run_expr::<OwnedFunction<fn(VmInt) -> VmInt>>(
+            "new_step",
+            r#"
+            let array = import! std.array
+            let offset = array.len []
+            let step x : Int -> Int = x #Int* 10 #Int+ offset
+            step
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    vm.swap_global("counter.step", new_step)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(step.call(1), Ok(13));
+    assert_eq!(twice.call(1), Ok(133));
+
+    let (wrong_type, _) = vm
+        .run_expr::<OwnedFunction<fn(String) -> String>>("wrong_type", r#"\x -> x"#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm.swap_global("counter.step", wrong_type).is_err());
+}
+
+#[test]
+fn load_std_from_std_root() {
+    let _ = ::env_logger::try_init();
+
+    let std_root = tempfile::tempdir().unwrap();
+    std::fs::write(std_root.path().join("VERSION"), gluon::import::STD_VERSION).unwrap();
+    std::fs::write(std_root.path().join("answer.glu"), "42").unwrap();
+
+    let vm = make_vm();
+    vm.get_database_mut()
+        .implicit_prelude(false)
+        .std_root(Some(std_root.path().to_owned()));
+
+    let (value, _) = vm
+        .run_expr::<VmInt>("test", "import! std.answer")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value, 42);
+}
+
+#[test]
+fn std_root_with_incompatible_version() {
+    let _ = ::env_logger::try_init();
+
+    let std_root = tempfile::tempdir().unwrap();
+    std::fs::write(std_root.path().join("VERSION"), "999.0.0").unwrap();
+    std::fs::write(std_root.path().join("answer.glu"), "42").unwrap();
+
+    let vm = make_vm();
+    vm.get_database_mut()
+        .implicit_prelude(false)
+        .std_root(Some(std_root.path().to_owned()));
+
+    let err = vm
+        .run_expr::<VmInt>("test", "import! std.answer")
+        .unwrap_err();
+    assert!(err.to_string().contains("999.0.0"), "{}", err);
+}
+
+#[test]
+fn import_not_found_lists_candidates_and_near_misses() {
+    let _ = ::env_logger::try_init();
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("Nested")).unwrap();
+    std::fs::write(dir.path().join("Nested").join("Answer.glu"), "42").unwrap();
+    std::fs::write(dir.path().join("Nested").join("answer"), "42").unwrap();
+
+    // Keep the default import paths so that the standard library can still be found when it is
+    // loaded from disk
+    let vm = gluon::new_vm();
+    vm.get_macros()
+        .get("import")
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro")
+        .add_path(dir.path());
+    vm.get_database_mut().implicit_prelude(false);
+
+    let err = vm
+        .run_expr::<VmInt>("test", "import! nested.answer")
+        .unwrap_err();
+    let not_found =
+        match err {
+            gluon::Error::Macro(errors) => errors.into_errors().into_iter().find_map(|err| {
+                match *err.value.downcast::<import::Error>().ok()? {
+                    import::Error::NotFound(not_found) => Some(not_found),
+                    _ => None,
+                }
+            }),
+            _ => None,
+        }
+        .unwrap_or_else(|| panic!("Expected a `NotFound` error"));
+
+    let in_dir = |paths: Vec<std::path::PathBuf>| -> Vec<_> {
+        paths
+            .into_iter()
+            .filter(|path| path.starts_with(dir.path()))
+            .collect()
+    };
+
+    assert_eq!(not_found.name, "nested.answer");
+    assert_eq!(not_found.search_roots.last(), Some(&dir.path().to_owned()));
+    assert_eq!(
+        in_dir(not_found.candidates),
+        vec![dir.path().join("nested/answer.glu")]
+    );
+    assert_eq!(
+        in_dir(not_found.near_misses),
+        vec![
+            dir.path().join("Nested").join("Answer.glu"),
+            dir.path().join("Nested").join("answer"),
+        ]
+    );
+}
+
+#[test]
+fn unused_imports_and_bindings_are_warned_about() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let text = r#"
+let list = import! std.list
+let { filter, sort } = import! std.list
+let f x y = filter x
+f
+"#;
+    load_script(&vm, "test", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let warnings = vm
+        .get_database()
+        .peek_warnings("test")
+        .expect("Module was not typechecked")
+        .iter()
+        .map(|warning| warning.value.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        [
+            "Unused import of `std.list`",
+            "Unused binding `sort`",
+            "Unused binding `y`",
+        ]
+    );
+}
+
+#[test]
+fn progress_callback_reports_imported_modules() {
+    let _ = ::env_logger::try_init();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+
+    let vm = make_vm();
+    {
+        let events = events.clone();
+        vm.get_database_mut()
+            .implicit_prelude(false)
+            .progress_callback(move |progress| {
+                events.lock().unwrap().push((
+                    progress.module.to_string(),
+                    progress.event,
+                    progress.finished,
+                    progress.total,
+                ))
+            });
+    }
+
+    load_script(&vm, "test", "let _ = import! std.int in ()")
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+        events[0],
+        ("test".to_string(), ProgressEvent::ModuleStarted, 0, 1)
+    );
+    assert!(
+        events
+            .iter()
+            .any(|(module, event, _, _)| module == "std.int"
+                && *event == ProgressEvent::Phase(Phase::Execute)),
+        "{:#?}",
+        events
+    );
+    let (module, event, finished, total) = events.last().unwrap();
+    assert_eq!(module, "test");
+    assert_eq!(*event, ProgressEvent::ModuleFinished);
+    assert_eq!(finished, total);
+}
+
+#[test]
+fn changing_a_module_only_recompiles_its_reverse_dependencies() {
+    let _ = ::env_logger::try_init();
+
+    let compiled = Arc::new(Mutex::new(BTreeSet::new()));
+
+    let vm = make_vm();
+    {
+        let compiled = compiled.clone();
+        vm.get_database_mut()
+            .implicit_prelude(false)
+            .progress_callback(move |progress| {
+                if let ProgressEvent::Phase(_) = progress.event {
+                    compiled.lock().unwrap().insert(progress.module.to_string());
+                }
+            });
+    }
+
+    let b = "let a = import! test.a in { a }";
+    let c = "let _ = import! std.int in 2";
+    load_script(&vm, "test.a", "1").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "test.b", b).unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "test.c", c).unwrap_or_else(|err| panic!("{}", err));
+    compiled.lock().unwrap().clear();
+
+    load_script(&vm, "test.a", "3").unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "test.b", b).unwrap_or_else(|err| panic!("{}", err));
+    load_script(&vm, "test.c", c).unwrap_or_else(|err| panic!("{}", err));
+
+    let compiled = compiled.lock().unwrap().iter().cloned().collect::<Vec<_>>();
+    assert_eq!(compiled, ["test.a", "test.b"]);
+
+    let (a, _) = vm
+        .run_expr::<i32>("test", "let { a } = import! test.b in a")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(a, 3);
+}
+
+#[test]
+fn run_tests_reports_every_test() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let text = r#"
+let { assert_eq, test, group, ? } = import! std.test
+let { ? } = import! std.effect
+
+group "arithmetic" [
+    test "add" (\_ -> assert_eq (1 + 1) 2),
+    test "sub" (\_ -> assert_eq (3 - 1) 1),
+]
+"#;
+    let report = vm
+        .run_tests("test_report", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let statuses = report
+        .results
+        .iter()
+        .map(|result| (result.full_name(), &result.status))
+        .collect::<Vec<_>>();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(
+        statuses[0],
+        ("arithmetic/add".to_string(), &TestStatus::Passed)
+    );
+    assert_eq!(statuses[1].0, "arithmetic/sub");
+    match statuses[1].1 {
+        TestStatus::Failed(failure) => {
+            assert_eq!(failure.message, "Assertion failed: 2 != 1");
+            let location = failure
+                .location
+                .as_ref()
+                .expect("Location of the failing test");
+            assert_eq!(location.source_name, "test_report");
+            assert_eq!(location.line.0 + 1, 7);
+        }
+        TestStatus::Passed => panic!("Expected `sub` to fail"),
+    }
+    assert_eq!((report.passed(), report.failed()), (1, 1));
+
+    let junit = report.to_junit();
+    assert!(
+        junit.contains(r#"<testsuite name="test_report" tests="2" failures="1""#),
+        "{}",
+        junit
+    );
+    assert!(
+        junit.contains(r#"<failure message="Assertion failed: 2 != 1">"#),
+        "{}",
+        junit
+    );
+}
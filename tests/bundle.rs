@@ -0,0 +1,98 @@
+use gluon::{bundle::Bundler, new_vm, query::CompilationBase, RootedThread, ThreadExt};
+
+fn add_module(vm: &RootedThread, name: &str, source: &str) {
+    vm.get_database_mut().add_module(name.into(), source);
+}
+
+#[test]
+fn bundle_runs_like_the_original_modules() {
+    let _ = env_logger::try_init();
+
+    let vm = new_vm();
+    add_module(
+        &vm,
+        "bundle_app.util",
+        r#"
+let text = r"multi
+line"
+let double x = x * 2
+{ text, double }
+"#,
+    );
+    add_module(
+        &vm,
+        "bundle_app.main",
+        r#"
+let util = import! bundle_app.util
+import! { double } from bundle_app.util
+import! std.int as int
+double (int.abs (-21))
+"#,
+    );
+
+    let bundle = Bundler::new().bundle(&vm, "bundle_app.main").unwrap();
+    assert!(
+        !bundle.source.contains("import! bundle_app"),
+        "{}",
+        bundle.source
+    );
+    assert!(
+        bundle.source.contains("import! std.int"),
+        "{}",
+        bundle.source
+    );
+
+    let vm = new_vm();
+    let (result, _) = vm.run_expr::<i32>("bundle", &bundle.source).unwrap();
+    assert_eq!(result, 42);
+
+    // The multiline string must not have been reindented
+    let (text, _) = vm
+        .run_expr::<String>(
+            "bundle_text",
+            &bundle.source.replace("double (int.abs (-21))", "util.text"),
+        )
+        .unwrap();
+    assert_eq!(text, "multi\nline");
+}
+
+#[test]
+fn source_map_points_to_original_module() {
+    let _ = env_logger::try_init();
+
+    let vm = new_vm();
+    let util = "let value = 1\n{ value }\n";
+    let main = "let util = import! bundle_map.util\nutil.value\n";
+    add_module(&vm, "bundle_map.util", util);
+    add_module(&vm, "bundle_map.main", main);
+
+    let bundle = Bundler::new().bundle(&vm, "bundle_map.main").unwrap();
+    let source_map = &bundle.source_map;
+
+    let offset = bundle.source.find("value = 1").unwrap();
+    assert_eq!(
+        source_map.original_position(offset),
+        Some(("bundle_map.util", util.find("value = 1").unwrap()))
+    );
+
+    let offset = bundle.source.rfind("util.value").unwrap();
+    assert_eq!(
+        source_map.original_position(offset),
+        Some(("bundle_map.main", main.find("util.value").unwrap()))
+    );
+
+    // Generated text has no original position
+    assert_eq!(source_map.original_position(0), None);
+
+    let (result, _) = vm.run_expr::<i32>("bundle", &bundle.source).unwrap();
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn cyclic_imports_are_an_error() {
+    let vm = new_vm();
+    add_module(&vm, "bundle_cycle.a", "import! bundle_cycle.b");
+    add_module(&vm, "bundle_cycle.b", "import! bundle_cycle.a");
+
+    assert!(Bundler::new().bundle(&vm, "bundle_cycle.a").is_err());
+}
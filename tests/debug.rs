@@ -14,9 +14,15 @@ use gluon::{
     base::{
         pos::Line,
         types::{ArcType, Type, TypeExt},
+        DebugLevel,
     },
+    debugger::FrameScope,
+    query::CompilationBase,
     vm::{
+        api::Getable,
         compiler::UpvarInfo,
+        core_dump::CoreDump,
+        coverage::Coverage,
         thread::{HookFlags, ThreadInternal},
     },
     RootedThread, ThreadExt,
@@ -483,3 +489,183 @@ fn implicit_prelude_variable_names() {
         _ => panic!("{:#?}", f[0]),
     }
 }
+
+#[test]
+fn line_coverage() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    thread.get_database_mut().implicit_prelude(false);
+
+    let coverage = Arc::new(Coverage::new());
+    thread.context().set_coverage(Some(coverage.clone()));
+
+    let source = r#"
+let f x = x #Int+ 1
+let unused x =
+    x #Int* 2
+let g x = f x
+g 1
+"#;
+    thread.run_expr::<i32>("test", source).unwrap();
+
+    assert_eq!(
+        coverage.line_counts("test"),
+        Some(vec![(2, 2), (3, 1), (4, 0), (5, 2), (6, 1)])
+    );
+
+    let lcov = coverage.to_lcov();
+    assert!(
+        lcov.contains(
+            "SF:test\nDA:2,2\nDA:3,1\nDA:4,0\nDA:5,2\nDA:6,1\nLH:4\nLF:5\nend_of_record\n"
+        ),
+        "{}",
+        lcov
+    );
+}
+
+#[test]
+fn executable_lines() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    thread.get_database_mut().implicit_prelude(false);
+
+    let source = r#"
+let f x = x #Int+ 1
+// Not executable
+let unused x =
+    x #Int* 2
+let g x = f x
+g 1
+"#;
+    thread.load_script("test", source).unwrap();
+
+    let lines = thread
+        .get_database()
+        .peek_executable_lines("test")
+        .expect("Executable lines");
+    assert_eq!(
+        lines.iter().map(|line| line.0 + 1).collect::<Vec<_>>(),
+        vec![2, 4, 5, 6, 7]
+    );
+    assert!(!lines.contains(Line(2)));
+    assert_eq!(lines.next_executable(Line(2)), Some(Line(3)));
+    assert_eq!(lines.next_executable(Line(7)), None);
+}
+
+#[test]
+fn core_dump_on_panic() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    let dumps = Arc::new(Mutex::new(Vec::new()));
+    {
+        let dumps = dumps.clone();
+        thread.global_env().set_debug_level(DebugLevel::High);
+        thread
+            .global_env()
+            .set_core_dump_handler(Some(Box::new(move |dump: &CoreDump| {
+                dumps.lock().unwrap().push(dump.clone())
+            })));
+    }
+
+    let expr = r#"
+let f x =
+    let y = x #Int+ 1
+    let z = if y #Int== 3 then error "y was 3" else y
+    z #Int* 2
+f 1 #Int+ f 2
+"#;
+    let result = thread.run_expr::<i32>("core_dump", expr);
+    assert!(result.is_err());
+
+    let dumps = dumps.lock().unwrap();
+    assert_eq!(dumps.len(), 1);
+    let dump = &dumps[0];
+    assert_eq!(dump.message, "y was 3");
+
+    let frame = &dump.frames[1];
+    assert_eq!(frame.function.as_deref(), Some("f"));
+    assert_eq!(frame.line, Some(4));
+    assert_eq!(
+        frame.source.as_deref(),
+        Some(r#"let z = if y #Int== 3 then error "y was 3" else y"#)
+    );
+    assert_eq!(
+        frame
+            .locals
+            .iter()
+            .map(|local| (&local.name[..], &local.typ[..], &local.value[..]))
+            .collect::<Vec<_>>(),
+        [("x", "Int", "2"), ("y", "Int", "3")]
+    );
+
+    assert_eq!(dump.frames[2].function.as_deref(), Some("core_dump"));
+    assert!(dump.globals.iter().any(|global| global == "std.prelude"));
+}
+
+#[test]
+fn eval_in_frame() {
+    let _ = env_logger::try_init();
+
+    let thread = new_vm();
+    let scope = Arc::new(Mutex::new(None));
+    {
+        let scope = scope.clone();
+        let mut context = thread.context();
+        context.set_hook(Some(Box::new(move |thread, debug_info| {
+            let stack_info = debug_info.stack_info(0).unwrap();
+            if stack_info.source_name() == "test" && stack_info.line() == Some(Line::from(4)) {
+                *scope.lock().unwrap() = Some(FrameScope::capture(thread, &stack_info));
+            }
+            Poll::Ready(Ok(()))
+        })));
+        context.set_hook_mask(HookFlags::LINE_FLAG);
+    }
+    let expr = r#"
+    let x = 1
+    let f y =
+        let z = "abc"
+        x #Int+ y
+    f 2
+    "#;
+
+    thread.get_database_mut().implicit_prelude(false);
+
+    thread
+        .run_expr::<i32>("test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let scope = scope
+        .lock()
+        .unwrap()
+        .take()
+        .expect("Frame was not captured");
+    assert_eq!(
+        scope
+            .variables()
+            .iter()
+            .map(|variable| (&variable.name[..], variable.typ.clone()))
+            .collect::<Vec<_>>(),
+        [
+            ("x", Type::int()),
+            ("y", Type::int()),
+            ("z", Type::string())
+        ]
+    );
+
+    let (value, typ) = thread
+        .eval_in_frame(&scope, "eval", "x #Int+ y #Int* 10")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(typ, Type::int());
+    assert_eq!(i32::from_value(&thread, value.get_variant()), 21);
+
+    let (_, typ) = thread
+        .eval_in_frame(&scope, "eval", "z")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(typ, Type::string());
+
+    let result = thread.eval_in_frame(&scope, "eval", "z #Int+ 1");
+    assert!(result.is_err());
+}
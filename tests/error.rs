@@ -1,8 +1,15 @@
 use expect_test::expect;
 
 use gluon::{
-    base, check::typecheck::TypeError, compiler_pipeline::*, parser, vm::Error as VMError, Error,
-    ThreadExt,
+    base::{
+        self,
+        diagnostic::{self, Diagnostic, Position, Severity},
+    },
+    check::typecheck::TypeError,
+    compiler_pipeline::*,
+    parser,
+    vm::{api::Getable, Error as VMError},
+    Error, ThreadExt,
 };
 
 mod support;
@@ -48,6 +55,23 @@ fn dont_miss_errors_in_file_if_import_has_errors() {
     }
 }
 
+#[test]
+fn import_binding_without_body() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let text = r#"
+        import! std.list as list
+    "#;
+    let error = vm.load_script("test", text).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("must be followed by the expression"),
+        "{}",
+        error
+    );
+}
+
 #[test]
 fn panics_contain_stacktrace() {
     let _ = ::env_logger::try_init();
@@ -55,11 +79,51 @@ fn panics_contain_stacktrace() {
     let vm = support::make_vm();
     let result = vm.run_expr::<i32>("test", "error \"some error\"");
     match result {
-        Err(Error::VM(VMError::Panic(_, Some(_)))) => (),
+        Err(Error::VM(VMError::Panic(_, Some(_), _))) => (),
         _ => panic!("Expected error with stacktrace {:?}", result),
     }
 }
 
+#[test]
+fn panic_payload_is_the_message() {
+    let _ = ::env_logger::try_init();
+
+    let vm = support::make_vm();
+    let result = vm.run_expr::<i32>("test", "error \"some error\"");
+    match result {
+        Err(Error::VM(VMError::Panic(message, _, Some(payload)))) => {
+            assert_eq!(message, "some error");
+            assert_eq!(
+                String::from_value(&vm, payload.value().get_variant()),
+                "some error"
+            );
+        }
+        _ => panic!("Expected error with payload {:?}", result),
+    }
+}
+
+#[test]
+fn panic_with_value() {
+    let _ = ::env_logger::try_init();
+
+    let vm = support::make_vm();
+    let text = r#"
+        let f x : Int -> Int = if x > 2 then error_with { code = x, reason = "too large" } else x
+        f 3
+    "#;
+    let result = vm.run_expr::<i32>("test", text);
+    match result {
+        Err(Error::VM(VMError::Panic(message, Some(_), Some(payload)))) => {
+            assert_eq!(message, r#"{ 3, "too large" }"#);
+            assert_eq!(
+                <(i32, String)>::from_value(&vm, payload.value().get_variant()),
+                (3, "too large".to_string())
+            );
+        }
+        _ => panic!("Expected error with payload {:?}", result),
+    }
+}
+
 #[test]
 fn undefined_infix() {
     let _ = ::env_logger::try_init();
@@ -138,3 +202,204 @@ fn module_imports_provide_a_type_despite_internal_errors() {
     "#]]
     .assert_eq(&result.unwrap_err().to_string());
 }
+
+#[test]
+fn errors_in_the_implicit_prelude_point_to_the_module() {
+    let _ = ::env_logger::try_init();
+    let vm = ::gluon::VmBuilder::new().import_paths(Some(vec![])).build();
+    vm.get_database_mut().set_use_standard_lib(false);
+    let text = r#"
+        1 + 2
+        "#;
+    let error = vm.load_script("test", text).unwrap_err().to_string();
+    expect![[r#"
+        error: Could not find module 'std.prelude'. Searched .
+          ┌─ test:2:9
+          │
+        2 │         1 + 2
+          │         ^
+          │
+          = in code generated by the implicit prelude
+    "#]]
+    .assert_eq(&error[..error.find("\n\n").unwrap() + 1]);
+    // The source of the prelude itself is never shown
+    assert!(!error.contains("import!"), "{}", error);
+}
+
+#[test]
+fn errors_as_structured_diagnostics() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let text = "let x = 1\nx + undefined_variable\n";
+    let error = vm.load_script("test", text).unwrap_err();
+
+    let diagnostics = error.diagnostics();
+    let diagnostic = diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.message.contains("undefined_variable"))
+        .unwrap_or_else(|| panic!("{}", error));
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(
+        diagnostic.message,
+        "Undefined variable `undefined_variable`"
+    );
+
+    let primary = diagnostic.primary.as_ref().expect("Primary label");
+    assert_eq!(primary.range.file, "test");
+    assert_eq!(primary.range.range, 14..32);
+    assert_eq!(primary.range.start, Position { line: 2, column: 5 });
+    assert_eq!(
+        primary.range.end,
+        Position {
+            line: 2,
+            column: 23
+        }
+    );
+
+    let json = diagnostic.to_json();
+    assert!(
+        json.contains(r#""range":[14,32],"start":{"line":2,"column":5}"#),
+        "{}",
+        json
+    );
+}
+
+#[test]
+fn undefined_variable_diagnostic_suggests_fix() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let text = "let length = 1\nlenght + 1\n";
+    let error = vm.load_script("test", text).unwrap_err();
+
+    let diagnostics = error.diagnostics();
+    let diagnostic = diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.message.contains("lenght"))
+        .unwrap_or_else(|| panic!("{}", error));
+    assert_eq!(diagnostic.notes, ["help: did you mean `length`?"]);
+    assert_eq!(diagnostic.fixes.len(), 1, "{:?}", diagnostic.fixes);
+    let fix = &diagnostic.fixes[0];
+    assert_eq!(fix.replacement, "length");
+    assert_eq!(fix.range.range, 15..21);
+}
+
+#[test]
+fn diagnostics_to_json() {
+    let diagnostics = [Diagnostic::message(
+        Severity::Warning,
+        "A \"quoted\"\nmessage",
+    )];
+    assert_eq!(
+        diagnostic::to_json(&diagnostics),
+        r#"[{"severity":"warning","code":null,"message":"A \"quoted\"\nmessage","primary":null,"secondary":[],"notes":[],"fixes":[]}]"#
+    );
+}
+
+fn assert_private_field_error(text: &str) {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let error = vm.load_script("test", text).unwrap_err();
+
+    match error {
+        Error::Typecheck(errors) => {
+            let errors: Vec<_> = errors.into_errors().into();
+            assert_eq!(errors.len(), 1, "{:?}", errors);
+            match errors[0].value.error {
+                TypeError::PrivateField { ref field, .. } => {
+                    assert_eq!(field.declared_name(), "helper")
+                }
+                ref err => panic!("{}", err),
+            }
+        }
+        error => panic!("{}", error),
+    }
+}
+
+#[test]
+fn private_field_is_hidden_from_projections() {
+    assert_private_field_error(
+        r#"
+        (import! tests.private_field).helper 1
+    "#,
+    );
+}
+
+#[test]
+fn private_field_is_hidden_from_projections_on_local_bindings() {
+    assert_private_field_error(
+        r#"
+        let m = import! tests.private_field
+        m.helper 1
+    "#,
+    );
+}
+
+#[test]
+fn private_field_is_hidden_from_record_patterns() {
+    assert_private_field_error(
+        r#"
+        let { helper } = import! tests.private_field
+        helper 1
+    "#,
+    );
+}
+
+#[test]
+fn private_field_of_a_variable_which_shadows_a_module() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let (value, _) = vm
+        .run_expr::<i32>(
+            "test",
+            r#"
+        let x =
+            let m = import! tests.private_field
+            m.add_two 1
+        let m = { helper = \x -> x + 10 }
+        m.helper x
+    "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value, 13);
+}
+
+#[test]
+fn private_field_in_module_which_does_not_end_with_a_record_expression() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let text = r#"
+        #[private]
+        let helper x = x + 1
+        let base = { add_two = \x -> helper (helper x) }
+        { helper, .. base }
+    "#;
+    let error = vm.load_script("test", text).unwrap_err();
+
+    match error {
+        Error::Typecheck(errors) => {
+            let errors: Vec<_> = errors.into_errors().into();
+            assert_eq!(errors.len(), 1, "{:?}", errors);
+            match errors[0].value.error {
+                TypeError::Message(_) => (),
+                ref err => panic!("{}", err),
+            }
+        }
+        error => panic!("{}", error),
+    }
+}
+
+#[test]
+fn public_fields_can_use_private_fields() {
+    let _ = ::env_logger::try_init();
+    let vm = support::make_vm();
+    let (value, _) = vm
+        .run_expr::<i32>(
+            "test",
+            r#"
+        let m = import! tests.private_field
+        m.add_two 1
+    "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value, 3);
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "fuzzing")]
+use support::*;
+
+mod support;
+
+use gluon::fuzz::{
+    parse_arbitrary, parse_arbitrary_with, typecheck_arbitrary, FuzzError, FuzzLimits,
+};
+
+#[test]
+fn parse_valid_and_invalid_input() {
+    let _ = env_logger::try_init();
+
+    assert_eq!(parse_arbitrary(b"let x = 1 in x"), Ok(()));
+    match parse_arbitrary(b"let x = in") {
+        Err(FuzzError::Rejected(_)) => (),
+        result => panic!("Expected a parse error, got {:?}", result),
+    }
+    assert_eq!(parse_arbitrary(b"\xff\xfe"), Err(FuzzError::InvalidUtf8));
+}
+
+#[test]
+fn reject_too_large_input() {
+    let limits = FuzzLimits {
+        max_input_len: 4,
+        ..FuzzLimits::default()
+    };
+    assert_eq!(
+        parse_arbitrary_with(&limits, b"1 + 2"),
+        Err(FuzzError::TooLarge(5, 4))
+    );
+}
+
+#[test]
+fn deeply_nested_input_does_not_overflow_the_stack() {
+    let _ = env_logger::try_init();
+
+    let limits = FuzzLimits::default();
+    let depth = limits.max_input_len / 2;
+    let input = format!("{}1{}", "(".repeat(depth - 1), ")".repeat(depth - 1));
+    assert_eq!(parse_arbitrary_with(&limits, input.as_bytes()), Ok(()));
+
+    let thread = make_vm();
+    assert_eq!(typecheck_arbitrary(&thread, input.as_bytes()), Ok(()));
+}
+
+#[test]
+fn typecheck_reuses_the_thread() {
+    let _ = env_logger::try_init();
+
+    let thread = make_vm();
+    assert_eq!(
+        typecheck_arbitrary(&thread, b"let x : Int = 1 in x"),
+        Ok(())
+    );
+
+    let err = typecheck_arbitrary(&thread, b"let x : Int = \"\" in x").unwrap_err();
+    assert!(!err.is_bug(), "{}", err);
+    assert!(err.to_string().contains("Expected: Int"), "{}", err);
+}
+
+#[test]
+fn inputs_with_different_stack_sizes() {
+    let _ = env_logger::try_init();
+
+    let small = FuzzLimits {
+        stack_size: 8 * 1024 * 1024,
+        ..FuzzLimits::default()
+    };
+    for limits in &[small.clone(), FuzzLimits::default(), small] {
+        assert_eq!(parse_arbitrary_with(limits, b"let x = 1 in x"), Ok(()));
+    }
+}
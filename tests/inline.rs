@@ -3,7 +3,126 @@ use support::*;
 
 mod support;
 
-use gluon::{self, query::AsyncCompilation, vm::core::tests::check_expr_eq, ThreadExt};
+use gluon::{
+    self,
+    query::AsyncCompilation,
+    vm::core::{interpreter::Global, tests::check_expr_eq, CoreExpr},
+    OptimizationLevel, ThreadExt,
+};
+
+async fn optimized_core_expr(level: OptimizationLevel, expr_str: &str) -> String {
+    optimized_core(level, expr_str).await.value.to_string()
+}
+
+async fn optimized_core(level: OptimizationLevel, expr_str: &str) -> Global<CoreExpr> {
+    let thread = make_vm();
+    thread.get_database_mut().set_implicit_prelude(false);
+    thread.get_database_mut().set_optimization_level(level);
+
+    thread
+        .load_script("test", expr_str)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut db = thread.get_database();
+    db.core_expr("test".into(), None)
+        .await
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[tokio::test]
+async fn inline_small_function_from_module() {
+    let _ = env_logger::try_init();
+
+    let thread = make_vm();
+    thread.get_database_mut().set_implicit_prelude(false);
+
+    thread
+        .load_script(
+            "test",
+            r#"
+            let mod = import! tests.optimize.small_functions
+            mod.add 1 2
+        "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut db = thread.get_database();
+    let core_expr = db
+        .core_expr("test".into(), None)
+        .await
+        .unwrap_or_else(|err| panic!("{}", err));
+    let expected_str = r#"
+        let x = 1
+        in
+        let y = 2
+        in
+        (#Int+) x y
+    "#;
+    check_expr_eq(core_expr.value.expr(), expected_str);
+}
+
+#[tokio::test]
+async fn do_not_inline_recursive_function_from_module() {
+    let _ = env_logger::try_init();
+
+    let core_expr = optimized_core_expr(
+        OptimizationLevel::Inline,
+        r#"
+        let { count_down } = import! tests.optimize.small_functions
+        count_down 3
+        "#,
+    )
+    .await;
+    assert!(core_expr.contains("count_down"), "{}", core_expr);
+    assert!(!core_expr.contains("#Int=="), "{}", core_expr);
+}
+
+#[tokio::test]
+async fn basic_optimization_level_does_not_inline() {
+    let _ = env_logger::try_init();
+
+    let script = r#"
+        let { add } = import! tests.optimize.small_functions
+        add 1 2
+        "#;
+
+    let inlined = optimized_core_expr(OptimizationLevel::Inline, script).await;
+    assert!(inlined.contains("#Int+"), "{}", inlined);
+
+    let not_inlined = optimized_core_expr(OptimizationLevel::Basic, script).await;
+    assert!(!not_inlined.contains("#Int+"), "{}", not_inlined);
+}
+
+#[tokio::test]
+async fn simplify_case_of_case() {
+    let _ = env_logger::try_init();
+
+    let script = r#"
+        type Flag = | On | Off
+        type Opt = | Some Int | None
+        let f x : Flag -> Int =
+            let opt =
+                match x with
+                | On -> None
+                | Off -> Some 2
+            match opt with
+            | Some y -> y
+            | None -> 1
+        f
+        "#;
+
+    let simplified = optimized_core(OptimizationLevel::Inline, script).await;
+    let expected_str = r#"
+        rec let f x =
+            match x with
+            | On -> 1
+            | Off -> let y = 2 in y
+            end
+        in
+        f
+    "#;
+    check_expr_eq(simplified.value.expr(), expected_str);
+}
 
 #[ignore]
 #[tokio::test]
@@ -118,6 +118,33 @@ fn run_expr_int() {
     }
 }
 
+#[test]
+fn run_expr_prints_plain_data_structurally() {
+    let _ = ::env_logger::try_init();
+
+    let text = r#"
+        let io = import! std.io
+        let { flat_map } = io.monad
+        do result = io.run_expr "
+            type Letters = Array Char
+            let letters : Letters = ['a', 'b']
+            let add x y = x #Int+ y
+            (letters, 1.0, 2b, add 1)
+        "
+        io.applicative.wrap result.value
+    "#;
+    let vm = make_vm();
+    vm.get_database_mut().run_io(true);
+    let (result, _) = vm.run_expr::<IO<String>>("<top>", text).unwrap();
+    match result {
+        IO::Value(result) => {
+            let expected = "(['a', 'b'], 1, 2, <add>)";
+            assert_eq!(result, expected);
+        }
+        IO::Exception(err) => panic!("{}", err),
+    }
+}
+
 test_expr! { io run_expr_io,
 r#"
 let io = import! std.io
@@ -130,6 +157,58 @@ io.flat_map (\x -> io.wrap 100)
 100i32
 }
 
+test_expr! { io recover_error_with_stacktrace,
+r#"
+let io @ { IO, ? } = import! std.io
+let { wrap } = import! std.applicative
+let { flat_map } = import! std.monad
+let { error } = import! std.prim
+let array = import! std.array
+
+let fail x : () -> IO String = error "boom"
+
+io.recover (flat_map fail (wrap ())) (\e ->
+    if array.is_empty e.stacktrace then wrap "no stacktrace" else wrap e.message)
+"#,
+String::from("boom")
+}
+
+test_expr! { io reference_assignment_sugar,
+r#"
+let { ? } = import! std.io
+let { ref } = import! std.reference
+let { wrap } = import! std.applicative
+let { flat_map } = import! std.monad
+
+do counter = ref 1
+seq counter := 2
+do x = !counter
+wrap (x #Int+ 1)
+"#,
+3i32
+}
+
+test_expr! { io reference_sugar_is_not_applied_to_user_defined_operators,
+r#"
+let { ? } = import! std.io
+let { ref } = import! std.reference
+let { wrap } = import! std.applicative
+let { flat_map } = import! std.monad
+
+let increment x =
+    #[infix(left, 4)]
+    let (!) y : Int -> Int = y #Int+ 10
+    #[infix(left, 4)]
+    let (:=) l r : Int -> Int -> Int = l #Int* r
+    !x := 2
+
+do counter = ref 1
+do x = !counter
+wrap (increment x)
+"#,
+22i32
+}
+
 #[test]
 fn dont_execute_io_in_run_expr_async() {
     let _ = ::env_logger::try_init();
@@ -381,3 +460,101 @@ fn io_error_in_catch3() {
 
     assert_eq!(result, expected);
 }
+
+#[test]
+fn recv_wait_between_joined_threads() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+        let io @ { ? } = import! std.io
+        let { flat_map } = import! std.monad
+        let { join } = import! std.thread
+        let { channel, send, recv_wait } = import! std.channel
+
+        do ping = channel 0
+        do pong = channel 0
+        let a =
+            seq send ping.sender 1
+            recv_wait pong.receiver
+        let b =
+            do x = recv_wait ping.receiver
+            seq send pong.sender (x + 1)
+            io.applicative.wrap x
+        do result = join a b
+        io.applicative.wrap (result._0 + result._1)
+    "#;
+
+    let vm = make_vm();
+    vm.get_database_mut().run_io(true);
+
+    let (result, _) = vm
+        .run_expr::<IO<i32>>("<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, IO::Value(3));
+}
+
+fn run_deadlock(expr: &str) -> String {
+    let vm = make_vm();
+    vm.get_database_mut().run_io(true);
+
+    match vm.run_expr::<IO<i32>>("<top>", expr) {
+        Ok((result, _)) => panic!("Expected a deadlock, got {:?}", result),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[test]
+fn recv_wait_deadlock() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+        let io @ { ? } = import! std.io
+        let { flat_map } = import! std.monad
+        let { channel, recv_wait } = import! std.channel
+
+        do { receiver } = channel 0
+        recv_wait receiver
+    "#;
+
+    let err = run_deadlock(expr);
+    assert!(err.contains("Deadlock"), "{}", err);
+    assert!(err.contains("created at `do_do_bind:Line 6`"), "{}", err);
+}
+
+#[test]
+fn recv_wait_deadlock_between_joined_threads() {
+    let _ = ::env_logger::try_init();
+
+    let expr = r#"
+        let io @ { ? } = import! std.io
+        let { flat_map } = import! std.monad
+        let { join } = import! std.thread
+        let { channel, send, recv_wait } = import! std.channel
+
+        do ping = channel 0
+        do pong = channel 0
+        let a =
+            do x = recv_wait pong.receiver
+            seq send ping.sender x
+            io.applicative.wrap x
+        let b =
+            do x = recv_wait ping.receiver
+            seq send pong.sender x
+            io.applicative.wrap x
+        do result = join a b
+        io.applicative.wrap (result._0 + result._1)
+    "#;
+
+    let err = run_deadlock(expr);
+    assert!(err.contains("Deadlock"), "{}", err);
+    assert!(
+        err.contains("created at `do_pong:Line 10`, waiting at `do_x:Line 11`"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("created at `do_ping:Line 8`, waiting at `do_x:Line 15`"),
+        "{}",
+        err
+    );
+}
@@ -2,11 +2,13 @@ mod support;
 
 use gluon::{
     vm::{
-        api::{Hole, OpaqueValue},
+        api::{Hole, OpaqueValue, IO},
+        gc::{ValueLimits, ValueSize},
+        stack::StackGrowth,
         thread::ThreadInternal,
         Error as VMError,
     },
-    Error, Thread, ThreadExt,
+    Error, RootedThread, Thread, ThreadExt,
 };
 
 use crate::support::make_vm;
@@ -47,3 +49,131 @@ fn stack_overflow() {
         Ok(_) => panic!("Expected an error"),
     }
 }
+
+/// Creates a vm with `limits`, loading the modules used by the tests first as the standard library
+/// itself contains values which exceed the limits
+fn limited_vm(limits: ValueLimits) -> RootedThread {
+    let vm = make_vm();
+    vm.run_expr::<()>(
+        "load",
+        "let _ = import! std.io in let _ = import! std.array in ()",
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+    vm.set_value_limits(limits);
+    vm
+}
+
+fn run_with_limits(limits: ValueLimits, expr: &str) -> Result<(), Error> {
+    let vm = limited_vm(limits);
+    vm.run_expr::<OpaqueValue<&Thread, Hole>>("example", expr)
+        .map(|_| ())
+}
+
+#[test]
+fn stack_shrinks_after_deep_recursion() {
+    let _ = ::env_logger::try_init();
+
+    let vm = make_vm();
+    let expr = r#"
+        let sum n : Int -> Int = if n == 0 then 0 else n + sum (n - 1)
+        sum 10000
+    "#;
+
+    vm.context().set_stack_growth(StackGrowth {
+        shrink_factor: 0,
+        ..StackGrowth::default()
+    });
+    vm.run_expr::<i32>("sum", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm.context().stack_capacity() > 10000);
+
+    vm.context().set_stack_growth(StackGrowth::default());
+    vm.run_expr::<i32>("sum", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm.context().stack_capacity() <= StackGrowth::default().retained_capacity);
+}
+
+#[test]
+fn string_too_long() {
+    let _ = ::env_logger::try_init();
+
+    let limits = ValueLimits {
+        max_string_len: 10,
+        ..ValueLimits::default()
+    };
+    assert_eq!(run_with_limits(limits, r#" "abcde" ++ "fghij" "#), Ok(()));
+
+    // Errors from primitive functions such as `string.append` are raised as panics
+    match run_with_limits(limits, r#" "abcde" ++ "fghijk" "#) {
+        Err(Error::VM(VMError::Panic(ref message, _, _)))
+            if *message == "A string of 11 bytes exceeds the limit of 10" => {}
+        result => panic!("Expected the string to exceed the limit: {:?}", result),
+    }
+}
+
+#[test]
+fn array_too_long() {
+    let _ = ::env_logger::try_init();
+
+    let limits = ValueLimits {
+        max_array_len: 4,
+        ..ValueLimits::default()
+    };
+    assert_eq!(run_with_limits(limits, " [1, 2, 3, 4] "), Ok(()));
+
+    match run_with_limits(limits, " [1, 2, 3, 4, 5] ") {
+        Err(Error::VM(VMError::ValueTooLarge {
+            size: ValueSize::Array(5),
+            limit: 4,
+        })) => (),
+        result => panic!("Expected the array to exceed the limit: {:?}", result),
+    }
+}
+
+#[test]
+fn record_too_deep() {
+    let _ = ::env_logger::try_init();
+
+    let limits = ValueLimits {
+        max_record_depth: 3,
+        ..ValueLimits::default()
+    };
+    assert_eq!(
+        run_with_limits(limits, " { a = { b = Some { c = 1 } } } "),
+        Ok(())
+    );
+
+    let expr = r#"
+        let nest x = { x }
+        nest (nest (Some (nest (nest 1))))
+    "#;
+    match run_with_limits(limits, expr) {
+        Err(Error::VM(VMError::ValueTooLarge {
+            size: ValueSize::RecordDepth(4),
+            limit: 3,
+        })) => (),
+        result => panic!("Expected the record to exceed the limit: {:?}", result),
+    }
+}
+
+#[test]
+fn catch_value_too_large() {
+    let _ = ::env_logger::try_init();
+
+    let vm = limited_vm(ValueLimits {
+        max_array_len: 4,
+        ..ValueLimits::default()
+    });
+    vm.get_database_mut().run_io(true);
+
+    let expr = r#"
+        let io @ { ? } = import! std.io
+        let array = import! std.array
+        let too_long = io.flat_map (\_ -> io.wrap (array.len (array.append [1, 2] [3, 4, 5]))) (io.wrap ())
+        io.catch too_long (\_ -> io.wrap 0)
+    "#;
+    let (result, _) = vm
+        .run_expr::<IO<i32>>("example", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, IO::Value(0));
+}
@@ -0,0 +1,95 @@
+use std::sync::Mutex;
+
+use gluon::{new_vm, vm::api::IO, ThreadExt};
+
+#[derive(Debug, PartialEq)]
+struct Captured {
+    level: log::Level,
+    target: String,
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+struct Visitor<'a>(&'a mut Vec<(String, String)>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for Visitor<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+struct CaptureLogger(Mutex<Vec<Captured>>);
+
+impl log::Log for CaptureLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target() == "test_log"
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut fields = Vec::new();
+        record
+            .key_values()
+            .visit(&mut Visitor(&mut fields))
+            .unwrap();
+        self.0.lock().unwrap().push(Captured {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            fields,
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger(Mutex::new(Vec::new()));
+
+#[test]
+fn log_records_are_forwarded_to_the_host_logger() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+
+    let vm = new_vm();
+    vm.get_database_mut().run_io(true);
+    let text = r#"
+        let log @ { Level, field } = import! std.log
+        let io @ { ? } = import! std.io
+
+        seq log.info "Listening" [field "port" 8080, { key = "host", value = "localhost" }]
+        seq log.debug "Not logged" []
+        do debug_enabled = log.enabled Debug
+        seq log.warn "Debug enabled" [field "enabled" debug_enabled]
+        io.wrap ()
+    "#;
+    vm.run_expr::<IO<()>>("test_log", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(
+        *LOGGER.0.lock().unwrap(),
+        vec![
+            Captured {
+                level: log::Level::Info,
+                target: "test_log".into(),
+                message: "Listening".into(),
+                fields: vec![
+                    ("port".into(), "8080".into()),
+                    ("host".into(), "localhost".into())
+                ],
+            },
+            Captured {
+                level: log::Level::Warn,
+                target: "test_log".into(),
+                message: "Debug enabled".into(),
+                fields: vec![("enabled".into(), "False".into())],
+            },
+        ]
+    );
+}
@@ -26,3 +26,75 @@ let { List, of }  = import! std.list
     assert!(env.get_metadata("test.of").is_ok());
     assert!(env.get_metadata("test.List").is_ok());
 }
+
+#[test]
+fn metadata_through_import_alias() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+import! { List, of } from std.list
+import! std.list as list
+{ List, of, list_filter = list.filter }
+"#;
+    vm.load_script("test", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let env = vm.get_env();
+    assert!(env.get_metadata("test.List").is_ok());
+    for name in &["test.of", "test.list_filter"] {
+        let metadata = env
+            .get_metadata(name)
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert!(metadata.comment.is_some(), "{} is undocumented", name);
+    }
+}
+
+#[test]
+fn module_metadata_lists_exports() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let text = r#"
+/// Handles a request
+#[plugin]
+let handler x : Int -> Int = x
+
+/// Not exported
+let helper = 1
+
+{ handler, undocumented = helper }
+"#;
+    vm.load_script("plugin", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let metadata = vm
+        .module_metadata("plugin")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        metadata.module.keys().collect::<Vec<_>>(),
+        ["handler", "undocumented"]
+    );
+
+    let handler = &metadata.module["handler"];
+    assert_eq!(
+        handler.comment.as_ref().map(|comment| &comment.content[..]),
+        Some("Handles a request")
+    );
+    assert!(handler.get_attribute("plugin").is_some());
+}
+
+#[test]
+fn module_metadata_loads_the_module() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let metadata = vm
+        .module_metadata("std.list")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(metadata.module.contains_key("of"));
+    assert!(metadata.module["of"].comment.is_some());
+
+    let metadata = vm
+        .module_metadata("std.int.prim")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(metadata.module.contains_key("abs"));
+}
@@ -4,12 +4,13 @@ extern crate gluon_vm;
 use std::thread::spawn;
 
 use gluon::{
+    import_graph::ImportGraph,
     new_vm,
     vm::{
         api::{FunctionRef, OpaqueValue, IO},
         channel::{ChannelRecord, Receiver, Sender},
     },
-    Error, RootedThread, ThreadExt,
+    Error, RootedThread, ThreadExt, VmBuilder,
 };
 
 #[test]
@@ -88,3 +89,56 @@ fn parallel_() -> Result<(), Error> {
     handle1.join().unwrap()?;
     handle2.join().unwrap()
 }
+
+#[test]
+fn compile_imports_in_parallel() {
+    let _ = env_logger::try_init();
+
+    let vm = VmBuilder::new().compile_threads(Some(4)).build();
+
+    let expr = r#"
+        let { left } = import! "tests/parallel/left.glu"
+        let { right } = import! "tests/parallel/right.glu"
+        let list = import! std.list
+        let map = import! std.map
+        let string = import! std.string
+        left + right
+        "#;
+    let (value, _) = vm.run_expr::<i32>("<top>", expr).unwrap();
+    assert_eq!(value, 5);
+}
+
+#[test]
+fn report_errors_from_imports_compiled_in_parallel() {
+    let _ = env_logger::try_init();
+
+    let vm = VmBuilder::new().compile_threads(Some(2)).build();
+
+    let expr = r#"
+        let { ok } = import! "tests/unrelated_type_error.glu"
+        let list = import! std.list
+        ()
+        "#;
+    let result = vm.run_expr::<()>("<top>", expr);
+    assert!(result.is_err(), "{:?}", result.map(|_| ()));
+}
+
+#[test]
+fn import_graph_levels() {
+    let _ = env_logger::try_init();
+
+    let vm = new_vm();
+    let graph = ImportGraph::resolve(&vm, "tests.parallel.root").unwrap();
+    assert_eq!(
+        graph.imports("tests.parallel.root"),
+        ["tests.parallel.left", "tests.parallel.right"]
+    );
+    assert_eq!(
+        graph.levels(),
+        [
+            vec!["tests.parallel.base"],
+            vec!["tests.parallel.left", "tests.parallel.right"],
+            vec!["tests.parallel.root"],
+        ]
+    );
+}
@@ -199,3 +199,48 @@ getName ("abc", 123)
 "#,
 "abc".to_string()
 }
+
+test_expr! { switch_on_many_constructors,
+r#"
+type Shape = | Circle Int | Square Int | Triangle Int Int | Line | Point
+let area shape =
+    match shape with
+    | Point -> 0
+    | Triangle b h -> b #Int* h #Int/ 2
+    | Circle r -> 3 #Int* r #Int* r
+    | Line -> 1
+    | Square s -> s #Int* s
+area (Circle 2) #Int+ area (Square 3) #Int+ area (Triangle 4 5) #Int+ area Line #Int+ area Point
+"#,
+32i32
+}
+
+test_expr! { switch_with_default_alternative,
+r#"
+type Op = | Add | Sub | Mul | Div | Rem
+let apply op x y =
+    match op with
+    | Mul -> x #Int* y
+    | Add -> x #Int+ y
+    | Sub -> x #Int- y
+    | _ -> 0
+apply Add 5 3 #Int+ apply Sub 5 3 #Int+ apply Mul 5 3 #Int+ apply Div 5 3 #Int+ apply Rem 5 3
+"#,
+25i32
+}
+
+test_expr! { switch_with_nested_patterns,
+r#"
+type Option a = | None | Some a
+type Tree = | Leaf | Node Tree Int Tree | Many (Option Int)
+let f tree =
+    match tree with
+    | Leaf -> 1
+    | Node Leaf x _ -> x
+    | Node _ x _ -> x #Int+ 100
+    | Many (Some x) -> x
+    | Many None -> 2
+f Leaf #Int+ f (Node Leaf 10 Leaf) #Int+ f (Node (Node Leaf 1 Leaf) 20 Leaf) #Int+ f (Many (Some 1000)) #Int+ f (Many None)
+"#,
+1133i32
+}
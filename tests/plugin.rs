@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use gluon::{
+    new_vm,
+    plugin::{load_plugins, PluginError},
+    vm::api::FunctionRef,
+};
+
+#[test]
+fn load_plugins_with_entry_point() {
+    let _ = ::env_logger::try_init();
+    let vm = new_vm();
+
+    let plugins =
+        load_plugins::<FunctionRef<fn(String) -> String>>(&vm, "tests/plugins", "handler")
+            .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(plugins.len(), 3);
+
+    let mut results = Vec::new();
+    for plugin in plugins {
+        match plugin {
+            Ok(mut plugin) => {
+                assert_eq!(plugin.path.parent(), Some(Path::new("tests/plugins")));
+                results.push((
+                    plugin.module,
+                    plugin.entry_point.call("gluon".into()).unwrap(),
+                ));
+            }
+            Err(PluginError::EntryPoint(path, _)) => {
+                assert_eq!(path, Path::new("tests/plugins/wrong_type.glu"))
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+    assert_eq!(
+        results,
+        [
+            ("greet".to_string(), "Hello gluon".to_string()),
+            ("shout".to_string(), "gluon!".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn load_plugins_missing_directory() {
+    let vm = new_vm();
+    assert!(load_plugins::<i32>(&vm, "tests/missing_plugins", "handler").is_err());
+}
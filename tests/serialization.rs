@@ -282,3 +282,111 @@ fn issue_805_no_deadlock_in_deserialize() {
         .to_string()
         .contains("is not defined"));
 }
+
+#[tokio::test]
+async fn typecheck_against_interface() {
+    use gluon::interface::{interface_path, ModuleInterface};
+
+    let dir = tempfile::tempdir().unwrap();
+
+    let thread = new_vm_async().await;
+    thread
+        .get_database_mut()
+        .set_emit_interfaces(Some(dir.path().into()));
+    thread
+        .load_script_async(
+            "interface_test",
+            r#"
+/// Adds two integers
+let add x y = x #Int+ y
+type Pair = { first : Int, second : Int }
+{ add, Pair }
+"#,
+        )
+        .await
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let interface = ModuleInterface::read(
+        File::open(dir.path().join(interface_path("interface_test"))).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(interface.name, "interface_test");
+    assert_eq!(
+        interface.metadata.module["add"]
+            .comment
+            .as_ref()
+            .map(|comment| &comment.content[..]),
+        Some("Adds two integers")
+    );
+
+    // The source of `interface_test` is not available to this thread
+    let thread = gluon::VmBuilder::new()
+        .import_paths(Some(vec![dir.path().into()]))
+        .build_async()
+        .await;
+    let (_, typ) = thread
+        .typecheck_str_async(
+            "test",
+            r#"
+let m = import! interface_test
+let pair : m.Pair = { first = 1, second = 2 }
+m.add pair.first pair.second
+"#,
+            None,
+        )
+        .await
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(typ.to_string(), "Int");
+}
+
+#[test]
+fn load_file_cached() {
+    use gluon::bytecode_cache::cache_path;
+
+    let dir = tempfile::Builder::new()
+        .prefix("bytecode_cache")
+        .tempdir()
+        .unwrap();
+    let filename = dir.path().join("cached.glu");
+    let filename = filename.to_str().unwrap();
+    let module = gluon::base::filename_to_module(filename);
+    let len = format!("{}.len", module);
+
+    std::fs::write(
+        filename,
+        r#"
+let array = import! std.array
+{ len = array.len [1, 2, 3] }
+"#,
+    )
+    .unwrap();
+
+    let thread = new_vm();
+    thread
+        .load_file_cached(filename, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(thread.get_global::<i32>(&len).unwrap(), 3);
+    assert!(cache_path(filename, None).exists());
+
+    // A new thread loads the module from the cache
+    let thread = new_vm();
+    thread
+        .load_file_cached(filename, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(thread.get_global::<i32>(&len).unwrap(), 3);
+
+    // Changing the source invalidates the cache
+    std::fs::write(
+        filename,
+        r#"
+let array = import! std.array
+{ len = array.len [1, 2, 3, 4] }
+"#,
+    )
+    .unwrap();
+    let thread = new_vm();
+    thread
+        .load_file_cached(filename, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(thread.get_global::<i32>(&len).unwrap(), 4);
+}
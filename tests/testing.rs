@@ -0,0 +1,60 @@
+#![cfg(feature = "testing")]
+use support::*;
+
+mod support;
+
+use std::{fs, path::Path};
+
+use gluon::testing::{self, assert_snapshot, check_snapshot};
+
+fn read_source(name: &str) -> String {
+    fs::read_to_string(Path::new("tests/golden").join(name)).unwrap()
+}
+
+#[test]
+fn snapshot_module_types() {
+    let _ = env_logger::try_init();
+
+    let thread = make_vm();
+    let types = testing::module_types(&thread, "module", &read_source("module.glu"))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_snapshot("tests/golden/module.types", &types);
+}
+
+#[test]
+fn snapshot_diagnostics() {
+    let _ = env_logger::try_init();
+
+    let thread = make_vm();
+    let diagnostics = testing::diagnostics(&thread, "type_error", &read_source("type_error.glu"));
+    assert_snapshot("tests/golden/type_error.diagnostics", &diagnostics);
+
+    let diagnostics = testing::diagnostics(&thread, "module", &read_source("module.glu"));
+    assert_eq!(diagnostics, "");
+}
+
+#[test]
+fn snapshot_formatted() {
+    let _ = env_logger::try_init();
+
+    let thread = make_vm();
+    let formatted = testing::formatted(&thread, "module", &read_source("module.glu"))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_snapshot("tests/golden/module.fmt.glu", &formatted);
+}
+
+#[test]
+fn mismatched_snapshot_reports_diff() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot");
+    fs::write(&path, "a\nb\n").unwrap();
+
+    check_snapshot(&path, "a\nb\n", false).unwrap();
+
+    let mismatch = check_snapshot(&path, "a\nc\n", false).unwrap_err();
+    let message = mismatch.to_string();
+    assert!(message.contains("-b\n+c\n"), "{}", message);
+
+    check_snapshot(&path, "a\nc\n", true).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\nc\n");
+}
@@ -9,7 +9,11 @@ mod support;
 use crate::support::*;
 
 use gluon::{
-    base::{pos::BytePos, source::Source, types::Type},
+    base::{
+        pos::BytePos,
+        source::Source,
+        types::{Type, TypeExt},
+    },
     vm,
     vm::{
         api::{FunctionRef, Hole, OpaqueValue, ValueRef, IO},
@@ -57,6 +61,14 @@ r" 120.0 #Float/ 4.0
 30.0f64
 }
 
+test_expr! { tuple_field_access,
+r"
+let t = (1, (1.5, 2), 3)
+t.0 #Int+ t.1.1 #Int+ t.2
+",
+6i32
+}
+
 test_expr! { infix_propagates,
 r"
 #[infix(left, 6)]
@@ -322,6 +334,25 @@ string_compare "a" "b"
 ValueRef::tag(0)
 }
 
+test_expr! { prelude import_as,
+r#"
+import! std.prim as prim
+prim.discriminant_value (Some 1)
+"#,
+1
+}
+
+test_expr! { prelude import_from,
+r#"
+import! { List, filter, of = list_of } from std.list
+let xs : List Int = filter (\x -> x > 1) (list_of [1, 2, 3])
+match xs with
+| Cons x _ -> x
+| Nil -> 0
+"#,
+2
+}
+
 test_expr! { discriminant_value,
 r#"
 type Variant a = | A | B Int | C String
@@ -732,7 +763,7 @@ g 10
     vm.get_database_mut().set_optimize(false);
     let result = vm.run_expr::<i32>("<top>", text);
     match result {
-        Err(Error::VM(vm::Error::Panic(_, Some(stacktrace)))) => {
+        Err(Error::VM(vm::Error::Panic(_, Some(stacktrace), _))) => {
             let g = stacktrace.frames[0].as_ref().unwrap().name.clone();
             assert_eq!(g.declared_name(), "g");
             let f = stacktrace.frames[1].as_ref().unwrap().name.clone();
@@ -925,6 +956,132 @@ fn deep_clone_partial_application() {
     );
 }
 
+#[test]
+fn heap_stats_and_collect_hook() {
+    use std::sync::{Arc, Mutex};
+
+    let _ = ::env_logger::try_init();
+    let vm = gluon::VmBuilder::new().build();
+
+    let child = vm.new_thread().unwrap();
+    child.get_database_mut().set_implicit_prelude(false);
+
+    let collections = Arc::new(Mutex::new(Vec::new()));
+    {
+        let collections = collections.clone();
+        child.set_collect_hook(Some(Box::new(move |stats| {
+            collections.lock().unwrap().push(stats.collections)
+        })));
+    }
+
+    child
+        .run_expr::<OpaqueValue<&Thread, Hole>>("test", "let f x y = y in f 1")
+        .unwrap_or_else(|err| panic!("{}", err));
+    let before = child.heap_stats();
+    assert!(before.total_allocated_bytes > 0);
+
+    child.collect();
+
+    let after = child.heap_stats();
+    assert_eq!(after.collections, before.collections + 1);
+    assert_eq!(after.total_allocated_bytes, before.total_allocated_bytes);
+    assert_eq!(
+        collections.lock().unwrap().last().cloned(),
+        Some(after.collections)
+    );
+}
+
+#[test]
+fn stack_maps_mark_unboxed_locals() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let (f, _) = vm
+        .run_expr::<OpaqueValue<&Thread, Hole>>(
+            "test",
+            r#"
+                \x ->
+                    let y = x #Int+ 1
+                    [y, x]
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let value = f.get_variant();
+    let closure = match value.as_ref() {
+        ValueRef::Closure(closure) => closure,
+        value => panic!("Expected closure, got {:?}", value),
+    };
+    let (_, map) = closure
+        .stack_maps()
+        .iter()
+        .last()
+        .expect("Stack map for the array construction");
+    // [x, y, y, x], only the temporaries pushed for the array are assumed to be pointers
+    assert_eq!(map.len(), 4);
+    assert_eq!(map.pointer_slots().collect::<Vec<_>>(), [2, 3], "{:?}", map);
+}
+
+#[test]
+fn collect_while_frames_with_unboxed_locals_are_suspended() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let before = vm.heap_stats();
+    // `f (n #Int- 1)` is not a tail call so each level keeps its frame, with `x` in an unboxed
+    // slot, while the arrays allocated by the inner levels cause collections
+    let (result, _) = vm
+        .run_expr::<i32>(
+            "test",
+            r#"
+                let array = import! std.array
+                rec let f n : Int -> Int =
+                    if n #Int== 0 then 0
+                    else
+                        let x = n #Int+ 1
+                        let xs = [x, x]
+                        f (n #Int- 1) #Int+ array.len xs
+                f 10000
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 20000);
+    assert!(vm.heap_stats().collections > before.collections);
+}
+
+#[test]
+fn short_strings_reuse_interned_strings() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let expr = r#"
+        let string = import! std.string
+        let record = { field = 1 }
+        (string.slice "abc" 1 2, "" ++ "c", "" ++ "", "fie" ++ "ld", record)
+    "#;
+    let ((single, appended, empty, field, _), _) = vm
+        .run_expr::<(
+            OpaqueValue<&Thread, Hole>,
+            OpaqueValue<&Thread, Hole>,
+            OpaqueValue<&Thread, Hole>,
+            OpaqueValue<&Thread, Hole>,
+            OpaqueValue<&Thread, Hole>,
+        )>("test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let assert_interned = |value: &OpaqueValue<&Thread, Hole>, expected: &str| {
+        let interned = vm.global_env().intern(expected).unwrap();
+        match value.get_variant().as_ref() {
+            ValueRef::String(s) => assert_eq!(s.as_ptr(), interned.as_ptr(), "{}", s),
+            value => panic!("Expected string, got {:?}", value),
+        }
+    };
+    assert_interned(&single, "b");
+    assert_interned(&appended, "c");
+    assert_interned(&empty, "");
+    assert_interned(&field, "field");
+}
+
 test_expr! { prelude issue_601,
 r"
 let { wrap } = import! std.applicative
@@ -1103,3 +1260,304 @@ let f a =
 { f }
 "
 }
+
+test_expr! { lazy_and_force_sugar,
+r#"
+let { Lazy } = import! std.lazy
+
+type Stream = { value : Int, ~tail : Lazy Stream }
+
+let ones _ : () -> Stream = { value = 1, tail = lazy! (ones ()) }
+
+let stream = ones ()
+(force! stream.tail).value #Int+ force! (lazy! 2)
+"#,
+3i32
+}
+
+test_expr! { lazy_field_with_alias_of_lazy,
+r#"
+let { Lazy } = import! std.lazy
+
+type Later a = Lazy a
+type Stream = { value : Int, ~tail : Later Stream }
+
+let ones _ : () -> Stream = { value = 1, tail = lazy! (ones ()) }
+
+(force! (ones ()).tail).value
+"#,
+1i32
+}
+
+test_expr! { embed_file,
+r#"
+embed! "tests/pass/path.glu"
+"#,
+String::from(include_str!("pass/path.glu"))
+}
+
+#[test]
+fn embed_missing_file() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+    let result = vm.run_expr::<String>("example", r#"embed! "tests/missing.txt""#);
+    match result {
+        Err(err) => assert!(
+            err.to_string()
+                .contains("Could not find file 'tests/missing.txt'"),
+            "{}",
+            err
+        ),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn format_missing_field() {
+    let _ = ::env_logger::try_init();
+    let text = r#"
+let { format } = import! std.fmt
+format "{x} {y}" { x = 1 }
+"#;
+    let vm = make_vm();
+    let result = vm.run_expr::<String>("<top>", text);
+    match result {
+        Err(err) => assert!(
+            err.to_string()
+                .contains("The formatted record has no field `y`"),
+            "{}",
+            err
+        ),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+test_expr! { prelude int_literal_used_as_float,
+r#"
+let x : Float = 2
+1.5 + x + 1
+"#,
+4.5f64
+}
+
+test_expr! { int_literal_used_as_byte,
+r#"
+let x : Byte = 200
+x
+"#,
+200u8
+}
+
+test_expr! { prelude int_literal_used_as_user_defined_number,
+r#"
+type Meters = | Meters Int
+let unwrap m : Meters -> Int = match m with | Meters x -> x
+let eq : Eq Meters = { (==) = \l r -> unwrap l == unwrap r }
+let ord : Ord Meters = {
+    eq,
+    compare = \l r -> (import! std.int).ord.compare (unwrap l) (unwrap r),
+}
+let num : Num Meters = {
+    ord,
+    (+) = \l r -> Meters (unwrap l + unwrap r),
+    (-) = \l r -> Meters (unwrap l - unwrap r),
+    (*) = \l r -> Meters (unwrap l * unwrap r),
+    (/) = \l r -> Meters (unwrap l / unwrap r),
+    negate = \m -> Meters (negate (unwrap m)),
+    from_int = Meters,
+}
+let x : Meters = 3
+unwrap (x + 4)
+"#,
+7i32
+}
+
+test_expr! { prelude int_literal_used_as_number_in_polymorphic_function,
+r#"
+let double x : [Num a] -> a -> a = x * 2
+double 1.25 + double 1.0
+"#,
+4.5f64
+}
+
+#[tokio::test]
+async fn int_literal_is_not_float_in_int_addition() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm_async().await;
+
+    vm.typecheck_str_async("example", "1 + 1.0", None)
+        .await
+        .unwrap_err();
+}
+
+test_expr! { record_field_defaults,
+r#"
+let default_host = "0.0.0.0"
+type Config = { port : Int = 8080, host : String = default_host, workers : Int }
+let config : Config = { workers = 4 }
+let other : Config = { port = 80, workers = 1 }
+config.port #Int+ other.port #Int+ config.workers
+"#,
+8164i32
+}
+
+test_expr! { record_field_defaults_of_imported_type,
+r#"
+let { Config } = import! tests.record_defaults
+let config : Config = { workers = 4 }
+let other : Config = { port = 80, workers = 1 }
+config.port #Int+ other.port #Int+ config.workers
+"#,
+8164i32
+}
+
+test_expr! { record_field_defaults_of_imported_module_type,
+r#"
+let m = import! tests.record_defaults
+let config : m.Config = { host = "localhost", workers = 4 }
+config.port
+"#,
+8080i32
+}
+
+test_expr! { record_field_string_default_of_imported_type,
+r#"
+let { Config } = import! tests.record_defaults
+let config : Config = { workers = 1 }
+config.host
+"#,
+String::from("0.0.0.0")
+}
+
+#[test]
+fn only_literal_record_field_defaults_are_exported() {
+    let _ = ::env_logger::try_init();
+    let vm = make_vm();
+
+    let (_, module_type) = vm
+        .typecheck_str("test", "import! tests.record_defaults", None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(module_type.row_iter().count(), 0);
+    assert_eq!(module_type.type_field_iter().count(), 1);
+
+    // `workers` defaults to a variable of the module which is not known to importers
+    let result = vm.run_expr::<i32>(
+        "test",
+        r#"
+            let { Config } = import! tests.record_defaults
+            let config : Config = { port = 80 }
+            config.port
+        "#,
+    );
+    assert!(result.is_err());
+}
+
+test_expr! { prelude applicative_do,
+r#"
+type Validation a = | Failure Int | Success a
+
+let functor : Functor Validation = {
+    map = \f v ->
+        match v with
+        | Failure e -> Failure e
+        | Success x -> Success (f x),
+}
+
+let applicative : Applicative Validation = {
+    functor,
+    apply = \f x ->
+        match (f, x) with
+        | (Failure l, Failure r) -> Failure (l + r)
+        | (Failure l, _) -> Failure l
+        | (_, Failure r) -> Failure r
+        | (Success g, Success y) -> Success (g y),
+    wrap = Success,
+}
+
+let validated : Validation Int =
+    do x = Failure 1
+    do y = Success 2
+    do z = Failure 3
+    wrap (x + y + z)
+
+match validated with
+| Failure errors -> errors
+| Success _ -> 0
+"#,
+4i32
+}
+
+test_expr! { named_arguments,
+r#"
+type Rect = { width : Int = 1, height : Int = 1 }
+let area rect : Rect -> Int = rect.width #Int* rect.height
+area ~width=3 #Int+ area ~height=5 ~width=2
+"#,
+13i32
+}
+
+test_expr! { prelude record_pattern_arguments,
+r#"
+let add { x, y } = x + y
+let sub = \{ x, y = z } -> x - z
+let nested { x } { y, z } w = x + y + z + w
+add { x = 1, y = 2 } + sub { x = 10, y = 4 } + nested { x = 1 } { y = 2, z = 3 } 4
+"#,
+19i32
+}
+
+test_expr! { prelude applicative_do_with_imported_wrap,
+r#"
+let { wrap } = import! std.applicative
+
+type Validation a = | Failure Int | Success a
+
+let functor : Functor Validation = {
+    map = \f v ->
+        match v with
+        | Failure e -> Failure e
+        | Success x -> Success (f x),
+}
+
+let applicative : Applicative Validation = {
+    functor,
+    apply = \f x ->
+        match (f, x) with
+        | (Failure l, Failure r) -> Failure (l + r)
+        | (Failure l, _) -> Failure l
+        | (_, Failure r) -> Failure r
+        | (Success g, Success y) -> Success (g y),
+    wrap = Success,
+}
+
+// A `wrap` bound in an unrelated scope does not affect the `do` block below
+let apply_wrap wrap : (Int -> Int) -> Int = wrap 1
+
+let validated : Validation Int =
+    do x = Failure 1
+    do y = Success 2
+    do z = Failure 3
+    wrap (x + y + z)
+
+match validated with
+| Failure errors -> errors + apply_wrap (\x -> x)
+| Success _ -> 0
+"#,
+5i32
+}
+
+test_expr! { prelude no_applicative_do_with_user_defined_wrap,
+r#"
+let wrap x : Int -> Option Int = Some (x + 100)
+
+let result =
+    do x = Some 1
+    do y = Some 2
+    wrap (x + y)
+
+match result with
+| Some x -> x
+| None -> 0
+"#,
+103i32
+}
@@ -0,0 +1,43 @@
+//! Primitives for `std.cbor`, encoding `std.json.Value` as [CBOR](https://cbor.io)
+extern crate serde_cbor;
+
+use std::result::Result as StdResult;
+
+use crate::{
+    api::{
+        json::{JsonValue, Value},
+        WithVM,
+    },
+    serde::{de::DeserializeState, ser::SerializeState},
+    thread::Thread,
+    ExternModule, Result,
+};
+
+fn deserialize(value: WithVM<&[u8]>) -> StdResult<JsonValue, String> {
+    let WithVM { vm, value: input } = value;
+    let mut context = vm.current_context();
+    let mut deserializer = serde_cbor::Deserializer::from_slice(input);
+    let value = JsonValue::deserialize_state(&mut context, &mut deserializer)
+        .map_err(|err| err.to_string())?;
+    deserializer.end().map_err(|err| err.to_string())?;
+    Ok(value)
+}
+
+fn serialize(value: WithVM<Value>) -> StdResult<Vec<u8>, String> {
+    let WithVM { vm, value: input } = value;
+
+    let mut output = Vec::new();
+    SerializeState::serialize_state(&input, &mut serde_cbor::Serializer::new(&mut output), vm)
+        .map_err(|err| err.to_string())?;
+    Ok(output)
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            deserialize => primitive!(1, "std.cbor.prim.deserialize", deserialize),
+            serialize => primitive!(1, "std.cbor.prim.serialize", serialize),
+        },
+    )
+}
@@ -17,6 +17,7 @@ use crate::api::{ActiveThread, AsyncPushable, Getable, Pushable, RootedValue, Vm
 use crate::compiler::{CompiledFunction, CompiledModule};
 use crate::gc::{Move, Trace};
 use crate::stack::{ExternState, StackFrame};
+use crate::stack_map::StackMaps;
 use crate::thread::{RootedThread, Status, Thread, ThreadInternal, VmRoot, VmRootInternal};
 use crate::types::{Instruction, VmIndex};
 use crate::value::ExternFunction;
@@ -518,7 +519,7 @@ where
         block_on_sync(self.call_any_async(args))
     }
 
-    async fn call_any_async<A, R>(&'vm self, args: impl IntoIterator<Item = A>) -> Result<R>
+    pub async fn call_any_async<A, R>(&'vm self, args: impl IntoIterator<Item = A>) -> Result<R>
     where
         A: Pushable<'vm>,
         R: for<'value> Getable<'vm, 'value> + VmType,
@@ -604,6 +605,8 @@ impl<'vm, T: VmType> Pushable<'vm> for TypedBytecode<T> {
                 T::make_forall_type(thread),
                 "".into(),
             ));
+            compiled_module.function.stack_maps =
+                StackMaps::conservative(self.args, &self.instructions);
             compiled_module.function.instructions = self.instructions;
             thread
                 .global_env()
@@ -236,13 +236,31 @@ impl<'vm, 'value> crate::api::Getable<'vm, 'value> for JsonValue {
     }
 }
 
+impl JsonValue {
+    /// Pushes `value` to the stack of `context` and roots it as a `JsonValue`
+    pub(crate) fn marshal<'vm, T>(context: &mut ActiveThread<'vm>, value: T) -> Result<JsonValue>
+    where
+        T: crate::api::Pushable<'vm>,
+    {
+        value.vm_push(context)?;
+        let thread = context.thread();
+        let value = context.pop();
+        Ok(JsonValue(thread.root_value((*value).clone())))
+    }
+
+    /// Returns the contents of the value
+    pub(crate) fn to_value(&self, vm: &Thread) -> Value {
+        Value::from_value(vm, self.0.get_variant())
+    }
+}
+
 use crate::serde::ser::{SerializeState, Serializer};
 impl SerializeState<Thread> for JsonValue {
     fn serialize_state<S>(&self, serializer: S, vm: &Thread) -> StdResult<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        Value::from_value(vm, self.0.get_variant()).serialize_state(serializer, vm)
+        self.to_value(vm).serialize_state(serializer, vm)
     }
 }
 
@@ -261,13 +279,7 @@ impl<'de> de::DeserializeState<'de, ActiveThread<'de>> for JsonValue {
             where
                 T: crate::api::Pushable<'vm>,
             {
-                let context = &mut *self.0;
-                value
-                    .vm_push(context)
-                    .unwrap_or_else(|err| panic!("{}", err));
-                let thread = context.thread();
-                let value = context.pop();
-                JsonValue(thread.root_value((*value).clone()))
+                JsonValue::marshal(self.0, value).unwrap_or_else(|err| panic!("{}", err))
             }
         }
 
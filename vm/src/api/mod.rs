@@ -1,12 +1,13 @@
 //! The marshalling api
 use std::{
     any::Any,
-    borrow::Borrow,
+    borrow::{Borrow, Cow},
     cell::Ref,
     cmp::Ordering,
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     ffi::{OsStr, OsString},
     fmt,
+    hash::{BuildHasher, Hash},
     marker::PhantomData,
     ops::Deref,
     path::{Path, PathBuf},
@@ -68,11 +69,16 @@ pub mod function;
 mod opaque;
 pub mod record;
 pub mod scoped;
+mod time;
 
+#[cfg(feature = "cbor")]
+pub mod cbor;
 #[cfg(feature = "serde")]
 pub mod de;
 #[cfg(feature = "serde")]
 pub mod json;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
 #[cfg(feature = "serde")]
 pub mod ser;
 #[cfg(feature = "serde")]
@@ -158,6 +164,9 @@ impl<'a> Closure<'a> {
     pub fn debug_info(&self) -> &crate::compiler::DebugInfo {
         &self.0.function.debug_info
     }
+    pub fn stack_maps(&self) -> &crate::stack_map::StackMaps {
+        &self.0.function.stack_maps
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -243,11 +252,13 @@ impl<'a> Data<'a> {
         }
     }
 
+    /// Returns the names of the fields of this record in the order they are stored in, which is
+    /// the order of the fields in the record type.
     #[doc(hidden)]
     pub fn field_names(&self) -> impl Iterator<Item = &crate::interner::InternedStr> {
         match &self.0 {
             DataInner::Tag(_) => itertools::Either::Left(None.into_iter()),
-            DataInner::Data(data) => itertools::Either::Right(data.field_map().keys()),
+            DataInner::Data(data) => itertools::Either::Right(data.field_names().iter()),
         }
     }
 }
@@ -1004,6 +1015,12 @@ impl<'vm, 's> Pushable<'vm> for &'s String {
 impl<'vm, 's> Pushable<'vm> for &'s str {
     fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
         let mut context = context.context();
+        if let Some(interned) = context.thread.global_env().short_string(self) {
+            context
+                .stack
+                .push(&*construct_gc!(ValueRepr::String(@interned.inner())));
+            return Ok(());
+        }
         let s = alloc!(context, self)?;
         context.stack.push(Variants::from(s));
         Ok(())
@@ -1025,6 +1042,22 @@ impl<'vm> Pushable<'vm> for String {
     }
 }
 
+impl<'s> VmType for Cow<'s, str> {
+    type Type = <String as VmType>::Type;
+}
+impl<'vm, 's> Pushable<'vm> for Cow<'s, str> {
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        <&str as Pushable>::vm_push(&self, context)
+    }
+}
+impl<'vm, 'value> Getable<'vm, 'value> for Cow<'value, str> {
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        Cow::Borrowed(<&'value str>::from_value(vm, value))
+    }
+}
+
 impl VmType for char {
     type Type = Self;
 }
@@ -1292,6 +1325,47 @@ where
     }
 }
 
+impl<K, V, S> VmType for HashMap<K, V, S>
+where
+    K: VmType,
+    K::Type: Sized,
+    V: VmType,
+    V::Type: Sized,
+{
+    type Type = BTreeMap<K::Type, V::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        BTreeMap::<K, V>::make_type(vm)
+    }
+}
+
+impl<'vm, K, V, S> Pushable<'vm> for HashMap<K, V, S>
+where
+    K: Borrow<str> + VmType,
+    K::Type: Sized,
+    V: for<'vm2> Pushable<'vm2> + VmType,
+    V::Type: Sized,
+{
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        to_gluon_map(self, context)
+    }
+}
+
+impl<'vm, 'value, K, V, S> Getable<'vm, 'value> for HashMap<K, V, S>
+where
+    K: Getable<'vm, 'value> + Eq + Hash,
+    V: Getable<'vm, 'value>,
+    S: BuildHasher + Default,
+{
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        let mut map = HashMap::default();
+        from_gluon_map(&mut map, vm, value);
+        map
+    }
+}
+
 fn to_gluon_map<'vm, K, V>(
     map_iter: impl IntoIterator<Item = (K, V)>,
     context: &mut ActiveThread<'vm>,
@@ -1321,7 +1395,7 @@ where
 fn from_gluon_map<'vm2, 'value2, M, K2, V2>(map: &mut M, vm: &'vm2 Thread, value: Variants<'value2>)
 where
     M: Extend<(K2, V2)>,
-    K2: Getable<'vm2, 'value2> + Ord,
+    K2: Getable<'vm2, 'value2>,
     V2: Getable<'vm2, 'value2>,
 {
     match value.as_ref() {
@@ -1693,7 +1767,7 @@ macro_rules! define_tuples {
         define_tuples!{ $($rest)+ }
     }
 }
-define_tuples! { A B C D E F G H I J K L }
+define_tuples! { A B C D E F G H I J K L M N O P }
 
 pub struct Map<K, V>(PhantomData<(K, V)>);
 
@@ -0,0 +1,302 @@
+//! Primitives for `std.msgpack`, encoding `std.json.Value` as [MessagePack](https://msgpack.org)
+use std::{collections::BTreeMap, convert::TryFrom, result::Result as StdResult};
+
+use crate::{
+    api::{
+        json::{JsonString, JsonValue, Value},
+        WithVM,
+    },
+    thread::{ActiveThread, Thread},
+    ExternModule, Result,
+};
+
+/// How deeply arrays and maps may be nested in the decoded input
+const MAX_DEPTH: usize = 128;
+
+fn deserialize(value: WithVM<&[u8]>) -> StdResult<JsonValue, String> {
+    let WithVM { vm, value: input } = value;
+    let mut context = vm.current_context();
+    let mut decoder = Decoder { input, depth: 0 };
+    let value = decoder.decode(&mut context)?;
+    if !decoder.input.is_empty() {
+        return Err(format!(
+            "Unexpected {} bytes after the MessagePack value",
+            decoder.input.len()
+        ));
+    }
+    Ok(value)
+}
+
+fn serialize(value: WithVM<Value>) -> StdResult<Vec<u8>, String> {
+    let WithVM { vm, value: input } = value;
+    let mut output = Vec::new();
+    encode(vm, &input, &mut output)?;
+    Ok(output)
+}
+
+fn encode(vm: &Thread, value: &Value, output: &mut Vec<u8>) -> StdResult<(), String> {
+    match value {
+        Value::Null => output.push(0xc0),
+        Value::Bool(false) => output.push(0xc2),
+        Value::Bool(true) => output.push(0xc3),
+        Value::Int(i) => encode_int(*i, output),
+        Value::Float(f) => {
+            output.push(0xcb);
+            output.extend_from_slice(&f.to_bits().to_be_bytes());
+        }
+        Value::String(s) => encode_str(s, output)?,
+        Value::Array(values) => {
+            encode_len(values.len(), 0x90, 0xdc, output)?;
+            for value in values {
+                encode(vm, &value.to_value(vm), output)?;
+            }
+        }
+        Value::Object(fields) => {
+            encode_len(fields.len(), 0x80, 0xde, output)?;
+            for (key, value) in fields {
+                encode_str(key, output)?;
+                encode(vm, &value.to_value(vm), output)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes `i` using the smallest representation which can hold it
+fn encode_int(i: i64, output: &mut Vec<u8>) {
+    if 0 <= i && i < 0x80 || -32 <= i && i < 0 {
+        output.push(i as u8);
+    } else if let Ok(i) = u8::try_from(i) {
+        output.push(0xcc);
+        output.push(i);
+    } else if let Ok(i) = u16::try_from(i) {
+        output.push(0xcd);
+        output.extend_from_slice(&i.to_be_bytes());
+    } else if let Ok(i) = u32::try_from(i) {
+        output.push(0xce);
+        output.extend_from_slice(&i.to_be_bytes());
+    } else if let Ok(i) = i8::try_from(i) {
+        output.push(0xd0);
+        output.extend_from_slice(&i.to_be_bytes());
+    } else if let Ok(i) = i16::try_from(i) {
+        output.push(0xd1);
+        output.extend_from_slice(&i.to_be_bytes());
+    } else if let Ok(i) = i32::try_from(i) {
+        output.push(0xd2);
+        output.extend_from_slice(&i.to_be_bytes());
+    } else if i > 0 {
+        output.push(0xcf);
+        output.extend_from_slice(&(i as u64).to_be_bytes());
+    } else {
+        output.push(0xd3);
+        output.extend_from_slice(&i.to_be_bytes());
+    }
+}
+
+fn encode_str(s: &str, output: &mut Vec<u8>) -> StdResult<(), String> {
+    if s.len() < 32 {
+        output.push(0xa0 | s.len() as u8);
+    } else if let Ok(len) = u8::try_from(s.len()) {
+        output.push(0xd9);
+        output.push(len);
+    } else {
+        encode_len(s.len(), 0xa0, 0xda, output)?;
+    }
+    output.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+/// Encodes the length of an array, map or string. `fix_marker` is used for lengths below 16 and
+/// `marker` and `marker + 1` for 16 and 32 bit lengths.
+fn encode_len(
+    len: usize,
+    fix_marker: u8,
+    marker: u8,
+    output: &mut Vec<u8>,
+) -> StdResult<(), String> {
+    if len < 16 {
+        output.push(fix_marker | len as u8);
+    } else if let Ok(len) = u16::try_from(len) {
+        output.push(marker);
+        output.extend_from_slice(&len.to_be_bytes());
+    } else if let Ok(len) = u32::try_from(len) {
+        output.push(marker + 1);
+        output.extend_from_slice(&len.to_be_bytes());
+    } else {
+        return Err(format!("{} elements are too many for MessagePack", len));
+    }
+    Ok(())
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> StdResult<&'a [u8], String> {
+        if self.input.len() < len {
+            return Err("Unexpected end of MessagePack input".to_string());
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> StdResult<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn read_u8(&mut self) -> StdResult<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> StdResult<u16, String> {
+        Ok(u16::from_be_bytes(self.take_array()?))
+    }
+
+    fn read_u32(&mut self) -> StdResult<u32, String> {
+        Ok(u32::from_be_bytes(self.take_array()?))
+    }
+
+    fn read_u64(&mut self) -> StdResult<u64, String> {
+        Ok(u64::from_be_bytes(self.take_array()?))
+    }
+
+    fn decode(&mut self, context: &mut ActiveThread) -> StdResult<JsonValue, String> {
+        let marker = self.read_u8()?;
+        let value = match marker {
+            0x00..=0x7f => Value::Int(marker.into()),
+            0x80..=0x8f => return self.decode_map(context, usize::from(marker & 0x0f)),
+            0x90..=0x9f => return self.decode_array(context, usize::from(marker & 0x0f)),
+            0xa0..=0xbf => Value::String(self.decode_str(context, usize::from(marker & 0x1f))?),
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xca => Value::Float(f32::from_bits(self.read_u32()?).into()),
+            0xcb => Value::Float(f64::from_bits(self.read_u64()?)),
+            0xcc => Value::Int(self.read_u8()?.into()),
+            0xcd => Value::Int(self.read_u16()?.into()),
+            0xce => Value::Int(self.read_u32()?.into()),
+            0xcf => Value::Int(
+                i64::try_from(self.read_u64()?)
+                    .map_err(|_| "MessagePack integer does not fit in an Int".to_string())?,
+            ),
+            0xd0 => Value::Int(i8::from_be_bytes(self.take_array()?).into()),
+            0xd1 => Value::Int(i16::from_be_bytes(self.take_array()?).into()),
+            0xd2 => Value::Int(i32::from_be_bytes(self.take_array()?).into()),
+            0xd3 => Value::Int(i64::from_be_bytes(self.take_array()?)),
+            0xd9 => {
+                let len = self.read_u8()?.into();
+                Value::String(self.decode_str(context, len)?)
+            }
+            0xda => {
+                let len = self.read_u16()?.into();
+                Value::String(self.decode_str(context, len)?)
+            }
+            0xdb => {
+                let len = self.read_u32()? as usize;
+                Value::String(self.decode_str(context, len)?)
+            }
+            0xdc => {
+                let len = self.read_u16()?.into();
+                return self.decode_array(context, len);
+            }
+            0xdd => {
+                let len = self.read_u32()? as usize;
+                return self.decode_array(context, len);
+            }
+            0xde => {
+                let len = self.read_u16()?.into();
+                return self.decode_map(context, len);
+            }
+            0xdf => {
+                let len = self.read_u32()? as usize;
+                return self.decode_map(context, len);
+            }
+            0xe0..=0xff => Value::Int((marker as i8).into()),
+            0xc4..=0xc6 => return Err("MessagePack binary data is not supported".to_string()),
+            _ => {
+                return Err(format!(
+                    "Unsupported MessagePack type marker 0x{:02x}",
+                    marker
+                ))
+            }
+        };
+        JsonValue::marshal(context, value).map_err(|err| err.to_string())
+    }
+
+    fn decode_str(
+        &mut self,
+        context: &mut ActiveThread,
+        len: usize,
+    ) -> StdResult<JsonString, String> {
+        let s = std::str::from_utf8(self.take(len)?).map_err(|err| err.to_string())?;
+        crate::api::convert_with_active_thread(context, s).map_err(|err| err.to_string())
+    }
+
+    fn decode_key(&mut self, context: &mut ActiveThread) -> StdResult<JsonString, String> {
+        let marker = self.read_u8()?;
+        let len = match marker {
+            0xa0..=0xbf => usize::from(marker & 0x1f),
+            0xd9 => self.read_u8()?.into(),
+            0xda => self.read_u16()?.into(),
+            0xdb => self.read_u32()? as usize,
+            _ => return Err("Expected a string as MessagePack map key".to_string()),
+        };
+        self.decode_str(context, len)
+    }
+
+    fn enter(&mut self) -> StdResult<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            Err("MessagePack input is nested too deeply".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn decode_array(
+        &mut self,
+        context: &mut ActiveThread,
+        len: usize,
+    ) -> StdResult<JsonValue, String> {
+        self.enter()?;
+        // Every element takes at least one byte so a corrupted length can not allocate more than
+        // the size of the input
+        let mut values = Vec::with_capacity(len.min(self.input.len()));
+        for _ in 0..len {
+            values.push(self.decode(context)?);
+        }
+        self.depth -= 1;
+        JsonValue::marshal(context, Value::Array(values)).map_err(|err| err.to_string())
+    }
+
+    fn decode_map(
+        &mut self,
+        context: &mut ActiveThread,
+        len: usize,
+    ) -> StdResult<JsonValue, String> {
+        self.enter()?;
+        let mut fields = BTreeMap::new();
+        for _ in 0..len {
+            let key = self.decode_key(context)?;
+            let value = self.decode(context)?;
+            fields.insert(key, value);
+        }
+        self.depth -= 1;
+        JsonValue::marshal(context, Value::Object(fields)).map_err(|err| err.to_string())
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            deserialize => primitive!(1, "std.msgpack.prim.deserialize", deserialize),
+            serialize => primitive!(1, "std.msgpack.prim.serialize", serialize),
+        },
+    )
+}
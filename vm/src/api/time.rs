@@ -0,0 +1,137 @@
+//! Marshalling of `std::time` types to the records defined in `std.time.types`.
+use std::{
+    convert::TryFrom,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    api::{ActiveThread, Getable, Pushable, VmType},
+    base::types::ArcType,
+    thread::Thread,
+    types::VmInt,
+    Error, Result, Variants,
+};
+
+use super::ValueRef;
+
+const NANOS_PER_SEC: VmInt = 1_000_000_000;
+
+fn time_type(vm: &Thread, name: &str) -> ArcType {
+    vm.find_type_info(name)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .clone()
+        .into_type()
+}
+
+fn push_time_record<'vm>(
+    context: &mut ActiveThread<'vm>,
+    secs_field: &str,
+    secs: VmInt,
+    nanos: u32,
+) -> Result<()> {
+    secs.vm_push(context)?;
+    VmInt::from(nanos).vm_push(context)?;
+    let thread = context.thread();
+    let field_names = [
+        thread.global_env().intern(secs_field)?,
+        thread.global_env().intern("nanos")?,
+    ];
+    context.context().push_new_record(2, &field_names)?;
+    Ok(())
+}
+
+fn time_record_fields(vm: &Thread, value: Variants, secs_field: &str) -> (VmInt, VmInt) {
+    match value.as_ref() {
+        ValueRef::Data(data) => {
+            let field = |name| {
+                data.lookup_field(vm, name)
+                    .map(|value| VmInt::from_value(vm, value))
+                    .unwrap_or_else(|| ice!("Time record is missing the `{}` field", name))
+            };
+            (field(secs_field), field("nanos"))
+        }
+        _ => ice!("ValueRef is not a time record"),
+    }
+}
+
+/// Maps to `std.time.types.Duration`
+impl VmType for Duration {
+    type Type = Self;
+    fn make_type(vm: &Thread) -> ArcType {
+        time_type(vm, "std.time.types.Duration")
+    }
+}
+
+/// Fails if the duration has more seconds than fit in an `Int`
+impl<'vm> Pushable<'vm> for Duration {
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        let secs = VmInt::try_from(self.as_secs()).map_err(|_| {
+            Error::Message(format!("Duration of {:?} is too large to be pushed", self))
+        })?;
+        push_time_record(context, "secs", secs, self.subsec_nanos())
+    }
+}
+
+/// Negative durations saturate to zero
+impl<'vm, 'value> Getable<'vm, 'value> for Duration {
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        let (secs, nanos) = time_record_fields(vm, value, "secs");
+        if secs < 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs(secs as u64) + Duration::from_nanos(nanos.max(0) as u64)
+        }
+    }
+}
+
+/// Maps to `std.time.types.SystemTime`
+impl VmType for SystemTime {
+    type Type = Self;
+    fn make_type(vm: &Thread) -> ArcType {
+        time_type(vm, "std.time.types.SystemTime")
+    }
+}
+
+/// Fails if the time is further away from the unix epoch than fits in an `Int` of seconds
+impl<'vm> Pushable<'vm> for SystemTime {
+    fn vm_push(self, context: &mut ActiveThread<'vm>) -> Result<()> {
+        let too_large = || {
+            Error::Message(format!(
+                "{:?} is too far from the unix epoch to be pushed",
+                self
+            ))
+        };
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => (
+                VmInt::try_from(since.as_secs()).map_err(|_| too_large())?,
+                since.subsec_nanos(),
+            ),
+            Err(err) => {
+                // Round towards negative infinity so that `nanos` stays positive
+                let before = err.duration();
+                let secs = VmInt::try_from(before.as_secs()).map_err(|_| too_large())?;
+                match before.subsec_nanos() {
+                    0 => (-secs, 0),
+                    nanos => (-secs - 1, NANOS_PER_SEC as u32 - nanos),
+                }
+            }
+        };
+        push_time_record(context, "secs_since_epoch", secs, nanos)
+    }
+}
+
+impl<'vm, 'value> Getable<'vm, 'value> for SystemTime {
+    impl_getable_simple!();
+
+    fn from_value(vm: &'vm Thread, value: Variants<'value>) -> Self {
+        let (secs, nanos) = time_record_fields(vm, value, "secs_since_epoch");
+        let nanos = Duration::from_nanos(nanos.max(0) as u64);
+        if secs >= 0 {
+            UNIX_EPOCH + Duration::from_secs(secs as u64) + nanos
+        } else {
+            UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + nanos
+        }
+    }
+}
@@ -1,21 +1,27 @@
 use crate::real_std::{
     any::Any,
+    cell::Cell,
     collections::VecDeque,
     fmt,
     marker::PhantomData,
+    pin::Pin,
     slice,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{self, AtomicUsize},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
 use futures::{
     future::{self, Either},
     prelude::*,
-    task::Poll,
+    task::{self, Poll, Waker},
     try_join,
 };
 
 use crate::base::{
+    fnv::FnvMap,
     kind::Kind,
     types::{ArcType, KindedIdent, Type},
 };
@@ -27,7 +33,7 @@ use crate::{
         Pushed, RuntimeResult, Unrooted, VmType, WithVM, IO,
     },
     gc::{self, CloneUnrooted, GcPtr, Trace},
-    stack::{ClosureState, ExternState, State},
+    stack::{ClosureState, ExternState, Stack, State},
     thread::{ActiveThread, ThreadInternal},
     types::VmInt,
     value::{Callable, Userdata, Value, ValueRepr},
@@ -67,6 +73,10 @@ impl<T> Sender<T> {
         unsafe {
             self.queue.lock().unwrap().push_back(value.clone_unrooted());
         }
+        self.thread
+            .global_env()
+            .deadlock_detector
+            .wake(channel_id(&self.queue));
     }
 }
 
@@ -76,6 +86,8 @@ unsafe impl<T> Trace for Receiver<T> {
 
 pub struct Receiver<T> {
     queue: Arc<Mutex<VecDeque<Value>>>,
+    /// Where in the gluon code the channel was created, if it is known
+    location: Option<Arc<str>>,
     _element_type: PhantomData<T>,
 }
 
@@ -96,6 +108,226 @@ impl<T> Receiver<T> {
     }
 }
 
+/// Identifies a channel by the address of its queue
+fn channel_id(queue: &Arc<Mutex<VecDeque<Value>>>) -> usize {
+    Arc::as_ptr(queue) as usize
+}
+
+fn thread_id(thread: &Thread) -> usize {
+    thread as *const Thread as usize
+}
+
+/// A receive which waits for a value to be sent
+struct BlockedReceiver {
+    id: usize,
+    channel: usize,
+    thread: usize,
+    /// Where in the gluon code the receive was made
+    location: String,
+    waker: Waker,
+}
+
+#[derive(Default)]
+struct Blocked {
+    /// How many suspended executions each thread has
+    executing: FnvMap<usize, usize>,
+    receivers: Vec<BlockedReceiver>,
+    /// The error for each receive which was part of a deadlock and has not yet been resumed
+    deadlocked: FnvMap<usize, String>,
+    next_id: usize,
+}
+
+impl Blocked {
+    /// Fails every blocked receive if all executing threads are waiting on a receive, as no
+    /// thread remains which could send the values they are waiting for
+    fn check_deadlock(&mut self) {
+        let receivers = &self.receivers;
+        let deadlocked = !self.executing.is_empty()
+            && self
+                .executing
+                .keys()
+                .all(|&thread| receivers.iter().any(|receiver| receiver.thread == thread));
+        if !deadlocked {
+            return;
+        }
+
+        let executing = &self.executing;
+        let (deadlocked, rest) = self
+            .receivers
+            .drain(..)
+            .partition::<Vec<_>, _>(|receiver| executing.contains_key(&receiver.thread));
+        self.receivers = rest;
+
+        let mut message =
+            "Deadlock: every thread is blocked receiving from a channel. Blocked receivers:"
+                .to_string();
+        for receiver in &deadlocked {
+            message.push_str("\n    ");
+            message.push_str(&receiver.location);
+        }
+        for receiver in deadlocked {
+            self.deadlocked.insert(receiver.id, message.clone());
+            receiver.waker.wake();
+        }
+    }
+}
+
+thread_local! {
+    /// How many executions are running on this OS thread, including nested ones
+    static RUNNING: Cell<usize> = Cell::new(0);
+}
+
+/// Tracks which threads are suspended while executing and which of them are blocked on a
+/// channel receive (`std.channel.recv_wait`), so that a receive which can never complete fails
+/// with an error instead of leaving the future which runs the vm pending forever.
+///
+/// The check is done whenever the vm stops running on an OS thread. If no thread is running at
+/// that point and every suspended thread is blocked on a receive then none of them can be woken
+/// by a send. Threads which are only resumed as coroutines (`std.thread.resume`) are not
+/// considered to be suspended.
+#[derive(Default)]
+pub(crate) struct DeadlockDetector {
+    /// How many executions are running, over all OS threads
+    running: AtomicUsize,
+    blocked: Mutex<Blocked>,
+}
+
+impl DeadlockDetector {
+    fn check(&self) {
+        if RUNNING.with(|running| running.get()) != 0 {
+            return;
+        }
+        let mut blocked = self.blocked.lock().unwrap();
+        if self.running.load(atomic::Ordering::SeqCst) == 0 {
+            blocked.check_deadlock();
+        }
+    }
+
+    fn enter(&self, thread: &Thread) {
+        {
+            let mut blocked = self.blocked.lock().unwrap();
+            *blocked.executing.entry(thread_id(thread)).or_insert(0) += 1;
+        }
+        self.check();
+    }
+
+    fn exit(&self, thread: &Thread) {
+        {
+            let mut blocked = self.blocked.lock().unwrap();
+            let id = thread_id(thread);
+            match blocked.executing.get_mut(&id) {
+                Some(count) if *count > 1 => *count -= 1,
+                _ => {
+                    blocked.executing.remove(&id);
+                }
+            }
+        }
+        // The thread may have been the only one which could send to the blocked receivers
+        self.check();
+    }
+
+    /// Wakes the receivers waiting on `channel`
+    fn wake(&self, channel: usize) {
+        let mut blocked = self.blocked.lock().unwrap();
+        let mut i = 0;
+        while i < blocked.receivers.len() {
+            if blocked.receivers[i].channel == channel {
+                blocked.receivers.swap_remove(i).waker.wake();
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    fn remove(&self, id: usize) {
+        let mut blocked = self.blocked.lock().unwrap();
+        blocked.receivers.retain(|receiver| receiver.id != id);
+        blocked.deadlocked.remove(&id);
+    }
+}
+
+/// Marks a thread as suspended while executing until it is dropped (see `Execute`)
+pub(crate) struct Executing(RootedThread);
+
+impl Executing {
+    pub(crate) fn new(thread: RootedThread) -> Self {
+        thread.global_env().deadlock_detector.enter(&thread);
+        Executing(thread)
+    }
+}
+
+impl Drop for Executing {
+    fn drop(&mut self) {
+        self.0.global_env().deadlock_detector.exit(&self.0);
+    }
+}
+
+/// Marks the vm as running on the current OS thread until it is dropped
+pub(crate) struct Running<'a>(&'a DeadlockDetector);
+
+impl<'a> Running<'a> {
+    pub(crate) fn new(thread: &'a Thread) -> Self {
+        let detector = &thread.global_env().deadlock_detector;
+        detector.running.fetch_add(1, atomic::Ordering::SeqCst);
+        RUNNING.with(|running| running.set(running.get() + 1));
+        Running(detector)
+    }
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        RUNNING.with(|running| running.set(running.get() - 1));
+        self.0.running.fetch_sub(1, atomic::Ordering::SeqCst);
+        self.0.check();
+    }
+}
+
+struct RecvWait {
+    thread: RootedThread,
+    queue: Arc<Mutex<VecDeque<Value>>>,
+    id: usize,
+    location: String,
+}
+
+impl Future for RecvWait {
+    type Output = Result<Value, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let mut blocked = self
+            .thread
+            .global_env()
+            .deadlock_detector
+            .blocked
+            .lock()
+            .unwrap();
+        if let Some(err) = blocked.deadlocked.remove(&self.id) {
+            return Poll::Ready(Err(err));
+        }
+        // Check the queue while holding the lock so a value sent after the check is guaranteed to
+        // see this receiver as blocked and wake it
+        if let Some(value) = self.queue.lock().unwrap().pop_front() {
+            return Poll::Ready(Ok(value));
+        }
+
+        let id = self.id;
+        blocked.receivers.retain(|receiver| receiver.id != id);
+        blocked.receivers.push(BlockedReceiver {
+            id,
+            channel: channel_id(&self.queue),
+            thread: thread_id(&self.thread),
+            location: self.location.clone(),
+            waker: cx.waker().clone(),
+        });
+        Poll::Pending
+    }
+}
+
+impl Drop for RecvWait {
+    fn drop(&mut self) {
+        self.thread.global_env().deadlock_detector.remove(self.id);
+    }
+}
+
 impl<T: VmType> VmType for Sender<T>
 where
     T::Type: Sized,
@@ -150,6 +382,7 @@ pub type ChannelRecord<S, R> = record_type!(sender => S, receiver => R);
 /// FIXME The dummy `a` argument should not be needed to ensure that the channel can only be used
 /// with a single type
 fn channel(WithVM { vm, .. }: WithVM<Generic<A>>) -> IO<ChannelRecord<Sender<A>, Receiver<A>>> {
+    let location = current_location(&vm.context().stack).map(Arc::from);
     let sender = Sender {
         thread: unsafe { GcPtr::from_raw(vm) },
         queue: Arc::new(Mutex::new(VecDeque::new())),
@@ -157,6 +390,7 @@ fn channel(WithVM { vm, .. }: WithVM<Generic<A>>) -> IO<ChannelRecord<Sender<A>,
     };
     let receiver = Receiver {
         queue: sender.queue.clone(),
+        location,
         _element_type: PhantomData,
     };
     IO::Value(record_no_decl!(sender => sender, receiver => receiver))
@@ -166,6 +400,75 @@ fn recv(receiver: &Receiver<A>) -> IO<Result<Unrooted<A>, ()>> {
     IO::Value(receiver.try_recv().map_err(|_| ()).map(Unrooted::from))
 }
 
+/// Returns the location of the gluon code which runs the current primitive.
+///
+/// An `IO` action is run by `std.io.prim.flat_map` after the code which created it has returned,
+/// so unless the innermost closure is still running the location is the start of the closure which
+/// `flat_map` continues with.
+fn current_location(stack: &Stack) -> Option<String> {
+    let frames = stack.get_frames();
+    let values = stack.get_values();
+    frames.iter().enumerate().rev().find_map(|(i, frame)| {
+        let running = match &frame.state {
+            State::Closure(state) => Some((&state.closure.function, state.instruction_index)),
+            _ => None,
+        };
+        let end = frames
+            .get(i + 1)
+            .map_or(values.len(), |next| next.offset as usize);
+        let continuations =
+            values[frame.offset as usize..end]
+                .iter()
+                .filter_map(|value| match value.get_repr() {
+                    ValueRepr::Closure(closure) => Some((&closure.function, 0)),
+                    _ => None,
+                });
+        running
+            .into_iter()
+            .chain(continuations)
+            .find_map(|(function, instruction_index)| {
+                let line = function.debug_info.source_map.line(instruction_index)?;
+                Some(format!(
+                    "{}:Line {}",
+                    function.name.declared_name(),
+                    line.number()
+                ))
+            })
+    })
+}
+
+fn recv_wait(
+    WithVM {
+        vm,
+        value: receiver,
+    }: WithVM<&Receiver<A>>,
+) -> impl Future<Output = IO<Unrooted<A>>> {
+    let location = {
+        let waiting = current_location(&vm.context().stack);
+        format!(
+            "Receiver of the channel created at `{}`, waiting at `{}`",
+            receiver.location.as_deref().unwrap_or("<unknown>"),
+            waiting.as_deref().unwrap_or("<unknown>")
+        )
+    };
+
+    let id = {
+        let mut blocked = vm.global_env().deadlock_detector.blocked.lock().unwrap();
+        blocked.next_id += 1;
+        blocked.next_id
+    };
+    RecvWait {
+        thread: vm.root_thread(),
+        queue: receiver.queue.clone(),
+        id,
+        location,
+    }
+    .map(|result| match result {
+        Ok(value) => IO::Value(Unrooted::from(value)),
+        Err(err) => IO::Exception(err),
+    })
+}
+
 fn send(sender: &Sender<A>, value: Generic<A>) -> IO<Result<(), ()>> {
     let value = match sender
         .thread
@@ -430,6 +733,7 @@ pub fn load_channel<'vm>(vm: &'vm Thread) -> VmResult<ExternModule> {
             type Receiver a => Sender<A>,
             channel => primitive!(1, std::channel::channel),
             recv => primitive!(1, std::channel::recv),
+            recv_wait => primitive!(1, async fn std::channel::recv_wait),
             send => primitive!(2, std::channel::send),
         },
     )
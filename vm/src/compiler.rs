@@ -1,4 +1,7 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
 
 use crate::base::{
     ast::{DisplayEnv, Typed, TypedIdent},
@@ -15,6 +18,7 @@ use crate::{
     core::{self, is_primitive, CExpr, Expr, Literal, Pattern},
     interner::InternedStr,
     source_map::{LocalMap, SourceMap},
+    stack_map::{StackMap, StackMaps},
     types::*,
     vm::GlobalVmState,
     Error, Result,
@@ -36,6 +40,30 @@ enum FieldAccess {
     Index(VmIndex),
 }
 
+/// The number of constructor alternatives a match needs before it is compiled to a `Switch`
+const MIN_SWITCH_ALTERNATIVES: usize = 3;
+
+/// Displays the decision made by a `Switch` for each constructor of `typ`
+struct DisplaySwitch<'a> {
+    typ: &'a ArcType,
+    targets: &'a [Option<VmIndex>],
+}
+
+impl fmt::Display for DisplaySwitch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (tag, field) in self.typ.row_iter().enumerate() {
+            if tag != 0 {
+                write!(f, ", ")?;
+            }
+            match self.targets.get(tag).cloned().flatten() {
+                Some(target) => write!(f, "{} -> {}", field.name.declared_name(), target)?,
+                None => write!(f, "{} -> default", field.name.declared_name())?,
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(
@@ -151,8 +179,14 @@ pub struct CompiledFunction {
     )]
     pub records: Vec<Vec<Symbol>>,
 
+    /// Maps tags to instruction indexes for each `Switch` instruction
+    pub jump_tables: Vec<Vec<VmIndex>>,
+
     #[cfg_attr(feature = "serde_derive_state", serde(state))]
     pub debug_info: DebugInfo,
+
+    /// Describes which stack slots may contain garbage collected pointers at each safepoint
+    pub stack_maps: StackMaps,
 }
 
 impl From<CompiledFunction> for CompiledModule {
@@ -175,12 +209,14 @@ impl CompiledFunction {
             inner_functions: Vec::new(),
             strings: Vec::new(),
             records: Vec::new(),
+            jump_tables: Vec::new(),
             debug_info: DebugInfo {
                 source_map: SourceMap::new(),
                 local_map: LocalMap::new(),
                 upvars: Vec::new(),
                 source_name: source_name,
             },
+            stack_maps: StackMaps::new(),
         }
     }
 }
@@ -289,6 +325,13 @@ impl FunctionEnv {
             return;
         }
 
+        if instruction.is_safepoint() {
+            let map = self.stack_map();
+            self.function
+                .stack_maps
+                .emit(self.function.instructions.len(), map);
+        }
+
         let adjustment = instruction.adjust();
         debug!("{:?} {} {}", instruction, self.stack_size, adjustment);
         if adjustment > 0 {
@@ -307,6 +350,20 @@ impl FunctionEnv {
         }
     }
 
+    /// Describes the current stack frame. Slots bound to variables of a primitive type can only
+    /// contain unboxed values, all other slots are assumed to contain pointers.
+    fn stack_map(&mut self) -> StackMap {
+        let mut map = StackMap::new(self.stack_size);
+        for (_, vars) in self.stack.iter_mut() {
+            for &(index, ref typ) in vars.iter() {
+                if index < self.stack_size && is_unboxed(typ) {
+                    map.set_scalar(index);
+                }
+            }
+        }
+        map
+    }
+
     fn increase_stack(&mut self, adjustment: VmIndex) {
         use std::cmp::max;
 
@@ -412,6 +469,16 @@ impl FunctionEnv {
     }
 }
 
+fn is_unboxed(typ: &ArcType) -> bool {
+    match **typ {
+        Type::Builtin(BuiltinType::Int)
+        | Type::Builtin(BuiltinType::Float)
+        | Type::Builtin(BuiltinType::Byte)
+        | Type::Builtin(BuiltinType::Char) => true,
+        _ => false,
+    }
+}
+
 pub trait CompilerEnv: TypeEnv {
     fn find_var(&self, id: &Symbol) -> Option<(Variable<Symbol>, ArcType)>;
 }
@@ -557,6 +624,32 @@ impl<'a> Compiler<'a> {
         self.find_resolved_tag(&typ, constructor)
     }
 
+    /// Returns the tag of each alternative if `alts` can be compiled to a `Switch`, that is if they
+    /// match on at least `MIN_SWITCH_ALTERNATIVES` constructors of a variant with indexed tags,
+    /// optionally followed by a catch all alternative.
+    fn switch_tags(&self, typ: &ArcType, alts: &[core::Alternative]) -> Option<Vec<Option<VmTag>>> {
+        let (last, init) = alts.split_last()?;
+        let constructors = match last.pattern {
+            Pattern::Ident(_) => init,
+            _ => alts,
+        };
+        if constructors.len() < MIN_SWITCH_ALTERNATIVES {
+            return None;
+        }
+        let mut tags = constructors
+            .iter()
+            .map(|alt| match alt.pattern {
+                Pattern::Constructor(ref id, _) => match self.find_resolved_tag(typ, &id.name)? {
+                    FieldAccess::Index(tag) => Some(Some(tag)),
+                    FieldAccess::Name => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        tags.resize(alts.len(), None);
+        Some(tags)
+    }
+
     fn find_resolved_tag(&self, typ: &ArcType, constructor: &Symbol) -> Option<FieldAccess> {
         match **typ {
             Type::Variant(ref row) => {
@@ -790,10 +883,25 @@ impl<'a> Compiler<'a> {
                 let mut start_jumps = Vec::new();
                 let typ = alts[0].pattern.env_type_of(self);
                 let typ = resolve::remove_aliases_cow(self, &mut NullInterner, typ.remove_forall());
+                // Matches on several constructors jump directly to the matching alternative
+                // through a jump table instead of testing each tag in turn
+                let switch = self.switch_tags(&typ, alts).map(|tags| {
+                    let table = function.function.jump_tables.len() as VmIndex;
+                    function.function.jump_tables.push(Vec::new());
+                    let switch_index = function.function.instructions.len();
+                    function.emit(Switch(table));
+                    (table, switch_index, tags)
+                });
+                // Jump targets for each tag of the switch, only the first alternative matching a
+                // tag is reachable
+                let mut switch_targets: Vec<Option<VmIndex>> = Vec::new();
                 // Emit a TestTag + Jump instuction for each alternative which jumps to the
                 // alternatives code if TestTag is sucessesful
                 for alt in alts.iter() {
                     match alt.pattern {
+                        Pattern::Constructor(_, _) if switch.is_some() => {
+                            start_jumps.push(function.function.instructions.len());
+                        }
                         Pattern::Constructor(ref id, _) => {
                             let tag = self.find_resolved_tag(&typ, &id.name).unwrap_or_else(|| {
                                 ice!(
@@ -871,12 +979,21 @@ impl<'a> Compiler<'a> {
                 // Indexes for each alternative from the end of the alternatives code to code
                 // after the alternative
                 let mut end_jumps = Vec::new();
-                for (alt, &start_index) in alts.iter().zip(start_jumps.iter()) {
+                for (i, (alt, &start_index)) in alts.iter().zip(start_jumps.iter()).enumerate() {
                     function.stack.enter_scope();
                     match alt.pattern {
                         Pattern::Constructor(_, ref args) => {
-                            function.function.instructions[start_index] =
-                                CJump(function.function.instructions.len() as VmIndex);
+                            let start = function.function.instructions.len() as VmIndex;
+                            match switch {
+                                Some((_, _, ref tags)) => {
+                                    let tag = tags[i].expect("Constructor tag") as usize;
+                                    if switch_targets.len() <= tag {
+                                        switch_targets.resize(tag + 1, None);
+                                    }
+                                    switch_targets[tag].get_or_insert(start);
+                                }
+                                None => function.function.instructions[start_index] = CJump(start),
+                            }
                             function.emit(Split);
                             for arg in args.iter() {
                                 function.push_stack_var(self, arg.name.clone(), arg.typ.clone());
@@ -908,6 +1025,23 @@ impl<'a> Compiler<'a> {
                     function.function.instructions[index] =
                         Jump(function.function.instructions.len() as VmIndex);
                 }
+                if let Some((table, switch_index, _)) = switch {
+                    // Tags without an alternative continue after the switch instruction
+                    let fallthrough = switch_index as VmIndex + 1;
+                    let table = &mut function.function.jump_tables[table as usize];
+                    table.extend(
+                        switch_targets
+                            .iter()
+                            .map(|target| target.unwrap_or(fallthrough)),
+                    );
+                    debug!(
+                        "Match compiled to switch: {}",
+                        DisplaySwitch {
+                            typ: &typ,
+                            targets: &switch_targets,
+                        }
+                    );
+                }
             }
             Expr::Data(ref id, exprs, _) => {
                 for expr in exprs {
@@ -1033,12 +1167,25 @@ impl<'a> Compiler<'a> {
             Pattern::Ident(ref name) => {
                 function.new_stack_var(self, name.name.clone(), pattern_type.clone());
             }
-            Pattern::Record { ref fields, .. } => {
+            Pattern::Record {
+                typ: ref record_type,
+                ref fields,
+            } => {
                 let typ = resolve::remove_aliases(
                     self,
                     &mut NullInterner,
                     pattern_type.remove_forall().clone(),
                 );
+                let typ = match **typ.remove_forall() {
+                    Type::Record(_) => typ,
+                    // The body of an inlined function may still have the type it had in the
+                    // function, such as `a`, so fall back to the type stored in the pattern
+                    _ => resolve::remove_aliases(
+                        self,
+                        &mut NullInterner,
+                        record_type.remove_forall().clone(),
+                    ),
+                };
                 let typ = typ.remove_forall();
                 match **typ {
                     Type::Record(_) => {
@@ -2575,21 +2575,23 @@ pub(crate) mod tests {
         let mut costs = Default::default();
 
         let global = with_allocator(|global_allocator| {
-            let expr = ExprParser::new()
-                .parse(
-                    symbols,
-                    &global_allocator,
-                    "rec let f x y = (#Int+) x y in { f }",
-                )
-                .unwrap_or_else(|err| panic!("{}", err));
-            pure_symbols = Some(crate::core::purity::purity(&expr));
+            let expr = &*global_allocator.arena.alloc(
+                ExprParser::new()
+                    .parse(
+                        symbols,
+                        &global_allocator,
+                        "rec let f x y = (#Int+) x y in { f }",
+                    )
+                    .unwrap_or_else(|err| panic!("{}", err)),
+            );
+            pure_symbols = Some(crate::core::purity::purity(expr));
 
             let mut dep_graph = dead_code::DepGraph::default();
-            let _: Vec<_> = dep_graph.used_bindings(&expr);
+            let _: Vec<_> = dep_graph.used_bindings(expr);
             let cyclic_bindings: FnvSet<_> = dep_graph.cycles().flat_map(|cycle| cycle).collect();
 
-            costs = crate::core::costs::analyze_costs(&cyclic_bindings, &expr);
-            crate::core::freeze_expr(global_allocator, global_allocator.arena.alloc(expr))
+            costs = crate::core::costs::analyze_costs(&cyclic_bindings, expr);
+            crate::core::freeze_expr(global_allocator, expr)
         });
 
         let info = Arc::new(OptimizerInfo {
@@ -28,7 +28,6 @@ pub mod costs;
 pub mod dead_code;
 pub mod interpreter;
 pub mod optimize;
-#[cfg(feature = "test")]
 mod pretty;
 pub mod purity;
 
@@ -167,7 +166,6 @@ pub enum Expr<'a> {
     Cast(CExpr<'a>, ArcType),
 }
 
-#[cfg(feature = "test")]
 impl fmt::Display for Pattern {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let arena = ::pretty::Arena::new();
@@ -176,14 +174,6 @@ impl fmt::Display for Pattern {
         write!(f, "{}", ::std::str::from_utf8(&s).expect("utf-8"))
     }
 }
-#[cfg(not(feature = "test"))]
-impl fmt::Display for Pattern {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-#[cfg(feature = "test")]
 impl<'a> fmt::Display for Expr<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use crate::core::pretty::Prec;
@@ -197,13 +187,6 @@ impl<'a> fmt::Display for Expr<'a> {
     }
 }
 
-#[cfg(not(feature = "test"))]
-impl<'a> fmt::Display for Expr<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
 impl Default for &'static Expr<'static> {
     fn default() -> Self {
         static X: Expr<'static> =
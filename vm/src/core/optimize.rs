@@ -2,7 +2,7 @@ use std::{marker::PhantomData, sync::Arc};
 
 use crate::base::{
     ast::TypedIdent,
-    fnv::FnvSet,
+    fnv::{FnvMap, FnvSet},
     merge::{merge, merge_collect, merge_fn, merge_iter},
     pos,
     symbol::Symbol,
@@ -10,12 +10,29 @@ use crate::base::{
 };
 
 use crate::core::{
+    costs::Cost,
     dead_code::{self},
-    interpreter::Global,
-    Allocator, Alternative, ArenaAllocatable, ArenaExt, CExpr, Closure, CoreExpr, Expr, LetBinding,
-    Named, Pattern,
+    interpreter::{Global, OptimizerInfo},
+    is_primitive, Allocator, Alternative, ArenaAllocatable, ArenaExt, CExpr, Closure, CoreExpr,
+    Expr, LetBinding, Named, Pattern,
 };
 
+/// Controls which optimizations `optimize` performs
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum OptimizationLevel {
+    /// Removes dead code and records which are destructured immediately after being constructed
+    Basic,
+    /// Also inlines calls to small, pure functions from imported modules and simplifies the
+    /// result with beta reduction and case of case
+    Inline,
+}
+
+impl Default for OptimizationLevel {
+    fn default() -> Self {
+        OptimizationLevel::Inline
+    }
+}
+
 pub trait OptimizeEnv: TypeEnv {
     fn find_expr(&self, id: &Symbol) -> Option<Global<CoreExpr>>;
 }
@@ -281,12 +298,493 @@ fn optimize_unnecessary_allocation<'a>(
     optimizer.visit_expr(expr).unwrap_or(expr)
 }
 
+/// Imported functions are only inlined if their cost, as computed by `costs::analyze_costs`, is at
+/// most this large
+const INLINE_COST_LIMIT: Cost = 40;
+
+#[derive(Clone)]
+enum Imported {
+    Module(Symbol),
+    Field(Symbol, Symbol),
+}
+
+/// Inlines calls to small, pure functions which are projected out of an imported module, using the
+/// `OptimizerInfo` recorded when that module was optimized
+struct InlineImported<'a, 'e> {
+    allocator: &'a Allocator<'a>,
+    env: &'e dyn OptimizeEnv<Type = ArcType>,
+    imported: FnvMap<Symbol, Imported>,
+}
+
+impl<'a> InlineImported<'a, '_> {
+    fn resolve(&self, name: &Symbol) -> Option<Imported> {
+        if name.is_global() {
+            Some(Imported::Module(name.clone()))
+        } else {
+            self.imported.get(name).cloned()
+        }
+    }
+
+    /// Resolves `f` if it refers to a field of an imported module, either through a binding or
+    /// directly through a projection such as `module.f`
+    fn resolve_function(&self, f: CExpr) -> Option<(Symbol, Symbol)> {
+        match *f {
+            Expr::Ident(ref id, _) => match self.imported.get(&id.name)? {
+                Imported::Field(module, field) => Some((module.clone(), field.clone())),
+                Imported::Module(_) => None,
+            },
+            Expr::Match(Expr::Ident(ref id, _), alts) if alts.len() == 1 => {
+                match (self.resolve(&id.name)?, &alts[0].pattern, alts[0].expr) {
+                    (
+                        Imported::Module(module),
+                        Pattern::Record { fields, .. },
+                        Expr::Ident(projected, _),
+                    ) if fields.len() == 1 && fields[0].0 == *projected => {
+                        Some((module, projected.name.clone()))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn inline_call(&self, f: CExpr, args: &'a [Expr<'a>]) -> Option<CExpr<'a>> {
+        let (module, field) = self.resolve_function(f)?;
+        let global = self.env.find_expr(&module)?;
+        let module_expr = global.value.expr();
+
+        let function = match *skip_bindings(module_expr) {
+            Expr::Data(ref id, exprs, _) => id
+                .typ
+                .row_iter()
+                .zip(exprs)
+                .find(|(f, _)| f.name.declared_name() == field.declared_name())
+                .map(|(_, expr)| expr)?,
+            _ => return None,
+        };
+        let function = match *function {
+            Expr::Ident(ref id, _) => &id.name,
+            _ => return None,
+        };
+
+        let closure = find_closure(module_expr, function)?;
+        if closure.args.len() > args.len() || !is_inlinable(&global.info, closure) {
+            return None;
+        }
+        trace!("Inlining {} from {}", function, module);
+
+        let body = DifferentLifetime::new(self.allocator).produce(closure.expr);
+        Some(bind_arguments(self.allocator, &closure.args, args, body))
+    }
+}
+
+/// Binds each parameter to its argument with a `let` around `body`. Any arguments left over are
+/// passed to the result of `body`
+fn bind_arguments<'a>(
+    allocator: &'a Allocator<'a>,
+    params: &[TypedIdent<Symbol>],
+    args: &'a [Expr<'a>],
+    body: CExpr<'a>,
+) -> CExpr<'a> {
+    let (args, rest) = args.split_at(params.len());
+    let body = params
+        .iter()
+        .zip(args)
+        .rev()
+        .fold(body, |body, (param, arg)| {
+            allocator.arena.alloc(Expr::Let(
+                allocator.let_binding_arena.alloc(LetBinding {
+                    name: param.clone(),
+                    expr: Named::Expr(arg),
+                    span_start: arg.span().start(),
+                }),
+                body,
+            ))
+        });
+    if rest.is_empty() {
+        body
+    } else {
+        // Functions such as `let (+) ?num = num.(+)` return another function which takes the
+        // remaining arguments
+        allocator.arena.alloc(Expr::Call(body, rest))
+    }
+}
+
+impl<'a> Visitor<'a, 'a> for InlineImported<'a, '_> {
+    type Producer = SameLifetime<'a>;
+
+    fn visit_expr(&mut self, expr: &'a Expr<'a>) -> Option<&'a Expr<'a>> {
+        match *expr {
+            Expr::Let(bind, _) => {
+                if let Named::Expr(Expr::Ident(id, _)) = bind.expr {
+                    if let Some(imported) = self.resolve(&id.name) {
+                        self.imported.insert(bind.name.name.clone(), imported);
+                    }
+                }
+                walk_expr_alloc(self, expr)
+            }
+            Expr::Match(Expr::Ident(id, _), alts) if alts.len() == 1 => {
+                if let (Some(Imported::Module(module)), Pattern::Record { fields, .. }) =
+                    (self.resolve(&id.name), &alts[0].pattern)
+                {
+                    for (field, bind) in fields {
+                        self.imported.insert(
+                            bind.as_ref().unwrap_or(&field.name).clone(),
+                            Imported::Field(module.clone(), field.name.clone()),
+                        );
+                    }
+                }
+                walk_expr_alloc(self, expr)
+            }
+            Expr::Call(..) => {
+                let new_expr = walk_expr_alloc(self, expr);
+                match *new_expr.unwrap_or(expr) {
+                    Expr::Call(f, args) => self.inline_call(f, args).or(new_expr),
+                    _ => new_expr,
+                }
+            }
+            _ => walk_expr_alloc(self, expr),
+        }
+    }
+
+    fn detach_allocator(&self) -> Option<&'a Allocator<'a>> {
+        Some(self.allocator)
+    }
+}
+
+/// Skips the bindings at the top of a module to find the expression it evaluates to
+fn skip_bindings(mut expr: CExpr) -> CExpr {
+    loop {
+        match *expr {
+            Expr::Let(_, body) => expr = body,
+            Expr::Match(_, alts) if alts.len() == 1 => expr = alts[0].expr,
+            _ => break expr,
+        }
+    }
+}
+
+fn find_closure<'b>(mut expr: CExpr<'b>, name: &Symbol) -> Option<&'b Closure<'b>> {
+    loop {
+        match *expr {
+            Expr::Let(bind, body) => {
+                if let Named::Recursive(closures) = &bind.expr {
+                    if let Some(closure) = closures.iter().find(|c| c.name.name == *name) {
+                        return Some(closure);
+                    }
+                }
+                expr = body;
+            }
+            Expr::Match(_, alts) if alts.len() == 1 => expr = alts[0].expr,
+            _ => return None,
+        }
+    }
+}
+
+fn is_inlinable(info: &OptimizerInfo, closure: &Closure) -> bool {
+    // Recursive functions are given the maximum cost so they are never inlined
+    info.costs.cost(&closure.name.name) <= INLINE_COST_LIMIT
+        && info.pure_symbols.pure_call(&closure.name.name)
+        && {
+            let mut bound = closure.args.iter().map(|arg| arg.name.clone()).collect();
+            // The body is copied into another module so it may only refer to its arguments,
+            // globals and primitives
+            is_closed(&mut bound, closure.expr)
+        }
+}
+
+fn is_closed(bound: &mut FnvSet<Symbol>, expr: CExpr) -> bool {
+    match *expr {
+        Expr::Ident(ref id, _) => {
+            id.name.is_global() || is_primitive(&id.name) || bound.contains(&id.name)
+        }
+        Expr::Const(..) => true,
+        Expr::Call(f, args) => is_closed(bound, f) && args.iter().all(|arg| is_closed(bound, arg)),
+        Expr::Data(_, args, _) => args.iter().all(|arg| is_closed(bound, arg)),
+        Expr::Cast(expr, _) => is_closed(bound, expr),
+        Expr::Let(bind, body) => {
+            let closed = match &bind.expr {
+                Named::Expr(expr) => is_closed(bound, expr),
+                Named::Recursive(closures) => {
+                    bound.extend(closures.iter().map(|c| c.name.name.clone()));
+                    closures.iter().all(|closure| {
+                        bound.extend(closure.args.iter().map(|arg| arg.name.clone()));
+                        is_closed(bound, closure.expr)
+                    })
+                }
+            };
+            bound.insert(bind.name.name.clone());
+            closed && is_closed(bound, body)
+        }
+        Expr::Match(scrutinee, alts) => {
+            is_closed(bound, scrutinee)
+                && alts.iter().all(|alt| {
+                    match &alt.pattern {
+                        Pattern::Ident(id) => {
+                            bound.insert(id.name.clone());
+                        }
+                        Pattern::Record { fields, .. } => bound.extend(
+                            fields
+                                .iter()
+                                .map(|(field, bind)| bind.as_ref().unwrap_or(&field.name).clone()),
+                        ),
+                        Pattern::Constructor(_, args) => {
+                            bound.extend(args.iter().map(|arg| arg.name.clone()))
+                        }
+                        Pattern::Literal(_) => (),
+                    }
+                    is_closed(bound, alt.expr)
+                })
+        }
+    }
+}
+
+fn inline_imported<'a>(
+    allocator: &'a Allocator<'a>,
+    env: &dyn OptimizeEnv<Type = ArcType>,
+    expr: &'a Expr<'a>,
+) -> &'a Expr<'a> {
+    let mut inliner = InlineImported {
+        allocator,
+        env,
+        imported: Default::default(),
+    };
+    inliner.visit_expr(expr).unwrap_or(expr)
+}
+
+/// Simplifies expressions which can be reduced by only looking at the expression itself.
+///
+/// Beta reduction of functions which are called immediately
+///
+/// (rec let f x = body in f) y
+///
+/// to
+///
+/// let x = y in body
+///
+/// Case of case, which removes the allocation of data which is immediately matched on
+///
+/// match (match x with | A -> C | B -> D end) with
+/// | C -> e1
+/// | D -> e2
+/// end
+///
+/// to
+///
+/// match x with
+/// | A -> e1
+/// | B -> e2
+/// end
+struct Simplify<'a> {
+    allocator: &'a Allocator<'a>,
+}
+
+impl<'a> Simplify<'a> {
+    fn beta_reduce(&mut self, f: CExpr<'a>, args: &'a [Expr<'a>]) -> Option<CExpr<'a>> {
+        let closure = match *f {
+            Expr::Let(
+                LetBinding {
+                    expr: Named::Recursive(closures),
+                    ..
+                },
+                Expr::Ident(id, _),
+            ) if closures.len() == 1 && closures[0].name.name == id.name => &closures[0],
+            _ => return None,
+        };
+        if closure.args.len() > args.len() || occurs(&closure.name.name, closure.expr) {
+            return None;
+        }
+        trace!("Beta reducing {}", closure.name.name);
+
+        let body = bind_arguments(self.allocator, &closure.args, args, closure.expr);
+        Some(self.visit_expr(body).unwrap_or(body))
+    }
+
+    fn case_of_case(
+        &mut self,
+        scrutinee: CExpr<'a>,
+        outer_alts: &'a [Alternative<'a>],
+    ) -> Option<CExpr<'a>> {
+        let (inner_scrutinee, inner_alts) = match *scrutinee {
+            Expr::Match(inner_scrutinee, inner_alts) => (inner_scrutinee, inner_alts),
+            Expr::Let(bind, body) => {
+                // Bindings in the scrutinee are moved out of the match if that exposes a match
+                // which can be simplified
+                let expr = self.case_of_case(body, outer_alts)?;
+                return Some(self.allocator.arena.alloc(Expr::Let(bind, expr)));
+            }
+            _ => return None,
+        };
+
+        let selected = inner_alts
+            .iter()
+            .map(|inner| select_alternative(inner.expr, outer_alts))
+            .collect::<Option<Vec<_>>>()?;
+
+        // Alternatives which are selected by several of the inner alternatives would be
+        // duplicated so only do that if they are trivial
+        let mut uses = vec![0; outer_alts.len()];
+        for &index in &selected {
+            uses[index] += 1;
+        }
+        if uses
+            .iter()
+            .zip(outer_alts)
+            .any(|(&uses, alt)| uses > 1 && !is_trivial(alt.expr))
+        {
+            return None;
+        }
+        trace!("Case of case with {} alternatives", inner_alts.len());
+
+        let allocator = self.allocator;
+        let alts = allocator
+            .alternative_arena
+            .alloc_fixed(inner_alts.iter().zip(selected).map(|(inner, index)| {
+                let outer = &outer_alts[index];
+                let expr = match (&outer.pattern, inner.expr) {
+                    (Pattern::Constructor(_, params), Expr::Data(_, args, _)) => {
+                        bind_arguments(allocator, params, args, outer.expr)
+                    }
+                    (Pattern::Ident(id), data) => allocator.arena.alloc(Expr::Let(
+                        allocator.let_binding_arena.alloc(LetBinding {
+                            name: id.clone(),
+                            expr: Named::Expr(data),
+                            span_start: data.span().start(),
+                        }),
+                        outer.expr,
+                    )),
+                    _ => unreachable!(),
+                };
+                Alternative {
+                    pattern: inner.pattern.clone(),
+                    expr,
+                }
+            }));
+        let expr = &*allocator.arena.alloc(Expr::Match(inner_scrutinee, alts));
+        Some(self.visit_expr(expr).unwrap_or(expr))
+    }
+}
+
+impl<'a> Visitor<'a, 'a> for Simplify<'a> {
+    type Producer = SameLifetime<'a>;
+
+    fn visit_expr(&mut self, expr: &'a Expr<'a>) -> Option<&'a Expr<'a>> {
+        match *expr {
+            Expr::Call(..) => {
+                let new_expr = walk_expr_alloc(self, expr);
+                match *new_expr.unwrap_or(expr) {
+                    Expr::Call(f, args) => self.beta_reduce(f, args).or(new_expr),
+                    _ => new_expr,
+                }
+            }
+            Expr::Match(..) => {
+                let new_expr = walk_expr_alloc(self, expr);
+                match *new_expr.unwrap_or(expr) {
+                    Expr::Match(scrutinee, alts) => self.case_of_case(scrutinee, alts).or(new_expr),
+                    _ => new_expr,
+                }
+            }
+            Expr::Let(..) => {
+                let new_expr = walk_expr_alloc(self, expr);
+                match *new_expr.unwrap_or(expr) {
+                    // let x = match y with ... end
+                    // match x with ... end
+                    Expr::Let(
+                        LetBinding {
+                            name,
+                            expr: Named::Expr(bound),
+                            ..
+                        },
+                        Expr::Match(Expr::Ident(id, _), alts),
+                    ) if id.name == name.name
+                        && !alts.iter().any(|alt| occurs(&name.name, alt.expr)) =>
+                    {
+                        self.case_of_case(bound, alts).or(new_expr)
+                    }
+                    _ => new_expr,
+                }
+            }
+            _ => walk_expr_alloc(self, expr),
+        }
+    }
+
+    fn detach_allocator(&self) -> Option<&'a Allocator<'a>> {
+        Some(self.allocator)
+    }
+}
+
+/// Returns the index of the alternative in `alts` which `expr` matches, if it can be decided
+/// statically
+fn select_alternative(expr: CExpr, alts: &[Alternative]) -> Option<usize> {
+    let (id, args) = match *expr {
+        Expr::Data(ref id, args, _) => (id, args),
+        _ => return None,
+    };
+    for (index, alt) in alts.iter().enumerate() {
+        match &alt.pattern {
+            Pattern::Constructor(ctor, params) if ctor.name.name_eq(&id.name) => {
+                return if params.len() == args.len() {
+                    Some(index)
+                } else {
+                    None
+                };
+            }
+            Pattern::Constructor(..) => (),
+            Pattern::Ident(_) => return Some(index),
+            Pattern::Record { .. } | Pattern::Literal(_) => return None,
+        }
+    }
+    None
+}
+
+fn is_trivial(expr: CExpr) -> bool {
+    match *expr {
+        Expr::Const(..) | Expr::Ident(..) => true,
+        Expr::Data(_, args, _) => args.is_empty(),
+        _ => false,
+    }
+}
+
+/// Returns true if `name` is referred to anywhere in `expr`
+fn occurs(name: &Symbol, expr: CExpr) -> bool {
+    match *expr {
+        Expr::Ident(ref id, _) => id.name == *name,
+        Expr::Const(..) => false,
+        Expr::Call(f, args) => occurs(name, f) || args.iter().any(|arg| occurs(name, arg)),
+        Expr::Data(_, args, _) => args.iter().any(|arg| occurs(name, arg)),
+        Expr::Cast(expr, _) => occurs(name, expr),
+        Expr::Let(bind, body) => {
+            (match &bind.expr {
+                Named::Expr(expr) => occurs(name, expr),
+                Named::Recursive(closures) => {
+                    closures.iter().any(|closure| occurs(name, closure.expr))
+                }
+            }) || occurs(name, body)
+        }
+        Expr::Match(scrutinee, alts) => {
+            occurs(name, scrutinee) || alts.iter().any(|alt| occurs(name, alt.expr))
+        }
+    }
+}
+
+fn simplify<'a>(allocator: &'a Allocator<'a>, expr: &'a Expr<'a>) -> &'a Expr<'a> {
+    let mut simplifier = Simplify { allocator };
+    simplifier.visit_expr(expr).unwrap_or(expr)
+}
+
 pub fn optimize<'a>(
     allocator: &'a Arc<Allocator<'a>>,
     env: &'a dyn OptimizeEnv<Type = ArcType>,
+    level: OptimizationLevel,
     expr: &'a Expr<'a>,
 ) -> Global<CoreExpr> {
     let expr = optimize_unnecessary_allocation(allocator, expr);
+    let expr = if level >= OptimizationLevel::Inline {
+        let expr = inline_imported(allocator, env, expr);
+        simplify(allocator, expr)
+    } else {
+        expr
+    };
 
     let pure_symbols = crate::core::purity::purity(expr);
 
@@ -322,9 +820,15 @@ pub fn optimize<'a>(
             info: Arc::new(interpreter.optimizer_info(allocator)),
         }
     } else {
+        // Record the size and purity of each binding so that modules importing this one can
+        // decide which functions to inline
         Global {
             value: crate::core::freeze_expr(allocator, expr),
-            info: Default::default(),
+            info: Arc::new(OptimizerInfo {
+                local_bindings: Default::default(),
+                pure_symbols,
+                costs,
+            }),
         }
     }
 }
@@ -707,4 +1211,101 @@ pub(crate) mod tests {
             "#;
         check_optimization(initial_str, expected_str, optimize_unnecessary_allocation);
     }
+
+    #[test]
+    fn beta_reduction() {
+        let initial_str = r#"
+            (rec let f x y = (#Int+) x y in f) 1 2
+            "#;
+        let expected_str = r#"
+            let x = 1
+            in
+            let y = 2
+            in
+            (#Int+) x y
+            "#;
+        check_optimization(initial_str, expected_str, simplify);
+    }
+
+    #[test]
+    fn beta_reduction_of_recursive_function() {
+        let initial_str = r#"
+            (rec let f x = f x in f) 1
+            "#;
+        check_optimization(initial_str, initial_str, simplify);
+    }
+
+    #[test]
+    fn case_of_case() {
+        let initial_str = r#"
+            match (match x with
+                | A -> C
+                | B -> D 1
+                end) with
+            | C -> 10
+            | D y -> y
+            end
+            "#;
+        let expected_str = r#"
+            match x with
+            | A -> 10
+            | B -> let y = 1 in y
+            end
+            "#;
+        check_optimization(initial_str, expected_str, simplify);
+    }
+
+    #[test]
+    fn case_of_case_with_default_alternative() {
+        let initial_str = r#"
+            match (match x with
+                | A -> C
+                | B -> D
+                end) with
+            | C -> 10
+            | z -> f z
+            end
+            "#;
+        let expected_str = r#"
+            match x with
+            | A -> 10
+            | B -> let z = D in f z
+            end
+            "#;
+        check_optimization(initial_str, expected_str, simplify);
+    }
+
+    #[test]
+    fn case_of_case_does_not_duplicate_large_alternatives() {
+        let initial_str = r#"
+            match (match x with
+                | A -> C
+                | B -> C
+                | E -> D
+                end) with
+            | C -> f 1
+            | D -> 2
+            end
+            "#;
+        check_optimization(initial_str, initial_str, simplify);
+    }
+
+    #[test]
+    fn beta_reduction_exposes_case_of_case() {
+        let initial_str = r#"
+            match (rec let f x = match x with | A -> C | B -> D end in f) y with
+            | C -> 1
+            | D -> 2
+            end
+            "#;
+        let expected_str = r#"
+            let x = y
+            in
+            match x with
+            | A -> 1
+            | B -> 2
+            end
+            "#;
+        check_optimization(initial_str, expected_str, simplify);
+    }
 }
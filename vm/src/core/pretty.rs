@@ -14,8 +14,8 @@ fn ident<'a, A>(
     arena: &'a pretty::Arena<'a, A>,
     name: &'a Symbol,
 ) -> pretty::DocBuilder<'a, pretty::Arena<'a, A>, A> {
-    // base::types::pretty_print::ident(arena, name.declared_name())
-    base::types::pretty_print::ident(arena, format!("{:?}", name))
+    // Print the full name, including the location suffix, so that shadowed names can be told apart
+    base::types::pretty_print::ident(arena, (**name).to_string())
 }
 
 #[derive(Clone, Copy)]
@@ -126,7 +126,11 @@ impl<'l, 'expr> Visitor<'l, 'expr> for Pure<'_> {
                             for arg in &closure.args {
                                 self.pure_symbols.0.insert(arg.name.clone(), Pureness::Load);
                             }
-                            self.is_pure(&closure.name.name, closure.expr);
+                            if self.is_pure(&closure.name.name, closure.expr) == Pureness::None {
+                                self.pure_symbols
+                                    .0
+                                    .insert(closure.name.name.clone(), Pureness::Load);
+                            }
                         }
                     }
                     Named::Expr(expr) => {
@@ -190,4 +194,22 @@ mod tests {
             collect![(symbols.simple_symbol("x"), Pureness::Load)]
         );
     }
+
+    #[test]
+    fn returning_impure_closure_is_pure() {
+        let mut symbols = Symbols::new();
+
+        let allocator = Arc::new(Allocator::new());
+
+        let expr = parse_expr(
+            &mut symbols,
+            &allocator,
+            "rec let f x = rec let g y = call x y in { g } in f",
+        );
+
+        let pure_symbols = purity(expr);
+        assert!(pure_symbols.pure_call(&symbols.simple_symbol("f")));
+        assert!(!pure_symbols.pure_call(&symbols.simple_symbol("g")));
+        assert!(pure_symbols.pure_load(&symbols.simple_symbol("g")));
+    }
 }
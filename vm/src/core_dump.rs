@@ -0,0 +1,266 @@
+//! Snapshots of the state of a thread at the point where it panicked.
+//!
+//! When the debug level of the vm is `DebugLevel::High` and a handler has been set through
+//! `GlobalVmState::set_core_dump_handler`, every panic which unwinds out of a call into gluon
+//! produces a `CoreDump`. The dump describes each stack frame (with its locals, upvariables and
+//! the source line it was executing) along with the globals which were loaded, letting failures
+//! in embedded scripts be diagnosed after the fact.
+//!
+//! ```rust
+//! # extern crate gluon;
+//! # use std::sync::{Arc, Mutex};
+//! # use gluon::{new_vm, ThreadExt};
+//! # use gluon::base::DebugLevel;
+//! # fn main() {
+//! # if ::std::env::var("GLUON_PATH").is_err() {
+//! #     ::std::env::set_var("GLUON_PATH", "..");
+//! # }
+//! let vm = new_vm();
+//! let dumps = Arc::new(Mutex::new(Vec::new()));
+//! let dumps2 = dumps.clone();
+//! vm.global_env().set_debug_level(DebugLevel::High);
+//! vm.global_env().set_core_dump_handler(Some(Box::new(move |dump| {
+//!     dumps2.lock().unwrap().push(dump.clone())
+//! })));
+//!
+//! let result = vm.run_expr::<i32>("example", "let x = 1\nx #Int+ error \"oops\"");
+//! assert!(result.is_err());
+//!
+//! let dumps = dumps.lock().unwrap();
+//! assert_eq!(dumps[0].message, "oops");
+//! // The first frame is the `error` function itself
+//! let frame = &dumps[0].frames[1];
+//! assert_eq!(frame.source.as_deref(), Some("x #Int+ error \"oops\""));
+//! assert!(frame.locals.iter().any(|local| local.name == "x" && local.value == "1"));
+//! # }
+//! ```
+use std::{fmt, fs::OpenOptions, io::Write, path::PathBuf};
+
+use crate::base::{pos::Line, types::ArcType, DebugLevel};
+
+use crate::{
+    stack::{ClosureState, Stack, State},
+    thread::Thread,
+    value::ValuePrinter,
+    vm::VmEnvInstance,
+    Variants,
+};
+
+/// Called with the `CoreDump` of every panic which occurs while a handler is set
+pub type CoreDumpHandler = Box<dyn Fn(&CoreDump) + Send + Sync>;
+
+/// The maximum depth that values in a dump are printed to
+const MAX_VALUE_LEVEL: i32 = 5;
+
+/// The maximum length of the printed types and values in a dump
+const MAX_TEXT_LEN: usize = 200;
+
+/// Collapses `text` onto a single line, truncating it if it is longer than `MAX_TEXT_LEN`
+fn summarize(text: String) -> String {
+    let mut summary = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((end, _)) = summary.char_indices().nth(MAX_TEXT_LEN) {
+        summary.truncate(end);
+        summary.push_str("...");
+    }
+    summary
+}
+
+/// Returns true for the names of the bindings that the compiler introduces, such as the implicit
+/// prelude
+fn is_generated(name: &str) -> bool {
+    name.starts_with("__") || name.contains('?')
+}
+
+/// The state of a thread at the point where it panicked
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize))]
+pub struct CoreDump {
+    /// The message of the panic
+    pub message: String,
+    /// The frames which were unwound by the panic, starting with the frame that panicked
+    pub frames: Vec<CoreDumpFrame>,
+    /// The names of the modules and globals which had been loaded when the panic occurred
+    pub globals: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize))]
+pub struct CoreDumpFrame {
+    /// The name of the executing function, `None` if the frame was not executing a function
+    pub function: Option<String>,
+    /// The name of the module which defined the function
+    pub source_name: String,
+    /// The (one-based) line that was executing
+    pub line: Option<u32>,
+    /// The text of the line that was executing, if the source is still available
+    pub source: Option<String>,
+    /// The locals which were in scope at the executing instruction
+    pub locals: Vec<CoreDumpValue>,
+    /// The values captured by the executing function
+    pub upvars: Vec<CoreDumpValue>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize))]
+pub struct CoreDumpValue {
+    pub name: String,
+    pub typ: String,
+    /// The value, printed as gluon code
+    pub value: String,
+}
+
+impl fmt::Display for CoreDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Panic: {}", self.message)?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(f)?;
+            write!(
+                f,
+                "{}: {} ({}",
+                i,
+                frame.function.as_deref().unwrap_or("<unknown>"),
+                frame.source_name
+            )?;
+            if let Some(line) = frame.line {
+                write!(f, ":{}", line)?;
+            }
+            writeln!(f, ")")?;
+            if let Some(source) = &frame.source {
+                writeln!(f, "    > {}", source)?;
+            }
+            for (kind, values) in &[("locals", &frame.locals), ("upvars", &frame.upvars)] {
+                if !values.is_empty() {
+                    writeln!(f, "    {}:", kind)?;
+                }
+                for value in values.iter() {
+                    writeln!(
+                        f,
+                        "        {} : {} = {}",
+                        value.name, value.typ, value.value
+                    )?;
+                }
+            }
+        }
+        if !self.globals.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Globals: {}", self.globals.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl CoreDump {
+    /// Captures the frames of `stack` above `level`. Only the information available from the
+    /// stack itself is filled in, `fill_from_env` adds the rest once the stack is unlocked.
+    pub(crate) fn capture(thread: &Thread, stack: &Stack, level: usize, message: &str) -> Self {
+        let env = thread.get_lookup_env();
+        let debug_level = DebugLevel::High;
+        let print = |name: &str, typ: &ArcType, value| {
+            let mut printer = ValuePrinter::new(&env, typ, Variants::new(value), &debug_level);
+            printer.max_level(MAX_VALUE_LEVEL);
+            CoreDumpValue {
+                name: name.to_string(),
+                typ: summarize(typ.to_string()),
+                value: summarize(printer.to_string()),
+            }
+        };
+
+        let all_frames = stack.get_frames();
+        let frames = all_frames
+            .iter()
+            .enumerate()
+            .skip(level)
+            .rev()
+            .map(|(i, frame)| match &frame.state {
+                State::Closure(ClosureState {
+                    closure,
+                    instruction_index,
+                }) => {
+                    let debug_info = &closure.function.debug_info;
+                    // Frames below the top have already advanced past their `Call` instruction
+                    let instruction_index = if i + 1 == all_frames.len() {
+                        *instruction_index
+                    } else {
+                        instruction_index.saturating_sub(1)
+                    };
+                    CoreDumpFrame {
+                        function: Some(closure.function.name.declared_name().to_string()),
+                        source_name: debug_info.source_name.clone(),
+                        line: debug_info
+                            .source_map
+                            .line(instruction_index)
+                            .map(|line| line.number().to_usize() as u32),
+                        source: None,
+                        locals: debug_info
+                            .local_map
+                            .locals(instruction_index)
+                            .filter(|local| !is_generated(local.name.declared_name()))
+                            .filter_map(|local| {
+                                let value = stack
+                                    .get_values()
+                                    .get(frame.offset as usize + local.index as usize)?;
+                                Some(print(local.name.declared_name(), &local.typ, value))
+                            })
+                            .collect(),
+                        upvars: debug_info
+                            .upvars
+                            .iter()
+                            .zip(closure.upvars.iter())
+                            .filter(|(info, _)| !is_generated(&info.name))
+                            .map(|(info, value)| print(&info.name, &info.typ, value))
+                            .collect(),
+                    }
+                }
+                State::Extern(function) => CoreDumpFrame {
+                    function: Some(function.function.id.declared_name().to_string()),
+                    source_name: "<extern>".to_string(),
+                    line: None,
+                    source: None,
+                    locals: Vec::new(),
+                    upvars: Vec::new(),
+                },
+                State::Unknown => CoreDumpFrame {
+                    function: None,
+                    source_name: "<unknown>".to_string(),
+                    line: None,
+                    source: None,
+                    locals: Vec::new(),
+                    upvars: Vec::new(),
+                },
+            })
+            .collect();
+
+        CoreDump {
+            message: message.to_string(),
+            frames,
+            globals: Vec::new(),
+        }
+    }
+
+    /// Fills in the source lines and globals which are only available through the environment
+    pub(crate) fn fill_from_env(&mut self, env: &VmEnvInstance) {
+        for frame in &mut self.frames {
+            if let Some(line) = frame.line {
+                frame.source = env
+                    .source_line(&frame.source_name, Line(line - 1))
+                    .map(|source| source.trim().to_string());
+            }
+        }
+        self.globals = env.global_names();
+    }
+}
+
+/// Returns a handler which appends each `CoreDump` to the file at `path`
+pub fn file_handler(path: impl Into<PathBuf>) -> CoreDumpHandler {
+    let path = path.into();
+    Box::new(move |dump| {
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{}", dump));
+        if let Err(err) = result {
+            error!("Unable to write core dump to `{}`: {}", path.display(), err);
+        }
+    })
+}
@@ -0,0 +1,135 @@
+//! Line coverage of executed gluon code.
+//!
+//! A `Coverage` is attached to a thread with `Context::set_coverage` after which the thread counts
+//! how many times execution moves onto each source line (using the source map of each function).
+//! When a function is executed for the first time all lines of it and the functions nested in it
+//! are recorded as well, so loaded modules report the lines which were never run with a count of
+//! zero. The same `Coverage` may be shared between threads to collect the coverage of all of them.
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::base::{fnv::FnvSet, pos::Line};
+
+use crate::{source_map::ExecutableLines, value::BytecodeFunction};
+
+#[derive(Default)]
+struct Counts {
+    /// Addresses of the functions whose lines have already been recorded
+    functions: FnvSet<usize>,
+    /// Execution counts of each line, keyed by source name
+    files: BTreeMap<String, BTreeMap<Line, u64>>,
+}
+
+#[derive(Default)]
+pub struct Coverage {
+    counts: Mutex<Counts>,
+}
+
+impl fmt::Debug for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Coverage")
+            .field("files", &self.counts().files)
+            .finish()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    fn counts(&self) -> MutexGuard<Counts> {
+        self.counts.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn hit(&self, function: &BytecodeFunction, line: Line) {
+        let mut counts = self.counts();
+        let counts = &mut *counts;
+        if counts
+            .functions
+            .insert(function as *const BytecodeFunction as usize)
+        {
+            add_lines(&mut counts.files, function);
+        }
+        *counts
+            .files
+            .entry(function.debug_info.source_name.clone())
+            .or_default()
+            .entry(line)
+            .or_insert(0) += 1;
+    }
+
+    /// Returns the names of the sources which have been seen
+    pub fn sources(&self) -> Vec<String> {
+        self.counts().files.keys().cloned().collect()
+    }
+
+    /// Returns the 1-indexed lines of `source_name` together with how many times execution moved
+    /// onto them
+    pub fn line_counts(&self, source_name: &str) -> Option<Vec<(u32, u64)>> {
+        self.counts().files.get(source_name).map(|lines| {
+            lines
+                .iter()
+                .map(|(line, &count)| (line.0 + 1, count))
+                .collect()
+        })
+    }
+
+    /// Returns the lines of `source_name` which have instructions, whether or not they have been
+    /// executed. Lines which are not executable can't be covered so they should not be reported
+    /// as missed.
+    pub fn executable_lines(&self, source_name: &str) -> Option<ExecutableLines> {
+        self.counts()
+            .files
+            .get(source_name)
+            .map(|lines| lines.keys().cloned().collect())
+    }
+
+    /// Removes all recorded counts
+    pub fn clear(&self) {
+        *self.counts() = Counts::default();
+    }
+
+    /// Writes the collected coverage in the `lcov` tracefile format. Each source is identified by
+    /// the name it was compiled with (usually the module name).
+    pub fn write_lcov<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let counts = self.counts();
+        for (source_name, lines) in &counts.files {
+            writeln!(writer, "TN:")?;
+            writeln!(writer, "SF:{}", source_name)?;
+            for (line, count) in lines {
+                writeln!(writer, "DA:{},{}", line.0 + 1, count)?;
+            }
+            writeln!(writer, "LH:{}", lines.values().filter(|&&n| n != 0).count())?;
+            writeln!(writer, "LF:{}", lines.len())?;
+            writeln!(writer, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    /// Returns the collected coverage in the `lcov` tracefile format
+    pub fn to_lcov(&self) -> String {
+        let mut output = Vec::new();
+        self.write_lcov(&mut output)
+            .expect("Writing to a `Vec` can't fail");
+        String::from_utf8(output).expect("lcov output is UTF-8")
+    }
+}
+
+fn add_lines(files: &mut BTreeMap<String, BTreeMap<Line, u64>>, function: &BytecodeFunction) {
+    let lines = files
+        .entry(function.debug_info.source_name.clone())
+        .or_default();
+    for line in function.debug_info.source_map.lines() {
+        lines.entry(line).or_insert(0);
+    }
+    for inner in &function.inner_functions {
+        add_lines(files, inner);
+    }
+}
@@ -0,0 +1,107 @@
+//! Evidence of the effect handlers used by `std.effect`
+use crate::real_std::fmt;
+
+use rpds::HashTrieMapSync;
+
+use crate::{
+    api::{generic::A, Generic, Unrooted, Userdata},
+    gc::{CloneUnrooted, Gc, GcRef, Move, Trace},
+    value::{Cloner, Value},
+    vm::Thread,
+    ExternModule, Result,
+};
+
+/// The handlers which enclose an effectful computation, keyed by the label of the effect they
+/// handle. Handlers installed later shadow the earlier ones for the same label.
+///
+/// The map is persistent so that installing a handler shares the handlers of the enclosing
+/// evidence instead of copying them.
+#[derive(VmType)]
+#[gluon(gluon_vm)]
+#[gluon(vm_type = "std.effect.Evidence")]
+pub struct Evidence {
+    handlers: HashTrieMapSync<String, Value>,
+}
+
+impl Default for Evidence {
+    fn default() -> Self {
+        Evidence {
+            handlers: HashTrieMapSync::new_sync(),
+        }
+    }
+}
+
+impl Userdata for Evidence {
+    fn deep_clone<'gc>(
+        &self,
+        deep_cloner: &'gc mut Cloner,
+    ) -> Result<GcRef<'gc, Box<dyn Userdata>>> {
+        // SAFETY During the `alloc` call the unrooted values are scanned through the `DataDef`
+        unsafe {
+            let mut handlers = HashTrieMapSync::new_sync();
+            for (label, handler) in self.handlers.iter() {
+                handlers.insert_mut(label.clone(), deep_cloner.deep_clone(handler)?.unrooted());
+            }
+            let data: Box<dyn Userdata> = Box::new(Evidence { handlers });
+            deep_cloner.gc().alloc(Move(data))
+        }
+    }
+}
+
+impl fmt::Debug for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.handlers.keys()).finish()
+    }
+}
+
+// Don't root/unroot the handlers as they are shared with the evidence of the enclosing handlers
+unsafe impl Trace for Evidence {
+    fn trace(&self, gc: &mut Gc) {
+        for handler in self.handlers.values() {
+            handler.trace(gc);
+        }
+    }
+}
+
+fn empty(_: ()) -> Evidence {
+    Evidence::default()
+}
+
+fn insert(label: &str, handler: Generic<A>, evidence: &Evidence) -> Evidence {
+    // SAFETY The handler is rooted by the returned evidence which gets pushed immediately to the
+    // stack
+    let handler = unsafe { handler.get_value().clone_unrooted() };
+    Evidence {
+        handlers: evidence.handlers.insert(label.into(), handler),
+    }
+}
+
+/// Returns the innermost handler for `label` or `default` if there is none. The caller must
+/// ensure that the handler was inserted with the same type as `default`.
+fn lookup(label: &str, default: Generic<A>, evidence: &Evidence) -> Unrooted<A> {
+    let handler = evidence
+        .handlers
+        .get(label)
+        .map_or(default.get_value(), |handler| handler);
+    // SAFETY The returned, unrooted value gets pushed immediately to the stack
+    unsafe { Unrooted::from(handler.clone_unrooted()) }
+}
+
+mod std {
+    pub mod effect {
+        pub use crate::effect as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    let _ = vm.register_type::<Evidence>("std.effect.Evidence", &[]);
+    ExternModule::new(
+        vm,
+        record! {
+            type Evidence => Evidence,
+            empty => primitive!(1, std::effect::prim::empty),
+            insert => primitive!(3, std::effect::prim::insert),
+            lookup => primitive!(3, std::effect::prim::lookup),
+        },
+    )
+}
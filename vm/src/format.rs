@@ -0,0 +1,289 @@
+//! Implementation of the `std.fmt` formatting mini-language.
+//!
+//! A format string consists of text and replacement fields, `{name}` or `{name:spec}`, which are
+//! replaced by the field `name` of the record which is formatted (`{}` refers to the formatted
+//! value itself and `{a.b}` to the field `b` of the record in field `a`). `{{` and `}}` are
+//! written as `{` and `}`.
+//!
+//! The spec of a field is a subset of the spec used by Rust's `format!`:
+//! `[[fill]align][+][0][width][.precision][type]` where `align` is one of `<`, `^` or `>` and
+//! `type` is one of `x`, `X`, `o`, `b` (for integers) or `e` (for floats).
+use crate::real_std::result::Result as StdResult;
+
+use crate::{
+    api::{generic::A, OpaqueRef, RuntimeResult, ValueRef, WithVM},
+    thread::Thread,
+    ExternModule, Result, Variants,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, PartialEq)]
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    sign: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+fn align(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None,
+    }
+}
+
+fn parse_spec(spec: &str) -> StdResult<Spec, String> {
+    let invalid = || format!("Invalid format spec `{}`", spec);
+
+    let chars: Vec<char> = spec.chars().collect();
+    let mut i = 0;
+    let mut result = Spec {
+        fill: ' ',
+        align: None,
+        sign: false,
+        zero: false,
+        width: 0,
+        precision: None,
+        kind: None,
+    };
+
+    if let Some(a) = chars.get(1).cloned().and_then(align) {
+        result.fill = chars[0];
+        result.align = Some(a);
+        i = 2;
+    } else if let Some(a) = chars.get(0).cloned().and_then(align) {
+        result.align = Some(a);
+        i = 1;
+    }
+    if chars.get(i) == Some(&'+') {
+        result.sign = true;
+        i += 1;
+    }
+    if chars.get(i) == Some(&'0') {
+        result.zero = true;
+        i += 1;
+    }
+
+    let number = |i: &mut usize| {
+        let start = *i;
+        while chars.get(*i).map_or(false, |c| c.is_ascii_digit()) {
+            *i += 1;
+        }
+        chars[start..*i].iter().collect::<String>().parse::<usize>()
+    };
+    if let Ok(width) = number(&mut i) {
+        result.width = width;
+    }
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        result.precision = Some(number(&mut i).map_err(|_| invalid())?);
+    }
+    match chars.get(i) {
+        Some(&c) if "xXobe".contains(c) => {
+            result.kind = Some(c);
+            i += 1;
+        }
+        _ => (),
+    }
+    if i != chars.len() {
+        return Err(invalid());
+    }
+    Ok(result)
+}
+
+fn pad(out: &mut String, spec: &Spec, value: &str, default_align: Align) {
+    let len = value.chars().count();
+    if len >= spec.width {
+        out.push_str(value);
+        return;
+    }
+    let padding = spec.width - len;
+    if spec.zero && spec.align.is_none() {
+        // Zeroes are inserted between the sign and the digits
+        let (sign, digits) = match value.chars().next() {
+            Some(c @ '+') | Some(c @ '-') => value.split_at(c.len_utf8()),
+            _ => ("", value),
+        };
+        out.push_str(sign);
+        out.extend((0..padding).map(|_| '0'));
+        out.push_str(digits);
+        return;
+    }
+    let (before, after) = match spec.align.unwrap_or(default_align) {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0),
+    };
+    out.extend((0..before).map(|_| spec.fill));
+    out.push_str(value);
+    out.extend((0..after).map(|_| spec.fill));
+}
+
+fn format_value(
+    out: &mut String,
+    name: &str,
+    spec: &Spec,
+    value: Variants,
+) -> StdResult<(), String> {
+    let invalid_kind = |kind| {
+        Err(format!(
+            "Format type `{}` can not be used to format the field `{}`",
+            kind, name
+        ))
+    };
+    let sign = |non_negative: bool| if spec.sign && non_negative { "+" } else { "" };
+
+    match value.as_ref() {
+        ValueRef::String(s) => {
+            if let Some(kind) = spec.kind {
+                return invalid_kind(kind);
+            }
+            match spec.precision {
+                Some(precision) => {
+                    let s: String = s.chars().take(precision).collect();
+                    pad(out, spec, &s, Align::Left)
+                }
+                None => pad(out, spec, s, Align::Left),
+            }
+        }
+        ValueRef::Int(i) => format_int(out, name, spec, i as i128, sign(i >= 0))?,
+        ValueRef::Byte(b) => format_int(out, name, spec, b as i128, sign(true))?,
+        ValueRef::Float(f) => {
+            let sign = sign(f >= 0.0);
+            let s = match (spec.kind, spec.precision) {
+                (None, None) => format!("{}{}", sign, f),
+                (None, Some(precision)) => format!("{}{:.*}", sign, precision, f),
+                (Some('e'), None) => format!("{}{:e}", sign, f),
+                (Some('e'), Some(precision)) => format!("{}{:.*e}", sign, precision, f),
+                (Some(kind), _) => return invalid_kind(kind),
+            };
+            pad(out, spec, &s, Align::Right)
+        }
+        _ => {
+            return Err(format!(
+                "The field `{}` can not be formatted, only `String`, `Int`, `Byte` and `Float` \
+                 values are supported",
+                name
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn format_int(
+    out: &mut String,
+    name: &str,
+    spec: &Spec,
+    i: i128,
+    sign: &str,
+) -> StdResult<(), String> {
+    if spec.precision.is_some() {
+        return Err(format!(
+            "Precision can not be used to format the integer field `{}`",
+            name
+        ));
+    }
+    let (minus, abs) = if i < 0 { ("-", -i) } else { ("", i) };
+    let s = match spec.kind {
+        None => format!("{}{}{}", sign, minus, abs),
+        Some('x') => format!("{}{}{:x}", sign, minus, abs),
+        Some('X') => format!("{}{}{:X}", sign, minus, abs),
+        Some('o') => format!("{}{}{:o}", sign, minus, abs),
+        Some('b') => format!("{}{}{:b}", sign, minus, abs),
+        Some(kind) => {
+            return Err(format!(
+                "Format type `{}` can not be used to format the field `{}`",
+                kind, name
+            ))
+        }
+    };
+    pad(out, spec, &s, Align::Right);
+    Ok(())
+}
+
+fn lookup<'a>(thread: &Thread, args: Variants<'a>, name: &str) -> StdResult<Variants<'a>, String> {
+    if name.is_empty() {
+        return Ok(args);
+    }
+    name.split('.')
+        .try_fold(args, |value, field| match value.as_ref() {
+            ValueRef::Data(data) => data
+                .lookup_field(thread, field)
+                .ok_or_else(|| format!("The formatted record has no field `{}`", name)),
+            _ => Err(format!(
+                "Expected a record when looking up the field `{}`",
+                name
+            )),
+        })
+}
+
+/// Writes `format` to `out`, replacing each replacement field with the field of `args`
+pub fn write_format(
+    thread: &Thread,
+    out: &mut String,
+    format: &str,
+    args: Variants,
+) -> StdResult<(), String> {
+    let mut rest = format;
+    while let Some(i) = rest.find(|c| c == '{' || c == '}') {
+        out.push_str(&rest[..i]);
+        let c = rest[i..].chars().next().unwrap();
+        rest = &rest[i + 1..];
+        if rest.starts_with(c) {
+            out.push(c);
+            rest = &rest[1..];
+            continue;
+        }
+        if c == '}' {
+            return Err("Unmatched `}` in format string, use `}}` to write `}`".to_string());
+        }
+        let end = rest
+            .find('}')
+            .ok_or_else(|| "Unterminated replacement field in format string".to_string())?;
+        let field = &rest[..end];
+        rest = &rest[end + 1..];
+
+        let (name, spec) = match field.find(':') {
+            Some(i) => (field[..i].trim(), parse_spec(&field[i + 1..])?),
+            None => (field.trim(), parse_spec("")?),
+        };
+        let value = lookup(thread, args.clone(), name)?;
+        format_value(out, name, &spec, value)?;
+    }
+    out.push_str(rest);
+    Ok(())
+}
+
+fn format(format: WithVM<&str>, args: OpaqueRef<A>) -> RuntimeResult<String, String> {
+    let mut out = String::with_capacity(format.value.len());
+    match write_format(format.vm, &mut out, format.value, args.get_variant()) {
+        Ok(()) => RuntimeResult::Return(out),
+        Err(err) => RuntimeResult::Panic(err),
+    }
+}
+
+mod std {
+    pub mod fmt {
+        pub use crate::format as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            format => primitive!(2, std::fmt::prim::format)
+        },
+    )
+}
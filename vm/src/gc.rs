@@ -12,6 +12,7 @@ use std::{
     rc::Rc,
     result::Result as StdResult,
     sync::{self, Arc},
+    time::{Duration, Instant},
 };
 
 use crate::{
@@ -205,6 +206,124 @@ impl Generation {
     }
 }
 
+/// Statistics about the memory usage and collections of a garbage collector.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeapStats {
+    /// How many bytes which are currently allocated
+    pub live_bytes: usize,
+    /// The maximum number of bytes the garbage collector may contain
+    pub memory_limit: usize,
+    /// The total number of bytes that have been allocated, including bytes that have since been
+    /// freed
+    pub total_allocated_bytes: u64,
+    /// The average number of bytes allocated per second since the first allocation
+    pub allocation_rate: f64,
+    /// How many collections have been run
+    pub collections: u64,
+    /// The number of bytes freed by the last collection
+    pub last_freed_bytes: usize,
+    /// How long the last collection took
+    pub last_pause: Duration,
+    /// The longest time a collection has taken
+    pub max_pause: Duration,
+    /// The total time spent in collections
+    pub total_pause: Duration,
+}
+
+/// Limits on the size of the individual values a garbage collector allocates, which stops a
+/// script from constructing huge values even when the memory limit is large.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct ValueLimits {
+    /// The maximum length of a string, in bytes
+    pub max_string_len: usize,
+    /// The maximum number of elements in an array
+    pub max_array_len: usize,
+    /// The maximum number of records which may be nested inside each other, directly or through
+    /// variants such as `Option`
+    pub max_record_depth: usize,
+}
+
+impl Default for ValueLimits {
+    fn default() -> Self {
+        ValueLimits {
+            max_string_len: usize::MAX,
+            max_array_len: usize::MAX,
+            max_record_depth: usize::MAX,
+        }
+    }
+}
+
+impl ValueLimits {
+    /// Returns an error if `size` exceeds the matching limit
+    pub fn check(&self, size: ValueSize) -> Result<()> {
+        let limit = match size {
+            ValueSize::String(_) => self.max_string_len,
+            ValueSize::Array(_) => self.max_array_len,
+            ValueSize::RecordDepth(_) => self.max_record_depth,
+        };
+        if size.get() > limit {
+            Err(Error::ValueTooLarge { size, limit })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The size of a value which is checked against the `ValueLimits` when it is allocated
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ValueSize {
+    /// A string of this many bytes
+    String(usize),
+    /// An array of this many elements
+    Array(usize),
+    /// A record with this many records nested inside it (including itself)
+    RecordDepth(usize),
+}
+
+impl ValueSize {
+    pub fn get(self) -> usize {
+        match self {
+            ValueSize::String(size) | ValueSize::Array(size) | ValueSize::RecordDepth(size) => size,
+        }
+    }
+}
+
+impl fmt::Display for ValueSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValueSize::String(len) => write!(f, "A string of {} bytes", len),
+            ValueSize::Array(len) => write!(f, "An array of {} elements", len),
+            ValueSize::RecordDepth(depth) => write!(f, "Records nested {} levels deep", depth),
+        }
+    }
+}
+
+/// Function called with the updated statistics after each collection
+pub type CollectHookFn = Box<dyn FnMut(&HeapStats) + Send + Sync>;
+
+#[derive(Default)]
+struct CollectHook(Option<CollectHookFn>);
+
+impl fmt::Debug for CollectHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("CollectHook")
+            .field(&self.0.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+#[derive(Debug, Default)]
+struct CollectionStats {
+    /// When the first value was allocated, used to calculate the allocation rate
+    first_allocation: Option<Instant>,
+    total_allocated_bytes: u64,
+    collections: u64,
+    last_freed_bytes: usize,
+    last_pause: Duration,
+    max_pause: Duration,
+    total_pause: Duration,
+}
+
 /// A mark and sweep garbage collector.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
@@ -229,6 +348,9 @@ pub struct Gc {
     collect_limit: usize,
     /// The maximum number of bytes this garbage collector may contain
     memory_limit: usize,
+    /// The limits on the size of each value allocated by this garbage collector
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    value_limits: ValueLimits,
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     type_infos: FnvMap<TypeId, Box<TypeInfo>>,
     #[cfg_attr(feature = "serde_derive", serde(skip))]
@@ -255,6 +377,10 @@ pub struct Gc {
     /// only refer to each other through some reference or channel allocated in generation 0 (and
     /// if they do interact with eachother this means the values are cloned into generation 0).
     generation: Generation,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    stats: CollectionStats,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    collect_hook: CollectHook,
 }
 
 impl Drop for Gc {
@@ -306,6 +432,12 @@ pub unsafe trait DataDef {
     fn tag(&self) -> Option<&InternedStr> {
         None
     }
+
+    /// Returns the size of the value to check against the `ValueLimits` of the garbage collector,
+    /// if it is a kind of value which is limited
+    fn value_size(&self) -> Option<ValueSize> {
+        None
+    }
 }
 
 /// `DataDef` that moves its value directly into the pointer
@@ -681,10 +813,14 @@ impl<T: ?Sized> GcPtr<T> {
         self.type_info().tag.as_ref()
     }
 
+    /// Returns a map from each field name to its index. The iteration order of the map is
+    /// unspecified, use `field_names` when the fields need to be visited in a stable order.
     pub fn field_map(&self) -> &FnvMap<InternedStr, VmIndex> {
         &self.type_info().fields
     }
 
+    /// Returns the field names in the order the fields are stored in (the `i`th name belongs to
+    /// the `i`th field).
     pub fn field_names(&self) -> &Arc<[InternedStr]> {
         &self.type_info().fields_key
     }
@@ -1055,10 +1191,13 @@ impl Gc {
             allocated_memory: 0,
             collect_limit: 100,
             memory_limit: memory_limit,
+            value_limits: ValueLimits::default(),
             type_infos: FnvMap::default(),
             record_infos: FnvMap::default(),
             tag_infos: FnvMap::default(),
             generation: generation,
+            stats: CollectionStats::default(),
+            collect_hook: CollectHook::default(),
         }
     }
 
@@ -1066,16 +1205,54 @@ impl Gc {
         self.allocated_memory
     }
 
+    /// Returns statistics about the memory usage and the collections run by this garbage
+    /// collector
+    pub fn stats(&self) -> HeapStats {
+        let stats = &self.stats;
+        let allocation_rate = stats
+            .first_allocation
+            .map(|start| start.elapsed().as_secs_f64())
+            .filter(|&elapsed| elapsed > 0.0)
+            .map_or(0.0, |elapsed| stats.total_allocated_bytes as f64 / elapsed);
+        HeapStats {
+            live_bytes: self.allocated_memory,
+            memory_limit: self.memory_limit,
+            total_allocated_bytes: stats.total_allocated_bytes,
+            allocation_rate,
+            collections: stats.collections,
+            last_freed_bytes: stats.last_freed_bytes,
+            last_pause: stats.last_pause,
+            max_pause: stats.max_pause,
+            total_pause: stats.total_pause,
+        }
+    }
+
+    /// Sets a function which is called with the heap statistics after each collection, returning
+    /// the previous function.
+    pub fn set_collect_hook(&mut self, hook: Option<CollectHookFn>) -> Option<CollectHookFn> {
+        mem::replace(&mut self.collect_hook.0, hook)
+    }
+
     pub fn set_memory_limit(&mut self, memory_limit: usize) {
         self.memory_limit = memory_limit;
     }
 
+    pub fn value_limits(&self) -> ValueLimits {
+        self.value_limits
+    }
+
+    pub fn set_value_limits(&mut self, value_limits: ValueLimits) {
+        self.value_limits = value_limits;
+    }
+
     pub fn generation(&self) -> Generation {
         self.generation
     }
 
     pub fn new_child_gc(&self) -> Gc {
-        Gc::new(self.generation.next(), self.memory_limit)
+        let mut gc = Gc::new(self.generation.next(), self.memory_limit);
+        gc.value_limits = self.value_limits;
+        gc
     }
 
     /// Allocates a new object. If the garbage collector has hit the collection limit a collection
@@ -1134,6 +1311,9 @@ impl Gc {
                 needed: needed,
             });
         }
+        if let Some(value_size) = def.value_size() {
+            self.value_limits.check(value_size)?;
+        }
         Ok(self.alloc_ignore_limit_(size, def))
     }
 
@@ -1228,6 +1408,8 @@ impl Gc {
         let mut ptr = AllocPtr::new::<D::Value>(type_info, size);
         ptr.next = self.values.take();
         self.allocated_memory += ptr.size();
+        self.stats.first_allocation.get_or_insert_with(Instant::now);
+        self.stats.total_allocated_bytes += ptr.size() as u64;
         unsafe {
             let p: *mut D::Value = D::Value::make_ptr(&def, ptr.value());
             let ret: *const D::Value = &*def.initialize(WriteOnly::new(p));
@@ -1260,11 +1442,28 @@ impl Gc {
         R: Trace + CollectScope,
     {
         info!("Start collect {:?}", self.generation);
+        let start = Instant::now();
+        let allocated_before = self.allocated_memory;
         roots.scope(self, |self_| {
             roots.trace(self_);
             self_.sweep();
             self_.collect_limit = 2 * self_.allocated_memory;
-        })
+        });
+        let pause = start.elapsed();
+
+        let stats = &mut self.stats;
+        stats.collections += 1;
+        stats.last_freed_bytes = allocated_before.saturating_sub(self.allocated_memory);
+        stats.last_pause = pause;
+        stats.max_pause = stats.max_pause.max(pause);
+        stats.total_pause += pause;
+
+        if self.collect_hook.0.is_some() {
+            let stats = self.stats();
+            if let Some(hook) = &mut self.collect_hook.0 {
+                hook(&stats);
+            }
+        }
     }
 
     /// Marks the GcPtr
@@ -1525,4 +1724,44 @@ mod tests {
 
         unsafe { gc.clear() }
     }
+
+    #[test]
+    fn stats_are_updated_by_collections() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        let collected = sync::Arc::new(sync::Mutex::new(Vec::new()));
+        {
+            let collected = collected.clone();
+            gc.set_collect_hook(Some(Box::new(move |stats: &HeapStats| {
+                collected.lock().unwrap().push(stats.clone())
+            })));
+        }
+
+        let mut stack: Vec<Value> = Vec::new();
+        stack.push(new_data(gc.alloc(Def { elems: &[Int(1)] }).unwrap()));
+        gc.alloc(Def { elems: &[Int(2)] }).unwrap();
+
+        let stats = gc.stats();
+        assert_eq!(stats.collections, 0);
+        assert_eq!(stats.live_bytes as u64, stats.total_allocated_bytes);
+
+        unsafe {
+            gc.collect(&mut *stack);
+        }
+
+        let stats = gc.stats();
+        assert_eq!(stats.collections, 1);
+        assert!(stats.last_freed_bytes > 0);
+        assert_eq!(
+            stats.live_bytes as u64 + stats.last_freed_bytes as u64,
+            stats.total_allocated_bytes
+        );
+        assert_eq!(stats.total_pause, stats.last_pause);
+
+        let collected = collected.lock().unwrap();
+        assert_eq!(collected.len(), 1);
+        assert_eq!(collected[0].collections, 1);
+        assert_eq!(collected[0].live_bytes, stats.live_bytes);
+
+        unsafe { gc.clear() }
+    }
 }
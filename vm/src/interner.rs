@@ -12,6 +12,11 @@ use crate::{
     Result,
 };
 
+/// Strings of at most this many bytes are looked up in the interner before they are allocated at
+/// runtime so that short, repeated strings such as field and constructor names share a single
+/// allocation
+pub const SHORT_STRING_LEN: usize = 16;
+
 /// Interned strings which allow for fast equality checks and hashing
 #[derive(Eq)]
 pub struct InternedStr(GcStr);
@@ -127,6 +132,14 @@ impl Interner {
         }
     }
 
+    /// Returns the interned string equal to `s` if it has been interned
+    pub fn get(&self, s: &str) -> Option<InternedStr> {
+        // SAFETY The interner is scanned
+        self.indexes
+            .get(s)
+            .map(|interned_str| unsafe { interned_str.clone_unrooted() })
+    }
+
     pub fn intern(&mut self, gc: &mut Gc, s: &str) -> Result<InternedStr> {
         // SAFETY The interner is scanned
         unsafe {
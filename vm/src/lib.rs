@@ -52,24 +52,36 @@ pub mod api;
 pub mod channel;
 pub mod compiler;
 pub mod core;
+pub mod core_dump;
+pub mod coverage;
 pub mod debug;
 pub mod dynamic;
+pub mod effect;
+pub mod format;
 pub mod lazy;
 pub mod macros;
 pub mod primitives;
+pub mod property;
 pub mod reference;
+pub mod source_map;
 pub mod stack;
+pub mod stack_map;
 pub mod thread;
+pub mod thread_local;
 pub mod types;
 pub mod vm;
 
 mod array;
 mod derive;
 mod interner;
-mod source_map;
+mod snapshot;
 mod value;
 
-use std::{self as real_std, fmt, marker::PhantomData};
+use std::{
+    self as real_std, fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
 
 use crate::base::{metadata::Metadata, source::FileId, symbol::Symbol, types::ArcType};
 use crate::{
@@ -178,6 +190,9 @@ quick_error! {
         OutOfMemory { limit: usize, needed: usize } {
             display("Thread is out of memory: Limit {}, needed {}", limit, needed)
         }
+        ValueTooLarge { size: gc::ValueSize, limit: usize } {
+            display("{} exceeds the limit of {}", size, limit)
+        }
         StackOverflow(limit: VmIndex) {
             display("The stack has overflowed: Limit `{}`", limit)
         }
@@ -188,7 +203,7 @@ quick_error! {
         Interrupted {
             display("Thread was interrupted")
         }
-        Panic(err: String, stacktrace: Option<Stacktrace>) {
+        Panic(err: String, stacktrace: Option<Stacktrace>, payload: Option<PanicPayload>) {
             display("{}", Panic { err, stacktrace })
         }
     }
@@ -200,6 +215,42 @@ impl base::error::AsDiagnostic for Error {
     }
 }
 
+/// The value a gluon program panicked with, either the message passed to `error` or the value
+/// passed to `error_with`.
+///
+/// `Error::Panic` stores a rendering of the payload as its message while the payload itself can be
+/// converted back into a Rust value with `Getable`, letting the host handle the errors of a
+/// script by their type.
+#[derive(Clone, Debug)]
+pub struct PanicPayload(RootedValue<RootedThread>);
+
+impl PanicPayload {
+    pub(crate) fn new(value: RootedValue<RootedThread>) -> Self {
+        PanicPayload(value)
+    }
+
+    pub fn value(&self) -> &RootedValue<RootedThread> {
+        &self.0
+    }
+
+    pub fn into_value(self) -> RootedValue<RootedThread> {
+        self.0
+    }
+}
+
+impl PartialEq for PanicPayload {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for PanicPayload {}
+
+impl Hash for PanicPayload {
+    // Values can't be hashed, equal payloads trivially hash to the same value
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
 struct Panic<'a> {
     err: &'a String,
     stacktrace: &'a Option<Stacktrace>,
@@ -16,13 +16,17 @@ use {
 use gluon_codegen::Trace;
 
 use crate::base::{
-    ast::{self, Expr, MutVisitor, SpannedExpr},
-    error::{AsDiagnostic, Errors as BaseErrors, Salvage, SalvageResult},
+    ast::{
+        self, Argument, AstClone, Do, Expr, Lambda, MutVisitor, Pattern, PatternField, SpannedExpr,
+        SpannedIdent, SpannedPattern, TypedIdent, ValueBinding, ValueBindings, Visitor,
+    },
+    error::{AsDiagnostic, Errors as BaseErrors, Salvage, SalvageResult, SuggestedFix},
     fnv::FnvMap,
     pos,
-    pos::{BytePos, Spanned},
+    pos::{BytePos, Span, Spanned},
     source::FileId,
     symbol::{Symbol, Symbols},
+    types::Type,
 };
 
 use crate::{
@@ -149,6 +153,10 @@ impl AsDiagnostic for Error {
     fn as_diagnostic(&self, map: &base::source::CodeMap) -> Diagnostic<FileId> {
         self.0.as_diagnostic(map)
     }
+
+    fn suggested_fixes(&self) -> Vec<SuggestedFix> {
+        self.0.suggested_fixes()
+    }
 }
 
 impl fmt::Display for Error {
@@ -464,6 +472,7 @@ impl<'a> MacroExpander<'a> {
             symbols,
             arena,
             exprs: Vec::new(),
+            scope: Vec::new(),
         };
         visitor.visit_expr(expr);
         let MacroVisitor { exprs, symbols, .. } = visitor;
@@ -486,7 +495,7 @@ impl<'a> MacroExpander<'a> {
                 Ok(result) => futures.push(result.compute().map(move |result| (expr, result))),
                 Err(err) => {
                     self.errors.push(pos::spanned(expr.span, err));
-                    replace_expr(arena, expr, Expr::Error(None));
+                    replace_expr(arena.borrow(), expr, Expr::Error(None));
                 }
             }
         }
@@ -508,7 +517,7 @@ impl<'a> MacroExpander<'a> {
                 }
             };
 
-            replace_expr(arena, expr, new_expr);
+            replace_expr(arena.borrow(), expr, new_expr);
         }
 
         unordered_errors.sort_by_key(|&(index, _)| index);
@@ -518,7 +527,7 @@ impl<'a> MacroExpander<'a> {
 }
 
 fn replace_expr<'ast>(
-    arena: &ast::OwnedArena<'ast, Symbol>,
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
     expr: &mut SpannedExpr<'ast, Symbol>,
     new: Expr<'ast, Symbol>,
 ) {
@@ -533,17 +542,474 @@ fn replace_expr<'ast>(
     );
 }
 
+/// Creates the expression `(import! module).field` where `module` is a `.` separated path
+pub fn import_field<'ast>(
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
+    symbols: &mut Symbols,
+    span: Span<BytePos>,
+    module: &str,
+    field: &str,
+) -> SpannedExpr<'ast, Symbol> {
+    let path = module
+        .split('.')
+        .fold(None, |acc, name| {
+            let symbol = symbols.simple_symbol(name);
+            Some(match acc {
+                Some(expr) => pos::spanned(
+                    span,
+                    Expr::Projection(arena.alloc(expr), symbol, Type::hole()),
+                ),
+                None => pos::spanned(span, Expr::Ident(TypedIdent::new(symbol))),
+            })
+        })
+        .expect("module path");
+    let import = pos::spanned(
+        span,
+        Expr::app(
+            arena,
+            pos::spanned(
+                span,
+                Expr::Ident(TypedIdent::new(symbols.simple_symbol("import!"))),
+            ),
+            Some(path),
+        ),
+    );
+    pos::spanned(
+        span,
+        Expr::Projection(
+            arena.alloc(import),
+            symbols.simple_symbol(field),
+            Type::hole(),
+        ),
+    )
+}
+
+/// The names which are desugared into functions from the standard library. The desugaring is
+/// only done if the name is not bound by the program or, for `wrap`, if it is bound to
+/// `std.applicative.wrap`.
+const DESUGARED_NAMES: [&str; 3] = [":=", "!", "wrap"];
+
+/// Returns true if the innermost binding of `name` in `scope` is not the function from the standard
+/// library which the desugaring refers to
+fn is_shadowed(scope: &[(&str, bool)], name: &str) -> bool {
+    scope
+        .iter()
+        .rev()
+        .find(|(bound, _)| *bound == name)
+        .map_or(false, |&(_, is_std)| !is_std)
+}
+
+/// Returns true if `expr` binds `wrap` by importing it from `std.applicative` (or `std.prelude`
+/// which re-exports it), as in `let { wrap } = import! std.applicative`
+fn imports_applicative_wrap(expr: &SpannedExpr<'_, Symbol>) -> bool {
+    let bindings = match &expr.value {
+        Expr::LetBindings(bindings, _) => bindings,
+        _ => return false,
+    };
+    bindings.iter().any(|bind| {
+        let binds_wrap = match &bind.name.value {
+            Pattern::Record { fields, .. } => fields.iter().any(|field| match field {
+                PatternField::Value { name, value: None } => name.value.as_str() == "wrap",
+                _ => false,
+            }),
+            _ => false,
+        };
+        let module = match &bind.expr.value {
+            Expr::App { func, args, .. }
+                if args.len() == 1
+                    && matches!(&func.value, Expr::Ident(id) if id.name.as_str() == "import!") =>
+            {
+                let mut module = String::new();
+                ast::expr_to_path(&args[0], &mut module)
+                    .ok()
+                    .map(|()| module)
+            }
+            _ => None,
+        };
+        binds_wrap
+            && module.map_or(false, |module| {
+                module == "std.applicative" || module == "std.prelude"
+            })
+    })
+}
+
+/// Desugars `r := value` into `(import! std.reference).(<-) r value` and `!r` into
+/// `(import! std.reference).load r`. Operators which are bound in `scope` are left as is.
+fn desugar_reference<'ast>(
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
+    symbols: &mut Symbols,
+    scope: &[(&str, bool)],
+    expr: &SpannedExpr<'ast, Symbol>,
+) -> Option<Expr<'ast, Symbol>> {
+    let is_sugar = |name: &str| !is_shadowed(scope, name);
+    let (span, function, args) = match &expr.value {
+        Expr::Infix { lhs, op, rhs, .. } if op.value.name.as_str() == ":=" && is_sugar(":=") => (
+            op.span,
+            "<-",
+            vec![(**lhs).ast_clone(arena), (**rhs).ast_clone(arena)],
+        ),
+        Expr::App { func, args, .. }
+            if args.len() == 1
+                && is_sugar("!")
+                && matches!(&func.value, Expr::Ident(id) if id.name.as_str() == "!") =>
+        {
+            (func.span, "load", vec![args[0].ast_clone(arena)])
+        }
+        _ => return None,
+    };
+
+    let func = import_field(arena, symbols, span, "std.reference", function);
+    Some(Expr::app(arena, func, args))
+}
+
+/// Desugars the statement forms of `import!` which bind the imported module
+///
+/// ```gluon,ignore
+/// import! std.list as list
+/// body
+/// // becomes
+/// let list = import! std.list
+/// body
+///
+/// import! { map, filter } from std.list
+/// body
+/// // becomes
+/// let { map, filter } = import! std.list
+/// body
+/// ```
+fn desugar_import_binding<'ast>(
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
+    expr: &SpannedExpr<'ast, Symbol>,
+) -> Option<Expr<'ast, Symbol>> {
+    let (bound, body) = match &expr.value {
+        Expr::Do(Do {
+            id: None,
+            bound,
+            body,
+            ..
+        }) => (bound, body),
+        _ => return None,
+    };
+    let (func, args) = match &bound.value {
+        Expr::App { func, args, .. }
+            if args.len() == 3
+                && matches!(&func.value, Expr::Ident(id) if id.name.as_str() == "import!") =>
+        {
+            (func, args)
+        }
+        _ => return None,
+    };
+
+    let (pattern, path) = match (&args[0].value, &args[1].value, &args[2].value) {
+        (_, Expr::Ident(keyword), Expr::Ident(alias)) if keyword.name.as_str() == "as" => (
+            pos::spanned(args[2].span, Pattern::Ident(alias.clone())),
+            &args[0],
+        ),
+        (
+            Expr::Record {
+                types,
+                exprs,
+                base: None,
+                ..
+            },
+            Expr::Ident(keyword),
+            _,
+        ) if keyword.name.as_str() == "from" => {
+            let type_fields = types.iter().map(|field| {
+                Some(PatternField::Type {
+                    name: field.name.clone(),
+                })
+            });
+            let value_fields = exprs.iter().map(|field| {
+                let value = match &field.value {
+                    Some(value) => match &value.value {
+                        Expr::Ident(id) => {
+                            Some(pos::spanned(value.span, Pattern::Ident(id.clone())))
+                        }
+                        _ => return None,
+                    },
+                    None => None,
+                };
+                Some(PatternField::Value {
+                    name: field.name.clone(),
+                    value,
+                })
+            });
+            let fields = match type_fields.chain(value_fields).collect::<Option<Vec<_>>>() {
+                Some(fields) => fields,
+                // Leave it to the import macro to report the error
+                None => return None,
+            };
+            (
+                pos::spanned(
+                    args[0].span,
+                    Pattern::Record {
+                        typ: Type::hole(),
+                        fields: arena.alloc_extend(fields),
+                        implicit_import: None,
+                    },
+                ),
+                &args[2],
+            )
+        }
+        _ => return None,
+    };
+
+    let import = pos::spanned(
+        bound.span,
+        Expr::app(
+            arena,
+            (**func).ast_clone(arena),
+            Some(path.ast_clone(arena)),
+        ),
+    );
+    let binding = arena.alloc(ValueBinding {
+        name: pattern,
+        expr: import,
+        ..ValueBinding::default()
+    });
+    Some(Expr::LetBindings(
+        ValueBindings::Plain(binding),
+        arena.alloc((**body).ast_clone(arena)),
+    ))
+}
+
+/// Returns true if `expr` refers to a variable named by `names`. Shadowing is not taken into
+/// account so this may give false positives.
+fn mentions_any(expr: &SpannedExpr<'_, Symbol>, names: &[&str]) -> bool {
+    struct Mentions<'n> {
+        names: &'n [&'n str],
+        found: bool,
+    }
+
+    impl<'a, 'ast> Visitor<'a, 'ast> for Mentions<'_> {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+            match &expr.value {
+                Expr::Ident(id) if self.names.contains(&id.name.as_str()) => self.found = true,
+                Expr::Record { exprs, .. } => {
+                    // `{ x }` refers to the variable `x`
+                    if exprs.iter().any(|field| {
+                        field.value.is_none() && self.names.contains(&field.name.value.as_str())
+                    }) {
+                        self.found = true;
+                    }
+                }
+                _ => (),
+            }
+            if !self.found {
+                ast::walk_expr(self, expr);
+            }
+        }
+    }
+
+    let mut mentions = Mentions {
+        names,
+        found: false,
+    };
+    mentions.visit_expr(expr);
+    mentions.found
+}
+
+struct Binds<'n> {
+    name: &'n str,
+    found: bool,
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for Binds<'_> {
+    type Ident = Symbol;
+
+    fn visit_pattern(&mut self, pattern: &'a SpannedPattern<'ast, Symbol>) {
+        match &pattern.value {
+            Pattern::Ident(id) if id.name.as_str() == self.name => self.found = true,
+            Pattern::As(id, _) if id.value.as_str() == self.name => self.found = true,
+            Pattern::Record { fields, .. } => {
+                if fields.iter().any(|field| match field {
+                    PatternField::Value { name, value: None } => name.value.as_str() == self.name,
+                    _ => false,
+                }) {
+                    self.found = true;
+                }
+            }
+            _ => (),
+        }
+        ast::walk_pattern(self, &pattern.value);
+    }
+
+    // Lambda and function arguments
+    fn visit_spanned_typed_ident(&mut self, id: &'a SpannedIdent<Symbol>) {
+        if id.value.name.as_str() == self.name {
+            self.found = true;
+        }
+    }
+}
+
+/// Returns true if `expr` itself (and not one of its sub-expressions) binds a variable called
+/// `name`. The variable is then in scope in some part of `expr`. The arguments of let bindings
+/// are not included as they are only in scope in the binding itself.
+fn introduces_name(expr: &SpannedExpr<'_, Symbol>, name: &str) -> bool {
+    let mut binds = Binds { name, found: false };
+    match &expr.value {
+        Expr::LetBindings(bindings, _) => {
+            for bind in &**bindings {
+                binds.visit_pattern(&bind.name);
+            }
+        }
+        Expr::Lambda(lambda) => {
+            for arg in &*lambda.args {
+                binds.visit_spanned_typed_ident(&arg.name);
+            }
+        }
+        Expr::Match(_, alts) => {
+            for alt in &**alts {
+                binds.visit_pattern(&alt.pattern);
+            }
+        }
+        Expr::Do(Do { id: Some(id), .. }) => binds.visit_pattern(id),
+        _ => (),
+    }
+    binds.found
+}
+
+/// Returns true if `expr` is a chain of `do` bindings, where no bound expression refers to a
+/// variable bound earlier in the chain, that ends in `wrap value`
+fn is_applicative_do(expr: &SpannedExpr<'_, Symbol>) -> bool {
+    let mut names = Vec::new();
+    let mut current = expr;
+    loop {
+        match &current.value {
+            Expr::Do(Do {
+                id: Some(id),
+                typ: None,
+                bound,
+                body,
+                ..
+            }) => {
+                let name = match &id.value {
+                    Pattern::Ident(id) => id.name.as_str(),
+                    _ => return false,
+                };
+                if mentions_any(bound, &names) {
+                    return false;
+                }
+                names.push(name);
+                current = body;
+            }
+            Expr::App {
+                func,
+                implicit_args,
+                args,
+            } => {
+                return !names.is_empty()
+                    && implicit_args.is_empty()
+                    && args.len() == 1
+                    && matches!(&func.value, Expr::Ident(id) if id.name.as_str() == "wrap");
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Desugars a `do` block whose bound expressions are independent of each other into applicative
+/// form, which does not need a `Monad` and lets the effects be evaluated independently.
+///
+/// ```f#
+/// do x = a
+/// do y = b
+/// wrap (f x y)
+/// ```
+///
+/// becomes `(import! std.applicative).map2 (\x y -> f x y) a b`, with any further bindings
+/// added through `(import! std.applicative).apply`. A single binding becomes
+/// `apply (wrap (\x -> ..)) a`.
+fn desugar_applicative_do<'ast>(
+    arena: ast::ArenaRef<'_, 'ast, Symbol>,
+    symbols: &mut Symbols,
+    expr: &SpannedExpr<'ast, Symbol>,
+) -> Option<Expr<'ast, Symbol>> {
+    if !is_applicative_do(expr) {
+        return None;
+    }
+
+    let span = expr.span;
+    let mut lambda_args = Vec::new();
+    let mut bounds = Vec::new();
+    let mut current = expr;
+    let value = loop {
+        match &current.value {
+            Expr::Do(do_expr) => {
+                let id = do_expr.id.as_ref().expect("do binding");
+                match &id.value {
+                    Pattern::Ident(ident) => {
+                        lambda_args.push(Argument::explicit(pos::spanned(id.span, ident.clone())))
+                    }
+                    _ => unreachable!(),
+                }
+                bounds.push((*do_expr.bound).ast_clone(arena));
+                current = &*do_expr.body;
+            }
+            Expr::App { args, .. } => break args[0].ast_clone(arena),
+            _ => unreachable!(),
+        }
+    };
+
+    let lambda = pos::spanned(
+        span,
+        Expr::Lambda(Lambda {
+            id: TypedIdent::new(symbols.simple_symbol("applicative_do")),
+            args: arena.alloc_extend(lambda_args),
+            body: arena.alloc(value),
+        }),
+    );
+
+    let mut bounds = bounds.into_iter();
+    let mut applied = if bounds.len() == 1 {
+        let wrap = import_field(arena, symbols, span, "std.applicative", "wrap");
+        Expr::app(arena, wrap, Some(lambda))
+    } else {
+        let map2 = import_field(arena, symbols, span, "std.applicative", "map2");
+        let args = Some(lambda).into_iter().chain(bounds.by_ref().take(2));
+        Expr::app(arena, map2, args)
+    };
+    for bound in bounds {
+        let apply = import_field(arena, symbols, span, "std.applicative", "apply");
+        applied = Expr::app(arena, apply, vec![pos::spanned(span, applied), bound]);
+    }
+
+    Some(applied)
+}
+
 struct MacroVisitor<'a: 'b, 'b, 'c, 'd, 'e, 'ast> {
     expander: &'b mut MacroExpander<'a>,
     symbols: &'c mut Symbols,
     arena: &'d mut ast::OwnedArena<'ast, Symbol>,
     exprs: Vec<(&'e mut SpannedExpr<'ast, Symbol>, Arc<dyn Macro>)>,
+    /// The names in `DESUGARED_NAMES` which are bound by an enclosing expression, and whether
+    /// the binding refers to the function from the standard library
+    scope: Vec<(&'static str, bool)>,
 }
 
 impl<'a, 'b, 'c, 'e, 'ast> MutVisitor<'e, 'ast> for MacroVisitor<'a, 'b, 'c, '_, 'e, 'ast> {
     type Ident = Symbol;
 
     fn visit_expr(&mut self, expr: &'e mut SpannedExpr<'ast, Symbol>) {
+        // The desugared forms keep the original expression so that it can still be formatted
+        let desugared = if let Some(desugared) =
+            desugar_reference(self.arena.borrow(), self.symbols, &self.scope, expr)
+        {
+            Some(desugared)
+        } else if let Some(desugared) = desugar_import_binding(self.arena.borrow(), expr) {
+            Some(desugared)
+        } else if !is_shadowed(&self.scope, "wrap") {
+            desugar_applicative_do(self.arena.borrow(), self.symbols, expr)
+        } else {
+            None
+        };
+        if let Some(desugared) = desugared {
+            replace_expr(self.arena.borrow(), expr, desugared);
+        }
+
         let replacement = match &mut expr.value {
             Expr::App {
                 implicit_args,
@@ -605,7 +1071,41 @@ impl<'a, 'b, 'c, 'e, 'ast> MutVisitor<'e, 'ast> for MacroVisitor<'a, 'b, 'c, '_,
         if let Some(future) = replacement {
             self.exprs.push((expr, future));
         } else {
-            ast::walk_mut_expr(self, expr);
+            let bound: Vec<_> = DESUGARED_NAMES
+                .iter()
+                .filter(|name| introduces_name(expr, name))
+                .map(|&name| (name, name == "wrap" && imports_applicative_wrap(expr)))
+                .collect();
+            let scope_len = self.scope.len();
+            if !matches!(expr.value, Expr::LetBindings(..)) {
+                self.scope.extend(bound);
+                ast::walk_mut_expr(self, expr);
+            } else if let Expr::LetBindings(bindings, body) = &mut expr.value {
+                // The arguments of a binding are only in scope in that binding and the names
+                // bound by it are only in scope in the body (unless the bindings are recursive)
+                let is_recursive = bindings.is_recursive();
+                for bind in bindings.iter_mut() {
+                    let bind_scope_len = self.scope.len();
+                    if is_recursive {
+                        self.scope.extend(bound.iter().cloned());
+                    }
+                    self.scope.extend(
+                        DESUGARED_NAMES
+                            .iter()
+                            .filter(|&&name| {
+                                bind.args
+                                    .iter()
+                                    .any(|arg| arg.name.value.name.as_str() == name)
+                            })
+                            .map(|&name| (name, false)),
+                    );
+                    self.visit_expr(&mut bind.expr);
+                    self.scope.truncate(bind_scope_len);
+                }
+                self.scope.extend(bound);
+                self.visit_expr(body);
+            }
+            self.scope.truncate(scope_len);
         }
     }
 }
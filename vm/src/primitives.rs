@@ -14,11 +14,12 @@ use crate::base::types::ArcType;
 
 use crate::{
     api::{
-        generic::{self, A, S},
+        generic::{self, A, E, S},
         primitive, Array, Getable, Opaque, OpaqueRef, Pushable, Pushed, RuntimeResult, ValueRef,
         VmType, WithVM, IO,
     },
-    gc::{DataDef, Trace, WriteOnly},
+    gc::{DataDef, Trace, ValueSize, WriteOnly},
+    interner::SHORT_STRING_LEN,
     stack::{ExternState, StackFrame},
     types::VmInt,
     value::{GcStr, Repr, ValueArray},
@@ -146,6 +147,9 @@ pub mod array {
                     result
                 }
             }
+            fn value_size(&self) -> Option<ValueSize> {
+                Some(ValueSize::Array(self.lhs.len() + self.rhs.len()))
+            }
         }
         let vm = lhs.vm();
         let mut context = vm.context();
@@ -245,7 +249,7 @@ mod int {
 
 mod string {
     use super::*;
-    use crate::value::ValueStr;
+    use crate::value::{ValueRepr, ValueStr};
 
     pub(crate) fn append(lhs: WithVM<&str>, rhs: &str) -> RuntimeResult<Pushed<String>, Error> {
         #[derive(Trace)]
@@ -275,6 +279,9 @@ mod string {
                     result
                 }
             }
+            fn value_size(&self) -> Option<ValueSize> {
+                Some(ValueSize::String(self.lhs.len() + self.rhs.len()))
+            }
         }
 
         let vm = lhs.vm;
@@ -282,6 +289,22 @@ mod string {
 
         let mut context = vm.current_context();
         let mut context = context.context();
+
+        let len = lhs.len() + rhs.len();
+        if len <= SHORT_STRING_LEN {
+            let mut buffer = [0; SHORT_STRING_LEN];
+            buffer[..lhs.len()].copy_from_slice(lhs.as_bytes());
+            buffer[lhs.len()..len].copy_from_slice(rhs.as_bytes());
+            // SAFETY Two UTF-8 strings appended together are valid UTF-8
+            let s = unsafe { crate::real_std::str::from_utf8_unchecked(&buffer[..len]) };
+            if let Some(interned) = vm.global_env().short_string(s) {
+                context
+                    .stack
+                    .push(&*construct_gc!(ValueRepr::String(@interned.inner())));
+                return RuntimeResult::Return(Pushed::default());
+            }
+        }
+
         let value = match alloc!(context, StrAppend { lhs: lhs, rhs: rhs }) {
             Ok(x) => x,
             Err(err) => return RuntimeResult::Panic(err),
@@ -386,8 +409,8 @@ fn show_byte(c: u8) -> String {
 }
 
 extern "C" fn error(_: &Thread) -> Status {
-    // We expect a string as an argument to this function but we only return Status::Error
-    // and let the caller take care of printing the message
+    // We expect a string (`error`) or any value (`error_with`) as an argument to this function but
+    // we only return Status::Error and let the caller turn the value into the panic's payload
     Status::Error
 }
 
@@ -858,6 +881,23 @@ pub mod st_string {
     pub(crate) fn push_str(buf: &StringBuf<S>, s: &str) {
         buf.0.lock().unwrap().push_str(s)
     }
+
+    pub(crate) fn push_format(
+        buf: WithVM<&StringBuf<S>>,
+        format: &str,
+        args: OpaqueRef<A>,
+    ) -> RuntimeResult<(), String> {
+        let mut out = buf.value.0.lock().unwrap();
+        let len = out.len();
+        match crate::format::write_format(buf.vm, &mut out, format, args.get_variant()) {
+            Ok(()) => RuntimeResult::Return(()),
+            Err(err) => {
+                // Do not leave a partially formatted string in the buffer
+                out.truncate(len);
+                RuntimeResult::Panic(err)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Default, VmType, Userdata, Trace)]
@@ -876,7 +916,8 @@ pub fn load_string_buf(vm: &Thread) -> Result<ExternModule> {
             new => primitive!(1, "std.effect.st.string.new", |()| StringBuf(Default::default(), PhantomData::<S>)),
             slice => primitive!(3, std::effect::st::string::prim::slice),
             pop => primitive!(1, std::effect::st::string::prim::pop),
-            push_str => primitive!(2, std::effect::st::string::prim::push_str)
+            push_str => primitive!(2, std::effect::st::string::prim::push_str),
+            push_format => primitive!(3, std::effect::st::string::prim::push_format)
         },
     )
 }
@@ -893,6 +934,10 @@ pub fn load<'vm>(vm: &'vm Thread) -> Result<ExternModule> {
             string_compare => primitive!(2, "std.prim.string_compare", str::cmp),
             string_eq => primitive!(2, "std.prim.string_eq", <str as PartialEq>::eq),
             error => primitive::<fn(StdString) -> Pushed<A>>("std.prim.error", std::prim::error),
+            error_with => primitive::<fn(OpaqueRef<'vm, E>) -> Pushed<A>>(
+                "std.prim.error_with",
+                std::prim::error
+            ),
             discriminant_value => primitive::<fn(OpaqueRef<'vm, A>) -> VmInt>(
                 "std.prim.discriminant_value",
                 std::prim::discriminant_value
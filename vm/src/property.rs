@@ -0,0 +1,251 @@
+//! Primitives used by `std.test` to generate random inputs for properties and to shrink the
+//! counter-examples which falsify them.
+//!
+//! Random generators are pure, each primitive takes the current seed and returns the generated
+//! value together with the next seed.
+
+use crate::real_std::iter;
+
+use crate::{
+    api::{generic::A, OpaqueRef},
+    thread::Thread,
+    types::VmInt,
+    ExternModule, Result,
+};
+
+field_decl! { value, seed }
+
+type Next<T> = record_type! {
+    value => T,
+    seed => VmInt
+};
+
+/// Advances `seed` using the SplitMix64 generator
+fn split_mix(seed: VmInt) -> (u64, VmInt) {
+    let seed = (seed as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31), seed as VmInt)
+}
+
+fn next_int(seed: VmInt) -> Next<VmInt> {
+    let (value, seed) = split_mix(seed);
+    record_no_decl! {
+        value => value as VmInt,
+        seed => seed
+    }
+}
+
+/// Maps a random `value` into the inclusive range `low..=high`
+fn in_range(value: u64, low: VmInt, high: VmInt) -> VmInt {
+    if low >= high {
+        low
+    } else {
+        let range = (i128::from(high) - i128::from(low) + 1) as u128;
+        (i128::from(low) + (u128::from(value) % range) as i128) as VmInt
+    }
+}
+
+/// Generates an integer in the inclusive range `low..=high`
+fn int_in_range(low: VmInt, high: VmInt, seed: VmInt) -> Next<VmInt> {
+    let (value, seed) = split_mix(seed);
+    record_no_decl! {
+        value => in_range(value, low, high),
+        seed => seed
+    }
+}
+
+/// Generates a float in the range `-size..size`
+fn gen_float(size: VmInt, seed: VmInt) -> Next<f64> {
+    let (value, seed) = split_mix(seed);
+    let unit = (value >> 11) as f64 / (1u64 << 53) as f64;
+    record_no_decl! {
+        value => (unit * 2.0 - 1.0) * size as f64,
+        seed => seed
+    }
+}
+
+/// Generates a string of at most `size` printable ASCII characters
+fn gen_string(size: VmInt, seed: VmInt) -> Next<String> {
+    let (len, mut seed) = split_mix(seed);
+    let len = len % (size.max(0) as u64 + 1);
+    let mut s = String::with_capacity(len as usize);
+    for _ in 0..len {
+        let (c, next_seed) = split_mix(seed);
+        seed = next_seed;
+        s.push((b' ' + (c % 95) as u8) as char);
+    }
+    record_no_decl! {
+        value => s,
+        seed => seed
+    }
+}
+
+/// Returns the ranges which are removed from a sequence of length `len` to shrink it, starting
+/// with the largest
+fn removals(len: usize) -> impl Iterator<Item = (usize, usize)> {
+    let mut chunk = len;
+    iter::from_fn(move || {
+        if chunk == 0 {
+            None
+        } else {
+            let current = chunk;
+            chunk /= 2;
+            Some(current)
+        }
+    })
+    .flat_map(move |chunk| {
+        (0..len)
+            .step_by(chunk)
+            .map(move |start| (start, (start + chunk).min(len)))
+    })
+}
+
+fn shrink_int(i: VmInt) -> Vec<VmInt> {
+    let mut candidates = Vec::new();
+    if i == 0 {
+        return candidates;
+    }
+    candidates.push(0);
+    if let Some(negated) = i.checked_neg() {
+        if negated > i {
+            candidates.push(negated);
+        }
+    }
+    let mut step = i / 2;
+    while step != 0 {
+        candidates.push(i - step);
+        step /= 2;
+    }
+    candidates
+}
+
+fn shrink_float(f: f64) -> Vec<f64> {
+    let mut candidates = Vec::new();
+    if f == 0.0 {
+        return candidates;
+    }
+    candidates.push(0.0);
+    if !f.is_finite() {
+        return candidates;
+    }
+    if f < 0.0 {
+        candidates.push(-f);
+    }
+    if f.trunc() != f {
+        candidates.push(f.trunc());
+    }
+    if f.abs() > 1.0 {
+        candidates.push(f / 2.0);
+    }
+    candidates
+}
+
+fn shrink_string(s: &str) -> Vec<String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut candidates: Vec<String> = removals(chars.len())
+        .map(|(start, end)| chars[..start].iter().chain(&chars[end..]).collect())
+        .collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if c != 'a' {
+            let mut simplified = chars.clone();
+            simplified[i] = 'a';
+            candidates.push(simplified.into_iter().collect());
+        }
+    }
+    candidates
+}
+
+fn shrink_array<'vm>(array: OpaqueRef<'vm, [A]>) -> Vec<Vec<OpaqueRef<'vm, A>>> {
+    let len = array.len();
+    removals(len)
+        .map(|(start, end)| {
+            (0..start)
+                .chain(end..len)
+                .filter_map(|i| array.get(i as VmInt))
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns copies of `array` where a single element is replaced by one of the candidates in
+/// `element_candidates` at the same index
+fn shrink_elements<'vm>(
+    array: OpaqueRef<'vm, [A]>,
+    element_candidates: Vec<OpaqueRef<'vm, [A]>>,
+) -> Vec<Vec<OpaqueRef<'vm, A>>> {
+    let elements: Vec<_> = array.iter().collect();
+    let mut shrunk = Vec::new();
+    for (i, candidates) in element_candidates.iter().enumerate().take(elements.len()) {
+        for candidate in candidates.iter() {
+            let mut elements = elements.clone();
+            elements[i] = candidate;
+            shrunk.push(elements);
+        }
+    }
+    shrunk
+}
+
+mod std {
+    pub mod test {
+        pub use crate::property as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    ExternModule::new(
+        vm,
+        record! {
+            next_int => primitive!(1, std::test::prim::next_int),
+            int_in_range => primitive!(3, std::test::prim::int_in_range),
+            gen_float => primitive!(2, std::test::prim::gen_float),
+            gen_string => primitive!(2, std::test::prim::gen_string),
+            shrink_int => primitive!(1, std::test::prim::shrink_int),
+            shrink_float => primitive!(1, std::test::prim::shrink_float),
+            shrink_string => primitive!(1, std::test::prim::shrink_string),
+            shrink_array => primitive!(1, std::test::prim::shrink_array),
+            shrink_elements => primitive!(2, std::test::prim::shrink_elements)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shrink_int_towards_zero() {
+        assert_eq!(shrink_int(0), Vec::<VmInt>::new());
+        assert_eq!(shrink_int(10), vec![0, 5, 8, 9]);
+        assert_eq!(shrink_int(-4), vec![0, 4, -2, -3]);
+        assert_eq!(shrink_int(VmInt::min_value())[0], 0);
+    }
+
+    #[test]
+    fn shrink_string_removes_chunks() {
+        let candidates = shrink_string("abc");
+        assert_eq!(candidates[0], "");
+        assert!(candidates.contains(&"bc".to_string()));
+        assert!(candidates.contains(&"aac".to_string()));
+        assert!(candidates.iter().all(|s| s != "abc"));
+    }
+
+    #[test]
+    fn in_range_is_inclusive() {
+        let mut seed = 0;
+        let mut seen = [false; 5];
+        for _ in 0..100 {
+            let (value, next_seed) = split_mix(seed);
+            seed = next_seed;
+            let value = in_range(value, -2, 2);
+            assert!(-2 <= value && value <= 2, "{}", value);
+            seen[(value + 2) as usize] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        assert_eq!(
+            in_range(123, VmInt::min_value(), VmInt::max_value()),
+            VmInt::min_value() + 123
+        );
+    }
+}
@@ -418,6 +418,49 @@ pub mod borrow {
     }
 }
 
+/// Persistent maps are serialized as ordinary maps
+pub mod persistent_map {
+    use super::*;
+    use crate::base::fnv::FnvMap;
+    use rpds::HashTrieMapSync;
+    use std::{
+        borrow::{Borrow, BorrowMut},
+        hash::Hash,
+    };
+
+    pub fn deserialize<'de, D, K, V, Seed, Seed2>(
+        seed: &mut Seed,
+        deserializer: D,
+    ) -> Result<HashTrieMapSync<K, V>, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Eq + Hash,
+        FnvMap<K, V>: DeserializeState<'de, Seed2>,
+        Seed: BorrowMut<Seed2>,
+    {
+        FnvMap::deserialize_state(seed.borrow_mut(), deserializer)
+            .map(|map| map.into_iter().collect())
+    }
+
+    pub fn serialize<S, K, V, Seed, Seed2>(
+        map: &HashTrieMapSync<K, V>,
+        serializer: S,
+        seed: &Seed,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Eq + Hash + Clone,
+        V: Clone,
+        FnvMap<K, V>: SerializeState<Seed2>,
+        Seed: Borrow<Seed2>,
+    {
+        map.iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<FnvMap<_, _>>()
+            .serialize_state(serializer, seed.borrow())
+    }
+}
+
 pub mod rw_lock {
     use super::*;
 
@@ -445,6 +488,75 @@ pub mod rw_lock {
     }
 }
 
+pub(crate) mod snapshot {
+    use super::*;
+    use crate::snapshot::Snapshot;
+    use std::borrow::{Borrow, BorrowMut};
+
+    pub fn deserialize<'de, D, T, Seed, Seed2>(
+        seed: &mut Seed,
+        deserializer: D,
+    ) -> Result<Snapshot<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: DeserializeState<'de, Seed2>,
+        Seed: BorrowMut<Seed2>,
+    {
+        T::deserialize_state(seed.borrow_mut(), deserializer).map(Snapshot::new)
+    }
+
+    pub fn serialize<S, T, Seed, Seed2>(
+        t: &Snapshot<T>,
+        serializer: S,
+        seed: &Seed,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: SerializeState<Seed2>,
+        Seed: Borrow<Seed2>,
+    {
+        t.read(|t| t.serialize_state(serializer, seed.borrow()))
+    }
+}
+
+pub(crate) mod persistent_map_snapshot {
+    use super::*;
+    use crate::{base::fnv::FnvMap, snapshot::Snapshot};
+    use rpds::HashTrieMapSync;
+    use std::{
+        borrow::{Borrow, BorrowMut},
+        hash::Hash,
+    };
+
+    pub fn deserialize<'de, D, K, V, Seed, Seed2>(
+        seed: &mut Seed,
+        deserializer: D,
+    ) -> Result<Snapshot<HashTrieMapSync<K, V>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Eq + Hash,
+        FnvMap<K, V>: DeserializeState<'de, Seed2>,
+        Seed: BorrowMut<Seed2>,
+    {
+        super::persistent_map::deserialize(seed, deserializer).map(Snapshot::new)
+    }
+
+    pub fn serialize<S, K, V, Seed, Seed2>(
+        t: &Snapshot<HashTrieMapSync<K, V>>,
+        serializer: S,
+        seed: &Seed,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Eq + Hash + Clone,
+        V: Clone,
+        FnvMap<K, V>: SerializeState<Seed2>,
+        Seed: Borrow<Seed2>,
+    {
+        t.read(|t| super::persistent_map::serialize(t, serializer, seed))
+    }
+}
+
 pub mod typ {
     use super::*;
     use crate::base::symbol::Symbol;
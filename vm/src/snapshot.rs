@@ -0,0 +1,142 @@
+//! Values which are read far more often than they are written, such as the global type
+//! information.
+//!
+//! A `Snapshot` stores its value behind an atomic pointer. Reading only loads the pointer, so
+//! threads executing simultaneously never wait on each other, while writers copy the value, modify
+//! the copy and publish it in place of the old value. Old values are reclaimed with
+//! `crossbeam_epoch` once no reader can still observe them. Since every write copies the value,
+//! large values should be persistent data structures whose copies share all unmodified parts.
+use std::{
+    fmt,
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+
+pub(crate) struct Snapshot<T> {
+    current: Atomic<Arc<T>>,
+    /// Serializes writers so that no update is lost
+    write_lock: Mutex<()>,
+}
+
+impl<T> Default for Snapshot<T>
+where
+    T: Default,
+{
+    fn default() -> Self {
+        Snapshot::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for Snapshot<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.read(|value| value.fmt(f))
+    }
+}
+
+impl<T> Drop for Snapshot<T> {
+    fn drop(&mut self) {
+        // SAFETY `&mut self` guarantees that there are no readers of the current value
+        unsafe {
+            drop(
+                self.current
+                    .load(Ordering::Relaxed, epoch::unprotected())
+                    .into_owned(),
+            );
+        }
+    }
+}
+
+impl<T> Snapshot<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Snapshot {
+            current: Atomic::new(Arc::new(value)),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns the current value, which is unaffected by later writes
+    pub(crate) fn load(&self) -> Arc<T> {
+        self.read_arc(Arc::clone)
+    }
+
+    /// Calls `f` with the current value without taking a reference count to it
+    pub(crate) fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.read_arc(|value| f(value))
+    }
+
+    fn read_arc<R>(&self, f: impl FnOnce(&Arc<T>) -> R) -> R {
+        let guard = epoch::pin();
+        // SAFETY The pointer is never null and the guard keeps the value alive until `f` returns
+        f(unsafe { self.current.load(Ordering::Acquire, &guard).deref() })
+    }
+
+    /// Replaces the value with a copy which has been modified by `f`
+    pub(crate) fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Clone,
+    {
+        let _write_lock = self
+            .write_lock
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let guard = epoch::pin();
+        let current = self.current.load(Ordering::Acquire, &guard);
+        // SAFETY The pointer is never null and only writers, which hold `write_lock`, replace it
+        let mut value = T::clone(unsafe { current.deref() });
+        let result = f(&mut value);
+        let old = self
+            .current
+            .swap(Owned::new(Arc::new(value)), Ordering::AcqRel, &guard);
+        // SAFETY `old` is no longer reachable from `self`, readers which loaded it before the swap
+        // are pinned to an earlier epoch
+        unsafe { guard.defer_destroy(old) };
+        result
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T
+    where
+        T: Clone,
+    {
+        // SAFETY `&mut self` guarantees that there are no readers or writers
+        unsafe {
+            let mut current = self.current.load(Ordering::Relaxed, epoch::unprotected());
+            Arc::make_mut(current.deref_mut())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_does_not_affect_loaded_values() {
+        let snapshot = Snapshot::new(vec![1]);
+        let before = snapshot.load();
+        snapshot.update(|values| values.push(2));
+        assert_eq!(*before, [1]);
+        assert_eq!(*snapshot.load(), [1, 2]);
+        assert_eq!(snapshot.read(|values| values.len()), 2);
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let snapshot = Snapshot::new(0);
+        crossbeam_utils::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|_| {
+                    for _ in 0..100 {
+                        snapshot.update(|value| *value += 1);
+                        snapshot.read(|value| assert!(*value > 0));
+                    }
+                });
+            }
+        })
+        .unwrap();
+        assert_eq!(*snapshot.load(), 400);
+    }
+}
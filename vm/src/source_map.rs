@@ -4,7 +4,7 @@ use crate::base::pos::Line;
 use crate::base::symbol::Symbol;
 use crate::base::types::ArcType;
 
-use crate::types::VmIndex;
+use crate::{types::VmIndex, value::BytecodeFunction};
 
 #[derive(Debug, Default, Eq, PartialEq, Clone, Hash)]
 #[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
@@ -53,6 +53,121 @@ impl SourceMap {
             Some(self.map[p - 1].1)
         }
     }
+
+    /// Returns the lines which have instructions defined on them
+    pub fn lines(&self) -> impl Iterator<Item = Line> + '_ {
+        // The last entry only marks the end of the function
+        self.map[..self.map.len().saturating_sub(1)]
+            .iter()
+            .map(|&(_, line)| line)
+    }
+}
+
+/// The set of lines which have instructions associated with them, usually collected from a module
+/// and all the functions defined in it. Lines without instructions can never be executed so a
+/// breakpoint on them is never hit and coverage can't be reported for them.
+///
+/// Checking whether a line is executable takes constant time.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Hash)]
+pub struct ExecutableLines {
+    /// `lines[i]` is true if the (zero-based) line `i` has instructions
+    lines: Vec<bool>,
+    len: usize,
+}
+
+impl ExecutableLines {
+    pub fn new() -> ExecutableLines {
+        ExecutableLines::default()
+    }
+
+    /// Collects the lines of `function` and of every function defined inside it
+    pub fn from_function(function: &BytecodeFunction) -> ExecutableLines {
+        let mut lines = ExecutableLines::new();
+        lines.add_function(function);
+        lines
+    }
+
+    /// Adds the lines of `function` and of every function defined inside it
+    pub fn add_function(&mut self, function: &BytecodeFunction) {
+        self.add_source_map(&function.debug_info.source_map);
+        for inner in &function.inner_functions {
+            self.add_function(inner);
+        }
+    }
+
+    pub fn add_source_map(&mut self, source_map: &SourceMap) {
+        for line in source_map.lines() {
+            self.insert(line);
+        }
+    }
+
+    pub fn insert(&mut self, line: Line) {
+        let index = line.to_usize();
+        if index >= self.lines.len() {
+            self.lines.resize(index + 1, false);
+        }
+        if !self.lines[index] {
+            self.lines[index] = true;
+            self.len += 1;
+        }
+    }
+
+    /// Returns true if `line` has instructions associated with it
+    pub fn contains(&self, line: Line) -> bool {
+        self.lines.get(line.to_usize()).cloned().unwrap_or(false)
+    }
+
+    /// Returns the first executable line at or after `line`. Can be used to move a breakpoint on
+    /// a line without instructions (such as a comment or a type definition) to the line that
+    /// would be executed next.
+    pub fn next_executable(&self, line: Line) -> Option<Line> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(line.to_usize())
+            .find(|&(_, &executable)| executable)
+            .map(|(index, _)| Line(index as u32))
+    }
+
+    /// Returns the number of executable lines
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the executable lines in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = Line> + '_ {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|&(_, &executable)| executable)
+            .map(|(index, _)| Line(index as u32))
+    }
+}
+
+impl Extend<Line> for ExecutableLines {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Line>,
+    {
+        for line in iter {
+            self.insert(line);
+        }
+    }
+}
+
+impl std::iter::FromIterator<Line> for ExecutableLines {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Line>,
+    {
+        let mut lines = ExecutableLines::new();
+        lines.extend(iter);
+        lines
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
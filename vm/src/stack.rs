@@ -1,5 +1,5 @@
 use std::{
-    fmt,
+    cmp, fmt,
     marker::PhantomData,
     ops::{Deref, DerefMut, Index, IndexMut, Range, RangeFrom, RangeFull, RangeTo},
 };
@@ -7,8 +7,9 @@ use std::{
 use crate::base::{pos::Line, symbol::Symbol};
 
 use crate::{
-    gc::{self, CloneUnrooted, CopyUnrooted, GcPtr, Trace},
-    types::VmIndex,
+    gc::{self, CloneUnrooted, CopyUnrooted, Gc, GcPtr, Trace},
+    stack_map::StackMap,
+    types::{Instruction, VmIndex},
     value::{ClosureData, DataStruct, ExternFunction, Value, ValueRepr},
     Error, Result, Variants,
 };
@@ -331,6 +332,25 @@ where
     }
 }
 
+impl Frame<State> {
+    /// Returns the stack map of the `Call` instruction that this frame is suspended at, if it is
+    /// suspended at one
+    fn call_stack_map(&self) -> Option<&StackMap> {
+        match &self.state {
+            State::Closure(state) => {
+                // The instruction index is stored after stepping past the `Call` instruction
+                let call_index = state.instruction_index.checked_sub(1)?;
+                let function = &state.closure.function;
+                match function.instructions.get(call_index)? {
+                    Instruction::Call(_) => function.stack_maps.get(call_index),
+                    _ => None,
+                }
+            }
+            State::Extern(_) | State::Unknown => None,
+        }
+    }
+}
+
 impl<S> Frame<S> {
     fn to_state(&self) -> gc::Borrow<Frame<State>>
     where
@@ -406,6 +426,30 @@ where
 #[must_use = "Unused locks will prevent the stack from unwinding"]
 pub struct Lock(VmIndex);
 
+/// Controls how the stack of a thread gives back the memory it allocated while growing.
+///
+/// The values of all frames are stored contiguously, since the interpreter addresses them by
+/// their offset from the bottom of the stack, so the stack doubles its capacity when it runs out
+/// of space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct StackGrowth {
+    /// The capacity, in values, which the stack never shrinks below.
+    pub retained_capacity: VmIndex,
+    /// The stack shrinks when a function returns and less than `1 / shrink_factor` of its
+    /// capacity is used, so that a spike of deep recursion does not keep its memory alive. `0`
+    /// never shrinks the stack.
+    pub shrink_factor: VmIndex,
+}
+
+impl Default for StackGrowth {
+    fn default() -> Self {
+        StackGrowth {
+            retained_capacity: 4096,
+            shrink_factor: 4,
+        }
+    }
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(
@@ -425,10 +469,62 @@ pub struct Stack {
     #[cfg_attr(feature = "serde_derive", serde(state))]
     frames: Vec<Frame<State>>,
     max_stack_size: VmIndex,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    growth: StackGrowth,
 }
 
 unsafe impl Trace for Stack {
-    impl_trace_fields! { self, gc; values }
+    unsafe fn root(&mut self) {
+        self.values.root();
+    }
+    unsafe fn unroot(&mut self) {
+        self.values.unroot();
+    }
+
+    /// Traces the values of each frame. The slots of a frame which is suspended at a call are
+    /// described by the stack map of that call so the slots holding unboxed values are skipped.
+    /// The innermost frame may be stopped anywhere so all of its values are traced.
+    fn trace(&self, gc: &mut Gc) {
+        let first_offset = self
+            .frames
+            .first()
+            .map_or(self.values.len(), |frame| frame.offset as usize);
+        self.values[..first_offset].trace(gc);
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            let offset = cmp::min(frame.offset as usize, self.values.len());
+            let end = self
+                .frames
+                .get(i + 1)
+                .map_or(self.values.len(), |next| next.offset as usize)
+                .max(offset);
+            let values = &self.values[offset..end];
+            match self.frames.get(i + 1).and_then(|_| frame.call_stack_map()) {
+                Some(map) => {
+                    for (slot, value) in values.iter().enumerate() {
+                        if slot >= map.len() as usize || map.may_contain_pointer(slot as VmIndex) {
+                            value.trace(gc);
+                        } else {
+                            debug_assert!(
+                                match value.get_repr() {
+                                    ValueRepr::Byte(_)
+                                    | ValueRepr::Int(_)
+                                    | ValueRepr::Float(_)
+                                    | ValueRepr::Tag(_) => true,
+                                    _ => false,
+                                },
+                                "Stack slot {} of {:?} holds a pointer: {:?}",
+                                slot,
+                                frame.state,
+                                value
+                            );
+                        }
+                    }
+                }
+                None => values.trace(gc),
+            }
+        }
+    }
 }
 
 impl Stack {
@@ -437,6 +533,7 @@ impl Stack {
             values: Vec::new(),
             frames: Vec::new(),
             max_stack_size: VmIndex::MAX,
+            growth: StackGrowth::default(),
         }
     }
 
@@ -444,6 +541,31 @@ impl Stack {
         self.max_stack_size = max_stack_size;
     }
 
+    pub fn set_growth(&mut self, growth: StackGrowth) {
+        self.growth = growth;
+    }
+
+    /// Returns the number of values the stack can hold without reallocating
+    pub fn capacity(&self) -> VmIndex {
+        self.values.capacity() as VmIndex
+    }
+
+    fn shrink(&mut self) {
+        let StackGrowth {
+            retained_capacity,
+            shrink_factor,
+        } = self.growth;
+        let capacity = self.values.capacity();
+        if shrink_factor != 0
+            && capacity > retained_capacity as usize
+            && self.values.len() < capacity / shrink_factor as usize
+        {
+            self.values
+                .shrink_to(cmp::max(self.values.len() * 2, retained_capacity as usize));
+            self.frames.shrink_to(self.frames.len() * 2);
+        }
+    }
+
     fn assert_pop(&self, count: VmIndex) {
         let frame = self.frames.last().unwrap();
         let args = if let State::Extern(ExternState {
@@ -879,6 +1001,7 @@ where
         }
         let stack = self.stack;
         stack.frames.pop().expect("Expected frame");
+        stack.shrink();
         match stack.frames.last() {
             Some(frame) => {
                 let stack = StackFrame {
@@ -937,6 +1060,7 @@ where
         if stack.len() + frame.state.max_stack_size() > stack.max_stack_size {
             return Err(Error::StackOverflow(stack.max_stack_size));
         }
+        stack.values.reserve(frame.state.max_stack_size() as usize);
 
         // SAFETY The frame's gc pointers are scanned the `Stack::trace` since they are on
         // the stack
@@ -1281,6 +1405,28 @@ mod tests {
         unsafe { gc.clear() }
     }
 
+    #[test]
+    fn shrink_after_spike() {
+        let _ = ::env_logger::try_init();
+
+        let mut stack = Stack::new();
+        stack.set_growth(StackGrowth {
+            retained_capacity: 16,
+            shrink_factor: 4,
+        });
+        let mut frame = StackFrame::new_frame(&mut stack, 0, State::Unknown).unwrap();
+        frame.push(Int(0));
+        frame = frame.enter_scope(1, &State::Unknown).unwrap();
+        for i in 0..1000 {
+            frame.push(Int(i));
+        }
+        assert!(frame.stack.capacity() > 1000);
+        frame.pop_many(1000);
+
+        frame = frame.exit_scope().unwrap();
+        assert_eq!(frame.stack.capacity(), 16);
+    }
+
     #[test]
     fn insert_stack_slice() {
         let _ = ::env_logger::try_init();
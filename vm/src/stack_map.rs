@@ -0,0 +1,140 @@
+//! Stack maps describing which slots of a function's stack frame may contain pointers into the
+//! garbage collected heap. A map is emitted for every instruction which may trigger a collection
+//! (a safepoint). The collector uses the map of the call that each suspended frame is stopped at
+//! to skip the slots which only hold unboxed values, the innermost frame is always traced in full.
+
+use crate::types::{Instruction, VmIndex};
+
+const BITS: usize = 64;
+
+/// The pointer layout of a stack frame at a single safepoint.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Hash)]
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
+pub struct StackMap {
+    /// The number of slots in the frame
+    len: VmIndex,
+    /// Bit set of the slots which may contain a pointer
+    pointers: Vec<u64>,
+}
+
+impl StackMap {
+    /// Creates a map of `len` slots where every slot may contain a pointer
+    pub fn new(len: VmIndex) -> StackMap {
+        let len_usize = len as usize;
+        let mut pointers = vec![!0; (len_usize + BITS - 1) / BITS];
+        if let Some(last) = pointers.last_mut() {
+            let used = len_usize % BITS;
+            if used != 0 {
+                *last = (1 << used) - 1;
+            }
+        }
+        StackMap { len, pointers }
+    }
+
+    /// The number of slots described by the map
+    pub fn len(&self) -> VmIndex {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Marks `slot` as only ever containing unboxed values
+    pub fn set_scalar(&mut self, slot: VmIndex) {
+        assert!(slot < self.len, "Stack map slot {} out of bounds", slot);
+        let slot = slot as usize;
+        self.pointers[slot / BITS] &= !(1 << (slot % BITS));
+    }
+
+    /// Returns true if `slot` may contain a pointer into the garbage collected heap
+    pub fn may_contain_pointer(&self, slot: VmIndex) -> bool {
+        slot < self.len && {
+            let slot = slot as usize;
+            self.pointers[slot / BITS] & (1 << (slot % BITS)) != 0
+        }
+    }
+
+    /// Returns the slots which may contain a pointer
+    pub fn pointer_slots(&self) -> impl Iterator<Item = VmIndex> + '_ {
+        (0..self.len).filter(move |&slot| self.may_contain_pointer(slot))
+    }
+}
+
+/// The stack maps of every safepoint in a function
+#[derive(Debug, Default, Eq, PartialEq, Clone, Hash)]
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
+pub struct StackMaps {
+    /// Maps ordered by the index of the instruction they describe
+    maps: Vec<(usize, StackMap)>,
+}
+
+impl StackMaps {
+    pub fn new() -> StackMaps {
+        StackMaps::default()
+    }
+
+    /// Defines the stack map for the safepoint at `instruction_index`.
+    /// This function must be called with indexes in increasing order
+    pub fn emit(&mut self, instruction_index: usize, map: StackMap) {
+        debug_assert!(self
+            .maps
+            .last()
+            .map_or(true, |&(index, _)| index < instruction_index));
+        self.maps.push((instruction_index, map));
+    }
+
+    /// Creates maps for hand written bytecode which treat every slot as a possible pointer
+    pub fn conservative(args: VmIndex, instructions: &[Instruction]) -> StackMaps {
+        let mut maps = StackMaps::new();
+        let mut stack_size = args as i64;
+        for (index, instruction) in instructions.iter().enumerate() {
+            if instruction.is_safepoint() {
+                maps.emit(index, StackMap::new(stack_size.max(0) as VmIndex));
+            }
+            stack_size += i64::from(instruction.adjust());
+        }
+        maps
+    }
+
+    /// Returns the stack map of the safepoint at `instruction_index`
+    pub fn get(&self, instruction_index: usize) -> Option<&StackMap> {
+        self.maps
+            .binary_search_by_key(&instruction_index, |&(index, _)| index)
+            .ok()
+            .map(|i| &self.maps[i].1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &StackMap)> {
+        self.maps.iter().map(|(index, map)| (*index, map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_map_bits() {
+        let mut map = StackMap::new(70);
+        assert!((0..70).all(|slot| map.may_contain_pointer(slot)));
+        assert!(!map.may_contain_pointer(70));
+
+        map.set_scalar(1);
+        map.set_scalar(65);
+        assert!(!map.may_contain_pointer(1));
+        assert!(!map.may_contain_pointer(65));
+        assert_eq!(map.pointer_slots().count(), 68);
+    }
+
+    #[test]
+    fn lookup_stack_maps() {
+        let mut maps = StackMaps::new();
+        maps.emit(2, StackMap::new(1));
+        maps.emit(5, StackMap::new(3));
+
+        assert_eq!(maps.get(2).map(|map| map.len()), Some(1));
+        assert_eq!(maps.get(5).map(|map| map.len()), Some(3));
+        assert_eq!(maps.get(3), None);
+    }
+}
@@ -28,32 +28,39 @@ use futures::{
 use async_trait::async_trait;
 
 use crate::base::{
+    fnv::FnvMap,
     pos::Line,
     symbol::Symbol,
-    types::{self, Alias, ArcType},
+    types::{self, Alias, ArcType, Type},
 };
 
 use crate::{
     api::{Getable, Pushable, ValueRef, VmType},
+    channel::{Executing, Running},
     compiler::UpvarInfo,
-    gc::{self, CloneUnrooted, DataDef, Gc, GcPtr, GcRef, Generation, Move},
+    core_dump::CoreDump,
+    coverage::Coverage,
+    gc::{
+        self, CloneUnrooted, CollectHookFn, DataDef, Gc, GcPtr, GcRef, Generation, HeapStats, Move,
+        ValueLimits,
+    },
     interner::InternedStr,
     macros::MacroEnv,
-    source_map::LocalIter,
+    source_map::{Local, LocalIter},
     stack::{
-        ClosureState, ExternCallState, ExternState, Frame, Lock, Stack, StackFrame, StackState,
-        State,
+        ClosureState, ExternCallState, ExternState, Frame, Lock, Stack, StackFrame, StackGrowth,
+        StackState, State,
     },
     types::*,
     value::{
         BytecodeFunction, Callable, ClosureData, ClosureDataDef, ClosureInitDef, Def,
         ExternFunction, PartialApplicationDataDef, RecordDef, UninitializedRecord,
-        UninitializedVariantDef, Userdata, Value, ValueRepr,
+        UninitializedVariantDef, Userdata, Value, ValuePrinter, ValueRepr,
         ValueRepr::{Closure, Data, Float, Function, Int, PartialApplication, String},
         VariantDef,
     },
     vm::{GlobalVmState, GlobalVmStateBuilder, ThreadSlab, VmEnvInstance},
-    Error, Result, Variants,
+    Error, PanicPayload, Result, Variants,
 };
 
 pub use crate::{gc::Trace, stack::PopValue};
@@ -62,6 +69,7 @@ pub type FutureValue<F> = Either<Ready<<F as Future>::Output>, F>;
 
 pub struct Execute<T> {
     thread: Option<T>,
+    executing: Option<Executing>,
 }
 
 impl<T> Execute<T>
@@ -70,12 +78,17 @@ where
 {
     pub fn new(thread: T) -> Execute<T> {
         Execute {
+            executing: Some(Executing::new(thread.root_thread())),
             thread: Some(thread),
         }
     }
 
     pub fn root(&mut self) -> Execute<RootedThread> {
         Execute {
+            executing: self
+                .thread
+                .as_ref()
+                .map(|t| Executing::new(t.root_thread())),
             thread: self.thread.as_ref().map(|t| t.root_thread()),
         }
     }
@@ -98,6 +111,7 @@ where
             let mut context = ready!(thread.resume(cx))?;
             context.stack.pop()
         };
+        self.executing = None;
 
         let thread = self.thread.take().unwrap();
         // SAFETY `value` is produced (and owned) by `thread`
@@ -124,12 +138,7 @@ where
                     .thread
                     .as_ref()
                     .expect("cannot poll Execute future after it has succeded");
-                let mut context = thread.context();
-                let stack = StackFrame::<State>::current(&mut context.stack);
-                let new_trace = reset_stack(stack, 1)?;
-                if let Error::Panic(_, ref mut trace) = err {
-                    *trace = Some(new_trace);
-                }
+                unwind_panic(thread, 1, &mut err)?;
                 Err(err).into()
             }
         }
@@ -460,6 +469,10 @@ pub struct Thread {
     #[cfg_attr(feature = "serde_derive", serde(state))]
     rooted_values: RwLock<Vec<Value>>,
 
+    /// Values stored through `std.thread.local`, keyed by the `Local` they were stored under
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub(crate) thread_locals: RwLock<FnvMap<usize, Value>>,
+
     /// All threads which this thread have spawned in turn. Necessary as this thread needs to scan
     /// the roots of all its children as well since those may contain references to this threads
     /// garbage collected values
@@ -666,6 +679,7 @@ impl RootedThread {
             context,
             global_state: global_state.clone(),
             rooted_values: RwLock::new(Vec::new()),
+            thread_locals: Default::default(),
             child_threads: Default::default(),
             interrupt: AtomicBool::new(false),
             thread_index: usize::max_value(),
@@ -689,6 +703,10 @@ impl RootedThread {
 
         let vm = ptr.root_thread();
 
+        vm.global_env()
+            .intern_short_strings()
+            .expect("Not enough memory to intern strings");
+
         // Enter the top level scope
         {
             let mut context = vm.context.lock().unwrap();
@@ -751,6 +769,7 @@ impl Thread {
             parent: Some(unsafe { GcPtr::from_raw(self) }),
             context: Mutex::new(Context::new(self.owned_context().gc.new_child_gc())),
             rooted_values: RwLock::new(Vec::new()),
+            thread_locals: Default::default(),
             child_threads: Default::default(),
             interrupt: AtomicBool::new(false),
             thread_index: usize::max_value(),
@@ -807,6 +826,39 @@ impl Thread {
         self.global_env().spawner()
     }
 
+    /// Deep clones `value`, which must be owned by this thread, into the heap of `other`. The
+    /// returned value stays valid after this thread is dropped, so a worker thread can hand its
+    /// result to the thread that spawned it without serializing it.
+    ///
+    /// Only threads sharing the same global environment (created through `new_thread`) can
+    /// exchange values as closures refer to the compiled code of the vm that created them.
+    /// Userdata is always `Send` but is only cloned if it implements `Userdata::deep_clone`.
+    ///
+    /// # Errors
+    ///
+    /// If `value` is not owned by this thread, if `other` belongs to another vm or if `value`
+    /// contains userdata or threads which can't be cloned.
+    pub fn clone_value_to<'vm, T>(
+        &self,
+        other: &'vm Thread,
+        value: &RootedValue<T>,
+    ) -> Result<RootedValue<&'vm Thread>>
+    where
+        T: VmRootInternal,
+    {
+        if !ptr::eq(&**value.vm(), self) {
+            return Err(Error::Message(
+                "The value to clone is not owned by this thread".into(),
+            ));
+        }
+        if !Arc::ptr_eq(&self.global_state, &other.global_state) {
+            return Err(Error::Message(
+                "Values can only be cloned to threads of the same vm".into(),
+            ));
+        }
+        other.deep_clone_value(self, value.get_value())
+    }
+
     /// Retrieves the global called `name`.
     ///
     /// # Examples
@@ -859,6 +911,102 @@ impl Thread {
         }
     }
 
+    /// Replaces the function bound to the global `name` with `value` without restarting any
+    /// running code.
+    ///
+    /// Calls of the old function which are already running finish with the old code while every
+    /// call made afterwards, including calls through references to the old function which were
+    /// captured before the swap, run `value` instead. Upvariables which `value` captures under
+    /// the same name and type as the old function are replaced by the values the old function
+    /// captured, letting any state held by the old function carry over to the new version
+    /// (matching upvariables by name requires the code to be compiled with debug information).
+    ///
+    /// ```rust
+    /// # extern crate gluon;
+    /// # use gluon::{new_vm, ThreadExt};
+    /// # use gluon::vm::api::{FunctionRef, OwnedFunction};
+    /// # fn main() {
+    /// # if ::std::env::var("GLUON_PATH").is_err() {
+    /// #     ::std::env::set_var("GLUON_PATH", "..");
+    /// # }
+    /// let vm = new_vm();
+    /// vm.load_script("greet", "let greet name = \"Hello \" ++ name in { greet }")
+    ///     .unwrap_or_else(|err| panic!("{}", err));
+    /// let mut greet: FunctionRef<fn(String) -> String> = vm.get_global("greet.greet").unwrap();
+    ///
+    /// let (new_greet, _) = vm
+    ///     .run_expr::<OwnedFunction<fn(String) -> String>>(
+    ///         "new_greet",
+    ///         "\\name -> \"Goodbye \" ++ name",
+    ///     )
+    ///     .unwrap_or_else(|err| panic!("{}", err));
+    /// vm.swap_global("greet.greet", new_greet).unwrap();
+    ///
+    /// assert_eq!(greet.call("world".to_string()), Ok("Goodbye world".to_string()));
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// if the global does not exist, is not a gluon function, does not have the type of `value`
+    /// or if `value` is not a gluon function taking the same number of arguments.
+    ///
+    pub fn swap_global<'vm, T>(&'vm self, name: &str, value: T) -> Result<()>
+    where
+        T: Pushable<'vm> + VmType,
+    {
+        use crate::check::check_signature;
+
+        let expected = T::make_type(self);
+
+        let old = {
+            let env = self.get_env();
+            let (old, actual) = env.get_binding(name)?;
+            if !check_signature(&env, &expected, &actual) {
+                return Err(Error::WrongType(expected, actual));
+            }
+            old
+        };
+        let not_a_function = || {
+            Error::Message(format!(
+                "Only gluon functions can be swapped and `{}` is not one",
+                name
+            ))
+        };
+        let old = match old.get_repr() {
+            Closure(closure) => closure,
+            _ => return Err(not_a_function()),
+        };
+
+        let mut context = self.current_context();
+        value.vm_push(&mut context)?;
+        let new = {
+            let new = context.pop();
+            self.root_value::<&Thread>((*new).clone())
+        };
+        drop(context);
+
+        // Globals live in the global gc so the replacement must as well
+        let mut gc = self.global_env().gc.lock().unwrap();
+        let new = {
+            let mut cloner = crate::value::Cloner::new(self, &mut gc);
+            let new = cloner.deep_clone(&new)?;
+            match new.get_repr() {
+                // SAFETY The global gc can't collect the clone while it is locked
+                Closure(closure) => unsafe { closure.clone_unrooted() },
+                _ => return Err(not_a_function()),
+            }
+        };
+        if new.function.args != old.function.args {
+            return Err(Error::Message(format!(
+                "`{}` takes {} arguments but its replacement takes {}",
+                name, old.function.args, new.function.args
+            )));
+        }
+
+        self.global_env().swap_closure(&mut gc, old, &new)
+    }
+
     pub fn get_global_type(&self, name: &str) -> Result<ArcType> {
         let env = self.get_env();
         let (_value, actual) = env.get_binding(name)?;
@@ -948,6 +1096,35 @@ impl Thread {
         self.owned_context().gc.set_memory_limit(memory_limit)
     }
 
+    /// Returns the limits on the size of the strings, arrays and records created by this thread.
+    pub fn value_limits(&self) -> ValueLimits {
+        self.owned_context().gc.value_limits()
+    }
+
+    /// Sets the limits on the size of the strings, arrays and records created by this thread.
+    /// Threads spawned from this thread afterwards inherit the limits.
+    ///
+    /// Creating a value which exceeds a limit fails with `Error::ValueTooLarge`, which a script can
+    /// catch with `io.catch` like any other error.
+    /// The limits also apply to the values of modules loaded after they are set, some of which
+    /// contain records nested several levels deep.
+    pub fn set_value_limits(&self, value_limits: ValueLimits) {
+        self.owned_context().gc.set_value_limits(value_limits)
+    }
+
+    /// Returns statistics about the memory usage and garbage collections of this thread.
+    pub fn heap_stats(&self) -> HeapStats {
+        self.owned_context().gc.stats()
+    }
+
+    /// Sets a function which is called with the heap statistics of this thread after each of its
+    /// garbage collections. Returns the previously set function.
+    ///
+    /// The function is called while the thread is locked so it must not call back into the thread.
+    pub fn set_collect_hook(&self, hook: Option<CollectHookFn>) -> Option<CollectHookFn> {
+        self.owned_context().gc.set_collect_hook(hook)
+    }
+
     pub fn interrupt(&self) {
         self.interrupt.store(true, atomic::Ordering::Relaxed)
     }
@@ -961,6 +1138,41 @@ impl Thread {
         &self.global_state
     }
 
+    /// Calls `f`, which may run gluon code on this thread, from a Rust function which was itself
+    /// called by gluon code on this thread.
+    ///
+    /// This is the supported way for a primitive to call back into the thread it runs on, for
+    /// instance by calling a `FunctionRef` it was passed. The functions called by `f` may in turn
+    /// call primitives which call back into gluon. `f` must not be called while a borrow of the
+    /// stack, such as an `ActiveThread` from `current_context`, is alive, which is reported as an
+    /// error. Compiling and running scripts blocks on an executor and is not supported inside `f`.
+    ///
+    /// Whether `f` succeeds, returns an error or unwinds from a Rust panic, the frames and values
+    /// it leaves on the stack are removed afterwards so the calling primitive continues with the
+    /// stack it was called with. Without this a callback which fails or yields leaves its frames
+    /// behind, corrupting the execution of the calling script once the primitive returns.
+    pub fn with_reentrant_call<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&Thread) -> Result<R>,
+    {
+        let (frames, len) = match self.context.try_lock() {
+            Ok(context) => (context.stack.get_frames().len(), context.stack.len()),
+            Err(sync::TryLockError::WouldBlock) => {
+                return Err(Error::Message(
+                    "Unable to call into a thread while its stack is borrowed".into(),
+                ))
+            }
+            Err(sync::TryLockError::Poisoned(err)) => panic!("{}", err),
+        };
+
+        let _reset = ResetStack {
+            thread: self,
+            frames,
+            len,
+        };
+        f(self)
+    }
+
     pub fn current_context(&self) -> ActiveThread {
         ActiveThread {
             thread: self,
@@ -977,6 +1189,7 @@ impl Thread {
             self.global_state.trace(gc);
         }
         self.rooted_values.read().unwrap().trace(gc);
+        self.thread_locals.read().unwrap().trace(gc);
         self.child_threads.read().unwrap().trace(gc);
     }
 
@@ -1088,12 +1301,7 @@ where
         let level = self_.context().stack.get_frames().len();
 
         self.call_thunk(closure).await.or_else(move |mut err| {
-            let mut context = self_.context();
-            let stack = StackFrame::<State>::current(&mut context.stack);
-            let new_trace = reset_stack(stack, level)?;
-            if let Error::Panic(_, ref mut trace) = err {
-                *trace = Some(new_trace);
-            }
+            unwind_panic(&self_, level, &mut err)?;
             Err(err)
         })
     }
@@ -1108,12 +1316,7 @@ where
         let self_ = RootedThread::new_root(self.borrow());
         let level = self_.context().stack.get_frames().len();
         self.execute_io(value).await.or_else(move |mut err| {
-            let mut context = self_.context();
-            let stack = StackFrame::<State>::current(&mut context.stack);
-            let new_trace = reset_stack(stack, level)?;
-            if let Error::Panic(_, ref mut trace) = err {
-                *trace = Some(new_trace);
-            }
+            unwind_panic(&self_, level, &mut err)?;
             Err(err)
         })
     }
@@ -1446,6 +1649,26 @@ impl<'a> StackInfo<'a> {
             _ => &[],
         }
     }
+
+    /// Returns the value of `local`, which must be one of the locals returned by `locals`
+    pub fn local_value(&self, local: &Local) -> Option<Variants> {
+        let frame = self.frame();
+        self.info
+            .stack
+            .get_values()
+            .get(frame.offset as usize + local.index as usize)
+            .map(Variants::new)
+    }
+
+    /// Returns the value of the upvariable at `index`, matching the order of `upvars`
+    pub fn upvar_value(&self, index: usize) -> Option<Variants> {
+        match self.frame().state {
+            State::Closure(ClosureState { ref closure, .. }) => {
+                closure.upvars.get(index).map(Variants::new)
+            }
+            _ => None,
+        }
+    }
 }
 
 bitflags::bitflags! {
@@ -1462,10 +1685,18 @@ bitflags::bitflags! {
 struct Hook {
     function: Option<HookFn>,
     flags: HookFlags,
+    coverage: Option<Arc<Coverage>>,
     // The index of the last executed instruction
     previous_instruction_index: usize,
 }
 
+impl Hook {
+    /// Returns true if execution needs to be tracked line by line
+    fn tracks_lines(&self) -> bool {
+        self.flags.contains(HookFlags::LINE_FLAG) || self.coverage.is_some()
+    }
+}
+
 type PollFnInner<'a> = Box<
     dyn for<'vm> FnMut(
             &mut task::Context<'_>,
@@ -1513,6 +1744,7 @@ impl Context {
             hook: Hook {
                 function: None,
                 flags: HookFlags::empty(),
+                coverage: None,
                 previous_instruction_index: usize::max_value(),
             },
             poll_fns: Vec::new(),
@@ -1593,6 +1825,22 @@ impl Context {
         self.stack.set_max_stack_size(limit);
     }
 
+    /// Counts the lines executed by this thread in `coverage`. Passing `None` stops collecting
+    /// coverage.
+    pub fn set_coverage(&mut self, coverage: Option<Arc<Coverage>>) -> Option<Arc<Coverage>> {
+        mem::replace(&mut self.hook.coverage, coverage)
+    }
+
+    /// Sets how the stack gives back the memory it allocated while growing
+    pub fn set_stack_growth(&mut self, growth: StackGrowth) {
+        self.stack.set_growth(growth);
+    }
+
+    /// Returns the number of values the stack can hold before it needs to grow
+    pub fn stack_capacity(&self) -> VmIndex {
+        self.stack.capacity()
+    }
+
     pub fn stacktrace(&self, frame_level: usize) -> crate::stack::Stacktrace {
         self.stack.stacktrace(frame_level)
     }
@@ -1735,7 +1983,29 @@ impl<'b> OwnedContext<'b> {
         }
     }
 
+    /// Creates the panic for an extern function which returned `Status::Error`, with the value
+    /// it left on the stack as the payload
+    fn extern_panic(&mut self) -> Error {
+        let value = self.stack.pop();
+        let payload: RootedValue<RootedThread> = self.thread.root_value(Variants::new(&value));
+        let message = match value.get_repr() {
+            String(s) => s.to_string(),
+            _ => {
+                let env = self.thread.get_env();
+                let debug_level = self.thread.global_env().get_debug_level();
+                ValuePrinter::new(&env, &Type::hole(), payload.get_variant(), &debug_level)
+                    .to_string()
+            }
+        };
+        Error::Panic(
+            message,
+            Some(self.stack.stacktrace(0)),
+            Some(PanicPayload::new(payload)),
+        )
+    }
+
     fn execute(mut self, cx: &mut task::Context<'_>) -> Poll<Result<Option<OwnedContext<'b>>>> {
+        let _running = Running::new(self.thread);
         let mut context = self.borrow_mut();
         // Return when the starting frame is finished
         loop {
@@ -1863,16 +2133,7 @@ impl<'b> OwnedContext<'b> {
                 self = thread.owned_context();
 
                 if status == Status::Error {
-                    return match self.stack.pop().get_repr() {
-                        String(s) => {
-                            Err(Error::Panic(s.to_string(), Some(self.stack.stacktrace(0)))).into()
-                        }
-                        _ => Err(Error::Message(format!(
-                            "Unexpected error calling function `{}`",
-                            function.id
-                        )))
-                        .into(),
-                    };
+                    return Err(self.extern_panic()).into();
                 }
 
                 // The `poll_fn` at the top may be for a stack frame at a lower level, return to the
@@ -1954,16 +2215,7 @@ impl<'b> OwnedContext<'b> {
         match status {
             Status::Ok => Ok(self).into(),
             Status::Yield => Poll::Pending,
-            Status::Error => match self.stack.pop().get_repr() {
-                String(s) => {
-                    Err(Error::Panic(s.to_string(), Some(self.stack.stacktrace(0)))).into()
-                }
-                _ => Err(Error::Message(format!(
-                    "Unexpected error calling function `{}`",
-                    function.id
-                )))
-                .into(),
-            },
+            Status::Error => Err(self.extern_panic()).into(),
         }
     }
 
@@ -2100,7 +2352,7 @@ impl<'b, 'gc> ExecuteContext<'b, 'gc> {
 
             debug_instruction(&self.stack, instruction_index, instr);
 
-            if !self.hook.flags.is_empty() && self.hook.flags.contains(HookFlags::LINE_FLAG) {
+            if self.hook.tracks_lines() {
                 ready!(self.run_hook(&function, instruction_index))?;
             }
 
@@ -2112,6 +2364,7 @@ impl<'b, 'gc> ExecuteContext<'b, 'gc> {
                             return Err(Error::Panic(
                                 format!("ICE: Stack push out of bounds in {}", function.name),
                                 Some(self.stack.stack().stacktrace(0)),
+                                None,
                             ))
                             .into();
                         }
@@ -2372,6 +2625,24 @@ impl<'b, 'gc> ExecuteContext<'b, 'gc> {
                         }
                     }
                 }
+                Switch(table_index) => {
+                    let data_tag = match self.stack.top().get_repr() {
+                        Data(data) => data.tag(),
+                        ValueRepr::Tag(tag) => *tag,
+                        data => {
+                            return Err(Error::Message(format!(
+                                "Op Switch called on non data type: {:?}",
+                                data
+                            )))
+                            .into();
+                        }
+                    };
+                    let table = &function.jump_tables[table_index as usize];
+                    if let Some(&i) = table.get(data_tag as usize) {
+                        program_counter.jump(i as usize);
+                        continue;
+                    }
+                }
                 Jump(i) => {
                     program_counter.jump(i as usize);
                     continue;
@@ -2516,21 +2787,28 @@ impl<'b, 'gc> ExecuteContext<'b, 'gc> {
     }
 
     fn run_hook(&mut self, function: &BytecodeFunction, index: usize) -> Poll<Result<()>> {
+        let current_line = function.debug_info.source_map.line(index);
+        let previous_line = function
+            .debug_info
+            .source_map
+            .line(self.hook.previous_instruction_index);
+        self.hook.previous_instruction_index = index;
+        if current_line == previous_line {
+            return Ok(()).into();
+        }
+        if let (Some(coverage), Some(line)) = (&self.hook.coverage, current_line) {
+            coverage.hit(function, line);
+        }
+        if !self.hook.flags.contains(HookFlags::LINE_FLAG) {
+            return Ok(()).into();
+        }
         if let Some(ref mut hook) = self.hook.function {
-            let current_line = function.debug_info.source_map.line(index);
-            let previous_line = function
-                .debug_info
-                .source_map
-                .line(self.hook.previous_instruction_index);
-            self.hook.previous_instruction_index = index;
-            if current_line != previous_line {
-                self.stack.frame_mut().state.instruction_index = index;
-                let info = DebugInfo {
-                    stack: &self.stack.stack(),
-                    state: HookFlags::LINE_FLAG,
-                };
-                ready!(hook(self.thread, info))?
-            }
+            self.stack.frame_mut().state.instruction_index = index;
+            let info = DebugInfo {
+                stack: &self.stack.stack(),
+                state: HookFlags::LINE_FLAG,
+            };
+            ready!(hook(self.thread, info))?
         }
         Ok(()).into()
     }
@@ -2590,7 +2868,7 @@ where
     ) -> StdResult<ExecuteContext<'b, 'gc, State>, ExecuteContext<'b, 'gc, State>> {
         match self.stack.exit_scope() {
             Ok(stack) => {
-                if self.hook.flags.bits() != 0 {
+                if self.hook.flags.bits() != 0 || self.hook.coverage.is_some() {
                     // Subtract 1 to compensate for the `Call` instruction adding one earlier
                     // ensuring that the line hook runs after function calls
                     if let State::Closure(ref state) = stack.frame().state {
@@ -2621,6 +2899,9 @@ where
         closure: &GcPtr<ClosureData>,
         excess: bool,
     ) -> Result<ExecuteContext<'b, 'gc, State>> {
+        // Calls of closures replaced by `Thread::swap_global` enter the replacement instead
+        let swapped = self.thread.global_env().swapped_closure(closure);
+        let closure = swapped.as_ref().unwrap_or(closure);
         info!("Call {} {:?}", closure.function.name, &self.stack[..]);
         Ok(self
             .enter_scope(
@@ -2916,7 +3197,57 @@ impl<'vm> ActiveThread<'vm> {
             .return_future(future, lock, frame_index)
     }
 }
+/// Unwinds the stack of `thread` down to `level` after `err` was raised, attaching the stacktrace
+/// to panics and writing a `CoreDump` of them if one was requested
+fn unwind_panic(thread: &Thread, level: usize, err: &mut Error) -> Result<()> {
+    let dump = {
+        let mut context = thread.context();
+        let stack = StackFrame::<State>::current(&mut context.stack);
+        let dump = match err {
+            Error::Panic(message, ..) if thread.global_env().wants_core_dump() => {
+                Some(CoreDump::capture(thread, stack.stack(), level, message))
+            }
+            _ => None,
+        };
+        let new_trace = reset_stack(stack, level)?;
+        if let Error::Panic(_, ref mut trace, _) = err {
+            *trace = Some(new_trace);
+        }
+        dump
+    };
+    // The environment may need to lock the thread so the source is looked up after unwinding
+    if let Some(mut dump) = dump {
+        dump.fill_from_env(&thread.get_env());
+        thread.global_env().write_core_dump(&dump);
+    }
+    Ok(())
+}
+
 #[doc(hidden)]
+/// Restores the stack of `thread` to `frames` frames and `len` values when dropped
+struct ResetStack<'t> {
+    thread: &'t Thread,
+    frames: usize,
+    len: VmIndex,
+}
+
+impl Drop for ResetStack<'_> {
+    fn drop(&mut self) {
+        // The lock is poisoned if the callback panicked while it held the stack, the stack is
+        // still reset since the panic may be caught by the calling primitive
+        let mut context = self
+            .thread
+            .context
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let stack = StackFrame::<State>::current(&mut context.stack);
+        // Only fails if the frames of the caller were already exited, leaving nothing to remove
+        let _ = reset_stack(stack, self.frames);
+        let excess = context.stack.len().saturating_sub(self.len);
+        context.stack.pop_many(excess);
+    }
+}
+
 pub fn reset_stack(mut stack: StackFrame<State>, level: usize) -> Result<crate::stack::Stacktrace> {
     let trace = stack.stack().stacktrace(level);
     while stack.stack().get_frames().len() > level {
@@ -0,0 +1,117 @@
+//! Per thread storage for gluon threads
+use crate::real_std::{
+    any::Any,
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    api::{generic::A, Generic, Unrooted, Userdata, WithVM, IO},
+    gc::{CloneUnrooted, GcRef, Move, Trace},
+    value::Cloner,
+    vm::Thread,
+    ExternModule, Result,
+};
+
+static NEXT_KEY: AtomicUsize = AtomicUsize::new(0);
+
+/// A key which each thread can store a separate value of type `T` under
+#[derive(VmType)]
+#[gluon(gluon_vm)]
+#[gluon(vm_type = "std.thread.local.Local")]
+pub struct Local<T> {
+    key: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Userdata for Local<T>
+where
+    T: Any + Send + Sync,
+{
+    fn deep_clone<'gc>(
+        &self,
+        deep_cloner: &'gc mut Cloner,
+    ) -> Result<GcRef<'gc, Box<dyn Userdata>>> {
+        // The values are stored in the threads themselves so a copy refers to the same storage
+        let data: Box<dyn Userdata> = Box::new(Local {
+            key: self.key,
+            _marker: PhantomData::<A>,
+        });
+        deep_cloner.gc().alloc(Move(data))
+    }
+}
+
+impl<T> fmt::Debug for Local<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Local({})", self.key)
+    }
+}
+
+unsafe impl<T> Trace for Local<T> {
+    impl_trace! { self, _gc, {} }
+}
+
+fn new(_: ()) -> IO<Local<A>> {
+    IO::Value(Local {
+        key: NEXT_KEY.fetch_add(1, Ordering::Relaxed),
+        _marker: PhantomData,
+    })
+}
+
+fn get(WithVM { vm, value: local }: WithVM<&Local<A>>) -> IO<Option<Unrooted<A>>> {
+    let thread_locals = vm.thread_locals.read().unwrap();
+    // SAFETY The returned, unrooted value gets pushed immediately to the stack
+    IO::Value(unsafe {
+        thread_locals
+            .get(&local.key)
+            .map(|value| Unrooted::from(value.clone_unrooted()))
+    })
+}
+
+fn set(local: &Local<A>, value: WithVM<Generic<A>>) -> IO<()> {
+    // The value comes from the stack of the current thread so it is already owned by the heap of
+    // this thread (or one of its parents) and does not need to be cloned
+    // SAFETY Rooted when stored in the thread, which traces its locals
+    unsafe {
+        value
+            .vm
+            .thread_locals
+            .write()
+            .unwrap()
+            .insert(local.key, value.value.get_value().clone_unrooted());
+    }
+    IO::Value(())
+}
+
+fn remove(WithVM { vm, value: local }: WithVM<&Local<A>>) -> IO<Option<Unrooted<A>>> {
+    IO::Value(
+        vm.thread_locals
+            .write()
+            .unwrap()
+            .remove(&local.key)
+            .map(Unrooted::from),
+    )
+}
+
+mod std {
+    pub mod thread {
+        pub mod local {
+            pub use crate::thread_local as prim;
+        }
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    let _ = vm.register_type::<Local<A>>("std.thread.local.Local", &["a"]);
+    ExternModule::new(
+        vm,
+        record! {
+            type Local a => Local<A>,
+            new => primitive!(1, std::thread::local::prim::new),
+            get => primitive!(1, std::thread::local::prim::get),
+            set => primitive!(2, std::thread::local::prim::set),
+            remove => primitive!(1, std::thread::local::prim::remove),
+        },
+    )
+}
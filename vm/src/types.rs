@@ -1,5 +1,6 @@
+use rpds::HashTrieMapSync;
+
 use crate::base::{
-    fnv::FnvMap,
     kind::{ArcKind, Kind, KindEnv},
     symbol::{Symbol, SymbolRef},
     types::{Alias, ArcType, Type, TypeEnv, TypeExt},
@@ -128,6 +129,10 @@ pub enum Instruction {
     /// matches, otherwise `False`
     TestTag(VmTag),
     TestPolyTag(VmIndex),
+    /// Looks up the tag of the value at the top of the stack in the jump table at `index` of the
+    /// currently executing function and jumps to the instruction it maps to. Continues with the
+    /// next instruction if the tag is not in the table.
+    Switch(VmIndex),
     /// Jumps to the instruction at `index` in the currently executing function.
     Jump(VmIndex),
     /// Jumps to the instruction at `index` in the currently executing function if `True` is at the
@@ -199,7 +204,7 @@ impl Instruction {
             // calculate the number of slots needed
             Split => -1,
             TestTag(_) | TestPolyTag(_) => 1,
-            Jump(_) => 0,
+            Switch(_) | Jump(_) => 0,
             CJump(_) => -1,
             Pop(n) => -(n as i32),
             Slide(n) => -(n as i32),
@@ -216,9 +221,29 @@ impl Instruction {
             Return => 0,
         }
     }
+
+    /// Returns true if executing `self` may allocate or call another function and therefore run
+    /// a garbage collection.
+    pub fn is_safepoint(&self) -> bool {
+        match *self {
+            Call(_)
+            | TailCall(_)
+            | ConstructVariant { .. }
+            | ConstructPolyVariant { .. }
+            | NewVariant { .. }
+            | NewRecord { .. }
+            | ConstructRecord { .. }
+            | ConstructArray(_)
+            | MakeClosure { .. }
+            | NewClosure { .. } => true,
+            _ => false,
+        }
+    }
 }
 
-#[derive(Default, Debug)]
+/// The types known to the virtual machine. The map is persistent so that copying it, as every
+/// update of the global environment does, only copies the path to the modified entry.
+#[derive(Clone, Default, Debug)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(
     feature = "serde_derive",
@@ -234,9 +259,9 @@ impl Instruction {
 pub struct TypeInfos {
     #[cfg_attr(
         feature = "serde_derive",
-        serde(state_with = "crate::serialization::borrow")
+        serde(state_with = "crate::serialization::persistent_map")
     )]
-    pub id_to_type: FnvMap<String, Alias<Symbol, ArcType>>,
+    pub id_to_type: HashTrieMapSync<String, Alias<Symbol, ArcType>>,
 }
 
 impl KindEnv for TypeInfos {
@@ -272,12 +297,14 @@ impl TypeEnv for TypeInfos {
 
 impl TypeInfos {
     pub fn new() -> TypeInfos {
-        let id_to_type = FnvMap::default();
+        let id_to_type = HashTrieMapSync::new_sync();
         TypeInfos { id_to_type }
     }
 
     pub fn extend(&mut self, other: TypeInfos) {
         let TypeInfos { id_to_type } = other;
-        self.id_to_type.extend(id_to_type);
+        for (id, alias) in &id_to_type {
+            self.id_to_type.insert_mut(id.clone(), alias.clone());
+        }
     }
 }
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     collections::hash_map::Entry,
     fmt, iter,
     marker::PhantomData,
@@ -24,9 +25,10 @@ use crate::{
     compiler::DebugInfo,
     gc::{
         self, CloneUnrooted, CopyUnrooted, DataDef, Gc, GcPtr, GcRef, Generation, Move, Trace,
-        WriteOnly,
+        ValueSize, WriteOnly,
     },
     interner::InternedStr,
+    stack_map::StackMaps,
     thread::{Status, Thread},
     types::*,
     Error, Result, Variants,
@@ -152,8 +154,10 @@ pub struct BytecodeFunction {
     pub strings: Vec<InternedStr>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub records: Vec<Vec<InternedStr>>,
+    pub jump_tables: Vec<Vec<VmIndex>>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub debug_info: DebugInfo,
+    pub stack_maps: StackMaps,
 }
 
 unsafe impl Trace for BytecodeFunction {
@@ -165,6 +169,8 @@ unsafe impl Trace for BytecodeFunction {
 #[repr(C)]
 pub struct DataStruct {
     tag: VmTag,
+    /// How many records are nested inside this value, including the value itself
+    record_depth: u32,
     pub(crate) fields: Array<Value>,
 }
 
@@ -188,6 +194,23 @@ impl DataStruct {
     }
 }
 
+/// Returns the `record_depth` of a value with the tag `tag` and the fields `elems`
+fn record_depth(tag: VmTag, elems: &[Value]) -> u32 {
+    let depth = elems
+        .iter()
+        .filter_map(|elem| match elem.get_repr() {
+            ValueRepr::Data(data) => Some(data.record_depth),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    if (tag & DataStruct::record_bit()) != 0 {
+        depth.saturating_add(1)
+    } else {
+        depth
+    }
+}
+
 impl<'gc> GcRef<'gc, DataStruct> {
     pub(crate) fn get(&self, vm: &Thread, field: &str) -> Result<Option<Variants<'gc>>> {
         let field = vm.global_env().intern(field)?;
@@ -217,6 +240,7 @@ unsafe impl<'b> DataDef for Def<'b> {
         unsafe {
             let result = &mut *result.as_mut_ptr();
             result.tag = self.tag;
+            result.record_depth = record_depth(self.tag, self.elems);
             result
                 .fields
                 .initialize(self.elems.iter().map(|v| v.clone_unrooted()));
@@ -269,6 +293,7 @@ unsafe impl DataDef for UninitializedVariantDef {
         unsafe {
             let result = &mut *result.as_mut_ptr();
             result.tag = self.tag;
+            result.record_depth = record_depth(self.tag, &[]);
             result
                 .fields
                 .initialize((0..self.elems).map(|_| Value::int(0)));
@@ -293,6 +318,7 @@ unsafe impl<'b> DataDef for RecordDef<'b> {
         unsafe {
             let result = &mut *result.as_mut_ptr();
             result.tag = 1 << ((size_of::<VmTag>() * 8) - 1);
+            result.record_depth = record_depth(result.tag, self.elems);
             result
                 .fields
                 .initialize(self.elems.iter().map(|v| v.clone_unrooted()));
@@ -302,6 +328,10 @@ unsafe impl<'b> DataDef for RecordDef<'b> {
     fn fields(&self) -> Option<&[InternedStr]> {
         Some(self.fields)
     }
+    fn value_size(&self) -> Option<ValueSize> {
+        let depth = record_depth(DataStruct::record_bit(), self.elems);
+        Some(ValueSize::RecordDepth(depth as usize))
+    }
 }
 
 pub(crate) struct UninitializedRecord<'b> {
@@ -318,6 +348,7 @@ unsafe impl<'b> DataDef for UninitializedRecord<'b> {
         unsafe {
             let result = &mut *result.as_mut_ptr();
             result.tag = 1 << ((size_of::<VmTag>() * 8) - 1);
+            result.record_depth = record_depth(result.tag, &[]);
             result
                 .fields
                 .initialize((0..self.elems).map(|_| Value::int(0)));
@@ -632,6 +663,17 @@ pub struct ValuePrinter<'a> {
     pub max_level: i32,
     pub width: usize,
     pub debug_level: &'a DebugLevel,
+    /// The number of decimals that floats are printed with, `None` prints the shortest
+    /// representation which round trips
+    pub float_precision: Option<usize>,
+    /// The radix that integers are printed in (2, 8, 10 or 16)
+    pub int_radix: u32,
+    /// The number of characters after which strings are cut off
+    pub max_string_length: Option<usize>,
+    /// The number of elements after which arrays are cut off
+    pub max_array_length: Option<usize>,
+    /// Prefixes records whose type is a named type with the name of that type
+    pub record_type_names: bool,
 }
 
 impl<'t> ValuePrinter<'t> {
@@ -648,6 +690,11 @@ impl<'t> ValuePrinter<'t> {
             max_level: 50,
             width: 80,
             debug_level,
+            float_precision: None,
+            int_radix: 10,
+            max_string_length: None,
+            max_array_length: None,
+            record_type_names: false,
         }
     }
 
@@ -660,10 +707,71 @@ impl<'t> ValuePrinter<'t> {
         self.width = width;
         self
     }
+
+    pub fn float_precision(&mut self, float_precision: Option<usize>) -> &mut ValuePrinter<'t> {
+        self.float_precision = float_precision;
+        self
+    }
+
+    /// Panics if `int_radix` is not one of 2, 8, 10 or 16
+    pub fn int_radix(&mut self, int_radix: u32) -> &mut ValuePrinter<'t> {
+        assert!(
+            [2, 8, 10, 16].contains(&int_radix),
+            "Integers can only be printed in radix 2, 8, 10 or 16 (got {})",
+            int_radix
+        );
+        self.int_radix = int_radix;
+        self
+    }
+
+    pub fn max_string_length(&mut self, max_string_length: Option<usize>) -> &mut ValuePrinter<'t> {
+        self.max_string_length = max_string_length;
+        self
+    }
+
+    pub fn max_array_length(&mut self, max_array_length: Option<usize>) -> &mut ValuePrinter<'t> {
+        self.max_array_length = max_array_length;
+        self
+    }
+
+    pub fn record_type_names(&mut self, record_type_names: bool) -> &mut ValuePrinter<'t> {
+        self.record_type_names = record_type_names;
+        self
+    }
 }
 
 const INDENT: isize = 4;
 
+fn function_name(name: &Symbol) -> std::string::String {
+    match name.declared_name() {
+        "" => "function".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Returns true if `row` has the fields `_0`, `_1`, ... of a tuple (and at least two of them)
+fn is_tuple(row: &ArcType) -> bool {
+    let mut len = 0;
+    row.type_field_iter().next().is_none()
+        && row.row_iter().enumerate().all(|(i, field)| {
+            len += 1;
+            let name = field.name.declared_name();
+            name.starts_with('_') && name[1..].parse() == Ok(i)
+        })
+        && len >= 2
+}
+
+fn format_int(i: VmInt, radix: u32) -> std::string::String {
+    let sign = if i < 0 { "-" } else { "" };
+    let abs = i.unsigned_abs();
+    match radix {
+        2 => format!("{}0b{:b}", sign, abs),
+        8 => format!("{}0o{:o}", sign, abs),
+        16 => format!("{}0x{:x}", sign, abs),
+        _ => i.to_string(),
+    }
+}
+
 struct InternalPrinter<'a, 't> {
     typ: &'t ArcType,
     env: &'t dyn TypeEnv<Type = ArcType>,
@@ -671,6 +779,7 @@ struct InternalPrinter<'a, 't> {
     prec: Prec,
     level: i32,
     debug_level: &'t DebugLevel,
+    options: &'t ValuePrinter<'t>,
 }
 
 impl<'a> fmt::Display for ValuePrinter<'a> {
@@ -684,6 +793,7 @@ impl<'a> fmt::Display for ValuePrinter<'a> {
             prec: Top,
             level: self.max_level,
             debug_level: self.debug_level,
+            options: self,
         }
         .pretty(self.value.clone())
         .group()
@@ -699,7 +809,13 @@ impl<'a, 't> InternalPrinter<'a, 't> {
         let arena = self.arena;
         match value.0 {
             _ if self.level == 0 => arena.text(".."),
-            ValueRepr::String(s) => arena.text(format!("{:?}", &s[..])),
+            ValueRepr::String(s) => match self.options.max_string_length {
+                Some(max) if s.chars().nth(max).is_some() => {
+                    let end = s.char_indices().nth(max).map_or(s.len(), |(i, _)| i);
+                    arena.text(format!("{:?}..", &s[..end]))
+                }
+                _ => arena.text(format!("{:?}", &s[..])),
+            },
             ValueRepr::Data(ref data) => self.pretty_data(data.tag(), variant_iter(&data.fields)),
             ValueRepr::Tag(tag) => self.pretty_data(tag, iter::empty()),
             ValueRepr::Function(ref function) => chain![
@@ -709,16 +825,11 @@ impl<'a, 't> InternalPrinter<'a, 't> {
                 ">"
             ],
             ValueRepr::Closure(ref closure) => match self.debug_level {
-                &DebugLevel::None => chain![
-                    arena,
-                    "<",
-                    arena.text(closure.function.name.declared_name().to_string()),
-                    ">"
-                ],
+                &DebugLevel::None => chain![arena, "<", function_name(&closure.function.name), ">"],
                 &DebugLevel::Low | &DebugLevel::High => chain![
                     arena,
                     "<",
-                    arena.text(closure.function.name.declared_name().to_string()),
+                    function_name(&closure.function.name),
                     arena
                         .concat(Itertools::intersperse(
                             variant_iter(&closure.upvars)
@@ -739,30 +850,58 @@ impl<'a, 't> InternalPrinter<'a, 't> {
                     ">"
                 ],
             },
-            ValueRepr::Array(ref array) => chain![
-                arena,
-                "[",
-                arena
-                    .concat(Itertools::intersperse(
-                        array.iter().map(|field| {
-                            match **self.typ {
-                                Type::App(_, ref args) => self.p(&args[0], Top).pretty(field),
-                                _ => arena.text(format!("{:?}", field)),
-                            }
-                        }),
-                        arena.text(",").append(arena.space())
-                    ))
-                    .nest(INDENT),
-                "]"
-            ],
-            ValueRepr::PartialApplication(p) => arena.text(format!("{:?}", p)),
+            ValueRepr::Array(ref array) => {
+                let typ = self.resolved_type();
+                let element_type = match **typ {
+                    Type::App(_, ref args) => args.last(),
+                    _ => None,
+                };
+                let len = array.len();
+                let shown = self
+                    .options
+                    .max_array_length
+                    .map_or(len, |max| max.min(len));
+                chain![
+                    arena,
+                    "[",
+                    arena
+                        .concat(Itertools::intersperse(
+                            array
+                                .iter()
+                                .take(shown)
+                                .map(|field| match element_type {
+                                    Some(element_type) => self.p(element_type, Top).pretty(field),
+                                    None => self.p(&Type::hole(), Top).pretty(field),
+                                })
+                                .chain(if shown < len {
+                                    Some(arena.text(".."))
+                                } else {
+                                    None
+                                }),
+                            arena.text(",").append(arena.space())
+                        ))
+                        .nest(INDENT),
+                    "]"
+                ]
+            }
+            ValueRepr::PartialApplication(p) => {
+                let name = match p.function {
+                    Callable::Closure(ref closure) => function_name(&closure.function.name),
+                    Callable::Extern(ref function) => {
+                        format!("extern {}", function.id.declared_name())
+                    }
+                };
+                chain![arena, "<", name, ">"]
+            }
             ValueRepr::Userdata(ref data) => arena.text(format!("{:?}", data)),
             ValueRepr::Thread(thread) => arena.text(format!("{:?}", thread)),
             ValueRepr::Byte(b) => arena.text(format!("{}", b)),
             ValueRepr::Int(i) => {
                 use crate::base::types::BuiltinType;
-                match **self.typ {
-                    Type::Builtin(BuiltinType::Int) => arena.text(format!("{}", i)),
+                match **self.resolved_type() {
+                    Type::Builtin(BuiltinType::Int) => {
+                        arena.text(format_int(i, self.options.int_radix))
+                    }
                     Type::Builtin(BuiltinType::Char) => match ::std::char::from_u32(i as u32) {
                         Some('"') => arena.text(format!("'{}'", '"')),
                         Some(c) => arena.text(format!("'{}'", c.escape_default())),
@@ -774,10 +913,18 @@ impl<'a, 't> InternalPrinter<'a, 't> {
                     _ => arena.text(format!("{}", i)),
                 }
             }
-            ValueRepr::Float(f) => arena.text(format!("{}", f)),
+            ValueRepr::Float(f) => match self.options.float_precision {
+                Some(precision) => arena.text(format!("{:.*}", precision, f)),
+                None => arena.text(format!("{}", f)),
+            },
         }
     }
 
+    fn resolved_type(&self) -> Cow<'t, ArcType> {
+        use crate::base::{resolve::remove_aliases_cow, types::NullInterner};
+        remove_aliases_cow(self.env, &mut NullInterner, self.typ)
+    }
+
     fn pretty_data<'b, I>(&self, tag: VmTag, fields: I) -> DocBuilder<'a, Arena<'a>>
     where
         I: IntoIterator<Item = Variants<'b>>,
@@ -803,6 +950,20 @@ impl<'a, 't> InternalPrinter<'a, 't> {
         let typ = remove_aliases_cow(self.env, &mut NullInterner, self.typ);
         let arena = self.arena;
         match **typ {
+            Type::Record(ref row) if is_tuple(row) => chain![
+                arena,
+                "(",
+                arena
+                    .concat(Itertools::intersperse(
+                        fields
+                            .into_iter()
+                            .zip(row.row_iter())
+                            .map(|(field, type_field)| self.p(&type_field.typ, Top).pretty(field)),
+                        arena.text(",").append(arena.space())
+                    ))
+                    .nest(INDENT),
+                ")"
+            ],
             Type::Record(ref row) => {
                 let mut is_empty = true;
                 let fields_doc = arena.concat(Itertools::intersperse(
@@ -827,8 +988,15 @@ impl<'a, 't> InternalPrinter<'a, 't> {
                         }),
                     arena.space(),
                 ));
+                let type_name = match self.typ.alias_ident() {
+                    Some(name) if self.options.record_type_names => {
+                        chain![arena, name.declared_name().to_string(), arena.space()]
+                    }
+                    _ => arena.nil(),
+                };
                 chain![
                     arena,
+                    type_name,
                     "{",
                     chain![arena, arena.space(), fields_doc].nest(INDENT),
                     if is_empty { arena.nil() } else { arena.space() },
@@ -886,6 +1054,7 @@ impl<'a, 't> InternalPrinter<'a, 't> {
             prec: prec,
             level: self.level - 1,
             debug_level: self.debug_level,
+            options: self.options,
         }
     }
 }
@@ -1172,6 +1341,9 @@ macro_rules! impl_repr {
                     result
                 }
             }
+            fn value_size(&self) -> Option<ValueSize> {
+                Some(ValueSize::Array(self.len()))
+            }
         }
 
         unsafe impl DataDef for Vec<$id> {
@@ -1182,6 +1354,9 @@ macro_rules! impl_repr {
             fn initialize<'w>(self, result: WriteOnly<'w, ValueArray>) -> &'w mut ValueArray {
                 DataDef::initialize(&self[..], result)
             }
+            fn value_size(&self) -> Option<ValueSize> {
+                DataDef::value_size(&&self[..])
+            }
         }
         )*
         impl Repr {
@@ -1220,6 +1395,9 @@ unsafe impl<'a> DataDef for &'a str {
             &mut *(ptr as *mut ValueArray as *mut ValueStr)
         }
     }
+    fn value_size(&self) -> Option<ValueSize> {
+        Some(ValueSize::String(self.len()))
+    }
 }
 
 impl Repr {
@@ -1498,6 +1676,9 @@ unsafe impl<'b> DataDef for ArrayDef<'b> {
             result
         }
     }
+    fn value_size(&self) -> Option<ValueSize> {
+        Some(ValueSize::Array(self.0.len()))
+    }
 }
 
 #[derive(PartialEq, Trace)]
@@ -1861,6 +2042,90 @@ mod tests {
         unsafe { gc.clear() }
     }
 
+    #[test]
+    fn pretty_options() {
+        let mut gc = Gc::new(Generation::default(), usize::max_value());
+
+        let env = MockEnv(None);
+        let print = |typ: &ArcType, value: Variants, f: &dyn Fn(&mut ValuePrinter)| {
+            let mut printer = ValuePrinter::new(&env, typ, value, &DebugLevel::None);
+            f(&mut printer);
+            printer.to_string()
+        };
+
+        let int = Value::from(ValueRepr::Int(-255));
+        assert_eq!(
+            print(&Type::int(), Variants::new(&int), &|p| {
+                p.int_radix(16);
+            }),
+            "-0xff"
+        );
+        assert_eq!(
+            print(&Type::int(), Variants::new(&int), &|p| {
+                p.int_radix(2);
+            }),
+            "-0b11111111"
+        );
+
+        let float = Value::from(ValueRepr::Float(1.0 / 3.0));
+        assert_eq!(
+            print(&Type::float(), Variants::new(&float), &|p| {
+                p.float_precision(Some(2));
+            }),
+            "0.33"
+        );
+
+        let string = Variants::from(gc.alloc("hello world").unwrap());
+        assert_eq!(
+            print(&Type::string(), string.clone(), &|p| {
+                p.max_string_length(Some(5));
+            }),
+            r#""hello".."#
+        );
+        assert_eq!(
+            print(&Type::string(), string, &|p| {
+                p.max_string_length(Some(11));
+            }),
+            r#""hello world""#
+        );
+
+        let array = Variants::from(gc.alloc(&[1 as VmInt, 2, 3][..]).unwrap());
+        assert_eq!(
+            print(&Type::array(Type::int()), array, &|p| {
+                p.max_array_length(Some(2));
+            }),
+            "[1, 2, ..]"
+        );
+
+        let point: ArcType = Type::alias(
+            Symbol::from("Point"),
+            Vec::new(),
+            Type::record(
+                Vec::new(),
+                vec![
+                    Field::new(Symbol::from("x"), Type::int()),
+                    Field::new(Symbol::from("y"), Type::int()),
+                ],
+            ),
+        );
+        let record = Variants::from(
+            gc.alloc(Def {
+                tag: 0,
+                elems: &[ValueRepr::Int(1).into(), ValueRepr::Int(2).into()],
+            })
+            .unwrap(),
+        );
+        assert_eq!(print(&point, record.clone(), &|_| ()), "{ x: 1, y: 2, }");
+        assert_eq!(
+            print(&point, record, &|p| {
+                p.record_type_names(true);
+            }),
+            "Point { x: 1, y: 2, }"
+        );
+
+        unsafe { gc.clear() }
+    }
+
     #[test]
     fn closure_data_upvars_location() {
         use std::ptr;
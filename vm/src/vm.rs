@@ -1,19 +1,26 @@
 use std::{
     any::{Any, TypeId},
+    io::{self, Write},
     result::Result as StdResult,
     string::String as StdString,
-    sync::{atomic::AtomicUsize, Arc, Mutex, RwLock},
+    sync::{
+        atomic::{self, AtomicBool, AtomicUsize},
+        Arc, Mutex, OnceLock, RwLock,
+    },
     usize,
 };
 
 #[cfg(feature = "serde_derive_state")]
 use serde::de::DeserializeState;
 
+use rpds::HashTrieMapSync;
+
 use crate::base::{
     ast,
     fnv::FnvMap,
     kind::{ArcKind, Kind, KindEnv},
     metadata::{Metadata, MetadataEnv},
+    pos::Line,
     symbol::{Name, Symbol, SymbolRef},
     types::{
         Alias, AliasData, AppVec, ArcType, Generic, NullInterner, PrimitiveEnv, Type, TypeCache,
@@ -24,12 +31,15 @@ use crate::base::{
 
 use crate::{
     api::{OpaqueValue, ValueRef, IO},
+    channel::DeadlockDetector,
     compiler::{CompiledFunction, CompiledModule, CompilerEnv, Variable},
     core::{interpreter, optimize::OptimizeEnv, CoreExpr},
-    gc::{Gc, GcPtr, GcRef, Generation, Move, Trace},
-    interner::{InternedStr, Interner},
+    core_dump::{CoreDump, CoreDumpHandler},
+    gc::{CloneUnrooted, Gc, GcPtr, GcRef, Generation, Move, Trace},
+    interner::{InternedStr, Interner, SHORT_STRING_LEN},
     lazy::Lazy,
     macros::MacroEnv,
+    snapshot::Snapshot,
     thread::ThreadInternal,
     types::*,
     value::{BytecodeFunction, ClosureData, ClosureDataDef},
@@ -41,6 +51,9 @@ pub use crate::{
     value::Userdata,
 };
 
+/// Receives the text which gluon programs print to stdout
+pub type OutputHandler = Box<dyn Fn(&str) + Send + Sync>;
+
 pub(crate) type ThreadSlab = slab::Slab<GcPtr<Thread>>;
 
 unsafe impl Trace for ThreadSlab {
@@ -97,7 +110,9 @@ fn new_bytecode_function<'gc>(
         inner_functions,
         strings,
         records,
+        jump_tables,
         debug_info,
+        stack_maps,
         ..
     } = f;
 
@@ -124,7 +139,9 @@ fn new_bytecode_function<'gc>(
         inner_functions: fs?,
         strings,
         records: records?,
+        jump_tables,
         debug_info,
+        stack_maps,
     }))
 }
 
@@ -202,21 +219,26 @@ where
 pub struct GlobalVmState {
     #[cfg_attr(
         feature = "serde_derive",
-        serde(state_with = "crate::serialization::rw_lock")
+        serde(state_with = "crate::serialization::snapshot")
     )]
-    env: parking_lot::RwLock<Globals>,
+    env: Snapshot<Globals>,
     #[cfg_attr(
         feature = "serde_derive",
-        serde(state_with = "crate::serialization::borrow")
+        serde(state_with = "crate::serialization::persistent_map_snapshot")
     )]
-    generics: RwLock<FnvMap<StdString, ArcType>>,
+    generics: Snapshot<HashTrieMapSync<StdString, ArcType>>,
 
     #[cfg_attr(feature = "serde_derive", serde(skip))]
-    typeids: RwLock<FnvMap<TypeId, ArcType>>,
+    typeids: Snapshot<HashTrieMapSync<TypeId, ArcType>>,
 
     #[cfg_attr(feature = "serde_derive", serde(state))]
     interner: RwLock<Interner>,
 
+    /// The empty string followed by every single character ASCII string, interned once when the
+    /// root thread is created so they can be read without locking
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    short_strings: OnceLock<Vec<InternedStr>>,
+
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     macros: MacroEnv,
 
@@ -245,6 +267,24 @@ pub struct GlobalVmState {
 
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     spawner: Option<Box<dyn futures::task::Spawn + Send + Sync>>,
+
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub(crate) deadlock_detector: DeadlockDetector,
+
+    /// Closures which have been replaced by `Thread::swap_global`, keyed by the address of the
+    /// replaced closure. Every entry refers directly to the latest replacement.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    swapped_closures: RwLock<FnvMap<usize, (GcPtr<ClosureData>, GcPtr<ClosureData>)>>,
+
+    /// Set when the first closure is swapped so that calls can skip the lookup until then
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    has_swapped_closures: AtomicBool,
+
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    core_dump_handler: RwLock<Option<CoreDumpHandler>>,
+
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    stdout_handler: RwLock<Option<OutputHandler>>,
 }
 
 unsafe impl Trace for GlobalVmState {
@@ -254,6 +294,7 @@ unsafe impl Trace for GlobalVmState {
         // Also need to check the interned string table
         self.interner.get_mut().unwrap().root();
         self.generation_0_threads.get_mut().unwrap().root();
+        self.swapped_closures.get_mut().unwrap().root();
     }
     unsafe fn unroot(&mut self) {
         self.macros.unroot();
@@ -261,6 +302,7 @@ unsafe impl Trace for GlobalVmState {
         // Also need to check the interned string table
         self.interner.get_mut().unwrap().unroot();
         self.generation_0_threads.get_mut().unwrap().unroot();
+        self.swapped_closures.get_mut().unwrap().unroot();
     }
 
     fn trace(&self, gc: &mut Gc) {
@@ -269,10 +311,11 @@ unsafe impl Trace for GlobalVmState {
         // Also need to check the interned string table
         self.interner.read().unwrap().trace(gc);
         self.generation_0_threads.read().unwrap().trace(gc);
+        self.swapped_closures.read().unwrap().trace(gc);
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(
     feature = "serde_derive",
@@ -294,12 +337,22 @@ pub trait VmEnv:
     OptimizeEnv + CompilerEnv<Type = ArcType> + MetadataEnv + PrimitiveEnv + Trace
 {
     fn get_global(&self, name: &str) -> Option<RootedGlobal>;
+
+    /// Returns the text of `line` in the source of the module `source_name`
+    fn source_line(&self, _source_name: &str, _line: Line) -> Option<String> {
+        None
+    }
+
+    /// Returns the names of all globals which have been loaded
+    fn global_names(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 pub struct VmEnvInstance<'a> {
     // FIXME Use the database stored here for lookups
     vm_envs: Vec<Box<dyn VmEnv>>,
-    globals: parking_lot::RwLockReadGuard<'a, Globals>,
+    globals: Arc<Globals>,
     thread: &'a Thread,
 }
 
@@ -391,6 +444,23 @@ impl<'a> VmEnv for VmEnvInstance<'a> {
 }
 
 impl<'a> VmEnvInstance<'a> {
+    pub fn source_line(&self, source_name: &str, line: Line) -> Option<String> {
+        self.vm_envs
+            .iter()
+            .find_map(|env| env.source_line(source_name, line))
+    }
+
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .vm_envs
+            .iter()
+            .flat_map(|env| env.global_names())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
     pub fn find_type_info(&self, name: &str) -> Result<Alias<Symbol, ArcType>> {
         let name = Name::new(name);
 
@@ -524,9 +594,10 @@ impl GlobalVmStateBuilder {
     pub fn build(self) -> GlobalVmState {
         let mut vm = GlobalVmState {
             env: Default::default(),
-            generics: RwLock::new(FnvMap::default()),
-            typeids: RwLock::new(FnvMap::default()),
+            generics: Default::default(),
+            typeids: Default::default(),
             interner: RwLock::new(Interner::new()),
+            short_strings: OnceLock::new(),
             gc: Mutex::new(Gc::new(Generation::default(), usize::MAX)),
             macros: MacroEnv::new(),
             type_cache: TypeCache::default(),
@@ -534,6 +605,11 @@ impl GlobalVmStateBuilder {
             debug_level: RwLock::new(DebugLevel::default()),
             thread_reference_count: Default::default(),
             spawner: self.spawner,
+            deadlock_detector: DeadlockDetector::default(),
+            swapped_closures: Default::default(),
+            has_swapped_closures: AtomicBool::new(false),
+            core_dump_handler: RwLock::new(None),
+            stdout_handler: RwLock::new(None),
         };
         vm.add_types().unwrap();
         vm
@@ -557,11 +633,10 @@ impl GlobalVmState {
             add_type(self_, b.to_str(), typ, id)
         }
         fn add_type(self_: &mut GlobalVmState, name: &str, typ: ArcType, id: TypeId) {
-            let ids = self_.typeids.get_mut().unwrap();
+            self_.typeids.get_mut().insert_mut(id, typ);
             let env = self_.env.get_mut();
-            ids.insert(id, typ);
             // Insert aliases so that `find_info` can retrieve information about the primitives
-            env.type_infos.id_to_type.insert(
+            env.type_infos.id_to_type.insert_mut(
                 name.into(),
                 Alias::from(AliasData::new(
                     Symbol::from(name),
@@ -610,17 +685,22 @@ impl GlobalVmState {
 
     pub fn get_type<T: ?Sized + Any>(&self) -> Option<ArcType> {
         let id = TypeId::of::<T>();
-        self.typeids.read().unwrap().get(&id).cloned()
+        self.typeids.read(|typeids| typeids.get(&id).cloned())
     }
 
     pub fn get_generic(&self, name: &str) -> ArcType {
-        let mut generics = self.generics.write().unwrap();
-        if let Some(g) = generics.get(name) {
-            return g.clone();
+        if let Some(g) = self.generics.read(|generics| generics.get(name).cloned()) {
+            return g;
         }
-        let g: ArcType = Type::generic(Generic::new(Symbol::from(name), Kind::typ()));
-        generics.insert(name.into(), g.clone());
-        g
+        self.generics.update(|generics| {
+            // Another thread may have inserted the generic after it was read above
+            if let Some(g) = generics.get(name) {
+                return g.clone();
+            }
+            let g: ArcType = Type::generic(Generic::new(Symbol::from(name), Kind::typ()));
+            generics.insert_mut(name.into(), g.clone());
+            g
+        })
     }
 
     /// Registers a new type called `name`
@@ -648,37 +728,35 @@ impl GlobalVmState {
         alias: Alias<Symbol, ArcType>,
         id: TypeId,
     ) -> Result<ArcType> {
-        let mut env = self.env.write();
-        let type_infos = &mut env.type_infos;
-        self.typeids
-            .write()
-            .unwrap()
-            .insert(id, alias.clone().into_type());
         let t = alias.clone().into_type();
-        type_infos
-            .id_to_type
-            .insert(name.definition_name().into(), alias);
+        self.env.update(|env| {
+            self.typeids
+                .update(|typeids| typeids.insert_mut(id, t.clone()));
+            env.type_infos
+                .id_to_type
+                .insert_mut(name.definition_name().into(), alias);
+        });
         Ok(t)
     }
 
     #[doc(hidden)]
     pub fn get_cache_alias(&self, name: &str) -> Option<ArcType> {
-        let env = self.env.read();
-        env
-            .type_infos
-            .id_to_type
-            .get(name)
-            .map(|alias| alias.clone().into_type())
+        self.env.read(|env| {
+            env.type_infos
+                .id_to_type
+                .get(name)
+                .map(|alias| alias.clone().into_type())
+        })
     }
 
     #[doc(hidden)]
     pub fn cache_alias(&self, alias: Alias<Symbol, ArcType>) -> ArcType {
-        let mut env = self.env.write();
-        let type_infos = &mut env.type_infos;
         let t = alias.clone().into_type();
-        type_infos
-            .id_to_type
-            .insert(alias.name.definition_name().into(), alias);
+        self.env.update(|env| {
+            env.type_infos
+                .id_to_type
+                .insert_mut(alias.name.definition_name().into(), alias)
+        });
         t
     }
 
@@ -692,12 +770,49 @@ impl GlobalVmState {
         interner.intern(&mut *gc, s)
     }
 
+    /// Interns the empty string and every single character ASCII string so that creating them
+    /// at runtime never allocates
+    pub(crate) fn intern_short_strings(&self) -> Result<()> {
+        let mut gc = self.gc.lock().unwrap();
+        let mut interner = self.interner.write().unwrap();
+        let mut short_strings = Vec::with_capacity(0x81);
+        short_strings.push(interner.intern(&mut *gc, "")?);
+        for c in 0..0x80u8 {
+            short_strings.push(interner.intern(&mut *gc, (c as char).encode_utf8(&mut [0; 4]))?);
+        }
+        // Only the root thread interns the strings so they can't have been set already
+        let _ = self.short_strings.set(short_strings);
+        Ok(())
+    }
+
+    /// Returns the interned string equal to `s` if `s` is at most `SHORT_STRING_LEN` bytes and has
+    /// been interned, such as the name of a field or a constructor.
+    ///
+    /// The empty string and single ASCII characters are found without locking. Other strings are
+    /// looked up in the interner, unless it is being written to in which case `None` is returned
+    /// instead of waiting for the lock.
+    pub fn short_string(&self, s: &str) -> Option<InternedStr> {
+        let index = match *s.as_bytes() {
+            [] => 0,
+            [c] if c.is_ascii() => 1 + usize::from(c),
+            _ if s.len() <= SHORT_STRING_LEN => {
+                return self.interner.try_read().ok()?.get(s);
+            }
+            _ => return None,
+        };
+        // SAFETY The strings are kept alive by the interner which is scanned by the gc
+        self.short_strings
+            .get()?
+            .get(index)
+            .map(|s| unsafe { s.clone_unrooted() })
+    }
+
     /// Returns a borrowed structure which implements `CompilerEnv`
     pub fn get_env<'t>(&'t self, thread: &'t Thread) -> VmEnvInstance<'t> {
         let capabilities = self.macros.get_capabilities::<Box<dyn VmEnv>>(thread);
         VmEnvInstance {
             vm_envs: capabilities,
-            globals: self.env.read_recursive(),
+            globals: self.env.load(),
             thread,
         }
     }
@@ -713,14 +828,16 @@ impl GlobalVmState {
     pub fn get_lookup_env<'t>(&'t self, thread: &'t Thread) -> VmEnvInstance<'t> {
         VmEnvInstance {
             vm_envs: Vec::new(),
-            globals: self.env.read_recursive(),
+            globals: self.env.load(),
             thread,
         }
     }
 
+    /// Returns the current global type information. Types registered afterwards are not visible
+    /// in the returned value.
     #[doc(hidden)]
-    pub fn get_globals(&self) -> parking_lot::RwLockReadGuard<Globals> {
-        self.env.read_recursive()
+    pub fn get_globals(&self) -> Arc<Globals> {
+        self.env.load()
     }
 
     pub fn get_debug_level(&self) -> DebugLevel {
@@ -731,7 +848,112 @@ impl GlobalVmState {
         *self.debug_level.write().unwrap() = debug_level;
     }
 
+    /// Sets the handler which is called with a `CoreDump` whenever a panic unwinds out of gluon
+    /// code. Dumps are only created while the debug level is `DebugLevel::High`.
+    pub fn set_core_dump_handler(&self, handler: Option<CoreDumpHandler>) {
+        *self.core_dump_handler.write().unwrap() = handler;
+    }
+
+    /// Returns true if panics should produce a `CoreDump`
+    pub(crate) fn wants_core_dump(&self) -> bool {
+        match *self.debug_level.read().unwrap() {
+            DebugLevel::High => self.core_dump_handler.read().unwrap().is_some(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn write_core_dump(&self, dump: &CoreDump) {
+        if let Some(handler) = &*self.core_dump_handler.read().unwrap() {
+            handler(dump);
+        }
+    }
+
+    /// Sets the handler which receives everything printed to stdout through `std.io`, instead of
+    /// it being written to the process's stdout
+    pub fn set_stdout_handler(&self, handler: Option<OutputHandler>) {
+        *self.stdout_handler.write().unwrap() = handler;
+    }
+
+    /// Writes `text` to the stdout handler, or to stdout if no handler is set
+    pub fn write_stdout(&self, text: &str) -> io::Result<()> {
+        match &*self.stdout_handler.read().unwrap() {
+            Some(handler) => {
+                handler(text);
+                Ok(())
+            }
+            None => io::stdout().write_all(text.as_bytes()),
+        }
+    }
+
     pub fn spawner(&self) -> Option<&(dyn futures::task::Spawn + Send + Sync)> {
         self.spawner.as_ref().map(|s| &**s)
     }
+
+    /// Returns the closure which replaces `closure` if it has been swapped out
+    pub(crate) fn swapped_closure(
+        &self,
+        closure: &GcPtr<ClosureData>,
+    ) -> Option<GcPtr<ClosureData>> {
+        if !self.has_swapped_closures.load(atomic::Ordering::Acquire) {
+            return None;
+        }
+        let swapped = self.swapped_closures.read().unwrap();
+        // SAFETY The replacement is kept alive by `swapped_closures` which is never shrunk
+        swapped
+            .get(&(&**closure as *const ClosureData as usize))
+            .map(|(_, new)| unsafe { new.clone_unrooted() })
+    }
+
+    /// Makes every call of `old` (and of the closures `old` has replaced) call `new` instead.
+    /// Upvariables of `new` which were also captured by the current version of `old` (same name
+    /// and type) are taken from `old` so that any state it has captured survives the swap.
+    ///
+    /// `old` and `new` must both be allocated in `gc`, which must be the (locked) global gc.
+    pub(crate) fn swap_closure(
+        &self,
+        gc: &mut Gc,
+        old: &GcPtr<ClosureData>,
+        new: &GcPtr<ClosureData>,
+    ) -> Result<()> {
+        let mut swapped = self.swapped_closures.write().unwrap();
+
+        let key = |closure: &GcPtr<ClosureData>| &**closure as *const ClosureData as usize;
+        let current = swapped.get(&key(old)).map_or(old, |(_, current)| current);
+
+        let upvars = new.upvars.iter().enumerate().map(|(i, value)| {
+            new.function
+                .debug_info
+                .upvars
+                .get(i)
+                .and_then(|info| {
+                    current
+                        .function
+                        .debug_info
+                        .upvars
+                        .iter()
+                        .position(|old_info| *old_info == *info)
+                })
+                .map_or(value, |j| &current.upvars[j])
+        });
+        // SAFETY The upvariables are all rooted by `old`, `new` or `swapped_closures` and the
+        // global gc does not collect while it is locked
+        let replacement = unsafe {
+            gc.alloc(ClosureDataDef(&new.function, upvars))?
+                .clone_unrooted()
+        };
+
+        // SAFETY `current` and `replacement` are rooted by being stored in `swapped_closures`
+        unsafe {
+            let current = current.clone_unrooted();
+            for (_, target) in swapped.values_mut() {
+                if target.ptr_eq(&current) {
+                    *target = replacement.clone_unrooted();
+                }
+            }
+            swapped.insert(key(&current), (current, replacement));
+        }
+        self.has_swapped_closures
+            .store(true, atomic::Ordering::Release);
+        Ok(())
+    }
 }